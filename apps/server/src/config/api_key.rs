@@ -0,0 +1,175 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use prisma_client_rust::chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use stump_core::{
+	db::entity::UserNotificationKind,
+	job::{
+		error::JobError, JobExecuteLog, JobExt, JobOutputExt, JobTaskOutput, WorkerCtx,
+		WorkingState, WrappedJob,
+	},
+	prisma::{api_key, api_key_usage, user},
+	Ctx,
+};
+use tokio::time::MissedTickBehavior;
+
+pub const API_KEY_MAINTENANCE_JOB_NAME: &str = "api_key_maintenance";
+
+/// How often expiring keys are checked for and old usage records are removed
+const API_KEY_MAINTENANCE_INTERVAL: tokio::time::Duration =
+	tokio::time::Duration::from_secs(60 * 60);
+/// How many days of usage records are kept
+const API_KEY_USAGE_RETENTION_DAYS: i64 = 30;
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct APIKeyMaintenanceJobOutput {
+	/// The number of keys whose owners were notified of their upcoming expiry
+	notified_keys: u64,
+	/// The number of removed usage records
+	removed_usages: u64,
+}
+
+impl JobOutputExt for APIKeyMaintenanceJobOutput {}
+
+/// A job which notifies users of API keys which are about to expire, and removes usage
+/// records which are past retention
+#[derive(Clone)]
+pub struct APIKeyMaintenanceJob;
+
+impl APIKeyMaintenanceJob {
+	pub fn new() -> Box<WrappedJob<APIKeyMaintenanceJob>> {
+		WrappedJob::new(Self)
+	}
+}
+
+#[async_trait::async_trait]
+impl JobExt for APIKeyMaintenanceJob {
+	const NAME: &'static str = API_KEY_MAINTENANCE_JOB_NAME;
+
+	type Output = APIKeyMaintenanceJobOutput;
+	type Task = ();
+
+	fn description(&self) -> Option<String> {
+		None
+	}
+
+	async fn init(
+		&mut self,
+		ctx: &WorkerCtx,
+	) -> Result<WorkingState<Self::Output, Self::Task>, JobError> {
+		let mut output = Self::Output::default();
+		let mut logs = vec![];
+		let now = Utc::now();
+
+		let notice_days = ctx.config.api_key_expiry_notice_days;
+		if notice_days > 0 {
+			let expiring_keys = ctx
+				.db
+				.api_key()
+				.find_many(vec![
+					api_key::expires_at::gt(now.into()),
+					api_key::expires_at::lte((now + Duration::days(notice_days)).into()),
+					api_key::expiry_notified_at::equals(None),
+				])
+				.exec()
+				.await
+				.unwrap_or_else(|e| {
+					logs.push(JobExecuteLog::error(format!(
+						"Failed to find expiring API keys: {:?}",
+						e.to_string()
+					)));
+					vec![]
+				});
+
+			for key in expiring_keys {
+				let Some(expires_at) = key.expires_at else {
+					continue;
+				};
+				let message = format!(
+					"Your API key \"{}\" expires on {}",
+					key.name,
+					expires_at.format("%Y-%m-%d %H:%M UTC")
+				);
+				// The notice and the mark are written together, so a key is only marked once its
+				// owner was actually notified and a failure is retried on the next run
+				let key_id = key.id;
+				let user_id = key.user_id;
+				let notify_result = ctx
+					.db
+					._batch((
+						ctx.db.user_notification().create(
+							UserNotificationKind::ApiKeyExpiring.to_string(),
+							message,
+							user::id::equals(user_id),
+							vec![],
+						),
+						ctx.db.api_key().update(
+							api_key::id::equals(key_id),
+							vec![api_key::expiry_notified_at::set(Some(now.into()))],
+						),
+					))
+					.await;
+				if let Err(e) = notify_result {
+					logs.push(JobExecuteLog::error(format!(
+						"Failed to notify the owner of API key {}: {:?}",
+						key_id,
+						e.to_string()
+					)));
+					continue;
+				}
+
+				output.notified_keys += 1;
+			}
+		}
+
+		let retention_cutoff = now - Duration::days(API_KEY_USAGE_RETENTION_DAYS);
+		output.removed_usages = ctx
+			.db
+			.api_key_usage()
+			.delete_many(vec![api_key_usage::day::lt(
+				retention_cutoff.date_naive().to_string(),
+			)])
+			.exec()
+			.await
+			.map_or_else(
+				|e| {
+					logs.push(JobExecuteLog::error(format!(
+						"Failed to delete old API key usage records: {:?}",
+						e.to_string()
+					)));
+					0
+				},
+				|count| count as u64,
+			);
+		tracing::debug!(?output, "Finished API key maintenance");
+
+		Ok(WorkingState {
+			output: Some(output),
+			tasks: VecDeque::default(),
+			completed_tasks: 0,
+			logs,
+		})
+	}
+
+	async fn execute_task(
+		&self,
+		_: &WorkerCtx,
+		_: Self::Task,
+	) -> Result<JobTaskOutput<Self>, JobError> {
+		unreachable!("APIKeyMaintenanceJob does not have any tasks! It should not be executed with any tasks!")
+	}
+}
+
+/// Periodically dispatch the [APIKeyMaintenanceJob]
+pub async fn continuously_maintain_api_keys(ctx: Arc<Ctx>) {
+	let mut interval = tokio::time::interval(API_KEY_MAINTENANCE_INTERVAL);
+	interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	loop {
+		interval.tick().await; // The first tick completes immediately
+		if let Err(error) = ctx.enqueue_job(APIKeyMaintenanceJob::new()) {
+			tracing::error!(error = ?error, "Failed to dispatch API key maintenance job");
+		} else {
+			tracing::trace!("Dispatched API key maintenance job");
+		}
+	}
+}
@@ -1,4 +1,8 @@
+pub mod api_key;
 pub mod cors;
 pub mod jwt;
+pub mod oidc;
+pub mod proxy_auth;
 pub mod session;
 pub mod state;
+pub mod two_factor;
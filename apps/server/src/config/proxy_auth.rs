@@ -0,0 +1,95 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use serde_json::Value;
+use stump_core::db::entity::UserPermission;
+
+/// Whether a peer is allowed to set the proxy authentication header. Each trusted proxy may
+/// either be a single address or a CIDR range, and malformed entries are ignored.
+pub(crate) fn is_trusted_proxy(trusted_proxies: &[String], peer: IpAddr) -> bool {
+	// An IPv4 peer connecting to a dual-stack socket shows up as an IPv4-mapped IPv6 address
+	let peer = match peer {
+		IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(peer, IpAddr::V4),
+		IpAddr::V4(_) => peer,
+	};
+
+	trusted_proxies.iter().any(|entry| {
+		let entry = entry.trim();
+		if let Ok(network) = entry.parse::<IpNet>() {
+			network.contains(&peer)
+		} else if let Ok(address) = entry.parse::<IpAddr>() {
+			address == peer
+		} else {
+			tracing::warn!(entry, "Ignoring malformed trusted proxy");
+			false
+		}
+	})
+}
+
+/// Parse the permissions given to users created through proxy authentication, ignoring any
+/// which are unknown
+pub(crate) fn parse_default_permissions(permissions: &[String]) -> Vec<UserPermission> {
+	permissions
+		.iter()
+		.filter_map(|permission| {
+			serde_json::from_value::<UserPermission>(Value::String(
+				permission.trim().to_string(),
+			))
+			.inspect_err(|_| {
+				tracing::warn!(permission, "Ignoring unknown proxy auth permission");
+			})
+			.ok()
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn trusted(entries: &[&str]) -> Vec<String> {
+		entries.iter().map(|entry| entry.to_string()).collect()
+	}
+
+	#[test]
+	fn test_trusted_proxy_ranges() {
+		let proxies = trusted(&["172.16.0.0/12", "10.0.0.5", "fd00::/8"]);
+
+		assert!(is_trusted_proxy(&proxies, "172.18.0.2".parse().unwrap()));
+		assert!(is_trusted_proxy(&proxies, "10.0.0.5".parse().unwrap()));
+		assert!(is_trusted_proxy(&proxies, "fd12::1".parse().unwrap()));
+		assert!(!is_trusted_proxy(&proxies, "10.0.0.6".parse().unwrap()));
+		assert!(!is_trusted_proxy(&proxies, "192.168.1.10".parse().unwrap()));
+	}
+
+	#[test]
+	fn test_trusted_proxy_ipv4_mapped() {
+		let proxies = trusted(&["127.0.0.1"]);
+		assert!(is_trusted_proxy(
+			&proxies,
+			"::ffff:127.0.0.1".parse().unwrap()
+		));
+	}
+
+	#[test]
+	fn test_nothing_trusted_by_default() {
+		assert!(!is_trusted_proxy(&[], "127.0.0.1".parse().unwrap()));
+		assert!(!is_trusted_proxy(
+			&trusted(&["not-an-ip"]),
+			"127.0.0.1".parse().unwrap()
+		));
+	}
+
+	#[test]
+	fn test_parse_default_permissions() {
+		let permissions = parse_default_permissions(&trusted(&[
+			"bookclub:read",
+			" file:upload",
+			"nope",
+		]));
+		assert_eq!(
+			permissions,
+			vec![UserPermission::AccessBookClub, UserPermission::UploadFile]
+		);
+	}
+}
@@ -4,4 +4,7 @@ mod utils;
 
 pub use cleanup::SessionCleanupJob;
 pub use store::PrismaSessionStore;
-pub use utils::{delete_cookie_header, get_session_layer, SESSION_USER_KEY};
+pub use utils::{
+	delete_cookie_header, get_session_layer, is_session_recently_authenticated,
+	mark_session_authenticated, SESSION_PATH, SESSION_USER_KEY,
+};
@@ -1,12 +1,21 @@
+use chrono::Utc;
 use std::sync::Arc;
 use stump_core::Ctx;
 use time::Duration;
 
-use tower_sessions::{cookie::SameSite, Expiry, SessionManagerLayer};
+use tower_sessions::{cookie::SameSite, Expiry, Session, SessionManagerLayer};
+
+use crate::errors::APIResult;
 
 use super::PrismaSessionStore;
 
 pub const SESSION_USER_KEY: &str = "user";
+/// The key of the unix timestamp at which the user of a session last proved who they are,
+/// e.g. by logging in
+pub const SESSION_AUTHENTICATED_AT_KEY: &str = "authenticated_at";
+/// How long after logging in a session may perform sensitive actions without re-entering a
+/// password
+const SESSION_REAUTHENTICATION_WINDOW_SECS: i64 = 60 * 10;
 pub const SESSION_NAME: &str = "stump_session";
 pub const SESSION_PATH: &str = "/";
 
@@ -39,6 +48,29 @@ pub fn get_session_layer(ctx: Arc<Ctx>) -> SessionManagerLayer<PrismaSessionStor
 		.with_secure(false)
 }
 
+/// Record that the user of a session just proved who they are
+pub async fn mark_session_authenticated(session: &Session) -> APIResult<()> {
+	session
+		.insert(SESSION_AUTHENTICATED_AT_KEY, Utc::now().timestamp())
+		.await?;
+	Ok(())
+}
+
+/// Whether the user of a session proved who they are within the re-authentication window
+pub async fn is_session_recently_authenticated(session: &Session) -> bool {
+	match session.get::<i64>(SESSION_AUTHENTICATED_AT_KEY).await {
+		Ok(Some(authenticated_at)) => {
+			Utc::now().timestamp() - authenticated_at
+				<= SESSION_REAUTHENTICATION_WINDOW_SECS
+		},
+		Ok(None) => false,
+		Err(error) => {
+			tracing::error!(?error, "Failed to read session authentication time");
+			false
+		},
+	}
+}
+
 /// Returns a tuple with the Set-Cookie header name and value to delete the session cookie.
 /// To do this, we'll just set the cookie on the same name, path and domain, but with an
 /// Expires value in the past. This *should* hopefully trigger the client to delete the cookie.
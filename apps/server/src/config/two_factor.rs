@@ -0,0 +1,302 @@
+use std::{
+	collections::HashMap,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use rand::{
+	distributions::{Alphanumeric, DistString},
+	RngCore,
+};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// The issuer shown in authenticator apps
+const TOTP_ISSUER: &str = "Stump";
+/// The number of seconds each code is valid for
+const TOTP_PERIOD: i64 = 30;
+const TOTP_DIGITS: u32 = 6;
+/// The number of steps before and after the current one which are still accepted, to
+/// tolerate clock drift between the server and the authenticator
+const TOTP_SKEW_STEPS: i64 = 1;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// How long a user has to provide a code after their password was accepted
+const PENDING_LOGIN_TTL: Duration = Duration::from_secs(60 * 5);
+/// How many codes may be attempted for a single password login before it is discarded. This
+/// only bounds a single token; failed codes are also recorded as failed logins, so the account
+/// is locked once too many fail in a row across all of the user's pending logins.
+const PENDING_LOGIN_MAX_ATTEMPTS: u8 = 5;
+
+/// Logins which have passed the password check and are waiting on a second factor, keyed by
+/// the token handed to the client
+static PENDING_TWO_FACTOR_LOGINS: Lazy<Mutex<HashMap<String, PendingTwoFactorLogin>>> =
+	Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Generate a new random TOTP secret, encoded as base32
+pub(crate) fn generate_totp_secret() -> String {
+	let mut secret = [0u8; 20];
+	rand::thread_rng().fill_bytes(&mut secret);
+	BASE32_NOPAD.encode(&secret)
+}
+
+/// Build the `otpauth://` URI which authenticator apps read from a QR code
+pub(crate) fn totp_provisioning_uri(secret: &str, username: &str) -> String {
+	format!(
+		"otpauth://totp/{issuer}:{username}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_PERIOD}",
+		issuer = urlencoding::encode(TOTP_ISSUER),
+		username = urlencoding::encode(username),
+	)
+}
+
+fn totp_code(secret: &[u8], step: i64) -> u32 {
+	let mut mac =
+		Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+	mac.update(&step.to_be_bytes());
+	let digest = mac.finalize().into_bytes();
+
+	// See https://datatracker.ietf.org/doc/html/rfc4226#section-5.3
+	let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+	let binary = u32::from_be_bytes([
+		digest[offset] & 0x7f,
+		digest[offset + 1],
+		digest[offset + 2],
+		digest[offset + 3],
+	]);
+
+	binary % 10u32.pow(TOTP_DIGITS)
+}
+
+/// Verify a TOTP code against a base32 secret at the given unix time. Returns the time step
+/// the code matched, which should be persisted so the same code can't be used again. Any
+/// step at or before `last_used_step` is rejected.
+pub(crate) fn verify_totp(
+	secret: &str,
+	code: &str,
+	unix_time: i64,
+	last_used_step: Option<i64>,
+) -> Option<i64> {
+	let code = code.trim();
+	if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+		return None;
+	}
+	let code = code.parse::<u32>().ok()?;
+	let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+	let current_step = unix_time / TOTP_PERIOD;
+	((current_step - TOTP_SKEW_STEPS)..=(current_step + TOTP_SKEW_STEPS))
+		.filter(|step| last_used_step.map_or(true, |last| *step > last))
+		.find(|step| totp_code(&secret, *step) == code)
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+	code.chars()
+		.filter(char::is_ascii_alphanumeric)
+		.map(|c| c.to_ascii_lowercase())
+		.collect()
+}
+
+/// Hash a recovery code for storage. Recovery codes are random and high entropy, so a fast
+/// hash is sufficient
+pub(crate) fn hash_recovery_code(code: &str) -> String {
+	Sha256::digest(normalize_recovery_code(code).as_bytes())
+		.iter()
+		.map(|byte| format!("{byte:02x}"))
+		.collect()
+}
+
+/// Generate a new set of one-time recovery codes, returning the codes to show the user and
+/// the comma separated hashes to persist
+pub(crate) fn generate_recovery_codes() -> (Vec<String>, String) {
+	let codes = (0..RECOVERY_CODE_COUNT)
+		.map(|_| {
+			let raw = Alphanumeric
+				.sample_string(&mut rand::thread_rng(), 10)
+				.to_ascii_lowercase();
+			format!("{}-{}", &raw[..5], &raw[5..])
+		})
+		.collect::<Vec<_>>();
+	let hashes = codes
+		.iter()
+		.map(|code| hash_recovery_code(code))
+		.collect::<Vec<_>>()
+		.join(",");
+	(codes, hashes)
+}
+
+/// Attempt to consume a recovery code. Returns the remaining hashes to persist if the code
+/// matched one of the stored hashes
+pub(crate) fn consume_recovery_code(stored_hashes: &str, code: &str) -> Option<String> {
+	let hash = hash_recovery_code(code);
+	let hashes = stored_hashes
+		.split(',')
+		.filter(|h| !h.is_empty())
+		.collect::<Vec<_>>();
+	hashes.contains(&hash.as_str()).then(|| {
+		hashes
+			.into_iter()
+			.filter(|h| *h != hash)
+			.collect::<Vec<_>>()
+			.join(",")
+	})
+}
+
+/// The number of unused recovery codes in a stored, comma separated list of hashes
+pub(crate) fn count_recovery_codes(stored_hashes: Option<&str>) -> usize {
+	stored_hashes.map_or(0, |hashes| {
+		hashes.split(',').filter(|h| !h.is_empty()).count()
+	})
+}
+
+/// A login which passed the password check and is waiting on a second factor. The options
+/// of the original login are kept so the second step can issue the same kind of credentials.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingTwoFactorLogin {
+	pub user_id: String,
+	pub generate_token: bool,
+	pub create_session: bool,
+	attempts: u8,
+	created_at: Instant,
+}
+
+impl PendingTwoFactorLogin {
+	pub fn new(user_id: String, generate_token: bool, create_session: bool) -> Self {
+		Self {
+			user_id,
+			generate_token,
+			create_session,
+			attempts: 0,
+			created_at: Instant::now(),
+		}
+	}
+
+	fn is_expired(&self) -> bool {
+		self.created_at.elapsed() > PENDING_LOGIN_TTL
+	}
+
+	/// Store the pending login, returning the token the client must send with its code
+	pub fn store(self) -> String {
+		let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 48);
+		let mut logins = PENDING_TWO_FACTOR_LOGINS
+			.lock()
+			.unwrap_or_else(|error| error.into_inner());
+		logins.retain(|_, login| !login.is_expired());
+		logins.insert(token.clone(), self);
+		token
+	}
+
+	/// Get the pending login for a token, counting it as an attempt. Logins are discarded
+	/// once they expire or run out of attempts.
+	pub fn attempt(token: &str) -> Option<Self> {
+		let mut logins = PENDING_TWO_FACTOR_LOGINS
+			.lock()
+			.unwrap_or_else(|error| error.into_inner());
+		let login = logins.get_mut(token)?;
+		login.attempts += 1;
+		if login.is_expired() || login.attempts > PENDING_LOGIN_MAX_ATTEMPTS {
+			logins.remove(token);
+			return None;
+		}
+		Some(login.clone())
+	}
+
+	/// Discard a pending login once it has been completed
+	pub fn complete(token: &str) {
+		PENDING_TWO_FACTOR_LOGINS
+			.lock()
+			.unwrap_or_else(|error| error.into_inner())
+			.remove(token);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The ASCII secret used by the RFC 6238 test vectors, encoded as base32
+	fn rfc_secret() -> String {
+		BASE32_NOPAD.encode(b"12345678901234567890")
+	}
+
+	#[test]
+	fn test_totp_rfc_vectors() {
+		// See https://datatracker.ietf.org/doc/html/rfc6238#appendix-B, truncated to 6 digits
+		let secret = rfc_secret();
+		for (time, code) in [
+			(59, "287082"),
+			(1111111109, "081804"),
+			(1234567890, "005924"),
+			(2000000000, "279037"),
+		] {
+			assert_eq!(
+				verify_totp(&secret, code, time, None),
+				Some(time / TOTP_PERIOD),
+				"code for {time} should verify"
+			);
+		}
+	}
+
+	#[test]
+	fn test_totp_allows_clock_skew() {
+		let secret = rfc_secret();
+		assert!(verify_totp(&secret, "287082", 59 + TOTP_PERIOD, None).is_some());
+		assert!(verify_totp(&secret, "287082", 59 + TOTP_PERIOD * 2, None).is_none());
+	}
+
+	#[test]
+	fn test_totp_rejects_replay_and_garbage() {
+		let secret = rfc_secret();
+		let step = verify_totp(&secret, "287082", 59, None).unwrap();
+		assert!(verify_totp(&secret, "287082", 59, Some(step)).is_none());
+		assert!(verify_totp(&secret, "28708", 59, None).is_none());
+		assert!(verify_totp(&secret, "28708a", 59, None).is_none());
+		assert!(verify_totp("not base32!", "287082", 59, None).is_none());
+	}
+
+	#[test]
+	fn test_provisioning_uri() {
+		let uri = totp_provisioning_uri("ABC", "oromei smith");
+		assert_eq!(
+			uri,
+			"otpauth://totp/Stump:oromei%20smith?secret=ABC&issuer=Stump&algorithm=SHA1&digits=6&period=30"
+		);
+	}
+
+	#[test]
+	fn test_generated_secret_is_usable() {
+		let secret = generate_totp_secret();
+		assert_eq!(BASE32_NOPAD.decode(secret.as_bytes()).unwrap().len(), 20);
+	}
+
+	#[test]
+	fn test_recovery_codes_are_single_use() {
+		let (codes, hashes) = generate_recovery_codes();
+		assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+		assert_eq!(count_recovery_codes(Some(&hashes)), RECOVERY_CODE_COUNT);
+
+		// Codes are accepted regardless of case or separators
+		let remaining =
+			consume_recovery_code(&hashes, &codes[0].to_uppercase().replace('-', ""))
+				.expect("code should be accepted");
+		assert_eq!(
+			count_recovery_codes(Some(&remaining)),
+			RECOVERY_CODE_COUNT - 1
+		);
+		assert!(consume_recovery_code(&remaining, &codes[0]).is_none());
+		assert!(consume_recovery_code(&remaining, &codes[1]).is_some());
+	}
+
+	#[test]
+	fn test_pending_login_attempts_are_limited() {
+		let token = PendingTwoFactorLogin::new("user".to_string(), false, true).store();
+		for _ in 0..PENDING_LOGIN_MAX_ATTEMPTS {
+			assert!(PendingTwoFactorLogin::attempt(&token).is_some());
+		}
+		assert!(PendingTwoFactorLogin::attempt(&token).is_none());
+		assert!(PendingTwoFactorLogin::attempt(&token).is_none());
+	}
+}
@@ -213,6 +213,8 @@ impl From<TryFromIntError> for APIError {
 impl From<CoreError> for APIError {
 	fn from(err: CoreError) -> Self {
 		match err {
+			CoreError::BadRequest(err) => APIError::BadRequest(err),
+			CoreError::NotFound(err) => APIError::NotFound(err),
 			CoreError::InternalError(err) => APIError::InternalServerError(err),
 			CoreError::IoError(err) => APIError::InternalServerError(err.to_string()),
 			CoreError::MigrationError(err) => APIError::InternalServerError(err),
@@ -361,4 +363,6 @@ pub mod api_error_message {
 		"Your account is locked. Please contact an administrator to unlock your account.";
 	pub const FORBIDDEN_ACTION: &str =
 		"You do not have permission to perform this action.";
+	pub const TWO_FACTOR_REQUIRED: &str =
+		"Two-factor authentication must be enabled for your account before continuing.";
 }
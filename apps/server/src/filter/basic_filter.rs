@@ -300,6 +300,8 @@ pub struct MediaBaseFilter {
 	pub extension: Vec<String>,
 	#[serde(default, deserialize_with = "string_or_seq_string")]
 	pub path: Vec<String>,
+	#[serde(default, deserialize_with = "string_or_seq_string")]
+	pub status: Vec<String>,
 	#[serde(default, deserialize_with = "read_status_or_seq_read_status")]
 	pub read_status: Vec<ReadStatus>,
 	#[serde(default, deserialize_with = "string_or_seq_string")]
@@ -3,6 +3,7 @@ use std::net::SocketAddr;
 use axum::{extract::connect_info::Connected, serve::IncomingStream, Router};
 use stump_core::{
 	config::{bootstrap_config_dir, logging::init_tracing},
+	db::backup::continuously_back_up,
 	job::JobControllerCommand,
 	StumpCore,
 };
@@ -10,7 +11,7 @@ use tokio::{net::TcpListener, sync::oneshot};
 use tower_http::trace::TraceLayer;
 
 use crate::{
-	config::{cors, session::get_session_layer},
+	config::{api_key::continuously_maintain_api_keys, cors, session::get_session_layer},
 	errors::{EntryError, ServerError, ServerResult},
 	routers,
 	utils::shutdown_signal_with_cleanup,
@@ -44,6 +45,10 @@ pub async fn run_http_server(config: StumpConfig) -> ServerResult<()> {
 		.await
 		.map_err(|e| ServerError::ServerStartError(e.to_string()))?;
 
+	core.init_epub_positions()
+		.await
+		.map_err(|e| ServerError::ServerStartError(e.to_string()))?;
+
 	// Initialize the scheduler
 	core.init_scheduler()
 		.await
@@ -55,6 +60,8 @@ pub async fn run_http_server(config: StumpConfig) -> ServerResult<()> {
 
 	let server_ctx = core.get_context();
 	let app_state = server_ctx.arced();
+	tokio::spawn(continuously_maintain_api_keys(app_state.clone()));
+	tokio::spawn(continuously_back_up(app_state.clone()));
 	let cors_layer = cors::get_cors_layer(config.clone());
 
 	println!("{}", core.get_shadow_text());
@@ -1,19 +1,24 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, net::IpAddr};
 
 use axum::{
 	body::Body,
-	extract::{OriginalUri, Path, Request, State},
-	http::{header, StatusCode},
+	extract::{ConnectInfo, OriginalUri, Path, Request, State},
+	http::{header, HeaderMap, Method, StatusCode},
 	middleware::Next,
 	response::{IntoResponse, Redirect, Response},
 	Extension, Json,
 };
 use base64::{engine::general_purpose::STANDARD, Engine};
 use prefixed_api_key::{PrefixedApiKey, PrefixedApiKeyController};
-use prisma_client_rust::or;
+use prisma_client_rust::{or, prisma_errors::query_engine::UniqueKeyViolation};
+use rand::distributions::{Alphanumeric, DistString};
 use serde::Deserialize;
 use stump_core::{
-	db::entity::{APIKeyPermissions, User, UserPermission, API_KEY_PREFIX},
+	config::StumpConfig,
+	db::entity::{
+		APIKey, APIKeyPermissions, APIKeyScopes, PermissionSet, User, UserPermission,
+		API_KEY_PREFIX,
+	},
 	opds::v2_0::{
 		authentication::{
 			OPDSAuthenticationDocumentBuilder, OPDSSupportedAuthFlow,
@@ -21,20 +26,25 @@ use stump_core::{
 		},
 		link::OPDSLink,
 	},
-	prisma::{api_key, session, user, PrismaClient},
+	prisma::{api_key, api_key_usage, session, user, user_preferences, PrismaClient},
 };
 use tower_sessions::Session;
 
 use crate::{
 	config::{
 		jwt::verify_user_jwt,
-		session::{delete_cookie_header, SESSION_USER_KEY},
+		proxy_auth::{is_trusted_proxy, parse_default_permissions},
+		session::{
+			delete_cookie_header, is_session_recently_authenticated,
+			mark_session_authenticated, SESSION_USER_KEY,
+		},
 		state::AppState,
 	},
 	errors::{api_error_message, APIError, APIResult},
-	routers::{enforce_max_sessions, relative_favicon_path},
+	http_server::StumpRequestInfo,
+	routers::{enforce_max_sessions, is_two_factor_required, relative_favicon_path},
 	utils::{
-		current_utc_time, decode_base64_credentials, get_session_user,
+		current_utc_time, decode_base64_credentials, get_session_user, hash_password,
 		user_has_all_permissions, verify_password,
 	},
 };
@@ -48,13 +58,48 @@ pub const STUMP_SAVE_BASIC_SESSION_HEADER: &str = "X-Stump-Save-Session";
 /// - They have a valid session
 /// - They have a valid bearer token (session may not exist)
 /// - They have valid basic auth credentials (session is created after successful authentication)
+/// - They were authenticated by a trusted reverse proxy (session is never created)
 #[derive(Debug, Clone)]
 pub struct RequestContext {
 	user: User,
-	api_key: Option<String>,
+	/// Whether the user proved who they are within the reauthentication window, e.g. by
+	/// logging in recently or by sending credentials with the request itself
+	recently_authenticated: bool,
+	api_key: Option<RequestAPIKey>,
+}
+
+/// The API key a request was authenticated with
+#[derive(Debug, Clone)]
+struct RequestAPIKey {
+	/// The fully qualified API key
+	raw: String,
+	/// The ID of the API key
+	id: i32,
+	/// The restrictions placed on the API key
+	scopes: APIKeyScopes,
+}
+
+impl RequestAPIKey {
+	fn new(raw: String, key: APIKey) -> Self {
+		Self {
+			raw,
+			id: key.id,
+			scopes: key.scopes,
+		}
+	}
 }
 
 impl RequestContext {
+	/// Create a context for a user who logged in with a session, for testing handlers
+	#[cfg(test)]
+	pub(crate) fn for_user(user: User) -> Self {
+		Self {
+			user,
+			recently_authenticated: false,
+			api_key: None,
+		}
+	}
+
 	/// Get a reference to the current user
 	pub fn user(&self) -> &User {
 		&self.user
@@ -65,8 +110,33 @@ impl RequestContext {
 		self.user.id.clone()
 	}
 
+	/// Whether the user proved who they are recently enough to make sensitive changes to
+	/// their account without entering their password again
+	pub fn is_recently_authenticated(&self) -> bool {
+		self.recently_authenticated
+	}
+
 	pub fn api_key(&self) -> Option<String> {
-		self.api_key.clone()
+		self.api_key.as_ref().map(|key| key.raw.clone())
+	}
+
+	/// Enforce that the API key used for the request, if any, is allowed to make it. Requests
+	/// which weren't made with an API key are always allowed.
+	#[tracing::instrument(skip(self))]
+	pub fn enforce_api_key_scopes(&self, method: &Method, path: &str) -> APIResult<()> {
+		let Some(RequestAPIKey { scopes, .. }) = &self.api_key else {
+			return Ok(());
+		};
+
+		if !scopes.allows_path(path) {
+			Err(APIError::Forbidden(
+				"This API key may not be used for this route".to_string(),
+			))
+		} else if scopes.read_only && !method.is_safe() {
+			Err(APIError::Forbidden("This API key is read-only".to_string()))
+		} else {
+			Ok(())
+		}
 	}
 
 	/// Enforce that the current user has all the permissions provided, otherwise return an error
@@ -159,10 +229,7 @@ pub async fn auth_middleware(
 		.and_then(|header| header.to_str().ok())
 		.is_none_or(|header| header == "true");
 
-	let request_uri = req.extensions().get::<OriginalUri>().cloned().map_or_else(
-		|| req.uri().path().to_owned(),
-		|path| path.0.path().to_owned(),
-	);
+	let request_uri = original_path(&req);
 
 	let session_user = get_session_user(&session).await.map_err(|e| {
 		tracing::error!(error = ?e, "Failed to get user from session");
@@ -171,14 +238,40 @@ pub async fn auth_middleware(
 
 	if let Some(user) = session_user {
 		if !user.is_locked {
-			req.extensions_mut().insert(RequestContext {
+			let req_ctx = RequestContext {
 				user,
+				recently_authenticated: is_session_recently_authenticated(&session).await,
 				api_key: None,
-			});
+			};
+			enforce_two_factor_enrollment(
+				&req_ctx,
+				&request_uri,
+				ctx.config.enforce_manager_two_factor,
+			)
+			.map_err(|e| e.into_response())?;
+			req.extensions_mut().insert(req_ctx);
 			return Ok(next.run(req).await);
 		}
 	}
 
+	let peer_addr = req
+		.extensions()
+		.get::<ConnectInfo<StumpRequestInfo>>()
+		.map(|info| info.0.ip_addr);
+	if let Some(req_ctx) = handle_proxy_auth(&req_headers, peer_addr, &ctx)
+		.await
+		.map_err(|e| e.into_response())?
+	{
+		enforce_two_factor_enrollment(
+			&req_ctx,
+			&request_uri,
+			ctx.config.enforce_manager_two_factor,
+		)
+		.map_err(|e| e.into_response())?;
+		req.extensions_mut().insert(req_ctx);
+		return Ok(next.run(req).await);
+	}
+
 	let is_opds = request_uri.starts_with("/opds");
 	let is_swagger = request_uri.starts_with("/swagger-ui");
 
@@ -226,11 +319,44 @@ pub async fn auth_middleware(
 		_ => return Err(APIError::Unauthorized.into_response()),
 	};
 
+	authorize_api_key_request(&req_ctx, &req, &ctx.db)
+		.await
+		.map_err(|e| e.into_response())?;
+	enforce_two_factor_enrollment(
+		&req_ctx,
+		&request_uri,
+		ctx.config.enforce_manager_two_factor,
+	)
+	.map_err(|e| e.into_response())?;
 	req.extensions_mut().insert(req_ctx);
 
 	Ok(next.run(req).await)
 }
 
+/// Reject requests from users who are required to use two-factor authentication but have
+/// not enrolled yet. The auth routes are still allowed, so the user is able to enroll.
+fn enforce_two_factor_enrollment(
+	req_ctx: &RequestContext,
+	request_uri: &str,
+	enforce_for_managers: bool,
+) -> APIResult<()> {
+	let user = req_ctx.user();
+	if user.two_factor_enabled
+		|| !is_two_factor_required(user, enforce_for_managers)
+		|| request_uri.starts_with("/api/v1/auth/")
+	{
+		return Ok(());
+	}
+
+	tracing::debug!(
+		username = &user.username,
+		"User must enroll in two-factor authentication"
+	);
+	Err(APIError::Forbidden(
+		api_error_message::TWO_FACTOR_REQUIRED.to_string(),
+	))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct APIKeyPath(HashMap<String, String>);
 
@@ -265,14 +391,19 @@ pub async fn api_key_middleware(
 		return Err(APIError::Unauthorized.into_response());
 	};
 
-	let user = validate_api_key(pak, &ctx.db)
+	let (user, key) = validate_api_key(pak, &ctx.db)
 		.await
 		.map_err(|e| e.into_response())?;
-
-	req.extensions_mut().insert(RequestContext {
+	let req_ctx = RequestContext {
 		user,
-		api_key: Some(api_key),
-	});
+		recently_authenticated: false,
+		api_key: Some(RequestAPIKey::new(api_key, key)),
+	};
+
+	authorize_api_key_request(&req_ctx, &req, &ctx.db)
+		.await
+		.map_err(|e| e.into_response())?;
+	req.extensions_mut().insert(req_ctx);
 
 	Ok(next.run(req).await)
 }
@@ -304,10 +435,12 @@ pub async fn server_owner_middleware(
 	Ok(next.run(req).await)
 }
 
+/// Validate an API key, returning the user it acts as along with the key itself. The user is
+/// limited to the permissions and libraries of the key.
 pub async fn validate_api_key(
 	pak: PrefixedApiKey,
 	client: &PrismaClient,
-) -> APIResult<User> {
+) -> APIResult<(User, APIKey)> {
 	let controller = PrefixedApiKeyController::configure()
 		.prefix(API_KEY_PREFIX.to_owned())
 		.seam_defaults()
@@ -350,30 +483,97 @@ pub async fn validate_api_key(
 		return Err(APIError::Unauthorized);
 	}
 
+	let key = APIKey::try_from(api_key.clone())?;
+	let constructed_user = match api_key_permissions {
+		APIKeyPermissions::Inherit(_) => User::from(key_user.clone()),
+		// Note: we don't construct permission sets for inferred permissions. What you
+		// give to your API key is what it gets.
+		APIKeyPermissions::Custom(permissions) => User {
+			permissions,
+			..User::from(key_user.clone())
+		},
+	};
+	let constructed_user = User {
+		library_allowlist: key.scopes.library_ids.clone(),
+		..constructed_user
+	};
+
+	Ok((constructed_user, key))
+}
+
+/// Enforce the scopes of the API key used for a request, if any, and record its use
+async fn authorize_api_key_request(
+	req_ctx: &RequestContext,
+	req: &Request,
+	client: &PrismaClient,
+) -> APIResult<()> {
+	let Some(api_key) = &req_ctx.api_key else {
+		return Ok(());
+	};
+
+	let path = original_path(req);
+	req_ctx.enforce_api_key_scopes(req.method(), &path)?;
+
+	let ip_address = req
+		.extensions()
+		.get::<ConnectInfo<StumpRequestInfo>>()
+		.map(|info| info.0.ip_addr.to_string());
+	// Keys may be embedded in the path (e.g. for OPDS), which must never be logged
+	let path = path.replace(&api_key.raw, "{api_key}");
+
+	// IMO we shouldn't fail the request if we can't track the usage of the key
+	let now = current_utc_time();
 	let update_result = client
 		.api_key()
 		.update(
 			api_key::id::equals(api_key.id),
-			vec![api_key::last_used_at::set(Some(current_utc_time().into()))],
+			vec![api_key::last_used_at::set(Some(now.into()))],
 		)
 		.exec()
 		.await;
 	if let Err(e) = update_result {
-		// IMO we shouldn't fail the request if we can't update the last used at field
 		tracing::error!(error = ?e, "Failed to update API key");
 	}
 
-	let constructed_user = match api_key_permissions {
-		APIKeyPermissions::Inherit(_) => User::from(key_user.clone()),
-		// Note: we don't construct permission sets for inferred permissions. What you
-		// give to your API key is what it gets.
-		APIKeyPermissions::Custom(permissions) => User {
-			permissions,
-			..User::from(key_user.clone())
-		},
-	};
+	// Usage is aggregated per day, so a busy key doesn't add a record for every request
+	let usage_result = client
+		.api_key_usage()
+		.upsert(
+			api_key_usage::api_key_id_day(api_key.id, now.date_naive().to_string()),
+			api_key_usage::create(
+				now.date_naive().to_string(),
+				req.method().to_string(),
+				path.clone(),
+				api_key::id::equals(api_key.id),
+				vec![
+					api_key_usage::request_count::set(1),
+					api_key_usage::last_used_at::set(now.into()),
+					api_key_usage::last_ip_address::set(ip_address.clone()),
+				],
+			),
+			vec![
+				api_key_usage::request_count::increment(1),
+				api_key_usage::last_used_at::set(now.into()),
+				api_key_usage::last_method::set(req.method().to_string()),
+				api_key_usage::last_path::set(path),
+				api_key_usage::last_ip_address::set(ip_address),
+			],
+		)
+		.exec()
+		.await;
+	if let Err(e) = usage_result {
+		tracing::error!(error = ?e, "Failed to record API key usage");
+	}
+
+	Ok(())
+}
 
-	Ok(constructed_user)
+/// Get the full path of a request, before any nested routers stripped their prefix
+fn original_path(req: &Request) -> String {
+	req.extensions().get::<OriginalUri>().cloned().map_or_else(
+		|| req.uri().path().to_owned(),
+		|path| path.0.path().to_owned(),
+	)
 }
 
 /// A function to handle bearer token authentication. This function will verify the token and
@@ -385,12 +585,13 @@ async fn handle_bearer_auth(
 ) -> APIResult<RequestContext> {
 	match PrefixedApiKey::from_string(token.as_str()) {
 		Ok(api_key) if api_key.prefix() == API_KEY_PREFIX => {
-			return validate_api_key(api_key, client)
-				.await
-				.map(|user| RequestContext {
+			return validate_api_key(api_key, client).await.map(|(user, key)| {
+				RequestContext {
 					user,
-					api_key: Some(token),
-				});
+					recently_authenticated: false,
+					api_key: Some(RequestAPIKey::new(token, key)),
+				}
+			});
 		},
 		_ => (),
 	};
@@ -422,10 +623,162 @@ async fn handle_bearer_auth(
 
 	Ok(RequestContext {
 		user: User::from(user),
+		recently_authenticated: false,
 		api_key: None,
 	})
 }
 
+/// A function to handle authentication by a trusted reverse proxy. If proxy authentication is
+/// enabled and the request came from a trusted proxy, the user is resolved from the username
+/// in the configured header. Returns `None` when the header should not be used, so the request
+/// falls back to the other authentication methods. This keeps clients which talk to the server
+/// directly, e.g. OPDS readers and KOReader, working with their usual credentials.
+#[tracing::instrument(skip_all)]
+async fn handle_proxy_auth(
+	headers: &HeaderMap,
+	peer_addr: Option<IpAddr>,
+	ctx: &AppState,
+) -> APIResult<Option<RequestContext>> {
+	let Some(header_name) = ctx.config.proxy_auth_header.as_deref() else {
+		return Ok(None);
+	};
+	let Some(username) = headers
+		.get(header_name)
+		.and_then(|value| value.to_str().ok())
+		.map(str::trim)
+		.filter(|value| !value.is_empty())
+	else {
+		return Ok(None);
+	};
+
+	let is_trusted = peer_addr.is_some_and(|addr| {
+		is_trusted_proxy(&ctx.config.proxy_auth_trusted_proxies, addr)
+	});
+	if !is_trusted {
+		tracing::warn!(
+			?peer_addr,
+			"Ignoring proxy authentication header from an untrusted peer"
+		);
+		return Ok(None);
+	}
+
+	let fetched_user = find_proxy_user(&ctx.db, username).await?;
+	let user = match fetched_user {
+		Some(user) => user,
+		None if ctx.config.proxy_auth_auto_create => {
+			create_proxy_user(&ctx.db, &ctx.config, username).await?
+		},
+		None => {
+			tracing::error!(username, "No user found for proxy authentication");
+			return Err(APIError::Unauthorized);
+		},
+	};
+
+	if user.is_locked {
+		tracing::error!(
+			username = &user.username,
+			"User is locked, denying authentication"
+		);
+		return Err(APIError::Forbidden(
+			api_error_message::LOCKED_ACCOUNT.to_string(),
+		));
+	}
+
+	Ok(Some(RequestContext {
+		user: User::from(user),
+		// The proxy authenticates every request it forwards
+		recently_authenticated: true,
+		api_key: None,
+	}))
+}
+
+async fn find_proxy_user(
+	client: &PrismaClient,
+	username: &str,
+) -> APIResult<Option<user::Data>> {
+	Ok(client
+		.user()
+		.find_first(vec![
+			user::username::equals(username.to_string()),
+			user::deleted_at::equals(None),
+		])
+		.with(user::user_preferences::fetch())
+		.with(user::age_restriction::fetch())
+		.exec()
+		.await?)
+}
+
+/// Create an account for a username sent by the authentication proxy. The account is given a
+/// random password, so it can only be used through the proxy unless an admin resets it.
+async fn create_proxy_user(
+	client: &PrismaClient,
+	config: &StumpConfig,
+	username: &str,
+) -> APIResult<user::Data> {
+	if client.user().find_first(vec![]).exec().await?.is_none() {
+		return Err(APIError::Forbidden(
+			"The server must be claimed before accounts can be created".to_string(),
+		));
+	}
+
+	let password = Alphanumeric.sample_string(&mut rand::thread_rng(), 64);
+	let hashed_password = hash_password(&password, config)?;
+	let permissions = PermissionSet::new(parse_default_permissions(
+		&config.proxy_auth_default_permissions,
+	))
+	.resolve_into_string();
+
+	let owned_username = username.to_string();
+	let result = client
+		._transaction()
+		.run(|client| async move {
+			let created_user = client
+				.user()
+				.create(
+					owned_username,
+					hashed_password,
+					vec![
+						user::is_server_owner::set(false),
+						user::permissions::set(permissions),
+					],
+				)
+				.exec()
+				.await?;
+
+			client
+				.user_preferences()
+				.create(vec![
+					user_preferences::user::connect(user::id::equals(
+						created_user.id.clone(),
+					)),
+					user_preferences::user_id::set(Some(created_user.id.clone())),
+				])
+				.exec()
+				.await
+				.map(|_| created_user)
+		})
+		.await;
+
+	match result {
+		Ok(created_user) => {
+			tracing::debug!(
+				user_id = created_user.id,
+				"Created user for proxy authentication"
+			);
+		},
+		// Concurrent requests for a new user race to create it, so the loser uses the
+		// winner's account
+		Err(error) if error.is_prisma_error::<UniqueKeyViolation>() => {
+			tracing::trace!(username, "Proxy user was created by another request");
+		},
+		Err(error) => return Err(error.into()),
+	}
+
+	find_proxy_user(client, username)
+		.await?
+		.ok_or(APIError::Unauthorized)
+}
+
 /// A function to handle basic authentication. This function will decode the credentials and
 /// attempt to authenticate the user. If the user is authenticated, a session will be created
 /// for the user.
@@ -474,6 +827,13 @@ async fn handle_basic_auth(
 		));
 	} else if !is_match {
 		return Err(APIError::Unauthorized);
+	} else if user.totp_enabled_at.is_some() {
+		// Basic auth can't carry a second factor, so these users must use an API key instead
+		tracing::error!(
+			username = &user.username,
+			"User has two-factor authentication enabled, denying basic authentication"
+		);
+		return Err(APIError::Unauthorized);
 	}
 
 	tracing::trace!(username = &user.username, "Basic authentication successful");
@@ -484,10 +844,13 @@ async fn handle_basic_auth(
 		session
 			.insert(SESSION_USER_KEY, User::from(user.clone()))
 			.await?;
+		mark_session_authenticated(session).await?;
 	}
 
 	Ok(RequestContext {
 		user: User::from(user),
+		// The password was sent with the request itself
+		recently_authenticated: true,
 		api_key: None,
 	})
 }
@@ -602,7 +965,11 @@ mod tests {
 	use axum_test::{TestServer, TestServerConfig};
 	use header::{HeaderName, HeaderValue};
 	use prisma_client_rust::MockStore;
-	use stump_core::{config::StumpConfig, db::entity::APIKey, Ctx};
+	use stump_core::{
+		config::StumpConfig,
+		db::entity::{APIKey, APIKeyRouteScope},
+		Ctx,
+	};
 	use time::Duration;
 	use tower_sessions::{cookie::SameSite, Expiry, MemoryStore, SessionManagerLayer};
 
@@ -619,6 +986,7 @@ mod tests {
 		let user = User::default();
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(user.is(request_context.user()));
@@ -629,11 +997,47 @@ mod tests {
 		let user = User::default();
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert_eq!(user.id, request_context.id());
 	}
 
+	#[test]
+	fn test_request_context_enforce_api_key_scopes() {
+		let session_ctx = RequestContext {
+			user: User::default(),
+			recently_authenticated: false,
+			api_key: None,
+		};
+		assert!(session_ctx
+			.enforce_api_key_scopes(&Method::POST, "/api/v1/libraries")
+			.is_ok());
+
+		let key_ctx = RequestContext {
+			user: User::default(),
+			recently_authenticated: false,
+			api_key: Some(RequestAPIKey {
+				raw: "stump_key".to_string(),
+				id: 1,
+				scopes: APIKeyScopes {
+					read_only: true,
+					routes: Some(vec![APIKeyRouteScope::Opds]),
+					..Default::default()
+				},
+			}),
+		};
+		assert!(key_ctx
+			.enforce_api_key_scopes(&Method::GET, "/opds/v1.2/catalog")
+			.is_ok());
+		assert!(key_ctx
+			.enforce_api_key_scopes(&Method::GET, "/api/v1/libraries")
+			.is_err());
+		assert!(key_ctx
+			.enforce_api_key_scopes(&Method::PUT, "/opds/v2.0/books/1/progression")
+			.is_err());
+	}
+
 	#[test]
 	fn test_request_context_enforce_permissions_when_server_owner() {
 		let user = User {
@@ -642,6 +1046,7 @@ mod tests {
 		};
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(request_context
@@ -649,6 +1054,53 @@ mod tests {
 			.is_ok());
 	}
 
+	#[test]
+	fn test_enforce_two_factor_enrollment() {
+		let manager = RequestContext {
+			user: User {
+				permissions: vec![UserPermission::ManageServer],
+				..Default::default()
+			},
+			recently_authenticated: false,
+			api_key: None,
+		};
+		let reader = RequestContext {
+			user: User::default(),
+			recently_authenticated: false,
+			api_key: None,
+		};
+
+		assert!(
+			enforce_two_factor_enrollment(&manager, "/api/v1/libraries", false).is_ok()
+		);
+		assert!(
+			enforce_two_factor_enrollment(&reader, "/api/v1/libraries", true).is_ok()
+		);
+		assert!(
+			enforce_two_factor_enrollment(&manager, "/api/v1/libraries", true).is_err()
+		);
+		// The auth routes must stay reachable so the user can enroll
+		assert!(
+			enforce_two_factor_enrollment(&manager, "/api/v1/auth/2fa/setup", true)
+				.is_ok()
+		);
+
+		let enrolled_manager = RequestContext {
+			user: User {
+				two_factor_enabled: true,
+				..manager.user.clone()
+			},
+			recently_authenticated: false,
+			api_key: None,
+		};
+		assert!(enforce_two_factor_enrollment(
+			&enrolled_manager,
+			"/api/v1/libraries",
+			true
+		)
+		.is_ok());
+	}
+
 	#[test]
 	fn test_request_context_enforce_permissions_when_permitted() {
 		let user = User {
@@ -657,6 +1109,7 @@ mod tests {
 		};
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(request_context
@@ -669,6 +1122,7 @@ mod tests {
 		let user = User::default();
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(request_context
@@ -684,6 +1138,7 @@ mod tests {
 		};
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(request_context
@@ -702,6 +1157,7 @@ mod tests {
 		};
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(user.is(&request_context
@@ -714,6 +1170,7 @@ mod tests {
 		let user = User::default();
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(request_context
@@ -729,6 +1186,7 @@ mod tests {
 		};
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(request_context.enforce_server_owner().is_ok());
@@ -739,6 +1197,7 @@ mod tests {
 		let user = User::default();
 		let request_context = RequestContext {
 			user: user.clone(),
+			recently_authenticated: false,
 			api_key: None,
 		};
 		assert!(request_context.enforce_server_owner().is_err());
@@ -786,7 +1245,14 @@ mod tests {
 	}
 
 	fn setup_test_app() -> (Arc<PrismaClient>, MockStore, TestServer) {
-		let (ctx, mock_store) = Ctx::mock();
+		setup_test_app_with_config(StumpConfig::debug())
+	}
+
+	fn setup_test_app_with_config(
+		config: StumpConfig,
+	) -> (Arc<PrismaClient>, MockStore, TestServer) {
+		let (mut ctx, mock_store) = Ctx::mock();
+		ctx.config = Arc::new(config);
 
 		let client = ctx.db.clone();
 
@@ -877,6 +1343,74 @@ mod tests {
 		assert_eq!(response.status_code().as_u16(), 200);
 	}
 
+	fn proxy_auth_config(trusted_proxies: &[&str]) -> StumpConfig {
+		StumpConfig {
+			proxy_auth_header: Some("Remote-User".to_string()),
+			proxy_auth_trusted_proxies: trusted_proxies
+				.iter()
+				.map(|proxy| proxy.to_string())
+				.collect(),
+			..StumpConfig::debug()
+		}
+	}
+
+	fn remote_user_header(username: &str) -> (HeaderName, HeaderValue) {
+		(
+			HeaderName::from_str("Remote-User").expect("Failed to create header"),
+			HeaderValue::from_str(username).expect("Failed to create header"),
+		)
+	}
+
+	#[tokio::test]
+	async fn test_auth_middleware_with_trusted_proxy_header() {
+		let config = proxy_auth_config(&["127.0.0.0/8", "::1"]);
+		let user = User {
+			id: "oromei-id".to_string(),
+			username: "oromei".to_string(),
+			..Default::default()
+		};
+		let hashed_pass =
+			hash_password("password", &config).expect("Failed to hash password");
+
+		let (client, mock_store, server) = setup_test_app_with_config(config);
+
+		mock_store
+			.expect(
+				client
+					.user()
+					.find_first(vec![
+						user::username::equals("oromei".to_string()),
+						user::deleted_at::equals(None),
+					])
+					.with(user::user_preferences::fetch())
+					.with(user::age_restriction::fetch()),
+				Some(create_prisma_user(&user, hashed_pass)),
+			)
+			.await;
+
+		let (header_name, header_value) = remote_user_header("oromei");
+		let response = server
+			.get("/test")
+			.add_header(header_name, header_value)
+			.await;
+
+		assert_eq!(response.status_code().as_u16(), 200);
+	}
+
+	#[tokio::test]
+	async fn test_auth_middleware_ignores_untrusted_proxy_header() {
+		let (_, _, server) =
+			setup_test_app_with_config(proxy_auth_config(&["10.0.0.0/8"]));
+
+		let (header_name, header_value) = remote_user_header("oromei");
+		let response = server
+			.get("/test")
+			.add_header(header_name, header_value)
+			.await;
+
+		assert_eq!(response.status_code().as_u16(), 401);
+	}
+
 	#[tokio::test]
 	async fn test_auth_middleware_with_invalid_jwt() {
 		let (_, _, server) = setup_test_app();
@@ -1049,9 +1583,12 @@ mod tests {
 			user: Some(Box::new(create_prisma_user(for_user, String::default()))),
 			permissions: serde_json::to_vec(&key.permissions)
 				.expect("Failed to serialize"),
+			scopes: Some(serde_json::to_vec(&key.scopes).expect("Failed to serialize")),
 			expires_at: key.expires_at,
 			created_at: key.created_at,
 			last_used_at: key.last_used_at,
+			expiry_notified_at: None,
+			usages: None,
 		}
 	}
 
@@ -1100,6 +1637,46 @@ mod tests {
 			)
 			.await;
 
+		let now = current_utc_time();
+		let day = now.date_naive().to_string();
+		let ip_address = Some("127.0.0.1".to_string());
+		mock_store
+			.expect(
+				client.api_key_usage().upsert(
+					api_key_usage::api_key_id_day(api_key.id, day.clone()),
+					api_key_usage::create(
+						day.clone(),
+						"GET".to_string(),
+						"/test".to_string(),
+						api_key::id::equals(api_key.id),
+						vec![
+							api_key_usage::request_count::set(1),
+							api_key_usage::last_used_at::set(now.into()),
+							api_key_usage::last_ip_address::set(ip_address.clone()),
+						],
+					),
+					vec![
+						api_key_usage::request_count::increment(1),
+						api_key_usage::last_used_at::set(now.into()),
+						api_key_usage::last_method::set("GET".to_string()),
+						api_key_usage::last_path::set("/test".to_string()),
+						api_key_usage::last_ip_address::set(ip_address.clone()),
+					],
+				),
+				api_key_usage::Data {
+					id: 1,
+					day,
+					request_count: 1,
+					last_used_at: now.into(),
+					last_method: "GET".to_string(),
+					last_path: "/test".to_string(),
+					last_ip_address: ip_address,
+					api_key_id: api_key.id,
+					api_key: None,
+				},
+			)
+			.await;
+
 		let response = server
 			.get("/test")
 			.add_header(
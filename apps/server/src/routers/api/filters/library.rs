@@ -1,3 +1,4 @@
+use prisma_client_rust::and;
 use stump_core::{
 	db::entity::User,
 	prisma::{
@@ -41,8 +42,15 @@ pub(crate) fn apply_library_relation_filters(
 	)
 }
 
+/// A filter for the libraries a user may access. This excludes libraries hidden from the
+/// user, and any outside of their library allowlist if they have one.
 pub(crate) fn library_not_hidden_from_user_filter(user: &User) -> WhereParam {
-	library::hidden_from_users::none(vec![user::id::equals(user.id.clone())])
+	let not_hidden =
+		library::hidden_from_users::none(vec![user::id::equals(user.id.clone())]);
+	match &user.library_allowlist {
+		Some(library_ids) => and![not_hidden, library::id::in_vec(library_ids.clone())],
+		None => not_hidden,
+	}
 }
 
 // FIXME: hidden libraries introduced a bug here, need to fix!
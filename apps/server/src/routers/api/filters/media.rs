@@ -73,6 +73,7 @@ pub(crate) fn apply_media_base_filters(filters: MediaBaseFilter) -> Vec<WherePar
 				let decoded_paths = decode_path_filter(filters.path);
 				media::path::in_vec(decoded_paths)
 			}),
+			(!filters.status.is_empty()).then(|| media::status::in_vec(filters.status)),
 			(!filters.tags.is_empty())
 				.then(|| media::tags::some(vec![tag::name::in_vec(filters.tags)])),
 			filters.search.map(|s| {
@@ -104,9 +105,11 @@ pub(crate) fn apply_media_relation_filters(
 	)
 }
 
+/// Builds the conditions for a media filter. Media in the trash is always excluded
 pub(crate) fn apply_media_filters(filters: MediaFilter) -> Vec<WhereParam> {
-	apply_media_base_filters(filters.base_filter)
+	[media::deleted_at::equals(None)]
 		.into_iter()
+		.chain(apply_media_base_filters(filters.base_filter))
 		.chain(apply_media_relation_filters(filters.relation_filter))
 		.collect()
 }
@@ -114,9 +117,12 @@ pub(crate) fn apply_media_filters(filters: MediaFilter) -> Vec<WhereParam> {
 pub(crate) fn apply_media_library_not_hidden_for_user_filter(
 	user: &User,
 ) -> Vec<WhereParam> {
-	vec![media::series::is(vec![series::library::is(vec![
-		library_not_hidden_from_user_filter(user),
-	])])]
+	vec![
+		media::deleted_at::equals(None),
+		media::series::is(vec![series::library::is(vec![
+			library_not_hidden_from_user_filter(user),
+		])]),
+	]
 }
 
 pub(crate) fn apply_media_filters_for_user(
@@ -247,9 +253,63 @@ pub fn apply_media_restrictions_for_user(user: &User) -> Vec<WhereParam> {
 		.map(|ar| apply_media_age_restriction(ar.age, ar.restrict_on_unset));
 
 	chain_optional_iter(
-		[media::series::is(vec![series::library::is(vec![
-			library_not_hidden_from_user_filter(user),
-		])])],
+		[
+			media::deleted_at::equals(None),
+			media::series::is(vec![series::library::is(vec![
+				library_not_hidden_from_user_filter(user),
+			])]),
+		],
 		[age_restrictions],
 	)
 }
+
+#[cfg(test)]
+mod tests {
+	use stump_core::prisma::PrismaClient;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_media_filters_exclude_trash() {
+		let (client, mock) = PrismaClient::_mock();
+
+		mock.expect(
+			client
+				.media()
+				.find_many(vec![media::deleted_at::equals(None)]),
+			vec![],
+		)
+		.await;
+
+		let result = client
+			.media()
+			.find_many(apply_media_filters(MediaFilter::default()))
+			.exec()
+			.await;
+		assert!(result.is_ok());
+	}
+
+	#[tokio::test]
+	async fn test_media_restrictions_exclude_trash() {
+		let (client, mock) = PrismaClient::_mock();
+		let user = User::default();
+
+		mock.expect(
+			client.media().find_many(vec![
+				media::deleted_at::equals(None),
+				media::series::is(vec![series::library::is(vec![
+					library_not_hidden_from_user_filter(&user),
+				])]),
+			]),
+			vec![],
+		)
+		.await;
+
+		let result = client
+			.media()
+			.find_many(apply_media_restrictions_for_user(&user))
+			.exec()
+			.await;
+		assert!(result.is_ok());
+	}
+}
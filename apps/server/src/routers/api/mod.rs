@@ -20,7 +20,10 @@ mod tests {
 		NamedType,
 	};
 
-	use stump_core::{config::StumpConfig, filesystem::scanner::LibraryScanRecord};
+	use stump_core::{
+		config::StumpConfig, db::trash::PurgedTrash,
+		filesystem::scanner::LibraryScanRecord,
+	};
 
 	use crate::{
 		config::jwt::CreatedToken,
@@ -28,16 +31,20 @@ mod tests {
 		routers::api::v1::{
 			api_key::*,
 			auth::*,
+			backup::*,
 			book_club::*,
 			config::*,
 			emailer::*,
 			epub::*,
 			job::*,
 			library::*,
-			media::{individual::*, thumbnails::*},
+			media::{duplicates::*, individual::*, thumbnails::*, verification::*},
 			metadata::*,
+			oidc::*,
 			series::*,
 			smart_list::*,
+			two_factor::*,
+			upload::session::*,
 			user::*,
 			ClaimResponse, StumpVersion, UpdateCheck,
 		},
@@ -83,12 +90,34 @@ mod tests {
 		file.write_all(
 			format!("{}\n\n", ts_export::<LoginOrRegisterArgs>()?).as_bytes(),
 		)?;
+		file.write_all(format!("{}\n\n", ts_export::<LoginTwoFactorArgs>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<TwoFactorStatus>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<SetupTwoFactor>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<TwoFactorSetup>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<VerifyTwoFactorCode>()?).as_bytes(),
+		)?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<TwoFactorRecoveryCodes>()?).as_bytes(),
+		)?;
+		file.write_all(format!("{}\n\n", ts_export::<DisableTwoFactor>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<OidcConfig>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<OidcAuthorizeParams>()?).as_bytes(),
+		)?;
 		file.write_all(format!("{}\n\n", ts_export::<CreateUser>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<UpdateUser>()?).as_bytes())?;
 		file.write_all(
 			format!("{}\n\n", ts_export::<UpdateUserPreferences>()?).as_bytes(),
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<DeleteUser>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<UpdateNewBooksDigestSubscription>()?)
+				.as_bytes(),
+		)?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<ConfirmNewBooksDigest>()?).as_bytes(),
+		)?;
 
 		file.write_all(
 			format!("{}\n\n", ts_export::<CreateOrUpdateAPIKey>()?).as_bytes(),
@@ -114,6 +143,15 @@ mod tests {
 			format!("{}\n\n", ts_export::<CreateOrUpdateEmailDevice>()?).as_bytes(),
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<PatchEmailDevice>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<UpdateEmailTemplate>()?).as_bytes(),
+		)?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<PreviewEmailTemplate>()?).as_bytes(),
+		)?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<EmailTemplatePreview>()?).as_bytes(),
+		)?;
 
 		file.write_all(format!("{}\n\n", ts_export::<LogFilter>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<LibraryBaseFilter>()?).as_bytes())?;
@@ -134,6 +172,9 @@ mod tests {
 		file.write_all(format!("{}\n\n", ts_export::<MediaBaseFilter>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<MediaFilter>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<PutMediaProgress>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<PutMediaProgressHeartbeat>()?).as_bytes(),
+		)?;
 		file.write_all(format!("{}\n\n", ts_export::<BookRelations>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<SeriesBaseFilter>()?).as_bytes())?;
 		file.write_all(
@@ -149,6 +190,13 @@ mod tests {
 			format!("{}\n\n", ts_export::<SeriesQueryRelation>()?).as_bytes(),
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<CreateLibrary>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<PreviewLibrarySeries>()?).as_bytes(),
+		)?;
+		file.write_all(format!("{}\n\n", ts_export::<SeriesPreview>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<LibrarySeriesPreview>()?).as_bytes(),
+		)?;
 		file.write_all(format!("{}\n\n", ts_export::<UpdateLibrary>()?).as_bytes())?;
 		file.write_all(
 			format!("{}\n\n", ts_export::<UpdateLibraryExcludedUsers>()?).as_bytes(),
@@ -156,15 +204,30 @@ mod tests {
 		file.write_all(
 			format!("{}\n\n", ts_export::<CleanLibraryResponse>()?).as_bytes(),
 		)?;
+		file.write_all(format!("{}\n\n", ts_export::<PurgedTrash>()?).as_bytes())?;
 		file.write_all(
 			format!("{}\n\n", ts_export::<GenerateLibraryThumbnails>()?).as_bytes(),
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<LibraryStatsParams>()?).as_bytes())?;
 
+		file.write_all(
+			format!("{}\n\n", ts_export::<CreateUploadSession>()?).as_bytes(),
+		)?;
+		file.write_all(format!("{}\n\n", ts_export::<UploadSession>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<UploadPreview>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<CompletedUpload>()?).as_bytes())?;
+
 		file.write_all(
 			format!("{}\n\n", ts_export::<PutMediaCompletionStatus>()?).as_bytes(),
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<MediaIsComplete>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<DuplicateGroupsParams>()?).as_bytes(),
+		)?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<ResolveDuplicateGroup>()?).as_bytes(),
+		)?;
+		file.write_all(format!("{}\n\n", ts_export::<VerifyMediaParams>()?).as_bytes())?;
 		file.write_all(
 			format!("{}\n\n", ts_export::<MediaMetadataOverview>()?).as_bytes(),
 		)?;
@@ -176,6 +239,8 @@ mod tests {
 		file.write_all(
 			format!("{}\n\n", ts_export::<UpdateSchedulerConfig>()?).as_bytes(),
 		)?;
+		file.write_all(format!("{}\n\n", ts_export::<QueuedJob>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<CreateBackup>()?).as_bytes())?;
 
 		file.write_all(format!("{}\n\n", ts_export::<GetBookClubsParams>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<CreateBookClub>()?).as_bytes())?;
@@ -8,19 +8,25 @@ use axum::{
 };
 use chrono::{DateTime, FixedOffset};
 use prefixed_api_key::PrefixedApiKey;
+use prisma_client_rust::Direction;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use stump_core::{
-	db::entity::{APIKey, APIKeyPermissions, UserPermission},
-	prisma::{api_key, user},
+	db::entity::{APIKey, APIKeyPermissions, APIKeyScopes, APIKeyUsage, UserPermission},
+	prisma::{api_key, api_key_usage, library, user, PrismaClient},
 };
 
 use crate::{
 	config::state::AppState,
 	errors::{APIError, APIResult},
+	filter::chain_optional_iter,
 	middleware::auth::{auth_middleware, validate_api_key, RequestContext},
+	routers::api::filters::library_not_hidden_from_user_filter,
 };
 
+/// The number of days of usage returned for an API key
+const API_KEY_USAGE_LIMIT: i64 = 100;
+
 pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 	Router::new()
 		.nest(
@@ -32,7 +38,8 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 					"/{id}",
 					get(get_api_key).put(update_api_key).delete(delete_api_key),
 				)
-				.route("/{id}/regenerate-secret", post(regenerate_api_key_secret)),
+				.route("/{id}/regenerate-secret", post(regenerate_api_key_secret))
+				.route("/{id}/usage", get(get_api_key_usage)),
 		)
 		.layer(middleware::from_fn(authorize)) // Note the order!
 		.layer(middleware::from_fn_with_state(app_state, auth_middleware))
@@ -108,7 +115,7 @@ async fn validate_api_key_handler(
 		.map_err(|_| APIError::BadRequest("Invalid API key in headers".to_string()))?;
 
 	let is_valid = match validate_api_key(pak, &ctx.db).await {
-		Ok(key_user) => key_user.is(req.user()),
+		Ok((key_user, _)) => key_user.is(req.user()),
 		// We swallow errors to avoid showing our hand a bit, i.e. if the key wasn't found
 		// or is expired etc that is not surfaced. This is a bit of a tradeoff since it might
 		// not be overly useful, but it is a little more secure.
@@ -128,6 +135,43 @@ pub struct CreateOrUpdateAPIKey {
 	/// The expiration date for the API key, if any
 	#[specta(optional)]
 	expires_at: Option<DateTime<FixedOffset>>,
+	/// The restrictions to place on the API key, if any
+	#[serde(default)]
+	#[specta(optional)]
+	scopes: Option<APIKeyScopes>,
+}
+
+/// Serialize the scopes requested for an API key, ensuring any libraries it is limited to
+/// are accessible to the user. Empty scopes are stored as `None`.
+async fn serialize_scopes(
+	client: &PrismaClient,
+	req: &RequestContext,
+	scopes: Option<APIKeyScopes>,
+) -> APIResult<Option<Vec<u8>>> {
+	let Some(scopes) = scopes.filter(|scopes| *scopes != APIKeyScopes::default()) else {
+		return Ok(None);
+	};
+
+	if let Some(library_ids) = &scopes.library_ids {
+		let accessible_count = client
+			.library()
+			.count(vec![
+				library::id::in_vec(library_ids.clone()),
+				library_not_hidden_from_user_filter(req.user()),
+			])
+			.exec()
+			.await?;
+		if accessible_count != library_ids.len() as i64 {
+			return Err(APIError::BadRequest(
+				"One or more of the requested libraries could not be found".to_string(),
+			));
+		}
+	}
+
+	serde_json::to_vec(&scopes).map(Some).map_err(|e| {
+		tracing::error!(?e, "Failed to serialize scopes");
+		APIError::BadRequest("Invalid scopes requested".to_string())
+	})
 }
 
 /// The response after creating a new API key
@@ -161,6 +205,8 @@ async fn create_api_key(
 		APIError::BadRequest("Invalid permissions requested".to_string())
 	})?;
 
+	let scopes = serialize_scopes(client, &req, body.scopes).await?;
+
 	let (pek, hash) = APIKey::create_prefixed_key()?;
 	let _api_key = client
 		.api_key()
@@ -170,7 +216,10 @@ async fn create_api_key(
 			hash,
 			permissions,
 			user::id::equals(user.id.clone()),
-			vec![api_key::expires_at::set(body.expires_at)],
+			vec![
+				api_key::expires_at::set(body.expires_at),
+				api_key::scopes::set(scopes),
+			],
 		)
 		.exec()
 		.await?;
@@ -213,15 +262,23 @@ async fn update_api_key(
 		APIError::BadRequest("Invalid permissions requested".to_string())
 	})?;
 
+	let scopes = serialize_scopes(client, &req, body.scopes).await?;
+	// A new expiry date deserves a new notice
+	let expiry_changed = api_key.expires_at != body.expires_at;
+
 	let updated_api_key = client
 		.api_key()
 		.update(
 			api_key::id::equals(api_key.id),
-			vec![
-				api_key::name::set(body.name),
-				api_key::permissions::set(permissions),
-				api_key::expires_at::set(body.expires_at),
-			],
+			chain_optional_iter(
+				[
+					api_key::name::set(body.name),
+					api_key::permissions::set(permissions),
+					api_key::scopes::set(scopes),
+					api_key::expires_at::set(body.expires_at),
+				],
+				[expiry_changed.then(|| api_key::expiry_notified_at::set(None))],
+			),
 		)
 		.exec()
 		.await?;
@@ -294,3 +351,33 @@ async fn regenerate_api_key_secret(
 		api_key: pek.to_string(),
 	}))
 }
+
+/// Get the daily usage of an API key of the current user, most recent day first
+async fn get_api_key_usage(
+	Path(id): Path<i32>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<Vec<APIKeyUsage>>> {
+	let user = req.user();
+	let client = &ctx.db;
+
+	let api_key = client
+		.api_key()
+		.find_first(vec![
+			api_key::id::equals(id),
+			api_key::user_id::equals(user.id.clone()),
+		])
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound("API key not found".to_string()))?;
+
+	let usages = client
+		.api_key_usage()
+		.find_many(vec![api_key_usage::api_key_id::equals(api_key.id)])
+		.order_by(api_key_usage::day::order(Direction::Desc))
+		.take(API_KEY_USAGE_LIMIT)
+		.exec()
+		.await?;
+
+	Ok(Json(usages.into_iter().map(APIKeyUsage::from).collect()))
+}
@@ -22,8 +22,9 @@ use utoipa::ToSchema;
 use crate::{
 	config::{
 		jwt::{create_user_jwt, CreatedToken},
-		session::{delete_cookie_header, SESSION_USER_KEY},
+		session::{delete_cookie_header, mark_session_authenticated, SESSION_USER_KEY},
 		state::AppState,
+		two_factor::PendingTwoFactorLogin,
 	},
 	errors::{api_error_message, APIError, APIResult},
 	http_server::StumpRequestInfo,
@@ -31,6 +32,8 @@ use crate::{
 	utils::{default_true, get_session_user, hash_password, verify_password},
 };
 
+use super::two_factor::verify_second_factor;
+
 pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 	Router::new().nest(
 		"/auth",
@@ -41,6 +44,7 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 					.layer(middleware::from_fn_with_state(app_state, auth_middleware)),
 			)
 			.route("/login", post(login))
+			.route("/login/2fa", post(login_two_factor))
 			.route("/logout", post(logout))
 			.route("/register", post(register)),
 	)
@@ -104,7 +108,7 @@ async fn viewer(Extension(req): Extension<RequestContext>) -> APIResult<Json<Use
 	Ok(Json(req.user().clone()))
 }
 
-async fn handle_login_attempt(
+pub(crate) async fn handle_login_attempt(
 	client: &PrismaClient,
 	for_user: user::Data,
 	user_agent: UserAgent,
@@ -153,7 +157,15 @@ async fn handle_remove_earliest_session(
 #[serde(untagged)]
 pub enum LoginResponse {
 	User(User),
-	AccessToken { for_user: User, token: CreatedToken },
+	AccessToken {
+		for_user: User,
+		token: CreatedToken,
+	},
+	/// The password was accepted, but a second factor must be verified using the
+	/// `/auth/login/2fa` endpoint before the login completes
+	TwoFactorRequired {
+		two_factor_token: String,
+	},
 }
 
 #[utoipa::path(
@@ -198,10 +210,6 @@ async fn login(
 	}
 
 	let client = state.db.clone();
-	let today: DateTime<FixedOffset> = Utc::now().into();
-	// TODO: make this configurable via environment variable so knowledgeable attackers can't bypass this
-	let twenty_four_hours_ago = today - Duration::hours(24);
-
 	let fetch_result = client
 		.user()
 		.find_first(vec![
@@ -210,14 +218,7 @@ async fn login(
 		])
 		.with(user::user_preferences::fetch())
 		.with(user::age_restriction::fetch())
-		.with(
-			user::login_activity::fetch(vec![
-				user_login_activity::timestamp::gte(twenty_four_hours_ago),
-				user_login_activity::timestamp::lte(today),
-			])
-			.order_by(user_login_activity::timestamp::order(Direction::Desc))
-			.take(10),
-		)
+		.with(recent_login_activity())
 		.with(user::sessions::fetch(vec![session::expiry_time::gt(
 			Utc::now().into(),
 		)]))
@@ -237,100 +238,255 @@ async fn login(
 			let user_id = db_user.id.clone();
 			let matches = verify_password(&db_user.hashed_password, &input.password)?;
 			if !matches {
-				// TODO: make this configurable via environment variable so knowledgeable attackers can't bypass this
-				let should_lock_account = db_user
-					.login_activity
-					.as_ref()
-					// If there are 9 or more failed login attempts _in a row_, within a 24 hour period, lock the account
-					.map(|activity| {
-						!activity
-							.iter()
-							.any(|activity| activity.authentication_successful)
-							&& activity.len() >= 9
-					})
-					.unwrap_or(false);
+				let should_lock = should_lock_account(&db_user);
 
 				handle_login_attempt(&client, db_user, user_agent, request_info, false)
 					.await?;
 
-				if should_lock_account {
-					let _locked_user = client
-						.user()
-						.update(
-							user::id::equals(user_id.clone()),
-							vec![user::is_locked::set(true)],
-						)
-						.exec()
-						.await?;
-
-					let removed_sessions_count = client
-						.session()
-						.delete_many(vec![session::user_id::equals(user_id.clone())])
-						.exec()
-						.await?;
-					tracing::debug!(
-						?removed_sessions_count,
-						?user_id,
-						"Locked user account and removed all associated sessions"
-					)
+				if should_lock {
+					lock_account(&client, &user_id).await?;
 				}
 
 				return Err(APIError::Unauthorized);
 			}
 
-			enforce_max_sessions(&db_user, &client).await?;
-
-			let updated_user = state
-				.db
-				.user()
-				.update(
-					user::id::equals(db_user.id.clone()),
-					vec![user::last_login::set(Some(Utc::now().into()))],
+			if db_user.totp_enabled_at.is_some() {
+				let two_factor_token = PendingTwoFactorLogin::new(
+					db_user.id.clone(),
+					generate_token,
+					create_session,
 				)
-				.with(user::user_preferences::fetch())
-				.with(user::age_restriction::fetch())
-				.exec()
-				.await
-				.unwrap_or_else(|err| {
-					error!(error = ?err, "Failed to update user last login!");
-					user::Data {
-						last_login: Some(Utc::now().into()),
-						..db_user
-					}
-				});
-
-			let login_track_result = handle_login_attempt(
-				&state.db,
-				updated_user.clone(),
+				.store();
+				return Ok(Json(LoginResponse::TwoFactorRequired { two_factor_token }));
+			}
+
+			let response = complete_login(
+				&state,
+				&session,
+				db_user,
 				user_agent,
 				request_info,
-				true,
+				generate_token,
+				create_session,
 			)
-			.await;
-			// I don't want to kill the login here, so not bubbling up the error
-			if let Err(err) = login_track_result {
-				error!(error = ?err, "Failed to track login attempt!");
-			}
+			.await?;
 
-			let user = User::from(updated_user);
+			Ok(Json(response))
+		},
+		_ => Err(APIError::Unauthorized),
+	}
+}
 
-			if create_session {
-				session.insert(SESSION_USER_KEY, user.clone()).await?;
-			}
+/// Fetch the login attempts of a user within the last 24 hours, most recent first, which are
+/// used to decide whether to lock their account
+fn recent_login_activity() -> user::login_activity::Fetch {
+	let today: DateTime<FixedOffset> = Utc::now().into();
+	// TODO: make this configurable via environment variable so knowledgeable attackers can't bypass this
+	let twenty_four_hours_ago = today - Duration::hours(24);
 
-			// TODO: should this be permission gated?
-			if generate_token {
-				let token = create_user_jwt(&user.id, &state.config)?;
-				Ok(Json(LoginResponse::AccessToken {
-					for_user: user,
-					token,
-				}))
-			} else {
-				Ok(Json(LoginResponse::User(user)))
+	user::login_activity::fetch(vec![
+		user_login_activity::timestamp::gte(twenty_four_hours_ago),
+		user_login_activity::timestamp::lte(today),
+	])
+	.order_by(user_login_activity::timestamp::order(Direction::Desc))
+	.take(10)
+}
+
+/// Whether another failed attempt should lock the account of the user, which requires their
+/// [recent_login_activity] to have been fetched. Failed passwords and failed second factor codes
+/// both count, so the limit holds across every pending two-factor login of the user.
+fn should_lock_account(db_user: &user::Data) -> bool {
+	// TODO: make this configurable via environment variable so knowledgeable attackers can't bypass this
+	db_user
+		.login_activity
+		.as_ref()
+		// If there are 9 or more failed login attempts _in a row_, within a 24 hour period, lock the account
+		.map(|activity| {
+			!activity
+				.iter()
+				.any(|activity| activity.authentication_successful)
+				&& activity.len() >= 9
+		})
+		.unwrap_or(false)
+}
+
+/// Lock the account of a user and remove all of their sessions
+async fn lock_account(client: &PrismaClient, user_id: &str) -> APIResult<()> {
+	let _locked_user = client
+		.user()
+		.update(
+			user::id::equals(user_id.to_string()),
+			vec![user::is_locked::set(true)],
+		)
+		.exec()
+		.await?;
+
+	let removed_sessions_count = client
+		.session()
+		.delete_many(vec![session::user_id::equals(user_id.to_string())])
+		.exec()
+		.await?;
+	tracing::debug!(
+		?removed_sessions_count,
+		?user_id,
+		"Locked user account and removed all associated sessions"
+	);
+
+	Ok(())
+}
+
+/// Finish a login which has passed all authentication checks, creating a session and/or
+/// access token for the user as requested
+async fn complete_login(
+	state: &AppState,
+	session: &Session,
+	db_user: user::Data,
+	user_agent: UserAgent,
+	request_info: StumpRequestInfo,
+	generate_token: bool,
+	create_session: bool,
+) -> APIResult<LoginResponse> {
+	enforce_max_sessions(&db_user, &state.db).await?;
+
+	let updated_user = state
+		.db
+		.user()
+		.update(
+			user::id::equals(db_user.id.clone()),
+			vec![user::last_login::set(Some(Utc::now().into()))],
+		)
+		.with(user::user_preferences::fetch())
+		.with(user::age_restriction::fetch())
+		.exec()
+		.await
+		.unwrap_or_else(|err| {
+			error!(error = ?err, "Failed to update user last login!");
+			user::Data {
+				last_login: Some(Utc::now().into()),
+				..db_user
 			}
-		},
-		_ => Err(APIError::Unauthorized),
+		});
+
+	let login_track_result = handle_login_attempt(
+		&state.db,
+		updated_user.clone(),
+		user_agent,
+		request_info,
+		true,
+	)
+	.await;
+	// I don't want to kill the login here, so not bubbling up the error
+	if let Err(err) = login_track_result {
+		error!(error = ?err, "Failed to track login attempt!");
+	}
+
+	let user = User::from(updated_user);
+
+	if create_session {
+		session.insert(SESSION_USER_KEY, user.clone()).await?;
+		mark_session_authenticated(session).await?;
+	}
+
+	// TODO: should this be permission gated?
+	if generate_token {
+		let token = create_user_jwt(&user.id, &state.config)?;
+		Ok(LoginResponse::AccessToken {
+			for_user: user,
+			token,
+		})
+	} else {
+		Ok(LoginResponse::User(user))
+	}
+}
+
+#[derive(Deserialize, Type, ToSchema)]
+pub struct LoginTwoFactorArgs {
+	/// The token returned by the password step of the login
+	pub two_factor_token: String,
+	/// A TOTP code or one of the user's recovery codes
+	pub code: String,
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/auth/login/2fa",
+	tag = "auth",
+	request_body = LoginTwoFactorArgs,
+	responses(
+		(status = 200, description = "Verifies the second factor and completes the login.", body = User),
+		(status = 401, description = "The code is invalid, or the login has expired."),
+		(status = 500, description = "An internal server error occurred.")
+	)
+)]
+/// Completes a login for a user with two-factor authentication enabled. The session and/or
+/// access token are only issued once the code has been verified.
+async fn login_two_factor(
+	TypedHeader(user_agent): TypedHeader<UserAgent>,
+	ConnectInfo(request_info): ConnectInfo<StumpRequestInfo>,
+	session: Session,
+	State(state): State<AppState>,
+	Json(input): Json<LoginTwoFactorArgs>,
+) -> APIResult<Json<LoginResponse>> {
+	let pending = PendingTwoFactorLogin::attempt(&input.two_factor_token)
+		.ok_or(APIError::Unauthorized)?;
+
+	let db_user = state
+		.db
+		.user()
+		.find_first(vec![
+			user::id::equals(pending.user_id.clone()),
+			user::deleted_at::equals(None),
+		])
+		.with(recent_login_activity())
+		.with(user::sessions::fetch(vec![session::expiry_time::gt(
+			Utc::now().into(),
+		)]))
+		.exec()
+		.await?
+		.ok_or(APIError::Unauthorized)?;
+
+	if db_user.is_locked {
+		PendingTwoFactorLogin::complete(&input.two_factor_token);
+		return Err(APIError::Forbidden(
+			api_error_message::LOCKED_ACCOUNT.to_string(),
+		));
 	}
+
+	let Some(params) = verify_second_factor(&db_user, &input.code) else {
+		let user_id = db_user.id.clone();
+		let should_lock = should_lock_account(&db_user);
+		handle_login_attempt(&state.db, db_user, user_agent, request_info, false).await?;
+		if should_lock {
+			PendingTwoFactorLogin::complete(&input.two_factor_token);
+			lock_account(&state.db, &user_id).await?;
+		}
+		return Err(APIError::Unauthorized);
+	};
+	PendingTwoFactorLogin::complete(&input.two_factor_token);
+
+	let db_user = state
+		.db
+		.user()
+		.update(user::id::equals(db_user.id.clone()), params)
+		.exec()
+		.await
+		.map(|updated| user::Data {
+			sessions: db_user.sessions.clone(),
+			..updated
+		})?;
+
+	let response = complete_login(
+		&state,
+		&session,
+		db_user,
+		user_agent,
+		request_info,
+		pending.generate_token,
+		pending.create_session,
+	)
+	.await?;
+
+	Ok(Json(response))
 }
 
 #[utoipa::path(
@@ -0,0 +1,114 @@
+use axum::{
+	extract::{Path, State},
+	middleware,
+	routing::get,
+	Json, Router,
+};
+use serde::Deserialize;
+use specta::Type;
+use stump_core::{
+	db::backup::{get_backup_path, list_backups, Backup, DatabaseBackupJob},
+	job::Executor,
+};
+use utoipa::ToSchema;
+
+use crate::{
+	config::state::AppState,
+	errors::{APIError, APIResult},
+	middleware::auth::{auth_middleware, server_owner_middleware},
+	utils::http::NamedFile,
+};
+
+use super::job::QueuedJob;
+
+pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
+	Router::new()
+		.nest(
+			"/backups",
+			Router::new()
+				.route("/", get(get_backups).post(create_backup))
+				.route("/{name}", get(download_backup)),
+		)
+		.layer(middleware::from_fn(server_owner_middleware))
+		.layer(middleware::from_fn_with_state(app_state, auth_middleware))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/backups",
+	tag = "backup",
+	responses(
+		(status = 200, description = "Successfully fetched backups", body = [Backup]),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// List the backups in the backups directory, newest first
+async fn get_backups(State(ctx): State<AppState>) -> APIResult<Json<Vec<Backup>>> {
+	Ok(Json(list_backups(&ctx.config)?))
+}
+
+#[derive(Debug, Deserialize, ToSchema, Type)]
+pub struct CreateBackup {
+	/// Whether to include the thumbnails in the backup. Defaults to true
+	#[serde(default = "default_include_thumbnails")]
+	include_thumbnails: bool,
+}
+
+fn default_include_thumbnails() -> bool {
+	true
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/backups",
+	tag = "backup",
+	request_body = CreateBackup,
+	responses(
+		(status = 200, description = "Successfully queued backup job", body = QueuedJob),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Queue a job which creates a backup, and then deletes the oldest backups beyond the
+/// configured retention
+async fn create_backup(
+	State(ctx): State<AppState>,
+	Json(input): Json<CreateBackup>,
+) -> APIResult<Json<QueuedJob>> {
+	let job = DatabaseBackupJob::new(input.include_thumbnails);
+	let id = job.id().to_string();
+	ctx.enqueue_job(job).map_err(|e| {
+		tracing::error!(?e, "Failed to enqueue database backup job");
+		APIError::InternalServerError("Failed to enqueue database backup job".to_string())
+	})?;
+
+	Ok(Json(QueuedJob { id }))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/backups/{name}",
+	tag = "backup",
+	params(
+		("name" = String, Path, description = "The file name of the backup"),
+	),
+	responses(
+		(status = 200, description = "Successfully downloaded backup"),
+		(status = 400, description = "Invalid backup name"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Backup not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Download a backup archive
+async fn download_backup(
+	Path(name): Path<String>,
+	State(ctx): State<AppState>,
+) -> APIResult<NamedFile> {
+	let path = get_backup_path(&ctx.config, &name)?;
+	Ok(NamedFile::open(path).await?)
+}
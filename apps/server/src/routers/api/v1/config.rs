@@ -32,7 +32,11 @@ async fn get_server_config(
 ) -> APIResult<Json<StumpConfig>> {
 	req.enforce_permissions(&[UserPermission::ManageServer])?;
 
-	Ok(Json(StumpConfig::clone(&ctx.config)))
+	let mut config = StumpConfig::clone(&ctx.config);
+	// The client secret should never leave the server, even for server managers
+	config.oidc_client_secret = config.oidc_client_secret.map(|_| "********".to_string());
+
+	Ok(Json(config))
 }
 
 #[derive(Debug, Deserialize, Serialize, Type)]
@@ -12,15 +12,17 @@ use serde_qs::axum::QsQuery;
 use specta::Type;
 use stump_core::{
 	db::entity::{
-		AttachmentMeta, EmailerConfig, EmailerConfigInput, EmailerSendRecord,
-		EmailerSendTo, Media, Notifier, RegisteredEmailDevice, SMTPEmailer, User,
-		UserPermission,
+		get_template_overrides, send_book_to_recipient, DeviceFormatProfile,
+		DeviceSendOutcome, EmailRecipient, EmailTemplateDetails, EmailerConfig,
+		EmailerConfigInput, EmailerSendRecord, EmailerSendTo, Media, Notifier,
+		RegisteredEmailDevice, SMTPEmailer, User, UserPermission,
 	},
-	filesystem::{ContentType, FileParts, PathUtils},
-	prisma::{emailer, emailer_send_record, registered_email_device, user, PrismaClient},
-	AttachmentPayload, EmailContentType,
+	prisma::{
+		email_template_override, emailer, emailer_send_record, registered_email_device,
+		user, PrismaClient,
+	},
+	render_template_with_overrides, validate_template, EmailTemplate,
 };
-use tokio::fs;
 use utoipa::ToSchema;
 
 use crate::{
@@ -69,6 +71,20 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 					),
 				),
 		)
+		.nest(
+			"/email-templates",
+			Router::new().route("/", get(get_email_templates)).nest(
+				"/{template}",
+				Router::new()
+					.route(
+						"/",
+						get(get_email_template)
+							.put(update_email_template)
+							.delete(delete_email_template),
+					)
+					.route("/preview", post(preview_email_template)),
+			),
+		)
 		.layer(middleware::from_fn_with_state(app_state, auth_middleware))
 }
 
@@ -404,7 +420,7 @@ async fn get_and_validate_recipients(
 	user: &User,
 	client: &PrismaClient,
 	send_to: &[EmailerSendTo],
-) -> APIResult<Vec<String>> {
+) -> APIResult<Vec<EmailRecipient>> {
 	let mut recipients = Vec::new();
 	for to in send_to {
 		let recipient = match to {
@@ -415,9 +431,28 @@ async fn get_and_validate_recipients(
 					.exec()
 					.await?
 					.ok_or(APIError::NotFound("Device not found".to_string()))?;
-				device.email
+				EmailRecipient {
+					format_profile: DeviceFormatProfile::from(&device),
+					email: device.email,
+				}
+			},
+			EmailerSendTo::Anonymous { email } => {
+				// An address which belongs to a registered device is converted for that device
+				let device = client
+					.registered_email_device()
+					.find_first(vec![registered_email_device::email::equals(
+						email.clone(),
+					)])
+					.exec()
+					.await?;
+				EmailRecipient {
+					email: email.clone(),
+					format_profile: device
+						.as_ref()
+						.map(DeviceFormatProfile::from)
+						.unwrap_or_default(),
+				}
 			},
-			EmailerSendTo::Anonymous { email } => email.clone(),
 		};
 		recipients.push(recipient);
 	}
@@ -429,8 +464,8 @@ async fn get_and_validate_recipients(
 		.await?;
 	let forbidden_recipients = recipients
 		.iter()
-		.filter(|r| forbidden_devices.iter().any(|d| d.email == **r))
-		.cloned()
+		.filter(|r| forbidden_devices.iter().any(|d| d.email == r.email))
+		.map(|r| r.email.clone())
 		.collect::<Vec<_>>();
 	let has_forbidden_recipients = !forbidden_recipients.is_empty();
 
@@ -510,90 +545,40 @@ async fn send_attachment_email(
 		Vec::<(i32, String, Vec<emailer_send_record::SetParam>)>::new();
 	let mut errors = Vec::new();
 
-	// TODO: Refactor this to chunk the books and send them in batches according to
-	// the max attachments per email limit
-
 	for book in books {
-		let FileParts {
-			file_name,
-			extension,
-			..
-		} = PathBuf::from(&book.path).file_parts();
-		let content = fs::read(book.path).await?;
-
-		// TODO: should error?
-		match (content.len(), max_attachment_size_bytes) {
-			(_, Some(max_size)) if content.len() as i32 > max_size => {
-				tracing::warn!("Attachment too large: {} > {}", content.len(), max_size);
-				continue;
-			},
-			(_, _) if content.len() < 5 => {
-				tracing::warn!("Attachment too small: {} < 5", content.len());
-				continue;
-			},
-			_ => {},
-		}
-
-		let content_type =
-			ContentType::from_bytes_with_fallback(&content[..5], &extension)
-				.mime_type()
-				.parse::<EmailContentType>()
-				.map_err(|_| {
-					APIError::InternalServerError(
-						"Failed to parse content type".to_string(),
-					)
-				})?;
-
-		let attachment_meta = AttachmentMeta::new(
-			file_name.clone(),
-			Some(book.id.clone()),
-			content.len() as i32,
-		)
-		.into_data()
-		.map_or_else(
-			|e| {
-				tracing::error!(?e, "Failed to serialize attachment meta");
-				None
-			},
-			Some,
-		);
-
 		for recipient in recipients.iter() {
-			let send_result = emailer_client
-				.send_attachment(
-					"Attachment from Stump",
-					recipient,
-					AttachmentPayload {
-						name: file_name.clone(),
-						content: content.clone(),
-						content_type: content_type.clone(),
+			let DeviceSendOutcome {
+				sent,
+				errors: send_errors,
+			} = send_book_to_recipient(
+				&emailer_client,
+				&book.id,
+				&PathBuf::from(&book.path),
+				recipient,
+				max_attachment_size_bytes,
+				&ctx.config,
+			)
+			.await;
+			errors.extend(send_errors);
+
+			for attachment_meta in sent {
+				let attachment_meta = attachment_meta.into_data().map_or_else(
+					|e| {
+						tracing::error!(?e, "Failed to serialize attachment meta");
+						None
 					},
-				)
-				.await;
-
-			match send_result {
-				Ok(_) => {
-					record_creates.push((
-						emailer_id,
-						recipient.clone(),
-						vec![
-							emailer_send_record::sent_by::connect(user::id::equals(
-								by_user.id.clone(),
-							)),
-							emailer_send_record::attachment_meta::set(
-								attachment_meta.clone(),
-							),
-						],
-					));
-				},
-				Err(e) => {
-					tracing::error!(?e, "Failed to send email");
-					errors.push(format!(
-						"Failed to send {} to {}: {}",
-						file_name, recipient, e
-					));
-					continue;
-				},
+					Some,
+				);
+				record_creates.push((
+					emailer_id,
+					recipient.email.clone(),
+					vec![
+						emailer_send_record::sent_by::connect(user::id::equals(
+							by_user.id.clone(),
+						)),
+						emailer_send_record::attachment_meta::set(attachment_meta),
+					],
+				));
 			}
 		}
 	}
@@ -711,6 +696,9 @@ pub struct CreateOrUpdateEmailDevice {
 	email: String,
 	/// Whether the device is forbidden from receiving emails from the server.
 	forbidden: bool,
+	/// How books are converted before they are sent to the device
+	#[serde(default)]
+	format_profile: DeviceFormatProfile,
 }
 
 /// Create a new email device
@@ -731,6 +719,7 @@ async fn create_email_device(
 	Json(payload): Json<CreateOrUpdateEmailDevice>,
 ) -> APIResult<Json<RegisteredEmailDevice>> {
 	req.enforce_permissions(&[UserPermission::EmailerManage])?;
+	payload.format_profile.validate()?;
 
 	let client = &ctx.db;
 
@@ -739,7 +728,15 @@ async fn create_email_device(
 		.create(
 			payload.name,
 			payload.email,
-			vec![registered_email_device::forbidden::set(payload.forbidden)],
+			vec![
+				registered_email_device::forbidden::set(payload.forbidden),
+				registered_email_device::target_format::set(
+					payload.format_profile.target_format.to_string(),
+				),
+				registered_email_device::max_image_dimension::set(
+					payload.format_profile.max_image_dimension,
+				),
+			],
 		)
 		.exec()
 		.await?;
@@ -770,6 +767,7 @@ async fn update_email_device(
 	Json(payload): Json<CreateOrUpdateEmailDevice>,
 ) -> APIResult<Json<RegisteredEmailDevice>> {
 	req.enforce_permissions(&[UserPermission::EmailerManage])?;
+	payload.format_profile.validate()?;
 
 	let client = &ctx.db;
 
@@ -781,6 +779,12 @@ async fn update_email_device(
 				registered_email_device::name::set(payload.name),
 				registered_email_device::email::set(payload.email),
 				registered_email_device::forbidden::set(payload.forbidden),
+				registered_email_device::target_format::set(
+					payload.format_profile.target_format.to_string(),
+				),
+				registered_email_device::max_image_dimension::set(
+					payload.format_profile.max_image_dimension,
+				),
 			],
 		)
 		.exec()
@@ -798,6 +802,8 @@ pub struct PatchEmailDevice {
 	pub email: Option<String>,
 	/// Whether the device is forbidden from receiving emails from the server.
 	pub forbidden: Option<bool>,
+	/// How books are converted before they are sent to the device
+	pub format_profile: Option<DeviceFormatProfile>,
 }
 
 #[utoipa::path(
@@ -822,9 +828,17 @@ async fn patch_email_device(
 	Json(payload): Json<PatchEmailDevice>,
 ) -> APIResult<Json<RegisteredEmailDevice>> {
 	req.enforce_permissions(&[UserPermission::EmailerManage])?;
+	if let Some(format_profile) = payload.format_profile.as_ref() {
+		format_profile.validate()?;
+	}
 
 	let client = &ctx.db;
 
+	let (target_format, max_image_dimension) = payload
+		.format_profile
+		.map(|profile| (profile.target_format, profile.max_image_dimension))
+		.unzip();
+
 	let device = client
 		.registered_email_device()
 		.update(
@@ -837,6 +851,11 @@ async fn patch_email_device(
 					payload
 						.forbidden
 						.map(registered_email_device::forbidden::set),
+					target_format.map(|format| {
+						registered_email_device::target_format::set(format.to_string())
+					}),
+					max_image_dimension
+						.map(registered_email_device::max_image_dimension::set),
 				],
 			),
 		)
@@ -879,3 +898,244 @@ async fn delete_email_device(
 
 	Ok(Json(RegisteredEmailDevice::from(device)))
 }
+
+async fn get_email_template_details(
+	ctx: &AppState,
+	template: EmailTemplate,
+) -> APIResult<EmailTemplateDetails> {
+	let stored_override = ctx
+		.db
+		.email_template_override()
+		.find_unique(email_template_override::template::equals(
+			template.to_string(),
+		))
+		.exec()
+		.await?;
+
+	Ok(EmailTemplateDetails::resolve(
+		template,
+		stored_override,
+		ctx.config.get_templates_dir(),
+	))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/email-templates",
+	tag = "email_template",
+	responses(
+		(status = 200, description = "Successfully retrieved email templates", body = Vec<EmailTemplateDetails>),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Get every email template, along with the source it is currently rendered from
+async fn get_email_templates(
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<Vec<EmailTemplateDetails>>> {
+	req.enforce_permissions(&[UserPermission::EmailerRead])?;
+
+	let mut stored_overrides = ctx
+		.db
+		.email_template_override()
+		.find_many(vec![])
+		.exec()
+		.await?;
+	let templates_dir = ctx.config.get_templates_dir();
+
+	let templates = EmailTemplate::ALL
+		.into_iter()
+		.map(|template| {
+			let stored_override = stored_overrides
+				.iter()
+				.position(|data| data.template == template.to_string())
+				.map(|idx| stored_overrides.swap_remove(idx));
+			EmailTemplateDetails::resolve(
+				template,
+				stored_override,
+				templates_dir.clone(),
+			)
+		})
+		.collect();
+
+	Ok(Json(templates))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/email-templates/{template}",
+	tag = "email_template",
+	params(
+		("template" = EmailTemplate, Path, description = "The email template"),
+	),
+	responses(
+		(status = 200, description = "Successfully retrieved email template", body = EmailTemplateDetails),
+		(status = 400, description = "Bad request"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Get an email template, along with the source it is currently rendered from
+async fn get_email_template(
+	State(ctx): State<AppState>,
+	Path(template): Path<EmailTemplate>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<EmailTemplateDetails>> {
+	req.enforce_permissions(&[UserPermission::EmailerRead])?;
+
+	Ok(Json(get_email_template_details(&ctx, template).await?))
+}
+
+#[derive(Deserialize, ToSchema, Type)]
+pub struct UpdateEmailTemplate {
+	/// The handlebars source which should replace the template
+	source: String,
+}
+
+#[utoipa::path(
+	put,
+	path = "/api/v1/email-templates/{template}",
+	tag = "email_template",
+	params(
+		("template" = EmailTemplate, Path, description = "The email template to override"),
+	),
+	request_body = UpdateEmailTemplate,
+	responses(
+		(status = 200, description = "Successfully overrode email template", body = EmailTemplateDetails),
+		(status = 400, description = "Invalid template"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Override an email template. The source is validated by rendering it with sample data before
+/// it is saved, so a template which doesn't compile is never used to send an email.
+async fn update_email_template(
+	State(ctx): State<AppState>,
+	Path(template): Path<EmailTemplate>,
+	Extension(req): Extension<RequestContext>,
+	Json(payload): Json<UpdateEmailTemplate>,
+) -> APIResult<Json<EmailTemplateDetails>> {
+	req.enforce_permissions(&[UserPermission::EmailerManage])?;
+
+	validate_template(template, &payload.source, ctx.config.get_templates_dir())
+		.map_err(|e| APIError::BadRequest(format!("Invalid template: {e}")))?;
+
+	let stored_override = ctx
+		.db
+		.email_template_override()
+		.upsert(
+			email_template_override::template::equals(template.to_string()),
+			email_template_override::create(
+				template.to_string(),
+				payload.source.clone(),
+				vec![],
+			),
+			vec![email_template_override::source::set(payload.source)],
+		)
+		.exec()
+		.await?;
+
+	Ok(Json(EmailTemplateDetails::resolve(
+		template,
+		Some(stored_override),
+		ctx.config.get_templates_dir(),
+	)))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/v1/email-templates/{template}",
+	tag = "email_template",
+	params(
+		("template" = EmailTemplate, Path, description = "The email template to reset"),
+	),
+	responses(
+		(status = 200, description = "Successfully reset email template", body = EmailTemplateDetails),
+		(status = 400, description = "Bad request"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Remove the override of an email template, so it is rendered from the templates directory or
+/// the default template again
+async fn delete_email_template(
+	State(ctx): State<AppState>,
+	Path(template): Path<EmailTemplate>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<EmailTemplateDetails>> {
+	req.enforce_permissions(&[UserPermission::EmailerManage])?;
+
+	ctx.db
+		.email_template_override()
+		.delete_many(vec![email_template_override::template::equals(
+			template.to_string(),
+		)])
+		.exec()
+		.await?;
+
+	Ok(Json(EmailTemplateDetails::resolve(
+		template,
+		None,
+		ctx.config.get_templates_dir(),
+	)))
+}
+
+#[derive(Default, Deserialize, ToSchema, Type)]
+pub struct PreviewEmailTemplate {
+	/// The handlebars source to preview, e.g. unsaved changes to the template. The current
+	/// source of the template is previewed when this is not set.
+	#[serde(default)]
+	source: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Type)]
+pub struct EmailTemplatePreview {
+	/// The HTML the template rendered with sample data
+	html: String,
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/email-templates/{template}/preview",
+	tag = "email_template",
+	params(
+		("template" = EmailTemplate, Path, description = "The email template to preview"),
+	),
+	request_body = PreviewEmailTemplate,
+	responses(
+		(status = 200, description = "Successfully rendered email template", body = EmailTemplatePreview),
+		(status = 400, description = "Invalid template"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Render an email template with sample data, so it can be previewed before an email is sent
+async fn preview_email_template(
+	State(ctx): State<AppState>,
+	Path(template): Path<EmailTemplate>,
+	Extension(req): Extension<RequestContext>,
+	Json(payload): Json<PreviewEmailTemplate>,
+) -> APIResult<Json<EmailTemplatePreview>> {
+	req.enforce_permissions(&[UserPermission::EmailerManage])?;
+
+	let mut overrides = get_template_overrides(&ctx.db).await?;
+	if let Some(source) = payload.source {
+		overrides.insert(template, source);
+	}
+
+	let html = render_template_with_overrides(
+		template,
+		&template.sample_data(),
+		ctx.config.get_templates_dir(),
+		&overrides,
+	)
+	.map_err(|e| APIError::BadRequest(format!("Invalid template: {e}")))?;
+
+	Ok(Json(EmailTemplatePreview { html }))
+}
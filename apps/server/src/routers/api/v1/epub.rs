@@ -2,21 +2,23 @@ use std::path::PathBuf;
 
 use axum::{
 	extract::{Path, State},
+	http::{header, HeaderName},
 	middleware,
 	routing::{get, put},
 	Extension, Json, Router,
 };
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Utc};
 use serde::Deserialize;
 use specta::Type;
 use stump_core::{
 	db::entity::{
-		ActiveReadingSession, Bookmark, Epub, FinishedReadingSession,
-		ProgressUpdateReturn, UpdateEpubProgress,
+		ActiveReadingSession, Bookmark, Epub, EpubPositionList, FinishedReadingSession,
+		ProgressUpdateReturn, ReadingTime, UpdateEpubProgress,
 	},
 	filesystem::media::EpubProcessor,
 	prisma::{
-		active_reading_session, bookmark, finished_reading_session, media,
-		media_annotation, user,
+		active_reading_session, bookmark, epub_positions, finished_reading_session,
+		media, media_annotation, user,
 	},
 };
 use utoipa::ToSchema;
@@ -24,16 +26,35 @@ use utoipa::ToSchema;
 use crate::{
 	config::state::AppState,
 	errors::{APIError, APIResult},
+	filter::chain_optional_iter,
 	middleware::auth::{auth_middleware, RequestContext},
 	utils::http::BufferResponse,
 };
 
+/// The policy EPUB resources are served with, so a document which gets past the sanitizer still
+/// can't run scripts, embed other documents or load remote resources when it is opened directly
+const EPUB_CONTENT_SECURITY_POLICY: &str = "default-src 'self' data:; style-src 'self' 'unsafe-inline' data:; script-src 'none'; object-src 'none'; frame-src 'none'; base-uri 'none'; form-action 'none'";
+
+/// A sanitized EPUB resource, along with its [EPUB_CONTENT_SECURITY_POLICY]
+type EpubResourceResponse = ([(HeaderName, &'static str); 1], BufferResponse);
+
+fn epub_resource_response(buffer: BufferResponse) -> EpubResourceResponse {
+	(
+		[(
+			header::CONTENT_SECURITY_POLICY,
+			EPUB_CONTENT_SECURITY_POLICY,
+		)],
+		buffer,
+	)
+}
+
 pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 	Router::new()
 		.nest(
 			"/epub/{id}",
 			Router::new()
 				.route("/", get(get_epub_by_id))
+				.route("/positions", get(get_epub_positions))
 				.route("/progress", put(update_epub_progress))
 				.route(
 					"/bookmarks",
@@ -78,9 +99,36 @@ async fn get_epub_by_id(
 	}
 }
 
+/// Get the positions list of an epub. The positions are stored when the epub is analyzed, and
+/// are otherwise read from the file.
+async fn get_epub_positions(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+) -> APIResult<Json<EpubPositionList>> {
+	let book = ctx
+		.db
+		.media()
+		.find_unique(media::id::equals(id.clone()))
+		.with(media::epub_positions::fetch())
+		.exec()
+		.await?
+		.ok_or_else(|| APIError::NotFound(format!("Media with id {id} not found")))?;
+
+	let positions = tokio::task::spawn_blocking(move || {
+		let stored = book.epub_positions().ok().flatten();
+		EpubPositionList::resolve(stored, &book.path, book.pages)
+	})
+	.await
+	.map_err(|e| APIError::InternalServerError(e.to_string()))??;
+
+	Ok(Json(positions))
+}
+
 /// Update the progress of an epub. This is separate from media progress updates
 /// since there is enough epub-specific data that needs to be updated that would
-/// convolute the media progress update.
+/// convolute the media progress update. When the epub has been analyzed, the position
+/// containing the progression is recorded as the page, so the progress is comparable to
+/// other books.
 async fn update_epub_progress(
 	Path(id): Path<String>,
 	State(ctx): State<AppState>,
@@ -91,6 +139,24 @@ async fn update_epub_progress(
 	let user_id = req.id();
 
 	let is_complete = input.is_complete.unwrap_or(input.percentage >= 1.0);
+	let page = client
+		.epub_positions()
+		.find_unique(epub_positions::media_id::equals(id.clone()))
+		.exec()
+		.await?
+		.and_then(|stored| EpubPositionList::from_stored(&stored.positions).ok())
+		.and_then(|positions| positions.resolve_progress(None, Some(input.percentage)).0);
+
+	let now: DateTime<FixedOffset> = Utc::now().into();
+	let existing_session = client
+		.active_reading_session()
+		.find_unique(active_reading_session::user_id_media_id(
+			user_id.clone(),
+			id.clone(),
+		))
+		.exec()
+		.await?;
+	let reading_time = ReadingTime::from(existing_session.as_ref()).heartbeat(now);
 
 	if is_complete {
 		let finished_session = client
@@ -112,7 +178,7 @@ async fn update_epub_progress(
 						deleted_session.map(|s| s.started_at).unwrap_or_default(),
 						media::id::equals(id.clone()),
 						user::id::equals(user_id.clone()),
-						vec![],
+						reading_time.into_finished_set_params(),
 					)
 					.exec()
 					.await
@@ -124,6 +190,17 @@ async fn update_epub_progress(
 			FinishedReadingSession::from(finished_session),
 		)))
 	} else {
+		let set_params = chain_optional_iter(
+			[
+				active_reading_session::epubcfi::set(Some(input.epubcfi)),
+				active_reading_session::percentage_completed::set(Some(input.percentage)),
+			]
+			.into_iter()
+			.chain(reading_time.into_active_set_params())
+			.collect::<Vec<_>>(),
+			[page.map(|page| active_reading_session::page::set(Some(page)))],
+		);
+
 		let active_session = client
 			.active_reading_session()
 			.upsert(
@@ -131,19 +208,9 @@ async fn update_epub_progress(
 				(
 					media::id::equals(id.clone()),
 					user::id::equals(user_id.clone()),
-					vec![
-						active_reading_session::epubcfi::set(Some(input.epubcfi.clone())),
-						active_reading_session::percentage_completed::set(Some(
-							input.percentage,
-						)),
-					],
+					set_params.clone(),
 				),
-				vec![
-					active_reading_session::epubcfi::set(Some(input.epubcfi)),
-					active_reading_session::percentage_completed::set(Some(
-						input.percentage,
-					)),
-				],
+				set_params,
 			)
 			.exec()
 			.await?;
@@ -267,7 +334,7 @@ async fn delete_bookmark(
 async fn get_epub_chapter(
 	Path((id, chapter)): Path<(String, usize)>,
 	State(ctx): State<AppState>,
-) -> APIResult<BufferResponse> {
+) -> APIResult<EpubResourceResponse> {
 	let result = ctx
 		.db
 		.media()
@@ -276,7 +343,11 @@ async fn get_epub_chapter(
 		.await?;
 
 	if let Some(book) = result {
-		Ok(EpubProcessor::get_chapter(book.path.as_str(), chapter)?.into())
+		let base_url = format!("/api/v1/epub/{id}");
+		Ok(epub_resource_response(
+			EpubProcessor::get_sanitized_chapter(book.path.as_str(), chapter, &base_url)?
+				.into(),
+		))
 	} else {
 		Err(APIError::NotFound(format!("Media with id {id} not found")))
 	}
@@ -286,11 +357,12 @@ async fn get_epub_chapter(
 /// grab a resource by resource ID (e.g. `META-INF/container.xml`, where `container.xml` is the
 /// resource ID). Otherwise, the `resource` query parameter represents the path to the requested
 /// resource. (e.g. `/EPUB/chapter1.xhtml`, where `EPUB` is the root and `chapter1.xhtml` is
-/// the resource path)
+/// the resource path). Resources at the top level of the epub, outside of any directory, use
+/// `~` as the root.
 async fn get_epub_meta(
 	Path((id, root, resource)): Path<(String, String, PathBuf)>,
 	State(ctx): State<AppState>,
-) -> APIResult<BufferResponse> {
+) -> APIResult<EpubResourceResponse> {
 	let result = ctx
 		.db
 		.media()
@@ -299,23 +371,30 @@ async fn get_epub_meta(
 		.await?;
 
 	if let Some(book) = result {
+		// Markup and stylesheets are sanitized, and their links rewritten to this route
+		let base_url = format!("/api/v1/epub/{id}");
 		let (content_type, buffer) = if root == "META-INF" {
 			// reserved for accessing resources via resource id
-			EpubProcessor::get_resource_by_id(
+			EpubProcessor::get_sanitized_resource_by_id(
 				book.path.as_str(),
 				resource.to_str().unwrap_or_default(),
+				&base_url,
 			)?
 		} else {
 			// NOTE: when a resource is loaded from a path, it is likely something inside the contents of an epub page,
 			// such as a css file or an image file.
-			EpubProcessor::get_resource_by_path(
+			EpubProcessor::get_sanitized_resource_by_path(
 				book.path.as_str(),
 				root.as_str(),
 				resource,
+				&base_url,
 			)?
 		};
 
-		Ok(BufferResponse::new(content_type, buffer))
+		Ok(epub_resource_response(BufferResponse::new(
+			content_type,
+			buffer,
+		)))
 	} else {
 		Err(APIError::NotFound(format!("Media with id {id} not found")))
 	}
@@ -9,12 +9,13 @@ use serde_qs::axum::QsQuery;
 use specta::Type;
 use stump_core::{
 	db::{
-		entity::{JobSchedulerConfig, PersistedJob},
+		entity::{CoreJobOutput, JobSchedulerConfig, PersistedJob},
 		query::{
 			ordering::QueryOrder,
 			pagination::{Pageable, Pagination, PaginationQuery},
 		},
 	},
+	filesystem::scanner::ScanDiff,
 	job::{AcknowledgeableCommand, JobControllerCommand},
 	prisma::{
 		job::{self, OrderByParam as JobOrderByParam},
@@ -41,7 +42,8 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 					"/{id}",
 					Router::new()
 						.route("/", get(get_job_by_id).delete(delete_job_by_id))
-						.route("/cancel", delete(cancel_job_by_id)),
+						.route("/cancel", delete(cancel_job_by_id))
+						.route("/scan-diff", get(get_job_scan_diff)),
 				)
 				.route(
 					"/scheduler-config",
@@ -53,6 +55,12 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 		.layer(middleware::from_fn_with_state(app_state, auth_middleware))
 }
 
+/// A job which was queued, which can be followed through the jobs API using its ID
+#[derive(Deserialize, Serialize, ToSchema, Type)]
+pub struct QueuedJob {
+	pub id: String,
+}
+
 #[derive(Deserialize, Serialize, ToSchema, Type)]
 pub struct GetJobsParams {
 	#[serde(default)]
@@ -190,6 +198,46 @@ async fn get_job_by_id(
 	Ok(Json(PersistedJob::from(job)))
 }
 
+#[utoipa::path(
+	get,
+	path = "/api/v1/jobs/{id}/scan-diff",
+	tag = "job",
+	params(
+		("id" = String, Path, description = "The ID of the scan job.")
+	),
+	responses(
+		(status = 200, description = "Successfully fetched scan diff", body = ScanDiff),
+		(status = 401, description = "No user is logged in (unauthorized)."),
+		(status = 403, description = "User does not have permission to access this resource."),
+		(status = 404, description = "Job not found, or it is not a completed dry run scan"),
+		(status = 500, description = "Internal server error."),
+	)
+)]
+/// Get the changes recorded by a dry run library or series scan. The diff is only available
+/// once the job has completed.
+async fn get_job_scan_diff(
+	State(ctx): State<AppState>,
+	Path(job_id): Path<String>,
+) -> APIResult<Json<ScanDiff>> {
+	let job = ctx
+		.db
+		.job()
+		.find_unique(job::id::equals(job_id))
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound("Job not found".to_string()))?;
+
+	PersistedJob::from(job)
+		.output_data
+		.as_ref()
+		.and_then(CoreJobOutput::scan_diff)
+		.cloned()
+		.map(Json)
+		.ok_or(APIError::NotFound(
+			"The job is not a completed dry run scan".to_string(),
+		))
+}
+
 #[utoipa::path(
 	delete,
 	path = "/api/v1/jobs/{id}",
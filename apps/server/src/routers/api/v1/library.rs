@@ -5,7 +5,7 @@ use axum::{
 	Extension, Json, Router,
 };
 use chrono::{DateTime, Duration, FixedOffset};
-use prisma_client_rust::{chrono::Utc, not, or, raw, Direction, PrismaValue};
+use prisma_client_rust::{chrono::Utc, or, raw, Direction, PrismaValue};
 use serde::{Deserialize, Serialize};
 use serde_qs::axum::QsQuery;
 use serde_with::skip_serializing_none;
@@ -24,8 +24,8 @@ use stump_core::{
 				library_series_ids_media_ids_include, library_tags_select,
 				library_thumbnails_deletion_include, series_or_library_thumbnail,
 			},
-			FileStatus, Library, LibraryConfig, LibraryScanMode, LibraryStats, Media,
-			Series, TagName, User, UserPermission,
+			FileStatus, IgnoreRules, Library, LibraryConfig, LibraryPattern,
+			LibraryScanMode, LibraryStats, Media, Series, TagName, User, UserPermission,
 		},
 		query::pagination::{
 			Pageable, PageableLibraries, PageableSeries, Pagination, PaginationQuery,
@@ -40,9 +40,14 @@ use stump_core::{
 			GenerateThumbnailOptions, ImageFormat, ImageProcessorOptions,
 			ThumbnailGenerationJob, ThumbnailGenerationJobParams,
 		},
-		scanner::{LastLibraryScan, LibraryScanJob, LibraryScanRecord, ScanOptions},
+		pdf::PdfRenderOptions,
+		scanner::{
+			detect_series, LastLibraryScan, LibraryScanJob, LibraryScanRecord,
+			ScanOptions, SeriesDetection, SeriesResolver,
+		},
 		ContentType,
 	},
+	job::Executor,
 	prisma::{
 		last_library_visit, library, library_config, library_scan_record,
 		media::{self, OrderByParam as MediaOrderByParam},
@@ -67,13 +72,14 @@ use crate::{
 	utils::{http::ImageResponse, validate_and_load_image},
 };
 
-use super::series::get_series_thumbnail;
+use super::{job::QueuedJob, series::get_series_thumbnail};
 
 // TODO: age restrictions!
 pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 	Router::new()
 		.route("/libraries", get(get_libraries).post(create_library))
 		.route("/libraries/stats", get(get_libraries_stats))
+		.route("/libraries/series-preview", post(preview_library_series))
 		.nest(
 			"/libraries/last-visited",
 			Router::new()
@@ -1111,22 +1117,24 @@ async fn delete_library_scan_history(
 	post,
 	path = "/api/v1/libraries/{id}/scan",
 	tag = "library",
+	request_body = ScanOptions,
 	responses(
-		(status = 200, description = "Successfully queued library scan"),
+		(status = 200, description = "Successfully queued library scan", body = QueuedJob),
 		(status = 401, description = "Unauthorized"),
 		(status = 404, description = "Library not found"),
 		(status = 500, description = "Internal server error")
 	)
 )]
 /// Queue a ScannerJob to scan the library by id. The job, when started, is
-/// executed in a separate thread.
+/// executed in a separate thread. When the scan is a dry run, the changes it would make
+/// can be fetched from the jobs API using the returned ID once it completes.
 #[tracing::instrument(skip(ctx, req))]
 async fn scan_library(
 	Path(id): Path<String>,
 	State(ctx): State<AppState>,
 	Extension(req): Extension<RequestContext>,
 	Json(options): Json<Option<ScanOptions>>,
-) -> Result<(), APIError> {
+) -> APIResult<Json<QueuedJob>> {
 	let user = req.user_and_enforce_permissions(&[UserPermission::ScanLibrary])?;
 	let db = &ctx.db;
 
@@ -1143,20 +1151,23 @@ async fn scan_library(
 			"Library with id {id} not found"
 		)))?;
 
-	ctx.enqueue_job(LibraryScanJob::new(library.id, library.path, options))
-		.map_err(|e| {
-			error!(?e, "Failed to enqueue library scan job");
-			APIError::InternalServerError(
-				"Failed to enqueue library scan job".to_string(),
-			)
-		})?;
-	tracing::debug!("Enqueued library scan job");
+	let job = LibraryScanJob::new(library.id, library.path, options);
+	let job_id = job.id().to_string();
+	ctx.enqueue_job(job).map_err(|e| {
+		error!(?e, "Failed to enqueue library scan job");
+		APIError::InternalServerError("Failed to enqueue library scan job".to_string())
+	})?;
+	tracing::debug!(job_id, "Enqueued library scan job");
 
-	Ok(())
+	Ok(Json(QueuedJob { id: job_id }))
 }
 
+/// The number of media trashed per query when cleaning a library
+const CLEAN_LIBRARY_CHUNK_SIZE: usize = 500;
+
 #[derive(Debug, Deserialize, Serialize, ToSchema, Type)]
 pub struct CleanLibraryResponse {
+	/// The number of media moved to the trash
 	deleted_media_count: i32,
 	deleted_series_count: i32,
 	is_empty: bool,
@@ -1178,6 +1189,9 @@ pub struct CleanLibraryResponse {
 		(status = 500, description = "Internal server error")
 	)
 )]
+/// Moves any media in the library which is not ready and whose file no longer exists to the
+/// trash, and deletes any series which are left without media. Trashed media is kept until
+/// it is restored or purged.
 async fn clean_library(
 	Path(id): Path<String>,
 	State(ctx): State<AppState>,
@@ -1186,9 +1200,8 @@ async fn clean_library(
 	let user = req.user_and_enforce_permissions(&[UserPermission::ManageLibrary])?;
 
 	let db = &ctx.db;
-	let thumbnails_dir = ctx.config.get_thumbnails_dir();
 
-	let result: APIResult<(CleanLibraryResponse, Vec<String>)> = db
+	let result: APIResult<CleanLibraryResponse> = db
 		._transaction()
 		.run(|client| async move {
 			// This isn't really necessary, but it is more for RESTful patterns (i.e. error
@@ -1203,54 +1216,46 @@ async fn clean_library(
 				.await?
 				.ok_or(APIError::NotFound("Library not found".to_string()))?;
 
-			let delete_media_params = vec![
-				media::series::is(vec![series::library_id::equals(Some(id.clone()))]),
-				// Delete media that are not ready
-				media::status::not(FileStatus::Ready.to_string()),
-			];
-
-			let media_to_delete = client
+			// Media which failed to process (e.g. an unsupported or corrupt file) is still on
+			// disk, so only media whose file is gone is trashed
+			let missing_media_ids = client
 				.media()
-				.find_many(delete_media_params.clone())
+				.find_many(vec![
+					media::series::is(vec![series::library_id::equals(Some(id.clone()))]),
+					media::status::not(FileStatus::Ready.to_string()),
+					media::deleted_at::equals(None),
+				])
+				.select(media::select!({ id path }))
 				.exec()
-				.await?;
-
-			let media_to_delete_count = media_to_delete.len();
-			let media_to_delete_ids = media_to_delete
+				.await?
 				.into_iter()
-				.map(|m| m.id)
+				.filter(|media| !path::Path::new(&media.path).exists())
+				.map(|media| media.id)
 				.collect::<Vec<_>>();
 
-			let deleted_media_count = client
-				.media()
-				.delete_many(delete_media_params)
-				.exec()
-				.await?
-				.try_into()?;
-
-			tracing::debug!(deleted_media_count, "Deleted media");
-
-			if media_to_delete_count != deleted_media_count as usize {
-				tracing::warn!(
-					fetched_media_count = media_to_delete_count,
-					deleted_media_count,
-					"Deleted media count does not match fetched media count"
-				);
+			let mut deleted_media_count = 0;
+			for chunk in missing_media_ids.chunks(CLEAN_LIBRARY_CHUNK_SIZE) {
+				let count: i32 = client
+					.media()
+					.update_many(
+						vec![media::id::in_vec(chunk.to_vec())],
+						vec![media::deleted_at::set(Some(Utc::now().into()))],
+					)
+					.exec()
+					.await?
+					.try_into()?;
+				deleted_media_count += count;
 			}
 
+			tracing::debug!(deleted_media_count, "Moved media to the trash");
+
+			// Series with media in the trash are kept, so the media may still be restored.
+			// They are deleted once the trash is purged.
 			let deleted_series_count = client
 				.series()
 				.delete_many(vec![
 					series::library_id::equals(Some(id.clone())),
-					or![
-						// Delete series that are missing
-						series::status::not(FileStatus::Ready.to_string()),
-						// Delete series that have no non-missing media. Since we deleted all the media
-						// above, this is effectively the same as deleting all series with no media
-						not![series::media::some(vec![media::status::equals(
-							FileStatus::Ready.to_string()
-						)])],
-					],
+					series::media::none(vec![]),
 				])
 				.exec()
 				.await?
@@ -1265,43 +1270,25 @@ async fn clean_library(
 					or![
 						// There are no series
 						library::series::none(vec![]),
-						// All series have no media
-						library::series::every(vec![series::media::none(vec![])])
+						// All series have no media outside of the trash
+						library::series::every(vec![series::media::none(vec![
+							media::deleted_at::equals(None)
+						])])
 					],
 				])
 				.exec()
 				.await?
 				.is_some();
 
-			Ok((
-				CleanLibraryResponse {
-					deleted_media_count,
-					deleted_series_count,
-					is_empty,
-				},
-				media_to_delete_ids,
-			))
+			Ok(CleanLibraryResponse {
+				deleted_media_count,
+				deleted_series_count,
+				is_empty,
+			})
 		})
 		.await;
-	let (response, media_to_delete_ids) = result?;
 
-	if !media_to_delete_ids.is_empty() {
-		image::remove_thumbnails(&media_to_delete_ids, &thumbnails_dir)
-			.await
-			.map_or_else(
-				|error| {
-					tracing::error!(
-						?error,
-						"Failed to remove thumbnails for library media"
-					);
-				},
-				|_| {
-					tracing::debug!("Removed thumbnails for deleted media");
-				},
-			);
-	}
-
-	Ok(Json(response))
+	Ok(Json(result?))
 }
 
 #[derive(Deserialize, Debug, Type, ToSchema)]
@@ -1369,7 +1356,16 @@ async fn create_library(
 	// TODO(prisma-nested-create): Refactor once nested create is supported
 	// https://github.com/Brendonovich/prisma-client-rust/issues/44
 	let library_config = input.config.unwrap_or_default();
+	if let Some(series_detection) = library_config.series_detection.as_ref() {
+		series_detection.validate()?;
+	}
+	if let Some(pdf_render_config) = library_config.pdf_render_config.as_ref() {
+		pdf_render_config.validate()?;
+	}
+	library_config.filename_rules.validate()?;
+	library_config.validate_poll_interval()?;
 	let watch = library_config.watch;
+	let watcher_backend = library_config.watcher_backend();
 	let path = input.path.clone();
 	let transaction_result: Result<Library, APIError> = db
 		._transaction()
@@ -1378,10 +1374,23 @@ async fn create_library(
 			let ignore_rules = (!library_config.ignore_rules.is_empty())
 				.then(|| library_config.ignore_rules.as_bytes())
 				.transpose()?;
+			let filename_rules = (!library_config.filename_rules.is_empty())
+				.then(|| library_config.filename_rules.as_bytes())
+				.transpose()?;
 			let thumbnail_config = library_config
 				.thumbnail_config
 				.map(|options| options.as_bytes())
 				.transpose()?;
+			let series_detection = library_config
+				.series_detection
+				.as_ref()
+				.map(SeriesDetection::as_bytes)
+				.transpose()?;
+			let pdf_render_config = library_config
+				.pdf_render_config
+				.as_ref()
+				.map(PdfRenderOptions::as_bytes)
+				.transpose()?;
 
 			let library_config = client
 				.library_config()
@@ -1415,7 +1424,14 @@ async fn create_library(
 					),
 					library_config::thumbnail_config::set(thumbnail_config),
 					library_config::ignore_rules::set(ignore_rules),
+					library_config::filename_rules::set(filename_rules),
+					library_config::series_detection::set(series_detection),
+					library_config::pdf_render_config::set(pdf_render_config),
 					library_config::watch::set(library_config.watch),
+					library_config::watcher_mode::set(
+						library_config.watcher_mode.to_string(),
+					),
+					library_config::poll_interval::set(library_config.poll_interval),
 				])
 				.exec()
 				.await?;
@@ -1520,7 +1536,7 @@ async fn create_library(
 
 	if watch {
 		ctx.library_watcher
-			.add_watcher(path.into())
+			.add_watcher(path.into(), watcher_backend)
 			.await
 			.map_err(|e| {
 				error!(?e, "Failed to add library watcher");
@@ -1531,6 +1547,134 @@ async fn create_library(
 	Ok(Json(library))
 }
 
+#[derive(Deserialize, Debug, Type, ToSchema)]
+pub struct PreviewLibrarySeries {
+	/// The path to the library, which does not need to be an existing library
+	pub path: String,
+	/// The pattern of the library
+	pub library_pattern: LibraryPattern,
+	/// The series detection strategy to preview, for collection-based libraries
+	#[serde(default)]
+	pub series_detection: Option<SeriesDetection>,
+	/// The ignore rules to apply while walking the library
+	#[serde(default)]
+	pub ignore_rules: IgnoreRules,
+}
+
+#[derive(Serialize, Debug, Type, ToSchema)]
+pub struct SeriesPreview {
+	/// The path to the series directory
+	pub path: String,
+	/// The name of the series, derived from its directory
+	pub name: String,
+	/// The number of books which would belong to the series
+	pub book_count: u64,
+	/// Whether the series already exists in the database
+	pub exists: bool,
+}
+
+#[derive(Serialize, Debug, Type, ToSchema)]
+pub struct LibrarySeriesPreview {
+	/// The series the library would be split into
+	pub series: Vec<SeriesPreview>,
+	/// The paths of existing series which would no longer be series. Their books would be
+	/// moved to the series they now belong to by the next scan.
+	pub orphaned_series: Vec<String>,
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/libraries/series-preview",
+	tag = "library",
+	request_body = PreviewLibrarySeries,
+	responses(
+		(status = 200, description = "Successfully previewed library series", body = LibrarySeriesPreview),
+		(status = 400, description = "Bad request"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Preview how a directory would be split into series with the given pattern and series
+/// detection strategy, without writing anything to the database. This may be used both
+/// before creating a library and before changing the strategy of an existing one.
+async fn preview_library_series(
+	Extension(req): Extension<RequestContext>,
+	State(ctx): State<AppState>,
+	Json(input): Json<PreviewLibrarySeries>,
+) -> APIResult<Json<LibrarySeriesPreview>> {
+	req.enforce_permissions(&[UserPermission::EditLibrary])?;
+
+	if !path::Path::new(&input.path).is_dir() {
+		return Err(APIError::BadRequest(format!(
+			"The library directory does not exist: {}",
+			input.path
+		)));
+	}
+
+	let resolver = SeriesResolver::new(
+		input.path.as_str(),
+		&input.library_pattern,
+		input.series_detection.as_ref(),
+	)?;
+	let ignore_rules = input.ignore_rules.build()?;
+	let library_path = path::PathBuf::from(&input.path);
+	let detected = tokio::task::spawn_blocking(move || {
+		detect_series(&library_path, &ignore_rules, &resolver)
+	})
+	.await
+	.map_err(|e| APIError::InternalServerError(e.to_string()))?;
+
+	// A separator is appended so that e.g. the series of `/comics-old` aren't listed for `/comics`
+	let library_dir = input.path.trim_end_matches(path::MAIN_SEPARATOR);
+	let existing_paths = ctx
+		.db
+		.series()
+		.find_many(vec![
+			or![
+				series::path::equals(library_dir.to_string()),
+				series::path::starts_with(format!(
+					"{library_dir}{}",
+					path::MAIN_SEPARATOR
+				)),
+			],
+			series::status::not(FileStatus::Missing.to_string()),
+		])
+		.select(series::select!({ path }))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|series| series.path)
+		.collect::<std::collections::HashSet<_>>();
+
+	let series = detected
+		.series
+		.iter()
+		.map(|(path, book_count)| {
+			let path_str = path.to_string_lossy().to_string();
+			SeriesPreview {
+				name: path
+					.file_name()
+					.map(|name| name.to_string_lossy().to_string())
+					.unwrap_or_else(|| path_str.clone()),
+				exists: existing_paths.contains(&path_str),
+				book_count: *book_count,
+				path: path_str,
+			}
+		})
+		.collect::<Vec<_>>();
+	let mut orphaned_series = existing_paths
+		.into_iter()
+		.filter(|path| !detected.series.contains_key(path::Path::new(path)))
+		.collect::<Vec<_>>();
+	orphaned_series.sort();
+
+	Ok(Json(LibrarySeriesPreview {
+		series,
+		orphaned_series,
+	}))
+}
+
 #[derive(Deserialize, Debug, Type, ToSchema)]
 pub struct UpdateLibrary {
 	/// The updated name of the library.
@@ -1599,8 +1743,18 @@ async fn update_library(
 		.await?
 		.ok_or(APIError::NotFound("Library not found".to_string()))?;
 	let existing_tags = existing_library.tags;
+	let previous_path = existing_library.path;
 
+	if let Some(series_detection) = input.config.series_detection.as_ref() {
+		series_detection.validate()?;
+	}
+	if let Some(pdf_render_config) = input.config.pdf_render_config.as_ref() {
+		pdf_render_config.validate()?;
+	}
+	input.config.filename_rules.validate()?;
+	input.config.validate_poll_interval()?;
 	let watch = input.config.watch;
+	let watcher_backend = input.config.watcher_backend();
 	let path = input.path.clone();
 	let update_result: Result<Library, APIError> = db
 		._transaction()
@@ -1610,10 +1764,23 @@ async fn update_library(
 			let ignore_rules = (!library_config.ignore_rules.is_empty())
 				.then(|| library_config.ignore_rules.as_bytes())
 				.transpose()?;
+			let filename_rules = (!library_config.filename_rules.is_empty())
+				.then(|| library_config.filename_rules.as_bytes())
+				.transpose()?;
 			let thumbnail_config = library_config
 				.thumbnail_config
 				.map(|options| options.as_bytes())
 				.transpose()?;
+			let series_detection = library_config
+				.series_detection
+				.as_ref()
+				.map(SeriesDetection::as_bytes)
+				.transpose()?;
+			let pdf_render_config = library_config
+				.pdf_render_config
+				.as_ref()
+				.map(PdfRenderOptions::as_bytes)
+				.transpose()?;
 
 			client
 				.library_config()
@@ -1645,7 +1812,14 @@ async fn update_library(
 							library_config.generate_koreader_hashes,
 						),
 						library_config::ignore_rules::set(ignore_rules),
+						library_config::filename_rules::set(filename_rules),
+						library_config::series_detection::set(series_detection),
+						library_config::pdf_render_config::set(pdf_render_config),
 						library_config::watch::set(library_config.watch),
+						library_config::watcher_mode::set(
+							library_config.watcher_mode.to_string(),
+						),
+						library_config::poll_interval::set(library_config.poll_interval),
 						library_config::thumbnail_config::set(thumbnail_config),
 					],
 				)
@@ -1765,17 +1939,11 @@ async fn update_library(
 		})?;
 	}
 
-	if watch {
+	// The watcher of the previous path is dropped, while one for the same path is kept so
+	// that a polled library doesn't lose its snapshot with every update
+	if previous_path != path || !watch {
 		ctx.library_watcher
-			.add_watcher(path.into())
-			.await
-			.map_err(|e| {
-				error!(?e, "Failed to add library watcher");
-				APIError::InternalServerError("Failed to add library watcher".to_string())
-			})?;
-	} else {
-		ctx.library_watcher
-			.remove_watcher(path.into())
+			.remove_watcher(previous_path.into())
 			.await
 			.map_err(|e| {
 				error!(?e, "Failed to remove library watcher");
@@ -1785,6 +1953,16 @@ async fn update_library(
 			})?;
 	}
 
+	if watch {
+		ctx.library_watcher
+			.add_watcher(path.into(), watcher_backend)
+			.await
+			.map_err(|e| {
+				error!(?e, "Failed to add library watcher");
+				APIError::InternalServerError("Failed to add library watcher".to_string())
+			})?;
+	}
+
 	Ok(Json(updated_library))
 }
 
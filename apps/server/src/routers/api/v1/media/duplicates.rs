@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+
+use axum::{
+	extract::{Path, State},
+	Extension, Json,
+};
+use axum_extra::extract::Query;
+use prisma_client_rust::{chrono::Utc, QueryError};
+use serde::Deserialize;
+use specta::Type;
+use stump_core::{
+	db::{
+		entity::{DuplicateGroup, Media, User, UserPermission},
+		FileStatus,
+	},
+	filesystem::media::duplicate_analysis_job::DuplicateAnalysisJob,
+	prisma::{
+		active_reading_session, duplicate_group, duplicate_group_media,
+		excluded_media_file, finished_reading_session, media, series, PrismaClient,
+	},
+};
+use tokio::fs;
+use utoipa::ToSchema;
+
+use crate::{
+	config::state::AppState,
+	errors::{APIError, APIResult},
+	middleware::auth::RequestContext,
+	routers::api::filters::library_not_hidden_from_user_filter,
+};
+
+/// The conditions for the media in a duplicate group which the user is allowed to see
+fn visible_duplicate_media(user: &User) -> Vec<media::WhereParam> {
+	vec![
+		media::deleted_at::equals(None),
+		media::series::is(vec![series::library::is(vec![
+			library_not_hidden_from_user_filter(user),
+		])]),
+	]
+}
+
+/// Fetch a duplicate group with only the media the user is allowed to see
+async fn get_duplicate_group_for_user(
+	ctx: &AppState,
+	id: String,
+	user: &User,
+) -> APIResult<duplicate_group::Data> {
+	ctx.db
+		.duplicate_group()
+		.find_unique(duplicate_group::id::equals(id))
+		.with(
+			duplicate_group::media::fetch(vec![duplicate_group_media::media::is(
+				visible_duplicate_media(user),
+			)])
+			.with(duplicate_group_media::media::fetch().with(media::metadata::fetch())),
+		)
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound("Duplicate group not found".to_string()))
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/media/duplicates/analyze",
+	tag = "media",
+	responses(
+		(status = 200, description = "Successfully started duplicate analysis"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Start a job which finds likely duplicate media, comparing file hashes, the first page of
+/// each book and their metadata. The result replaces the current duplicate report.
+pub(crate) async fn start_duplicate_analysis(
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<()>> {
+	req.enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	ctx.enqueue_job(DuplicateAnalysisJob::new()).map_err(|e| {
+		tracing::error!(?e, "Failed to enqueue duplicate analysis job");
+		APIError::InternalServerError(
+			"Failed to enqueue duplicate analysis job".to_string(),
+		)
+	})?;
+
+	Ok(Json(()))
+}
+
+#[derive(Debug, Deserialize, ToSchema, Type)]
+pub struct DuplicateGroupsParams {
+	/// Whether to include groups which were dismissed as not being duplicates
+	#[serde(default)]
+	include_dismissed: bool,
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/media/duplicates/groups",
+	tag = "media",
+	params(
+		("include_dismissed" = Option<bool>, Query, description = "Whether to include dismissed groups"),
+	),
+	responses(
+		(status = 200, description = "Successfully fetched duplicate groups", body = [DuplicateGroup]),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Get the report of duplicate groups from the last duplicate analysis. Groups which have
+/// fewer than two media left which the user can see (e.g. after the rest were trashed) are
+/// not included.
+pub(crate) async fn get_duplicate_groups(
+	Query(params): Query<DuplicateGroupsParams>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<Vec<DuplicateGroup>>> {
+	let user = req.user_and_enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	let where_params = if params.include_dismissed {
+		vec![]
+	} else {
+		vec![duplicate_group::dismissed_at::equals(None)]
+	};
+	let groups = ctx
+		.db
+		.duplicate_group()
+		.find_many(where_params)
+		.with(
+			duplicate_group::media::fetch(vec![duplicate_group_media::media::is(
+				visible_duplicate_media(&user),
+			)])
+			.with(duplicate_group_media::media::fetch().with(media::metadata::fetch())),
+		)
+		.exec()
+		.await?
+		.into_iter()
+		.map(DuplicateGroup::from)
+		.filter(|group| group.media.len() > 1)
+		.collect();
+
+	Ok(Json(groups))
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/media/duplicates/groups/{id}/dismiss",
+	tag = "media",
+	params(
+		("id" = String, Path, description = "The ID of the duplicate group"),
+	),
+	responses(
+		(status = 200, description = "Successfully dismissed duplicate group", body = DuplicateGroup),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Duplicate group not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Dismiss a duplicate group, marking its media as not being duplicates. The group will not
+/// be created again by future duplicate analyses.
+pub(crate) async fn dismiss_duplicate_group(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<DuplicateGroup>> {
+	let user = req.user_and_enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	let group = get_duplicate_group_for_user(&ctx, id, &user).await?;
+	let dismissed_group = ctx
+		.db
+		.duplicate_group()
+		.update(
+			duplicate_group::id::equals(group.id.clone()),
+			vec![duplicate_group::dismissed_at::set(Some(Utc::now().into()))],
+		)
+		.exec()
+		.await?;
+
+	Ok(Json(DuplicateGroup {
+		dismissed_at: dismissed_group.dismissed_at.map(|dt| dt.to_rfc3339()),
+		..DuplicateGroup::from(group)
+	}))
+}
+
+#[derive(Debug, Deserialize, ToSchema, Type)]
+pub struct ResolveDuplicateGroup {
+	/// The ID of the media to keep. Reading progress from the other media in the group is
+	/// merged into it, and the other media are moved to the trash.
+	keep_media_id: String,
+	/// Whether to also delete the files of the other media from disk. Otherwise, the files are
+	/// kept but excluded from future scans, so they aren't imported again once the trashed media
+	/// are purged. Restoring a trashed media lifts its exclusion. Requires the `library:delete`
+	/// permission.
+	#[serde(default)]
+	delete_files: bool,
+}
+
+impl ResolveDuplicateGroup {
+	/// The permissions needed to resolve a duplicate group. Deleting files from disk can't
+	/// be undone by restoring them from the trash, so it also requires the permission to
+	/// delete libraries.
+	fn required_permissions(&self) -> Vec<UserPermission> {
+		if self.delete_files {
+			vec![UserPermission::ManageLibrary, UserPermission::DeleteLibrary]
+		} else {
+			vec![UserPermission::ManageLibrary]
+		}
+	}
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/media/duplicates/groups/{id}/resolve",
+	tag = "media",
+	request_body = ResolveDuplicateGroup,
+	params(
+		("id" = String, Path, description = "The ID of the duplicate group"),
+	),
+	responses(
+		(status = 200, description = "Successfully resolved duplicate group", body = Media),
+		(status = 400, description = "Bad request"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Duplicate group not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Resolve a duplicate group by keeping one of its media. For each user, the most recent
+/// in-progress reading session across the group is moved to the kept media, along with all
+/// completed reading sessions. The other media are then moved to the trash, and their files
+/// are deleted once the changes are saved if requested.
+pub(crate) async fn resolve_duplicate_group(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(input): Json<ResolveDuplicateGroup>,
+) -> APIResult<Json<Media>> {
+	let user = req.user_and_enforce_permissions(&input.required_permissions())?;
+
+	let group = get_duplicate_group_for_user(&ctx, id, &user).await?;
+	let group_media = group
+		.media
+		.clone()
+		.unwrap_or_default()
+		.into_iter()
+		.filter_map(|item| item.media.map(|media| *media))
+		.collect::<Vec<_>>();
+	if !group_media
+		.iter()
+		.any(|media| media.id == input.keep_media_id)
+	{
+		return Err(APIError::BadRequest(
+			"The media to keep must be in the duplicate group".to_string(),
+		));
+	}
+
+	let keep_id = input.keep_media_id.clone();
+	let (trashed_media, kept_media): (Vec<_>, Vec<_>) = group_media
+		.into_iter()
+		.partition(|media| media.id != input.keep_media_id);
+	let trashed_ids = trashed_media
+		.iter()
+		.map(|media| media.id.clone())
+		.collect::<Vec<_>>();
+
+	let excluded_paths = trashed_media
+		.iter()
+		.map(|media| media.path.clone())
+		.collect::<Vec<_>>();
+
+	let group_id = group.id.clone();
+	let delete_files = input.delete_files;
+	ctx.db
+		._transaction()
+		.run(|client| async move {
+			let group_ids = [keep_id.clone()]
+				.into_iter()
+				.chain(trashed_ids.clone())
+				.collect::<Vec<_>>();
+
+			// Only the most recent in-progress session of each user is kept
+			let active_sessions = client
+				.active_reading_session()
+				.find_many(vec![active_reading_session::media_id::in_vec(group_ids)])
+				.exec()
+				.await?;
+			let mut latest_by_user =
+				HashMap::<String, active_reading_session::Data>::new();
+			for session in active_sessions {
+				match latest_by_user.get(&session.user_id) {
+					Some(latest) if latest.updated_at >= session.updated_at => {},
+					_ => {
+						latest_by_user.insert(session.user_id.clone(), session);
+					},
+				}
+			}
+			for session in latest_by_user.into_values() {
+				if session.media_id == keep_id {
+					continue;
+				}
+				client
+					.active_reading_session()
+					.delete_many(vec![
+						active_reading_session::user_id::equals(session.user_id.clone()),
+						active_reading_session::media_id::equals(keep_id.clone()),
+					])
+					.exec()
+					.await?;
+				client
+					.active_reading_session()
+					.update(
+						active_reading_session::id::equals(session.id),
+						vec![active_reading_session::media::connect(media::id::equals(
+							keep_id.clone(),
+						))],
+					)
+					.exec()
+					.await?;
+			}
+			client
+				.active_reading_session()
+				.delete_many(vec![active_reading_session::media_id::in_vec(
+					trashed_ids.clone(),
+				)])
+				.exec()
+				.await?;
+
+			client
+				.finished_reading_session()
+				.update_many(
+					vec![finished_reading_session::media_id::in_vec(
+						trashed_ids.clone(),
+					)],
+					vec![finished_reading_session::media_id::set(keep_id.clone())],
+				)
+				.exec()
+				.await?;
+
+			client
+				.media()
+				.update_many(
+					vec![media::id::in_vec(trashed_ids)],
+					vec![media::deleted_at::set(Some(Utc::now().into()))],
+				)
+				.exec()
+				.await?;
+			if !delete_files {
+				exclude_media_files(&client, excluded_paths).await?;
+			}
+
+			client
+				.duplicate_group()
+				.delete(duplicate_group::id::equals(group_id))
+				.exec()
+				.await?;
+
+			Ok::<_, QueryError>(())
+		})
+		.await?;
+
+	// The files are only deleted once the group is resolved, so a failure to save it never
+	// leaves the library without any copy of the book
+	if delete_files {
+		delete_trashed_files(&ctx.db, trashed_media).await?;
+	}
+
+	let kept_media = kept_media.into_iter().next().map(Media::from).ok_or(
+		APIError::InternalServerError("Failed to find the kept media".to_string()),
+	)?;
+
+	Ok(Json(kept_media))
+}
+
+/// Record files which should be skipped by future scans
+async fn exclude_media_files(
+	client: &PrismaClient,
+	paths: Vec<String>,
+) -> Result<(), QueryError> {
+	for path in paths {
+		client
+			.excluded_media_file()
+			.upsert(
+				excluded_media_file::path::equals(path.clone()),
+				excluded_media_file::create(path, vec![]),
+				vec![],
+			)
+			.exec()
+			.await?;
+	}
+	Ok(())
+}
+
+/// Delete the files of trashed duplicates from disk, marking their media as missing. A file
+/// which can't be deleted is excluded from future scans instead, as if it was kept.
+async fn delete_trashed_files(
+	client: &PrismaClient,
+	trashed_media: Vec<media::Data>,
+) -> APIResult<()> {
+	let mut deleted_ids = vec![];
+	let mut kept_paths = vec![];
+	for media in trashed_media {
+		match fs::remove_file(&media.path).await {
+			Ok(()) => deleted_ids.push(media.id),
+			Err(error) => {
+				tracing::error!(?error, path = %media.path, "Failed to delete duplicate file");
+				kept_paths.push(media.path);
+			},
+		}
+	}
+
+	client
+		.media()
+		.update_many(
+			vec![media::id::in_vec(deleted_ids)],
+			vec![media::status::set(FileStatus::Missing.to_string())],
+		)
+		.exec()
+		.await?;
+	exclude_media_files(client, kept_paths).await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use stump_core::Ctx;
+
+	use super::*;
+
+	#[test]
+	fn test_required_permissions() {
+		let input = ResolveDuplicateGroup {
+			keep_media_id: "keep".to_string(),
+			delete_files: false,
+		};
+		assert_eq!(
+			input.required_permissions(),
+			vec![UserPermission::ManageLibrary]
+		);
+
+		let input = ResolveDuplicateGroup {
+			delete_files: true,
+			..input
+		};
+		assert_eq!(
+			input.required_permissions(),
+			vec![UserPermission::ManageLibrary, UserPermission::DeleteLibrary]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_deleting_files_requires_delete_permission() {
+		let (ctx, _mock_store) = Ctx::mock();
+		let user = User {
+			permissions: vec![UserPermission::EditLibrary, UserPermission::ScanLibrary],
+			..Default::default()
+		};
+
+		let result = resolve_duplicate_group(
+			Path("group".to_string()),
+			State(Arc::new(ctx)),
+			Extension(RequestContext::for_user(user)),
+			Json(ResolveDuplicateGroup {
+				keep_media_id: "keep".to_string(),
+				delete_files: true,
+			}),
+		)
+		.await;
+
+		assert!(matches!(result, Err(APIError::Forbidden(_))));
+	}
+}
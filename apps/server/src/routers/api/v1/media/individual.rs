@@ -4,7 +4,10 @@ use axum::{
 	extract::{Path, Query, State},
 	Extension, Json,
 };
-use prisma_client_rust::{chrono::Duration, Direction};
+use prisma_client_rust::{
+	chrono::{DateTime, Duration, FixedOffset, Utc},
+	Direction,
+};
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use specta::Type;
@@ -12,15 +15,17 @@ use stump_core::{
 	db::entity::{
 		macros::{
 			finished_reading_session_with_book_pages, media_id_select,
-			reading_session_with_book_pages,
+			media_reading_preferences, reading_session_with_book_pages,
 		},
 		ActiveReadingSession, FinishedReadingSession, Media, MediaMetadata,
-		PageDimension, PageDimensionsEntity, ProgressUpdateReturn, User, UserPermission,
+		PageDimension, PageDimensionsEntity, ProgressUpdateReturn, ReadingOverrides,
+		ReadingPreferences, ReadingTime, User, UserPermission,
 	},
 	filesystem::{
 		analyze_media_job::AnalyzeMediaJob,
-		get_page_async,
+		get_page_with_options_async,
 		image::{resize_image, ScaledDimensionResize},
+		ContentType, PageOptions, PdfOutlineItem, PdfPageText, PdfProcessor,
 	},
 	prisma::{
 		active_reading_session, finished_reading_session, library,
@@ -369,7 +374,10 @@ pub(crate) async fn get_media_page(
 			"Page {page} is out of bounds for media {id}"
 		)))
 	} else {
-		let (content_type, buf) = get_page_async(&media.path, page, &ctx.config).await?;
+		let page_options = PageOptions::for_book(&ctx.db, &id, &media.path).await?;
+		let (content_type, buf) =
+			get_page_with_options_async(&media.path, page, page_options, &ctx.config)
+				.await?;
 		let scaled_buf = match requested_scale.to_scaled_dimension() {
 			Some(dimension) => resize_image(buf, dimension).await?,
 			_ => buf,
@@ -422,6 +430,32 @@ pub(crate) async fn update_media_progress(
 	// TODO: check library access? They don't gain access to the book here, so perhaps
 	// it is acceptable to not check library access here?
 
+	let now: DateTime<FixedOffset> = Utc::now().into();
+	let existing_session = client
+		.active_reading_session()
+		.find_unique(active_reading_session::user_id_media_id(
+			user_id.clone(),
+			id.clone(),
+		))
+		.exec()
+		.await?;
+	// A progress update counts as reading activity, so time is accrued the same way a
+	// heartbeat would. Any client-reported total is only used to fill in gaps.
+	let reading_time = ReadingTime::from(existing_session.as_ref())
+		.heartbeat_with_reported(
+			elapsed_seconds,
+			existing_session.as_ref().map_or(now, |s| s.started_at),
+			now,
+		);
+
+	let set_params = chain_optional_iter(
+		[active_reading_session::page::set(Some(page))]
+			.into_iter()
+			.chain(reading_time.into_active_set_params())
+			.collect::<Vec<_>>(),
+		[epubcfi.map(|cfi| active_reading_session::epubcfi::set(Some(cfi)))],
+	);
+
 	let active_session = client
 		.active_reading_session()
 		.upsert(
@@ -429,26 +463,9 @@ pub(crate) async fn update_media_progress(
 			(
 				media::id::equals(id.clone()),
 				user::id::equals(user_id.clone()),
-				chain_optional_iter(
-					[active_reading_session::page::set(Some(page))],
-					[
-						epubcfi
-							.clone()
-							.map(|cfi| active_reading_session::epubcfi::set(Some(cfi))),
-						elapsed_seconds.map(|es| {
-							active_reading_session::elapsed_seconds::set(Some(es))
-						}),
-					],
-				),
-			),
-			chain_optional_iter(
-				[active_reading_session::page::set(Some(page))],
-				[
-					epubcfi.map(|cfi| active_reading_session::epubcfi::set(Some(cfi))),
-					elapsed_seconds
-						.map(|es| active_reading_session::elapsed_seconds::set(Some(es))),
-				],
+				set_params.clone(),
 			),
+			set_params,
 		)
 		.include(reading_session_with_book_pages::include())
 		.exec()
@@ -478,12 +495,7 @@ pub(crate) async fn update_media_progress(
 						deleted_session.map(|s| s.started_at).unwrap_or_default(),
 						media::id::equals(id.clone()),
 						user::id::equals(user_id.clone()),
-						chain_optional_iter(
-							[],
-							[elapsed_seconds.map(|es| {
-								finished_reading_session::elapsed_seconds::set(Some(es))
-							})],
-						),
+						reading_time.into_finished_set_params(),
 					)
 					.exec()
 					.await
@@ -500,6 +512,96 @@ pub(crate) async fn update_media_progress(
 	}
 }
 
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, ToSchema, specta::Type)]
+pub struct PutMediaProgressHeartbeat {
+	/// The number of seconds the client observed the user reading since its last heartbeat.
+	/// If omitted, the time since the last heartbeat recorded by the server is used instead.
+	#[specta(optional)]
+	pub delta_seconds: Option<i64>,
+}
+
+#[utoipa::path(
+	put,
+	path = "/api/v1/media/{id}/progress/heartbeat",
+	tag = "media",
+	params(
+		("id" = String, Path, description = "The ID of the media being read"),
+	),
+	request_body = PutMediaProgressHeartbeat,
+	responses(
+		(status = 200, description = "Successfully recorded reading activity", body = ActiveReadingSession),
+		(status = 400, description = "Implausible reading time delta"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Media not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Record reading activity for a media, accumulating the time spent reading on the active
+/// session. Clients should send a heartbeat periodically while a book is open, since gaps longer
+/// than a few minutes are treated as idle time and not counted.
+pub(crate) async fn put_media_progress_heartbeat(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(PutMediaProgressHeartbeat { delta_seconds }): Json<PutMediaProgressHeartbeat>,
+) -> APIResult<Json<ActiveReadingSession>> {
+	let client = &ctx.db;
+	let user = req.user();
+	let user_id = user.id.clone();
+	let age_restrictions = user
+		.age_restriction
+		.as_ref()
+		.map(|ar| apply_media_age_restriction(ar.age, ar.restrict_on_unset));
+	let media_where_params = chain_optional_iter(
+		[media::id::equals(id.clone())]
+			.into_iter()
+			.chain(apply_media_library_not_hidden_for_user_filter(user))
+			.collect::<Vec<WhereParam>>(),
+		[age_restrictions],
+	);
+
+	client
+		.media()
+		.find_first(media_where_params)
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound(String::from("Media not found")))?;
+
+	let now: DateTime<FixedOffset> = Utc::now().into();
+	let existing_session = client
+		.active_reading_session()
+		.find_unique(active_reading_session::user_id_media_id(
+			user_id.clone(),
+			id.clone(),
+		))
+		.exec()
+		.await?;
+
+	let reading_time = ReadingTime::from(existing_session.as_ref());
+	let reading_time = match delta_seconds {
+		Some(delta) => reading_time.heartbeat_with_delta(delta, now)?,
+		None => reading_time.heartbeat(now),
+	};
+
+	let set_params = reading_time.into_active_set_params();
+	let active_session = client
+		.active_reading_session()
+		.upsert(
+			active_reading_session::user_id_media_id(user_id.clone(), id.clone()),
+			(
+				media::id::equals(id),
+				user::id::equals(user_id),
+				set_params.clone(),
+			),
+			set_params,
+		)
+		.exec()
+		.await?;
+
+	Ok(Json(ActiveReadingSession::from(active_session)))
+}
+
 #[utoipa::path(
 	get,
 	path = "/api/v1/media/{id}/progress",
@@ -696,12 +798,15 @@ pub(crate) async fn put_media_complete_status(
 					.ok();
 				tracing::trace!(?deleted_session, "Deleted active reading session");
 
+				let reading_time = ReadingTime::from(deleted_session.as_ref())
+					.heartbeat(Utc::now().into());
+
 				tx.finished_reading_session()
 					.create(
 						deleted_session.map(|s| s.started_at).unwrap_or_default(),
 						media::id::equals(id.clone()),
 						user::id::equals(user_id.clone()),
-						vec![],
+						reading_time.into_finished_set_params(),
 					)
 					.exec()
 					.await
@@ -886,6 +991,113 @@ async fn fetch_media_page_dimensions_with_permissions(
 	Ok(dimensions_entity)
 }
 
+#[utoipa::path(
+	get,
+	path = "/api/v1/media/{id}/outline",
+	tag = "media",
+	params(
+		("id" = String, Path, description = "The ID of the PDF to get the outline of")
+	),
+	responses(
+		(status = 200, description = "Successfully fetched PDF outline", body = [PdfOutlineItem]),
+		(status = 400, description = "Media is not a PDF"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Media not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Get the outline (bookmarks) of a PDF, which serves as its table of contents
+pub(crate) async fn get_media_outline(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<Vec<PdfOutlineItem>>> {
+	let media = fetch_pdf_with_permissions(&ctx, req.user(), id).await?;
+
+	let config = ctx.config.clone();
+	let outline = tokio::task::spawn_blocking(move || {
+		PdfProcessor::get_outline(&media.path, &config)
+	})
+	.await
+	.map_err(|e| APIError::InternalServerError(e.to_string()))??;
+
+	Ok(Json(outline))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/media/{id}/page/{page}/text",
+	tag = "media",
+	params(
+		("id" = String, Path, description = "The ID of the PDF to get the text of"),
+		("page" = i32, Path, description = "The page to get the text of (indexed from 1)")
+	),
+	responses(
+		(status = 200, description = "Successfully fetched PDF page text", body = PdfPageText),
+		(status = 400, description = "Media is not a PDF, or the page is out of bounds"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Media not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Get the text of a page of a PDF, e.g. for search or screen readers. Pages which are scanned
+/// images have no text.
+pub(crate) async fn get_media_page_text(
+	Path((id, page)): Path<(String, i32)>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<PdfPageText>> {
+	let media = fetch_pdf_with_permissions(&ctx, req.user(), id.clone()).await?;
+	if page <= 0 || page > media.pages {
+		return Err(APIError::BadRequest(format!(
+			"Page {page} is out of bounds for media {id}"
+		)));
+	}
+
+	let config = ctx.config.clone();
+	let text = tokio::task::spawn_blocking(move || {
+		PdfProcessor::get_page_text(&media.path, page, &config)
+	})
+	.await
+	.map_err(|e| APIError::InternalServerError(e.to_string()))??;
+
+	Ok(Json(text))
+}
+
+async fn fetch_pdf_with_permissions(
+	ctx: &Arc<Ctx>,
+	user: &User,
+	id: String,
+) -> APIResult<media::Data> {
+	let age_restrictions = user
+		.age_restriction
+		.as_ref()
+		.map(|ar| apply_media_age_restriction(ar.age, ar.restrict_on_unset));
+	let where_params = chain_optional_iter(
+		[media::id::equals(id.clone())]
+			.into_iter()
+			.chain(apply_media_library_not_hidden_for_user_filter(user))
+			.collect::<Vec<WhereParam>>(),
+		[age_restrictions],
+	);
+
+	let media = ctx
+		.db
+		.media()
+		.find_first(where_params)
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound(String::from("Media not found")))?;
+
+	if ContentType::from_file(&media.path) != ContentType::PDF {
+		return Err(APIError::BadRequest(format!("Media {id} is not a PDF")));
+	}
+
+	Ok(media)
+}
+
 #[utoipa::path(
 	get,
 	path = "/api/v1/media/{id}/metadata",
@@ -997,3 +1209,119 @@ pub(crate) async fn put_media_metadata(
 
 	Ok(Json(MediaMetadata::from(meta)))
 }
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/media/{id}/reading-preferences",
+	tag = "media",
+	params(
+		("id" = String, Path, description = "The ID of the media to get reading preferences for")
+	),
+	responses(
+		(status = 200, description = "Successfully fetched media reading preferences", body = ReadingPreferences),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Media not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Get the reading direction and mode a media record should be opened with. The overrides of
+/// the media and its series take priority, followed by its metadata (e.g. a ComicInfo `Manga`
+/// field of `YesAndRightToLeft`) and then the defaults of its library.
+pub(crate) async fn get_media_reading_preferences(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<ReadingPreferences>> {
+	let db = &ctx.db;
+	let user = req.user();
+	let age_restrictions = user
+		.age_restriction
+		.as_ref()
+		.map(|ar| apply_media_age_restriction(ar.age, ar.restrict_on_unset));
+	let where_params = chain_optional_iter(
+		[media::id::equals(id.clone())]
+			.into_iter()
+			.chain(apply_media_library_not_hidden_for_user_filter(user))
+			.collect::<Vec<WhereParam>>(),
+		[age_restrictions],
+	);
+
+	let preferences = db
+		.media()
+		.find_first(where_params)
+		.select(media_reading_preferences::select())
+		.exec()
+		.await?
+		.map(ReadingPreferences::from)
+		.ok_or(APIError::NotFound(String::from("Media not found")))?;
+
+	Ok(Json(preferences))
+}
+
+#[utoipa::path(
+	put,
+	path = "/api/v1/media/{id}/reading-overrides",
+	tag = "media",
+	params(
+		("id" = String, Path, description = "The ID of the media to update reading overrides for")
+	),
+	request_body = ReadingOverrides,
+	responses(
+		(status = 200, description = "Successfully updated media reading overrides", body = Media),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Media not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Update the reading direction and mode overrides for a media record. This is a full update,
+/// so an override which is not set is removed.
+pub(crate) async fn put_media_reading_overrides(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(overrides): Json<ReadingOverrides>,
+) -> APIResult<Json<Media>> {
+	req.enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	let db = &ctx.db;
+	let user = req.user();
+	let age_restrictions = user
+		.age_restriction
+		.as_ref()
+		.map(|ar| apply_media_age_restriction(ar.age, ar.restrict_on_unset));
+	let where_params = chain_optional_iter(
+		[media::id::equals(id.clone())]
+			.into_iter()
+			.chain(apply_media_library_not_hidden_for_user_filter(user))
+			.collect::<Vec<WhereParam>>(),
+		[age_restrictions],
+	);
+
+	let book = db
+		.media()
+		.find_first(where_params)
+		.select(media_id_select::select())
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound(String::from("Media not found")))?;
+
+	let updated_book = db
+		.media()
+		.update(
+			media::id::equals(book.id),
+			vec![
+				media::reading_direction::set(
+					overrides.reading_direction.map(|dir| dir.to_string()),
+				),
+				media::reading_mode::set(
+					overrides.reading_mode.map(|mode| mode.to_string()),
+				),
+			],
+		)
+		.exec()
+		.await?;
+
+	Ok(Json(Media::from(updated_book)))
+}
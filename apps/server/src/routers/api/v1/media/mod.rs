@@ -1,6 +1,8 @@
 pub(crate) mod bulk;
+pub(crate) mod duplicates;
 pub(crate) mod individual;
 pub(crate) mod thumbnails;
+pub(crate) mod verification;
 
 use axum::{
 	extract::{DefaultBodyLimit, Extension},
@@ -17,6 +19,30 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 	Router::new()
 		.route("/media", get(bulk::get_media))
 		.route("/media/duplicates", get(bulk::get_duplicate_media))
+		.route(
+			"/media/duplicates/analyze",
+			post(duplicates::start_duplicate_analysis),
+		)
+		.route(
+			"/media/duplicates/groups",
+			get(duplicates::get_duplicate_groups),
+		)
+		.route(
+			"/media/duplicates/groups/{id}/dismiss",
+			post(duplicates::dismiss_duplicate_group),
+		)
+		.route(
+			"/media/duplicates/groups/{id}/resolve",
+			post(duplicates::resolve_duplicate_group),
+		)
+		.route(
+			"/media/verify",
+			post(verification::start_media_verification),
+		)
+		.route(
+			"/media/verify/report",
+			get(verification::get_media_verification_report),
+		)
 		.route("/media/keep-reading", get(bulk::get_in_progress_media))
 		.route("/media/recently-added", get(bulk::get_recently_added_media))
 		.route("/media/path/{path}", get(individual::get_media_by_path))
@@ -43,6 +69,10 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 						.delete(individual::delete_media_progress),
 				)
 				.route("/progress", put(individual::update_media_progress))
+				.route(
+					"/progress/heartbeat",
+					put(individual::put_media_progress_heartbeat),
+				)
 				.route(
 					"/progress/complete",
 					get(individual::get_is_media_completed)
@@ -53,6 +83,16 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 					"/page/{page}/dimensions",
 					get(individual::get_media_page_dimensions),
 				)
+				.route("/page/{page}/text", get(individual::get_media_page_text))
+				.route("/outline", get(individual::get_media_outline))
+				.route(
+					"/reading-preferences",
+					get(individual::get_media_reading_preferences),
+				)
+				.route(
+					"/reading-overrides",
+					put(individual::put_media_reading_overrides),
+				)
 				.route(
 					"/metadata",
 					get(individual::get_media_metadata)
@@ -7,7 +7,7 @@ use stump_core::{
 	config::StumpConfig,
 	db::entity::{macros::media_thumbnail, LibraryConfig, User, UserPermission},
 	filesystem::{
-		get_page_async, get_thumbnail,
+		get_cover_async, get_thumbnail,
 		image::{
 			generate_book_thumbnail, place_thumbnail, remove_thumbnails,
 			GenerateThumbnailOptions, ImageFormat, ImageProcessorOptions,
@@ -94,7 +94,7 @@ pub(crate) async fn get_media_thumbnail(
 	if let Some((content_type, bytes)) = generated_thumb {
 		Ok((content_type, bytes))
 	} else {
-		Ok(get_page_async(path, 1, config).await?)
+		Ok(get_cover_async(path, config).await?)
 	}
 }
 
@@ -0,0 +1,150 @@
+use axum::{extract::State, Extension, Json};
+use axum_extra::extract::Query;
+use prisma_client_rust::Direction;
+use serde::Deserialize;
+use specta::Type;
+use stump_core::{
+	db::{
+		entity::{Media, UserPermission},
+		query::pagination::{Pageable, PageableMedia, Pagination, PaginationQuery},
+		FileStatus,
+	},
+	filesystem::media::verify_media_job::VerifyMediaJob,
+	prisma::{library, media, series},
+};
+use utoipa::ToSchema;
+
+use crate::{
+	config::state::AppState,
+	errors::{APIError, APIResult},
+	middleware::auth::RequestContext,
+	routers::api::filters::apply_media_library_not_hidden_for_user_filter,
+};
+
+#[derive(Debug, Deserialize, ToSchema, Type)]
+pub struct VerifyMediaParams {
+	/// The library to verify the media of. All media are verified when not set
+	#[serde(default)]
+	library_id: Option<String>,
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/media/verify",
+	tag = "media",
+	params(
+		("library_id" = Option<String>, Query, description = "The library to verify the media of"),
+	),
+	responses(
+		(status = 200, description = "Successfully started media verification"),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Library not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Start a job which verifies the integrity of media files, reading every entry of each
+/// archive and checking the structure of EPUBs and PDFs. Media which fail are put in the
+/// `ERROR` status along with the reason.
+pub(crate) async fn start_media_verification(
+	Query(params): Query<VerifyMediaParams>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<()>> {
+	req.enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	if let Some(library_id) = &params.library_id {
+		ctx.db
+			.library()
+			.find_unique(library::id::equals(library_id.clone()))
+			.exec()
+			.await?
+			.ok_or(APIError::NotFound("Library not found".to_string()))?;
+	}
+
+	ctx.enqueue_job(VerifyMediaJob::new(params.library_id))
+		.map_err(|e| {
+			tracing::error!(?e, "Failed to enqueue media verification job");
+			APIError::InternalServerError(
+				"Failed to enqueue media verification job".to_string(),
+			)
+		})?;
+
+	Ok(Json(()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/media/verify/report",
+	tag = "media",
+	params(
+		("library_id" = Option<String>, Query, description = "The library to report on"),
+		("pagination_query" = Option<PaginationQuery>, Query, description = "The pagination options"),
+	),
+	responses(
+		(status = 200, description = "Successfully fetched broken media", body = PageableMedia),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Get the media which failed verification, along with the reason they failed. To page
+/// through unhealthy media alongside other filters, use `/api/v1/media?status=ERROR` instead.
+pub(crate) async fn get_media_verification_report(
+	Query(params): Query<VerifyMediaParams>,
+	pagination_query: Query<PaginationQuery>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<Pageable<Vec<Media>>>> {
+	let user = req.user_and_enforce_permissions(&[UserPermission::ManageLibrary])?;
+	let pagination = pagination_query.0.get();
+	let is_unpaged = pagination.is_unpaged();
+
+	let mut where_params = apply_media_library_not_hidden_for_user_filter(&user);
+	where_params.push(media::status::equals(FileStatus::Error.to_string()));
+	if let Some(library_id) = params.library_id {
+		where_params.push(media::series::is(vec![series::library_id::equals(Some(
+			library_id,
+		))]));
+	}
+
+	let mut query = ctx
+		.db
+		.media()
+		.find_many(where_params.clone())
+		.with(media::metadata::fetch())
+		.order_by(media::path::order(Direction::Asc));
+
+	if !is_unpaged {
+		match pagination.clone() {
+			Pagination::Page(page_query) => {
+				let (skip, take) = page_query.get_skip_take();
+				query = query.skip(skip).take(take);
+			},
+			Pagination::Cursor(cursor_query) => {
+				if let Some(cursor) = cursor_query.cursor {
+					query = query.cursor(media::id::equals(cursor)).skip(1);
+				}
+				if let Some(limit) = cursor_query.limit {
+					query = query.take(limit);
+				}
+			},
+			_ => unreachable!("Pagination should be either page or cursor"),
+		}
+	}
+
+	let broken_media = query
+		.exec()
+		.await?
+		.into_iter()
+		.map(Media::from)
+		.collect::<Vec<_>>();
+
+	if is_unpaged {
+		return Ok(Json(broken_media.into()));
+	}
+
+	let count = ctx.db.media().count(where_params).exec().await?;
+
+	Ok(Json((broken_media, count, pagination).into()))
+}
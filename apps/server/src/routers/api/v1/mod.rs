@@ -20,6 +20,7 @@ use crate::{
 
 pub(crate) mod api_key;
 pub(crate) mod auth;
+pub(crate) mod backup;
 pub(crate) mod book_club;
 pub(crate) mod config;
 pub(crate) mod emailer;
@@ -31,17 +32,23 @@ pub(crate) mod log;
 pub(crate) mod media;
 pub(crate) mod metadata;
 pub(crate) mod notifier;
+pub(crate) mod oidc;
 pub(crate) mod reading_list;
 pub(crate) mod series;
 pub(crate) mod smart_list;
 pub(crate) mod tag;
+pub(crate) mod trash;
+pub(crate) mod two_factor;
 pub(crate) mod upload;
 pub(crate) mod user;
 
 pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 	let mut router = Router::new()
 		.merge(auth::mount(app_state.clone()))
+		.merge(oidc::mount(app_state.clone()))
+		.merge(two_factor::mount(app_state.clone()))
 		.merge(api_key::mount(app_state.clone()))
+		.merge(backup::mount(app_state.clone()))
 		.merge(epub::mount(app_state.clone()))
 		.merge(emailer::mount(app_state.clone()))
 		.merge(library::mount(app_state.clone()))
@@ -53,6 +60,7 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 		.merge(log::mount(app_state.clone()))
 		.merge(series::mount(app_state.clone()))
 		.merge(tag::mount(app_state.clone()))
+		.merge(trash::mount(app_state.clone()))
 		.merge(user::mount(app_state.clone()))
 		.merge(reading_list::mount(app_state.clone()))
 		.merge(smart_list::mount(app_state.clone()))
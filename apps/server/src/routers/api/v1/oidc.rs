@@ -0,0 +1,511 @@
+use axum::{
+	extract::{ConnectInfo, Path, Query, State},
+	http::{header::SET_COOKIE, HeaderMap},
+	middleware,
+	response::{AppendHeaders, IntoResponse, Redirect},
+	routing::{delete, get},
+	Extension, Json, Router,
+};
+use axum_extra::{headers::UserAgent, TypedHeader};
+use chrono::Utc;
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use stump_core::{
+	config::StumpConfig,
+	db::entity::{OidcIdentity, PermissionSet, User},
+	prisma::{oidc_identity, session, user, user_preferences, PrismaClient},
+};
+use tower_sessions::Session;
+use utoipa::ToSchema;
+
+use crate::{
+	config::{
+		oidc::{
+			expired_state_cookie, is_state_bound, resolve_group_permissions,
+			sanitize_redirect, state_cookie, two_factor_redirect, OidcClient,
+			OidcUserInfo, PendingOidcFlow,
+		},
+		session::{mark_session_authenticated, SESSION_USER_KEY},
+		state::AppState,
+		two_factor::PendingTwoFactorLogin,
+	},
+	errors::{api_error_message, APIError, APIResult},
+	http_server::StumpRequestInfo,
+	middleware::auth::{auth_middleware, RequestContext},
+	routers::enforce_max_sessions,
+	utils::{get_session_user, hash_password},
+};
+
+use super::auth::handle_login_attempt;
+
+pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
+	Router::new().nest(
+		"/auth/oidc",
+		Router::new()
+			.route("/config", get(get_oidc_config))
+			.route("/authorize", get(oidc_authorize))
+			.route("/callback", get(oidc_callback))
+			.merge(
+				Router::new()
+					.route("/identities", get(get_oidc_identities))
+					.route("/identities/{id}", delete(delete_oidc_identity))
+					.layer(middleware::from_fn_with_state(app_state, auth_middleware)),
+			),
+	)
+}
+
+#[derive(Debug, Serialize, Type, ToSchema)]
+pub struct OidcConfig {
+	/// Whether OpenID Connect login is enabled and fully configured
+	pub enabled: bool,
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/auth/oidc/config",
+	tag = "auth",
+	responses(
+		(status = 200, description = "Returns whether OpenID Connect login is available.", body = OidcConfig),
+	)
+)]
+/// Returns whether OpenID Connect login is available, so clients know whether to offer it
+async fn get_oidc_config(State(ctx): State<AppState>) -> Json<OidcConfig> {
+	Json(OidcConfig {
+		enabled: ctx.config.is_oidc_configured(),
+	})
+}
+
+#[derive(Debug, Default, Deserialize, Type, ToSchema)]
+pub struct OidcAuthorizeParams {
+	/// A relative path to redirect to after logging in
+	#[serde(default)]
+	pub redirect: Option<String>,
+	/// Whether to link the identity to the currently logged in user rather than logging in
+	#[serde(default)]
+	pub link: bool,
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/auth/oidc/authorize",
+	tag = "auth",
+	params(
+		("params" = OidcAuthorizeParams, Query, description = "The login options")
+	),
+	responses(
+		(status = 303, description = "Redirects to the identity provider."),
+		(status = 401, description = "Linking an identity requires a logged in user."),
+		(status = 501, description = "OpenID Connect is not configured."),
+	)
+)]
+/// Starts an OpenID Connect login by redirecting to the identity provider. When `link` is
+/// set, the resulting identity is linked to the currently logged in user instead. The request
+/// is bound to the browser with a cookie, which the callback requires.
+async fn oidc_authorize(
+	session: Session,
+	State(ctx): State<AppState>,
+	Query(params): Query<OidcAuthorizeParams>,
+) -> APIResult<impl IntoResponse> {
+	let link_user_id = if params.link {
+		let user = get_session_user(&session)
+			.await?
+			.ok_or(APIError::Unauthorized)?;
+		Some(user.id)
+	} else {
+		None
+	};
+
+	let client = OidcClient::discover(&ctx.config).await?;
+	let flow = PendingOidcFlow::new(sanitize_redirect(params.redirect), link_user_id);
+	let state = flow.clone().store();
+	let authorization_url = client.authorization_url(&state, &flow)?;
+
+	Ok((
+		AppendHeaders([(SET_COOKIE, state_cookie(&state).to_string())]),
+		Redirect::to(&authorization_url),
+	))
+}
+
+#[derive(Debug, Deserialize, Type, ToSchema)]
+pub struct OidcCallbackParams {
+	pub code: Option<String>,
+	pub state: String,
+	pub error: Option<String>,
+	pub error_description: Option<String>,
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/auth/oidc/callback",
+	tag = "auth",
+	params(
+		("params" = OidcCallbackParams, Query, description = "The authorization response")
+	),
+	responses(
+		(status = 303, description = "Logs the user in and redirects into the app, or to the two-factor step when the user has it enabled."),
+		(status = 400, description = "The login request is invalid, has expired, or was started by another browser."),
+		(status = 401, description = "The identity provider did not authenticate the user."),
+		(status = 403, description = "No account is linked to the identity."),
+	)
+)]
+/// The endpoint the identity provider redirects back to. The identity is resolved to a Stump
+/// user (linking or provisioning one as configured), and a session is created for them. Users
+/// with two-factor authentication enabled must enter a code before the session is created.
+async fn oidc_callback(
+	TypedHeader(user_agent): TypedHeader<UserAgent>,
+	ConnectInfo(request_info): ConnectInfo<StumpRequestInfo>,
+	session: Session,
+	headers: HeaderMap,
+	State(ctx): State<AppState>,
+	Query(params): Query<OidcCallbackParams>,
+) -> APIResult<impl IntoResponse> {
+	let invalid_request = || {
+		APIError::BadRequest("The login request is invalid or has expired".to_string())
+	};
+
+	// The flow must have been started by this browser, otherwise anyone could complete a
+	// login (or an identity link) on someone else's behalf by handing them a callback URL
+	if !is_state_bound(&headers, &params.state) {
+		tracing::warn!(
+			"OIDC callback is not bound to the browser which started the login"
+		);
+		return Err(invalid_request());
+	}
+	let flow = PendingOidcFlow::take(&params.state).ok_or_else(invalid_request)?;
+	let clear_state_cookie =
+		AppendHeaders([(SET_COOKIE, expired_state_cookie().to_string())]);
+
+	if let Some(error) = params.error {
+		tracing::error!(
+			error,
+			description = ?params.error_description,
+			"Identity provider rejected the login"
+		);
+		return Err(APIError::Unauthorized);
+	}
+	let code = params.code.ok_or(APIError::BadRequest(
+		"Missing authorization code".to_string(),
+	))?;
+
+	let client = OidcClient::discover(&ctx.config).await?;
+	let identity = client.authenticate(&code, &flow).await?;
+
+	let db = &ctx.db;
+	let user_id =
+		resolve_identity_user(db, &ctx.config, &identity, flow.link_user_id).await?;
+
+	let db_user = db
+		.user()
+		.find_first(vec![
+			user::id::equals(user_id.clone()),
+			user::deleted_at::equals(None),
+		])
+		.with(user::sessions::fetch(vec![session::expiry_time::gt(
+			Utc::now().into(),
+		)]))
+		.exec()
+		.await?
+		.ok_or(APIError::Unauthorized)?;
+
+	if db_user.is_locked {
+		return Err(APIError::Forbidden(
+			api_error_message::LOCKED_ACCOUNT.to_string(),
+		));
+	}
+
+	// When group mappings are configured, the identity provider is the source of truth
+	// for the permissions of anyone logging in through it
+	if !ctx.config.oidc_group_permissions.is_empty() && !db_user.is_server_owner {
+		let permissions = resolve_group_permissions(
+			&ctx.config.oidc_group_permissions,
+			&identity.groups,
+		);
+		db.user()
+			.update(
+				user::id::equals(user_id.clone()),
+				vec![user::permissions::set(
+					PermissionSet::new(permissions).resolve_into_string(),
+				)],
+			)
+			.exec()
+			.await?;
+	}
+
+	// The identity provider only replaces the password, so the second factor is still
+	// required before a session is created
+	if db_user.totp_enabled_at.is_some() {
+		let two_factor_token = PendingTwoFactorLogin::new(user_id, false, true).store();
+		return Ok((
+			clear_state_cookie,
+			Redirect::to(&two_factor_redirect(&two_factor_token, &flow.redirect_to)),
+		));
+	}
+
+	enforce_max_sessions(&db_user, db).await?;
+
+	let updated_user = db
+		.user()
+		.update(
+			user::id::equals(user_id),
+			vec![user::last_login::set(Some(Utc::now().into()))],
+		)
+		.with(user::user_preferences::fetch())
+		.with(user::age_restriction::fetch())
+		.exec()
+		.await?;
+
+	let login_track_result =
+		handle_login_attempt(db, updated_user.clone(), user_agent, request_info, true)
+			.await;
+	if let Err(err) = login_track_result {
+		tracing::error!(error = ?err, "Failed to track login attempt!");
+	}
+
+	session
+		.insert(SESSION_USER_KEY, User::from(updated_user))
+		.await?;
+	mark_session_authenticated(&session).await?;
+
+	Ok((clear_state_cookie, Redirect::to(&flow.redirect_to)))
+}
+
+/// Resolve the Stump user for an authenticated identity, linking the identity to the user
+/// who started the flow or provisioning a new account when it isn't known yet
+async fn resolve_identity_user(
+	db: &PrismaClient,
+	config: &StumpConfig,
+	identity: &OidcUserInfo,
+	link_user_id: Option<String>,
+) -> APIResult<String> {
+	let existing_identity = db
+		.oidc_identity()
+		.find_unique(oidc_identity::issuer_subject(
+			identity.issuer.clone(),
+			identity.subject.clone(),
+		))
+		.exec()
+		.await?;
+
+	match (existing_identity, link_user_id) {
+		(Some(existing), Some(link_user_id)) if existing.user_id != link_user_id => {
+			Err(APIError::BadRequest(
+				"This identity is already linked to another account".to_string(),
+			))
+		},
+		(Some(existing), _) => {
+			db.oidc_identity()
+				.update(
+					oidc_identity::id::equals(existing.id),
+					vec![
+						oidc_identity::last_used::set(Some(Utc::now().into())),
+						oidc_identity::email::set(identity.email.clone()),
+					],
+				)
+				.exec()
+				.await?;
+			Ok(existing.user_id)
+		},
+		(None, Some(link_user_id)) => {
+			let linked_identity = create_identity(db, identity, &link_user_id).await?;
+			tracing::debug!(?linked_identity, "Linked OIDC identity to user");
+			Ok(link_user_id)
+		},
+		(None, None) if config.oidc_auto_provision => {
+			provision_user(db, config, identity).await
+		},
+		(None, None) => Err(APIError::Forbidden(
+			"No account is linked to this identity".to_string(),
+		)),
+	}
+}
+
+async fn create_identity(
+	db: &PrismaClient,
+	identity: &OidcUserInfo,
+	user_id: &str,
+) -> APIResult<oidc_identity::Data> {
+	Ok(db
+		.oidc_identity()
+		.create(
+			identity.issuer.clone(),
+			identity.subject.clone(),
+			user::id::equals(user_id.to_string()),
+			vec![
+				oidc_identity::email::set(identity.email.clone()),
+				oidc_identity::last_used::set(Some(Utc::now().into())),
+			],
+		)
+		.exec()
+		.await?)
+}
+
+/// Create a new Stump account for an identity. The account is given a random password, so it
+/// can only be logged into through the identity provider.
+async fn provision_user(
+	db: &PrismaClient,
+	config: &StumpConfig,
+	identity: &OidcUserInfo,
+) -> APIResult<String> {
+	if db.user().find_first(vec![]).exec().await?.is_none() {
+		return Err(APIError::Forbidden(
+			"The server must be claimed before accounts can be provisioned".to_string(),
+		));
+	}
+
+	let username = identity
+		.username
+		.clone()
+		.or_else(|| {
+			identity
+				.email
+				.as_deref()
+				.and_then(|email| email.split('@').next())
+				.filter(|local| !local.is_empty())
+				.map(String::from)
+		})
+		.unwrap_or_else(|| identity.subject.clone());
+
+	let username_taken = db
+		.user()
+		.find_unique(user::username::equals(username.clone()))
+		.exec()
+		.await?
+		.is_some();
+	if username_taken {
+		// Silently linking to a local account with the same name would let anyone who
+		// controls the claim take it over, so the user must link it themselves instead
+		return Err(APIError::BadRequest(format!(
+			"An account named {username} already exists. Log in to it and link your identity instead"
+		)));
+	}
+
+	let password = Alphanumeric.sample_string(&mut rand::thread_rng(), 64);
+	let hashed_password = hash_password(&password, config)?;
+	let permissions = PermissionSet::new(resolve_group_permissions(
+		&config.oidc_group_permissions,
+		&identity.groups,
+	))
+	.resolve_into_string();
+
+	let (issuer, subject, email) = (
+		identity.issuer.clone(),
+		identity.subject.clone(),
+		identity.email.clone(),
+	);
+	let created_user = db
+		._transaction()
+		.run(|client| async move {
+			let created_user = client
+				.user()
+				.create(
+					username,
+					hashed_password,
+					vec![
+						user::is_server_owner::set(false),
+						user::permissions::set(permissions),
+					],
+				)
+				.exec()
+				.await?;
+
+			client
+				.user_preferences()
+				.create(vec![
+					user_preferences::user::connect(user::id::equals(
+						created_user.id.clone(),
+					)),
+					user_preferences::user_id::set(Some(created_user.id.clone())),
+				])
+				.exec()
+				.await?;
+
+			client
+				.oidc_identity()
+				.create(
+					issuer,
+					subject,
+					user::id::equals(created_user.id.clone()),
+					vec![
+						oidc_identity::email::set(email),
+						oidc_identity::last_used::set(Some(Utc::now().into())),
+					],
+				)
+				.exec()
+				.await
+				.map(|_| created_user)
+		})
+		.await?;
+	tracing::debug!(
+		user_id = created_user.id,
+		"Provisioned user for OIDC identity"
+	);
+
+	Ok(created_user.id)
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/auth/oidc/identities",
+	tag = "auth",
+	responses(
+		(status = 200, description = "Returns the identities linked to the current user.", body = Vec<OidcIdentity>),
+		(status = 401, description = "No user is logged in (unauthorized)."),
+	)
+)]
+/// Returns the OpenID Connect identities linked to the current user
+async fn get_oidc_identities(
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<Vec<OidcIdentity>>> {
+	let identities = ctx
+		.db
+		.oidc_identity()
+		.find_many(vec![oidc_identity::user_id::equals(req.id())])
+		.exec()
+		.await?;
+
+	Ok(Json(
+		identities.into_iter().map(OidcIdentity::from).collect(),
+	))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/v1/auth/oidc/identities/{id}",
+	tag = "auth",
+	params(
+		("id" = String, Path, description = "The ID of the identity to unlink")
+	),
+	responses(
+		(status = 200, description = "Unlinks the identity from the current user.", body = OidcIdentity),
+		(status = 401, description = "No user is logged in (unauthorized)."),
+		(status = 404, description = "The identity was not found."),
+	)
+)]
+/// Unlinks an OpenID Connect identity from the current user
+async fn delete_oidc_identity(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<OidcIdentity>> {
+	let client = &ctx.db;
+
+	let identity = client
+		.oidc_identity()
+		.find_first(vec![
+			oidc_identity::id::equals(id),
+			oidc_identity::user_id::equals(req.id()),
+		])
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound("Identity not found".to_string()))?;
+
+	let deleted_identity = client
+		.oidc_identity()
+		.delete(oidc_identity::id::equals(identity.id))
+		.exec()
+		.await?;
+
+	Ok(Json(OidcIdentity::from(deleted_identity)))
+}
@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use axum::{
 	extract::{DefaultBodyLimit, Multipart, Path, State},
 	middleware,
-	routing::{get, post},
+	routing::{get, post, put},
 	Extension, Json, Router,
 };
 use axum_extra::extract::Query;
@@ -17,7 +17,7 @@ use stump_core::{
 			macros::{
 				finished_reading_session_series_complete, series_or_library_thumbnail,
 			},
-			LibraryConfig, Media, Series, UserPermission,
+			LibraryConfig, Media, ReadingOverrides, Series, UserPermission,
 		},
 		query::{
 			ordering::QueryOrder,
@@ -35,9 +35,10 @@ use stump_core::{
 			generate_book_thumbnail, place_thumbnail, remove_thumbnails,
 			GenerateThumbnailOptions, ImageFormat, ImageProcessorOptions,
 		},
-		scanner::SeriesScanJob,
+		scanner::{ScanOptions, SeriesScanJob},
 		ContentType,
 	},
+	job::Executor,
 	prisma::{
 		active_reading_session, finished_reading_session, library,
 		media::{self, OrderByParam as MediaOrderByParam},
@@ -62,7 +63,7 @@ use crate::{
 			apply_series_filters_for_user,
 			apply_series_library_not_hidden_for_user_filter,
 		},
-		v1::media::thumbnails::get_media_thumbnail,
+		v1::{job::QueuedJob, media::thumbnails::get_media_thumbnail},
 	},
 	utils::{http::ImageResponse, validate_and_load_image},
 };
@@ -97,7 +98,8 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 				.route(
 					"/complete",
 					get(get_series_is_complete).put(put_series_is_complete),
-				),
+				)
+				.route("/reading-overrides", put(put_series_reading_overrides)),
 		)
 		.layer(middleware::from_fn_with_state(app_state, auth_middleware))
 }
@@ -152,7 +154,7 @@ async fn get_series(
 			let mut query = db.series().find_many(where_conditions.clone());
 			if load_media {
 				query = query.with(
-					series::media::fetch(vec![])
+					series::media::fetch(vec![media::deleted_at::equals(None)])
 						.with(media::active_user_reading_sessions::fetch(vec![
 							active_reading_session::user_id::equals(user_id.clone()),
 						]))
@@ -265,7 +267,7 @@ async fn get_series_by_id(
 
 	if load_media {
 		query = query.with(
-			series::media::fetch(vec![])
+			series::media::fetch(vec![media::deleted_at::equals(None)])
 				.with(media::active_user_reading_sessions::fetch(vec![
 					active_reading_session::user_id::equals(user_id.clone()),
 				]))
@@ -304,19 +306,22 @@ async fn get_series_by_id(
 	post,
 	path = "/api/v1/series/{id}/scan",
 	tag = "series",
+	request_body = ScanOptions,
 	responses(
-		(status = 200, description = "Successfully queued series scan"),
+		(status = 200, description = "Successfully queued series scan", body = QueuedJob),
 		(status = 401, description = "Unauthorized"),
 		(status = 404, description = "Series not found"),
 		(status = 500, description = "Internal server error")
 	)
 )]
-/// Queue a job to scan the series by ID
+/// Queue a job to scan the series by ID. When the scan is a dry run, the changes it would
+/// make can be fetched from the jobs API using the returned ID once it completes.
 async fn scan_series(
 	Path(id): Path<String>,
 	State(ctx): State<AppState>,
 	Extension(req): Extension<RequestContext>,
-) -> Result<(), APIError> {
+	options: Option<Json<ScanOptions>>,
+) -> APIResult<Json<QueuedJob>> {
 	let db = &ctx.db;
 	req.enforce_permissions(&[UserPermission::ScanLibrary])?;
 
@@ -327,13 +332,15 @@ async fn scan_series(
 		.await?
 		.ok_or(APIError::NotFound("Series not found".to_string()))?;
 
-	ctx.enqueue_job(SeriesScanJob::new(series.id, series.path, None))
-		.map_err(|e| {
-			error!(?e, "Failed to enqueue series scan job");
-			APIError::InternalServerError("Failed to enqueue series scan job".to_string())
-		})?;
+	let options = options.map(|Json(options)| options);
+	let job = SeriesScanJob::new(series.id, series.path, options);
+	let job_id = job.id().to_string();
+	ctx.enqueue_job(job).map_err(|e| {
+		error!(?e, "Failed to enqueue series scan job");
+		APIError::InternalServerError("Failed to enqueue series scan job".to_string())
+	})?;
 
-	Ok(())
+	Ok(Json(QueuedJob { id: job_id }))
 }
 
 // FIXME: This hand written SQL needs to factor in age restrictions!
@@ -693,7 +700,10 @@ async fn get_series_media(
 		[age_restrictions.as_ref().map(|(sr, _)| sr.clone())],
 	);
 	let media_where_params = chain_optional_iter(
-		[media::series_id::equals(Some(id.clone()))],
+		[
+			media::series_id::equals(Some(id.clone())),
+			media::deleted_at::equals(None),
+		],
 		[age_restrictions.as_ref().map(|(_, mr)| mr.clone())],
 	);
 
@@ -815,7 +825,8 @@ async fn get_next_in_series(
 			.collect::<Vec<WhereParam>>(),
 		[series_age_restrictions],
 	);
-	let media_where_params = chain_optional_iter([], [media_age_restrictions]);
+	let media_where_params =
+		chain_optional_iter([media::deleted_at::equals(None)], [media_age_restrictions]);
 
 	let result = db
 		.series()
@@ -910,7 +921,10 @@ async fn get_series_is_complete(
 		[age_restrictions.as_ref().map(|(sr, _)| sr.clone())],
 	);
 	let media_where_params = chain_optional_iter(
-		[media::series::is(series_where_params)],
+		[
+			media::series::is(series_where_params),
+			media::deleted_at::equals(None),
+		],
 		[age_restrictions.as_ref().map(|(_, mr)| mr.clone())],
 	);
 
@@ -952,6 +966,72 @@ async fn put_series_is_complete() -> APIResult<Json<SeriesIsComplete>> {
 	Err(APIError::NotImplemented)
 }
 
+#[utoipa::path(
+	put,
+	path = "/api/v1/series/{id}/reading-overrides",
+	tag = "series",
+	params(
+		("id" = String, Path, description = "The ID of the series to update reading overrides for")
+	),
+	request_body = ReadingOverrides,
+	responses(
+		(status = 200, description = "Successfully updated series reading overrides", body = Series),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Series not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Update the reading direction and mode overrides for a series, which apply to every book in
+/// the series without overrides of its own. This is a full update, so an override which is not
+/// set is removed.
+async fn put_series_reading_overrides(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(overrides): Json<ReadingOverrides>,
+) -> APIResult<Json<Series>> {
+	req.enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	let db = &ctx.db;
+	let user = req.user();
+	let age_restrictions = user
+		.age_restriction
+		.as_ref()
+		.map(|ar| apply_series_age_restriction(ar.age, ar.restrict_on_unset));
+	let where_params = [series::id::equals(id.clone())]
+		.into_iter()
+		.chain(apply_series_library_not_hidden_for_user_filter(user))
+		.chain(age_restrictions.map(|ar| vec![ar]).unwrap_or_default())
+		.collect::<Vec<WhereParam>>();
+
+	let series = db
+		.series()
+		.find_first(where_params)
+		.select(series::select!({ id }))
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound(String::from("Series not found")))?;
+
+	let updated_series = db
+		.series()
+		.update(
+			series::id::equals(series.id),
+			vec![
+				series::reading_direction::set(
+					overrides.reading_direction.map(|dir| dir.to_string()),
+				),
+				series::reading_mode::set(
+					overrides.reading_mode.map(|mode| mode.to_string()),
+				),
+			],
+		)
+		.exec()
+		.await?;
+
+	Ok(Json(Series::from(updated_series)))
+}
+
 #[utoipa::path(
 	post,
 	path = "/api/v1/series/{id}/analyze",
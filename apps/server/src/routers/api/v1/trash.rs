@@ -0,0 +1,278 @@
+use axum::{
+	extract::{Path, State},
+	middleware,
+	routing::{delete, get, post},
+	Extension, Json, Router,
+};
+use axum_extra::extract::Query;
+use prisma_client_rust::Direction;
+use stump_core::{
+	db::{
+		entity::{Media, User, UserPermission},
+		query::pagination::{Pageable, PageableMedia, PaginationQuery},
+		trash::{is_restored_as_ready, purge_trashed_media, PurgedTrash},
+		FileStatus,
+	},
+	prisma::{excluded_media_file, media, series, PrismaClient},
+};
+
+use crate::{
+	config::state::AppState,
+	errors::{APIError, APIResult},
+	middleware::auth::{auth_middleware, RequestContext},
+	routers::api::filters::{
+		apply_media_pagination, library_not_hidden_from_user_filter,
+	},
+};
+
+pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
+	Router::new()
+		.nest(
+			"/trash",
+			Router::new()
+				.route("/", get(get_trashed_media).delete(empty_trash))
+				.route("/{id}", delete(purge_trashed_media_by_id))
+				.route("/{id}/restore", post(restore_trashed_media)),
+		)
+		.layer(middleware::from_fn_with_state(app_state, auth_middleware))
+}
+
+/// The conditions for media in the trash which the user is allowed to see
+fn trashed_media_for_user(user: &User) -> Vec<media::WhereParam> {
+	vec![
+		media::deleted_at::not(None),
+		media::series::is(vec![series::library::is(vec![
+			library_not_hidden_from_user_filter(user),
+		])]),
+	]
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/trash",
+	tag = "trash",
+	params(
+		("pagination_query" = Option<PaginationQuery>, Query, description = "The pagination options"),
+	),
+	responses(
+		(status = 200, description = "Successfully fetched trashed media", body = PageableMedia),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Get the media in the trash, most recently trashed first
+pub(crate) async fn get_trashed_media(
+	pagination_query: Query<PaginationQuery>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<Pageable<Vec<Media>>>> {
+	req.enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	let pagination = pagination_query.0.get();
+	let is_unpaged = pagination.is_unpaged();
+	let where_params = trashed_media_for_user(req.user());
+
+	let pagination_cloned = pagination.clone();
+	let (media, count) = ctx
+		.db
+		._transaction()
+		.run(|client| async move {
+			let query = client
+				.media()
+				.find_many(where_params.clone())
+				.with(media::metadata::fetch())
+				.order_by(media::deleted_at::order(Direction::Desc));
+
+			let media = apply_media_pagination(query, &pagination_cloned)
+				.exec()
+				.await?
+				.into_iter()
+				.map(Media::from)
+				.collect::<Vec<_>>();
+
+			if is_unpaged {
+				return Ok((media, None));
+			}
+
+			client
+				.media()
+				.count(where_params)
+				.exec()
+				.await
+				.map(|count| (media, Some(count)))
+		})
+		.await?;
+
+	if let Some(count) = count {
+		return Ok(Json(Pageable::from((media, count, pagination))));
+	}
+
+	Ok(Json(Pageable::from(media)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/trash/{id}/restore",
+	tag = "trash",
+	params(
+		("id" = String, Path, description = "The ID of the trashed media")
+	),
+	responses(
+		(status = 200, description = "Successfully restored media", body = Media),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Media not found in the trash"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Restore a media from the trash. Reading sessions, bookmarks, annotations and anything else
+/// tied to the media are kept. If the file is still missing from disk, the media will be moved
+/// back to the trash by the next scan.
+pub(crate) async fn restore_trashed_media(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<Media>> {
+	req.enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	let restored_media = restore_media(&ctx.db, req.user(), id).await?;
+
+	Ok(Json(Media::from(restored_media)))
+}
+
+async fn restore_media(
+	db: &PrismaClient,
+	user: &User,
+	id: String,
+) -> APIResult<media::Data> {
+	let trashed_media = db
+		.media()
+		.find_first(
+			[media::id::equals(id.clone())]
+				.into_iter()
+				.chain(trashed_media_for_user(user))
+				.collect(),
+		)
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound(
+			"Media not found in the trash".to_string(),
+		))?;
+
+	let mut params = vec![media::deleted_at::set(None)];
+	if is_restored_as_ready(&trashed_media.status, &trashed_media.path).await {
+		params.push(media::status::set(FileStatus::Ready.to_string()));
+	}
+
+	let restored_media = db
+		.media()
+		.update(media::id::equals(id), params)
+		.with(media::metadata::fetch())
+		.exec()
+		.await?;
+	// A duplicate which was trashed without deleting its file is scanned again once restored
+	db.excluded_media_file()
+		.delete_many(vec![excluded_media_file::path::equals(
+			restored_media.path.clone(),
+		)])
+		.exec()
+		.await?;
+
+	Ok(restored_media)
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/v1/trash/{id}",
+	tag = "trash",
+	params(
+		("id" = String, Path, description = "The ID of the trashed media")
+	),
+	responses(
+		(status = 200, description = "Successfully deleted media", body = PurgedTrash),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 404, description = "Media not found in the trash"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Permanently delete a single media from the trash
+pub(crate) async fn purge_trashed_media_by_id(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<PurgedTrash>> {
+	req.enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	let purged = purge_trashed_media(
+		&ctx.db,
+		&ctx.config.get_thumbnails_dir(),
+		[media::id::equals(id)]
+			.into_iter()
+			.chain(trashed_media_for_user(req.user()))
+			.collect(),
+	)
+	.await?;
+
+	if purged.deleted_media_count == 0 {
+		return Err(APIError::NotFound(
+			"Media not found in the trash".to_string(),
+		));
+	}
+
+	Ok(Json(purged))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/v1/trash",
+	tag = "trash",
+	responses(
+		(status = 200, description = "Successfully emptied the trash", body = PurgedTrash),
+		(status = 401, description = "Unauthorized"),
+		(status = 403, description = "Forbidden"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Permanently delete all media in the trash, regardless of the retention period
+pub(crate) async fn empty_trash(
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<PurgedTrash>> {
+	req.enforce_permissions(&[UserPermission::ManageLibrary])?;
+
+	let purged = purge_trashed_media(
+		&ctx.db,
+		&ctx.config.get_thumbnails_dir(),
+		trashed_media_for_user(req.user()),
+	)
+	.await?;
+
+	Ok(Json(purged))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_restore_media_not_in_trash() {
+		let (client, mock) = PrismaClient::_mock();
+		let user = User::default();
+
+		mock.expect(
+			client.media().find_first(
+				[media::id::equals("book".to_string())]
+					.into_iter()
+					.chain(trashed_media_for_user(&user))
+					.collect(),
+			),
+			None,
+		)
+		.await;
+
+		let result = restore_media(&client, &user, "book".to_string()).await;
+		assert!(matches!(result, Err(APIError::NotFound(_))));
+	}
+}
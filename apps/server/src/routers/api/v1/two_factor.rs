@@ -0,0 +1,355 @@
+use axum::{
+	extract::State,
+	middleware,
+	routing::{get, post},
+	Extension, Json, Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use stump_core::{
+	db::entity::{User, UserPermission},
+	prisma::user,
+};
+use tower_sessions::Session;
+use utoipa::ToSchema;
+
+use crate::{
+	config::{
+		session::SESSION_USER_KEY,
+		state::AppState,
+		two_factor::{
+			consume_recovery_code, count_recovery_codes, generate_recovery_codes,
+			generate_totp_secret, totp_provisioning_uri, verify_totp,
+		},
+	},
+	errors::{APIError, APIResult},
+	middleware::auth::{auth_middleware, RequestContext},
+	utils::{get_session_user, verify_password},
+};
+
+pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
+	Router::new()
+		.nest(
+			"/auth/2fa",
+			Router::new()
+				.route("/", get(get_two_factor_status))
+				.route("/setup", post(setup_two_factor))
+				.route("/confirm", post(confirm_two_factor))
+				.route("/recovery-codes", post(regenerate_recovery_codes))
+				.route("/disable", post(disable_two_factor)),
+		)
+		.layer(middleware::from_fn_with_state(app_state, auth_middleware))
+}
+
+/// Whether a user must enroll in two-factor authentication before using the API
+pub(crate) fn is_two_factor_required(user: &User, enforce_for_managers: bool) -> bool {
+	enforce_for_managers && user.has_permission(UserPermission::ManageServer)
+}
+
+/// Verify a second factor for a user, which may either be a TOTP code or one of their
+/// recovery codes. Returns the updates to persist so the code can't be used again, or
+/// `None` if the code is invalid.
+pub(crate) fn verify_second_factor(
+	db_user: &user::Data,
+	code: &str,
+) -> Option<Vec<user::SetParam>> {
+	let secret = db_user
+		.totp_secret
+		.as_deref()
+		.filter(|_| db_user.totp_enabled_at.is_some())?;
+
+	let last_used_step = db_user.totp_last_used_step.map(i64::from);
+	if let Some(step) = verify_totp(secret, code, Utc::now().timestamp(), last_used_step)
+	{
+		return Some(vec![user::totp_last_used_step::set(
+			i32::try_from(step).ok(),
+		)]);
+	}
+
+	let remaining = consume_recovery_code(db_user.totp_recovery_codes.as_deref()?, code)?;
+	Some(vec![user::totp_recovery_codes::set(Some(remaining))])
+}
+
+/// Fetch the full user for a request. Two-factor settings may only be changed by the user
+/// themselves, so requests authenticated with an API key are rejected.
+async fn get_request_user(ctx: &AppState, req: &RequestContext) -> APIResult<user::Data> {
+	if req.api_key().is_some() {
+		return Err(APIError::Forbidden(
+			"Two-factor authentication cannot be managed with an API key".to_string(),
+		));
+	}
+
+	ctx.db
+		.user()
+		.find_unique(user::id::equals(req.id()))
+		.exec()
+		.await?
+		.ok_or(APIError::Unauthorized)
+}
+
+/// Update the user and refresh the copy stored in the session, if any, so the session
+/// reflects the new two-factor status
+async fn update_user(
+	ctx: &AppState,
+	session: &Session,
+	user_id: String,
+	params: Vec<user::SetParam>,
+) -> APIResult<User> {
+	let updated_user = ctx
+		.db
+		.user()
+		.update(user::id::equals(user_id), params)
+		.with(user::user_preferences::fetch())
+		.with(user::age_restriction::fetch())
+		.exec()
+		.await?;
+	let user = User::from(updated_user);
+
+	if get_session_user(session).await?.is_some() {
+		session.insert(SESSION_USER_KEY, user.clone()).await?;
+	}
+
+	Ok(user)
+}
+
+#[derive(Debug, Serialize, Type, ToSchema)]
+pub struct TwoFactorStatus {
+	/// Whether the user has verified two-factor authentication
+	pub enabled: bool,
+	/// Whether the server requires the user to enroll
+	pub required: bool,
+	/// The number of unused recovery codes the user has left
+	pub recovery_codes_remaining: usize,
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/auth/2fa",
+	tag = "auth",
+	responses(
+		(status = 200, description = "Returns the two-factor status of the current user.", body = TwoFactorStatus),
+		(status = 401, description = "No user is logged in (unauthorized)."),
+	)
+)]
+/// Returns the two-factor authentication status of the current user
+async fn get_two_factor_status(
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<TwoFactorStatus>> {
+	let db_user = get_request_user(&ctx, &req).await?;
+
+	Ok(Json(TwoFactorStatus {
+		enabled: db_user.totp_enabled_at.is_some(),
+		required: is_two_factor_required(
+			req.user(),
+			ctx.config.enforce_manager_two_factor,
+		),
+		recovery_codes_remaining: count_recovery_codes(
+			db_user.totp_recovery_codes.as_deref(),
+		),
+	}))
+}
+
+#[derive(Debug, Deserialize, Type, ToSchema)]
+pub struct SetupTwoFactor {
+	/// The password of the user. It may be omitted when the user authenticated recently, e.g.
+	/// users who log in with OIDC or through a proxy and may not have a password.
+	#[serde(default)]
+	pub password: Option<String>,
+}
+
+#[derive(Debug, Serialize, Type, ToSchema)]
+pub struct TwoFactorSetup {
+	/// The base32 encoded secret, for manual entry into an authenticator app
+	pub secret: String,
+	/// The `otpauth://` URI to render as a QR code
+	pub provisioning_uri: String,
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/auth/2fa/setup",
+	tag = "auth",
+	request_body = SetupTwoFactor,
+	responses(
+		(status = 200, description = "Starts two-factor enrollment.", body = TwoFactorSetup),
+		(status = 400, description = "Two-factor authentication is already enabled."),
+		(status = 401, description = "The password is incorrect, or it was omitted and the user did not authenticate recently."),
+	)
+)]
+/// Starts two-factor enrollment by generating a new secret. Two-factor authentication is not
+/// enabled until a code from the secret is confirmed.
+async fn setup_two_factor(
+	session: Session,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(input): Json<SetupTwoFactor>,
+) -> APIResult<Json<TwoFactorSetup>> {
+	let db_user = get_request_user(&ctx, &req).await?;
+
+	if db_user.totp_enabled_at.is_some() {
+		return Err(APIError::BadRequest(
+			"Two-factor authentication is already enabled".to_string(),
+		));
+	}
+	match input.password {
+		Some(password) if !verify_password(&db_user.hashed_password, &password)? => {
+			return Err(APIError::Unauthorized);
+		},
+		None if !req.is_recently_authenticated() => {
+			return Err(APIError::Unauthorized);
+		},
+		_ => {},
+	}
+
+	let secret = generate_totp_secret();
+	let provisioning_uri = totp_provisioning_uri(&secret, &db_user.username);
+	update_user(
+		&ctx,
+		&session,
+		db_user.id,
+		vec![user::totp_secret::set(Some(secret.clone()))],
+	)
+	.await?;
+
+	Ok(Json(TwoFactorSetup {
+		secret,
+		provisioning_uri,
+	}))
+}
+
+#[derive(Debug, Deserialize, Type, ToSchema)]
+pub struct VerifyTwoFactorCode {
+	pub code: String,
+}
+
+#[derive(Debug, Serialize, Type, ToSchema)]
+pub struct TwoFactorRecoveryCodes {
+	/// One-time codes which may be used in place of a TOTP code. These are only shown once.
+	pub recovery_codes: Vec<String>,
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/auth/2fa/confirm",
+	tag = "auth",
+	request_body = VerifyTwoFactorCode,
+	responses(
+		(status = 200, description = "Enables two-factor authentication.", body = TwoFactorRecoveryCodes),
+		(status = 400, description = "Enrollment was not started or is already complete."),
+		(status = 401, description = "The code is invalid."),
+	)
+)]
+/// Completes two-factor enrollment by verifying a code from the new secret, and returns the
+/// user's recovery codes
+async fn confirm_two_factor(
+	session: Session,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(input): Json<VerifyTwoFactorCode>,
+) -> APIResult<Json<TwoFactorRecoveryCodes>> {
+	let db_user = get_request_user(&ctx, &req).await?;
+
+	let secret = match (&db_user.totp_secret, db_user.totp_enabled_at) {
+		(Some(secret), None) => secret,
+		_ => {
+			return Err(APIError::BadRequest(
+				"Two-factor enrollment has not been started".to_string(),
+			))
+		},
+	};
+	let step = verify_totp(secret, &input.code, Utc::now().timestamp(), None)
+		.ok_or(APIError::Unauthorized)?;
+
+	let (recovery_codes, hashes) = generate_recovery_codes();
+	update_user(
+		&ctx,
+		&session,
+		db_user.id,
+		vec![
+			user::totp_enabled_at::set(Some(Utc::now().into())),
+			user::totp_last_used_step::set(i32::try_from(step).ok()),
+			user::totp_recovery_codes::set(Some(hashes)),
+		],
+	)
+	.await?;
+
+	Ok(Json(TwoFactorRecoveryCodes { recovery_codes }))
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/auth/2fa/recovery-codes",
+	tag = "auth",
+	request_body = VerifyTwoFactorCode,
+	responses(
+		(status = 200, description = "Replaces the user's recovery codes.", body = TwoFactorRecoveryCodes),
+		(status = 401, description = "The code is invalid."),
+	)
+)]
+/// Replaces the current user's recovery codes, invalidating any unused ones
+async fn regenerate_recovery_codes(
+	session: Session,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(input): Json<VerifyTwoFactorCode>,
+) -> APIResult<Json<TwoFactorRecoveryCodes>> {
+	let db_user = get_request_user(&ctx, &req).await?;
+
+	let mut params =
+		verify_second_factor(&db_user, &input.code).ok_or(APIError::Unauthorized)?;
+	let (recovery_codes, hashes) = generate_recovery_codes();
+	params.push(user::totp_recovery_codes::set(Some(hashes)));
+	update_user(&ctx, &session, db_user.id, params).await?;
+
+	Ok(Json(TwoFactorRecoveryCodes { recovery_codes }))
+}
+
+#[derive(Debug, Deserialize, Type, ToSchema)]
+pub struct DisableTwoFactor {
+	pub password: String,
+	pub code: String,
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/auth/2fa/disable",
+	tag = "auth",
+	request_body = DisableTwoFactor,
+	responses(
+		(status = 200, description = "Disables two-factor authentication.", body = User),
+		(status = 401, description = "The password or code is invalid."),
+	)
+)]
+/// Disables two-factor authentication for the current user. Both the password and a code
+/// (or recovery code) are required.
+async fn disable_two_factor(
+	session: Session,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(input): Json<DisableTwoFactor>,
+) -> APIResult<Json<User>> {
+	let db_user = get_request_user(&ctx, &req).await?;
+
+	if !verify_password(&db_user.hashed_password, &input.password)?
+		|| verify_second_factor(&db_user, &input.code).is_none()
+	{
+		return Err(APIError::Unauthorized);
+	}
+
+	let user = update_user(
+		&ctx,
+		&session,
+		db_user.id,
+		vec![
+			user::totp_secret::set(None),
+			user::totp_enabled_at::set(None),
+			user::totp_last_used_step::set(None),
+			user::totp_recovery_codes::set(None),
+		],
+	)
+	.await?;
+
+	Ok(Json(user))
+}
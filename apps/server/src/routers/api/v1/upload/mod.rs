@@ -7,7 +7,7 @@ use std::{
 use axum::{
 	extract::{DefaultBodyLimit, Path, State},
 	middleware,
-	routing::post,
+	routing::{get, post},
 	Extension, Json, Router,
 };
 use axum_typed_multipart::{FieldData, TryFromMultipart, TypedMultipart};
@@ -23,18 +23,35 @@ use crate::{
 	routers::api::filters::library_not_hidden_from_user_filter,
 };
 use stump_core::{
-	db::entity::{macros::library_path_with_options_select, User, UserPermission},
-	filesystem::scanner::LibraryScanJob,
-	prisma::{library, PrismaClient},
+	db::entity::{
+		macros::library_path_with_options_select, LibraryConfig, User, UserPermission,
+	},
+	filesystem::scanner::{LibraryScanJob, SeriesScanJob},
+	prisma::{library, series, PrismaClient},
+	Ctx,
 };
 
+pub(crate) mod session;
+
 pub fn mount(app_state: AppState) -> Router<AppState> {
 	Router::new()
 		.nest(
 			"/upload",
 			Router::new()
 				.route("/libraries/{id}/books", post(upload_books))
-				.route("/libraries/{id}/series", post(upload_series)),
+				.route("/libraries/{id}/series", post(upload_series))
+				.route(
+					"/libraries/{id}/sessions",
+					post(session::create_upload_session),
+				)
+				.route(
+					"/sessions/{id}",
+					get(session::get_upload_session)
+						.patch(session::upload_chunk)
+						.delete(session::abort_upload_session),
+				)
+				.route("/sessions/{id}/preview", get(session::preview_upload))
+				.route("/sessions/{id}/complete", post(session::complete_upload)),
 		)
 		.layer(DefaultBodyLimit::max(app_state.config.max_file_upload_size))
 		.layer(middleware::from_fn_with_state(app_state, auth_middleware))
@@ -100,14 +117,7 @@ async fn upload_books(
 		copy_tempfile_to_location(f, &target_path).await?;
 	}
 
-	// Start a scan of the library
-	ctx.enqueue_job(LibraryScanJob::new(id, library.path, None))
-		.map_err(|e| {
-			tracing::error!(?e, "Failed to enqueue library scan job");
-			APIError::InternalServerError(
-				"Failed to enqueue library scan job".to_string(),
-			)
-		})?;
+	enqueue_targeted_scan(&ctx, &id, &library, &placement_path).await?;
 
 	Ok(Json(()))
 }
@@ -217,21 +227,18 @@ async fn copy_tempfile_to_location(
 /// A helper function to validate the file used for a books upload, this function
 /// will return an error if the file is not the appropriate file type.
 fn validate_book_file(f: &FieldData<NamedTempFile>) -> APIResult<()> {
-	/// Any file extension not in this list will trigger an error
-	const ALLOWED_EXTENSIONS: &[&str] = &["cbr", "cbz", "epub", "pdf"];
-
-	/// Any inferred mime type not in this list will trigger an error
-	const ALLOWED_TYPES: &[&str] = &[
-		"application/zip",
-		"application/vnd.comicbook+zip",
-		"application/vnd.comicbook-rar",
-		"application/epub+zip",
-		"application/pdf",
-	];
-
 	let file_name = f.metadata.file_name.as_ref().ok_or(APIError::BadRequest(
 		"Uploaded files must have filenames.".to_string(),
 	))?;
+	validate_book_extension(file_name)?;
+	validate_book_contents(file_name, &mut f.contents.reopen()?)
+}
+
+/// A helper function to validate the extension of a book's file name, this function
+/// will return an error if the extension is not one which Stump can process.
+fn validate_book_extension(file_name: &str) -> APIResult<()> {
+	/// Any file extension not in this list will trigger an error
+	const ALLOWED_EXTENSIONS: &[&str] = &["cbr", "cbz", "epub", "pdf"];
 
 	let extension = path::Path::new(file_name)
 		.extension()
@@ -249,10 +256,25 @@ fn validate_book_file(f: &FieldData<NamedTempFile>) -> APIResult<()> {
 		)));
 	}
 
+	Ok(())
+}
+
+/// A helper function to validate the contents of a book, based on the magic bytes at the
+/// start of the file. This function will return an error if the inferred type is not one
+/// which Stump can process.
+fn validate_book_contents(file_name: &str, reader: &mut impl Read) -> APIResult<()> {
+	/// Any inferred mime type not in this list will trigger an error
+	const ALLOWED_TYPES: &[&str] = &[
+		"application/zip",
+		"application/vnd.comicbook+zip",
+		"application/vnd.comicbook-rar",
+		"application/epub+zip",
+		"application/pdf",
+	];
+
 	// Read first five bytes from which to infer content type
-	let mut f_read = f.contents.reopen()?;
 	let mut magic_bytes = [0u8; 5];
-	f_read.read_exact(&mut magic_bytes).map_err(|_| {
+	reader.read_exact(&mut magic_bytes).map_err(|_| {
 		APIError::InternalServerError(
 			"Failed to read first five bytes of zip file.".to_string(),
 		)
@@ -479,23 +501,94 @@ fn get_books_path(
 	books_request: &UploadBooksRequest,
 	library: &LibraryData,
 ) -> APIResult<PathBuf> {
+	get_placement_path(&books_request.place_at, library)
+}
+
+/// A helper function to generate the directory at which uploaded books should be placed,
+/// given the requested placement (either relative to the library or a full path within it)
+fn get_placement_path(place_at: &str, library: &LibraryData) -> APIResult<PathBuf> {
 	// Validate the placement path parameters, error otherwise
 	// This is an important security check.
-	if !is_subpath_secure(&books_request.place_at) {
+	if !is_subpath_secure(place_at) {
 		return Err(APIError::BadRequest(
 			"Invalid upload path placement parameters".to_string(),
 		));
 	}
 	// Get path that uploads will be placed at, account for possible full path
-	let placement_path = if books_request.place_at.starts_with(&library.path) {
-		path::PathBuf::from(&books_request.place_at)
+	let placement_path = if place_at.starts_with(&library.path) {
+		path::PathBuf::from(place_at)
 	} else {
-		path::Path::new(&library.path).join(&books_request.place_at)
+		path::Path::new(&library.path).join(place_at)
 	};
 
 	Ok(placement_path)
 }
 
+/// A helper function to find the existing series which books placed in the given directory
+/// would belong to. For series-based libraries this must be a series at exactly that
+/// directory, whereas collection-based libraries also consider the nearest ancestor.
+async fn find_target_series(
+	client: &PrismaClient,
+	library_id: &str,
+	library: &LibraryData,
+	placement_path: &path::Path,
+) -> APIResult<Option<series::Data>> {
+	let candidate_paths = if LibraryConfig::from(&library.config).is_collection_based() {
+		placement_path
+			.ancestors()
+			.take_while(|ancestor| ancestor.starts_with(&library.path))
+			.map(|ancestor| ancestor.to_string_lossy().to_string())
+			.collect::<Vec<_>>()
+	} else {
+		vec![placement_path.to_string_lossy().to_string()]
+	};
+
+	let target_series = client
+		.series()
+		.find_many(vec![
+			series::library_id::equals(Some(library_id.to_string())),
+			series::path::in_vec(candidate_paths),
+		])
+		.exec()
+		.await?
+		.into_iter()
+		.max_by_key(|series| series.path.len());
+
+	Ok(target_series)
+}
+
+/// A helper function to scan the books placed in the given directory. If the directory belongs
+/// to an existing series, only that series is scanned. Otherwise, the books would create a new
+/// series and so the whole library is scanned.
+async fn enqueue_targeted_scan(
+	ctx: &Ctx,
+	library_id: &str,
+	library: &LibraryData,
+	placement_path: &path::Path,
+) -> APIResult<Option<series::Data>> {
+	let target_series =
+		find_target_series(&ctx.db, library_id, library, placement_path).await?;
+
+	let result = match &target_series {
+		Some(series) => ctx.enqueue_job(SeriesScanJob::new(
+			series.id.clone(),
+			series.path.clone(),
+			None,
+		)),
+		None => ctx.enqueue_job(LibraryScanJob::new(
+			library_id.to_string(),
+			library.path.clone(),
+			None,
+		)),
+	};
+	result.map_err(|e| {
+		tracing::error!(?e, "Failed to enqueue scan job");
+		APIError::InternalServerError("Failed to enqueue scan job".to_string())
+	})?;
+
+	Ok(target_series)
+}
+
 /// A helper function to generate the path at which a series zip should be placed
 /// given an input [`UploadSeriesRequest`] and library.
 fn get_series_path(
@@ -0,0 +1,744 @@
+//! Resumable uploads of individual books. An upload is started by creating a session, the
+//! file is then sent in any number of chunks (which may be resumed from the last received
+//! offset after a failure or restart), previewed, and finally completed.
+//!
+//! The chunk requests loosely follow the tus protocol: each chunk is a `PATCH` carrying an
+//! `Upload-Offset` header and, optionally, an `Upload-Checksum` header of the form
+//! `sha256 <base64 digest>`.
+
+use std::{
+	collections::HashSet,
+	io::{self, Read},
+	path::{self, PathBuf},
+	sync::Mutex,
+};
+
+use axum::{
+	body::Bytes,
+	extract::{Path, State},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+	Extension, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use once_cell::sync::Lazy;
+use prisma_client_rust::chrono::{DateTime, Duration, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
+use stump_core::{
+	db::entity::{MediaMetadata, Series, UserPermission},
+	filesystem::{process, FileProcessorOptions},
+};
+use tokio::{fs, io::AsyncWriteExt};
+use utoipa::ToSchema;
+
+use crate::{
+	config::state::AppState,
+	errors::{APIError, APIResult},
+	middleware::auth::RequestContext,
+};
+
+use super::{
+	enqueue_targeted_scan, find_target_series, get_library, get_placement_path,
+	validate_book_contents, validate_book_extension,
+};
+
+/// The header carrying the offset a chunk should be written at
+const UPLOAD_OFFSET_HEADER: &str = "Upload-Offset";
+/// The header carrying the checksum of a chunk
+const UPLOAD_CHECKSUM_HEADER: &str = "Upload-Checksum";
+/// How long an upload session is kept before it is considered abandoned
+const UPLOAD_SESSION_TTL_HOURS: i64 = 24;
+
+/// The IDs of the sessions which currently have a chunk being written, so that concurrent
+/// chunks for the same session are rejected rather than interleaved
+static ACTIVE_SESSIONS: Lazy<Mutex<HashSet<String>>> =
+	Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// A guard which marks a session as having a chunk in flight until it is dropped
+struct ActiveSessionGuard(String);
+
+impl ActiveSessionGuard {
+	fn acquire(id: &str) -> APIResult<Self> {
+		let mut active = ACTIVE_SESSIONS
+			.lock()
+			.map_err(|_| APIError::InternalServerError("Upload lock poisoned".into()))?;
+		if !active.insert(id.to_string()) {
+			return Err(APIError::BadRequest(
+				"Another chunk is already being written for this upload".to_string(),
+			));
+		}
+		Ok(Self(id.to_string()))
+	}
+}
+
+impl Drop for ActiveSessionGuard {
+	fn drop(&mut self) {
+		if let Ok(mut active) = ACTIVE_SESSIONS.lock() {
+			active.remove(&self.0);
+		}
+	}
+}
+
+#[derive(Debug, Deserialize, Type, ToSchema)]
+pub struct CreateUploadSession {
+	/// The directory to place the book in, either relative to the library root or a full
+	/// path within the library
+	place_at: String,
+	/// The name of the file, including its extension
+	file_name: String,
+	/// The total size of the file, in bytes
+	size: u64,
+	/// An optional hex-encoded SHA-256 digest of the entire file, which is verified before
+	/// the upload may be completed
+	#[serde(default)]
+	checksum: Option<String>,
+}
+
+/// A resumable upload of a single book
+#[derive(Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
+pub struct UploadSession {
+	pub id: String,
+	pub library_id: String,
+	pub user_id: String,
+	pub file_name: String,
+	/// The directory the book will be placed in once the upload is completed
+	pub placement_path: String,
+	/// The total size of the file, in bytes
+	pub size: u64,
+	/// The number of bytes received so far. The next chunk must start at this offset.
+	#[serde(default)]
+	pub offset: u64,
+	/// The expected hex-encoded SHA-256 digest of the entire file, if one was provided
+	pub checksum: Option<String>,
+	pub created_at: String,
+}
+
+impl UploadSession {
+	fn is_complete(&self) -> bool {
+		self.offset == self.size
+	}
+
+	fn is_expired(&self) -> bool {
+		DateTime::parse_from_rfc3339(&self.created_at)
+			.map(|created_at| {
+				Utc::now() - created_at.with_timezone(&Utc)
+					> Duration::hours(UPLOAD_SESSION_TTL_HOURS)
+			})
+			.unwrap_or(true)
+	}
+}
+
+/// A preview of what completing an upload would result in
+#[derive(Debug, Serialize, Type, ToSchema)]
+pub struct UploadPreview {
+	pub file_name: String,
+	/// The full path the book will be placed at
+	pub path: String,
+	pub pages: i32,
+	/// The metadata detected in the file, if any
+	pub metadata: Option<MediaMetadata>,
+	/// The existing series the book will be added to. If there is none, a new series will be
+	/// created by the scan which follows the upload
+	pub series: Option<Series>,
+}
+
+/// The result of a completed upload
+#[derive(Debug, Serialize, Type, ToSchema)]
+pub struct CompletedUpload {
+	/// The full path the book was placed at
+	pub path: String,
+	/// The existing series which is being scanned for the book. If there is none, the whole
+	/// library is scanned instead
+	pub series: Option<Series>,
+}
+
+/// The on-disk storage for upload sessions. Each session is kept as a JSON file next to the
+/// partially uploaded data, so uploads may be resumed after the server restarts.
+struct UploadSessionStore {
+	root: PathBuf,
+}
+
+impl UploadSessionStore {
+	fn new(ctx: &AppState) -> Self {
+		Self {
+			root: ctx.config.get_config_dir().join("uploads"),
+		}
+	}
+
+	fn session_path(&self, id: &str) -> PathBuf {
+		self.root.join(format!("{id}.json"))
+	}
+
+	fn data_path(&self, session: &UploadSession) -> PathBuf {
+		// The extension is kept so the partial file can be processed for a preview
+		let extension = path::Path::new(&session.file_name)
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.unwrap_or_default();
+		self.root.join(format!("{}.{extension}", session.id))
+	}
+
+	async fn create(&self, session: &UploadSession) -> APIResult<()> {
+		fs::create_dir_all(&self.root).await?;
+		fs::File::create(self.data_path(session)).await?;
+		fs::write(
+			self.session_path(&session.id),
+			serde_json::to_vec(session).map_err(|e| {
+				APIError::InternalServerError(format!(
+					"Failed to serialize upload session: {e}"
+				))
+			})?,
+		)
+		.await?;
+		Ok(())
+	}
+
+	/// Get a session owned by the given user. The offset is always read from the size of the
+	/// data received, so that it is accurate even if the server stopped mid-chunk.
+	async fn get(&self, id: &str, user_id: &str) -> APIResult<UploadSession> {
+		let not_found = || APIError::NotFound("Upload session not found".to_string());
+
+		// Session IDs are alphanumeric, anything else can't be a valid session
+		if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+			return Err(not_found());
+		}
+
+		let contents = match fs::read(self.session_path(id)).await {
+			Ok(contents) => contents,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(not_found()),
+			Err(e) => return Err(e.into()),
+		};
+		let mut session =
+			serde_json::from_slice::<UploadSession>(&contents).map_err(|e| {
+				tracing::error!(?e, %id, "Failed to deserialize upload session");
+				not_found()
+			})?;
+		if session.user_id != user_id {
+			return Err(not_found());
+		}
+
+		session.offset = fs::metadata(self.data_path(&session)).await?.len();
+		Ok(session)
+	}
+
+	async fn remove(&self, session: &UploadSession) -> APIResult<()> {
+		for path in [self.data_path(session), self.session_path(&session.id)] {
+			if let Err(e) = fs::remove_file(&path).await {
+				if e.kind() != io::ErrorKind::NotFound {
+					return Err(e.into());
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Remove any sessions which have been abandoned for longer than the TTL
+	async fn remove_expired(&self) -> APIResult<()> {
+		let mut entries = match fs::read_dir(&self.root).await {
+			Ok(entries) => entries,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+			Err(e) => return Err(e.into()),
+		};
+
+		while let Some(entry) = entries.next_entry().await? {
+			let path = entry.path();
+			if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+				continue;
+			}
+			let Ok(contents) = fs::read(&path).await else {
+				continue;
+			};
+			match serde_json::from_slice::<UploadSession>(&contents) {
+				Ok(session) if session.is_expired() => {
+					tracing::debug!(id = %session.id, "Removing expired upload session");
+					self.remove(&session).await?;
+				},
+				Ok(_) => {},
+				Err(_) => {
+					tracing::warn!(?path, "Removing unreadable upload session");
+					fs::remove_file(&path).await?;
+				},
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Parse the value of an `Upload-Checksum` header, which must be of the form
+/// `sha256 <base64 digest>`
+fn parse_chunk_checksum(value: &str) -> APIResult<Vec<u8>> {
+	let (algorithm, digest) = value.trim().split_once(' ').ok_or_else(|| {
+		APIError::BadRequest(format!("Invalid {UPLOAD_CHECKSUM_HEADER} header"))
+	})?;
+	if !algorithm.eq_ignore_ascii_case("sha256") {
+		return Err(APIError::BadRequest(format!(
+			"Unsupported checksum algorithm: {algorithm}. Only sha256 is supported"
+		)));
+	}
+	STANDARD.decode(digest.trim()).map_err(|_| {
+		APIError::BadRequest(format!("Invalid {UPLOAD_CHECKSUM_HEADER} header"))
+	})
+}
+
+/// Compute the hex-encoded SHA-256 digest of everything read from the reader
+fn sha256_hex(reader: &mut impl Read) -> io::Result<String> {
+	let mut hasher = Sha256::new();
+	let mut buffer = [0u8; 64 * 1024];
+	loop {
+		let read = reader.read(&mut buffer)?;
+		if read == 0 {
+			break;
+		}
+		hasher.update(&buffer[..read]);
+	}
+	Ok(data_encoding::HEXLOWER.encode(&hasher.finalize()))
+}
+
+/// Verify that an upload has been fully received, matches its checksum (if one was given)
+/// and contains a book which Stump can process
+async fn verify_upload(session: &UploadSession, data_path: &path::Path) -> APIResult<()> {
+	if !session.is_complete() {
+		return Err(APIError::BadRequest(format!(
+			"Upload is incomplete, received {} of {} bytes",
+			session.offset, session.size
+		)));
+	}
+
+	let file_name = session.file_name.clone();
+	let expected_checksum = session.checksum.clone();
+	let data_path = data_path.to_path_buf();
+	tokio::task::spawn_blocking(move || {
+		if let Some(expected) = expected_checksum {
+			let actual = sha256_hex(&mut std::fs::File::open(&data_path)?)?;
+			if !actual.eq_ignore_ascii_case(&expected) {
+				return Err(APIError::BadRequest(
+					"Uploaded file does not match the expected checksum".to_string(),
+				));
+			}
+		}
+		validate_book_contents(&file_name, &mut std::fs::File::open(&data_path)?)
+	})
+	.await
+	.map_err(|e| APIError::InternalServerError(e.to_string()))?
+}
+
+fn upload_response(session: UploadSession) -> impl IntoResponse {
+	(
+		[(UPLOAD_OFFSET_HEADER, session.offset.to_string())],
+		Json(session),
+	)
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/upload/libraries/{id}/sessions",
+	tag = "library",
+	request_body = CreateUploadSession,
+	params(
+		("id" = String, Path, description = "The library ID"),
+	),
+	responses(
+		(status = 201, description = "Successfully started upload", body = UploadSession),
+		(status = 400, description = "Bad request"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Library not found"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Start a resumable upload of a single book to a library
+pub(super) async fn create_upload_session(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(input): Json<CreateUploadSession>,
+) -> APIResult<impl IntoResponse> {
+	let user = req.user_and_enforce_permissions(&[
+		UserPermission::UploadFile,
+		UserPermission::ManageLibrary,
+	])?;
+
+	let library = get_library(&ctx.db, &id, &user).await?;
+	let placement_path = get_placement_path(&input.place_at, &library)?;
+
+	// The file name must not be able to escape the placement directory
+	let file_name = path::Path::new(&input.file_name)
+		.file_name()
+		.and_then(|name| name.to_str())
+		.filter(|name| *name == input.file_name)
+		.ok_or_else(|| APIError::BadRequest("Invalid file name".to_string()))?;
+	validate_book_extension(file_name)?;
+
+	validate_upload_size(input.size, ctx.config.max_file_upload_size)?;
+	if let Some(checksum) = &input.checksum {
+		let is_valid =
+			checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit());
+		if !is_valid {
+			return Err(APIError::BadRequest(
+				"Checksum must be a hex-encoded SHA-256 digest".to_string(),
+			));
+		}
+	}
+
+	if !fs::metadata(&placement_path).await?.is_dir() {
+		return Err(APIError::BadRequest(
+			"Book uploads must be placed at an existing directory.".to_string(),
+		));
+	}
+	let target_path = placement_path.join(file_name);
+	if fs::metadata(&target_path).await.is_ok() {
+		return Err(APIError::BadRequest(format!(
+			"File already exists at {target_path:?}",
+		)));
+	}
+
+	let store = UploadSessionStore::new(&ctx);
+	if let Err(error) = store.remove_expired().await {
+		tracing::error!(?error, "Failed to remove expired upload sessions");
+	}
+
+	let session = UploadSession {
+		id: Alphanumeric.sample_string(&mut rand::thread_rng(), 32),
+		library_id: id,
+		user_id: user.id,
+		file_name: file_name.to_string(),
+		placement_path: placement_path.to_string_lossy().to_string(),
+		size: input.size,
+		offset: 0,
+		checksum: input.checksum,
+		created_at: Utc::now().to_rfc3339(),
+	};
+	store.create(&session).await?;
+
+	Ok((StatusCode::CREATED, upload_response(session)))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/upload/sessions/{id}",
+	tag = "library",
+	params(
+		("id" = String, Path, description = "The upload session ID"),
+	),
+	responses(
+		(status = 200, description = "Successfully fetched upload", body = UploadSession),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Upload session not found"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Get the state of an upload, most importantly the offset it should be resumed from
+pub(super) async fn get_upload_session(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<impl IntoResponse> {
+	let user = req.user_and_enforce_permissions(&[UserPermission::UploadFile])?;
+	let session = UploadSessionStore::new(&ctx).get(&id, &user.id).await?;
+	Ok(upload_response(session))
+}
+
+#[utoipa::path(
+	patch,
+	path = "/api/v1/upload/sessions/{id}",
+	tag = "library",
+	request_body(content_type = "application/offset+octet-stream", content = Vec<u8>),
+	params(
+		("id" = String, Path, description = "The upload session ID"),
+		("Upload-Offset" = u64, Header, description = "The offset the chunk starts at"),
+		("Upload-Checksum" = Option<String>, Header, description = "The checksum of the chunk, as `sha256 <base64 digest>`"),
+	),
+	responses(
+		(status = 200, description = "Successfully received chunk", body = UploadSession),
+		(status = 400, description = "Invalid chunk, offset mismatch or concurrent chunk"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Upload session not found"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Append a chunk of the file to an upload. The chunk must start at the current offset of
+/// the upload, otherwise it is rejected and the client should resume from the current offset.
+pub(super) async fn upload_chunk(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> APIResult<impl IntoResponse> {
+	let user = req.user_and_enforce_permissions(&[UserPermission::UploadFile])?;
+
+	let offset = headers
+		.get(UPLOAD_OFFSET_HEADER)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.parse::<u64>().ok())
+		.ok_or_else(|| {
+			APIError::BadRequest(format!("Missing or invalid {UPLOAD_OFFSET_HEADER}"))
+		})?;
+	let chunk_checksum = headers
+		.get(UPLOAD_CHECKSUM_HEADER)
+		.map(|value| {
+			value
+				.to_str()
+				.map_err(|_| {
+					APIError::BadRequest(format!(
+						"Invalid {UPLOAD_CHECKSUM_HEADER} header"
+					))
+				})
+				.and_then(parse_chunk_checksum)
+		})
+		.transpose()?;
+
+	let _guard = ActiveSessionGuard::acquire(&id)?;
+	let store = UploadSessionStore::new(&ctx);
+	let mut session = store.get(&id, &user.id).await?;
+
+	if offset != session.offset {
+		return Err(APIError::BadRequest(format!(
+			"Chunk offset {offset} does not match the upload offset {}",
+			session.offset
+		)));
+	}
+	// The limit may have been lowered since the upload was started
+	validate_upload_size(session.size, ctx.config.max_file_upload_size)?;
+	if session.offset.saturating_add(body.len() as u64) > session.size {
+		return Err(APIError::BadRequest(
+			"Chunk exceeds the declared size of the upload".to_string(),
+		));
+	}
+	if let Some(expected) = chunk_checksum {
+		if Sha256::digest(&body).as_slice() != expected.as_slice() {
+			return Err(APIError::BadRequest(
+				"Chunk does not match the provided checksum".to_string(),
+			));
+		}
+	}
+
+	let mut file = fs::OpenOptions::new()
+		.append(true)
+		.open(store.data_path(&session))
+		.await?;
+	file.write_all(&body).await?;
+	file.flush().await?;
+	session.offset += body.len() as u64;
+
+	Ok(upload_response(session))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/upload/sessions/{id}/preview",
+	tag = "library",
+	params(
+		("id" = String, Path, description = "The upload session ID"),
+	),
+	responses(
+		(status = 200, description = "Successfully previewed upload", body = UploadPreview),
+		(status = 400, description = "Upload is incomplete or invalid"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Upload session not found"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Process a fully received upload without placing it in the library, to preview the
+/// detected metadata and the series the book will be added to
+pub(super) async fn preview_upload(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<UploadPreview>> {
+	let user = req.user_and_enforce_permissions(&[UserPermission::UploadFile])?;
+
+	let store = UploadSessionStore::new(&ctx);
+	let session = store.get(&id, &user.id).await?;
+	let data_path = store.data_path(&session);
+	verify_upload(&session, &data_path).await?;
+
+	let library = get_library(&ctx.db, &session.library_id, &user).await?;
+	let options = FileProcessorOptions {
+		convert_rar_to_zip: false,
+		delete_conversion_source: false,
+		generate_file_hashes: false,
+		generate_koreader_hashes: false,
+		process_metadata: true,
+	};
+	let config = ctx.config.clone();
+	let processed =
+		tokio::task::spawn_blocking(move || process(&data_path, options, &config))
+			.await
+			.map_err(|e| APIError::InternalServerError(e.to_string()))?
+			.map_err(|e| {
+				APIError::BadRequest(format!("Failed to process upload: {e}"))
+			})?;
+
+	let placement_path = PathBuf::from(&session.placement_path);
+	let series =
+		find_target_series(&ctx.db, &session.library_id, &library, &placement_path)
+			.await?
+			.map(Series::from);
+
+	Ok(Json(UploadPreview {
+		path: placement_path
+			.join(&session.file_name)
+			.to_string_lossy()
+			.to_string(),
+		file_name: session.file_name,
+		pages: processed.pages,
+		metadata: processed.metadata,
+		series,
+	}))
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/upload/sessions/{id}/complete",
+	tag = "library",
+	params(
+		("id" = String, Path, description = "The upload session ID"),
+	),
+	responses(
+		(status = 200, description = "Successfully completed upload", body = CompletedUpload),
+		(status = 400, description = "Upload is incomplete or invalid"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Upload session not found"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Complete an upload by moving the file into the library and scanning it. When the book
+/// belongs to an existing series, only that series is scanned.
+pub(super) async fn complete_upload(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<CompletedUpload>> {
+	let user = req.user_and_enforce_permissions(&[
+		UserPermission::UploadFile,
+		UserPermission::ManageLibrary,
+	])?;
+
+	let _guard = ActiveSessionGuard::acquire(&id)?;
+	let store = UploadSessionStore::new(&ctx);
+	let session = store.get(&id, &user.id).await?;
+	let data_path = store.data_path(&session);
+	verify_upload(&session, &data_path).await?;
+
+	let library = get_library(&ctx.db, &session.library_id, &user).await?;
+	let placement_path = PathBuf::from(&session.placement_path);
+	let target_path = placement_path.join(&session.file_name);
+	// We want to prevent overwriting something that already exists
+	if fs::metadata(&target_path).await.is_ok() {
+		return Err(APIError::BadRequest(format!(
+			"File already exists at {target_path:?}",
+		)));
+	}
+
+	// Renaming fails across filesystems, in which case the file is copied instead
+	if fs::rename(&data_path, &target_path).await.is_err() {
+		fs::copy(&data_path, &target_path).await?;
+	}
+	store.remove(&session).await?;
+
+	let series =
+		enqueue_targeted_scan(&ctx, &session.library_id, &library, &placement_path)
+			.await?
+			.map(Series::from);
+
+	Ok(Json(CompletedUpload {
+		path: target_path.to_string_lossy().to_string(),
+		series,
+	}))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/v1/upload/sessions/{id}",
+	tag = "library",
+	params(
+		("id" = String, Path, description = "The upload session ID"),
+	),
+	responses(
+		(status = 200, description = "Successfully aborted upload"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Upload session not found"),
+		(status = 500, description = "Internal server error")
+	)
+)]
+/// Abort an upload, discarding any data received so far
+pub(super) async fn abort_upload_session(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+) -> APIResult<Json<()>> {
+	let user = req.user_and_enforce_permissions(&[UserPermission::UploadFile])?;
+
+	let _guard = ActiveSessionGuard::acquire(&id)?;
+	let store = UploadSessionStore::new(&ctx);
+	let session = store.get(&id, &user.id).await?;
+	store.remove(&session).await?;
+
+	Ok(Json(()))
+}
+
+/// Check the declared size of an upload against the upload limit of the server. The body
+/// limit only applies to each chunk, so this is what keeps a chunked upload within it.
+fn validate_upload_size(size: u64, max_file_upload_size: usize) -> APIResult<()> {
+	if size == 0 {
+		return Err(APIError::BadRequest(
+			"Uploaded files must not be empty".into(),
+		));
+	}
+	if size > max_file_upload_size as u64 {
+		return Err(APIError::BadRequest(format!(
+			"Uploaded files must not be larger than {max_file_upload_size} bytes"
+		)));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_validate_upload_size() {
+		assert!(validate_upload_size(1024, 1024).is_ok());
+		assert!(matches!(
+			validate_upload_size(0, 1024),
+			Err(APIError::BadRequest(_))
+		));
+		assert!(matches!(
+			validate_upload_size(1025, 1024),
+			Err(APIError::BadRequest(_))
+		));
+	}
+
+	#[test]
+	fn test_parse_chunk_checksum() {
+		let digest = Sha256::digest(b"chunk");
+		let header = format!("sha256 {}", STANDARD.encode(digest));
+		assert_eq!(parse_chunk_checksum(&header).unwrap(), digest.to_vec());
+
+		assert!(parse_chunk_checksum("md5 abc").is_err());
+		assert!(parse_chunk_checksum("sha256").is_err());
+		assert!(parse_chunk_checksum("sha256 not-base64!").is_err());
+	}
+
+	#[test]
+	fn test_sha256_hex() {
+		let digest = sha256_hex(&mut "abc".as_bytes()).unwrap();
+		assert_eq!(
+			digest,
+			"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+		);
+	}
+
+	#[test]
+	fn test_active_session_guard() {
+		let guard = ActiveSessionGuard::acquire("session").unwrap();
+		assert!(ActiveSessionGuard::acquire("session").is_err());
+		drop(guard);
+		assert!(ActiveSessionGuard::acquire("session").is_ok());
+	}
+}
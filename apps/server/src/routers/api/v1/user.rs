@@ -3,7 +3,7 @@ use std::{fs::File, io::Write};
 use axum::{
 	extract::{DefaultBodyLimit, Multipart, Path, State},
 	middleware,
-	routing::{delete, get, put},
+	routing::{delete, get, post, put},
 	Extension, Json, Router,
 };
 use axum_extra::extract::Query;
@@ -14,15 +14,21 @@ use stump_core::{
 	config::StumpConfig,
 	db::{
 		entity::{
-			AgeRestriction, Arrangement, LoginActivity, NavigationItem, SupportedFont,
-			User, UserPermission, UserPreferences,
+			AgeRestriction, Arrangement, DigestFrequency, LoginActivity, NavigationItem,
+			NewBooksDigestSubscription, SupportedFont, User, UserNotification,
+			UserPermission, UserPreferences,
 		},
 		query::pagination::{Pageable, Pagination, PaginationQuery},
 	},
+	digest::{
+		generate_confirmation_code, is_valid_confirmation_code, send_digest_confirmation,
+		NewBooksDigestConfirmationData,
+	},
 	filesystem::{get_unknown_image, ContentType, FileParts, PathUtils},
+	is_valid_email,
 	prisma::{
-		age_restriction, session, user, user_login_activity, user_preferences,
-		PrismaClient,
+		age_restriction, new_books_digest_subscription, session, user,
+		user_login_activity, user_notification, user_preferences, PrismaClient,
 	},
 };
 use tokio::fs;
@@ -50,6 +56,15 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 			Router::new()
 				.route("/", put(update_current_user))
 				.route("/preferences", put(update_current_user_preferences))
+				.route(
+					"/new-books-digest",
+					get(get_new_books_digest)
+						.put(update_new_books_digest)
+						.delete(delete_new_books_digest),
+				)
+				.route("/new-books-digest/confirm", post(confirm_new_books_digest))
+				.route("/notifications", get(get_notifications))
+				.route("/notifications/{id}/read", put(mark_notification_read))
 				.route(
 					"/navigation-arrangement",
 					get(get_navigation_arrangement).put(update_navigation_arrangement),
@@ -711,6 +726,319 @@ async fn update_navigation_arrangement(
 	Ok(Json(input))
 }
 
+#[utoipa::path(
+	get,
+	path = "/api/v1/users/me/new-books-digest",
+	tag = "user",
+	responses(
+		(status = 200, description = "Successfully fetched the new books digest subscription", body = Option<NewBooksDigestSubscription>),
+		(status = 401, description = "Unauthorized"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Get the new books digest subscription of the current user, if they opted in to it
+async fn get_new_books_digest(
+	Extension(req): Extension<RequestContext>,
+	State(ctx): State<AppState>,
+) -> APIResult<Json<Option<NewBooksDigestSubscription>>> {
+	let user = req.user();
+
+	let subscription = ctx
+		.db
+		.new_books_digest_subscription()
+		.find_unique(new_books_digest_subscription::user_id::equals(
+			user.id.clone(),
+		))
+		.exec()
+		.await?;
+
+	Ok(Json(subscription.map(NewBooksDigestSubscription::from)))
+}
+
+#[derive(Deserialize, Type, ToSchema)]
+pub struct UpdateNewBooksDigestSubscription {
+	/// The address the digest is sent to
+	pub email: String,
+	/// How often the digest is sent
+	#[serde(default)]
+	pub frequency: DigestFrequency,
+}
+
+#[utoipa::path(
+	put,
+	path = "/api/v1/users/me/new-books-digest",
+	tag = "user",
+	request_body = UpdateNewBooksDigestSubscription,
+	responses(
+		(status = 200, description = "Successfully opted in to the new books digest", body = NewBooksDigestSubscription),
+		(status = 400, description = "Bad request"),
+		(status = 401, description = "Unauthorized"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Opt the current user in to the new books digest, or update their existing subscription.
+/// A code is emailed to a new address, and no digest is sent until it is confirmed with
+/// that code. The first digest covers the books added after the address was confirmed.
+async fn update_new_books_digest(
+	Extension(req): Extension<RequestContext>,
+	State(ctx): State<AppState>,
+	Json(input): Json<UpdateNewBooksDigestSubscription>,
+) -> APIResult<Json<NewBooksDigestSubscription>> {
+	let user = req.user();
+	let client = &ctx.db;
+
+	let email = input.email.trim().to_string();
+	if !is_valid_email(&email) {
+		return Err(APIError::BadRequest(format!(
+			"\"{email}\" is not a valid email address"
+		)));
+	}
+
+	let existing_subscription = client
+		.new_books_digest_subscription()
+		.find_unique(new_books_digest_subscription::user_id::equals(
+			user.id.clone(),
+		))
+		.exec()
+		.await?;
+	let is_confirmed_address = existing_subscription
+		.as_ref()
+		.is_some_and(|s| s.confirmed_at.is_some() && s.email == email);
+
+	if is_confirmed_address {
+		let subscription = client
+			.new_books_digest_subscription()
+			.update(
+				new_books_digest_subscription::user_id::equals(user.id.clone()),
+				vec![new_books_digest_subscription::frequency::set(
+					input.frequency.to_string(),
+				)],
+			)
+			.exec()
+			.await?;
+		return Ok(Json(NewBooksDigestSubscription::from(subscription)));
+	}
+
+	let (code, code_hash) = generate_confirmation_code();
+	let sent_at = Utc::now();
+	let pending_params = || {
+		vec![
+			new_books_digest_subscription::frequency::set(input.frequency.to_string()),
+			new_books_digest_subscription::confirmed_at::set(None),
+			new_books_digest_subscription::last_digest_at::set(None),
+			new_books_digest_subscription::confirmation_code_hash::set(Some(
+				code_hash.clone(),
+			)),
+			new_books_digest_subscription::confirmation_sent_at::set(Some(
+				sent_at.into(),
+			)),
+		]
+	};
+	let subscription = client
+		.new_books_digest_subscription()
+		.upsert(
+			new_books_digest_subscription::user_id::equals(user.id.clone()),
+			new_books_digest_subscription::create(
+				email.clone(),
+				user::id::equals(user.id.clone()),
+				pending_params(),
+			),
+			pending_params()
+				.into_iter()
+				.chain([new_books_digest_subscription::email::set(email.clone())])
+				.collect(),
+		)
+		.exec()
+		.await?;
+
+	send_digest_confirmation(
+		client,
+		&ctx.config,
+		NewBooksDigestConfirmationData::new(user.username.clone(), code),
+		&email,
+	)
+	.await?;
+
+	Ok(Json(NewBooksDigestSubscription::from(subscription)))
+}
+
+#[derive(Deserialize, Type, ToSchema)]
+pub struct ConfirmNewBooksDigest {
+	/// The code which was emailed to the address of the subscription
+	pub code: String,
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/v1/users/me/new-books-digest/confirm",
+	tag = "user",
+	request_body = ConfirmNewBooksDigest,
+	responses(
+		(status = 200, description = "Successfully confirmed the address of the new books digest", body = NewBooksDigestSubscription),
+		(status = 400, description = "Invalid or expired confirmation code"),
+		(status = 401, description = "Unauthorized"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Confirm the address of the current user's new books digest with the code which was
+/// emailed to it
+async fn confirm_new_books_digest(
+	Extension(req): Extension<RequestContext>,
+	State(ctx): State<AppState>,
+	Json(input): Json<ConfirmNewBooksDigest>,
+) -> APIResult<Json<NewBooksDigestSubscription>> {
+	let user = req.user();
+	let client = &ctx.db;
+
+	let subscription = client
+		.new_books_digest_subscription()
+		.find_unique(new_books_digest_subscription::user_id::equals(
+			user.id.clone(),
+		))
+		.exec()
+		.await?
+		.filter(|s| is_valid_confirmation_code(s, &input.code, Utc::now()))
+		.ok_or_else(|| {
+			APIError::BadRequest("Invalid or expired confirmation code".to_string())
+		})?;
+
+	let subscription = client
+		.new_books_digest_subscription()
+		.update(
+			new_books_digest_subscription::id::equals(subscription.id),
+			vec![
+				new_books_digest_subscription::confirmed_at::set(Some(Utc::now().into())),
+				new_books_digest_subscription::confirmation_code_hash::set(None),
+				new_books_digest_subscription::confirmation_sent_at::set(None),
+			],
+		)
+		.exec()
+		.await?;
+
+	Ok(Json(NewBooksDigestSubscription::from(subscription)))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/v1/users/me/new-books-digest",
+	tag = "user",
+	responses(
+		(status = 200, description = "Successfully opted out of the new books digest", body = Option<NewBooksDigestSubscription>),
+		(status = 401, description = "Unauthorized"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Opt the current user out of the new books digest
+async fn delete_new_books_digest(
+	Extension(req): Extension<RequestContext>,
+	State(ctx): State<AppState>,
+) -> APIResult<Json<Option<NewBooksDigestSubscription>>> {
+	let user = req.user();
+	let client = &ctx.db;
+
+	let subscription = client
+		.new_books_digest_subscription()
+		.find_unique(new_books_digest_subscription::user_id::equals(
+			user.id.clone(),
+		))
+		.exec()
+		.await?;
+	if subscription.is_some() {
+		client
+			.new_books_digest_subscription()
+			.delete(new_books_digest_subscription::user_id::equals(
+				user.id.clone(),
+			))
+			.exec()
+			.await?;
+	}
+
+	Ok(Json(subscription.map(NewBooksDigestSubscription::from)))
+}
+
+/// The number of notifications returned for the current user
+const USER_NOTIFICATION_LIMIT: i64 = 50;
+
+#[utoipa::path(
+	get,
+	path = "/api/v1/users/me/notifications",
+	tag = "user",
+	responses(
+		(status = 200, description = "Successfully fetched notifications", body = [UserNotification]),
+		(status = 401, description = "Unauthorized"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Get the most recent notifications of the current user, newest first
+async fn get_notifications(
+	Extension(req): Extension<RequestContext>,
+	State(ctx): State<AppState>,
+) -> APIResult<Json<Vec<UserNotification>>> {
+	let user = req.user();
+
+	let notifications = ctx
+		.db
+		.user_notification()
+		.find_many(vec![user_notification::user_id::equals(user.id.clone())])
+		.order_by(user_notification::created_at::order(Direction::Desc))
+		.take(USER_NOTIFICATION_LIMIT)
+		.exec()
+		.await?
+		.into_iter()
+		.map(UserNotification::try_from)
+		.collect::<Result<Vec<_>, _>>()?;
+
+	Ok(Json(notifications))
+}
+
+#[utoipa::path(
+	put,
+	path = "/api/v1/users/me/notifications/{id}/read",
+	tag = "user",
+	params(
+		("id" = String, Path, description = "The ID of the notification")
+	),
+	responses(
+		(status = 200, description = "Successfully marked the notification as read", body = UserNotification),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Notification not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+/// Mark a notification of the current user as read
+async fn mark_notification_read(
+	Path(id): Path<String>,
+	Extension(req): Extension<RequestContext>,
+	State(ctx): State<AppState>,
+) -> APIResult<Json<UserNotification>> {
+	let user = req.user();
+	let client = &ctx.db;
+
+	let notification = client
+		.user_notification()
+		.find_first(vec![
+			user_notification::id::equals(id.clone()),
+			user_notification::user_id::equals(user.id.clone()),
+		])
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound("Notification not found".to_string()))?;
+	if notification.read_at.is_some() {
+		return Ok(Json(UserNotification::try_from(notification)?));
+	}
+
+	let notification = client
+		.user_notification()
+		.update(
+			user_notification::id::equals(id),
+			vec![user_notification::read_at::set(Some(Utc::now().into()))],
+		)
+		.exec()
+		.await?;
+
+	Ok(Json(UserNotification::try_from(notification)?))
+}
+
 #[derive(Deserialize, Type, ToSchema)]
 pub struct DeleteUser {
 	pub hard_delete: Option<bool>,
@@ -5,12 +5,13 @@ use axum::{
 	routing::{get, put},
 	Extension, Router,
 };
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Utc};
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use stump_core::{
 	db::entity::{
 		macros::{finished_session_koreader, reading_session_koreader},
-		UserPermission,
+		ReadingTime, UserPermission,
 	},
 	prisma::{
 		active_reading_session, finished_reading_session, media,
@@ -243,6 +244,7 @@ async fn put_progress(
 		.ok_or_else(|| APIError::NotFound("Book not found".to_string()))?;
 
 	let is_completed = percentage == 1.0;
+	let now: DateTime<FixedOffset> = Utc::now().into();
 	let document_cpy = document.clone();
 	let (active_session, finished_session) = client
 		._transaction()
@@ -270,6 +272,8 @@ async fn put_progress(
 				])
 				.exec()
 				.await?;
+			let reading_time =
+				ReadingTime::from(existing_active_session.as_ref()).heartbeat(now);
 
 			if is_completed {
 				if let Some(ref active_session) = existing_active_session {
@@ -290,7 +294,10 @@ async fn put_progress(
 						user::id::equals(user.id.clone()),
 						vec![finished_reading_session::device::connect(
 							registered_reading_device::id::equals(device_id.clone()),
-						)],
+						)]
+						.into_iter()
+						.chain(reading_time.into_finished_set_params())
+						.collect(),
 					)
 					.exec()
 					.await
@@ -324,7 +331,10 @@ async fn put_progress(
 						active_reading_session::device::connect(
 							registered_reading_device::id::equals(device_id.clone()),
 						),
-					],
+					]
+					.into_iter()
+					.chain(reading_time.into_active_set_params())
+					.collect::<Vec<_>>(),
 					[native_progress_set_param],
 				);
 
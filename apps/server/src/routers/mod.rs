@@ -11,7 +11,9 @@ mod utoipa;
 #[allow(dead_code)]
 mod ws;
 
-pub(crate) use api::v1::auth::enforce_max_sessions;
+pub(crate) use api::v1::{
+	auth::enforce_max_sessions, two_factor::is_two_factor_required,
+};
 pub(crate) use spa::relative_favicon_path;
 
 pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
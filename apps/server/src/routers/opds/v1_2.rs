@@ -8,11 +8,14 @@ use prisma_client_rust::or;
 use prisma_client_rust::{chrono, Direction};
 use serde::{Deserialize, Serialize};
 use stump_core::{
-	db::{entity::UserPermission, query::pagination::PageQuery},
+	db::{
+		entity::{ReadingTime, UserPermission},
+		query::pagination::PageQuery,
+	},
 	filesystem::{
-		get_page_async,
+		get_page_with_options_async,
 		image::{GenericImageProcessor, ImageProcessor, ImageProcessorOptions},
-		ContentType,
+		ContentType, PageOptions,
 	},
 	opds::v1_2::{
 		entry::{IntoOPDSEntry, OPDSEntryBuilder, OpdsEntry},
@@ -130,6 +133,12 @@ fn pagination_bounds(page: i64, page_size: i64) -> (i64, i64) {
 	(skip, page_size)
 }
 
+/// Fetch the config of the library a book is in, so its OPDS entry can advertise the format
+/// its PDF pages are rendered to
+fn media_library_config_fetch() -> media::series::Fetch {
+	media::series::fetch().with(series::library::fetch().with(library::config::fetch()))
+}
+
 fn catalog_url(req_ctx: &RequestContext, path: &str) -> String {
 	if let Some(api_key) = req_ctx.api_key() {
 		format!("/opds/{}/v1.2/{}", api_key, path)
@@ -256,6 +265,7 @@ async fn keep_reading(
 		.with(media::active_user_reading_sessions::fetch(
 			in_progress_filter,
 		))
+		.with(media_library_config_fetch())
 		.order_by(media::name::order(Direction::Asc))
 		.exec()
 		.await?;
@@ -606,7 +616,8 @@ async fn get_series_by_id(
 					[age_restrictions.clone()],
 				))
 				.with(
-					series::media::fetch(vec![])
+					series::media::fetch(vec![media::deleted_at::equals(None)])
+						.with(media_library_config_fetch())
 						.skip(skip)
 						.take(take)
 						.order_by(media::name::order(Direction::Asc)),
@@ -618,6 +629,7 @@ async fn get_series_by_id(
 				.media()
 				.count(vec![
 					media::series_id::equals(Some(id.clone())),
+					media::deleted_at::equals(None),
 					media::series::is(chain_optional_iter(
 						[series::id::equals(id.clone())],
 						[age_restrictions],
@@ -734,6 +746,10 @@ async fn get_book_page(
 		.ok_or(APIError::NotFound(String::from("Book not found")))?;
 	let is_completed = book.pages == correct_page;
 
+	// Streaming pages is the only signal of reading activity OPDS 1.2 clients give us, so each
+	// page request is treated as a heartbeat
+	let now: chrono::DateTime<chrono::FixedOffset> = chrono::Utc::now().into();
+
 	if is_completed {
 		let deleted_session = client
 			.active_reading_session()
@@ -746,18 +762,33 @@ async fn get_book_page(
 			.ok();
 		tracing::trace!(?deleted_session, "Deleted active reading session");
 
+		let reading_time = ReadingTime::from(deleted_session.as_ref()).heartbeat(now);
 		let finished_session = client
 			.finished_reading_session()
 			.create(
 				deleted_session.map(|s| s.started_at).unwrap_or_default(),
 				media::id::equals(id.clone()),
 				user::id::equals(user.id.clone()),
-				vec![],
+				reading_time.into_finished_set_params(),
 			)
 			.exec()
 			.await?;
 		tracing::trace!(?finished_session, "Created finished reading session");
 	} else {
+		let existing_session = client
+			.active_reading_session()
+			.find_unique(active_reading_session::user_id_media_id(
+				user.id.clone(),
+				id.clone(),
+			))
+			.exec()
+			.await?;
+		let reading_time = ReadingTime::from(existing_session.as_ref()).heartbeat(now);
+		let set_params = [active_reading_session::page::set(Some(correct_page))]
+			.into_iter()
+			.chain(reading_time.into_active_set_params())
+			.collect::<Vec<_>>();
+
 		client
 			.active_reading_session()
 			.upsert(
@@ -765,16 +796,22 @@ async fn get_book_page(
 				(
 					media::id::equals(id.clone()),
 					user::id::equals(user.id.clone()),
-					vec![active_reading_session::page::set(Some(correct_page))],
+					set_params.clone(),
 				),
-				vec![active_reading_session::page::set(Some(correct_page))],
+				set_params,
 			)
 			.exec()
 			.await?;
 	}
 
-	let (content_type, image_buffer) =
-		get_page_async(book.path.as_str(), correct_page, &ctx.config).await?;
+	let page_options = PageOptions::for_book(client, &id, &book.path).await?;
+	let (content_type, image_buffer) = get_page_with_options_async(
+		book.path.as_str(),
+		correct_page,
+		page_options,
+		&ctx.config,
+	)
+	.await?;
 	handle_opds_image_response(content_type, image_buffer)
 }
 
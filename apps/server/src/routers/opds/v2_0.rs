@@ -8,7 +8,11 @@ use axum::{
 	routing::get,
 	Extension, Json, Router,
 };
-use prisma_client_rust::{and, operator, or, Direction};
+use prisma_client_rust::{
+	and,
+	chrono::{DateTime, FixedOffset, Utc},
+	operator, or, Direction,
+};
 use serde::{Deserialize, Serialize};
 use stump_core::{
 	db::{
@@ -21,11 +25,11 @@ use stump_core::{
 				apply_media_age_restriction,
 				apply_media_library_not_hidden_for_user_filter,
 			},
-			User, UserPermission,
+			EpubPositionList, ReadingTime, User, UserPermission,
 		},
 		query::pagination::PageQuery,
 	},
-	filesystem::get_page_async,
+	filesystem::{get_page_with_options_async, PageOptions},
 	opds::v2_0::{
 		authentication::{
 			OPDSAuthenticationDocument, OPDSAuthenticationDocumentBuilder,
@@ -44,7 +48,8 @@ use stump_core::{
 		reading_session_opds_progression,
 	},
 	prisma::{
-		active_reading_session, library, media, media_metadata, series, series_metadata,
+		active_reading_session, epub_positions, finished_reading_session, library, media,
+		media_metadata, registered_reading_device, series, series_metadata, user,
 	},
 	Ctx,
 };
@@ -110,8 +115,10 @@ pub(crate) fn mount(app_state: AppState) -> Router<AppState> {
 								.route("/", get(get_book_by_id))
 								.route("/thumbnail", get(get_book_thumbnail))
 								.route("/pages/{page}", get(get_book_page))
-								// TODO: PUT progression
-								.route("/progression", get(get_book_progression))
+								.route(
+									"/progression",
+									get(get_book_progression).put(put_book_progression),
+								)
 								.route("/file", get(download_book)),
 						),
 				),
@@ -1102,7 +1109,7 @@ async fn fetch_book_page_for_user(
 	// - The book is not hidden from the user via the library
 	// - The book is not restricted by age
 	let where_params = chain_optional_iter(
-		[media::id::equals(book_id)]
+		[media::id::equals(book_id.clone())]
 			.into_iter()
 			.chain(apply_media_library_not_hidden_for_user_filter(user))
 			.collect::<Vec<media::WhereParam>>(),
@@ -1119,8 +1126,14 @@ async fn fetch_book_page_for_user(
 		.await?
 		.ok_or(APIError::NotFound(String::from("Book not found")))?;
 
-	let (content_type, image_buffer) =
-		get_page_async(PathBuf::from(book.path), page, &ctx.config).await?;
+	let page_options = PageOptions::for_book(client, &book_id, &book.path).await?;
+	let (content_type, image_buffer) = get_page_with_options_async(
+		PathBuf::from(book.path),
+		page,
+		page_options,
+		&ctx.config,
+	)
+	.await?;
 	Ok(ImageResponse::new(content_type, image_buffer))
 }
 
@@ -1214,6 +1227,161 @@ async fn get_book_progression(
 	Ok(Json(OPDSProgression::new(reading_session, link_finalizer)?))
 }
 
+/// A route handler which updates the progression of a book for a user. The progression is
+/// treated as reading activity, so it also accrues reading time on the session.
+#[tracing::instrument(skip(ctx, progression))]
+async fn put_book_progression(
+	Path(id): Path<String>,
+	State(ctx): State<AppState>,
+	Extension(req): Extension<RequestContext>,
+	Json(progression): Json<OPDSProgression>,
+) -> APIResult<Json<OPDSProgression>> {
+	let client = &ctx.db;
+	let user = req.user();
+
+	let book = client
+		.media()
+		.find_first(
+			[media::id::equals(id.clone())]
+				.into_iter()
+				.chain(apply_media_restrictions_for_user(user))
+				.collect(),
+		)
+		.exec()
+		.await?
+		.ok_or(APIError::NotFound(String::from("Book not found")))?;
+
+	let total_progression = progression.total_progression();
+	if total_progression.is_some_and(|p| !(0.0..=1.0).contains(&p)) {
+		return Err(APIError::BadRequest("Invalid progression".to_string()));
+	}
+	let page = progression.position();
+	// The pages of an EPUB are positions, so whichever of the position and progression the
+	// client didn't report can be derived from the other when the book has been analyzed
+	let (page, total_progression) = if book.extension.eq_ignore_ascii_case("epub") {
+		client
+			.epub_positions()
+			.find_unique(epub_positions::media_id::equals(id.clone()))
+			.exec()
+			.await?
+			.and_then(|stored| EpubPositionList::from_stored(&stored.positions).ok())
+			.map_or((page, total_progression), |positions| {
+				positions.resolve_progress(page, total_progression)
+			})
+	} else {
+		(page, total_progression)
+	};
+	let is_completed = total_progression.is_some_and(|p| p >= 1.0)
+		|| page.is_some_and(|page| page >= book.pages && book.pages > 0);
+
+	let now: DateTime<FixedOffset> = Utc::now().into();
+	let user_id = user.id.clone();
+	let device = progression.device();
+	let epubcfi = progression.epubcfi();
+
+	client
+		._transaction()
+		.run(|tx| async move {
+			let device_param = match device {
+				Some((device_id, device_name)) => {
+					tx.registered_reading_device()
+						.upsert(
+							registered_reading_device::id::equals(device_id.clone()),
+							(
+								device_name.clone(),
+								vec![registered_reading_device::id::set(
+									device_id.clone(),
+								)],
+							),
+							vec![registered_reading_device::name::set(device_name)],
+						)
+						.exec()
+						.await?;
+					Some(device_id)
+				},
+				None => None,
+			};
+
+			let existing_session = tx
+				.active_reading_session()
+				.find_unique(active_reading_session::user_id_media_id(
+					user_id.clone(),
+					id.clone(),
+				))
+				.exec()
+				.await?;
+			let reading_time =
+				ReadingTime::from(existing_session.as_ref()).heartbeat(now);
+
+			if is_completed {
+				if existing_session.is_some() {
+					tx.active_reading_session()
+						.delete(active_reading_session::user_id_media_id(
+							user_id.clone(),
+							id.clone(),
+						))
+						.exec()
+						.await?;
+				}
+
+				tx.finished_reading_session()
+					.create(
+						existing_session.map(|s| s.started_at).unwrap_or_default(),
+						media::id::equals(id.clone()),
+						user::id::equals(user_id.clone()),
+						chain_optional_iter(
+							reading_time.into_finished_set_params(),
+							[device_param.map(|device_id| {
+								finished_reading_session::device::connect(
+									registered_reading_device::id::equals(device_id),
+								)
+							})],
+						),
+					)
+					.exec()
+					.await
+					.map(|_| ())
+			} else {
+				let set_params = chain_optional_iter(
+					reading_time.into_active_set_params(),
+					[
+						page.map(|page| active_reading_session::page::set(Some(page))),
+						epubcfi
+							.map(|cfi| active_reading_session::epubcfi::set(Some(cfi))),
+						total_progression.map(|p| {
+							active_reading_session::percentage_completed::set(Some(p))
+						}),
+						device_param.map(|device_id| {
+							active_reading_session::device::connect(
+								registered_reading_device::id::equals(device_id),
+							)
+						}),
+					],
+				);
+
+				tx.active_reading_session()
+					.upsert(
+						active_reading_session::user_id_media_id(
+							user_id.clone(),
+							id.clone(),
+						),
+						(
+							media::id::equals(id.clone()),
+							user::id::equals(user_id.clone()),
+							set_params.clone(),
+						),
+						set_params,
+					)
+					.exec()
+					.await
+					.map(|_| ())
+			}
+		})
+		.await?;
+
+	Ok(Json(progression))
+}
+
 /// A route handler which downloads a book for a user.
 #[tracing::instrument(skip(ctx))]
 async fn download_book(
@@ -1,10 +1,13 @@
 use axum::middleware;
 use axum::Router;
+use stump_core::db::backup::{Backup, BackupManifest};
 use stump_core::db::entity::*;
 // TODO: investigate how to get this working for swagger...
 use stump_core::db::filter::{SmartFilterSchema as SmartFilter, *};
 use stump_core::db::query::{ordering::*, pagination::*};
+use stump_core::db::trash::PurgedTrash;
 use stump_core::filesystem::{
+	scanner::{CustomVisit, ScanConfig, ScanDiff, ScanOptions, SeriesDetection},
 	DirectoryListing, DirectoryListingFile, DirectoryListingInput,
 };
 use stump_core::job::JobStatus;
@@ -20,8 +23,18 @@ use crate::middleware::auth::auth_middleware;
 use super::api::{
 	self,
 	v1::{
-		auth::LoginOrRegisterArgs, library::*, media::individual::*, notifier::*,
-		series::*, smart_list::*, user::*, ClaimResponse, StumpVersion,
+		auth::{LoginOrRegisterArgs, LoginTwoFactorArgs},
+		backup::CreateBackup,
+		job::QueuedJob,
+		library::*,
+		media::{duplicates::*, individual::*, verification::*},
+		notifier::*,
+		oidc::*,
+		series::*,
+		smart_list::*,
+		two_factor::*,
+		user::*,
+		ClaimResponse, StumpVersion,
 	},
 };
 
@@ -37,14 +50,29 @@ use super::api::{
         api::v1::version,
         api::v1::auth::viewer,
         api::v1::auth::login,
+        api::v1::auth::login_two_factor,
         api::v1::auth::logout,
         api::v1::auth::register,
+        api::v1::backup::get_backups,
+        api::v1::backup::create_backup,
+        api::v1::backup::download_backup,
+        api::v1::oidc::get_oidc_config,
+        api::v1::oidc::oidc_authorize,
+        api::v1::oidc::oidc_callback,
+        api::v1::oidc::get_oidc_identities,
+        api::v1::oidc::delete_oidc_identity,
+        api::v1::two_factor::get_two_factor_status,
+        api::v1::two_factor::setup_two_factor,
+        api::v1::two_factor::confirm_two_factor,
+        api::v1::two_factor::regenerate_recovery_codes,
+        api::v1::two_factor::disable_two_factor,
         // TODO: epub here
         api::v1::filesystem::list_directory,
         api::v1::job::get_jobs,
         api::v1::job::delete_jobs,
         api::v1::job::delete_job_by_id,
         api::v1::job::cancel_job_by_id,
+        api::v1::job::get_job_scan_diff,
         api::v1::job::get_scheduler_config,
         api::v1::job::update_scheduler_config,
         api::v1::library::get_libraries,
@@ -57,12 +85,19 @@ use super::api::{
         api::v1::library::scan_library,
         api::v1::library::clean_library,
         api::v1::library::create_library,
+        api::v1::library::preview_library_series,
         api::v1::library::update_library,
         api::v1::library::delete_library,
         api::v1::log::get_logs,
         api::v1::log::delete_logs,
         api::v1::media::bulk::get_media,
         api::v1::media::bulk::get_duplicate_media,
+        api::v1::media::duplicates::start_duplicate_analysis,
+        api::v1::media::duplicates::get_duplicate_groups,
+        api::v1::media::duplicates::dismiss_duplicate_group,
+        api::v1::media::duplicates::resolve_duplicate_group,
+        api::v1::media::verification::start_media_verification,
+        api::v1::media::verification::get_media_verification_report,
         api::v1::media::bulk::get_in_progress_media,
         api::v1::media::bulk::get_recently_added_media,
         api::v1::media::individual::get_media_by_id,
@@ -70,10 +105,13 @@ use super::api::{
         api::v1::media::individual::convert_media,
         api::v1::media::individual::get_media_page,
         api::v1::media::individual::update_media_progress,
+        api::v1::media::individual::put_media_progress_heartbeat,
         api::v1::media::individual::get_media_progress,
         api::v1::media::individual::delete_media_progress,
         api::v1::media::individual::get_is_media_completed,
         api::v1::media::individual::put_media_complete_status,
+        api::v1::media::individual::get_media_reading_preferences,
+        api::v1::media::individual::put_media_reading_overrides,
         api::v1::media::thumbnails::get_media_thumbnail_handler,
         api::v1::metadata::get_metadata_overview,
         api::v1::metadata::get_genres_handler,
@@ -104,6 +142,8 @@ use super::api::{
         api::v1::series::get_series_media,
         api::v1::series::get_series_is_complete,
         api::v1::series::get_next_in_series,
+        api::v1::series::scan_series,
+        api::v1::series::put_series_reading_overrides,
         api::v1::smart_list::get_smart_lists,
         api::v1::smart_list::create_smart_list,
         api::v1::smart_list::get_smart_list_by_id,
@@ -118,6 +158,10 @@ use super::api::{
         api::v1::smart_list::delete_smart_list_view,
         api::v1::tag::get_tags,
         api::v1::tag::create_tags,
+        api::v1::trash::get_trashed_media,
+        api::v1::trash::restore_trashed_media,
+        api::v1::trash::purge_trashed_media_by_id,
+        api::v1::trash::empty_trash,
         api::v1::user::get_users,
         api::v1::user::get_user_login_activity,
         api::v1::user::delete_user_login_activity,
@@ -128,31 +172,46 @@ use super::api::{
         api::v1::user::update_user_handler,
         api::v1::user::get_user_preferences,
         api::v1::user::update_user_preferences,
+        api::v1::user::get_new_books_digest,
+        api::v1::user::update_new_books_digest,
+        api::v1::user::confirm_new_books_digest,
+        api::v1::user::delete_new_books_digest,
+        api::v1::user::get_notifications,
+        api::v1::user::mark_notification_read,
         api::v1::user::update_user_lock_status
     ),
     components(
         schemas(
             Library, LibraryConfig, Media, ReadingList, ActiveReadingSession, FinishedReadingSession, Series,
-            Tag, User, UserPreferences, LibraryPattern, LibraryScanMode, LogLevel, ClaimResponse, StumpVersion,
+            Tag, User, UserPreferences, LibraryPattern, WatcherMode, LibraryScanMode, LogLevel, ClaimResponse, StumpVersion,
             FileStatus, PageableDirectoryListing, DirectoryListing, DirectoryListingFile, CursorInfo, PageInfo,
-            PageableLibraries, PageableMedia, PageableSeries, LoginOrRegisterArgs, DirectoryListingInput, PageQuery,
+            PageableLibraries, PageableMedia, PageableSeries, LoginOrRegisterArgs, OidcConfig, OidcAuthorizeParams,
+            OidcCallbackParams, OidcIdentity, LoginTwoFactorArgs, TwoFactorStatus, SetupTwoFactor, TwoFactorSetup,
+            VerifyTwoFactorCode, TwoFactorRecoveryCodes, DisableTwoFactor, DirectoryListingInput, PageQuery,
             FilterableLibraryQuery, PaginationQuery, QueryOrder, LibraryFilter,Direction, CreateLibrary,
+            PreviewLibrarySeries, SeriesPreview, LibrarySeriesPreview, SeriesDetection,
             UpdateLibrary, APIError, MediaFilter, SeriesFilter,FilterableMediaQuery, FilterableSeriesQuery,
             LibraryStats, JobStatus, SeriesQueryRelation, CreateReadingList, UpdateUserPreferences, UpdateUser,
-            CreateTags, CleanLibraryResponse, MediaIsComplete, SeriesIsComplete, PutMediaCompletionStatus, SmartList,
+            CreateTags, CleanLibraryResponse, MediaIsComplete, SeriesIsComplete, PutMediaCompletionStatus, PutMediaProgressHeartbeat,
+            SmartList,
             SmartListMeta, SmartListItems, SmartListView, CreateOrUpdateSmartList, CreateOrUpdateSmartListView,
             SmartListItemGrouping, SmartFilter, FilterJoin, EntityVisibility, SmartListViewConfig,
             ReactTableColumnSort, ReactTableGlobalSort, MediaSmartFilter, MediaMetadataSmartFilter,
             SeriesSmartFilter, SeriesMetadataSmartFilter, LibrarySmartFilter, Notifier, CreateOrUpdateNotifier,
             PatchNotifier, LibraryBaseFilter, LibraryRelationFilter, MediaBaseFilter, MediaRelationFilter,
             SeriesBaseFilter, SeriesRelationFilter, NotifierConfig, NotifierType, ReadingListItem,
-            ReadingListVisibility, SeriesMetadataFilter
+            ReadingListVisibility, SeriesMetadataFilter, PurgedTrash, DuplicateGroup, DuplicateReason,
+            ResolveDuplicateGroup, VerifyMediaParams, ScanOptions, ScanConfig, CustomVisit, ScanDiff, QueuedJob,
+            Backup, BackupManifest, CreateBackup, ReadingOverrides, ReadingPreferences,
+            DigestFrequency, NewBooksDigestSubscription, UpdateNewBooksDigestSubscription, ConfirmNewBooksDigest,
+            UserNotification, UserNotificationKind
         )
     ),
     tags(
         (name = "util", description = "Utility API"),
         (name = "book_club", description = "Book Club API"),
         (name = "auth", description = "Authentication API"),
+        (name = "backup", description = "Backup API"),
         (name = "epub", description = "EPUB API"),
         (name = "filesystem", description = "Filesystem API"),
         (name = "job", description = "Job API"),
@@ -160,6 +219,7 @@ use super::api::{
         (name = "media", description = "Media API"),
         (name = "series", description = "Series API"),
         (name = "tag", description = "Tag API"),
+        (name = "trash", description = "Trash API"),
         (name = "reading-list", description = "Reading List API"),
         (name = "user", description = "User API"),
         (name = "opds", description = "OPDS API"),
@@ -19,6 +19,10 @@ pub fn create_prisma_user(user: &User, hashed_pass: String) -> user::Data {
 		is_locked: user.is_locked,
 		max_sessions_allowed: user.max_sessions_allowed,
 		permissions: user_permissions,
+		totp_secret: None,
+		totp_enabled_at: user.two_factor_enabled.then(|| user.created_at),
+		totp_last_used_step: None,
+		totp_recovery_codes: None,
 		reviews: None,
 		user_preferences: Some(Some(Box::new(user_preferences::Data {
 			id: user_preferences.id,
@@ -63,5 +67,6 @@ pub fn create_prisma_user(user: &User, hashed_pass: String) -> user::Data {
 		bookmarks: None,
 		media_annotations: None,
 		api_keys: None,
+		oidc_identities: None,
 	}
 }
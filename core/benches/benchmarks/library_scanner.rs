@@ -207,6 +207,8 @@ async fn setup_test(
 		id: library.id.clone(),
 		path: library.path.clone(),
 		config: Some(library.config.clone()),
+		filename_rules: vec![],
+		triggered_by: vec![],
 		options: Default::default(),
 	});
 
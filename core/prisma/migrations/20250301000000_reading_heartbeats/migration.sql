@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "reading_sessions" ADD COLUMN "last_heartbeat_at" DATETIME;
@@ -0,0 +1,5 @@
+-- AlterTable
+ALTER TABLE "users" ADD COLUMN "totp_secret" TEXT;
+ALTER TABLE "users" ADD COLUMN "totp_enabled_at" DATETIME;
+ALTER TABLE "users" ADD COLUMN "totp_last_used_step" INTEGER;
+ALTER TABLE "users" ADD COLUMN "totp_recovery_codes" TEXT;
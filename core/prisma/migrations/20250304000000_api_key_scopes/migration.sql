@@ -0,0 +1,17 @@
+-- AlterTable
+ALTER TABLE "api_keys" ADD COLUMN "scopes" BLOB;
+ALTER TABLE "api_keys" ADD COLUMN "expiry_notified_at" DATETIME;
+
+-- CreateTable
+CREATE TABLE "api_key_usages" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "used_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "method" TEXT NOT NULL,
+    "path" TEXT NOT NULL,
+    "ip_address" TEXT,
+    "api_key_id" INTEGER NOT NULL,
+    CONSTRAINT "api_key_usages_api_key_id_fkey" FOREIGN KEY ("api_key_id") REFERENCES "api_keys" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE INDEX "api_key_usages_api_key_id_used_at_idx" ON "api_key_usages"("api_key_id", "used_at");
@@ -0,0 +1,20 @@
+-- AlterTable
+ALTER TABLE "media" ADD COLUMN "cover_hash" TEXT;
+
+-- CreateTable
+CREATE TABLE "duplicate_groups" (
+    "id" TEXT NOT NULL PRIMARY KEY,
+    "reasons" TEXT NOT NULL,
+    "created_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "dismissed_at" DATETIME
+);
+
+-- CreateTable
+CREATE TABLE "duplicate_group_media" (
+    "group_id" TEXT NOT NULL,
+    "media_id" TEXT NOT NULL,
+
+    PRIMARY KEY ("group_id", "media_id"),
+    CONSTRAINT "duplicate_group_media_group_id_fkey" FOREIGN KEY ("group_id") REFERENCES "duplicate_groups" ("id") ON DELETE CASCADE ON UPDATE CASCADE,
+    CONSTRAINT "duplicate_group_media_media_id_fkey" FOREIGN KEY ("media_id") REFERENCES "media" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
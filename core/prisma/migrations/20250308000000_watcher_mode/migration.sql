@@ -0,0 +1,3 @@
+-- AlterTable
+ALTER TABLE "library_configs" ADD COLUMN "watcher_mode" TEXT NOT NULL DEFAULT 'NATIVE';
+ALTER TABLE "library_configs" ADD COLUMN "poll_interval" INTEGER;
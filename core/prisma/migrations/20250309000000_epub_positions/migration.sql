@@ -0,0 +1,10 @@
+-- CreateTable
+CREATE TABLE "epub_positions" (
+    "id" TEXT NOT NULL PRIMARY KEY,
+    "positions" TEXT NOT NULL,
+    "media_id" TEXT NOT NULL,
+    CONSTRAINT "epub_positions_media_id_fkey" FOREIGN KEY ("media_id") REFERENCES "media" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "epub_positions_media_id_key" ON "epub_positions"("media_id");
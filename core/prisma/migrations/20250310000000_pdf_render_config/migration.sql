@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "library_configs" ADD COLUMN "pdf_render_config" BLOB;
@@ -0,0 +1,3 @@
+-- AlterTable
+ALTER TABLE "media" ADD COLUMN "status_reason" TEXT;
+ALTER TABLE "media" ADD COLUMN "verified_at" DATETIME;
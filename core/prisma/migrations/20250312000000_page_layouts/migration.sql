@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "page_dimensions" ADD COLUMN "layouts" TEXT;
@@ -0,0 +1,10 @@
+-- AlterTable
+ALTER TABLE "media" ADD COLUMN "reading_direction" TEXT;
+ALTER TABLE "media" ADD COLUMN "reading_mode" TEXT;
+
+-- AlterTable
+ALTER TABLE "series" ADD COLUMN "reading_direction" TEXT;
+ALTER TABLE "series" ADD COLUMN "reading_mode" TEXT;
+
+-- AlterTable
+ALTER TABLE "media_metadata" ADD COLUMN "manga" TEXT;
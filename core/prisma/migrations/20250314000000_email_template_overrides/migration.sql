@@ -0,0 +1,6 @@
+-- CreateTable
+CREATE TABLE "email_template_overrides" (
+    "template" TEXT NOT NULL PRIMARY KEY,
+    "source" TEXT NOT NULL,
+    "updated_at" DATETIME NOT NULL
+);
@@ -0,0 +1,3 @@
+-- AlterTable
+ALTER TABLE "registered_email_devices" ADD COLUMN "target_format" TEXT NOT NULL DEFAULT 'ORIGINAL';
+ALTER TABLE "registered_email_devices" ADD COLUMN "max_image_dimension" INTEGER;
@@ -0,0 +1,13 @@
+-- CreateTable
+CREATE TABLE "new_books_digest_subscriptions" (
+    "id" TEXT NOT NULL PRIMARY KEY,
+    "email" TEXT NOT NULL,
+    "frequency" TEXT NOT NULL DEFAULT 'WEEKLY',
+    "last_digest_at" DATETIME,
+    "created_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "user_id" TEXT NOT NULL,
+    CONSTRAINT "new_books_digest_subscriptions_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "users" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "new_books_digest_subscriptions_user_id_key" ON "new_books_digest_subscriptions"("user_id");
@@ -0,0 +1,31 @@
+-- CreateTable
+CREATE TABLE "user_notifications" (
+    "id" TEXT NOT NULL PRIMARY KEY,
+    "kind" TEXT NOT NULL,
+    "message" TEXT NOT NULL,
+    "created_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "read_at" DATETIME,
+    "user_id" TEXT NOT NULL,
+    CONSTRAINT "user_notifications_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "users" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE INDEX "user_notifications_user_id_created_at_idx" ON "user_notifications"("user_id", "created_at");
+
+-- RedefineTables
+-- Usage is now aggregated per key per day, so the individual request records are dropped
+DROP TABLE "api_key_usages";
+CREATE TABLE "api_key_usages" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "day" TEXT NOT NULL,
+    "request_count" INTEGER NOT NULL DEFAULT 0,
+    "last_used_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "last_method" TEXT NOT NULL,
+    "last_path" TEXT NOT NULL,
+    "last_ip_address" TEXT,
+    "api_key_id" INTEGER NOT NULL,
+    CONSTRAINT "api_key_usages_api_key_id_fkey" FOREIGN KEY ("api_key_id") REFERENCES "api_keys" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "api_key_usages_api_key_id_day_key" ON "api_key_usages"("api_key_id", "day");
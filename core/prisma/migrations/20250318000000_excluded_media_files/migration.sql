@@ -0,0 +1,9 @@
+-- CreateTable
+CREATE TABLE "excluded_media_files" (
+    "id" TEXT NOT NULL PRIMARY KEY,
+    "path" TEXT NOT NULL,
+    "created_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "excluded_media_files_path_key" ON "excluded_media_files"("path");
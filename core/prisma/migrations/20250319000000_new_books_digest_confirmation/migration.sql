@@ -0,0 +1,4 @@
+-- AlterTable
+ALTER TABLE "new_books_digest_subscriptions" ADD COLUMN "confirmed_at" DATETIME;
+ALTER TABLE "new_books_digest_subscriptions" ADD COLUMN "confirmation_code_hash" TEXT;
+ALTER TABLE "new_books_digest_subscriptions" ADD COLUMN "confirmation_sent_at" DATETIME;
@@ -0,0 +1,5 @@
+-- Positions were counted by characters rather than bytes, so they are recomputed on analysis
+DELETE FROM "epub_positions";
+
+-- AlterTable
+ALTER TABLE "server_config" ADD COLUMN "epub_positions_backfill_complete" BOOLEAN NOT NULL DEFAULT false;
@@ -35,6 +35,29 @@ pub mod env_keys {
 	pub const MAX_IMAGE_UPLOAD_SIZE_KEY: &str = "STUMP_MAX_IMAGE_UPLOAD_SIZE";
 	pub const ENABLE_UPLOAD_KEY: &str = "STUMP_ENABLE_UPLOAD";
 	pub const MAX_FILE_UPLOAD_SIZE_KEY: &str = "STUMP_MAX_FILE_UPLOAD_SIZE";
+	pub const OIDC_ENABLED_KEY: &str = "STUMP_OIDC_ENABLED";
+	pub const OIDC_ISSUER_URL_KEY: &str = "STUMP_OIDC_ISSUER_URL";
+	pub const OIDC_CLIENT_ID_KEY: &str = "STUMP_OIDC_CLIENT_ID";
+	pub const OIDC_CLIENT_SECRET_KEY: &str = "STUMP_OIDC_CLIENT_SECRET";
+	pub const OIDC_REDIRECT_URI_KEY: &str = "STUMP_OIDC_REDIRECT_URI";
+	pub const OIDC_SCOPES_KEY: &str = "STUMP_OIDC_SCOPES";
+	pub const OIDC_USERNAME_CLAIM_KEY: &str = "STUMP_OIDC_USERNAME_CLAIM";
+	pub const OIDC_GROUPS_CLAIM_KEY: &str = "STUMP_OIDC_GROUPS_CLAIM";
+	pub const OIDC_AUTO_PROVISION_KEY: &str = "STUMP_OIDC_AUTO_PROVISION";
+	pub const OIDC_ALLOW_SYMMETRIC_SIGNING_KEY: &str =
+		"STUMP_OIDC_ALLOW_SYMMETRIC_SIGNING";
+	pub const OIDC_GROUP_PERMISSIONS_KEY: &str = "STUMP_OIDC_GROUP_PERMISSIONS";
+	pub const ENFORCE_MANAGER_TWO_FACTOR_KEY: &str = "STUMP_ENFORCE_MANAGER_TWO_FACTOR";
+	pub const PROXY_AUTH_HEADER_KEY: &str = "STUMP_PROXY_AUTH_HEADER";
+	pub const PROXY_AUTH_TRUSTED_PROXIES_KEY: &str = "STUMP_PROXY_AUTH_TRUSTED_PROXIES";
+	pub const PROXY_AUTH_AUTO_CREATE_KEY: &str = "STUMP_PROXY_AUTH_AUTO_CREATE";
+	pub const PROXY_AUTH_DEFAULT_PERMISSIONS_KEY: &str =
+		"STUMP_PROXY_AUTH_DEFAULT_PERMISSIONS";
+	pub const API_KEY_EXPIRY_NOTICE_DAYS_KEY: &str = "STUMP_API_KEY_EXPIRY_NOTICE_DAYS";
+	pub const TRASH_RETENTION_DAYS_KEY: &str = "STUMP_TRASH_RETENTION_DAYS";
+	pub const BACKUP_DIR_KEY: &str = "STUMP_BACKUP_DIR";
+	pub const BACKUP_INTERVAL_HOURS_KEY: &str = "STUMP_BACKUP_INTERVAL_HOURS";
+	pub const BACKUP_RETENTION_KEY: &str = "STUMP_BACKUP_RETENTION";
 }
 use env_keys::*;
 
@@ -48,6 +71,17 @@ pub mod defaults {
 	pub const DEFAULT_MAX_IMAGE_UPLOAD_SIZE: usize = 20 * 1024 * 1024; // 20 MB
 	pub const DEFAULT_ENABLE_UPLOAD: bool = false;
 	pub const DEFAULT_MAX_FILE_UPLOAD_SIZE: usize = 20 * 1024 * 1024; // 20 MB
+	pub const DEFAULT_OIDC_ENABLED: bool = false;
+	pub const DEFAULT_OIDC_USERNAME_CLAIM: &str = "preferred_username";
+	pub const DEFAULT_OIDC_GROUPS_CLAIM: &str = "groups";
+	pub const DEFAULT_OIDC_AUTO_PROVISION: bool = true;
+	pub const DEFAULT_OIDC_ALLOW_SYMMETRIC_SIGNING: bool = false;
+	pub const DEFAULT_ENFORCE_MANAGER_TWO_FACTOR: bool = false;
+	pub const DEFAULT_PROXY_AUTH_AUTO_CREATE: bool = false;
+	pub const DEFAULT_API_KEY_EXPIRY_NOTICE_DAYS: i64 = 7;
+	pub const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+	pub const DEFAULT_BACKUP_INTERVAL_HOURS: u64 = 0;
+	pub const DEFAULT_BACKUP_RETENTION: usize = 7;
 }
 use defaults::*;
 
@@ -194,6 +228,125 @@ pub struct StumpConfig {
 	#[default_value(DEFAULT_MAX_FILE_UPLOAD_SIZE)]
 	#[env_key(MAX_FILE_UPLOAD_SIZE_KEY)]
 	pub max_file_upload_size: usize,
+
+	/// Whether or not users may log in through an OpenID Connect provider.
+	#[default_value(DEFAULT_OIDC_ENABLED)]
+	#[env_key(OIDC_ENABLED_KEY)]
+	pub oidc_enabled: bool,
+
+	/// The issuer URL of the OpenID Connect provider, used for discovery via
+	/// `{issuer}/.well-known/openid-configuration`.
+	#[default_value(None)]
+	#[env_key(OIDC_ISSUER_URL_KEY)]
+	pub oidc_issuer_url: Option<String>,
+
+	/// The client ID Stump is registered with at the OpenID Connect provider.
+	#[default_value(None)]
+	#[env_key(OIDC_CLIENT_ID_KEY)]
+	pub oidc_client_id: Option<String>,
+
+	/// The client secret Stump is registered with at the OpenID Connect provider, if it is a
+	/// confidential client.
+	#[default_value(None)]
+	#[env_key(OIDC_CLIENT_SECRET_KEY)]
+	pub oidc_client_secret: Option<String>,
+
+	/// The redirect URI registered with the OpenID Connect provider. This should point to
+	/// `/api/v1/auth/oidc/callback` on the public URL of the server.
+	#[default_value(None)]
+	#[env_key(OIDC_REDIRECT_URI_KEY)]
+	pub oidc_redirect_uri: Option<String>,
+
+	/// The scopes to request from the OpenID Connect provider. `openid` is always requested.
+	#[default_value(vec!["openid".to_string(), "profile".to_string(), "email".to_string()])]
+	#[env_key(OIDC_SCOPES_KEY)]
+	pub oidc_scopes: Vec<String>,
+
+	/// The ID token (or userinfo) claim used as the username for provisioned accounts.
+	#[default_value(DEFAULT_OIDC_USERNAME_CLAIM.to_string())]
+	#[env_key(OIDC_USERNAME_CLAIM_KEY)]
+	pub oidc_username_claim: String,
+
+	/// The ID token (or userinfo) claim which lists the groups a user belongs to.
+	#[default_value(DEFAULT_OIDC_GROUPS_CLAIM.to_string())]
+	#[env_key(OIDC_GROUPS_CLAIM_KEY)]
+	pub oidc_groups_claim: String,
+
+	/// Whether or not to automatically create a Stump account the first time an unknown
+	/// OpenID Connect identity logs in.
+	#[default_value(DEFAULT_OIDC_AUTO_PROVISION)]
+	#[env_key(OIDC_AUTO_PROVISION_KEY)]
+	pub oidc_auto_provision: bool,
+
+	/// Whether or not to accept ID tokens signed with the client secret (HS256, HS384 or
+	/// HS512). Only the asymmetric algorithms advertised by the provider are accepted
+	/// otherwise.
+	#[default_value(DEFAULT_OIDC_ALLOW_SYMMETRIC_SIGNING)]
+	#[env_key(OIDC_ALLOW_SYMMETRIC_SIGNING_KEY)]
+	pub oidc_allow_symmetric_signing: bool,
+
+	/// A list of `group=permission` pairs which grant permissions to users based on their
+	/// OpenID Connect groups, e.g. `admins=server:manage`.
+	#[default_value(vec![])]
+	#[env_key(OIDC_GROUP_PERMISSIONS_KEY)]
+	pub oidc_group_permissions: Vec<String>,
+
+	/// Whether or not users who can manage the server are required to enroll in two-factor
+	/// authentication before they may use the API.
+	#[default_value(DEFAULT_ENFORCE_MANAGER_TWO_FACTOR)]
+	#[env_key(ENFORCE_MANAGER_TWO_FACTOR_KEY)]
+	pub enforce_manager_two_factor: bool,
+
+	/// The name of a header, e.g. `Remote-User`, which an upstream authentication proxy sets
+	/// to the username of the authenticated user. Proxy authentication is disabled when unset.
+	#[default_value(None)]
+	#[env_key(PROXY_AUTH_HEADER_KEY)]
+	pub proxy_auth_header: Option<String>,
+
+	/// A list of IP addresses or CIDR ranges, e.g. `172.16.0.0/12`, which are trusted to set
+	/// the proxy authentication header. The header is ignored for any other peer.
+	#[default_value(vec![])]
+	#[env_key(PROXY_AUTH_TRUSTED_PROXIES_KEY)]
+	pub proxy_auth_trusted_proxies: Vec<String>,
+
+	/// Whether or not to automatically create an account for unknown usernames sent by the
+	/// authentication proxy.
+	#[default_value(DEFAULT_PROXY_AUTH_AUTO_CREATE)]
+	#[env_key(PROXY_AUTH_AUTO_CREATE_KEY)]
+	pub proxy_auth_auto_create: bool,
+
+	/// The permissions given to accounts created by proxy authentication, e.g. `bookclub:read`.
+	#[default_value(vec![])]
+	#[env_key(PROXY_AUTH_DEFAULT_PERMISSIONS_KEY)]
+	pub proxy_auth_default_permissions: Vec<String>,
+
+	/// The number of days before an API key expires that its owner is notified. Set to 0 to
+	/// disable expiry notifications.
+	#[default_value(DEFAULT_API_KEY_EXPIRY_NOTICE_DAYS)]
+	#[env_key(API_KEY_EXPIRY_NOTICE_DAYS_KEY)]
+	pub api_key_expiry_notice_days: i64,
+
+	/// The number of days media is kept in the trash before it is permanently deleted. Set to
+	/// 0 to keep trashed media until it is manually purged.
+	#[default_value(DEFAULT_TRASH_RETENTION_DAYS)]
+	#[env_key(TRASH_RETENTION_DAYS_KEY)]
+	pub trash_retention_days: i64,
+
+	/// An optional directory to write backups to. Defaults to `backups` in the config directory.
+	#[default_value(None)]
+	#[env_key(BACKUP_DIR_KEY)]
+	pub backup_dir: Option<String>,
+
+	/// The number of hours between scheduled backups. Set to 0 to disable scheduled backups.
+	#[default_value(DEFAULT_BACKUP_INTERVAL_HOURS)]
+	#[env_key(BACKUP_INTERVAL_HOURS_KEY)]
+	pub backup_interval_hours: u64,
+
+	/// The number of backups to keep, after which the oldest are deleted when a new backup is
+	/// created. Set to 0 to keep every backup.
+	#[default_value(DEFAULT_BACKUP_RETENTION)]
+	#[env_key(BACKUP_RETENTION_KEY)]
+	pub backup_retention: usize,
 }
 
 impl StumpConfig {
@@ -288,10 +441,38 @@ impl StumpConfig {
 		PathBuf::from(&self.config_dir).join("avatars")
 	}
 
+	/// Returns a `PathBuf` to the Stump backups directory.
+	pub fn get_backups_dir(&self) -> PathBuf {
+		self.backup_dir.clone().map_or_else(
+			|| PathBuf::from(&self.config_dir).join("backups"),
+			PathBuf::from,
+		)
+	}
+
+	/// Returns a `PathBuf` to the SQLite database file.
+	pub fn get_db_path(&self) -> PathBuf {
+		if let Some(path) = self.db_path.clone() {
+			PathBuf::from(path).join("stump.db")
+		} else if self.profile == "release" {
+			self.get_config_dir().join("stump.db")
+		} else {
+			PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("prisma/dev.db")
+		}
+	}
+
 	/// Returns a `PathBuf` to the Stump log file.
 	pub fn get_log_file(&self) -> PathBuf {
 		self.get_config_dir().join("Stump.log")
 	}
+
+	/// Returns true if OpenID Connect login is enabled and the minimum required options
+	/// (issuer, client ID and redirect URI) are configured.
+	pub fn is_oidc_configured(&self) -> bool {
+		self.oidc_enabled
+			&& self.oidc_issuer_url.is_some()
+			&& self.oidc_client_id.is_some()
+			&& self.oidc_redirect_uri.is_some()
+	}
 }
 
 fn do_validate_profile(profile: &String) -> bool {
@@ -340,6 +521,27 @@ mod tests {
 			max_image_upload_size: None,
 			enable_upload: None,
 			max_file_upload_size: None,
+			oidc_enabled: None,
+			oidc_issuer_url: None,
+			oidc_client_id: None,
+			oidc_client_secret: None,
+			oidc_redirect_uri: None,
+			oidc_scopes: None,
+			oidc_username_claim: None,
+			oidc_groups_claim: None,
+			oidc_auto_provision: None,
+			oidc_allow_symmetric_signing: None,
+			oidc_group_permissions: None,
+			enforce_manager_two_factor: None,
+			proxy_auth_header: None,
+			proxy_auth_trusted_proxies: None,
+			proxy_auth_auto_create: None,
+			proxy_auth_default_permissions: None,
+			api_key_expiry_notice_days: None,
+			trash_retention_days: None,
+			backup_dir: None,
+			backup_interval_hours: None,
+			backup_retention: None,
 		};
 		partial_config.apply_to_config(&mut config);
 
@@ -378,7 +580,32 @@ mod tests {
 				max_thumbnail_concurrency: Some(DEFAULT_MAX_THUMBNAIL_CONCURRENCY),
 				max_image_upload_size: Some(DEFAULT_MAX_IMAGE_UPLOAD_SIZE),
 				enable_upload: Some(DEFAULT_ENABLE_UPLOAD),
-				max_file_upload_size: Some(DEFAULT_MAX_FILE_UPLOAD_SIZE)
+				max_file_upload_size: Some(DEFAULT_MAX_FILE_UPLOAD_SIZE),
+				oidc_enabled: Some(DEFAULT_OIDC_ENABLED),
+				oidc_issuer_url: None,
+				oidc_client_id: None,
+				oidc_client_secret: None,
+				oidc_redirect_uri: None,
+				oidc_scopes: Some(vec![
+					"openid".to_string(),
+					"profile".to_string(),
+					"email".to_string()
+				]),
+				oidc_username_claim: Some(DEFAULT_OIDC_USERNAME_CLAIM.to_string()),
+				oidc_groups_claim: Some(DEFAULT_OIDC_GROUPS_CLAIM.to_string()),
+				oidc_auto_provision: Some(DEFAULT_OIDC_AUTO_PROVISION),
+				oidc_allow_symmetric_signing: Some(DEFAULT_OIDC_ALLOW_SYMMETRIC_SIGNING),
+				oidc_group_permissions: Some(vec![]),
+				enforce_manager_two_factor: Some(DEFAULT_ENFORCE_MANAGER_TWO_FACTOR),
+				proxy_auth_header: None,
+				proxy_auth_trusted_proxies: Some(vec![]),
+				proxy_auth_auto_create: Some(DEFAULT_PROXY_AUTH_AUTO_CREATE),
+				proxy_auth_default_permissions: Some(vec![]),
+				api_key_expiry_notice_days: Some(DEFAULT_API_KEY_EXPIRY_NOTICE_DAYS),
+				trash_retention_days: Some(DEFAULT_TRASH_RETENTION_DAYS),
+				backup_dir: None,
+				backup_interval_hours: Some(DEFAULT_BACKUP_INTERVAL_HOURS),
+				backup_retention: Some(DEFAULT_BACKUP_RETENTION),
 			}
 		);
 
@@ -433,6 +660,32 @@ mod tests {
 						max_image_upload_size: DEFAULT_MAX_IMAGE_UPLOAD_SIZE,
 						enable_upload: DEFAULT_ENABLE_UPLOAD,
 						max_file_upload_size: DEFAULT_MAX_FILE_UPLOAD_SIZE,
+						oidc_enabled: DEFAULT_OIDC_ENABLED,
+						oidc_issuer_url: None,
+						oidc_client_id: None,
+						oidc_client_secret: None,
+						oidc_redirect_uri: None,
+						oidc_scopes: vec![
+							"openid".to_string(),
+							"profile".to_string(),
+							"email".to_string()
+						],
+						oidc_username_claim: DEFAULT_OIDC_USERNAME_CLAIM.to_string(),
+						oidc_groups_claim: DEFAULT_OIDC_GROUPS_CLAIM.to_string(),
+						oidc_auto_provision: DEFAULT_OIDC_AUTO_PROVISION,
+						oidc_allow_symmetric_signing:
+							DEFAULT_OIDC_ALLOW_SYMMETRIC_SIGNING,
+						oidc_group_permissions: vec![],
+						enforce_manager_two_factor: DEFAULT_ENFORCE_MANAGER_TWO_FACTOR,
+						proxy_auth_header: None,
+						proxy_auth_trusted_proxies: vec![],
+						proxy_auth_auto_create: DEFAULT_PROXY_AUTH_AUTO_CREATE,
+						proxy_auth_default_permissions: vec![],
+						api_key_expiry_notice_days: DEFAULT_API_KEY_EXPIRY_NOTICE_DAYS,
+						trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+						backup_dir: None,
+						backup_interval_hours: DEFAULT_BACKUP_INTERVAL_HOURS,
+						backup_retention: DEFAULT_BACKUP_RETENTION,
 					}
 				);
 			},
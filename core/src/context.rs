@@ -1,6 +1,5 @@
 use std::sync::Arc;
 
-use prisma_client_rust::not;
 use tokio::sync::{
 	broadcast::{channel, Receiver, Sender},
 	mpsc::error::SendError,
@@ -12,8 +11,9 @@ use crate::{
 	event::CoreEvent,
 	filesystem::scanner::LibraryWatcher,
 	job::{Executor, JobController, JobControllerCommand},
-	prisma::{self, server_config},
-	CoreError, CoreResult,
+	prisma,
+	utils::get_encryption_key,
+	CoreResult,
 };
 
 type EventChannel = (Sender<CoreEvent>, Receiver<CoreEvent>);
@@ -217,17 +217,6 @@ impl Ctx {
 	}
 
 	pub async fn get_encryption_key(&self) -> CoreResult<String> {
-		let server_config = self
-			.db
-			.server_config()
-			.find_first(vec![not![server_config::encryption_key::equals(None)]])
-			.exec()
-			.await?;
-
-		let encryption_key = server_config
-			.and_then(|config| config.encryption_key)
-			.ok_or(CoreError::EncryptionKeyNotSet)?;
-
-		Ok(encryption_key)
+		get_encryption_key(&self.db).await
 	}
 }
@@ -0,0 +1,115 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+	job::{
+		error::JobError, JobExecuteLog, JobExt, JobOutputExt, JobTaskOutput, WorkerCtx,
+		WorkingState, WrappedJob,
+	},
+	Ctx,
+};
+
+use super::{create_backup, prune_backups};
+
+pub const DATABASE_BACKUP_JOB_NAME: &str = "database_backup";
+
+#[derive(Clone, Serialize, Deserialize, Default, Debug, Type)]
+pub struct DatabaseBackupOutput {
+	/// The name of the backup which was created
+	backup: Option<String>,
+	/// The size of the backup which was created, in bytes
+	size: u64,
+	/// The names of the old backups which were deleted
+	pruned: Vec<String>,
+}
+
+impl JobOutputExt for DatabaseBackupOutput {}
+
+/// A job which creates a backup of the database, the config file and the thumbnails, and then
+/// deletes the oldest backups beyond the configured retention
+#[derive(Clone)]
+pub struct DatabaseBackupJob {
+	include_thumbnails: bool,
+}
+
+impl DatabaseBackupJob {
+	pub fn new(include_thumbnails: bool) -> Box<WrappedJob<DatabaseBackupJob>> {
+		WrappedJob::new(Self { include_thumbnails })
+	}
+}
+
+#[async_trait::async_trait]
+impl JobExt for DatabaseBackupJob {
+	const NAME: &'static str = DATABASE_BACKUP_JOB_NAME;
+
+	type Output = DatabaseBackupOutput;
+	type Task = ();
+
+	fn description(&self) -> Option<String> {
+		Some("Back up the database".to_string())
+	}
+
+	async fn init(
+		&mut self,
+		ctx: &WorkerCtx,
+	) -> Result<WorkingState<Self::Output, Self::Task>, JobError> {
+		let mut output = Self::Output::default();
+		let mut logs = vec![];
+
+		let backup = create_backup(&ctx.db, &ctx.config, self.include_thumbnails)
+			.await
+			.map_err(|e| JobError::InitFailed(format!("Failed to create backup: {e}")))?;
+		tracing::info!(name = backup.name, size = backup.size, "Created backup");
+		output.backup = Some(backup.name);
+		output.size = backup.size;
+
+		match prune_backups(&ctx.config) {
+			Ok(pruned) => output.pruned = pruned,
+			Err(e) => {
+				logs.push(JobExecuteLog::error(format!(
+					"Failed to delete old backups: {e}"
+				)));
+			},
+		}
+
+		Ok(WorkingState {
+			output: Some(output),
+			tasks: VecDeque::default(),
+			completed_tasks: 0,
+			logs,
+		})
+	}
+
+	async fn execute_task(
+		&self,
+		_: &WorkerCtx,
+		_: Self::Task,
+	) -> Result<JobTaskOutput<Self>, JobError> {
+		unreachable!("DatabaseBackupJob does not have any tasks! It should not be executed with any tasks!")
+	}
+}
+
+/// Periodically dispatch the [DatabaseBackupJob], unless scheduled backups are disabled
+pub async fn continuously_back_up(ctx: Arc<Ctx>) {
+	if ctx.config.backup_interval_hours == 0 {
+		tracing::debug!("Scheduled backups are disabled");
+		return;
+	}
+
+	let period = Duration::from_secs(ctx.config.backup_interval_hours * 60 * 60);
+	// The first backup is taken one period after boot, rather than on every restart
+	let mut interval =
+		tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+	interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	loop {
+		interval.tick().await;
+		if let Err(error) = ctx.enqueue_job(DatabaseBackupJob::new(true)) {
+			tracing::error!(error = ?error, "Failed to dispatch database backup job");
+		} else {
+			tracing::trace!("Dispatched database backup job");
+		}
+	}
+}
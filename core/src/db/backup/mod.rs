@@ -0,0 +1,523 @@
+mod backup_job;
+
+use std::{
+	fs::{self, File, OpenOptions},
+	io::{self, Read, Write},
+	path::{Component, Path, PathBuf},
+};
+
+use prisma_client_rust::{
+	chrono::{DateTime, Utc},
+	raw,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+use walkdir::WalkDir;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{config::StumpConfig, prisma::PrismaClient, CoreError, CoreResult};
+
+pub use backup_job::{continuously_back_up, DatabaseBackupJob, DATABASE_BACKUP_JOB_NAME};
+
+/// The version of the backup archive layout. It is bumped whenever the layout changes in a
+/// way older versions of Stump can't restore.
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+const BACKUP_PREFIX: &str = "stump-backup-";
+const BACKUP_EXTENSION: &str = "zip";
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "stump.db";
+const CONFIG_ENTRY: &str = "Stump.toml";
+const THUMBNAILS_ENTRY: &str = "thumbnails";
+/// The number of names to try for a backup before giving up, see [`reserve_backup_file`]
+const MAX_NAME_ATTEMPTS: u32 = 100;
+
+/// The manifest stored at the root of each backup archive, describing what it contains
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+pub struct BackupManifest {
+	/// The version of the archive layout, see [`BACKUP_FORMAT_VERSION`]
+	pub format_version: u32,
+	/// The version of Stump which created the backup
+	pub stump_version: String,
+	pub created_at: String,
+	/// Whether the thumbnails directory is included in the backup
+	pub includes_thumbnails: bool,
+}
+
+/// A backup archive in the backups directory
+#[derive(Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
+pub struct Backup {
+	/// The file name of the backup, which is used to refer to it
+	pub name: String,
+	/// The size of the archive, in bytes
+	pub size: u64,
+	pub manifest: BackupManifest,
+}
+
+fn zip_error(error: zip::result::ZipError) -> CoreError {
+	CoreError::InternalError(format!("Failed to read or write backup archive: {error}"))
+}
+
+/// Write a consistent copy of the database to the given path. This uses `VACUUM INTO`, so the
+/// copy is taken from a single read transaction while the server keeps running.
+pub async fn snapshot_database(db: &PrismaClient, destination: &Path) -> CoreResult<()> {
+	if destination.exists() {
+		fs::remove_file(destination)?;
+	}
+	let destination = destination.to_string_lossy().replace('\'', "''");
+	db._execute_raw(raw!(&format!("VACUUM INTO '{destination}';")))
+		.exec()
+		.await?;
+	Ok(())
+}
+
+/// Create a backup of the database, the config file and (optionally) the thumbnails in the
+/// backups directory
+pub async fn create_backup(
+	db: &PrismaClient,
+	config: &StumpConfig,
+	include_thumbnails: bool,
+) -> CoreResult<Backup> {
+	let backups_dir = config.get_backups_dir();
+	fs::create_dir_all(&backups_dir)?;
+
+	let created_at = Utc::now();
+	let (name, archive) = reserve_backup_file(
+		&backups_dir,
+		&created_at.format("%Y%m%d-%H%M%S").to_string(),
+	)?;
+	let archive_path = backups_dir.join(&name);
+	let snapshot_path = backups_dir.join(format!(".{name}.db"));
+	// A snapshot may have been left behind by a backup which was interrupted, and SQLite
+	// won't vacuum into a file which already exists
+	let _ = fs::remove_file(&snapshot_path);
+	if let Err(error) = snapshot_database(db, &snapshot_path).await {
+		let _ = fs::remove_file(&archive_path);
+		return Err(error);
+	}
+
+	let manifest = BackupManifest {
+		format_version: BACKUP_FORMAT_VERSION,
+		stump_version: env!("CARGO_PKG_VERSION").to_string(),
+		created_at: created_at.to_rfc3339(),
+		includes_thumbnails: include_thumbnails,
+	};
+	let result = {
+		let config = config.clone();
+		let snapshot_path = snapshot_path.clone();
+		let manifest = manifest.clone();
+		tokio::task::spawn_blocking(move || {
+			write_archive(&config, &snapshot_path, archive, &manifest)
+		})
+		.await
+		.map_err(|e| CoreError::InternalError(e.to_string()))?
+	};
+	let _ = fs::remove_file(&snapshot_path);
+	if let Err(error) = result {
+		let _ = fs::remove_file(&archive_path);
+		return Err(error);
+	}
+
+	let size = fs::metadata(&archive_path)?.len();
+	Ok(Backup {
+		name,
+		size,
+		manifest,
+	})
+}
+
+/// Create the archive file for a new backup, returning its name. The file is created
+/// exclusively, so that backups started within the same second (e.g. a scheduled backup and
+/// one from the CLI) never overwrite each other. Instead, a counter is appended to the name.
+fn reserve_backup_file(
+	backups_dir: &Path,
+	timestamp: &str,
+) -> CoreResult<(String, File)> {
+	for attempt in 1..=MAX_NAME_ATTEMPTS {
+		let name = if attempt == 1 {
+			format!("{BACKUP_PREFIX}{timestamp}.{BACKUP_EXTENSION}")
+		} else {
+			format!("{BACKUP_PREFIX}{timestamp}-{attempt}.{BACKUP_EXTENSION}")
+		};
+		match OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(backups_dir.join(&name))
+		{
+			Ok(file) => return Ok((name, file)),
+			Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+			Err(error) => return Err(error.into()),
+		}
+	}
+
+	Err(CoreError::InternalError(format!(
+		"Failed to find an unused name for a backup created at {timestamp}"
+	)))
+}
+
+/// Write the archive for a backup. This is blocking, and should be called from a blocking
+/// context.
+fn write_archive(
+	config: &StumpConfig,
+	snapshot_path: &Path,
+	archive: File,
+	manifest: &BackupManifest,
+) -> CoreResult<()> {
+	let mut writer = ZipWriter::new(archive);
+	let options: FileOptions<()> =
+		FileOptions::default().compression_method(CompressionMethod::Deflated);
+	// Thumbnails are already compressed, so there is nothing to gain from deflating them
+	let stored_options: FileOptions<()> =
+		FileOptions::default().compression_method(CompressionMethod::Stored);
+
+	writer
+		.start_file(MANIFEST_ENTRY, options)
+		.map_err(zip_error)?;
+	writer.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+
+	writer
+		.start_file(DATABASE_ENTRY, options)
+		.map_err(zip_error)?;
+	io::copy(&mut File::open(snapshot_path)?, &mut writer)?;
+
+	let config_path = config.get_config_dir().join(CONFIG_ENTRY);
+	if config_path.exists() {
+		writer
+			.start_file(CONFIG_ENTRY, options)
+			.map_err(zip_error)?;
+		io::copy(&mut File::open(config_path)?, &mut writer)?;
+	}
+
+	let thumbnails_dir = config.get_thumbnails_dir();
+	if manifest.includes_thumbnails && thumbnails_dir.exists() {
+		for entry in WalkDir::new(&thumbnails_dir)
+			.into_iter()
+			.filter_map(Result::ok)
+			.filter(|entry| entry.file_type().is_file())
+		{
+			let Ok(relative_path) = entry.path().strip_prefix(&thumbnails_dir) else {
+				continue;
+			};
+			let name = Path::new(THUMBNAILS_ENTRY).join(relative_path);
+			writer
+				.start_file(name.to_string_lossy().replace('\\', "/"), stored_options)
+				.map_err(zip_error)?;
+			io::copy(&mut File::open(entry.path())?, &mut writer)?;
+		}
+	}
+
+	writer.finish().map_err(zip_error)?;
+	Ok(())
+}
+
+/// Read the manifest of a backup archive
+pub fn read_manifest(archive_path: &Path) -> CoreResult<BackupManifest> {
+	let mut archive = ZipArchive::new(File::open(archive_path)?).map_err(zip_error)?;
+	let mut manifest = archive.by_name(MANIFEST_ENTRY).map_err(zip_error)?;
+	let mut contents = Vec::new();
+	manifest.read_to_end(&mut contents)?;
+	Ok(serde_json::from_slice(&contents)?)
+}
+
+/// List the backups in the backups directory, newest first. Files which aren't readable
+/// backup archives are skipped.
+pub fn list_backups(config: &StumpConfig) -> CoreResult<Vec<Backup>> {
+	let backups_dir = config.get_backups_dir();
+	if !backups_dir.exists() {
+		return Ok(vec![]);
+	}
+
+	let mut backups = fs::read_dir(&backups_dir)?
+		.filter_map(Result::ok)
+		.filter_map(|entry| {
+			let name = entry.file_name().to_string_lossy().to_string();
+			if !is_backup_name(&name) {
+				return None;
+			}
+			let manifest = read_manifest(&entry.path())
+				.inspect_err(|error| {
+					tracing::warn!(?error, ?name, "Skipping unreadable backup");
+				})
+				.ok()?;
+			let size = entry.metadata().ok()?.len();
+			Some(Backup {
+				name,
+				size,
+				manifest,
+			})
+		})
+		.collect::<Vec<_>>();
+	// Backups created within the same second only differ by the counter in their name, so
+	// they are ordered by when they were created first
+	backups.sort_by(|a, b| {
+		let created_at = |backup: &Backup| {
+			DateTime::parse_from_rfc3339(&backup.manifest.created_at).ok()
+		};
+		created_at(b)
+			.cmp(&created_at(a))
+			.then_with(|| b.name.cmp(&a.name))
+	});
+
+	Ok(backups)
+}
+
+fn is_backup_name(name: &str) -> bool {
+	name.starts_with(BACKUP_PREFIX) && name.ends_with(&format!(".{BACKUP_EXTENSION}"))
+}
+
+/// Get the path of the backup with the given name. The name must refer to an existing backup
+/// directly within the backups directory.
+pub fn get_backup_path(config: &StumpConfig, name: &str) -> CoreResult<PathBuf> {
+	let mut components = Path::new(name).components();
+	let is_plain_name = matches!(
+		(components.next(), components.next()),
+		(Some(Component::Normal(_)), None)
+	);
+	if !is_plain_name || !is_backup_name(name) {
+		return Err(CoreError::BadRequest(format!(
+			"Invalid backup name: {name}"
+		)));
+	}
+
+	let path = config.get_backups_dir().join(name);
+	if !path.is_file() {
+		return Err(CoreError::NotFound(format!("Backup {name} does not exist")));
+	}
+	Ok(path)
+}
+
+/// Delete the oldest backups beyond the configured retention, returning the names of the
+/// deleted backups
+pub fn prune_backups(config: &StumpConfig) -> CoreResult<Vec<String>> {
+	if config.backup_retention == 0 {
+		return Ok(vec![]);
+	}
+
+	let backups_dir = config.get_backups_dir();
+	let mut pruned = vec![];
+	for backup in list_backups(config)?
+		.into_iter()
+		.skip(config.backup_retention)
+	{
+		fs::remove_file(backups_dir.join(&backup.name))?;
+		pruned.push(backup.name);
+	}
+
+	Ok(pruned)
+}
+
+/// Options for restoring a backup
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+	/// Whether to replace the thumbnails directory with the thumbnails in the backup
+	pub thumbnails: bool,
+	/// Whether to replace the config file with the one in the backup. This is off by default,
+	/// since the config may contain paths which only make sense on the original machine
+	pub config: bool,
+}
+
+/// Restore a backup archive, replacing the database and (optionally) the thumbnails and config
+/// file. This must only be done while the server is stopped.
+pub fn restore_backup(
+	config: &StumpConfig,
+	archive_path: &Path,
+	options: RestoreOptions,
+) -> CoreResult<BackupManifest> {
+	let manifest = read_manifest(archive_path)?;
+	if manifest.format_version > BACKUP_FORMAT_VERSION {
+		return Err(CoreError::BadRequest(format!(
+			"The backup was created by Stump {}, which uses a newer backup format",
+			manifest.stump_version
+		)));
+	}
+
+	let mut archive = ZipArchive::new(File::open(archive_path)?).map_err(zip_error)?;
+
+	// The database is first extracted next to the existing one, so a failed extraction leaves
+	// the existing database untouched
+	let db_path = config.get_db_path();
+	if let Some(parent) = db_path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	let restored_db_path = db_path.with_extension("db.restore");
+	{
+		let mut entry = archive.by_name(DATABASE_ENTRY).map_err(zip_error)?;
+		io::copy(&mut entry, &mut File::create(&restored_db_path)?)?;
+	}
+	// A leftover write-ahead log would otherwise be replayed over the restored database
+	for suffix in ["-wal", "-shm"] {
+		let sidecar = PathBuf::from(format!("{}{suffix}", db_path.display()));
+		if sidecar.exists() {
+			fs::remove_file(sidecar)?;
+		}
+	}
+	fs::rename(&restored_db_path, &db_path)?;
+
+	if options.config {
+		if let Ok(mut entry) = archive.by_name(CONFIG_ENTRY) {
+			let config_path = config.get_config_dir().join(CONFIG_ENTRY);
+			io::copy(&mut entry, &mut File::create(config_path)?)?;
+		}
+	}
+
+	if options.thumbnails && manifest.includes_thumbnails {
+		let thumbnails_dir = config.get_thumbnails_dir();
+		if thumbnails_dir.exists() {
+			fs::remove_dir_all(&thumbnails_dir)?;
+		}
+		fs::create_dir_all(&thumbnails_dir)?;
+
+		for index in 0..archive.len() {
+			let mut entry = archive.by_index(index).map_err(zip_error)?;
+			// enclosed_name guards against entries escaping the thumbnails directory
+			let Some(relative_path) = entry.enclosed_name().and_then(|path| {
+				path.strip_prefix(THUMBNAILS_ENTRY)
+					.ok()
+					.map(Path::to_path_buf)
+			}) else {
+				continue;
+			};
+			if !entry.is_file() || relative_path.as_os_str().is_empty() {
+				continue;
+			}
+			let destination = thumbnails_dir.join(relative_path);
+			if let Some(parent) = destination.parent() {
+				fs::create_dir_all(parent)?;
+			}
+			io::copy(&mut entry, &mut File::create(destination)?)?;
+		}
+	}
+
+	Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::TempDir;
+
+	use super::*;
+
+	fn config_for(dir: &TempDir) -> StumpConfig {
+		let mut config = StumpConfig::new(dir.path().to_string_lossy().to_string());
+		config.profile = "release".to_string();
+		fs::create_dir_all(config.get_thumbnails_dir()).unwrap();
+		config
+	}
+
+	fn write_test_archive(config: &StumpConfig, name: &str, includes_thumbnails: bool) {
+		let snapshot_path = config.get_config_dir().join("snapshot.db");
+		fs::write(&snapshot_path, b"database").unwrap();
+		fs::create_dir_all(config.get_backups_dir()).unwrap();
+		let manifest = BackupManifest {
+			format_version: BACKUP_FORMAT_VERSION,
+			stump_version: "0.0.0".to_string(),
+			created_at: Utc::now().to_rfc3339(),
+			includes_thumbnails,
+		};
+		write_archive(
+			config,
+			&snapshot_path,
+			File::create(config.get_backups_dir().join(name)).unwrap(),
+			&manifest,
+		)
+		.unwrap();
+	}
+
+	#[test]
+	fn test_write_and_restore_archive() {
+		let dir = TempDir::new().unwrap();
+		let config = config_for(&dir);
+		fs::write(config.get_thumbnails_dir().join("book.webp"), b"thumbnail").unwrap();
+		fs::write(config.get_config_dir().join(CONFIG_ENTRY), b"port = 10801").unwrap();
+
+		let name = "stump-backup-20250101-000000.zip";
+		write_test_archive(&config, name, true);
+
+		fs::write(config.get_db_path(), b"newer database").unwrap();
+		fs::write(format!("{}-wal", config.get_db_path().display()), b"wal").unwrap();
+		fs::remove_file(config.get_thumbnails_dir().join("book.webp")).unwrap();
+
+		let manifest = restore_backup(
+			&config,
+			&get_backup_path(&config, name).unwrap(),
+			RestoreOptions {
+				thumbnails: true,
+				config: false,
+			},
+		)
+		.unwrap();
+		assert!(manifest.includes_thumbnails);
+		assert_eq!(fs::read(config.get_db_path()).unwrap(), b"database");
+		assert!(
+			!PathBuf::from(format!("{}-wal", config.get_db_path().display())).exists()
+		);
+		assert_eq!(
+			fs::read(config.get_thumbnails_dir().join("book.webp")).unwrap(),
+			b"thumbnail"
+		);
+	}
+
+	#[test]
+	fn test_list_and_prune_backups() {
+		let dir = TempDir::new().unwrap();
+		let mut config = config_for(&dir);
+		for name in [
+			"stump-backup-20250101-000000.zip",
+			"stump-backup-20250102-000000.zip",
+			"stump-backup-20250103-000000.zip",
+		] {
+			write_test_archive(&config, name, false);
+		}
+		fs::write(config.get_backups_dir().join("notes.txt"), b"").unwrap();
+
+		let backups = list_backups(&config).unwrap();
+		assert_eq!(
+			backups.iter().map(|b| b.name.as_str()).collect::<Vec<_>>(),
+			vec![
+				"stump-backup-20250103-000000.zip",
+				"stump-backup-20250102-000000.zip",
+				"stump-backup-20250101-000000.zip",
+			]
+		);
+
+		config.backup_retention = 2;
+		assert_eq!(
+			prune_backups(&config).unwrap(),
+			vec!["stump-backup-20250101-000000.zip"]
+		);
+		assert_eq!(list_backups(&config).unwrap().len(), 2);
+	}
+
+	#[test]
+	fn test_reserve_backup_file() {
+		let dir = TempDir::new().unwrap();
+
+		let (first, _) = reserve_backup_file(dir.path(), "20250101-000000").unwrap();
+		let (second, _) = reserve_backup_file(dir.path(), "20250101-000000").unwrap();
+		assert_eq!(first, "stump-backup-20250101-000000.zip");
+		assert_eq!(second, "stump-backup-20250101-000000-2.zip");
+		assert!(is_backup_name(&second));
+	}
+
+	#[test]
+	fn test_backup_path_is_validated() {
+		let dir = TempDir::new().unwrap();
+		let config = config_for(&dir);
+		write_test_archive(&config, "stump-backup-20250101-000000.zip", false);
+
+		assert!(get_backup_path(&config, "stump-backup-20250101-000000.zip").is_ok());
+		assert!(matches!(
+			get_backup_path(&config, "../stump-backup-20250101-000000.zip"),
+			Err(CoreError::BadRequest(_))
+		));
+		assert!(matches!(
+			get_backup_path(&config, "backups/stump-backup-20250101-000000.zip"),
+			Err(CoreError::BadRequest(_))
+		));
+		assert!(matches!(
+			get_backup_path(&config, "stump-backup-20250102-000000.zip"),
+			Err(CoreError::NotFound(_))
+		));
+	}
+}
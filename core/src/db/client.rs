@@ -9,11 +9,6 @@ pub(crate) struct JournalModeQueryResult {
 
 /// Creates the [`prisma::PrismaClient`]. Will call `create_data_dir` as well
 pub async fn create_client(config: &StumpConfig) -> prisma::PrismaClient {
-	let config_dir = config
-		.get_config_dir()
-		.to_str()
-		.expect("Error parsing config directory")
-		.to_string();
 	// NOTE: Prisma 5.16.0 will potentially have a few fixes related to SQLite, in particular fixes for timeouts
 	// during query execution. It seems the latest PCR is on 5.1.0 (with a custom patch for PCR-specific things).
 	// Hopefully once 5.16.0 is released, PCR will be updated shortly after to take advantage of the improvements.
@@ -23,14 +18,7 @@ pub async fn create_client(config: &StumpConfig) -> prisma::PrismaClient {
 	// TODO: experiment with this. I experienced some issues with concurrent writes still :/
 	// let postfix = "?socket_timeout=15000&busy_timeout=15000&connection_limit=1";
 
-	let sqlite_url = if let Some(path) = config.db_path.clone() {
-		format!("file:{path}/stump.db")
-	} else if config.profile == "release" {
-		tracing::trace!("file:{config_dir}/stump.db");
-		format!("file:{config_dir}/stump.db")
-	} else {
-		format!("file:{}/prisma/dev.db", env!("CARGO_MANIFEST_DIR"))
-	};
+	let sqlite_url = format!("file:{}", config.get_db_path().display());
 
 	tracing::trace!(?sqlite_url, "Creating Prisma client");
 	create_client_with_url(&sqlite_url).await
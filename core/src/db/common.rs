@@ -35,7 +35,9 @@ pub trait PrismaCountTrait {
 impl PrismaCountTrait for PrismaClient {
 	async fn media_count(&self) -> CoreResult<i64> {
 		let count_res: Vec<CountQueryReturn> = self
-			._query_raw(raw!("SELECT COUNT(*) as count FROM media"))
+			._query_raw(raw!(
+				"SELECT COUNT(*) as count FROM media WHERE deleted_at IS NULL"
+			))
 			.exec()
 			.await?;
 
@@ -48,7 +50,7 @@ impl PrismaCountTrait for PrismaClient {
 	async fn media_in_series_count(&self, series_id: String) -> CoreResult<i64> {
 		let count_res: Vec<CountQueryReturn> = self
 			._query_raw(raw!(
-				"SELECT COUNT(*) as count FROM media WHERE series_id={}",
+				"SELECT COUNT(*) as count FROM media WHERE series_id={} AND deleted_at IS NULL",
 				PrismaValue::String(series_id)
 			))
 			.exec()
@@ -94,7 +96,7 @@ impl PrismaCountTrait for PrismaClient {
 		series_ids: Vec<String>,
 	) -> Result<HashMap<String, i64>, QueryError> {
 		let count_res: Vec<SeriesMediaCountQueryReturn> = self
-		._query_raw(raw!(format!("SELECT DISTINCT series_id as series_id, COUNT(*) as count FROM media WHERE series_id in ({}) GROUP BY series_id",
+		._query_raw(raw!(format!("SELECT DISTINCT series_id as series_id, COUNT(*) as count FROM media WHERE series_id in ({}) AND deleted_at IS NULL GROUP BY series_id",
 		series_ids
 			.into_iter()
 			.map(|id| format!("\"{id}\""))
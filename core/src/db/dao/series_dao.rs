@@ -73,7 +73,7 @@ impl SeriesDAO {
 				FROM 
 					series
 					LEFT OUTER JOIN series_metadata sm ON sm.series_id = series.id
-					LEFT OUTER JOIN media series_media ON series_media.series_id = series.id
+					LEFT OUTER JOIN media series_media ON series_media.series_id = series.id AND series_media.deleted_at IS NULL
 					LEFT OUTER JOIN media_metadata mm ON mm.media_id = series_media.id
 					LEFT OUTER JOIN read_progresses media_progress ON media_progress.media_id = series_media.id AND media_progress.user_id = {}
 					LEFT OUTER JOIN age_restrictions ar ON ar.user_id = {}
@@ -113,7 +113,7 @@ impl SeriesDAO {
 			FROM 
 				series 
 				LEFT OUTER JOIN series_metadata sm ON sm.series_id = series.id
-				LEFT OUTER JOIN media series_media ON series_media.series_id = series.id
+				LEFT OUTER JOIN media series_media ON series_media.series_id = series.id AND series_media.deleted_at IS NULL
 				LEFT OUTER JOIN media_metadata mm ON mm.media_id = series_media.id
 				LEFT OUTER JOIN read_progresses media_progress ON media_progress.media_id = series_media.id AND media_progress.user_id = {}
 				LEFT OUTER JOIN age_restrictions ar ON ar.user_id = {}
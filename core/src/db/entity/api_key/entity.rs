@@ -5,7 +5,7 @@ use specta::Type;
 
 use crate::{
 	db::entity::{User, UserPermission},
-	prisma::api_key,
+	prisma::{api_key, api_key_usage},
 	CoreError, CoreResult,
 };
 
@@ -37,6 +37,79 @@ impl Default for APIKeyPermissions {
 	}
 }
 
+/// A family of routes an API key may be restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "lowercase")]
+pub enum APIKeyRouteScope {
+	/// The main API, i.e. `/api/*`
+	Api,
+	/// The OPDS catalogs, i.e. `/opds/*`
+	Opds,
+	/// The KOReader sync API, i.e. `/koreader/*`
+	Koreader,
+}
+
+impl APIKeyRouteScope {
+	/// Get the route family a request path belongs to, if any
+	pub fn from_path(path: &str) -> Option<Self> {
+		let first_segment = path.trim_start_matches('/').split('/').next()?;
+		match first_segment {
+			"api" => Some(APIKeyRouteScope::Api),
+			"opds" => Some(APIKeyRouteScope::Opds),
+			"koreader" => Some(APIKeyRouteScope::Koreader),
+			_ => None,
+		}
+	}
+}
+
+/// Restrictions placed on an API key, on top of its permissions. An empty set of scopes
+/// does not restrict the key at all.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct APIKeyScopes {
+	/// The libraries the key may access. Any library the user can access is allowed when
+	/// this is not set.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[specta(optional)]
+	pub library_ids: Option<Vec<String>>,
+	/// Whether the key is limited to requests which don't modify anything
+	#[serde(default)]
+	pub read_only: bool,
+	/// The route families the key may be used with. Any route is allowed when this is not
+	/// set.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[specta(optional)]
+	pub routes: Option<Vec<APIKeyRouteScope>>,
+}
+
+impl APIKeyScopes {
+	/// Whether the scopes permit a request to the given path
+	pub fn allows_path(&self, path: &str) -> bool {
+		match &self.routes {
+			Some(routes) => APIKeyRouteScope::from_path(path)
+				.is_some_and(|scope| routes.contains(&scope)),
+			None => true,
+		}
+	}
+}
+
+impl TryFrom<Option<Vec<u8>>> for APIKeyScopes {
+	type Error = CoreError;
+
+	fn try_from(value: Option<Vec<u8>>) -> Result<Self, Self::Error> {
+		value.map_or_else(
+			|| Ok(APIKeyScopes::default()),
+			|bytes| {
+				serde_json::from_slice(&bytes).map_err(|e| {
+					CoreError::InternalError(format!(
+						"Failed to deserialize API key scopes: {}",
+						e
+					))
+				})
+			},
+		)
+	}
+}
+
 /// An API key which can be used to interact with the API. API keys are scoped to a user,
 /// so all actions taken with an API key are done as if the user was taking them.
 #[derive(Default, Debug, Clone, Serialize, Deserialize, Type)]
@@ -48,6 +121,8 @@ pub struct APIKey {
 	/// The permissions for the API key, either inherited from the user or custom
 	/// permissions set on the key
 	pub permissions: APIKeyPermissions,
+	/// The restrictions placed on the API key, e.g. which libraries it can access
+	pub scopes: APIKeyScopes,
 	/// The hashed long token for the API key
 	#[serde(skip_serializing)]
 	pub long_token_hash: String,
@@ -145,6 +220,7 @@ impl TryFrom<api_key::Data> for APIKey {
 			id: data.id,
 			name: data.name,
 			permissions: serde_json::from_slice(&data.permissions)?,
+			scopes: APIKeyScopes::try_from(data.scopes)?,
 			long_token_hash: data.long_token_hash,
 			user_id: data.user_id,
 			created_at: data.created_at,
@@ -154,6 +230,38 @@ impl TryFrom<api_key::Data> for APIKey {
 	}
 }
 
+/// The requests made with an API key on a single day (UTC)
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct APIKeyUsage {
+	pub id: i32,
+	/// The day the requests were made on, formatted as YYYY-MM-DD
+	pub day: String,
+	/// The number of requests made on the day
+	pub request_count: i32,
+	/// The date of the last request made on the day
+	pub last_used_at: DateTime<FixedOffset>,
+	/// The HTTP method of the last request
+	pub last_method: String,
+	/// The path of the last request
+	pub last_path: String,
+	/// The IP address the last request came from, if known
+	pub last_ip_address: Option<String>,
+}
+
+impl From<api_key_usage::Data> for APIKeyUsage {
+	fn from(data: api_key_usage::Data) -> Self {
+		APIKeyUsage {
+			id: data.id,
+			day: data.day,
+			request_count: data.request_count,
+			last_used_at: data.last_used_at,
+			last_method: data.last_method,
+			last_path: data.last_path,
+			last_ip_address: data.last_ip_address,
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -185,6 +293,56 @@ mod tests {
 		assert_eq!(custom, r#"["feature:api_keys"]"#);
 	}
 
+	#[test]
+	fn test_route_scope_from_path() {
+		assert_eq!(
+			APIKeyRouteScope::from_path("/api/v1/libraries"),
+			Some(APIKeyRouteScope::Api)
+		);
+		assert_eq!(
+			APIKeyRouteScope::from_path("/opds/stump_abc/v1.2/catalog"),
+			Some(APIKeyRouteScope::Opds)
+		);
+		assert_eq!(
+			APIKeyRouteScope::from_path("/koreader/stump_abc/syncs/progress"),
+			Some(APIKeyRouteScope::Koreader)
+		);
+		assert_eq!(APIKeyRouteScope::from_path("/swagger-ui"), None);
+		assert_eq!(APIKeyRouteScope::from_path("/apiary"), None);
+	}
+
+	#[test]
+	fn test_scopes_allow_path() {
+		assert!(APIKeyScopes::default().allows_path("/swagger-ui"));
+
+		let opds_only = APIKeyScopes {
+			routes: Some(vec![APIKeyRouteScope::Opds]),
+			..Default::default()
+		};
+		assert!(opds_only.allows_path("/opds/v2.0/catalog"));
+		assert!(!opds_only.allows_path("/api/v1/libraries"));
+		assert!(!opds_only.allows_path("/swagger-ui"));
+	}
+
+	#[test]
+	fn test_deserialize_api_key_scopes() {
+		let scopes = APIKeyScopes::try_from(None).expect("Failed to deserialize none");
+		assert_eq!(scopes, APIKeyScopes::default());
+
+		let scopes = APIKeyScopes::try_from(Some(
+			br#"{"library_ids":["a"],"read_only":true,"routes":["koreader"]}"#.to_vec(),
+		))
+		.expect("Failed to deserialize scopes");
+		assert_eq!(
+			scopes,
+			APIKeyScopes {
+				library_ids: Some(vec!["a".to_string()]),
+				read_only: true,
+				routes: Some(vec![APIKeyRouteScope::Koreader]),
+			}
+		);
+	}
+
 	#[test]
 	fn test_validate() {
 		let (pek, hash) = APIKey::create_prefixed_key().expect("Failed to create key");
@@ -17,7 +17,9 @@ pub enum LayoutMode {
 	TABLE,
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
+#[derive(
+	Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema,
+)]
 pub enum ReadingDirection {
 	#[default]
 	#[serde(rename = "ltr")]
@@ -47,7 +49,9 @@ impl fmt::Display for ReadingDirection {
 	}
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
+#[derive(
+	Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema,
+)]
 pub enum ReadingMode {
 	#[default]
 	#[serde(rename = "paged")]
@@ -81,6 +85,72 @@ impl fmt::Display for ReadingMode {
 	}
 }
 
+/// The reading direction and mode set on a book or series, which take priority over the
+/// defaults of its library. Fields which are `None` are not overridden.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
+pub struct ReadingOverrides {
+	pub reading_direction: Option<ReadingDirection>,
+	pub reading_mode: Option<ReadingMode>,
+}
+
+impl ReadingOverrides {
+	/// Create overrides from the values stored on a book or series. Values which can't be
+	/// parsed are ignored.
+	pub fn from_stored(
+		reading_direction: Option<&str>,
+		reading_mode: Option<&str>,
+	) -> Self {
+		Self {
+			reading_direction: reading_direction
+				.and_then(|dir| ReadingDirection::from_str(dir).ok()),
+			reading_mode: reading_mode.and_then(|mode| ReadingMode::from_str(mode).ok()),
+		}
+	}
+}
+
+/// The reading direction and mode a book should be opened with
+#[derive(
+	Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema,
+)]
+pub struct ReadingPreferences {
+	pub reading_direction: ReadingDirection,
+	pub reading_mode: ReadingMode,
+}
+
+impl ReadingPreferences {
+	/// Create preferences from the defaults stored on a library config. Values which can't be
+	/// parsed fall back to the global defaults.
+	pub fn from_library_defaults(reading_direction: &str, reading_mode: &str) -> Self {
+		Self {
+			reading_direction: ReadingDirection::from_str(reading_direction)
+				.unwrap_or_default(),
+			reading_mode: ReadingMode::from_str(reading_mode).unwrap_or_default(),
+		}
+	}
+
+	/// Resolve the preferences of a book. The overrides of the book take priority over those of
+	/// its series, followed by the direction implied by the book's metadata (e.g. a manga which
+	/// is read right-to-left) and finally the defaults of its library.
+	pub fn resolve(
+		book: &ReadingOverrides,
+		series: &ReadingOverrides,
+		metadata_direction: Option<ReadingDirection>,
+		library_defaults: ReadingPreferences,
+	) -> Self {
+		Self {
+			reading_direction: book
+				.reading_direction
+				.or(series.reading_direction)
+				.or(metadata_direction)
+				.unwrap_or(library_defaults.reading_direction),
+			reading_mode: book
+				.reading_mode
+				.or(series.reading_mode)
+				.unwrap_or(library_defaults.reading_mode),
+		}
+	}
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
 pub enum ReadingImageScaleFit {
 	#[default]
@@ -234,3 +304,59 @@ impl AccessRole {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_resolve_reading_preferences() {
+		let library_defaults = ReadingPreferences::from_library_defaults("ltr", "paged");
+		let none = ReadingOverrides::default();
+
+		let resolved = ReadingPreferences::resolve(&none, &none, None, library_defaults);
+		assert_eq!(resolved, library_defaults);
+
+		let resolved = ReadingPreferences::resolve(
+			&none,
+			&none,
+			Some(ReadingDirection::RightToLeft),
+			library_defaults,
+		);
+		assert_eq!(resolved.reading_direction, ReadingDirection::RightToLeft);
+
+		let series =
+			ReadingOverrides::from_stored(Some("ltr"), Some("continuous:vertical"));
+		let resolved = ReadingPreferences::resolve(
+			&none,
+			&series,
+			Some(ReadingDirection::RightToLeft),
+			library_defaults,
+		);
+		assert_eq!(
+			resolved,
+			ReadingPreferences {
+				reading_direction: ReadingDirection::LeftToRight,
+				reading_mode: ReadingMode::ContinuousVertical,
+			}
+		);
+
+		let book = ReadingOverrides::from_stored(Some("rtl"), None);
+		let resolved =
+			ReadingPreferences::resolve(&book, &series, None, library_defaults);
+		assert_eq!(
+			resolved,
+			ReadingPreferences {
+				reading_direction: ReadingDirection::RightToLeft,
+				reading_mode: ReadingMode::ContinuousVertical,
+			}
+		);
+	}
+
+	#[test]
+	fn test_reading_overrides_ignore_invalid_values() {
+		let overrides = ReadingOverrides::from_stored(Some("up"), Some("scroll"));
+		assert!(overrides.reading_direction.is_none());
+		assert!(overrides.reading_mode.is_none());
+	}
+}
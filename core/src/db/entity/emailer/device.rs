@@ -1,8 +1,70 @@
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use utoipa::ToSchema;
 
-use crate::prisma::registered_email_device;
+use crate::{
+	filesystem::{DeviceConversionOptions, DeviceTargetFormat},
+	prisma::registered_email_device,
+	CoreError, CoreResult,
+};
+
+/// How books are converted before they are emailed to a device, e.g. a Kindle which rejects
+/// CBR files
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, Type)]
+pub struct DeviceFormatProfile {
+	/// The format books are converted to
+	#[serde(default)]
+	pub target_format: DeviceTargetFormat,
+	/// The maximum width or height of a page, in pixels. Larger pages are downscaled
+	#[serde(default)]
+	pub max_image_dimension: Option<i32>,
+}
+
+impl DeviceFormatProfile {
+	/// Validate that the maximum image dimension, if any, is a positive number of pixels
+	pub fn validate(&self) -> CoreResult<()> {
+		match self.max_image_dimension {
+			Some(dimension) if dimension <= 0 => Err(CoreError::BadRequest(
+				"The maximum image dimension must be greater than 0".to_string(),
+			)),
+			_ => Ok(()),
+		}
+	}
+
+	/// The options used to convert a book for the device, where files which are larger than
+	/// `max_attachment_size_bytes` once converted are split into volumes
+	pub fn conversion_options(
+		&self,
+		max_attachment_size_bytes: Option<i32>,
+	) -> DeviceConversionOptions {
+		DeviceConversionOptions {
+			target_format: self.target_format,
+			max_image_dimension: self
+				.max_image_dimension
+				.and_then(|dimension| u32::try_from(dimension).ok())
+				.filter(|dimension| *dimension > 0),
+			max_size_bytes: max_attachment_size_bytes
+				.and_then(|size| u64::try_from(size).ok()),
+		}
+	}
+}
+
+impl From<&registered_email_device::Data> for DeviceFormatProfile {
+	fn from(data: &registered_email_device::Data) -> Self {
+		let target_format = DeviceTargetFormat::from_str(&data.target_format)
+			.unwrap_or_else(|error| {
+				tracing::warn!(?error, "Falling back to the original format for device");
+				DeviceTargetFormat::default()
+			});
+
+		Self {
+			target_format,
+			max_image_dimension: data.max_image_dimension,
+		}
+	}
+}
 
 #[derive(Serialize, Deserialize, ToSchema, Type)]
 pub struct RegisteredEmailDevice {
@@ -10,15 +72,29 @@ pub struct RegisteredEmailDevice {
 	name: String,
 	email: String,
 	forbidden: bool,
+	format_profile: DeviceFormatProfile,
 }
 
 impl From<registered_email_device::Data> for RegisteredEmailDevice {
 	fn from(data: registered_email_device::Data) -> Self {
+		let format_profile = DeviceFormatProfile::from(&data);
+
 		Self {
 			id: data.id,
 			name: data.name,
 			email: data.email,
 			forbidden: data.forbidden,
+			format_profile,
 		}
 	}
 }
+
+/// A validated recipient of an email, along with how books should be converted for them
+#[derive(Debug, Clone)]
+pub struct EmailRecipient {
+	/// The email address of the recipient
+	pub email: String,
+	/// The format profile of the registered device with this address, or the default profile
+	/// for an address which isn't registered
+	pub format_profile: DeviceFormatProfile,
+}
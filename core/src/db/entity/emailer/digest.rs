@@ -0,0 +1,192 @@
+use std::{fmt, str::FromStr};
+
+use prisma_client_rust::chrono::{DateTime, Duration, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+
+use crate::prisma::new_books_digest_subscription;
+
+/// How often a user receives the new books digest
+#[derive(
+	Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Type,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DigestFrequency {
+	/// A digest of the books added over the last day
+	Daily,
+	/// A digest of the books added over the last week
+	#[default]
+	Weekly,
+}
+
+impl DigestFrequency {
+	/// The length of the window each digest covers
+	pub fn period(&self) -> Duration {
+		match self {
+			Self::Daily => Duration::days(1),
+			Self::Weekly => Duration::weeks(1),
+		}
+	}
+}
+
+impl fmt::Display for DigestFrequency {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Daily => write!(f, "DAILY"),
+			Self::Weekly => write!(f, "WEEKLY"),
+		}
+	}
+}
+
+impl FromStr for DigestFrequency {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"DAILY" => Ok(Self::Daily),
+			"WEEKLY" => Ok(Self::Weekly),
+			_ => Err(format!("\"{s}\" is not a valid digest frequency")),
+		}
+	}
+}
+
+/// The start of the window the next digest covers, which is the end of the window covered by
+/// the last digest. A subscription which never received a digest starts from when its address
+/// was confirmed.
+pub fn digest_window_start(
+	data: &new_books_digest_subscription::Data,
+) -> DateTime<FixedOffset> {
+	data.last_digest_at
+		.or(data.confirmed_at)
+		.unwrap_or(data.created_at)
+}
+
+/// Whether the address of the subscription was confirmed and a full period has passed since
+/// the window of the next digest started
+pub fn is_digest_due(
+	data: &new_books_digest_subscription::Data,
+	now: DateTime<Utc>,
+) -> bool {
+	let frequency = DigestFrequency::from_str(&data.frequency).unwrap_or_default();
+	data.confirmed_at.is_some() && digest_window_start(data) + frequency.period() <= now
+}
+
+/// A user's opt-in to a periodic email listing the books which were added to the libraries
+/// they can see
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Type)]
+pub struct NewBooksDigestSubscription {
+	/// The address the digest is sent to
+	pub email: String,
+	/// How often the digest is sent
+	pub frequency: DigestFrequency,
+	/// The end of the window covered by the last digest, if one was sent
+	pub last_digest_at: Option<String>,
+	/// When the address was confirmed with the code emailed to it. No digest is sent until
+	/// it is confirmed
+	pub confirmed_at: Option<String>,
+	pub created_at: String,
+}
+
+impl From<new_books_digest_subscription::Data> for NewBooksDigestSubscription {
+	fn from(data: new_books_digest_subscription::Data) -> Self {
+		let frequency =
+			DigestFrequency::from_str(&data.frequency).unwrap_or_else(|error| {
+				tracing::warn!(?error, "Falling back to the default digest frequency");
+				DigestFrequency::default()
+			});
+
+		Self {
+			email: data.email,
+			frequency,
+			last_digest_at: data.last_digest_at.map(|t| t.to_rfc3339()),
+			confirmed_at: data.confirmed_at.map(|t| t.to_rfc3339()),
+			created_at: data.created_at.to_rfc3339(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn subscription(
+		frequency: DigestFrequency,
+		created_at: DateTime<Utc>,
+		last_digest_at: Option<DateTime<Utc>>,
+	) -> new_books_digest_subscription::Data {
+		new_books_digest_subscription::Data {
+			id: "subscription".to_string(),
+			email: "reader@stumpapp.dev".to_string(),
+			frequency: frequency.to_string(),
+			last_digest_at: last_digest_at.map(Into::into),
+			confirmed_at: Some(created_at.into()),
+			confirmation_code_hash: None,
+			confirmation_sent_at: None,
+			created_at: created_at.into(),
+			user_id: "user".to_string(),
+			user: None,
+		}
+	}
+
+	#[test]
+	fn test_digest_due_from_creation() {
+		let now = Utc::now();
+
+		let data = subscription(DigestFrequency::Daily, now - Duration::hours(23), None);
+		assert!(!is_digest_due(&data, now));
+
+		let data = subscription(DigestFrequency::Daily, now - Duration::hours(25), None);
+		assert!(is_digest_due(&data, now));
+
+		let data = subscription(DigestFrequency::Weekly, now - Duration::days(3), None);
+		assert!(!is_digest_due(&data, now));
+	}
+
+	#[test]
+	fn test_digest_not_due_until_confirmed() {
+		let now = Utc::now();
+
+		let mut data =
+			subscription(DigestFrequency::Daily, now - Duration::days(3), None);
+		data.confirmed_at = None;
+		assert!(!is_digest_due(&data, now));
+
+		// The window starts from the confirmation, not from when the user opted in
+		data.confirmed_at = Some((now - Duration::hours(2)).into());
+		assert!(!is_digest_due(&data, now));
+		assert_eq!(digest_window_start(&data), now - Duration::hours(2));
+	}
+
+	#[test]
+	fn test_digest_due_from_last_digest() {
+		let now = Utc::now();
+		let created_at = now - Duration::weeks(4);
+
+		let data = subscription(
+			DigestFrequency::Weekly,
+			created_at,
+			Some(now - Duration::days(2)),
+		);
+		assert!(!is_digest_due(&data, now));
+		assert_eq!(digest_window_start(&data), now - Duration::days(2));
+
+		let data = subscription(
+			DigestFrequency::Weekly,
+			created_at,
+			Some(now - Duration::days(8)),
+		);
+		assert!(is_digest_due(&data, now));
+	}
+
+	#[test]
+	fn test_digest_frequency_round_trip() {
+		for frequency in [DigestFrequency::Daily, DigestFrequency::Weekly] {
+			assert_eq!(
+				DigestFrequency::from_str(&frequency.to_string()).unwrap(),
+				frequency
+			);
+		}
+		assert!(DigestFrequency::from_str("MONTHLY").is_err());
+	}
+}
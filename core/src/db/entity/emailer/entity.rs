@@ -4,8 +4,10 @@ use specta::Type;
 use utoipa::ToSchema;
 
 use crate::{
-	prisma::emailer,
-	utils::{decrypt_string, encrypt_string},
+	config::StumpConfig,
+	db::entity::get_template_overrides,
+	prisma::{emailer, PrismaClient},
+	utils::{decrypt_string, encrypt_string, get_encryption_key},
 	CoreError, CoreResult, Ctx,
 };
 
@@ -36,7 +38,16 @@ pub struct EmailerConfig {
 impl EmailerConfig {
 	/// Convert the config into a client config, which is used for the actual sending of emails
 	pub async fn into_client_config(self, ctx: &Ctx) -> CoreResult<EmailerClientConfig> {
-		let encryption_key = ctx.get_encryption_key().await?;
+		self.into_client_config_with(&ctx.db).await
+	}
+
+	/// Convert the config into a client config outside of a request, e.g. in a job, where only
+	/// the database is available
+	pub async fn into_client_config_with(
+		self,
+		client: &PrismaClient,
+	) -> CoreResult<EmailerClientConfig> {
+		let encryption_key = get_encryption_key(client).await?;
 		let password = decrypt_string(
 			&self
 				.encrypted_password
@@ -107,9 +118,20 @@ pub struct SMTPEmailer {
 
 impl SMTPEmailer {
 	pub async fn into_client(self, ctx: &Ctx) -> CoreResult<EmailerClient> {
-		let config = self.config.into_client_config(ctx).await?;
-		let template_dir = ctx.config.get_templates_dir();
-		Ok(EmailerClient::new(config, template_dir))
+		self.into_client_with(&ctx.db, &ctx.config).await
+	}
+
+	/// Create a client outside of a request, e.g. in a job
+	pub async fn into_client_with(
+		self,
+		client: &PrismaClient,
+		config: &StumpConfig,
+	) -> CoreResult<EmailerClient> {
+		let template_dir = config.get_templates_dir();
+		let config = self.config.into_client_config_with(client).await?;
+		let template_overrides = get_template_overrides(client).await?;
+		Ok(EmailerClient::new(config, template_dir)
+			.with_template_overrides(template_overrides))
 	}
 }
 
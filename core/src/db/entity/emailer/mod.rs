@@ -1,7 +1,13 @@
 mod device;
+mod digest;
 mod entity;
 mod history;
+mod send;
+mod template;
 
 pub use device::*;
+pub use digest::*;
 pub use entity::*;
 pub use history::*;
+pub use send::*;
+pub use template::*;
@@ -0,0 +1,240 @@
+use std::path::Path;
+
+use email::{AttachmentPayload, EmailContentType, EmailResult, EmailerClient};
+
+use crate::{
+	config::StumpConfig, db::entity::AttachmentMeta, filesystem::convert_for_device_async,
+};
+
+use super::EmailRecipient;
+
+/// The subject of emails which contain a book
+const ATTACHMENT_SUBJECT: &str = "Attachment from Stump";
+
+/// Something which can email attachments. This is implemented by [EmailerClient], and exists
+/// so the send-to-device flow can be exercised with a local stand-in instead of an SMTP server
+#[async_trait::async_trait]
+pub trait AttachmentSender: Sync {
+	/// Send an email with the given subject and attachments to the given recipient
+	async fn send_attachments(
+		&self,
+		subject: &str,
+		recipient: &str,
+		payloads: Vec<AttachmentPayload>,
+	) -> EmailResult<()>;
+}
+
+#[async_trait::async_trait]
+impl AttachmentSender for EmailerClient {
+	async fn send_attachments(
+		&self,
+		subject: &str,
+		recipient: &str,
+		payloads: Vec<AttachmentPayload>,
+	) -> EmailResult<()> {
+		EmailerClient::send_attachments(self, subject, recipient, payloads).await
+	}
+}
+
+/// The outcome of sending a book to a recipient
+#[derive(Default)]
+pub struct DeviceSendOutcome {
+	/// The attachments which were sent, e.g. the volumes of a book which was split
+	pub sent: Vec<AttachmentMeta>,
+	/// Descriptions of the conversions or emails which failed
+	pub errors: Vec<String>,
+}
+
+/// Convert a book according to the format profile of the recipient and email it to them. Each
+/// converted file is sent in its own email, since devices tend to limit the size of an email
+/// rather than that of a single attachment.
+pub async fn send_book_to_recipient<S: AttachmentSender>(
+	sender: &S,
+	book_id: &str,
+	book_path: &Path,
+	recipient: &EmailRecipient,
+	max_attachment_size_bytes: Option<i32>,
+	config: &StumpConfig,
+) -> DeviceSendOutcome {
+	let mut outcome = DeviceSendOutcome::default();
+	let options = recipient
+		.format_profile
+		.conversion_options(max_attachment_size_bytes);
+
+	let files = match convert_for_device_async(book_path, options, config).await {
+		Ok(files) => files,
+		Err(error) => {
+			tracing::error!(?error, ?book_path, "Failed to convert book for device");
+			outcome.errors.push(format!(
+				"Failed to convert {} for {}: {error}",
+				book_path.display(),
+				recipient.email
+			));
+			return outcome;
+		},
+	};
+
+	for file in files {
+		let Ok(content_type) = file.content_type.mime_type().parse::<EmailContentType>()
+		else {
+			outcome
+				.errors
+				.push(format!("Failed to parse content type of {}", file.name));
+			continue;
+		};
+		let meta = AttachmentMeta::new(
+			file.name.clone(),
+			Some(book_id.to_string()),
+			file.content.len() as i32,
+		);
+
+		let send_result = sender
+			.send_attachments(
+				ATTACHMENT_SUBJECT,
+				&recipient.email,
+				vec![AttachmentPayload {
+					name: file.name.clone(),
+					content: file.content,
+					content_type,
+				}],
+			)
+			.await;
+
+		match send_result {
+			Ok(_) => outcome.sent.push(meta),
+			Err(error) => {
+				tracing::error!(?error, "Failed to send email");
+				outcome.errors.push(format!(
+					"Failed to send {} to {}: {error}",
+					file.name, recipient.email
+				));
+			},
+		}
+	}
+
+	outcome
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use email::EmailError;
+
+	use super::*;
+	use crate::{
+		db::entity::DeviceFormatProfile,
+		filesystem::{media::tests::get_test_zip_path, DeviceTargetFormat},
+	};
+
+	/// A local stand-in for an SMTP server, which records the emails it is asked to send
+	#[derive(Default)]
+	struct RecordingSender {
+		sent: Mutex<Vec<(String, String, usize)>>,
+		fail: bool,
+	}
+
+	#[async_trait::async_trait]
+	impl AttachmentSender for RecordingSender {
+		async fn send_attachments(
+			&self,
+			_: &str,
+			recipient: &str,
+			payloads: Vec<AttachmentPayload>,
+		) -> EmailResult<()> {
+			if self.fail {
+				return Err(EmailError::NoPassword);
+			}
+
+			let mut sent = self.sent.lock().unwrap();
+			for payload in payloads {
+				sent.push((recipient.to_string(), payload.name, payload.content.len()));
+			}
+			Ok(())
+		}
+	}
+
+	fn recipient(target_format: DeviceTargetFormat) -> EmailRecipient {
+		EmailRecipient {
+			email: "reader@kindle.com".to_string(),
+			format_profile: DeviceFormatProfile {
+				target_format,
+				max_image_dimension: Some(600),
+			},
+		}
+	}
+
+	fn test_config(dir: &Path) -> StumpConfig {
+		StumpConfig::new(dir.to_string_lossy().to_string())
+	}
+
+	#[tokio::test]
+	async fn test_send_converted_book() {
+		let dir = tempfile::tempdir().unwrap();
+		let sender = RecordingSender::default();
+
+		let outcome = send_book_to_recipient(
+			&sender,
+			"book-id",
+			Path::new(&get_test_zip_path()),
+			&recipient(DeviceTargetFormat::Epub),
+			None,
+			&test_config(dir.path()),
+		)
+		.await;
+
+		assert!(outcome.errors.is_empty(), "{:?}", outcome.errors);
+		assert_eq!(outcome.sent.len(), 1);
+		assert_eq!(outcome.sent[0].filename, "book.epub");
+		assert_eq!(outcome.sent[0].media_id.as_deref(), Some("book-id"));
+
+		let sent = sender.sent.lock().unwrap();
+		assert_eq!(sent.len(), 1);
+		assert_eq!(sent[0].0, "reader@kindle.com");
+		assert_eq!(sent[0].1, "book.epub");
+		assert_eq!(sent[0].2 as i32, outcome.sent[0].size);
+	}
+
+	#[tokio::test]
+	async fn test_send_book_too_large() {
+		let dir = tempfile::tempdir().unwrap();
+		let sender = RecordingSender::default();
+
+		let outcome = send_book_to_recipient(
+			&sender,
+			"book-id",
+			Path::new(&get_test_zip_path()),
+			&recipient(DeviceTargetFormat::Original),
+			Some(16),
+			&test_config(dir.path()),
+		)
+		.await;
+
+		assert!(outcome.sent.is_empty());
+		assert_eq!(outcome.errors.len(), 1);
+		assert!(sender.sent.lock().unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_send_failure_is_reported() {
+		let dir = tempfile::tempdir().unwrap();
+		let sender = RecordingSender {
+			fail: true,
+			..Default::default()
+		};
+
+		let outcome = send_book_to_recipient(
+			&sender,
+			"book-id",
+			Path::new(&get_test_zip_path()),
+			&recipient(DeviceTargetFormat::Cbz),
+			None,
+			&test_config(dir.path()),
+		)
+		.await;
+
+		assert!(outcome.sent.is_empty());
+		assert_eq!(outcome.errors.len(), 1);
+		assert!(outcome.errors[0].contains("reader@kindle.com"));
+	}
+}
@@ -0,0 +1,102 @@
+use std::{path::PathBuf, str::FromStr};
+
+use email::{read_template_from_disk, EmailTemplate, TemplateOverrides};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+
+use crate::{
+	prisma::{email_template_override, PrismaClient},
+	CoreResult,
+};
+
+/// Where the source of an email template comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Type)]
+pub enum EmailTemplateOrigin {
+	/// The template bundled with Stump
+	#[serde(rename = "DEFAULT")]
+	Default,
+	/// A template file in the custom templates directory
+	#[serde(rename = "DISK")]
+	Disk,
+	/// A template which was overridden through the API
+	#[serde(rename = "OVERRIDE")]
+	Override,
+}
+
+/// An email template and the source it is currently rendered from
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Type)]
+pub struct EmailTemplateDetails {
+	/// The kind of email the template is for
+	pub template: EmailTemplate,
+	/// The handlebars source the template is currently rendered from
+	pub source: String,
+	/// The handlebars source of the template bundled with Stump
+	pub default_source: String,
+	/// Where the current source comes from
+	pub origin: EmailTemplateOrigin,
+	/// When the template was last overridden through the API, if it was
+	pub updated_at: Option<String>,
+}
+
+impl EmailTemplateDetails {
+	/// Resolve the source of a template. An override stored in the database takes priority over
+	/// a file in the templates directory, which takes priority over the default template.
+	pub fn resolve(
+		template: EmailTemplate,
+		stored_override: Option<email_template_override::Data>,
+		templates_dir: PathBuf,
+	) -> Self {
+		let default_source = template.default_source().to_string();
+
+		if let Some(data) = stored_override {
+			return Self {
+				template,
+				source: data.source,
+				default_source,
+				origin: EmailTemplateOrigin::Override,
+				updated_at: Some(data.updated_at.to_rfc3339()),
+			};
+		}
+
+		match read_template_from_disk(template, templates_dir) {
+			Some(source) => Self {
+				template,
+				source,
+				default_source,
+				origin: EmailTemplateOrigin::Disk,
+				updated_at: None,
+			},
+			None => Self {
+				template,
+				source: default_source.clone(),
+				default_source,
+				origin: EmailTemplateOrigin::Default,
+				updated_at: None,
+			},
+		}
+	}
+}
+
+/// Load the email templates which were overridden through the API. Stored templates which
+/// don't match a known template are ignored.
+pub async fn get_template_overrides(
+	client: &PrismaClient,
+) -> CoreResult<TemplateOverrides> {
+	let stored = client
+		.email_template_override()
+		.find_many(vec![])
+		.exec()
+		.await?;
+
+	Ok(stored
+		.into_iter()
+		.filter_map(|data| match EmailTemplate::from_str(&data.template) {
+			Ok(template) => Some((template, data.source)),
+			Err(error) => {
+				tracing::warn!(?error, "Ignoring unknown email template override");
+				None
+			},
+		})
+		.collect())
+}
@@ -6,7 +6,11 @@ use specta::Type;
 use tracing::error;
 use utoipa::ToSchema;
 
-use crate::{filesystem::FileError, prisma::media};
+use crate::{
+	filesystem::{media::EpubProcessor, ContentType, FileError},
+	prisma::{epub_positions, media, PrismaClient},
+	CoreResult,
+};
 
 use super::{media::Media, MediaAnnotation};
 
@@ -121,3 +125,273 @@ pub struct UpdateEpubProgress {
 	pub percentage: f64,
 	pub is_complete: Option<bool>,
 }
+
+/// The number of bytes in each position of an EPUB's positions list. Like Readium, each
+/// resource is split by its length in bytes, so positions line up with those computed by
+/// Readium clients.
+pub const EPUB_POSITION_LENGTH: usize = 1024;
+
+/// A resource in the spine of an EPUB and the number of positions it spans. This is what
+/// gets stored for an EPUB after analysis, since the full [`EpubPositionList`] can be
+/// derived from it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EpubSpinePositions {
+	/// The path of the resource, relative to the root of the EPUB
+	pub href: String,
+	/// The mime type of the resource
+	#[serde(rename = "type")]
+	pub _type: String,
+	/// The number of positions in the resource, always at least 1
+	pub count: i32,
+}
+
+impl EpubSpinePositions {
+	/// Creates the positions for a resource with the given length in bytes
+	pub fn new(href: String, _type: String, length: usize) -> Self {
+		let count = length.div_ceil(EPUB_POSITION_LENGTH).max(1);
+		Self {
+			href,
+			_type,
+			count: count as i32,
+		}
+	}
+}
+
+/// The locations of a single position in an EPUB
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubPositionLocations {
+	/// The position in the publication (1-based)
+	pub position: i32,
+	/// The progression within the resource, from 0.0 to 1.0
+	pub progression: f64,
+	/// The progression within the publication, from 0.0 to 1.0
+	pub total_progression: f64,
+}
+
+/// A single position in an EPUB, in the form of a Readium locator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+pub struct EpubPosition {
+	/// The path of the resource the position is in, relative to the root of the EPUB
+	pub href: String,
+	/// The mime type of the resource the position is in
+	#[serde(rename = "type")]
+	pub _type: String,
+	pub locations: EpubPositionLocations,
+}
+
+/// The positions list of an EPUB, which splits each resource in the spine into chunks of
+/// [`EPUB_POSITION_LENGTH`] bytes. Pages of an EPUB are positions in this list. See
+/// https://readium.org/architecture/models/locators/positions/
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+pub struct EpubPositionList {
+	pub total: i32,
+	pub positions: Vec<EpubPosition>,
+}
+
+impl EpubPositionList {
+	/// Expands the stored positions of each resource in the spine into the full list
+	pub fn from_spine(spine: Vec<EpubSpinePositions>) -> Self {
+		let total = spine.iter().map(|resource| resource.count).sum::<i32>();
+
+		let mut positions = Vec::with_capacity(total.max(0) as usize);
+		for resource in spine {
+			for index in 0..resource.count {
+				let position = positions.len() as i32 + 1;
+				positions.push(EpubPosition {
+					href: resource.href.clone(),
+					_type: resource._type.clone(),
+					locations: EpubPositionLocations {
+						position,
+						progression: index as f64 / resource.count as f64,
+						total_progression: (position - 1) as f64 / total as f64,
+					},
+				});
+			}
+		}
+
+		Self { total, positions }
+	}
+
+	/// Deserializes the positions stored for an EPUB, see [`EpubSpinePositions`]
+	pub fn from_stored(stored: &str) -> Result<Self, serde_json::Error> {
+		serde_json::from_str::<Vec<EpubSpinePositions>>(stored).map(Self::from_spine)
+	}
+
+	/// Gets a position by its 1-based index
+	pub fn get(&self, position: i32) -> Option<&EpubPosition> {
+		usize::try_from(position - 1)
+			.ok()
+			.and_then(|index| self.positions.get(index))
+	}
+
+	/// Gets the position containing the given progression through the publication, i.e.
+	/// the last position which starts at or before it
+	pub fn for_total_progression(&self, total_progression: f64) -> Option<&EpubPosition> {
+		self.positions
+			.iter()
+			.take_while(|position| {
+				position.locations.total_progression <= total_progression
+			})
+			.last()
+	}
+
+	/// Fills in whichever of a position and a progression through the publication is
+	/// missing, using the other
+	pub fn resolve_progress(
+		&self,
+		position: Option<i32>,
+		total_progression: Option<f64>,
+	) -> (Option<i32>, Option<f64>) {
+		let position = position.or_else(|| {
+			total_progression
+				.and_then(|progression| self.for_total_progression(progression))
+				.map(|position| position.locations.position)
+		});
+		let total_progression = total_progression.or_else(|| {
+			position
+				.and_then(|position| self.get(position))
+				.map(|position| position.locations.total_progression)
+		});
+
+		(position, total_progression)
+	}
+
+	/// Get the positions list stored for the book with the given ID. Returns `None` when the
+	/// book isn't an EPUB, or its stored positions are missing or inconsistent with its page
+	/// count, e.g. because it hasn't been analyzed since the file changed.
+	pub async fn stored_for_book(
+		client: &PrismaClient,
+		id: &str,
+		path: &str,
+	) -> CoreResult<Option<Self>> {
+		if !ContentType::from_file(path).is_epub() {
+			return Ok(None);
+		}
+
+		let book = client
+			.media()
+			.find_unique(media::id::equals(id.to_string()))
+			.with(media::epub_positions::fetch())
+			.exec()
+			.await?;
+
+		Ok(book.and_then(|book| {
+			let stored = book.epub_positions().ok().flatten()?;
+			Self::from_stored(&stored.positions)
+				.inspect_err(|error| {
+					tracing::error!(?error, "Failed to deserialize stored positions");
+				})
+				.ok()
+				.filter(|positions| positions.total == book.pages)
+		}))
+	}
+
+	/// Gets the positions list of an EPUB, using its stored positions when they are
+	/// consistent with its page count and reading them from the file otherwise. This is
+	/// blocking, and should be called from a blocking context.
+	pub fn resolve(
+		stored: Option<&epub_positions::Data>,
+		path: &str,
+		pages: i32,
+	) -> Result<Self, FileError> {
+		let stored = stored.and_then(|stored| {
+			Self::from_stored(&stored.positions)
+				.inspect_err(|error| {
+					tracing::error!(?error, "Failed to deserialize stored positions");
+				})
+				.ok()
+		});
+
+		match stored {
+			Some(positions) if positions.total == pages => Ok(positions),
+			_ => EpubProcessor::get_positions(path),
+		}
+	}
+}
+
+/// Serializes the positions of each resource in the spine of an EPUB for storage
+pub fn spine_positions_to_string(
+	spine: &[EpubSpinePositions],
+) -> Result<String, serde_json::Error> {
+	serde_json::to_string(spine)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn spine() -> Vec<EpubSpinePositions> {
+		vec![
+			EpubSpinePositions::new(
+				"OEBPS/cover.xhtml".to_string(),
+				"application/xhtml+xml".to_string(),
+				0,
+			),
+			EpubSpinePositions::new(
+				"OEBPS/chapter1.xhtml".to_string(),
+				"application/xhtml+xml".to_string(),
+				EPUB_POSITION_LENGTH * 2 + 1,
+			),
+		]
+	}
+
+	#[test]
+	fn test_spine_positions_count() {
+		let counts = spine()
+			.into_iter()
+			.map(|resource| resource.count)
+			.collect::<Vec<_>>();
+		assert_eq!(counts, vec![1, 3]);
+	}
+
+	#[test]
+	fn test_position_list_from_spine() {
+		let list = EpubPositionList::from_spine(spine());
+		assert_eq!(list.total, 4);
+		assert_eq!(list.positions.len(), 4);
+
+		let third = list.get(3).unwrap();
+		assert_eq!(third.href, "OEBPS/chapter1.xhtml");
+		assert_eq!(third.locations.position, 3);
+		assert_eq!(third.locations.progression, 1.0 / 3.0);
+		assert_eq!(third.locations.total_progression, 0.5);
+
+		assert!(list.get(0).is_none());
+		assert!(list.get(5).is_none());
+	}
+
+	#[test]
+	fn test_position_for_total_progression() {
+		let list = EpubPositionList::from_spine(spine());
+		let position = |p: f64| {
+			list.for_total_progression(p)
+				.map(|position| position.locations.position)
+		};
+
+		assert_eq!(position(0.0), Some(1));
+		assert_eq!(position(0.3), Some(2));
+		assert_eq!(position(0.5), Some(3));
+		assert_eq!(position(1.0), Some(4));
+	}
+
+	#[test]
+	fn test_resolve_progress() {
+		let list = EpubPositionList::from_spine(spine());
+
+		assert_eq!(list.resolve_progress(Some(3), None), (Some(3), Some(0.5)));
+		assert_eq!(list.resolve_progress(None, Some(0.3)), (Some(2), Some(0.3)));
+		assert_eq!(
+			list.resolve_progress(Some(1), Some(0.9)),
+			(Some(1), Some(0.9))
+		);
+		assert_eq!(list.resolve_progress(None, None), (None, None));
+	}
+
+	#[test]
+	fn test_stored_positions_round_trip() {
+		let stored = spine_positions_to_string(&spine()).unwrap();
+		let list = EpubPositionList::from_stored(&stored).unwrap();
+		assert_eq!(list, EpubPositionList::from_spine(spine()));
+	}
+}
@@ -5,7 +5,7 @@ use utoipa::ToSchema;
 use crate::{
 	filesystem::{
 		image::ThumbnailGenerationOutput,
-		scanner::{LibraryScanOutput, SeriesScanOutput},
+		scanner::{LibraryScanOutput, ScanDiff, SeriesScanOutput},
 	},
 	job::JobStatus,
 	prisma::job,
@@ -28,6 +28,17 @@ pub enum CoreJobOutput {
 	External(ExternalJobOutput),
 }
 
+impl CoreJobOutput {
+	/// The changes recorded by a dry run scan, if this is the output of one
+	pub fn scan_diff(&self) -> Option<&ScanDiff> {
+		match self {
+			CoreJobOutput::LibraryScan(output) => output.diff(),
+			CoreJobOutput::SeriesScan(output) => output.diff(),
+			_ => None,
+		}
+	}
+}
+
 #[derive(Clone, Serialize, Deserialize, ToSchema, Type)]
 pub struct PersistedJob {
 	/// The unique identifier of the job
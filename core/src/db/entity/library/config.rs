@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -8,12 +8,23 @@ use crate::{
 	db::entity::common::{ReadingDirection, ReadingImageScaleFit, ReadingMode},
 	filesystem::{
 		image::ImageProcessorOptions,
-		scanner::{CustomVisit, ScanConfig, ScanOptions},
+		pdf::PdfRenderOptions,
+		scanner::{
+			CustomVisit, ScanConfig, ScanOptions, SeriesDetection, SeriesResolver,
+			WatcherBackend,
+		},
 	},
 	prisma::library_config,
+	CoreError, CoreResult,
 };
 
-use super::{IgnoreRules, LibraryPattern};
+use super::{FilenameRules, IgnoreRules, LibraryPattern, WatcherMode};
+
+/// The number of seconds between polls when a polled library has no interval set
+pub const DEFAULT_POLL_INTERVAL_SECS: i32 = 60;
+/// The smallest allowed number of seconds between polls, since every poll reads the metadata
+/// of every file in the library
+pub const MIN_POLL_INTERVAL_SECS: i32 = 10;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Type, ToSchema, Default)]
 pub struct LibraryConfig {
@@ -25,6 +36,13 @@ pub struct LibraryConfig {
 	pub generate_koreader_hashes: bool,
 	pub process_metadata: bool,
 	pub watch: bool,
+	/// How the library is watched for changes when `watch` is enabled
+	#[serde(default)]
+	pub watcher_mode: WatcherMode,
+	/// The number of seconds between polls when the library is watched by polling. Defaults
+	/// to 60 seconds when not set.
+	#[serde(default)]
+	pub poll_interval: Option<i32>,
 	pub library_pattern: LibraryPattern,
 	pub thumbnail_config: Option<ImageProcessorOptions>,
 	#[serde(default)] // TODO: remove this after update with experimental
@@ -35,6 +53,18 @@ pub struct LibraryConfig {
 	pub default_reading_image_scale_fit: ReadingImageScaleFit,
 	#[serde(default)]
 	pub ignore_rules: IgnoreRules,
+	/// Custom rules for parsing the series, number, volume and year of books from their
+	/// paths, which are tried before the built-in patterns
+	#[serde(default)]
+	pub filename_rules: FilenameRules,
+	/// How series are detected in a collection-based library. When not set, each top-level
+	/// directory is a series.
+	#[serde(default)]
+	pub series_detection: Option<SeriesDetection>,
+	/// How the pages of PDFs in the library are rendered to images. When not set, pages are
+	/// rendered to PNG at 150 DPI.
+	#[serde(default)]
+	pub pdf_render_config: Option<PdfRenderOptions>,
 	// TODO(prisma-nested-create): Refactor once nested create is supported
 	// https://github.com/Brendonovich/prisma-client-rust/issues/44
 	#[specta(optional)]
@@ -46,6 +76,48 @@ impl LibraryConfig {
 		self.library_pattern == LibraryPattern::CollectionBased
 	}
 
+	/// Build the resolver for which series each book in the library at the given path
+	/// belongs to
+	pub fn series_resolver(&self, library_path: &str) -> CoreResult<SeriesResolver> {
+		SeriesResolver::new(
+			library_path,
+			&self.library_pattern,
+			self.series_detection.as_ref(),
+		)
+	}
+
+	/// Whether the library uses a series detection strategy other than the default one for
+	/// its pattern
+	pub fn has_series_detection(&self) -> bool {
+		self.is_collection_based() && self.series_detection.is_some()
+	}
+
+	/// Validate that the poll interval, if set, is not too short
+	pub fn validate_poll_interval(&self) -> CoreResult<()> {
+		match self.poll_interval {
+			Some(interval) if interval < MIN_POLL_INTERVAL_SECS => {
+				Err(CoreError::BadRequest(format!(
+					"The poll interval must be at least {MIN_POLL_INTERVAL_SECS} seconds"
+				)))
+			},
+			_ => Ok(()),
+		}
+	}
+
+	/// The backend the library should be watched with, according to its watcher mode
+	pub fn watcher_backend(&self) -> WatcherBackend {
+		match self.watcher_mode {
+			WatcherMode::Native => WatcherBackend::Native,
+			WatcherMode::Poll => {
+				let interval = self
+					.poll_interval
+					.unwrap_or(DEFAULT_POLL_INTERVAL_SECS)
+					.max(MIN_POLL_INTERVAL_SECS);
+				WatcherBackend::Poll(Duration::from_secs(interval as u64))
+			},
+		}
+	}
+
 	pub fn apply(&mut self, options: ScanOptions) {
 		if let ScanConfig::Custom(CustomVisit {
 			regen_hashes,
@@ -74,6 +146,8 @@ impl From<library_config::Data> for LibraryConfig {
 			generate_koreader_hashes: data.generate_koreader_hashes,
 			process_metadata: data.process_metadata,
 			watch: data.watch,
+			watcher_mode: WatcherMode::from_str(&data.watcher_mode).unwrap_or_default(),
+			poll_interval: data.poll_interval,
 			library_pattern: LibraryPattern::from(data.library_pattern),
 			default_reading_dir: ReadingDirection::from_str(
 				data.default_reading_dir.as_str(),
@@ -95,6 +169,17 @@ impl From<library_config::Data> for LibraryConfig {
 				.map_or_else(IgnoreRules::default, |rules| {
 					IgnoreRules::try_from(rules).unwrap_or_default()
 				}),
+			filename_rules: data
+				.filename_rules
+				.map_or_else(FilenameRules::default, |rules| {
+					FilenameRules::try_from(rules).unwrap_or_default()
+				}),
+			series_detection: data
+				.series_detection
+				.and_then(|detection| SeriesDetection::try_from(detection).ok()),
+			pdf_render_config: data
+				.pdf_render_config
+				.and_then(|options| PdfRenderOptions::try_from(options).ok()),
 			library_id: data.library_id,
 		}
 	}
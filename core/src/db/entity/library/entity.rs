@@ -86,6 +86,42 @@ impl fmt::Display for LibraryPattern {
 	}
 }
 
+/// How a library is watched for changes when watching is enabled
+#[derive(
+	Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, Type, ToSchema,
+)]
+pub enum WatcherMode {
+	/// Use the native filesystem events of the operating system
+	#[default]
+	#[serde(rename = "NATIVE")]
+	Native,
+	/// Periodically compare the modification time and size of the files in the library.
+	/// This is needed for network shares (e.g. SMB or NFS), which don't emit native events
+	#[serde(rename = "POLL")]
+	Poll,
+}
+
+impl FromStr for WatcherMode {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_uppercase().as_str() {
+			"NATIVE" | "" => Ok(WatcherMode::Native),
+			"POLL" => Ok(WatcherMode::Poll),
+			_ => Err(format!("Invalid watcher mode: {s}")),
+		}
+	}
+}
+
+impl fmt::Display for WatcherMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			WatcherMode::Native => write!(f, "NATIVE"),
+			WatcherMode::Poll => write!(f, "POLL"),
+		}
+	}
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone, Type, ToSchema)]
 pub enum LibraryScanMode {
 	#[serde(rename = "DEFAULT")]
@@ -8,6 +8,7 @@ library::select!(library_idents_select { id path });
 
 library::select!(library_tags_select {
 	id
+	path
 	tags: select {
 		id
 		name
@@ -1,4 +1,5 @@
 use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use utoipa::ToSchema;
@@ -99,6 +100,81 @@ impl TryFrom<IgnoreRules> for GlobSet {
 	}
 }
 
+/// The names of the capture groups which a filename rule may use to extract values
+pub const FILENAME_RULE_GROUPS: [&str; 4] = ["series", "number", "volume", "year"];
+
+// Note: These are regular expressions, which are stored as strings and validated upon
+// creation, the same as the ignore rules above.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
+pub struct FilenameRules(Vec<String>);
+
+impl FilenameRules {
+	/// Create a new set of filename rules. This will validate each rule to ensure that it is
+	/// a valid regular expression with at least one of the named capture groups in
+	/// [FILENAME_RULE_GROUPS].
+	pub fn new(rules: Vec<String>) -> CoreResult<Self> {
+		let rules = Self(rules);
+		rules.validate()?;
+		Ok(rules)
+	}
+
+	/// Check if the filename rules set is empty
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Validate each of the rules. This is separate from [FilenameRules::new] since rules
+	/// which are deserialized are not validated.
+	pub fn validate(&self) -> CoreResult<()> {
+		self.build().map(|_| ())
+	}
+
+	/// Compile the rules into regular expressions, in the order they should be tried
+	pub fn build(&self) -> CoreResult<Vec<Regex>> {
+		self.0
+			.iter()
+			.map(|rule| {
+				let regex = Regex::new(rule).map_err(|error| {
+					CoreError::BadRequest(format!(
+						"Invalid filename rule {rule}: {error}"
+					))
+				})?;
+				let has_known_group = regex
+					.capture_names()
+					.flatten()
+					.any(|name| FILENAME_RULE_GROUPS.contains(&name));
+				if has_known_group {
+					Ok(regex)
+				} else {
+					Err(CoreError::BadRequest(format!(
+						"Filename rule {rule} must capture at least one of: {}",
+						FILENAME_RULE_GROUPS.join(", ")
+					)))
+				}
+			})
+			.collect()
+	}
+
+	/// Serialize the filename rules to a byte vector, which gets dumped into the database.
+	pub fn as_bytes(&self) -> CoreResult<Vec<u8>> {
+		serde_json::to_vec(self).map_err(|error| {
+			tracing::error!(?error, "Failed to serialize filename rules");
+			error.into()
+		})
+	}
+}
+
+impl TryFrom<Vec<u8>> for FilenameRules {
+	type Error = CoreError;
+
+	fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+		serde_json::from_slice(&value).map_err(|error| {
+			tracing::error!(?error, "Failed to deserialize filename rules");
+			error.into()
+		})
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -228,4 +304,33 @@ mod tests {
 
 		assert!(!rules.is_empty());
 	}
+
+	#[test]
+	fn test_filename_rules() {
+		let rules = FilenameRules::new(vec![
+			r"(?P<series>.+) #(?P<number>\d+)".to_string(),
+			r"Volume (?P<volume>\d+)".to_string(),
+		])
+		.unwrap();
+
+		assert_eq!(rules.build().unwrap().len(), 2);
+	}
+
+	#[test]
+	fn test_invalid_filename_rules() {
+		assert!(FilenameRules::new(vec!["(".to_string()]).is_err());
+		// A rule without any of the known groups can't extract anything
+		assert!(FilenameRules::new(vec![r"(?P<issue>\d+)".to_string()]).is_err());
+		assert!(FilenameRules::new(vec![r"(\d+)".to_string()]).is_err());
+	}
+
+	#[test]
+	fn test_filename_rules_serialization() {
+		let rules = FilenameRules::new(vec![r"#(?P<number>\d+)".to_string()]).unwrap();
+
+		let bytes = rules.as_bytes().unwrap();
+		let deserialized = FilenameRules::try_from(bytes).unwrap();
+
+		assert_eq!(rules.0, deserialized.0);
+	}
 }
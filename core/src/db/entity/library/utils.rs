@@ -1,8 +1,17 @@
+use prisma_client_rust::and;
+
 use crate::{
 	db::entity::User,
 	prisma::{library, user},
 };
 
+/// A filter for the libraries a user may access. This excludes libraries hidden from the
+/// user, and any outside of their library allowlist if they have one.
 pub fn apply_library_not_hidden_from_user_filter(user: &User) -> library::WhereParam {
-	library::hidden_from_users::none(vec![user::id::equals(user.id.clone())])
+	let not_hidden =
+		library::hidden_from_users::none(vec![user::id::equals(user.id.clone())]);
+	match &user.library_allowlist {
+		Some(library_ids) => and![not_hidden, library::id::in_vec(library_ids.clone())],
+		None => not_hidden,
+	}
 }
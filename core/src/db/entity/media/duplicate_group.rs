@@ -0,0 +1,116 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+
+use crate::prisma::duplicate_group;
+
+use super::Media;
+
+/// The reason a set of media were considered to be duplicates of one another
+#[derive(
+	Debug, Clone, Copy, Deserialize, Serialize, Type, ToSchema, PartialEq, Eq, Hash,
+)]
+pub enum DuplicateReason {
+	/// The files have the same (sampled) file hash
+	#[serde(rename = "HASH")]
+	Hash,
+	/// The first pages of the files look the same, e.g. a re-encode or a different scan
+	#[serde(rename = "COVER")]
+	Cover,
+	/// The files have the same series, number and year in their metadata
+	#[serde(rename = "METADATA")]
+	Metadata,
+}
+
+impl fmt::Display for DuplicateReason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DuplicateReason::Hash => write!(f, "HASH"),
+			DuplicateReason::Cover => write!(f, "COVER"),
+			DuplicateReason::Metadata => write!(f, "METADATA"),
+		}
+	}
+}
+
+impl FromStr for DuplicateReason {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"HASH" => Ok(DuplicateReason::Hash),
+			"COVER" => Ok(DuplicateReason::Cover),
+			"METADATA" => Ok(DuplicateReason::Metadata),
+			_ => Err(()),
+		}
+	}
+}
+
+/// Join the reasons for a duplicate group into the format they are stored in
+pub fn join_duplicate_reasons(reasons: &[DuplicateReason]) -> String {
+	reasons
+		.iter()
+		.map(ToString::to_string)
+		.collect::<Vec<_>>()
+		.join(",")
+}
+
+/// A group of media which were found to likely be duplicates of one another by the
+/// duplicate analysis job
+#[derive(Debug, Clone, Deserialize, Serialize, Type, ToSchema)]
+pub struct DuplicateGroup {
+	pub id: String,
+	/// Why the media in the group were considered duplicates
+	pub reasons: Vec<DuplicateReason>,
+	pub created_at: String,
+	/// When the group was dismissed as not being duplicates, if it was
+	pub dismissed_at: Option<String>,
+	/// The media in the group
+	pub media: Vec<Media>,
+}
+
+impl From<duplicate_group::Data> for DuplicateGroup {
+	fn from(data: duplicate_group::Data) -> Self {
+		let media = data
+			.media()
+			.map(|group_media| {
+				group_media
+					.iter()
+					.filter_map(|item| item.media().ok().cloned())
+					.map(Media::from)
+					.collect()
+			})
+			.unwrap_or_default();
+
+		Self {
+			id: data.id,
+			reasons: data
+				.reasons
+				.split(',')
+				.filter_map(|reason| reason.parse().ok())
+				.collect(),
+			created_at: data.created_at.to_rfc3339(),
+			dismissed_at: data.dismissed_at.map(|dt| dt.to_rfc3339()),
+			media,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_duplicate_reasons_round_trip() {
+		let reasons = vec![DuplicateReason::Hash, DuplicateReason::Cover];
+		let joined = join_duplicate_reasons(&reasons);
+		assert_eq!(joined, "HASH,COVER");
+
+		let parsed = joined
+			.split(',')
+			.filter_map(|reason| reason.parse().ok())
+			.collect::<Vec<DuplicateReason>>();
+		assert_eq!(parsed, reasons);
+	}
+}
@@ -7,14 +7,21 @@ use utoipa::ToSchema;
 
 use crate::{
 	db::{
-		entity::{common::Cursor, LibraryConfig, MediaMetadata, Series, Tag},
+		entity::{
+			common::{Cursor, ReadingDirection, ReadingMode},
+			LibraryConfig, MangaFormat, MediaMetadata, ReadingOverrides,
+			ReadingPreferences, Series, Tag,
+		},
 		FileStatus,
 	},
 	error::CoreError,
 	prisma::{active_reading_session, media},
 };
 
-use super::{ActiveReadingSession, Bookmark, FinishedReadingSession};
+use super::{
+	prisma_macros::media_reading_preferences, ActiveReadingSession, Bookmark,
+	FinishedReadingSession,
+};
 
 // TODO: Now that we have a single ActiveReadingSession, reevaluate if we need root-level fields for current_page, current_epubcfi, etc.
 
@@ -39,6 +46,8 @@ pub struct Media {
 	pub created_at: String,
 	/// The timestamp when the file was last modified on disk.
 	pub modified_at: Option<String>,
+	/// The timestamp when the media was moved to the trash, if it is in the trash.
+	pub deleted_at: Option<String>,
 	/// The hash of the file contents. Used to ensure only one instance of a file in the database.
 	pub hash: Option<String>,
 	/// The hash of the file contents using the koreader algorithm.
@@ -47,6 +56,14 @@ pub struct Media {
 	pub path: String,
 	/// The status of the media
 	pub status: FileStatus,
+	/// Why the media is in the `ERROR` status, e.g. the integrity check the file failed.
+	pub status_reason: Option<String>,
+	/// The timestamp when the file was last checked for integrity.
+	pub verified_at: Option<String>,
+	/// The reading direction of the media, overriding the defaults of its series and library.
+	pub reading_direction: Option<ReadingDirection>,
+	/// The reading mode of the media, overriding the defaults of its series and library.
+	pub reading_mode: Option<ReadingMode>,
 	/// The ID of the series this media belongs to.
 	pub series_id: String,
 	/// Optional metadata for the media. Will be `None` if the relation is not loaded, or if the
@@ -177,6 +194,14 @@ impl From<media::Data> for Media {
 				.collect::<Vec<Bookmark>>()
 		});
 
+		let ReadingOverrides {
+			reading_direction,
+			reading_mode,
+		} = ReadingOverrides::from_stored(
+			data.reading_direction.as_deref(),
+			data.reading_mode.as_deref(),
+		);
+
 		Media {
 			id: data.id,
 			name: data.name,
@@ -186,10 +211,15 @@ impl From<media::Data> for Media {
 			updated_at: data.updated_at.to_rfc3339(),
 			created_at: data.created_at.to_rfc3339(),
 			modified_at: data.modified_at.map(|dt| dt.to_rfc3339()),
+			deleted_at: data.deleted_at.map(|dt| dt.to_rfc3339()),
 			hash: data.hash,
 			koreader_hash: data.koreader_hash,
 			path: data.path,
 			status: FileStatus::from_str(&data.status).unwrap_or(FileStatus::Error),
+			status_reason: data.status_reason,
+			verified_at: data.verified_at.map(|dt| dt.to_rfc3339()),
+			reading_direction,
+			reading_mode,
 			series_id: data.series_id.unwrap(),
 			metadata,
 			series,
@@ -203,3 +233,38 @@ impl From<media::Data> for Media {
 		}
 	}
 }
+
+impl From<media_reading_preferences::Data> for ReadingPreferences {
+	fn from(data: media_reading_preferences::Data) -> Self {
+		let book = ReadingOverrides::from_stored(
+			data.reading_direction.as_deref(),
+			data.reading_mode.as_deref(),
+		);
+		let metadata_direction = data
+			.metadata
+			.and_then(|metadata| metadata.manga)
+			.and_then(|manga| MangaFormat::from_str(&manga).ok())
+			.and_then(|manga| manga.reading_direction());
+		let (series, library_defaults) = data
+			.series
+			.map(|series| {
+				let overrides = ReadingOverrides::from_stored(
+					series.reading_direction.as_deref(),
+					series.reading_mode.as_deref(),
+				);
+				let library_defaults = series
+					.library
+					.map(|library| {
+						ReadingPreferences::from_library_defaults(
+							&library.config.default_reading_dir,
+							&library.config.default_reading_mode,
+						)
+					})
+					.unwrap_or_default();
+				(overrides, library_defaults)
+			})
+			.unwrap_or_default();
+
+		ReadingPreferences::resolve(&book, &series, metadata_direction, library_defaults)
+	}
+}
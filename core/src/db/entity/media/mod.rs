@@ -1,5 +1,6 @@
 mod annotation;
 mod bookmark;
+mod duplicate_group;
 mod entity;
 pub(crate) mod prisma_macros;
 mod reading_session;
@@ -7,5 +8,6 @@ pub(crate) mod utils;
 
 pub use annotation::*;
 pub use bookmark::*;
+pub use duplicate_group::*;
 pub use entity::*;
 pub use reading_session::*;
@@ -21,6 +21,19 @@ media::select!(media_thumbnail {
    }
 });
 
+media::select!(media_reading_preferences {
+   reading_direction
+   reading_mode
+   metadata: select { manga }
+   series: select {
+	  reading_direction
+	  reading_mode
+	  library: select {
+		config: select { default_reading_dir default_reading_mode }
+	  }
+   }
+});
+
 active_reading_session::include!(reading_session_with_book_pages {
 	media: select { pages }
 });
@@ -1,8 +1,12 @@
+use prisma_client_rust::chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use utoipa::ToSchema;
 
-use crate::prisma::{active_reading_session, finished_reading_session};
+use crate::{
+	prisma::{active_reading_session, finished_reading_session},
+	CoreError, CoreResult,
+};
 
 use crate::db::entity::{Media, User};
 
@@ -23,6 +27,8 @@ pub struct ActiveReadingSession {
 	// TODO(specta): replace with DateTime<FixedOffset>
 	/// The timestamp when the reading session was started
 	pub started_at: String,
+	/// The timestamp of the last recorded reading activity (heartbeat or progress update)
+	pub last_heartbeat_at: Option<String>,
 	/// The ID of the media which has progress.
 	pub media_id: String,
 	/// The media which has progress. Will be `None` if the relation is not loaded.
@@ -79,6 +85,7 @@ impl From<active_reading_session::Data> for ActiveReadingSession {
 			page: data.page,
 			epubcfi: data.epubcfi,
 			started_at: data.started_at.to_rfc3339(),
+			last_heartbeat_at: data.last_heartbeat_at.map(|dt| dt.to_rfc3339()),
 			elapsed_seconds: data.elapsed_seconds,
 			percentage_completed: data.percentage_completed,
 			media_id: data.media_id,
@@ -98,6 +105,7 @@ impl From<reading_session_with_book_pages::Data> for ActiveReadingSession {
 			percentage_completed: value.percentage_completed,
 			elapsed_seconds: value.elapsed_seconds,
 			started_at: value.started_at.to_rfc3339(),
+			last_heartbeat_at: value.last_heartbeat_at.map(|dt| dt.to_rfc3339()),
 			media_id: value.media_id,
 			media: None,
 			user_id: value.user_id,
@@ -127,3 +135,248 @@ impl From<finished_reading_session::Data> for FinishedReadingSession {
 		}
 	}
 }
+
+/// The largest gap, in seconds, between two recorded reading activities which is still counted
+/// as time spent reading. Anything longer is assumed to be idle time (e.g. a reader left open
+/// overnight), so the clock restarts instead of accruing the gap.
+pub const MAX_READING_HEARTBEAT_GAP_SECONDS: i64 = 5 * 60;
+
+/// A small allowance for client-reported deltas, to account for network latency and clock
+/// drift between the client and the server
+const HEARTBEAT_DELTA_TOLERANCE_SECONDS: i64 = 5;
+
+/// The server-side reading time of an active session. Time is accumulated from the gaps between
+/// heartbeats (and progress updates), rather than trusting whatever total a client sends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadingTime {
+	/// The total number of seconds accrued so far
+	pub elapsed_seconds: i64,
+	/// The timestamp of the last recorded reading activity
+	pub last_heartbeat_at: Option<DateTime<FixedOffset>>,
+}
+
+impl ReadingTime {
+	pub fn new(
+		elapsed_seconds: Option<i64>,
+		last_heartbeat_at: Option<DateTime<FixedOffset>>,
+	) -> Self {
+		Self {
+			elapsed_seconds: elapsed_seconds.unwrap_or_default().max(0),
+			last_heartbeat_at,
+		}
+	}
+
+	/// Returns the number of seconds since the last heartbeat, if it is plausible that the
+	/// user was reading the entire time
+	pub fn gap_seconds(&self, now: DateTime<FixedOffset>) -> Option<i64> {
+		let last = self.last_heartbeat_at?;
+		let gap = (now - last).num_seconds();
+		(0..=MAX_READING_HEARTBEAT_GAP_SECONDS)
+			.contains(&gap)
+			.then_some(gap)
+	}
+
+	/// Records reading activity at `now`, accruing the time since the last heartbeat when
+	/// the gap is plausible. An implausible gap only restarts the clock.
+	pub fn heartbeat(self, now: DateTime<FixedOffset>) -> Self {
+		let gap = self.gap_seconds(now).unwrap_or_default();
+		Self {
+			elapsed_seconds: self.elapsed_seconds + gap,
+			last_heartbeat_at: Some(now),
+		}
+	}
+
+	/// Records reading activity at `now` using a delta reported by the client. The delta is
+	/// rejected if it is negative or exceeds the time the server observed since the last
+	/// heartbeat. Without a plausible previous heartbeat nothing is accrued, and the clock
+	/// only starts.
+	pub fn heartbeat_with_delta(
+		self,
+		delta_seconds: i64,
+		now: DateTime<FixedOffset>,
+	) -> CoreResult<Self> {
+		if delta_seconds < 0 {
+			return Err(CoreError::BadRequest(format!(
+				"Implausible reading time delta of {delta_seconds}s"
+			)));
+		}
+
+		let Some(observed) = self.gap_seconds(now) else {
+			return Ok(self.heartbeat(now));
+		};
+
+		if delta_seconds > observed + HEARTBEAT_DELTA_TOLERANCE_SECONDS {
+			return Err(CoreError::BadRequest(format!(
+				"Implausible reading time delta of {delta_seconds}s (observed {observed}s since the last heartbeat)"
+			)));
+		}
+
+		Ok(Self {
+			elapsed_seconds: self.elapsed_seconds + delta_seconds.min(observed),
+			last_heartbeat_at: Some(now),
+		})
+	}
+
+	/// Records reading activity at `now`, reconciling a total reported by a client (e.g. an
+	/// older client which tracks time itself) with the server-side total. The reported value
+	/// may add at most [MAX_READING_HEARTBEAT_GAP_SECONDS] since the last heartbeat, is capped
+	/// at the wall-clock time since the session started, and never decreases the accrued time.
+	pub fn heartbeat_with_reported(
+		self,
+		reported_seconds: Option<i64>,
+		started_at: DateTime<FixedOffset>,
+		now: DateTime<FixedOffset>,
+	) -> Self {
+		let accrued = self.heartbeat(now);
+		let Some(reported) = reported_seconds else {
+			return accrued;
+		};
+
+		let max_increase =
+			self.last_heartbeat_at
+				.map_or(MAX_READING_HEARTBEAT_GAP_SECONDS, |last| {
+					(now - last)
+						.num_seconds()
+						.clamp(0, MAX_READING_HEARTBEAT_GAP_SECONDS)
+				});
+		let wall_clock = (now - started_at).num_seconds().max(0);
+		let cap = (self.elapsed_seconds + max_increase).min(wall_clock);
+		Self {
+			elapsed_seconds: accrued.elapsed_seconds.max(reported.clamp(0, cap)),
+			..accrued
+		}
+	}
+
+	/// Converts the reading time into the parameters needed to persist it on an active session
+	pub fn into_active_set_params(self) -> Vec<active_reading_session::SetParam> {
+		vec![
+			active_reading_session::elapsed_seconds::set(Some(self.elapsed_seconds)),
+			active_reading_session::last_heartbeat_at::set(self.last_heartbeat_at),
+		]
+	}
+
+	/// Converts the reading time into the parameters needed to roll it up into a finished
+	/// session
+	pub fn into_finished_set_params(self) -> Vec<finished_reading_session::SetParam> {
+		vec![finished_reading_session::elapsed_seconds::set(Some(
+			self.elapsed_seconds,
+		))]
+	}
+}
+
+impl From<&active_reading_session::Data> for ReadingTime {
+	fn from(data: &active_reading_session::Data) -> Self {
+		Self::new(data.elapsed_seconds, data.last_heartbeat_at)
+	}
+}
+
+impl From<Option<&active_reading_session::Data>> for ReadingTime {
+	fn from(data: Option<&active_reading_session::Data>) -> Self {
+		data.map(ReadingTime::from).unwrap_or_default()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use prisma_client_rust::chrono::{Duration, Utc};
+
+	use super::*;
+
+	fn now() -> DateTime<FixedOffset> {
+		Utc::now().into()
+	}
+
+	#[test]
+	fn test_first_heartbeat_starts_clock() {
+		let now = now();
+		let time = ReadingTime::default().heartbeat(now);
+		assert_eq!(time.elapsed_seconds, 0);
+		assert_eq!(time.last_heartbeat_at, Some(now));
+	}
+
+	#[test]
+	fn test_heartbeat_accrues_plausible_gap() {
+		let now = now();
+		let time =
+			ReadingTime::new(Some(100), Some(now - Duration::seconds(30))).heartbeat(now);
+		assert_eq!(time.elapsed_seconds, 130);
+	}
+
+	#[test]
+	fn test_heartbeat_discards_idle_gap() {
+		let now = now();
+		let time =
+			ReadingTime::new(Some(100), Some(now - Duration::hours(8))).heartbeat(now);
+		assert_eq!(time.elapsed_seconds, 100);
+		assert_eq!(time.last_heartbeat_at, Some(now));
+	}
+
+	#[test]
+	fn test_heartbeat_with_delta() {
+		let now = now();
+		let time = ReadingTime::new(Some(10), Some(now - Duration::seconds(60)));
+		assert_eq!(
+			time.heartbeat_with_delta(45, now).unwrap().elapsed_seconds,
+			55
+		);
+		assert!(time.heartbeat_with_delta(600, now).is_err());
+		assert!(time.heartbeat_with_delta(-1, now).is_err());
+	}
+
+	#[test]
+	fn test_heartbeat_with_delta_starts_clock() {
+		let now = now();
+
+		let time = ReadingTime::default()
+			.heartbeat_with_delta(120, now)
+			.unwrap();
+		assert_eq!(time.elapsed_seconds, 0);
+		assert_eq!(time.last_heartbeat_at, Some(now));
+
+		let time = ReadingTime::new(Some(100), Some(now - Duration::hours(8)))
+			.heartbeat_with_delta(120, now)
+			.unwrap();
+		assert_eq!(time.elapsed_seconds, 100);
+		assert_eq!(time.last_heartbeat_at, Some(now));
+	}
+
+	#[test]
+	fn test_heartbeat_with_reported_is_capped() {
+		let now = now();
+		let started_at = now - Duration::seconds(120);
+		let time = ReadingTime::new(Some(30), None);
+
+		let reconciled = time.heartbeat_with_reported(Some(90), started_at, now);
+		assert_eq!(reconciled.elapsed_seconds, 90);
+		assert_eq!(reconciled.last_heartbeat_at, Some(now));
+
+		let reconciled = time.heartbeat_with_reported(Some(10_000), started_at, now);
+		assert_eq!(reconciled.elapsed_seconds, 120);
+
+		let reconciled = time.heartbeat_with_reported(Some(5), started_at, now);
+		assert_eq!(reconciled.elapsed_seconds, 30);
+
+		let reconciled = time.heartbeat_with_reported(None, started_at, now);
+		assert_eq!(reconciled.elapsed_seconds, 30);
+	}
+
+	#[test]
+	fn test_heartbeat_with_reported_caps_increase_since_last_heartbeat() {
+		let now = now();
+		// A session left open for days only gains the heartbeat gap limit per report
+		let started_at = now - Duration::days(3);
+		let time = ReadingTime::new(Some(600), Some(now - Duration::hours(8)));
+
+		let reconciled =
+			time.heartbeat_with_reported(Some(3 * 24 * 60 * 60), started_at, now);
+		assert_eq!(
+			reconciled.elapsed_seconds,
+			600 + MAX_READING_HEARTBEAT_GAP_SECONDS
+		);
+
+		let time = ReadingTime::new(Some(600), Some(now - Duration::seconds(60)));
+		let reconciled =
+			time.heartbeat_with_reported(Some(3 * 24 * 60 * 60), started_at, now);
+		assert_eq!(reconciled.elapsed_seconds, 660);
+	}
+}
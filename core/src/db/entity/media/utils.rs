@@ -59,7 +59,10 @@ pub fn apply_media_age_restriction(min_age: i32, restrict_on_unset: bool) -> Whe
 }
 
 pub fn apply_media_library_not_hidden_for_user_filter(user: &User) -> Vec<WhereParam> {
-	vec![media::series::is(vec![series::library::is(vec![
-		apply_library_not_hidden_from_user_filter(user),
-	])])]
+	vec![
+		media::deleted_at::equals(None),
+		media::series::is(vec![series::library::is(vec![
+			apply_library_not_hidden_from_user_filter(user),
+		])]),
+	]
 }
@@ -1,6 +1,10 @@
+use std::str::FromStr;
+
 use regex::Regex;
 use serde::{Deserialize, Deserializer};
 
+use super::MangaFormat;
+
 pub fn string_list_deserializer<'de, D>(
 	deserializer: D,
 ) -> Result<Option<Vec<String>>, D::Error>
@@ -35,6 +39,18 @@ where
 	Ok(parse_age_restriction(&str_sequence))
 }
 
+/// Deserializes the ComicInfo `Manga` field. Unknown or unrecognized values are treated as if
+/// the field was not set.
+pub fn manga_deserializer<'de, D>(
+	deserializer: D,
+) -> Result<Option<MangaFormat>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	Ok(Option::<String>::deserialize(deserializer)?
+		.and_then(|value| MangaFormat::from_str(&value).ok()))
+}
+
 pub fn parse_age_restriction(str_sequence: &str) -> Option<i32> {
 	// check for the first case G/PG/PG-13/R
 	let movie_rating = match str_sequence.to_lowercase().as_str() {
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, str::FromStr};
 
 use merge::Merge;
 use pdf::{
@@ -12,9 +12,10 @@ use utoipa::ToSchema;
 
 use crate::{
 	db::entity::{
+		common::ReadingDirection,
 		metadata::common::{
-			age_rating_deserializer, comma_separated_list_to_vec, parse_age_restriction,
-			string_list_deserializer,
+			age_rating_deserializer, comma_separated_list_to_vec, manga_deserializer,
+			parse_age_restriction, string_list_deserializer,
 		},
 		page_dimension::PageDimensionsEntity,
 	},
@@ -23,6 +24,53 @@ use crate::{
 
 const NAIVE_DATE_FORMATS: [&str; 2] = ["%Y-%m-%d", "%m-%d-%Y"];
 
+// https://anansi-project.github.io/docs/comicinfo/documentation#manga
+/// Whether a book is a manga, as described by the ComicInfo `Manga` field. The `Unknown` value
+/// of the field is represented by the absence of a value.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Type, ToSchema, PartialEq, Eq)]
+pub enum MangaFormat {
+	/// The book is not a manga
+	No,
+	/// The book is a manga, but its reading direction is not specified
+	Yes,
+	/// The book is a manga which is read right-to-left
+	YesAndRightToLeft,
+}
+
+impl MangaFormat {
+	/// The reading direction implied by the format, if any. Only `YesAndRightToLeft` says
+	/// anything about how the book is read.
+	pub fn reading_direction(&self) -> Option<ReadingDirection> {
+		match self {
+			MangaFormat::YesAndRightToLeft => Some(ReadingDirection::RightToLeft),
+			_ => None,
+		}
+	}
+}
+
+impl FromStr for MangaFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"no" => Ok(MangaFormat::No),
+			"yes" => Ok(MangaFormat::Yes),
+			"yesandrighttoleft" => Ok(MangaFormat::YesAndRightToLeft),
+			_ => Err(format!("\"{s}\" is not a valid manga format")),
+		}
+	}
+}
+
+impl fmt::Display for MangaFormat {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MangaFormat::No => write!(f, "No"),
+			MangaFormat::Yes => write!(f, "Yes"),
+			MangaFormat::YesAndRightToLeft => write!(f, "YesAndRightToLeft"),
+		}
+	}
+}
+
 // TODO: use skip_serializing_none after upgrade specta: https://github.com/oscartbeaumont/specta/issues/235
 // TODO: author field?
 // NOTE: alias is used primarily to support ComicInfo.xml files, as that metadata
@@ -165,6 +213,14 @@ pub struct MediaMetadata {
 		skip_serializing_if = "Option::is_none"
 	)]
 	pub teams: Option<Vec<String>>,
+	/// Whether the associated media is a manga, and whether it is read right-to-left
+	#[serde(
+		alias = "Manga",
+		deserialize_with = "manga_deserializer",
+		default = "Option::default",
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub manga: Option<MangaFormat>,
 
 	/// The number of pages in the associated media. This does *not* take priority over
 	/// the number of pages detected by the file processor.
@@ -204,6 +260,7 @@ impl MediaMetadata {
 			media_metadata::links::set(self.links.map(|v| v.join(", "))),
 			media_metadata::characters::set(self.characters.map(|v| v.join(", "))),
 			media_metadata::teams::set(self.teams.map(|v| v.join(", "))),
+			media_metadata::manga::set(self.manga.map(|v| v.to_string())),
 			media_metadata::page_count::set(self.page_count),
 		]
 	}
@@ -240,6 +297,10 @@ impl From<media_metadata::Data> for MediaMetadata {
 			links: metadata.links.map(comma_separated_list_to_vec),
 			characters: metadata.characters.map(comma_separated_list_to_vec),
 			teams: metadata.teams.map(comma_separated_list_to_vec),
+			manga: metadata
+				.manga
+				.as_deref()
+				.and_then(|v| MangaFormat::from_str(v).ok()),
 			page_count: metadata.page_count,
 			page_dimensions,
 		}
@@ -438,4 +499,28 @@ mod tests {
 
 		assert_eq!(metadata.age_rating, Some(13));
 	}
+
+	#[test]
+	fn test_manga_from_comic_info() {
+		let contents = "<?xml version=\"1.0\"?>\n<ComicInfo>\n  <Series>Berserk</Series>\n  <Manga>YesAndRightToLeft</Manga>\n</ComicInfo>";
+		let metadata: MediaMetadata =
+			serde_xml_rs::from_str(contents).expect("Failed to parse metadata");
+		assert_eq!(metadata.manga, Some(MangaFormat::YesAndRightToLeft));
+		assert!(matches!(
+			metadata.manga.and_then(|m| m.reading_direction()),
+			Some(ReadingDirection::RightToLeft)
+		));
+
+		let contents = "<?xml version=\"1.0\"?>\n<ComicInfo>\n  <Manga>Unknown</Manga>\n</ComicInfo>";
+		let metadata: MediaMetadata =
+			serde_xml_rs::from_str(contents).expect("Failed to parse metadata");
+		assert_eq!(metadata.manga, None);
+	}
+
+	#[test]
+	fn test_manga_format_reading_direction() {
+		assert!(MangaFormat::No.reading_direction().is_none());
+		assert!(MangaFormat::Yes.reading_direction().is_none());
+		assert!(MangaFormat::YesAndRightToLeft.reading_direction().is_some());
+	}
 }
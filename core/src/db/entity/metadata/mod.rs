@@ -6,5 +6,7 @@ mod series_metadata;
 
 pub use common::{age_rating_deserializer, parse_age_restriction};
 pub use media_metadata::*;
-pub use page_dimension::{PageDimension, PageDimensionsEntity};
+pub use page_dimension::{
+	page_positions, PageDimension, PageDimensionsEntity, PageLayout, PagePosition,
+};
 pub use series_metadata::*;
@@ -7,6 +7,9 @@
 //! algorithm for storing and retrieving [Vec]<[`PageDimension`]s. The [`dimension_vec_to_string`]
 //! and [`dimension_vec_from_str`] methods can be used for serializing and deserializing this
 //! structure
+//!
+//! The dimensions are also used to derive a [`PageLayout`] for each page, which tells readers
+//! how to lay out pages when displaying two at a time. See [`classify_page_layouts`]
 
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -20,6 +23,10 @@ use thiserror::Error;
 
 use crate::prisma::page_dimensions;
 
+/// How much wider than a typical page of the book a page must be to be considered a spread.
+/// A spread holds two pages, so it is about twice as wide, but scans are often trimmed
+const SPREAD_RATIO_THRESHOLD: f64 = 1.5;
+
 #[derive(Error, Debug)]
 pub enum PageDimensionParserError {
 	#[error("Error parsing {0}, expected height and width")]
@@ -28,6 +35,8 @@ pub enum PageDimensionParserError {
 	MalformedRunSyntax(String),
 	#[error("Failed to parse number: {0}")]
 	ErrorParsingInt(#[from] std::num::ParseIntError),
+	#[error("Unknown page layout: {0}")]
+	UnknownPageLayout(char),
 }
 
 /// Represents a database [`page_dimensions::Data`] object.
@@ -43,7 +52,7 @@ pub struct PageDimensionsEntity {
 
 impl From<page_dimensions::Data> for PageDimensionsEntity {
 	fn from(value: page_dimensions::Data) -> Self {
-		let dimensions = match dimension_vec_from_str(&value.dimensions) {
+		let mut dimensions = match dimension_vec_from_str(&value.dimensions) {
 			Ok(res) => res,
 			Err(e) => {
 				tracing::error!("Failed to deserialize page dimensions: {}", e);
@@ -51,6 +60,28 @@ impl From<page_dimensions::Data> for PageDimensionsEntity {
 			},
 		};
 
+		// Layouts are only stored once the media has been analyzed since they were added
+		let layouts = value
+			.layouts
+			.as_deref()
+			.map(layout_vec_from_str)
+			.transpose()
+			.unwrap_or_else(|e| {
+				tracing::error!("Failed to deserialize page layouts: {}", e);
+				None
+			});
+		match layouts {
+			Some(layouts) if layouts.len() == dimensions.len() => {
+				for (dimension, layout) in dimensions.iter_mut().zip(layouts) {
+					dimension.layout = Some(layout);
+				}
+			},
+			Some(_) => {
+				tracing::warn!("Page layouts do not match the number of page dimensions");
+			},
+			None => {},
+		}
+
 		Self {
 			id: value.id,
 			dimensions,
@@ -60,17 +91,83 @@ impl From<page_dimensions::Data> for PageDimensionsEntity {
 }
 
 /// Represents a page dimension for a page of a Stump media item. It consists of a
-/// height and a width.
+/// height and a width, along with the layout derived from them once the media is analyzed.
 #[derive(Serialize, Deserialize, Debug, Clone, Type, PartialEq, ToSchema)]
 pub struct PageDimension {
 	pub height: u32,
 	pub width: u32,
+	/// How the page should be laid out when displaying two pages at a time
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub layout: Option<PageLayout>,
 }
 
 impl PageDimension {
 	pub fn new(height: u32, width: u32) -> Self {
-		Self { height, width }
+		Self {
+			height,
+			width,
+			layout: None,
+		}
 	}
+
+	/// Whether two pages have the same size, regardless of their layouts
+	fn same_size(&self, other: &PageDimension) -> bool {
+		self.height == other.height && self.width == other.width
+	}
+
+	/// The ratio of the width to the height of the page, or `None` for empty pages
+	fn aspect_ratio(&self) -> Option<f64> {
+		(self.height > 0 && self.width > 0)
+			.then(|| f64::from(self.width) / f64::from(self.height))
+	}
+}
+
+/// How a page should be laid out by a reader which displays two pages at a time
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Type, PartialEq, Eq, ToSchema)]
+pub enum PageLayout {
+	/// A page which is displayed on its own: the cover, a page which is shifted so the pages
+	/// after it stay aligned (e.g. the page before a spread), or a lone last page
+	#[serde(rename = "SINGLE")]
+	Single,
+	/// A page which is displayed alongside its neighbor, as one half of a two-page view
+	#[serde(rename = "DOUBLE")]
+	Double,
+	/// A single image which holds two facing pages, which is displayed on its own
+	#[serde(rename = "SPREAD")]
+	Spread,
+}
+
+impl PageLayout {
+	fn code(&self) -> char {
+		match self {
+			PageLayout::Single => 'S',
+			PageLayout::Double => 'D',
+			PageLayout::Spread => 'W',
+		}
+	}
+}
+
+impl TryFrom<char> for PageLayout {
+	type Error = PageDimensionParserError;
+
+	fn try_from(value: char) -> Result<Self, Self::Error> {
+		match value {
+			'S' => Ok(PageLayout::Single),
+			'D' => Ok(PageLayout::Double),
+			'W' => Ok(PageLayout::Spread),
+			_ => Err(PageDimensionParserError::UnknownPageLayout(value)),
+		}
+	}
+}
+
+/// The side of a two-page view a page is displayed on, or the center when it is displayed
+/// on its own. Pages read right-to-left start on the right.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Type, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PagePosition {
+	Left,
+	Right,
+	Center,
 }
 
 impl fmt::Display for PageDimension {
@@ -96,8 +193,90 @@ impl FromStr for PageDimension {
 		let height = dims[0].trim().parse::<u32>()?;
 		let width = dims[1].trim().parse::<u32>()?;
 
-		Ok(PageDimension { height, width })
+		Ok(PageDimension::new(height, width))
+	}
+}
+
+/// Classifies each page as a [`PageLayout`] from the dimensions of all pages of a book.
+///
+/// A page is a spread when it is considerably wider than the typical (median) portrait page
+/// of the book. Books without portrait pages, like landscape art books, have no spreads.
+/// The remaining pages are paired up, starting after the cover, and a page is shifted to be
+/// displayed on its own when it would otherwise be paired with a spread.
+pub fn classify_page_layouts(dimensions: &[PageDimension]) -> Vec<PageLayout> {
+	let mut portrait_ratios = dimensions
+		.iter()
+		.filter_map(PageDimension::aspect_ratio)
+		.filter(|ratio| *ratio < 1.0)
+		.collect::<Vec<_>>();
+	portrait_ratios.sort_by(f64::total_cmp);
+	let typical_ratio = portrait_ratios.get(portrait_ratios.len() / 2).copied();
+
+	let is_spread = dimensions
+		.iter()
+		.map(
+			|dimension| match (dimension.aspect_ratio(), typical_ratio) {
+				(Some(ratio), Some(typical)) => {
+					ratio > 1.0 && ratio >= typical * SPREAD_RATIO_THRESHOLD
+				},
+				_ => false,
+			},
+		)
+		.collect::<Vec<_>>();
+
+	let mut layouts = Vec::with_capacity(dimensions.len());
+	// Whether the previous page started a pair, and is waiting for this page
+	let mut awaiting_pair = false;
+	for (idx, spread) in is_spread.iter().enumerate() {
+		let layout = if *spread {
+			PageLayout::Spread
+		} else if awaiting_pair || (idx > 0 && is_spread.get(idx + 1) == Some(&false)) {
+			PageLayout::Double
+		} else {
+			// The cover, a page before a spread or the last page
+			PageLayout::Single
+		};
+		awaiting_pair = layout == PageLayout::Double && !awaiting_pair;
+		layouts.push(layout);
 	}
+
+	layouts
+}
+
+/// Gets the [`PagePosition`] of each page from its [`PageLayout`]. The first page of each
+/// pair is on the left for books read left-to-right, and on the right for right-to-left.
+pub fn page_positions(layouts: &[PageLayout], right_to_left: bool) -> Vec<PagePosition> {
+	let (first, second) = if right_to_left {
+		(PagePosition::Right, PagePosition::Left)
+	} else {
+		(PagePosition::Left, PagePosition::Right)
+	};
+
+	let mut is_first_of_pair = true;
+	layouts
+		.iter()
+		.map(|layout| match layout {
+			PageLayout::Double => {
+				let position = if is_first_of_pair { first } else { second };
+				is_first_of_pair = !is_first_of_pair;
+				position
+			},
+			PageLayout::Single | PageLayout::Spread => {
+				is_first_of_pair = true;
+				PagePosition::Center
+			},
+		})
+		.collect()
+}
+
+/// Serializes a list of [`PageLayout`]s as a [String] with one character per page
+pub fn layout_vec_to_string(layouts: &[PageLayout]) -> String {
+	layouts.iter().map(PageLayout::code).collect()
+}
+
+/// Deserializes a list of [`PageLayout`]s from its serialized form. See [`layout_vec_to_string`]
+pub fn layout_vec_from_str(s: &str) -> Result<Vec<PageLayout>, PageDimensionParserError> {
+	s.trim().chars().map(PageLayout::try_from).collect()
 }
 
 /// Serializes a [Vec]<[`PageDimension`]> as a [String].
@@ -114,7 +293,7 @@ pub fn dimension_vec_to_string(list: Vec<PageDimension>) -> String {
 	for next_dim in list {
 		match run_dimension {
 			// If there's already a run going and it matches the next, increment the counter
-			Some(ref run_dim) if *run_dim == next_dim => run_count += 1,
+			Some(ref run_dim) if run_dim.same_size(&next_dim) => run_count += 1,
 			// If there's either a run going and it doesn't match, or no run...
 			_ => {
 				// This branch handles write-out if a run is going and it didn't match
@@ -265,4 +444,99 @@ mod tests {
 		let deserialized_dimensions = dimension_vec_from_str("").unwrap();
 		assert_eq!(deserialized_dimensions, vec![]);
 	}
+
+	#[test]
+	fn test_dimension_vec_to_string_ignores_layouts() {
+		let list = vec![
+			PageDimension {
+				layout: Some(PageLayout::Single),
+				..PageDimension::new(800, 600)
+			},
+			PageDimension {
+				layout: Some(PageLayout::Double),
+				..PageDimension::new(800, 600)
+			},
+		];
+		assert_eq!(dimension_vec_to_string(list), "2>800,600");
+	}
+
+	#[test]
+	fn test_classify_page_layouts() {
+		let page = PageDimension::new(1600, 1000);
+		let spread = PageDimension::new(1600, 2000);
+		let dimensions = vec![
+			page.clone(),
+			page.clone(),
+			page.clone(),
+			page.clone(),
+			spread,
+			page.clone(),
+			page.clone(),
+			page,
+		];
+
+		use PageLayout::*;
+		assert_eq!(
+			classify_page_layouts(&dimensions),
+			vec![Single, Double, Double, Single, Spread, Double, Double, Single]
+		);
+	}
+
+	#[test]
+	fn test_classify_page_layouts_trimmed_spread() {
+		// Pages of slightly different sizes, and a spread which was trimmed when scanned
+		let dimensions = vec![
+			PageDimension::new(1600, 1040),
+			PageDimension::new(1600, 1000),
+			PageDimension::new(1600, 1010),
+			PageDimension::new(1600, 1700),
+			PageDimension::new(1600, 990),
+		];
+
+		use PageLayout::*;
+		assert_eq!(
+			classify_page_layouts(&dimensions),
+			vec![Single, Double, Double, Spread, Single]
+		);
+	}
+
+	#[test]
+	fn test_classify_page_layouts_landscape_book() {
+		// Without portrait pages, there is nothing to compare wide pages to
+		let dimensions = vec![PageDimension::new(1000, 1600); 3];
+
+		use PageLayout::*;
+		assert_eq!(
+			classify_page_layouts(&dimensions),
+			vec![Single, Double, Double]
+		);
+		assert!(classify_page_layouts(&[]).is_empty());
+	}
+
+	#[test]
+	fn test_page_positions() {
+		use PageLayout::*;
+		let layouts = vec![Single, Double, Double, Single, Spread, Double, Double];
+
+		use PagePosition::*;
+		assert_eq!(
+			page_positions(&layouts, false),
+			vec![Center, Left, Right, Center, Center, Left, Right]
+		);
+		assert_eq!(
+			page_positions(&layouts, true),
+			vec![Center, Right, Left, Center, Center, Right, Left]
+		);
+	}
+
+	#[test]
+	fn test_layout_vec_round_trip() {
+		use PageLayout::*;
+		let layouts = vec![Single, Double, Double, Spread];
+
+		let serialized = layout_vec_to_string(&layouts);
+		assert_eq!(serialized, "SDDW");
+		assert_eq!(layout_vec_from_str(&serialized).unwrap(), layouts);
+		assert!(layout_vec_from_str("SDX").is_err());
+	}
 }
@@ -36,7 +36,7 @@ pub use user::*;
 
 pub use common::{
 	AccessRole, Cursor, EntityVisibility, FileStatus, LayoutMode, ReactTableColumnSort,
-	ReactTableGlobalSort,
+	ReactTableGlobalSort, ReadingOverrides, ReadingPreferences,
 };
 
 pub mod utils {
@@ -7,7 +7,8 @@ use utoipa::ToSchema;
 use crate::{
 	db::{
 		entity::{
-			common::Cursor, Library, Media, SeriesMetadata, SeriesMetadataCreateAction,
+			common::{Cursor, ReadingDirection, ReadingMode},
+			Library, Media, ReadingOverrides, SeriesMetadata, SeriesMetadataCreateAction,
 			Tag,
 		},
 		FileStatus,
@@ -34,6 +35,10 @@ pub struct Series {
 	pub created_at: String,
 	/// The ID of the library this series belongs to.
 	pub library_id: String,
+	/// The reading direction of the books in the series, overriding the library default.
+	pub reading_direction: Option<ReadingDirection>,
+	/// The reading mode of the books in the series, overriding the library default.
+	pub reading_mode: Option<ReadingMode>,
 	/// The library this series belongs to. Will be `None` only if the relation is not loaded.
 	#[schema(no_recursion)]
 	pub library: Option<Library>,
@@ -126,6 +131,14 @@ impl From<series::Data> for Series {
 			Err(_e) => None,
 		};
 
+		let ReadingOverrides {
+			reading_direction,
+			reading_mode,
+		} = ReadingOverrides::from_stored(
+			data.reading_direction.as_deref(),
+			data.reading_mode.as_deref(),
+		);
+
 		Series {
 			id: data.id,
 			name: data.name,
@@ -135,6 +148,8 @@ impl From<series::Data> for Series {
 			updated_at: data.updated_at.to_rfc3339(),
 			created_at: data.created_at.to_rfc3339(),
 			library_id: data.library_id.unwrap(),
+			reading_direction,
+			reading_mode,
 			library,
 			media,
 			metadata,
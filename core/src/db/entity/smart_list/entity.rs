@@ -8,14 +8,14 @@ use utoipa::ToSchema;
 use crate::{
 	db::{
 		entity::{
-			utils::apply_media_age_restriction, EntityVisibility, Library, Media, Series,
-			User,
+			utils::{
+				apply_library_not_hidden_from_user_filter, apply_media_age_restriction,
+			},
+			EntityVisibility, Library, Media, Series, User,
 		},
 		filter::{FilterGroup, FilterJoin, MediaSmartFilter, SmartFilter},
 	},
-	prisma::{
-		active_reading_session, library, media, series, smart_list, user, PrismaClient,
-	},
+	prisma::{active_reading_session, library, media, series, smart_list, PrismaClient},
 	utils::chain_optional_iter,
 	CoreError, CoreResult,
 };
@@ -71,11 +71,12 @@ impl SmartList {
 			.as_ref()
 			.map(|ar| apply_media_age_restriction(ar.age, ar.restrict_on_unset));
 		let library_not_hidden_restriction =
-			library::hidden_from_users::none(vec![user::id::equals(user.id.clone())]);
+			apply_library_not_hidden_from_user_filter(user);
 
 		let params_for_user = operator::and(chain_optional_iter(
 			[
 				params,
+				media::deleted_at::equals(None),
 				media::series::is(vec![series::library::is(vec![
 					library_not_hidden_restriction,
 				])]),
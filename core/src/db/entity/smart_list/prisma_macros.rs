@@ -2,7 +2,10 @@ use std::str::FromStr;
 
 use crate::{
 	db::{
-		entity::{ActiveReadingSession, FinishedReadingSession, Media, MediaMetadata},
+		entity::{
+			ActiveReadingSession, FinishedReadingSession, Media, MediaMetadata,
+			ReadingOverrides,
+		},
 		FileStatus,
 	},
 	prisma::{active_reading_session, finished_reading_session, media},
@@ -40,6 +43,13 @@ impl From<media_grouped_by_library::Data> for Media {
 			.map(|data| FinishedReadingSession::from(data.to_owned()))
 			.collect::<Vec<FinishedReadingSession>>();
 		let is_completed = !finished_reading_sessions.is_empty();
+		let ReadingOverrides {
+			reading_direction,
+			reading_mode,
+		} = ReadingOverrides::from_stored(
+			data.reading_direction.as_deref(),
+			data.reading_mode.as_deref(),
+		);
 
 		Media {
 			id: data.id,
@@ -53,6 +63,9 @@ impl From<media_grouped_by_library::Data> for Media {
 			hash: data.hash,
 			path: data.path,
 			status: FileStatus::from_str(&data.status).unwrap_or(FileStatus::Error),
+			status_reason: data.status_reason,
+			reading_direction,
+			reading_mode,
 			series_id: data.series_id.unwrap_or_default(),
 			metadata: data.metadata.map(|m| MediaMetadata::from(m.clone())),
 			active_reading_session,
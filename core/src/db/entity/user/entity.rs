@@ -31,6 +31,8 @@ pub struct User {
 	pub last_login: Option<DateTime<FixedOffset>>,
 	/// A boolean to indicate if the user is locked, which prevents them from logging in
 	pub is_locked: bool,
+	/// A boolean to indicate if the user has verified TOTP two-factor authentication
+	pub two_factor_enabled: bool,
 	/// The permissions of the user, influences what actions throughout the app they can perform
 	pub permissions: Vec<UserPermission>,
 	/// The maximum number of sessions the user is allowed to have at once
@@ -54,6 +56,10 @@ pub struct User {
 	/// The finished reading sessions for the user. Will be `None` if the relation is not loaded.
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub finished_reading_sessions: Option<Vec<FinishedReadingSession>>,
+	/// The libraries the user is limited to for the current request, which is set when they
+	/// authenticate with an API key scoped to specific libraries. This is never persisted.
+	#[serde(skip)]
+	pub library_allowlist: Option<Vec<String>>,
 }
 
 impl User {
@@ -182,7 +188,9 @@ impl From<user::Data> for User {
 			last_login: data.last_login,
 			login_activity,
 			is_locked: data.is_locked,
+			two_factor_enabled: data.totp_enabled_at.is_some(),
 			login_sessions_count,
+			library_allowlist: None,
 		}
 	}
 }
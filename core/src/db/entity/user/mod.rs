@@ -1,10 +1,14 @@
 mod activity;
 mod entity;
+mod notification;
+mod oidc_identity;
 mod permissions;
 mod preferences;
 pub(crate) mod prisma_macros;
 
 pub use activity::*;
 pub use entity::*;
+pub use notification::*;
+pub use oidc_identity::*;
 pub use permissions::*;
 pub use preferences::*;
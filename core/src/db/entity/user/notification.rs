@@ -0,0 +1,75 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+
+use crate::{prisma::user_notification, CoreError};
+
+/// What a [UserNotification] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum UserNotificationKind {
+	/// One of the user's API keys is about to expire
+	ApiKeyExpiring,
+}
+
+impl fmt::Display for UserNotificationKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::ApiKeyExpiring => write!(f, "API_KEY_EXPIRING"),
+		}
+	}
+}
+
+impl FromStr for UserNotificationKind {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"API_KEY_EXPIRING" => Ok(Self::ApiKeyExpiring),
+			_ => Err(format!("\"{s}\" is not a valid notification kind")),
+		}
+	}
+}
+
+/// A notice for a single user, which is kept until they read it
+#[derive(Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
+pub struct UserNotification {
+	pub id: String,
+	pub kind: UserNotificationKind,
+	pub message: String,
+	pub created_at: String,
+	/// When the user marked the notification as read, if they have
+	pub read_at: Option<String>,
+}
+
+impl TryFrom<user_notification::Data> for UserNotification {
+	type Error = CoreError;
+
+	fn try_from(data: user_notification::Data) -> Result<Self, Self::Error> {
+		Ok(Self {
+			id: data.id,
+			kind: UserNotificationKind::from_str(&data.kind)
+				.map_err(CoreError::InternalError)?,
+			message: data.message,
+			created_at: data.created_at.to_rfc3339(),
+			read_at: data.read_at.map(|t| t.to_rfc3339()),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_notification_kind_round_trip() {
+		let kind = UserNotificationKind::ApiKeyExpiring;
+		assert_eq!(
+			UserNotificationKind::from_str(&kind.to_string()).unwrap(),
+			kind
+		);
+		assert!(UserNotificationKind::from_str("UNKNOWN").is_err());
+	}
+}
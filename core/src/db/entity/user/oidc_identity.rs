@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+
+use crate::prisma;
+
+/// An identity at an OpenID Connect provider which is linked to a Stump user. A user may
+/// have multiple identities, but an identity may only be linked to a single user.
+#[derive(Debug, Clone, Serialize, Deserialize, Type, ToSchema)]
+pub struct OidcIdentity {
+	pub id: String,
+	/// The issuer of the provider which authenticated the identity
+	pub issuer: String,
+	/// The subject of the identity, which is unique per issuer
+	pub subject: String,
+	/// The email address reported by the provider, if any
+	pub email: Option<String>,
+	pub created_at: String,
+	/// The last time the identity was used to log in
+	pub last_used: Option<String>,
+	pub user_id: String,
+}
+
+impl From<prisma::oidc_identity::Data> for OidcIdentity {
+	fn from(data: prisma::oidc_identity::Data) -> OidcIdentity {
+		OidcIdentity {
+			id: data.id,
+			issuer: data.issuer,
+			subject: data.subject,
+			email: data.email,
+			created_at: data.created_at.to_rfc3339(),
+			last_used: data.last_used.map(|dt| dt.to_rfc3339()),
+			user_id: data.user_id,
+		}
+	}
+}
@@ -549,8 +549,11 @@ mod tests {
 			book_club_member_favorite_book: None,
 			book_club_suggestions: None,
 			bookmarks: None,
+			cover_hash: None,
 			created_at: Utc::now().into(),
 			deleted_at: None,
+			duplicate_groups: None,
+			epub_positions: None,
 			extension: "CBZ".to_string(),
 			hash: None,
 			koreader_hash: None,
@@ -560,11 +563,15 @@ mod tests {
 			path: "test-path".to_string(),
 			active_user_reading_sessions: None,
 			finished_user_reading_sessions: None,
+			reading_direction: None,
 			reading_list_items: None,
+			reading_mode: None,
 			size: 100,
 			status: "READY".to_string(),
+			status_reason: None,
 			tags: None,
 			updated_at: Utc::now().into(),
+			verified_at: None,
 		}
 	}
 
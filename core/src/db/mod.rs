@@ -1,3 +1,4 @@
+pub mod backup;
 mod client;
 mod common;
 pub(crate) mod dao;
@@ -5,6 +6,7 @@ pub mod entity;
 pub mod filter;
 pub mod migration;
 pub mod query;
+pub mod trash;
 
 pub use dao::*;
 
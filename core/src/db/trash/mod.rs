@@ -0,0 +1,151 @@
+mod trash_purge_job;
+
+use std::path::Path;
+
+use prisma_client_rust::QueryError;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::fs;
+use utoipa::ToSchema;
+
+use crate::{
+	db::FileStatus,
+	filesystem::image::remove_thumbnails,
+	prisma::{media, series, PrismaClient},
+};
+
+pub use trash_purge_job::{TrashPurgeJob, TRASH_PURGE_JOB_NAME};
+
+#[derive(Debug, Default, Serialize, Deserialize, Type, ToSchema)]
+pub struct PurgedTrash {
+	/// The number of media which were permanently deleted
+	pub deleted_media_count: u64,
+	/// The number of series which were deleted because they were missing and left empty
+	pub deleted_series_count: u64,
+}
+
+/// Whether trashed media goes back to being ready when it is restored, which is the case when
+/// it was missing from disk and its file has since come back
+pub async fn is_restored_as_ready(status: &str, path: &str) -> bool {
+	status == FileStatus::Missing.to_string()
+		&& fs::try_exists(path).await.unwrap_or(false)
+}
+
+/// Permanently delete the trashed media matching the given conditions, along with their
+/// thumbnails. Any series which are missing from disk and left without media are deleted, too.
+pub async fn purge_trashed_media(
+	db: &PrismaClient,
+	thumbnails_dir: &Path,
+	params: Vec<media::WhereParam>,
+) -> Result<PurgedTrash, QueryError> {
+	let where_params = [media::deleted_at::not(None)]
+		.into_iter()
+		.chain(params)
+		.collect::<Vec<_>>();
+
+	let trashed_media = db
+		.media()
+		.find_many(where_params)
+		.select(media::select!({ id series_id }))
+		.exec()
+		.await?;
+	if trashed_media.is_empty() {
+		return Ok(PurgedTrash::default());
+	}
+
+	let (media_ids, series_ids): (Vec<String>, Vec<Option<String>>) = trashed_media
+		.into_iter()
+		.map(|data| (data.id, data.series_id))
+		.unzip();
+	let mut series_ids = series_ids.into_iter().flatten().collect::<Vec<_>>();
+	series_ids.sort();
+	series_ids.dedup();
+
+	let ids_to_delete = media_ids.clone();
+	let (deleted_media_count, deleted_series_count) = db
+		._transaction()
+		.run(|client| async move {
+			let deleted_media_count = client
+				.media()
+				.delete_many(vec![media::id::in_vec(ids_to_delete)])
+				.exec()
+				.await?;
+			let deleted_series_count = client
+				.series()
+				.delete_many(vec![
+					series::id::in_vec(series_ids),
+					series::status::not(FileStatus::Ready.to_string()),
+					series::media::none(vec![]),
+				])
+				.exec()
+				.await?;
+			Ok::<_, QueryError>((deleted_media_count, deleted_series_count))
+		})
+		.await?;
+
+	if let Err(error) = remove_thumbnails(&media_ids, thumbnails_dir).await {
+		tracing::error!(?error, "Failed to remove thumbnails for purged media");
+	}
+
+	Ok(PurgedTrash {
+		deleted_media_count: deleted_media_count as u64,
+		deleted_series_count: deleted_series_count as u64,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{config::StumpConfig, job::PeriodicJob};
+
+	use super::*;
+
+	#[test]
+	fn test_trash_purge_period() {
+		let mut config = StumpConfig::debug();
+		config.trash_retention_days = 30;
+		assert!(TrashPurgeJob.period(&config).is_some());
+
+		config.trash_retention_days = 0;
+		assert!(TrashPurgeJob.period(&config).is_none());
+	}
+
+	#[tokio::test]
+	async fn test_is_restored_as_ready() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = dir.path().join("book.cbz");
+		std::fs::write(&path, b"book").unwrap();
+		let path = path.to_string_lossy().to_string();
+		let missing = FileStatus::Missing.to_string();
+
+		assert!(is_restored_as_ready(&missing, &path).await);
+		assert!(!is_restored_as_ready(&missing, "/does/not/exist.cbz").await);
+		assert!(!is_restored_as_ready(&FileStatus::Error.to_string(), &path).await);
+	}
+
+	#[tokio::test]
+	async fn test_purge_only_considers_trashed_media() {
+		let (client, mock) = PrismaClient::_mock();
+
+		mock.expect(
+			client
+				.media()
+				.find_many(vec![
+					media::deleted_at::not(None),
+					media::id::equals("book".to_string()),
+				])
+				.select(media::select!({ id series_id })),
+			vec![],
+		)
+		.await;
+
+		let purged = purge_trashed_media(
+			&client,
+			Path::new("thumbnails"),
+			vec![media::id::equals("book".to_string())],
+		)
+		.await
+		.unwrap();
+		assert_eq!(purged.deleted_media_count, 0);
+		assert_eq!(purged.deleted_series_count, 0);
+	}
+}
@@ -0,0 +1,120 @@
+use std::{collections::VecDeque, time::Duration};
+
+use prisma_client_rust::chrono::{self, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	config::StumpConfig,
+	job::{
+		error::JobError, Executor, JobExecuteLog, JobExt, JobOutputExt, JobTaskOutput,
+		PeriodicJob, WorkerCtx, WorkingState, WrappedJob,
+	},
+	prisma::media,
+	Ctx,
+};
+
+use super::purge_trashed_media;
+
+pub const TRASH_PURGE_JOB_NAME: &str = "trash_purge";
+
+/// How often the trash is checked for media which is past retention
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60 * 6);
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct TrashPurgeJobOutput {
+	/// The number of media which were permanently deleted
+	deleted_media: u64,
+	/// The number of series which were deleted
+	deleted_series: u64,
+}
+
+impl JobOutputExt for TrashPurgeJobOutput {}
+
+/// A job which permanently deletes media which has been in the trash for longer than the
+/// configured retention period
+#[derive(Clone)]
+pub struct TrashPurgeJob;
+
+impl TrashPurgeJob {
+	pub fn new() -> Box<WrappedJob<TrashPurgeJob>> {
+		WrappedJob::new(Self)
+	}
+}
+
+#[async_trait::async_trait]
+impl JobExt for TrashPurgeJob {
+	const NAME: &'static str = TRASH_PURGE_JOB_NAME;
+
+	type Output = TrashPurgeJobOutput;
+	type Task = ();
+
+	fn description(&self) -> Option<String> {
+		None
+	}
+
+	async fn init(
+		&mut self,
+		ctx: &WorkerCtx,
+	) -> Result<WorkingState<Self::Output, Self::Task>, JobError> {
+		let mut output = Self::Output::default();
+		let mut logs = vec![];
+
+		let retention_cutoff =
+			Utc::now() - chrono::Duration::days(ctx.config.trash_retention_days);
+		let result = purge_trashed_media(
+			&ctx.db,
+			&ctx.config.get_thumbnails_dir(),
+			vec![media::deleted_at::lt(retention_cutoff.into())],
+		)
+		.await;
+		match result {
+			Ok(purged) => {
+				output.deleted_media = purged.deleted_media_count;
+				output.deleted_series = purged.deleted_series_count;
+			},
+			Err(e) => {
+				logs.push(JobExecuteLog::error(format!(
+					"Failed to purge trashed media: {:?}",
+					e.to_string()
+				)));
+			},
+		}
+		tracing::debug!(?output, "Finished purging the trash");
+
+		Ok(WorkingState {
+			output: Some(output),
+			tasks: VecDeque::default(),
+			completed_tasks: 0,
+			logs,
+		})
+	}
+
+	async fn execute_task(
+		&self,
+		_: &WorkerCtx,
+		_: Self::Task,
+	) -> Result<JobTaskOutput<Self>, JobError> {
+		unreachable!("TrashPurgeJob does not have any tasks! It should not be executed with any tasks!")
+	}
+}
+
+/// The trash is purged on boot and then periodically, unless trashed media is configured to be
+/// kept until it is manually purged
+#[async_trait::async_trait]
+impl PeriodicJob for TrashPurgeJob {
+	fn name(&self) -> &'static str {
+		TRASH_PURGE_JOB_NAME
+	}
+
+	fn period(&self, config: &StumpConfig) -> Option<Duration> {
+		(config.trash_retention_days > 0).then_some(TRASH_PURGE_INTERVAL)
+	}
+
+	fn dispatch_on_boot(&self) -> bool {
+		true
+	}
+
+	async fn create_job(&self, _: &Ctx) -> Option<Box<dyn Executor>> {
+		Some(Self::new())
+	}
+}
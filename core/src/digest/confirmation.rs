@@ -0,0 +1,109 @@
+use data_encoding::HEXLOWER;
+use prisma_client_rust::chrono::{DateTime, Duration, FixedOffset, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use ring::digest::{digest, SHA256};
+
+use crate::prisma::new_books_digest_subscription;
+
+use super::DIGEST_CONFIRMATION_TTL_HOURS;
+
+const CONFIRMATION_CODE_LENGTH: usize = 10;
+
+/// Generate a new code which confirms the address of a digest, returning the code to email
+/// and the hash to persist
+pub fn generate_confirmation_code() -> (String, String) {
+	let code = Alphanumeric
+		.sample_string(&mut rand::thread_rng(), CONFIRMATION_CODE_LENGTH)
+		.to_ascii_lowercase();
+	let hash = hash_confirmation_code(&code);
+	(code, hash)
+}
+
+/// Hash a confirmation code for storage. Codes are random and short lived, so a fast hash is
+/// sufficient
+pub fn hash_confirmation_code(code: &str) -> String {
+	HEXLOWER.encode(digest(&SHA256, code.trim().to_ascii_lowercase().as_bytes()).as_ref())
+}
+
+/// Whether the code matches the pending confirmation of the subscription, and was sent
+/// recently enough to still be accepted
+pub fn is_valid_confirmation_code(
+	data: &new_books_digest_subscription::Data,
+	code: &str,
+	now: DateTime<Utc>,
+) -> bool {
+	let Some(expected_hash) = data.confirmation_code_hash.as_deref() else {
+		return false;
+	};
+	let is_expired = data.confirmation_sent_at.map_or(true, |sent_at| {
+		DateTime::<FixedOffset>::from(now) - sent_at
+			> Duration::hours(DIGEST_CONFIRMATION_TTL_HOURS)
+	});
+
+	!is_expired && hash_confirmation_code(code) == expected_hash
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pending_subscription(
+		code_hash: Option<String>,
+		sent_at: Option<DateTime<Utc>>,
+	) -> new_books_digest_subscription::Data {
+		new_books_digest_subscription::Data {
+			id: "subscription".to_string(),
+			email: "reader@stumpapp.dev".to_string(),
+			frequency: "DAILY".to_string(),
+			last_digest_at: None,
+			confirmed_at: None,
+			confirmation_code_hash: code_hash,
+			confirmation_sent_at: sent_at.map(Into::into),
+			created_at: Utc::now().into(),
+			user_id: "user".to_string(),
+			user: None,
+		}
+	}
+
+	#[test]
+	fn test_confirmation_code() {
+		let now = Utc::now();
+		let (code, hash) = generate_confirmation_code();
+		assert_eq!(code.len(), CONFIRMATION_CODE_LENGTH);
+
+		let data = pending_subscription(Some(hash), Some(now - Duration::hours(1)));
+		assert!(is_valid_confirmation_code(&data, &code, now));
+		assert!(is_valid_confirmation_code(
+			&data,
+			&format!(" {} ", code.to_ascii_uppercase()),
+			now
+		));
+		assert!(!is_valid_confirmation_code(&data, "wrong-code", now));
+	}
+
+	#[test]
+	fn test_hash_confirmation_code() {
+		assert_eq!(
+			hash_confirmation_code(" ABC "),
+			"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+		);
+	}
+
+	#[test]
+	fn test_confirmation_code_expires() {
+		let now = Utc::now();
+		let (code, hash) = generate_confirmation_code();
+
+		let data = pending_subscription(
+			Some(hash.clone()),
+			Some(now - Duration::hours(DIGEST_CONFIRMATION_TTL_HOURS + 1)),
+		);
+		assert!(!is_valid_confirmation_code(&data, &code, now));
+
+		let data = pending_subscription(Some(hash), None);
+		assert!(!is_valid_confirmation_code(&data, &code, now));
+
+		let data = pending_subscription(None, Some(now));
+		assert!(!is_valid_confirmation_code(&data, &code, now));
+	}
+}
@@ -0,0 +1,323 @@
+use std::{collections::VecDeque, time::Duration};
+
+use email::EmailTemplate;
+use prisma_client_rust::{
+	chrono::{DateTime, FixedOffset, Utc},
+	Direction,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::{
+	config::StumpConfig,
+	db::entity::{digest_window_start, SMTPEmailer, User},
+	job::{
+		error::JobError, Executor, JobExecuteLog, JobExt, JobOutputExt, JobTaskOutput,
+		PeriodicJob, WorkerCtx, WorkingState, WrappedJob,
+	},
+	prisma::{emailer, media, new_books_digest_subscription, user},
+	Ctx,
+};
+
+use super::{
+	digest_subject, find_due_subscriptions, get_digest_thumbnail,
+	new_books_for_user_params, DigestBook, NewBooksDigestData, DIGEST_MAX_BOOKS,
+};
+
+pub const NEW_BOOKS_DIGEST_JOB_NAME: &str = "new_books_digest";
+
+/// How often subscriptions are checked for digests which are due
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum NewBooksDigestTask {
+	/// Send the digest of the subscription specified by ID
+	Send(String),
+}
+
+#[derive(Clone, Serialize, Deserialize, Default, Debug, Type)]
+pub struct NewBooksDigestOutput {
+	/// The number of digests which were sent
+	digests_sent: u64,
+	/// The number of digests which were skipped, since no books were added in their window
+	digests_skipped: u64,
+	/// The number of digests which failed to send
+	digests_failed: u64,
+}
+
+impl JobOutputExt for NewBooksDigestOutput {
+	fn update(&mut self, updated: Self) {
+		self.digests_sent += updated.digests_sent;
+		self.digests_skipped += updated.digests_skipped;
+		self.digests_failed += updated.digests_failed;
+	}
+}
+
+/// A job which emails each user who is due a digest the books which were added to the
+/// libraries they can see since their last digest. The digests are sent with the primary
+/// emailer, and each one is recorded in its send history.
+#[derive(Clone)]
+pub struct NewBooksDigestJob;
+
+impl NewBooksDigestJob {
+	pub fn new() -> Box<WrappedJob<NewBooksDigestJob>> {
+		WrappedJob::new(Self)
+	}
+}
+
+#[async_trait::async_trait]
+impl JobExt for NewBooksDigestJob {
+	const NAME: &'static str = NEW_BOOKS_DIGEST_JOB_NAME;
+
+	type Output = NewBooksDigestOutput;
+	type Task = NewBooksDigestTask;
+
+	fn description(&self) -> Option<String> {
+		Some("Send new books digests".to_string())
+	}
+
+	async fn init(
+		&mut self,
+		ctx: &WorkerCtx,
+	) -> Result<WorkingState<Self::Output, Self::Task>, JobError> {
+		let tasks = find_due_subscriptions(&ctx.db, Utc::now())
+			.await
+			.map_err(|e| JobError::InitFailed(e.to_string()))?
+			.into_iter()
+			.map(|subscription| NewBooksDigestTask::Send(subscription.id))
+			.collect::<VecDeque<_>>();
+
+		if !tasks.is_empty() {
+			let primary_emailer = ctx
+				.db
+				.emailer()
+				.find_first(vec![emailer::is_primary::equals(true)])
+				.exec()
+				.await
+				.map_err(|e| JobError::InitFailed(e.to_string()))?;
+			if primary_emailer.is_none() {
+				return Err(JobError::InitFailed(
+					"No primary emailer is configured".to_string(),
+				));
+			}
+		}
+
+		Ok(WorkingState {
+			output: Some(Self::Output::default()),
+			tasks,
+			completed_tasks: 0,
+			logs: vec![],
+		})
+	}
+
+	async fn execute_task(
+		&self,
+		ctx: &WorkerCtx,
+		task: Self::Task,
+	) -> Result<JobTaskOutput<Self>, JobError> {
+		let mut output = Self::Output::default();
+		let mut logs = vec![];
+
+		let NewBooksDigestTask::Send(subscription_id) = task;
+		let Some(subscription) = ctx
+			.db
+			.new_books_digest_subscription()
+			.find_unique(new_books_digest_subscription::id::equals(
+				subscription_id.clone(),
+			))
+			.with(
+				new_books_digest_subscription::user::fetch()
+					.with(user::age_restriction::fetch()),
+			)
+			.exec()
+			.await?
+		else {
+			// The user unsubscribed after the job started
+			return Ok(JobTaskOutput {
+				output,
+				subtasks: vec![],
+				logs,
+			});
+		};
+		let user = subscription
+			.user
+			.clone()
+			.map(|data| User::from(*data))
+			.ok_or_else(|| {
+				JobError::TaskFailed(format!(
+					"Failed to load the user of digest subscription {subscription_id}"
+				))
+			})?;
+
+		let since = digest_window_start(&subscription);
+		let until: DateTime<FixedOffset> = Utc::now().into();
+		let book_count = ctx
+			.db
+			.media()
+			.count(new_books_for_user_params(&user, since, until))
+			.exec()
+			.await? as usize;
+
+		if book_count == 0 {
+			tracing::debug!(user_id = %user.id, "No new books for digest");
+			output.digests_skipped += 1;
+		} else {
+			let books = ctx
+				.db
+				.media()
+				.find_many(new_books_for_user_params(&user, since, until))
+				.order_by(media::created_at::order(Direction::Desc))
+				.take(DIGEST_MAX_BOOKS as i64)
+				.select(media::select!({
+					id
+					name
+					metadata: select { title }
+					series: select { name }
+				}))
+				.exec()
+				.await?;
+
+			let mut digest_books = Vec::with_capacity(books.len());
+			let mut images = vec![];
+			for book in books {
+				let thumbnail = get_digest_thumbnail(&ctx.config, &book.id).await;
+				digest_books.push(DigestBook {
+					title: book
+						.metadata
+						.and_then(|metadata| metadata.title)
+						.unwrap_or(book.name),
+					series: book.series.map(|series| series.name),
+					has_thumbnail: thumbnail.is_some(),
+					id: book.id,
+				});
+				images.extend(thumbnail);
+			}
+
+			let public_url = ctx
+				.db
+				.server_config()
+				.find_first(vec![])
+				.exec()
+				.await?
+				.and_then(|config| config.public_url);
+			if public_url.is_none() {
+				logs.push(
+					JobExecuteLog::warn(
+						"The server has no public URL, so the digest has no links",
+					)
+					.with_ctx(subscription_id.clone()),
+				);
+			}
+
+			let data = NewBooksDigestData::new(
+				user.username.clone(),
+				public_url.as_deref(),
+				digest_books,
+				book_count,
+			);
+			let data = serde_json::to_value(data)
+				.map_err(|e| JobError::TaskFailed(e.to_string()))?;
+
+			let primary_emailer = ctx
+				.db
+				.emailer()
+				.find_first(vec![emailer::is_primary::equals(true)])
+				.exec()
+				.await?
+				.ok_or_else(|| {
+					JobError::TaskFailed("No primary emailer is configured".to_string())
+				})?;
+			let emailer_id = primary_emailer.id;
+			let client = SMTPEmailer::try_from(primary_emailer)?
+				.into_client_with(&ctx.db, &ctx.config)
+				.await?;
+
+			let send_result = client
+				.send_template(
+					&digest_subject(),
+					&subscription.email,
+					EmailTemplate::NewBooksDigest,
+					&data,
+					images,
+				)
+				.await;
+			if let Err(error) = send_result {
+				tracing::error!(?error, user_id = %user.id, "Failed to send digest");
+				logs.push(
+					JobExecuteLog::error(format!(
+						"Failed to send digest to {}: {error}",
+						subscription.email
+					))
+					.with_ctx(subscription_id),
+				);
+				output.digests_failed += 1;
+				// The window isn't moved, so the books are included in the next attempt
+				return Ok(JobTaskOutput {
+					output,
+					subtasks: vec![],
+					logs,
+				});
+			}
+			output.digests_sent += 1;
+
+			ctx.db
+				.emailer_send_record()
+				.create(
+					emailer::id::equals(emailer_id),
+					subscription.email.clone(),
+					vec![],
+				)
+				.exec()
+				.await?;
+			ctx.db
+				.emailer()
+				.update(
+					emailer::id::equals(emailer_id),
+					vec![emailer::last_used_at::set(Some(Utc::now().into()))],
+				)
+				.exec()
+				.await?;
+		}
+
+		ctx.db
+			.new_books_digest_subscription()
+			.update(
+				new_books_digest_subscription::id::equals(subscription_id),
+				vec![new_books_digest_subscription::last_digest_at::set(Some(
+					until,
+				))],
+			)
+			.exec()
+			.await?;
+
+		Ok(JobTaskOutput {
+			output,
+			subtasks: vec![],
+			logs,
+		})
+	}
+}
+
+/// Subscriptions are checked for digests which are due periodically, and the job is only
+/// dispatched when at least one of them is
+#[async_trait::async_trait]
+impl PeriodicJob for NewBooksDigestJob {
+	fn name(&self) -> &'static str {
+		NEW_BOOKS_DIGEST_JOB_NAME
+	}
+
+	fn period(&self, _: &StumpConfig) -> Option<Duration> {
+		Some(DIGEST_CHECK_INTERVAL)
+	}
+
+	async fn create_job(&self, ctx: &Ctx) -> Option<Box<dyn Executor>> {
+		match find_due_subscriptions(&ctx.db, Utc::now()).await {
+			Ok(due) if due.is_empty() => None,
+			Ok(_) => Some(Self::new()),
+			Err(error) => {
+				tracing::error!(error = ?error, "Failed to find due new books digests");
+				None
+			},
+		}
+	}
+}
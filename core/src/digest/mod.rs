@@ -0,0 +1,384 @@
+mod confirmation;
+mod digest_job;
+
+use std::io::Cursor;
+
+use email::{EmailContentType, EmailTemplate, InlineImage};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType};
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Utc};
+use serde::Serialize;
+
+use crate::{
+	config::StumpConfig,
+	db::entity::{
+		is_digest_due,
+		utils::{
+			apply_media_age_restriction, apply_media_library_not_hidden_for_user_filter,
+		},
+		SMTPEmailer, User,
+	},
+	filesystem::get_thumbnail,
+	prisma::{emailer, media, new_books_digest_subscription, user, PrismaClient},
+	utils::chain_optional_iter,
+	CoreError, CoreResult,
+};
+
+pub use confirmation::{
+	generate_confirmation_code, hash_confirmation_code, is_valid_confirmation_code,
+};
+pub use digest_job::{NewBooksDigestJob, NEW_BOOKS_DIGEST_JOB_NAME};
+
+/// The most books listed in a single digest. The rest are summarized by a count, so a large
+/// import doesn't produce an enormous email
+pub const DIGEST_MAX_BOOKS: usize = 25;
+
+/// The name of the server used in digests
+const DIGEST_SERVER_NAME: &str = "Stump";
+/// The largest width of a cover embedded in a digest, in pixels
+const DIGEST_THUMBNAIL_WIDTH: u32 = 160;
+/// The largest height of a cover embedded in a digest, in pixels
+const DIGEST_THUMBNAIL_HEIGHT: u32 = 240;
+/// The quality of the covers embedded in a digest
+const DIGEST_THUMBNAIL_QUALITY: u8 = 75;
+
+/// A book which was added within the window of a digest
+#[derive(Debug, Clone)]
+pub struct DigestBook {
+	pub id: String,
+	/// The title from the metadata of the book, or its name
+	pub title: String,
+	/// The name of the series the book belongs to
+	pub series: Option<String>,
+	/// Whether the cover of the book is embedded in the digest
+	pub has_thumbnail: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct DigestBookData {
+	title: String,
+	series: Option<String>,
+	url: Option<String>,
+	thumbnail_cid: Option<String>,
+}
+
+/// The data the [email::EmailTemplate::NewBooksDigest] template is rendered with
+#[derive(Debug, Serialize)]
+pub struct NewBooksDigestData {
+	title: String,
+	username: String,
+	server_name: String,
+	server_url: Option<String>,
+	book_count: usize,
+	more_count: usize,
+	books: Vec<DigestBookData>,
+}
+
+impl NewBooksDigestData {
+	/// Build the data for a digest. The books are the ones listed in the email, out of the
+	/// `book_count` books which were added within the window of the digest. Links are only
+	/// included when the server has a public URL, since there is nothing to link to otherwise.
+	pub fn new(
+		username: String,
+		public_url: Option<&str>,
+		books: Vec<DigestBook>,
+		book_count: usize,
+	) -> Self {
+		let server_url = public_url
+			.map(|url| url.trim_end_matches('/').to_string())
+			.filter(|url| !url.is_empty());
+		let more_count = book_count.saturating_sub(books.len());
+
+		let books = books
+			.into_iter()
+			.map(|book| DigestBookData {
+				url: server_url
+					.as_ref()
+					.map(|server_url| format!("{server_url}/books/{}", book.id)),
+				thumbnail_cid: book.has_thumbnail.then(|| thumbnail_content_id(&book.id)),
+				title: book.title,
+				series: book.series,
+			})
+			.collect();
+
+		Self {
+			title: digest_subject(),
+			username,
+			server_name: DIGEST_SERVER_NAME.to_string(),
+			server_url,
+			book_count,
+			more_count,
+			books,
+		}
+	}
+}
+
+/// The subject of a digest email
+pub fn digest_subject() -> String {
+	format!("New books on {DIGEST_SERVER_NAME}")
+}
+
+/// How long the code sent to confirm the address of a digest is valid for, in hours
+pub const DIGEST_CONFIRMATION_TTL_HOURS: i64 = 24;
+
+/// The data the [email::EmailTemplate::NewBooksDigestConfirmation] template is rendered with
+#[derive(Debug, Serialize)]
+pub struct NewBooksDigestConfirmationData {
+	title: String,
+	username: String,
+	server_name: String,
+	code: String,
+	expires_in_hours: i64,
+}
+
+impl NewBooksDigestConfirmationData {
+	pub fn new(username: String, code: String) -> Self {
+		Self {
+			title: digest_confirmation_subject(),
+			username,
+			server_name: DIGEST_SERVER_NAME.to_string(),
+			code,
+			expires_in_hours: DIGEST_CONFIRMATION_TTL_HOURS,
+		}
+	}
+}
+
+pub fn digest_confirmation_subject() -> String {
+	format!("Confirm your {DIGEST_SERVER_NAME} new books digest")
+}
+
+/// Send the code which confirms the address of a digest subscription using the primary
+/// emailer. Digests are only sent to an address once it has been confirmed, so a user can't
+/// sign up an address they don't control.
+pub async fn send_digest_confirmation(
+	client: &PrismaClient,
+	config: &StumpConfig,
+	data: NewBooksDigestConfirmationData,
+	recipient: &str,
+) -> CoreResult<()> {
+	let primary_emailer = client
+		.emailer()
+		.find_first(vec![emailer::is_primary::equals(true)])
+		.exec()
+		.await?
+		.ok_or_else(|| {
+			CoreError::BadRequest("No primary emailer is configured".to_string())
+		})?;
+	let emailer_id = primary_emailer.id;
+	let emailer_client = SMTPEmailer::try_from(primary_emailer)?
+		.into_client_with(client, config)
+		.await?;
+
+	let data = serde_json::to_value(data)
+		.map_err(|e| CoreError::InternalError(e.to_string()))?;
+	emailer_client
+		.send_template(
+			&digest_confirmation_subject(),
+			recipient,
+			EmailTemplate::NewBooksDigestConfirmation,
+			&data,
+			vec![],
+		)
+		.await?;
+
+	client
+		.emailer_send_record()
+		.create(
+			emailer::id::equals(emailer_id),
+			recipient.to_string(),
+			vec![],
+		)
+		.exec()
+		.await?;
+
+	Ok(())
+}
+
+/// The content ID a cover is embedded under, which the template references with `cid:`
+fn thumbnail_content_id(book_id: &str) -> String {
+	format!("book-{book_id}")
+}
+
+/// Load the cover of a book and shrink it, so it can be embedded in a digest. Books without a
+/// generated thumbnail are listed without a cover.
+pub async fn get_digest_thumbnail(
+	config: &StumpConfig,
+	book_id: &str,
+) -> Option<InlineImage> {
+	let (_, content) =
+		match get_thumbnail(config.get_thumbnails_dir(), book_id, None).await {
+			Ok(thumbnail) => thumbnail?,
+			Err(error) => {
+				tracing::warn!(?error, book_id, "Failed to read thumbnail for digest");
+				return None;
+			},
+		};
+
+	match encode_digest_thumbnail(&content) {
+		Ok(content) => Some(InlineImage {
+			content_id: thumbnail_content_id(book_id),
+			content,
+			content_type: EmailContentType::parse("image/jpeg").ok()?,
+		}),
+		Err(error) => {
+			tracing::warn!(?error, book_id, "Failed to encode thumbnail for digest");
+			None
+		},
+	}
+}
+
+/// Downscale an image to fit in a digest and encode it as a JPEG, which every email client can
+/// display (unlike the WebP thumbnails Stump may generate)
+fn encode_digest_thumbnail(content: &[u8]) -> Result<Vec<u8>, image::ImageError> {
+	let mut image = image::load_from_memory(content)?;
+	if image.width() > DIGEST_THUMBNAIL_WIDTH || image.height() > DIGEST_THUMBNAIL_HEIGHT
+	{
+		image = image.resize(
+			DIGEST_THUMBNAIL_WIDTH,
+			DIGEST_THUMBNAIL_HEIGHT,
+			FilterType::Triangle,
+		);
+	}
+
+	let mut buffer = Cursor::new(Vec::new());
+	JpegEncoder::new_with_quality(&mut buffer, DIGEST_THUMBNAIL_QUALITY)
+		.encode_image(&image.to_rgb8())?;
+	Ok(buffer.into_inner())
+}
+
+/// The conditions for the books a user can see which were added within the window of a
+/// digest, i.e. after `since` and up to `until`
+pub fn new_books_for_user_params(
+	user: &User,
+	since: DateTime<FixedOffset>,
+	until: DateTime<FixedOffset>,
+) -> Vec<media::WhereParam> {
+	let age_restriction = user
+		.age_restriction
+		.as_ref()
+		.map(|ar| apply_media_age_restriction(ar.age, ar.restrict_on_unset));
+
+	chain_optional_iter(
+		[
+			media::created_at::gt(since),
+			media::created_at::lte(until),
+			media::deleted_at::equals(None),
+		]
+		.into_iter()
+		.chain(apply_media_library_not_hidden_for_user_filter(user))
+		.collect::<Vec<_>>(),
+		[age_restriction],
+	)
+}
+
+/// Find the subscriptions of active users which are due a digest
+pub async fn find_due_subscriptions(
+	client: &PrismaClient,
+	now: DateTime<Utc>,
+) -> CoreResult<Vec<new_books_digest_subscription::Data>> {
+	let subscriptions = client
+		.new_books_digest_subscription()
+		.find_many(vec![
+			new_books_digest_subscription::confirmed_at::not(None),
+			new_books_digest_subscription::user::is(vec![
+				user::deleted_at::equals(None),
+				user::is_locked::equals(false),
+			]),
+		])
+		.exec()
+		.await?;
+
+	Ok(subscriptions
+		.into_iter()
+		.filter(|subscription| is_digest_due(subscription, now))
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use image::{ImageFormat, RgbImage};
+
+	use super::*;
+
+	fn book(id: &str, has_thumbnail: bool) -> DigestBook {
+		DigestBook {
+			id: id.to_string(),
+			title: format!("Book {id}"),
+			series: None,
+			has_thumbnail,
+		}
+	}
+
+	#[test]
+	fn test_digest_data_links() {
+		let data = NewBooksDigestData::new(
+			"reader".to_string(),
+			Some("https://stump.example.com/"),
+			vec![book("1", true), book("2", false)],
+			5,
+		);
+
+		assert_eq!(
+			data.server_url.as_deref(),
+			Some("https://stump.example.com")
+		);
+		assert_eq!(data.book_count, 5);
+		assert_eq!(data.more_count, 3);
+		assert_eq!(
+			data.books[0].url.as_deref(),
+			Some("https://stump.example.com/books/1")
+		);
+		assert_eq!(data.books[0].thumbnail_cid.as_deref(), Some("book-1"));
+		assert_eq!(data.books[1].thumbnail_cid, None);
+	}
+
+	#[test]
+	fn test_digest_data_without_public_url() {
+		for public_url in [None, Some("")] {
+			let data = NewBooksDigestData::new(
+				"reader".to_string(),
+				public_url,
+				vec![book("1", false)],
+				1,
+			);
+
+			assert_eq!(data.server_url, None);
+			assert_eq!(data.more_count, 0);
+			assert_eq!(data.books[0].url, None);
+		}
+	}
+
+	#[test]
+	fn test_digest_data_renders() {
+		let data = NewBooksDigestData::new(
+			"reader".to_string(),
+			Some("https://stump.example.com"),
+			vec![book("1", true)],
+			2,
+		);
+
+		let rendered = email::render_template(
+			email::EmailTemplate::NewBooksDigest,
+			&serde_json::to_value(data).unwrap(),
+			Default::default(),
+		)
+		.unwrap();
+
+		assert!(rendered.contains("https://stump.example.com/books/1"));
+		assert!(rendered.contains("cid:book-1"));
+		assert!(rendered.contains("1 more"));
+	}
+
+	#[test]
+	fn test_encode_digest_thumbnail() {
+		let mut content = Cursor::new(Vec::new());
+		RgbImage::new(400, 600)
+			.write_to(&mut content, ImageFormat::Png)
+			.unwrap();
+
+		let encoded = encode_digest_thumbnail(content.get_ref()).unwrap();
+		let image = image::load_from_memory(&encoded).unwrap();
+		assert_eq!(image.width(), DIGEST_THUMBNAIL_WIDTH);
+		assert_eq!(image.height(), DIGEST_THUMBNAIL_HEIGHT);
+		assert_eq!(image::guess_format(&encoded).unwrap(), ImageFormat::Jpeg);
+	}
+}
@@ -50,6 +50,10 @@ pub enum FileError {
 	WebpEncodeError(String),
 	#[error("Failed to read directory")]
 	DirectoryReadError,
+	#[error("The file failed an integrity check: {0}")]
+	IntegrityError(String),
+	#[error("Failed to convert the file: {0}")]
+	ConversionError(String),
 	#[error("Incorrect image processor for requested format")]
 	IncorrectProcessorError,
 	#[error("An unknown error occurred: {0}")]
@@ -58,7 +58,7 @@ impl ImageResizeOptions {
 
 /// Supported image formats for processing images throughout Stump.
 #[derive(
-	Default, Debug, Clone, Serialize, Deserialize, Type, ToSchema, PartialEq, Eq,
+	Default, Debug, Clone, Serialize, Deserialize, Type, ToSchema, PartialEq, Eq, Hash,
 )]
 pub enum ImageFormat {
 	Webp,
@@ -5,7 +5,7 @@ use tokio::{fs, sync::oneshot, task::spawn_blocking};
 use crate::{
 	config::StumpConfig,
 	filesystem::{
-		get_page,
+		get_cover, get_page,
 		image::{
 			GenericImageProcessor, ImageFormat, ImageProcessor, ImageProcessorOptions,
 			ProcessorError, WebpProcessor,
@@ -51,7 +51,10 @@ fn do_generate_book_thumbnail(
 	config: &StumpConfig,
 	options: ImageProcessorOptions,
 ) -> Result<GenerateOutput, ProcessorError> {
-	let (_, page_data) = get_page(book_path, options.page.unwrap_or(1), config)?;
+	let (_, page_data) = match options.page {
+		Some(page) => get_page(book_path, page, config)?,
+		None => get_cover(book_path, config)?,
+	};
 	let ext = options.format.extension();
 
 	let thumbnail_path = config
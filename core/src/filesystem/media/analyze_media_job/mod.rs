@@ -1,4 +1,5 @@
 mod task_analyze_dimensions;
+mod task_epub_positions;
 mod task_page_count;
 mod utils;
 
@@ -35,8 +36,11 @@ pub enum AnalyzeMediaTask {
 	UpdatePageCount(MediaID),
 	/// Analyze and store dimensions for each page of a media item specified by an ID.
 	AnalyzePageDimensions(MediaID),
-	/// Performs both [`UpdatePageCount`] and then [`AnalyzePageDimensions`] in sequence for
-	/// the media item specified by an ID.
+	/// Compute and store the positions list of an EPUB specified by an ID.
+	AnalyzeEpubPositions(MediaID),
+	/// Performs [`UpdatePageCount`] and then either [`AnalyzeEpubPositions`] for EPUBs or
+	/// [`AnalyzePageDimensions`] for everything else in sequence for the media item
+	/// specified by an ID.
 	FullAnalysis(MediaID),
 }
 
@@ -46,6 +50,8 @@ pub struct AnalyzeMediaOutput {
 	page_counts_analyzed: u64,
 	/// The number of images whose dimensions were analyzed.
 	image_dimensions_analyzed: u64,
+	/// The number of EPUBs whose positions were analyzed.
+	epub_positions_analyzed: u64,
 	/// The number of media item updates performed.
 	media_updated: u64,
 }
@@ -54,6 +60,7 @@ impl JobOutputExt for AnalyzeMediaOutput {
 	fn update(&mut self, updated: Self) {
 		self.page_counts_analyzed += updated.page_counts_analyzed;
 		self.image_dimensions_analyzed += updated.image_dimensions_analyzed;
+		self.epub_positions_analyzed += updated.epub_positions_analyzed;
 		self.media_updated += updated.media_updated;
 	}
 }
@@ -196,12 +203,20 @@ impl JobExt for AnalyzeMediaJob {
 			AnalyzeMediaTask::AnalyzePageDimensions(id) => {
 				task_analyze_dimensions::execute(id, ctx, &mut output).await?;
 			},
+			AnalyzeMediaTask::AnalyzeEpubPositions(id) => {
+				task_epub_positions::execute(id, ctx, &mut output).await?;
+			},
 			AnalyzeMediaTask::FullAnalysis(id) => {
 				// TODO This is suboptimal because it buffers the file twice, this should be improved later.
 				// First page count needs to be updated
 				task_page_count::execute(id.clone(), ctx, &mut output).await?;
-				// Then we can do the dimensions analysis
-				task_analyze_dimensions::execute(id, ctx, &mut output).await?;
+				// Then we can do the positions or dimensions analysis. EPUBs are paginated by
+				// position rather than by image, so they don't have page dimensions
+				if utils::is_epub_media(&id, ctx).await? {
+					task_epub_positions::execute(id, ctx, &mut output).await?;
+				} else {
+					task_analyze_dimensions::execute(id, ctx, &mut output).await?;
+				}
 			},
 		}
 
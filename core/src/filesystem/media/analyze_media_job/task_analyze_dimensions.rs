@@ -1,7 +1,10 @@
 use image::GenericImageView;
 
 use crate::{
-	db::entity::page_dimension::{dimension_vec_to_string, PageDimension},
+	db::entity::page_dimension::{
+		classify_page_layouts, dimension_vec_to_string, layout_vec_to_string,
+		PageDimension,
+	},
 	filesystem::{
 		analyze_media_job::{utils::fetch_media_with_dimensions, AnalyzeMediaOutput},
 		media::process::get_page,
@@ -12,8 +15,9 @@ use crate::{
 
 /// The logic for [`super::AnalyzeMediaTask::AnalyzePageDimensions`].
 ///
-/// Reads each page of the media item and determines its dimensions then writes them to
-/// the database.
+/// Reads each page of the media item and determines its dimensions, classifies the layout
+/// of each page from them (see [`classify_page_layouts`]) and then writes both to the
+/// database.
 ///
 /// # Arguments
 /// * `id` - The id for the media item being analyzed
@@ -67,16 +71,23 @@ pub(crate) async fn execute(
 				})?
 				.dimensions();
 
-		image_dimensions.push(PageDimension { height, width });
+		image_dimensions.push(PageDimension::new(height, width));
 		output.image_dimensions_analyzed += 1;
 	}
 
+	let layouts = classify_page_layouts(&image_dimensions);
+	for (dimension, layout) in image_dimensions.iter_mut().zip(&layouts) {
+		dimension.layout = Some(*layout);
+	}
+	let layouts_str = layout_vec_to_string(&layouts);
+
 	ctx.report_progress(JobProgress::msg("Writing to database"));
 
 	// Update stored page count
 	// Check if dimensions are stored already or not yet stored
 	if let Some(current_dimensions) = metadata.page_dimensions {
-		// There are already dimensions, we only need to update them if there's a mismatch
+		// There are already dimensions, we only need to update them if there's a mismatch.
+		// Dimensions stored before layouts were classified will mismatch as well
 		if current_dimensions.dimensions != image_dimensions {
 			// Serialize collected dimensions
 			let dimensions_str = dimension_vec_to_string(image_dimensions);
@@ -85,7 +96,10 @@ pub(crate) async fn execute(
 				.page_dimensions()
 				.update(
 					page_dimensions::id::equals(current_dimensions.id),
-					vec![page_dimensions::dimensions::set(dimensions_str)],
+					vec![
+						page_dimensions::dimensions::set(dimensions_str),
+						page_dimensions::layouts::set(Some(layouts_str)),
+					],
 				)
 				.exec()
 				.await?;
@@ -102,7 +116,7 @@ pub(crate) async fn execute(
 			.create(
 				dimensions_str,
 				media_metadata::id::equals(metadata.id.clone()),
-				vec![],
+				vec![page_dimensions::layouts::set(Some(layouts_str))],
 			)
 			.exec()
 			.await?;
@@ -0,0 +1,79 @@
+use crate::{
+	db::entity::spine_positions_to_string,
+	filesystem::{analyze_media_job::AnalyzeMediaOutput, media::EpubProcessor},
+	job::{error::JobError, JobProgress, WorkerCtx},
+	prisma::{epub_positions, media},
+};
+
+/// The logic for [`super::AnalyzeMediaTask::AnalyzeEpubPositions`].
+///
+/// Computes the positions of each resource in the spine of an EPUB and writes them to the
+/// database, so the positions list can be served without reading the entire file. Since the
+/// pages of an EPUB are its positions, the page count of the media item is updated to match.
+///
+/// # Arguments
+/// * `id` - The id for the media item being analyzed
+/// * `ctx` - A reference to the [`WorkerCtx`] for the job
+/// * `output` - A mutable reference to the job output
+pub(crate) async fn execute(
+	id: String,
+	ctx: &WorkerCtx,
+	output: &mut AnalyzeMediaOutput,
+) -> Result<(), JobError> {
+	let media_item = ctx
+		.db
+		.media()
+		.find_unique(media::id::equals(id.clone()))
+		.select(media::select!({ id path pages }))
+		.exec()
+		.await?
+		.ok_or_else(|| {
+			JobError::TaskFailed(format!("Unable to find media item with id: {id}"))
+		})?;
+
+	ctx.report_progress(JobProgress::msg("Computing positions"));
+
+	let path = media_item.path;
+	let spine_positions =
+		tokio::task::spawn_blocking(move || EpubProcessor::get_spine_positions(&path))
+			.await
+			.map_err(|e| JobError::TaskFailed(e.to_string()))??;
+	let total = spine_positions
+		.iter()
+		.map(|resource| resource.count)
+		.sum::<i32>();
+	let positions = spine_positions_to_string(&spine_positions).map_err(|e| {
+		JobError::TaskFailed(format!("Failed to serialize positions: {e}"))
+	})?;
+
+	ctx.report_progress(JobProgress::msg("Writing to database"));
+
+	ctx.db
+		.epub_positions()
+		.upsert(
+			epub_positions::media_id::equals(media_item.id.clone()),
+			(
+				positions.clone(),
+				media::id::equals(media_item.id.clone()),
+				vec![],
+			),
+			vec![epub_positions::positions::set(positions)],
+		)
+		.exec()
+		.await?;
+	output.epub_positions_analyzed += 1;
+
+	if media_item.pages != total {
+		ctx.db
+			.media()
+			.update(
+				media::id::equals(media_item.id),
+				vec![media::pages::set(total)],
+			)
+			.exec()
+			.await?;
+		output.media_updated += 1;
+	}
+
+	Ok(())
+}
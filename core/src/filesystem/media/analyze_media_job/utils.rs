@@ -1,6 +1,6 @@
 use crate::{
 	db::entity::Media,
-	filesystem::analyze_media_job::MediaID,
+	filesystem::{analyze_media_job::MediaID, ContentType},
 	job::{error::JobError, WorkerCtx},
 	prisma::{media, media_metadata},
 };
@@ -46,3 +46,20 @@ pub async fn fetch_media_with_dimensions(
 
 	Ok(media_item)
 }
+
+/// A utility function for checking whether the media specified by its [`MediaID`] is an EPUB.
+pub async fn is_epub_media(id: &MediaID, ctx: &WorkerCtx) -> Result<bool, JobError> {
+	let media_item = ctx
+		.db
+		.media()
+		.find_unique(media::id::equals(id.clone()))
+		.select(media::select!({ path }))
+		.exec()
+		.await
+		.map_err(|e: prisma_client_rust::QueryError| JobError::TaskFailed(e.to_string()))?
+		.ok_or_else(|| {
+			JobError::TaskFailed(format!("Unable to find media item with id: {id}"))
+		})?;
+
+	Ok(ContentType::from_file(&media_item.path).is_epub())
+}
@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use prisma_client_rust::chrono::{DateTime, FixedOffset, Utc};
+use regex::Regex;
 
 use crate::{
 	config::StumpConfig,
@@ -13,12 +14,13 @@ use crate::{
 	CoreError, CoreResult,
 };
 
-use super::{generate_hashes, process_metadata, ProcessedFileHashes};
+use super::{generate_hashes, parse_filename, process_metadata, ProcessedFileHashes};
 
 pub struct MediaBuilder {
 	path: PathBuf,
 	series_id: String,
 	library_config: LibraryConfig,
+	filename_rules: Vec<Regex>,
 	config: StumpConfig,
 }
 
@@ -33,10 +35,19 @@ impl MediaBuilder {
 			path: path.to_path_buf(),
 			series_id: series_id.to_string(),
 			library_config,
+			filename_rules: vec![],
 			config: config.clone(),
 		}
 	}
 
+	/// Set the compiled filename rules of the library, which are tried before the built-in
+	/// patterns when parsing metadata from the path. They are compiled by the caller so that
+	/// a scan only compiles them once, rather than once per book.
+	pub fn with_filename_rules(mut self, filename_rules: Vec<Regex>) -> Self {
+		self.filename_rules = filename_rules;
+		self
+	}
+
 	pub fn rebuild(self, media: &Media) -> CoreResult<Media> {
 		let generated = self.build()?;
 		Ok(Media {
@@ -45,7 +56,18 @@ impl MediaBuilder {
 		})
 	}
 
+	/// Parse metadata from the path of the book, which is used when the file itself has no
+	/// embedded metadata. Nothing is parsed when metadata processing is disabled.
+	fn parse_path_metadata(&self) -> Option<MediaMetadata> {
+		if !self.library_config.process_metadata {
+			return None;
+		}
+
+		parse_filename(&self.path, &self.filename_rules).into_metadata()
+	}
+
 	pub fn build(self) -> CoreResult<Media> {
+		let parsed_metadata = self.parse_path_metadata();
 		let mut processed_entry =
 			process(&self.path, self.library_config.into(), &self.config)?;
 
@@ -72,6 +94,14 @@ impl MediaBuilder {
 			0
 		});
 
+		if processed_entry.metadata.is_none() {
+			tracing::trace!(
+				?parsed_metadata,
+				"No embedded metadata, using parsed metadata"
+			);
+			processed_entry.metadata = parsed_metadata;
+		}
+
 		let pages = processed_entry.pages;
 		if let Some(ref mut metadata) = processed_entry.metadata {
 			let conflicting_page_counts =
@@ -109,7 +139,7 @@ impl MediaBuilder {
 	}
 
 	pub fn regen_meta(&self) -> CoreResult<Option<MediaMetadata>> {
-		Ok(process_metadata(self.path.clone())?)
+		Ok(process_metadata(self.path.clone())?.or_else(|| self.parse_path_metadata()))
 	}
 
 	pub fn custom_visit(self, config: CustomVisit) -> CoreResult<CustomVisitResult> {
@@ -0,0 +1,980 @@
+use std::{
+	fmt,
+	fs::File,
+	io::{Cursor, Read, Write},
+	ops::Range,
+	path::{Path, PathBuf},
+	str::FromStr,
+};
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType};
+use prisma_client_rust::chrono::Utc;
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::{sync::oneshot, task::spawn_blocking};
+use utoipa::ToSchema;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+	config::StumpConfig,
+	filesystem::{
+		content_type::ContentType, error::FileError, media::utils::sort_file_names,
+		FileParts, PathUtils,
+	},
+};
+
+use super::{
+	pdf::PdfProcessor, process::FileConverter, rar::RarProcessor, FileProcessor,
+};
+
+/// The quality pages are encoded with when they are re-encoded for a device
+const DEVICE_JPEG_QUALITY: u8 = 80;
+/// A rough estimate of the bytes each page adds to a converted file, on top of its image
+const PAGE_OVERHEAD_BYTES: u64 = 2048;
+/// A rough estimate of the bytes a converted file needs, regardless of its pages
+const CONTAINER_OVERHEAD_BYTES: u64 = 8192;
+
+/// The format a book is converted to before it is emailed to a device
+#[derive(
+	Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Type,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DeviceTargetFormat {
+	/// The book is sent as-is
+	#[default]
+	Original,
+	/// Comic archives are sent as CBZ files, e.g. a CBR is converted to a CBZ
+	Cbz,
+	/// Comic archives and PDFs are sent as fixed-layout EPUB files
+	Epub,
+	/// Comic archives are sent as PDF files
+	Pdf,
+}
+
+impl DeviceTargetFormat {
+	/// The extension of a file in this format, if the book is converted
+	pub fn extension(&self) -> &'static str {
+		match self {
+			Self::Original | Self::Cbz => "cbz",
+			Self::Epub => "epub",
+			Self::Pdf => "pdf",
+		}
+	}
+
+	/// The content type of a file in this format, if the book is converted
+	pub fn content_type(&self) -> ContentType {
+		match self {
+			Self::Original | Self::Cbz => ContentType::COMIC_ZIP,
+			Self::Epub => ContentType::EPUB_ZIP,
+			Self::Pdf => ContentType::PDF,
+		}
+	}
+}
+
+impl fmt::Display for DeviceTargetFormat {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Original => write!(f, "ORIGINAL"),
+			Self::Cbz => write!(f, "CBZ"),
+			Self::Epub => write!(f, "EPUB"),
+			Self::Pdf => write!(f, "PDF"),
+		}
+	}
+}
+
+impl FromStr for DeviceTargetFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"ORIGINAL" => Ok(Self::Original),
+			"CBZ" => Ok(Self::Cbz),
+			"EPUB" => Ok(Self::Epub),
+			"PDF" => Ok(Self::Pdf),
+			_ => Err(format!("\"{s}\" is not a valid device target format")),
+		}
+	}
+}
+
+/// The options used to convert a book before it is emailed to a device
+#[derive(Debug, Default, Clone)]
+pub struct DeviceConversionOptions {
+	/// The format the book should be converted to
+	pub target_format: DeviceTargetFormat,
+	/// The maximum width or height of a page, in pixels. Larger pages are downscaled
+	pub max_image_dimension: Option<u32>,
+	/// The maximum size of a converted file, in bytes. Books which are larger once converted
+	/// are split into multiple volumes
+	pub max_size_bytes: Option<u64>,
+}
+
+/// A file which is ready to be emailed to a device
+#[derive(Debug)]
+pub struct ConvertedFile {
+	/// The name of the file, including its extension
+	pub name: String,
+	/// The bytes of the file
+	pub content: Vec<u8>,
+	/// The content type of the file
+	pub content_type: ContentType,
+}
+
+/// A page of a book which is being converted
+struct DevicePage {
+	content: Vec<u8>,
+	content_type: ContentType,
+	/// The dimensions of the page, which are only known once it is re-encoded
+	dimensions: Option<(u32, u32)>,
+}
+
+/// A scratch directory in the cache directory, which is removed once the conversion is done
+struct ConversionWorkspace(PathBuf);
+
+impl ConversionWorkspace {
+	fn create(config: &StumpConfig) -> Result<Self, FileError> {
+		let path = config
+			.get_cache_dir()
+			.join("device-conversions")
+			.join(uuid::Uuid::new_v4().to_string());
+		std::fs::create_dir_all(&path)?;
+		Ok(Self(path))
+	}
+}
+
+impl Drop for ConversionWorkspace {
+	fn drop(&mut self) {
+		if let Err(error) = std::fs::remove_dir_all(&self.0) {
+			tracing::warn!(?error, path = ?self.0, "Failed to remove conversion workspace");
+		}
+	}
+}
+
+/// Convert a book so it can be emailed to a device, according to the given options. Books
+/// which don't need to be converted (or can't be, like an EPUB) are returned as-is. A book
+/// which is larger than [DeviceConversionOptions::max_size_bytes] once converted is split into
+/// multiple volumes, so more than one file may be returned.
+pub fn convert_for_device(
+	path: &Path,
+	options: &DeviceConversionOptions,
+	config: &StumpConfig,
+) -> Result<Vec<ConvertedFile>, FileError> {
+	if options.max_image_dimension == Some(0) {
+		return Err(FileError::ConversionError(
+			"The maximum image dimension must be greater than 0".to_string(),
+		));
+	}
+
+	let source_type = ContentType::from_path(path);
+	let FileParts {
+		file_name,
+		file_stem,
+		..
+	} = path.file_parts();
+	let is_convertible =
+		source_type.is_zip() || source_type.is_rar() || source_type == ContentType::PDF;
+
+	let needs_conversion = match options.target_format {
+		DeviceTargetFormat::Original => false,
+		_ if !is_convertible => {
+			tracing::debug!(
+				?source_type,
+				target_format = ?options.target_format,
+				"Book can't be converted, sending it as-is"
+			);
+			false
+		},
+		DeviceTargetFormat::Cbz => {
+			!source_type.is_zip() || options.max_image_dimension.is_some()
+		},
+		DeviceTargetFormat::Epub => true,
+		DeviceTargetFormat::Pdf => {
+			source_type != ContentType::PDF || options.max_image_dimension.is_some()
+		},
+	};
+
+	if !needs_conversion {
+		let content = std::fs::read(path)?;
+		if fits_within(content.len(), options.max_size_bytes) {
+			return Ok(vec![ConvertedFile {
+				name: file_name,
+				content,
+				content_type: source_type,
+			}]);
+		} else if options.target_format == DeviceTargetFormat::Original || !is_convertible
+		{
+			return Err(FileError::ConversionError(format!(
+				"{file_name} is larger than the attachment size limit and can't be split into volumes"
+			)));
+		}
+	}
+
+	let workspace = ConversionWorkspace::create(config)?;
+
+	// A CBR is converted to a CBZ first, which may be all the device needs
+	let (source_path, source_type) = if source_type.is_rar() {
+		let copied_path = workspace.0.join(&file_name);
+		std::fs::copy(path, &copied_path)?;
+		let zip_path = RarProcessor::to_zip(
+			copied_path.to_str().unwrap_or_default(),
+			false,
+			None,
+			config,
+		)?;
+
+		if options.target_format == DeviceTargetFormat::Cbz
+			&& options.max_image_dimension.is_none()
+		{
+			let content = std::fs::read(&zip_path)?;
+			if fits_within(content.len(), options.max_size_bytes) {
+				return Ok(vec![ConvertedFile {
+					name: format!("{file_stem}.cbz"),
+					content,
+					content_type: ContentType::COMIC_ZIP,
+				}]);
+			}
+		}
+
+		(zip_path, ContentType::COMIC_ZIP)
+	} else {
+		(path.to_path_buf(), source_type)
+	};
+
+	let reencode = options.target_format != DeviceTargetFormat::Cbz
+		|| options.max_image_dimension.is_some();
+	let pages = read_pages(&source_path, source_type, config)?
+		.into_iter()
+		.map(|(content_type, content)| {
+			if reencode {
+				encode_page(&content, options.max_image_dimension)
+			} else {
+				Ok(DevicePage {
+					content,
+					content_type,
+					dimensions: None,
+				})
+			}
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+
+	build_volumes(&file_stem, &pages, options)
+}
+
+/// Convert a book in the context of a spawned, blocking task. See [convert_for_device]
+#[tracing::instrument(err, skip(config), fields(path = %path.as_ref().display()))]
+pub async fn convert_for_device_async(
+	path: impl AsRef<Path>,
+	options: DeviceConversionOptions,
+	config: &StumpConfig,
+) -> Result<Vec<ConvertedFile>, FileError> {
+	let (tx, rx) = oneshot::channel();
+
+	let handle = spawn_blocking({
+		let path = path.as_ref().to_path_buf();
+		let config = config.clone();
+
+		move || {
+			let send_result = tx.send(convert_for_device(&path, &options, &config));
+			tracing::trace!(
+				is_err = send_result.is_err(),
+				"Sending result of sync convert_for_device"
+			);
+		}
+	});
+
+	let converted = if let Ok(recv) = rx.await {
+		recv?
+	} else {
+		handle
+			.await
+			.map_err(|e| FileError::UnknownError(e.to_string()))?;
+		return Err(FileError::UnknownError(
+			"Failed to receive converted files".to_string(),
+		));
+	};
+
+	Ok(converted)
+}
+
+fn fits_within(len: usize, max_size_bytes: Option<u64>) -> bool {
+	max_size_bytes.is_none_or(|max| len as u64 <= max)
+}
+
+/// Read the pages of a comic archive or PDF, in order
+fn read_pages(
+	path: &Path,
+	content_type: ContentType,
+	config: &StumpConfig,
+) -> Result<Vec<(ContentType, Vec<u8>)>, FileError> {
+	if content_type == ContentType::PDF {
+		let path_str = path.to_str().unwrap_or_default();
+		let page_count = PdfProcessor::get_page_count(path_str, config)?;
+		return (1..=page_count)
+			.map(|page| PdfProcessor::get_page(path_str, page, config))
+			.collect();
+	}
+
+	let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+	let mut file_names = archive.file_names().map(String::from).collect::<Vec<_>>();
+	sort_file_names(&mut file_names);
+
+	let mut pages = Vec::new();
+	for name in file_names {
+		let mut file = archive.by_name(&name)?;
+		if file.is_dir() {
+			continue;
+		}
+
+		let entry_path = file.enclosed_name().unwrap_or_else(|| PathBuf::from(&name));
+		let content_type = entry_path.naive_content_type();
+		if entry_path.is_hidden_file() || !content_type.is_image() {
+			continue;
+		}
+
+		let mut content = Vec::new();
+		file.read_to_end(&mut content)?;
+		pages.push((content_type, content));
+	}
+
+	if pages.is_empty() {
+		return Err(FileError::NoImageError);
+	}
+
+	Ok(pages)
+}
+
+/// Decode a page, downscale it so neither side is larger than `max_dimension` and encode it
+/// as a JPEG, which every device format supports
+fn encode_page(
+	content: &[u8],
+	max_dimension: Option<u32>,
+) -> Result<DevicePage, FileError> {
+	let mut image = image::load_from_memory(content)?;
+	if let Some(max) = max_dimension {
+		if image.width() > max || image.height() > max {
+			image = image.resize(max, max, FilterType::Triangle);
+		}
+	}
+
+	let image = image.to_rgb8();
+	let mut buffer = Cursor::new(Vec::new());
+	JpegEncoder::new_with_quality(&mut buffer, DEVICE_JPEG_QUALITY)
+		.encode_image(&image)?;
+
+	Ok(DevicePage {
+		content: buffer.into_inner(),
+		content_type: ContentType::JPEG,
+		dimensions: Some((image.width(), image.height())),
+	})
+}
+
+/// Group the pages into volumes which are estimated to fit within the size limit
+fn estimate_volumes(
+	pages: &[DevicePage],
+	max_size_bytes: Option<u64>,
+) -> Vec<Range<usize>> {
+	let Some(max) = max_size_bytes else {
+		return vec![0..pages.len()];
+	};
+
+	let mut volumes = Vec::new();
+	let mut start = 0;
+	let mut size = CONTAINER_OVERHEAD_BYTES;
+	for (idx, page) in pages.iter().enumerate() {
+		let page_size = page.content.len() as u64 + PAGE_OVERHEAD_BYTES;
+		if idx > start && size + page_size > max {
+			volumes.push(start..idx);
+			start = idx;
+			size = CONTAINER_OVERHEAD_BYTES;
+		}
+		size += page_size;
+	}
+	volumes.push(start..pages.len());
+
+	volumes
+}
+
+/// Write the pages into one or more files of the target format. Volumes which turn out to be
+/// larger than the size limit are split in half until they fit, and a single page which doesn't
+/// fit is an error
+fn build_volumes(
+	file_stem: &str,
+	pages: &[DevicePage],
+	options: &DeviceConversionOptions,
+) -> Result<Vec<ConvertedFile>, FileError> {
+	let format = options.target_format;
+	let split = |ranges: &mut Vec<Range<usize>>, idx: usize| {
+		let range = ranges[idx].clone();
+		if range.len() <= 1 {
+			return Err(FileError::ConversionError(format!(
+				"Page {} of {file_stem} is larger than the attachment size limit",
+				range.start + 1
+			)));
+		}
+		let middle = range.start + range.len() / 2;
+		ranges.splice(idx..=idx, [range.start..middle, middle..range.end]);
+		Ok(())
+	};
+
+	// The volumes are first measured without their final titles, which settles how many
+	// there are without writing every volume again each time one is split
+	let mut ranges = estimate_volumes(pages, options.max_size_bytes);
+	let mut idx = 0;
+	while idx < ranges.len() {
+		let content = write_volume(format, file_stem, &pages[ranges[idx].clone()])?;
+		if fits_within(content.len(), options.max_size_bytes) {
+			idx += 1;
+		} else {
+			split(&mut ranges, idx)?;
+		}
+	}
+
+	// The title is part of the EPUB and PDF metadata, so the final volumes are checked
+	// against the limit again and any which no longer fit are split
+	loop {
+		let total = ranges.len();
+		let mut volumes = Vec::with_capacity(total);
+		let mut oversized = None;
+		for (idx, range) in ranges.iter().enumerate() {
+			let title = match total {
+				1 => file_stem.to_string(),
+				_ => format!("{file_stem} ({} of {total})", idx + 1),
+			};
+			let content = write_volume(format, &title, &pages[range.clone()])?;
+			if !fits_within(content.len(), options.max_size_bytes) {
+				oversized = Some(idx);
+				break;
+			}
+			volumes.push(ConvertedFile {
+				name: format!("{title}.{}", format.extension()),
+				content,
+				content_type: format.content_type(),
+			});
+		}
+
+		match oversized {
+			Some(idx) => split(&mut ranges, idx)?,
+			None => return Ok(volumes),
+		}
+	}
+}
+
+fn write_volume(
+	format: DeviceTargetFormat,
+	title: &str,
+	pages: &[DevicePage],
+) -> Result<Vec<u8>, FileError> {
+	match format {
+		DeviceTargetFormat::Original | DeviceTargetFormat::Cbz => write_cbz(pages),
+		DeviceTargetFormat::Epub => write_epub(title, pages),
+		DeviceTargetFormat::Pdf => write_pdf(title, pages),
+	}
+}
+
+fn page_dimensions(page: &DevicePage) -> Result<(u32, u32), FileError> {
+	page.dimensions.ok_or_else(|| {
+		FileError::ConversionError("A page was not encoded before it was written".into())
+	})
+}
+
+fn write_cbz(pages: &[DevicePage]) -> Result<Vec<u8>, FileError> {
+	let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+	// Images are already compressed, so there is nothing to gain from compressing them again
+	let options: FileOptions<()> =
+		FileOptions::default().compression_method(CompressionMethod::Stored);
+
+	for (idx, page) in pages.iter().enumerate() {
+		writer.start_file(
+			format!("{:04}.{}", idx + 1, page.content_type.extension()),
+			options,
+		)?;
+		writer.write_all(&page.content)?;
+	}
+
+	Ok(writer.finish()?.into_inner())
+}
+
+const EPUB_CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+/// Write a fixed-layout EPUB with one page per image
+fn write_epub(title: &str, pages: &[DevicePage]) -> Result<Vec<u8>, FileError> {
+	let title = escape(title);
+	let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+	let options: FileOptions<()> =
+		FileOptions::default().compression_method(CompressionMethod::Stored);
+
+	// The mimetype must be the first entry of the archive, and must not be compressed
+	writer.start_file("mimetype", options)?;
+	writer.write_all(b"application/epub+zip")?;
+	writer.start_file("META-INF/container.xml", options)?;
+	writer.write_all(EPUB_CONTAINER_XML.as_bytes())?;
+
+	let mut manifest = String::new();
+	let mut spine = String::new();
+	for (idx, page) in pages.iter().enumerate() {
+		let (width, height) = page_dimensions(page)?;
+		let number = idx + 1;
+		let image_name = format!("images/{number:04}.{}", page.content_type.extension());
+
+		writer.start_file(format!("OEBPS/{image_name}"), options)?;
+		writer.write_all(&page.content)?;
+
+		writer.start_file(format!("OEBPS/pages/{number:04}.xhtml"), options)?;
+		writer.write_all(
+			format!(
+				r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+<title>{title}</title>
+<meta name="viewport" content="width={width}, height={height}"/>
+<style>body {{ margin: 0; padding: 0; }} img {{ width: 100%; height: 100%; }}</style>
+</head>
+<body><img src="../{image_name}" alt="Page {number}"/></body>
+</html>"#
+			)
+			.as_bytes(),
+		)?;
+
+		let cover_property = if idx == 0 {
+			r#" properties="cover-image""#
+		} else {
+			""
+		};
+		manifest.push_str(&format!(
+			"    <item id=\"image-{number}\" href=\"{image_name}\" media-type=\"{}\"{cover_property}/>\n    <item id=\"page-{number}\" href=\"pages/{number:04}.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+			page.content_type.mime_type(),
+		));
+		spine.push_str(&format!("    <itemref idref=\"page-{number}\"/>\n"));
+	}
+
+	writer.start_file("OEBPS/nav.xhtml", options)?;
+	writer.write_all(
+		format!(
+			r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head><title>{title}</title></head>
+<body>
+<nav epub:type="toc"><ol><li><a href="pages/0001.xhtml">{title}</a></li></ol></nav>
+</body>
+</html>"#
+		)
+		.as_bytes(),
+	)?;
+
+	writer.start_file("OEBPS/content.opf", options)?;
+	writer.write_all(
+		format!(
+			r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">urn:uuid:{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>en</dc:language>
+    <meta property="dcterms:modified">{modified}</meta>
+    <meta property="rendition:layout">pre-paginated</meta>
+    <meta property="rendition:spread">auto</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>"#,
+			identifier = uuid::Uuid::new_v4(),
+			modified = Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+		)
+		.as_bytes(),
+	)?;
+
+	Ok(writer.finish()?.into_inner())
+}
+
+/// Encode a string as a UTF-16BE hex string, which PDF readers accept for any text
+fn pdf_text(value: &str) -> String {
+	let hex = value
+		.encode_utf16()
+		.map(|unit| format!("{unit:04X}"))
+		.collect::<String>();
+	format!("<FEFF{hex}>")
+}
+
+/// Write a PDF with one JPEG page per image. Each page is sized to its image, at 72 DPI
+fn write_pdf(title: &str, pages: &[DevicePage]) -> Result<Vec<u8>, FileError> {
+	let mut buffer = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+	let mut offsets = Vec::new();
+
+	// Objects 1 to 3 are the catalog, page tree and info dictionary. Each page then takes up
+	// three objects: the page itself, its image and its content stream
+	let page_object = |idx: usize| 4 + idx * 3;
+	let kids = (0..pages.len())
+		.map(|idx| format!("{} 0 R", page_object(idx)))
+		.collect::<Vec<_>>()
+		.join(" ");
+
+	let mut write_object = |buffer: &mut Vec<u8>, body: &[u8]| {
+		offsets.push(buffer.len());
+		buffer.extend_from_slice(format!("{} 0 obj\n", offsets.len()).as_bytes());
+		buffer.extend_from_slice(body);
+		buffer.extend_from_slice(b"\nendobj\n");
+	};
+
+	write_object(&mut buffer, b"<< /Type /Catalog /Pages 2 0 R >>");
+	write_object(
+		&mut buffer,
+		format!("<< /Type /Pages /Kids [{kids}] /Count {} >>", pages.len()).as_bytes(),
+	);
+	write_object(
+		&mut buffer,
+		format!("<< /Title {} /Producer (Stump) >>", pdf_text(title)).as_bytes(),
+	);
+
+	for (idx, page) in pages.iter().enumerate() {
+		let (width, height) = page_dimensions(page)?;
+		let object = page_object(idx);
+
+		write_object(
+			&mut buffer,
+			format!(
+				"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {width} {height}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+				object + 1,
+				object + 2,
+			)
+			.as_bytes(),
+		);
+
+		let mut image = format!(
+			"<< /Type /XObject /Subtype /Image /Width {width} /Height {height} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+			page.content.len()
+		)
+		.into_bytes();
+		image.extend_from_slice(&page.content);
+		image.extend_from_slice(b"\nendstream");
+		write_object(&mut buffer, &image);
+
+		let content = format!("q {width} 0 0 {height} 0 0 cm /Im0 Do Q");
+		write_object(
+			&mut buffer,
+			format!(
+				"<< /Length {} >>\nstream\n{content}\nendstream",
+				content.len()
+			)
+			.as_bytes(),
+		);
+	}
+
+	let xref_offset = buffer.len();
+	buffer.extend_from_slice(
+		format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1).as_bytes(),
+	);
+	for offset in &offsets {
+		buffer.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+	}
+	buffer.extend_from_slice(
+		format!(
+			"trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+			offsets.len() + 1
+		)
+		.as_bytes(),
+	);
+
+	Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::filesystem::media::tests::{get_test_rar_path, get_test_zip_path};
+
+	fn noise_png(width: u32, height: u32, seed: u32) -> Vec<u8> {
+		let image = image::RgbImage::from_fn(width, height, |x, y| {
+			let value = (x * 31 + y * 17 + seed * 7) ^ (x * y + seed);
+			image::Rgb([value as u8, (value >> 3) as u8, (value >> 5) as u8])
+		});
+		let mut buffer = Cursor::new(vec![]);
+		image::DynamicImage::ImageRgb8(image)
+			.write_to(&mut buffer, image::ImageFormat::Png)
+			.unwrap();
+		buffer.into_inner()
+	}
+
+	fn write_cbz_fixture(dir: &Path, pages: usize, width: u32, height: u32) -> PathBuf {
+		let path = dir.join("book.cbz");
+		let mut writer = ZipWriter::new(File::create(&path).unwrap());
+		let options: FileOptions<()> = FileOptions::default();
+		for idx in 0..pages {
+			writer
+				.start_file(format!("{:03}.png", idx + 1), options)
+				.unwrap();
+			writer
+				.write_all(&noise_png(width, height, idx as u32))
+				.unwrap();
+		}
+		writer.finish().unwrap();
+		path
+	}
+
+	fn test_config(dir: &Path) -> StumpConfig {
+		StumpConfig::new(dir.join("config").to_string_lossy().to_string())
+	}
+
+	fn cbz_page_count(content: &[u8]) -> usize {
+		zip::ZipArchive::new(Cursor::new(content)).unwrap().len()
+	}
+
+	#[test]
+	fn test_original_is_sent_as_is() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_cbz_fixture(dir.path(), 2, 16, 16);
+
+		let converted = convert_for_device(
+			&path,
+			&DeviceConversionOptions::default(),
+			&test_config(dir.path()),
+		)
+		.unwrap();
+
+		assert_eq!(converted.len(), 1);
+		assert_eq!(converted[0].name, "book.cbz");
+		assert_eq!(converted[0].content, std::fs::read(&path).unwrap());
+	}
+
+	#[test]
+	fn test_original_too_large() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_cbz_fixture(dir.path(), 2, 16, 16);
+
+		let result = convert_for_device(
+			&path,
+			&DeviceConversionOptions {
+				max_size_bytes: Some(10),
+				..Default::default()
+			},
+			&test_config(dir.path()),
+		);
+
+		assert!(matches!(result, Err(FileError::ConversionError(_))));
+	}
+
+	#[test]
+	fn test_rar_to_cbz() {
+		let dir = tempfile::tempdir().unwrap();
+		let config = test_config(dir.path());
+
+		let converted = convert_for_device(
+			Path::new(&get_test_rar_path()),
+			&DeviceConversionOptions {
+				target_format: DeviceTargetFormat::Cbz,
+				..Default::default()
+			},
+			&config,
+		)
+		.unwrap();
+
+		assert_eq!(converted.len(), 1);
+		assert_eq!(converted[0].name, "book.cbz");
+		assert_eq!(converted[0].content_type, ContentType::COMIC_ZIP);
+		assert!(zip::ZipArchive::new(Cursor::new(&converted[0].content)).is_ok());
+		// The workspace should be cleaned up once the conversion is done
+		let workspaces = config.get_cache_dir().join("device-conversions");
+		assert_eq!(std::fs::read_dir(workspaces).unwrap().count(), 0);
+	}
+
+	#[test]
+	fn test_cbz_to_epub() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_cbz_fixture(dir.path(), 3, 40, 60);
+
+		let converted = convert_for_device(
+			&path,
+			&DeviceConversionOptions {
+				target_format: DeviceTargetFormat::Epub,
+				..Default::default()
+			},
+			&test_config(dir.path()),
+		)
+		.unwrap();
+
+		assert_eq!(converted.len(), 1);
+		assert_eq!(converted[0].name, "book.epub");
+
+		let epub_path = dir.path().join("converted.epub");
+		std::fs::write(&epub_path, &converted[0].content).unwrap();
+		let doc = epub::doc::EpubDoc::new(&epub_path).unwrap();
+		assert_eq!(doc.spine.len(), 3);
+		assert_eq!(doc.mdata("title").as_deref(), Some("book"));
+	}
+
+	#[test]
+	fn test_zip_to_pdf() {
+		let dir = tempfile::tempdir().unwrap();
+
+		let converted = convert_for_device(
+			Path::new(&get_test_zip_path()),
+			&DeviceConversionOptions {
+				target_format: DeviceTargetFormat::Pdf,
+				..Default::default()
+			},
+			&test_config(dir.path()),
+		)
+		.unwrap();
+
+		assert_eq!(converted.len(), 1);
+		assert_eq!(converted[0].content_type, ContentType::PDF);
+
+		let pdf_path = dir.path().join("converted.pdf");
+		std::fs::write(&pdf_path, &converted[0].content).unwrap();
+		assert!(
+			PdfProcessor::verify(pdf_path.to_str().unwrap(), &StumpConfig::debug())
+				.is_ok()
+		);
+	}
+
+	#[test]
+	fn test_downscale_pages() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_cbz_fixture(dir.path(), 2, 200, 100);
+
+		let converted = convert_for_device(
+			&path,
+			&DeviceConversionOptions {
+				target_format: DeviceTargetFormat::Cbz,
+				max_image_dimension: Some(50),
+				..Default::default()
+			},
+			&test_config(dir.path()),
+		)
+		.unwrap();
+
+		let mut archive =
+			zip::ZipArchive::new(Cursor::new(&converted[0].content)).unwrap();
+		let mut page = Vec::new();
+		archive.by_index(0).unwrap().read_to_end(&mut page).unwrap();
+		let image = image::load_from_memory(&page).unwrap();
+		assert_eq!((image.width(), image.height()), (50, 25));
+	}
+
+	#[test]
+	fn test_split_into_volumes() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_cbz_fixture(dir.path(), 6, 64, 64);
+		let max_size_bytes = 24 * 1024;
+
+		let converted = convert_for_device(
+			&path,
+			&DeviceConversionOptions {
+				target_format: DeviceTargetFormat::Cbz,
+				max_image_dimension: Some(64),
+				max_size_bytes: Some(max_size_bytes),
+			},
+			&test_config(dir.path()),
+		)
+		.unwrap();
+
+		assert!(converted.len() > 1);
+		let total = converted.len();
+		for (idx, file) in converted.iter().enumerate() {
+			assert!(file.content.len() as u64 <= max_size_bytes);
+			assert_eq!(file.name, format!("book ({} of {total}).cbz", idx + 1));
+		}
+		let pages = converted
+			.iter()
+			.map(|file| cbz_page_count(&file.content))
+			.sum::<usize>();
+		assert_eq!(pages, 6);
+	}
+
+	#[test]
+	fn test_split_into_titled_volumes() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_cbz_fixture(dir.path(), 6, 64, 64);
+		let max_size_bytes = 24 * 1024;
+
+		for target_format in [DeviceTargetFormat::Epub, DeviceTargetFormat::Pdf] {
+			let converted = convert_for_device(
+				&path,
+				&DeviceConversionOptions {
+					target_format,
+					max_image_dimension: Some(64),
+					max_size_bytes: Some(max_size_bytes),
+				},
+				&test_config(dir.path()),
+			)
+			.unwrap();
+
+			assert!(converted.len() > 1);
+			let total = converted.len();
+			for (idx, file) in converted.iter().enumerate() {
+				let title = format!("book ({} of {total})", idx + 1);
+				assert!(file.content.len() as u64 <= max_size_bytes);
+				assert_eq!(file.name, format!("{title}.{}", target_format.extension()));
+				// The PDF title is written as UTF-16 hex
+				let encoded_title = match target_format {
+					DeviceTargetFormat::Pdf => pdf_text(&title),
+					_ => title.clone(),
+				};
+				assert!(file
+					.content
+					.windows(encoded_title.len())
+					.any(|window| window == encoded_title.as_bytes()));
+			}
+		}
+	}
+
+	#[test]
+	fn test_zero_max_image_dimension() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_cbz_fixture(dir.path(), 1, 64, 64);
+
+		let result = convert_for_device(
+			&path,
+			&DeviceConversionOptions {
+				target_format: DeviceTargetFormat::Cbz,
+				max_image_dimension: Some(0),
+				..Default::default()
+			},
+			&test_config(dir.path()),
+		);
+
+		assert!(matches!(result, Err(FileError::ConversionError(_))));
+	}
+
+	#[test]
+	fn test_page_too_large_for_limit() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_cbz_fixture(dir.path(), 2, 64, 64);
+
+		let result = convert_for_device(
+			&path,
+			&DeviceConversionOptions {
+				target_format: DeviceTargetFormat::Pdf,
+				max_size_bytes: Some(512),
+				..Default::default()
+			},
+			&test_config(dir.path()),
+		);
+
+		assert!(matches!(result, Err(FileError::ConversionError(_))));
+	}
+
+	#[test]
+	fn test_device_target_format_round_trip() {
+		for format in [
+			DeviceTargetFormat::Original,
+			DeviceTargetFormat::Cbz,
+			DeviceTargetFormat::Epub,
+			DeviceTargetFormat::Pdf,
+		] {
+			assert_eq!(
+				DeviceTargetFormat::from_str(&format.to_string()).unwrap(),
+				format
+			);
+		}
+	}
+}
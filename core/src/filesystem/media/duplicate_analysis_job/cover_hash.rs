@@ -0,0 +1,112 @@
+use image::imageops::FilterType;
+
+use crate::filesystem::FileError;
+
+/// The width of the grayscale image a cover hash is computed from. It is one pixel wider
+/// than it is tall, since each bit of the hash compares a pixel to its right neighbour.
+const HASH_WIDTH: u32 = 9;
+/// The height of the grayscale image a cover hash is computed from
+const HASH_HEIGHT: u32 = 8;
+
+/// Compute a perceptual (difference) hash of an image. Unlike a file hash, images which
+/// look alike produce hashes which differ by only a few bits, even if they were re-encoded,
+/// resized or scanned separately.
+pub(crate) fn cover_hash(buffer: &[u8]) -> Result<u64, FileError> {
+	let image = image::load_from_memory(buffer)?
+		.resize_exact(HASH_WIDTH, HASH_HEIGHT, FilterType::Triangle)
+		.into_luma8();
+
+	let mut hash = 0u64;
+	for y in 0..HASH_HEIGHT {
+		for x in 0..HASH_WIDTH - 1 {
+			let left = image.get_pixel(x, y)[0];
+			let right = image.get_pixel(x + 1, y)[0];
+			hash = (hash << 1) | u64::from(left > right);
+		}
+	}
+
+	Ok(hash)
+}
+
+/// Whether a hash carries too little information to be compared, e.g. for a blank page
+pub(crate) fn is_uninformative(hash: u64) -> bool {
+	hash == 0 || hash == u64::MAX
+}
+
+/// The number of bits which differ between two hashes
+pub(crate) fn distance(a: u64, b: u64) -> u32 {
+	(a ^ b).count_ones()
+}
+
+pub(crate) fn encode(hash: u64) -> String {
+	format!("{hash:016x}")
+}
+
+pub(crate) fn decode(hash: &str) -> Option<u64> {
+	u64::from_str_radix(hash, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use image::{DynamicImage, ImageFormat, RgbImage};
+
+	use super::*;
+
+	fn encode_image(image: RgbImage, format: ImageFormat) -> Vec<u8> {
+		let mut buffer = Cursor::new(Vec::new());
+		DynamicImage::ImageRgb8(image)
+			.write_to(&mut buffer, format)
+			.unwrap();
+		buffer.into_inner()
+	}
+
+	/// A cover made up of a 9x8 grid of blocks, so its hash doesn't depend on fine detail
+	/// which is lost when resizing
+	fn cover(width: u32, height: u32, column_step: u32, row_step: u32) -> RgbImage {
+		RgbImage::from_fn(width, height, |x, y| {
+			let column = x * HASH_WIDTH / width;
+			let row = y * HASH_HEIGHT / height;
+			let value = ((column * column_step + row * row_step) % 256) as u8;
+			image::Rgb([value, value, value])
+		})
+	}
+
+	#[test]
+	fn test_similar_covers_have_close_hashes() {
+		let original =
+			cover_hash(&encode_image(cover(360, 480, 97, 61), ImageFormat::Png)).unwrap();
+		let rescanned =
+			cover_hash(&encode_image(cover(180, 240, 97, 61), ImageFormat::Jpeg))
+				.unwrap();
+
+		assert!(!is_uninformative(original));
+		assert!(distance(original, rescanned) <= 6);
+	}
+
+	#[test]
+	fn test_different_covers_have_distant_hashes() {
+		let a =
+			cover_hash(&encode_image(cover(360, 480, 97, 61), ImageFormat::Png)).unwrap();
+		let b = cover_hash(&encode_image(cover(360, 480, 59, 149), ImageFormat::Png))
+			.unwrap();
+
+		assert!(distance(a, b) > 6);
+	}
+
+	#[test]
+	fn test_blank_cover_is_uninformative() {
+		let blank = RgbImage::from_pixel(100, 150, image::Rgb([255, 255, 255]));
+		let hash = cover_hash(&encode_image(blank, ImageFormat::Png)).unwrap();
+		assert!(is_uninformative(hash));
+	}
+
+	#[test]
+	fn test_encode_decode() {
+		let hash = 0x0123_4567_89ab_cdef;
+		assert_eq!(encode(hash), "0123456789abcdef");
+		assert_eq!(decode(&encode(hash)), Some(hash));
+		assert_eq!(decode("not a hash"), None);
+	}
+}
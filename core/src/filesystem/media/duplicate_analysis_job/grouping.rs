@@ -0,0 +1,315 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::db::entity::DuplicateReason;
+
+use super::cover_hash;
+
+/// The maximum number of bits two cover hashes may differ by for the covers to be
+/// considered the same
+pub(crate) const COVER_HASH_THRESHOLD: u32 = 6;
+
+/// The number of bytes in a cover hash. Since the threshold is lower than this, two covers
+/// within the threshold must have at least one byte in common at the same position, which
+/// is used to avoid comparing every cover against every other.
+const COVER_HASH_BYTES: u32 = 8;
+const _: () = assert!(COVER_HASH_THRESHOLD < COVER_HASH_BYTES);
+
+/// The details of a media used to find its duplicates
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DuplicateCandidate {
+	pub id: String,
+	pub hash: Option<String>,
+	pub cover_hash: Option<u64>,
+	pub metadata_key: Option<String>,
+}
+
+/// Build a key from the metadata of a media which identifies the issue it is, regardless of
+/// how the series name is formatted. All of the series, number and year are required, since
+/// any subset of them is too likely to match different books.
+pub(crate) fn metadata_key(
+	series: Option<&str>,
+	number: Option<f64>,
+	year: Option<i32>,
+) -> Option<String> {
+	let series = series?
+		.to_lowercase()
+		.chars()
+		.map(|c| if c.is_alphanumeric() { c } else { ' ' })
+		.collect::<String>()
+		.split_whitespace()
+		.collect::<Vec<_>>()
+		.join(" ");
+	if series.is_empty() {
+		return None;
+	}
+
+	Some(format!("{series}|{}|{}", number?, year?))
+}
+
+/// A minimal union-find, used to merge media into groups when any of the checks match
+struct DisjointSet {
+	parents: Vec<usize>,
+}
+
+impl DisjointSet {
+	fn new(size: usize) -> Self {
+		Self {
+			parents: (0..size).collect(),
+		}
+	}
+
+	fn find(&mut self, index: usize) -> usize {
+		let mut root = index;
+		while self.parents[root] != root {
+			root = self.parents[root];
+		}
+		// Compress the path, so later lookups are quicker
+		let mut current = index;
+		while self.parents[current] != root {
+			let next = self.parents[current];
+			self.parents[current] = root;
+			current = next;
+		}
+		root
+	}
+
+	fn union(&mut self, a: usize, b: usize) {
+		let (a, b) = (self.find(a), self.find(b));
+		if a != b {
+			self.parents[b] = a;
+		}
+	}
+}
+
+/// A group of media which are likely duplicates of one another
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CandidateGroup {
+	pub media_ids: Vec<String>,
+	pub reasons: Vec<DuplicateReason>,
+}
+
+/// Group media which are likely duplicates of one another. Media are grouped when they share
+/// a file hash, have covers which look the same, or have matching metadata. The checks are
+/// transitive, so a group may be formed from different checks matching different pairs.
+pub(crate) fn group_duplicates(candidates: &[DuplicateCandidate]) -> Vec<CandidateGroup> {
+	let mut set = DisjointSet::new(candidates.len());
+	let mut matches = Vec::<(usize, usize, DuplicateReason)>::new();
+
+	let mut union_by_key = |keys: Vec<(usize, &str)>, reason: DuplicateReason| {
+		let mut first_with_key = HashMap::<&str, usize>::new();
+		for (index, key) in keys {
+			match first_with_key.get(key) {
+				Some(first) => matches.push((*first, index, reason)),
+				None => {
+					first_with_key.insert(key, index);
+				},
+			}
+		}
+	};
+	union_by_key(
+		candidates
+			.iter()
+			.enumerate()
+			.filter_map(|(index, c)| c.hash.as_deref().map(|hash| (index, hash)))
+			.collect(),
+		DuplicateReason::Hash,
+	);
+	union_by_key(
+		candidates
+			.iter()
+			.enumerate()
+			.filter_map(|(index, c)| c.metadata_key.as_deref().map(|key| (index, key)))
+			.collect(),
+		DuplicateReason::Metadata,
+	);
+
+	let cover_hashes = candidates
+		.iter()
+		.enumerate()
+		.filter_map(|(index, c)| c.cover_hash.map(|hash| (index, hash)))
+		.filter(|(_, hash)| !cover_hash::is_uninformative(*hash))
+		.collect::<Vec<_>>();
+	let mut buckets = HashMap::<(usize, u8), Vec<(usize, u64)>>::new();
+	for (index, hash) in &cover_hashes {
+		for (position, byte) in hash.to_be_bytes().into_iter().enumerate() {
+			buckets
+				.entry((position, byte))
+				.or_default()
+				.push((*index, *hash));
+		}
+	}
+	let mut compared = BTreeSet::new();
+	for bucket in buckets.values() {
+		for (i, (a_index, a_hash)) in bucket.iter().enumerate() {
+			for (b_index, b_hash) in &bucket[i + 1..] {
+				let pair = (*a_index.min(b_index), *a_index.max(b_index));
+				if !compared.insert(pair) {
+					continue;
+				}
+				if cover_hash::distance(*a_hash, *b_hash) <= COVER_HASH_THRESHOLD {
+					matches.push((pair.0, pair.1, DuplicateReason::Cover));
+				}
+			}
+		}
+	}
+
+	for (a, b, _) in &matches {
+		set.union(*a, *b);
+	}
+
+	let mut groups = HashMap::<usize, (BTreeSet<usize>, Vec<DuplicateReason>)>::new();
+	for (a, _, reason) in matches {
+		let root = set.find(a);
+		let (_, reasons) = groups.entry(root).or_default();
+		if !reasons.contains(&reason) {
+			reasons.push(reason);
+		}
+	}
+	for index in 0..candidates.len() {
+		let root = set.find(index);
+		if let Some((members, _)) = groups.get_mut(&root) {
+			members.insert(index);
+		}
+	}
+
+	let mut groups = groups
+		.into_values()
+		.map(|(members, mut reasons)| {
+			reasons.sort_by_key(|reason| *reason as u8);
+			CandidateGroup {
+				media_ids: members
+					.into_iter()
+					.map(|index| candidates[index].id.clone())
+					.collect(),
+				reasons,
+			}
+		})
+		.collect::<Vec<_>>();
+	groups.sort_by(|a, b| a.media_ids.cmp(&b.media_ids));
+	groups
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn candidate(id: &str) -> DuplicateCandidate {
+		DuplicateCandidate {
+			id: id.to_string(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_metadata_key() {
+		assert_eq!(
+			metadata_key(Some("The Amazing  Spider-Man"), Some(1.0), Some(2018)),
+			metadata_key(Some("the amazing spider man"), Some(1.0), Some(2018)),
+		);
+		assert_ne!(
+			metadata_key(Some("Batman"), Some(1.0), Some(1940)),
+			metadata_key(Some("Batman"), Some(1.0), Some(2016)),
+		);
+		assert_eq!(metadata_key(Some("Batman"), Some(1.0), None), None);
+		assert_eq!(metadata_key(Some("Batman"), None, Some(1940)), None);
+		assert_eq!(metadata_key(Some("  "), Some(1.0), Some(1940)), None);
+		assert_eq!(metadata_key(None, Some(1.0), Some(1940)), None);
+	}
+
+	#[test]
+	fn test_group_by_each_reason() {
+		let candidates = vec![
+			DuplicateCandidate {
+				hash: Some("abc".to_string()),
+				..candidate("a")
+			},
+			DuplicateCandidate {
+				hash: Some("abc".to_string()),
+				..candidate("b")
+			},
+			DuplicateCandidate {
+				cover_hash: Some(0x0f0f_0f0f_0f0f_0f0f),
+				..candidate("c")
+			},
+			DuplicateCandidate {
+				cover_hash: Some(0x0f0f_0f0f_0f0f_0f1f),
+				..candidate("d")
+			},
+			DuplicateCandidate {
+				metadata_key: Some("batman|1|1940".to_string()),
+				..candidate("e")
+			},
+			DuplicateCandidate {
+				metadata_key: Some("batman|1|1940".to_string()),
+				..candidate("f")
+			},
+			DuplicateCandidate {
+				hash: Some("unique".to_string()),
+				cover_hash: Some(0xf0f0_f0f0_f0f0_f0f0),
+				metadata_key: Some("batman|2|1940".to_string()),
+				..candidate("g")
+			},
+		];
+
+		assert_eq!(
+			group_duplicates(&candidates),
+			vec![
+				CandidateGroup {
+					media_ids: vec!["a".to_string(), "b".to_string()],
+					reasons: vec![DuplicateReason::Hash],
+				},
+				CandidateGroup {
+					media_ids: vec!["c".to_string(), "d".to_string()],
+					reasons: vec![DuplicateReason::Cover],
+				},
+				CandidateGroup {
+					media_ids: vec!["e".to_string(), "f".to_string()],
+					reasons: vec![DuplicateReason::Metadata],
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn test_groups_are_transitive() {
+		let candidates = vec![
+			DuplicateCandidate {
+				hash: Some("abc".to_string()),
+				..candidate("a")
+			},
+			DuplicateCandidate {
+				hash: Some("abc".to_string()),
+				metadata_key: Some("batman|1|1940".to_string()),
+				..candidate("b")
+			},
+			DuplicateCandidate {
+				metadata_key: Some("batman|1|1940".to_string()),
+				..candidate("c")
+			},
+		];
+
+		assert_eq!(
+			group_duplicates(&candidates),
+			vec![CandidateGroup {
+				media_ids: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+				reasons: vec![DuplicateReason::Hash, DuplicateReason::Metadata],
+			}]
+		);
+	}
+
+	#[test]
+	fn test_uninformative_covers_are_ignored() {
+		let candidates = vec![
+			DuplicateCandidate {
+				cover_hash: Some(0),
+				..candidate("a")
+			},
+			DuplicateCandidate {
+				cover_hash: Some(0),
+				..candidate("b")
+			},
+		];
+
+		assert!(group_duplicates(&candidates).is_empty());
+	}
+}
@@ -0,0 +1,283 @@
+mod cover_hash;
+mod grouping;
+
+use std::collections::HashSet;
+
+use prisma_client_rust::QueryError;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::{
+	db::entity::{join_duplicate_reasons, FileStatus},
+	filesystem::media::process::get_cover_async,
+	job::{
+		error::JobError, JobExecuteLog, JobExt, JobOutputExt, JobTaskOutput, WorkerCtx,
+		WorkingState, WrappedJob,
+	},
+	prisma::{duplicate_group, media},
+};
+
+use grouping::{group_duplicates, metadata_key, DuplicateCandidate};
+
+pub const DUPLICATE_ANALYSIS_JOB_NAME: &str = "duplicate_analysis";
+
+/// The number of media whose covers are hashed in a single task
+const COVER_HASH_CHUNK_SIZE: usize = 25;
+
+media::select!(duplicate_candidate_select {
+	id
+	hash
+	cover_hash
+	metadata: select {
+		series
+		number
+		year
+	}
+});
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum DuplicateAnalysisTask {
+	/// Compute the cover hashes of the media specified by ID
+	HashCovers(Vec<String>),
+	/// Group all media into likely duplicates and replace the stored report
+	BuildReport,
+}
+
+#[derive(Clone, Serialize, Deserialize, Default, Debug, Type)]
+pub struct DuplicateAnalysisOutput {
+	/// The number of covers which were hashed
+	covers_hashed: u64,
+	/// The number of duplicate groups which were found
+	duplicate_groups: u64,
+	/// The number of media which belong to a duplicate group
+	duplicate_media: u64,
+}
+
+impl JobOutputExt for DuplicateAnalysisOutput {
+	fn update(&mut self, updated: Self) {
+		self.covers_hashed += updated.covers_hashed;
+		self.duplicate_groups += updated.duplicate_groups;
+		self.duplicate_media += updated.duplicate_media;
+	}
+}
+
+/// A job which finds media which are likely duplicates of one another, and stores the result
+/// as a report of duplicate groups. Unlike the file hash alone, this also catches re-encodes,
+/// different scans of the same issue and the same book in different formats, by comparing a
+/// perceptual hash of the first page and the normalized metadata of each book.
+#[derive(Clone)]
+pub struct DuplicateAnalysisJob;
+
+impl DuplicateAnalysisJob {
+	pub fn new() -> Box<WrappedJob<DuplicateAnalysisJob>> {
+		WrappedJob::new(Self)
+	}
+}
+
+#[async_trait::async_trait]
+impl JobExt for DuplicateAnalysisJob {
+	const NAME: &'static str = DUPLICATE_ANALYSIS_JOB_NAME;
+
+	type Output = DuplicateAnalysisOutput;
+	type Task = DuplicateAnalysisTask;
+
+	fn description(&self) -> Option<String> {
+		Some("Find duplicate media".to_string())
+	}
+
+	async fn init(
+		&mut self,
+		ctx: &WorkerCtx,
+	) -> Result<WorkingState<Self::Output, Self::Task>, JobError> {
+		// Covers are only hashed once, and the hash is cleared when a scan finds the file changed
+		let unhashed_media = ctx
+			.db
+			.media()
+			.find_many(vec![
+				media::cover_hash::equals(None),
+				media::deleted_at::equals(None),
+				media::status::equals(FileStatus::Ready.to_string()),
+			])
+			.select(media::select!({ id }))
+			.exec()
+			.await
+			.map_err(|e| JobError::InitFailed(e.to_string()))?;
+
+		let ids = unhashed_media
+			.into_iter()
+			.map(|media| media.id)
+			.collect::<Vec<_>>();
+		let tasks = ids
+			.chunks(COVER_HASH_CHUNK_SIZE)
+			.map(|chunk| DuplicateAnalysisTask::HashCovers(chunk.to_vec()))
+			.chain(std::iter::once(DuplicateAnalysisTask::BuildReport))
+			.collect();
+
+		Ok(WorkingState {
+			output: Some(Self::Output::default()),
+			tasks,
+			completed_tasks: 0,
+			logs: vec![],
+		})
+	}
+
+	async fn execute_task(
+		&self,
+		ctx: &WorkerCtx,
+		task: Self::Task,
+	) -> Result<JobTaskOutput<Self>, JobError> {
+		let mut output = Self::Output::default();
+		let mut logs = vec![];
+
+		match task {
+			DuplicateAnalysisTask::HashCovers(ids) => {
+				let books = ctx
+					.db
+					.media()
+					.find_many(vec![media::id::in_vec(ids)])
+					.select(media::select!({ id path }))
+					.exec()
+					.await?;
+
+				for book in books {
+					let result = match get_cover_async(&book.path, &ctx.config).await {
+						Ok((_, buffer)) => tokio::task::spawn_blocking(move || {
+							cover_hash::cover_hash(&buffer)
+						})
+						.await
+						.map_err(|e| JobError::TaskFailed(e.to_string()))?,
+						Err(e) => Err(e),
+					};
+
+					match result {
+						Ok(hash) => {
+							ctx.db
+								.media()
+								.update(
+									media::id::equals(book.id),
+									vec![media::cover_hash::set(Some(
+										cover_hash::encode(hash),
+									))],
+								)
+								.exec()
+								.await?;
+							output.covers_hashed += 1;
+						},
+						Err(e) => {
+							tracing::warn!(?e, path = ?book.path, "Failed to hash cover");
+							logs.push(
+								JobExecuteLog::warn(&format!(
+									"Failed to hash the cover of {}: {}",
+									book.path, e
+								))
+								.with_ctx(book.id),
+							);
+						},
+					}
+				}
+			},
+			DuplicateAnalysisTask::BuildReport => {
+				let (groups, media) = build_report(ctx).await?;
+				output.duplicate_groups = groups;
+				output.duplicate_media = media;
+			},
+		}
+
+		Ok(JobTaskOutput {
+			output,
+			subtasks: vec![],
+			logs,
+		})
+	}
+}
+
+/// Group all media into likely duplicates and replace the stored report with the result.
+/// Groups which were previously dismissed are kept, and are not re-created. Returns the
+/// number of groups and the number of media in them.
+async fn build_report(ctx: &WorkerCtx) -> Result<(u64, u64), JobError> {
+	let candidates = ctx
+		.db
+		.media()
+		.find_many(vec![
+			media::deleted_at::equals(None),
+			media::status::equals(FileStatus::Ready.to_string()),
+		])
+		.select(duplicate_candidate_select::select())
+		.exec()
+		.await?
+		.into_iter()
+		.map(|data| DuplicateCandidate {
+			metadata_key: data.metadata.as_ref().and_then(|metadata| {
+				metadata_key(metadata.series.as_deref(), metadata.number, metadata.year)
+			}),
+			cover_hash: data.cover_hash.as_deref().and_then(cover_hash::decode),
+			hash: data.hash,
+			id: data.id,
+		})
+		.collect::<Vec<_>>();
+
+	let dismissed_groups = ctx
+		.db
+		.duplicate_group()
+		.find_many(vec![duplicate_group::dismissed_at::not(None)])
+		.with(duplicate_group::media::fetch(vec![]))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|group| {
+			group
+				.media
+				.unwrap_or_default()
+				.into_iter()
+				.map(|item| item.media_id)
+				.collect::<HashSet<_>>()
+		})
+		.collect::<Vec<_>>();
+
+	let groups = group_duplicates(&candidates)
+		.into_iter()
+		.filter(|group| {
+			!dismissed_groups
+				.iter()
+				.any(|dismissed| group.media_ids.iter().all(|id| dismissed.contains(id)))
+		})
+		.collect::<Vec<_>>();
+	let group_count = groups.len() as u64;
+	let media_count: u64 = groups
+		.iter()
+		.map(|group| group.media_ids.len() as u64)
+		.sum();
+
+	ctx.db
+		._transaction()
+		.run(|client| async move {
+			client
+				.duplicate_group()
+				.delete_many(vec![duplicate_group::dismissed_at::equals(None)])
+				.exec()
+				.await?;
+
+			for group in groups {
+				let created_group = client
+					.duplicate_group()
+					.create(join_duplicate_reasons(&group.reasons), vec![])
+					.exec()
+					.await?;
+				client
+					._batch(group.media_ids.into_iter().map(|media_id| {
+						client.duplicate_group_media().create(
+							duplicate_group::id::equals(created_group.id.clone()),
+							media::id::equals(media_id),
+							vec![],
+						)
+					}))
+					.await?;
+			}
+
+			Ok::<_, QueryError>(())
+		})
+		.await?;
+	tracing::debug!(group_count, media_count, "Stored duplicate report");
+
+	Ok((group_count, media_count))
+}
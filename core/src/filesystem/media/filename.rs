@@ -0,0 +1,343 @@
+use std::{ops::Range, path::Path, sync::LazyLock};
+
+use regex::Regex;
+
+use crate::db::entity::MediaMetadata;
+
+/// Matches a year wrapped in parentheses or brackets, e.g. `(2019)` or `[1988]`
+static YEAR_PATTERN: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r"[(\[]((?:19|20)\d{2})[)\]]").unwrap());
+/// Matches any group wrapped in parentheses, brackets or braces, e.g. `(Digital)`
+static BRACKETED_PATTERN: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r"[(\[{][^)\]}]*[)\]}]").unwrap());
+/// Matches a volume marker, e.g. `v02`, `Vol.3` or `Volume 3`
+static VOLUME_PATTERN: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r"(?i)\b(?:volume|vol\.?|v)\s*(\d+)\b").unwrap());
+/// Matches an explicit issue or chapter marker, e.g. `#013`, `Ch.25`, `c139` or `Chapter 97`
+static NUMBER_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+	Regex::new(r"(?i)(?:#|\b(?:chapter|ch\.?|c|issue)\s*)(\d+(?:\.\d+)?)\b").unwrap()
+});
+/// Matches any number, which is checked to be standalone separately
+static BARE_NUMBER_PATTERN: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r"\d+(?:\.\d+)?").unwrap());
+
+/// The details which could be parsed from the path of a book
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ParsedFilename {
+	pub series: Option<String>,
+	pub number: Option<f64>,
+	pub volume: Option<i32>,
+	pub year: Option<i32>,
+}
+
+impl ParsedFilename {
+	pub fn is_empty(&self) -> bool {
+		self.series.is_none()
+			&& self.number.is_none()
+			&& self.volume.is_none()
+			&& self.year.is_none()
+	}
+
+	/// Fill any missing details from another parse result
+	fn or(self, other: ParsedFilename) -> ParsedFilename {
+		ParsedFilename {
+			series: self.series.or(other.series),
+			number: self.number.or(other.number),
+			volume: self.volume.or(other.volume),
+			year: self.year.or(other.year),
+		}
+	}
+
+	/// Convert the parsed details into metadata, or `None` if nothing was parsed
+	pub fn into_metadata(self) -> Option<MediaMetadata> {
+		(!self.is_empty()).then(|| MediaMetadata {
+			series: self.series,
+			number: self.number,
+			volume: self.volume,
+			year: self.year,
+			..Default::default()
+		})
+	}
+}
+
+/// Parse the series, issue number, volume and year of a book from its path. The custom rules
+/// are tried first, in order, against the path without its extension (using `/` as the
+/// separator). The first rule which matches provides the values of its named capture groups
+/// (`series`, `number`, `volume` and `year`), and any values it does not provide are filled
+/// by the built-in patterns, which only look at the file name.
+pub fn parse_filename(path: &Path, custom_rules: &[Regex]) -> ParsedFilename {
+	let stem = path
+		.file_stem()
+		.map(|stem| stem.to_string_lossy().to_string())
+		.unwrap_or_default();
+
+	let custom = {
+		let haystack = path
+			.with_extension("")
+			.components()
+			.map(|component| component.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/");
+		custom_rules
+			.iter()
+			.find_map(|rule| rule.captures(&haystack))
+			.map(|captures| {
+				let group = |name: &str| {
+					captures
+						.name(name)
+						.map(|value| value.as_str().trim().to_string())
+						.filter(|value| !value.is_empty())
+				};
+				ParsedFilename {
+					series: group("series").map(|series| clean_series(&series)),
+					number: group("number").and_then(|number| number.parse().ok()),
+					volume: group("volume").and_then(|volume| volume.parse().ok()),
+					year: group("year").and_then(|year| year.parse().ok()),
+				}
+			})
+			.unwrap_or_default()
+	};
+
+	custom.or(parse_stem(&stem))
+}
+
+/// Parse a file name (without its extension) using the built-in patterns for common comic
+/// and manga naming conventions
+fn parse_stem(stem: &str) -> ParsedFilename {
+	let normalized = stem.replace('_', " ");
+
+	let year = YEAR_PATTERN
+		.captures(&normalized)
+		.and_then(|captures| captures[1].parse().ok());
+
+	// Anything bracketed is either the year, which was already captured, or a tag such as
+	// `(Digital)` or `[Scanlator]` which would otherwise be mistaken for the issue number.
+	// The groups are blanked out, rather than removed, so the positions of the rest stay put
+	let core = BRACKETED_PATTERN
+		.replace_all(&normalized, |captures: &regex::Captures| {
+			" ".repeat(captures[0].len())
+		})
+		.to_string();
+
+	let volume_match = VOLUME_PATTERN.captures(&core).map(|captures| {
+		let whole = captures.get(0).unwrap();
+		(whole.range(), captures[1].parse::<i32>().ok())
+	});
+	let volume_range = volume_match.as_ref().map(|(range, _)| range.clone());
+
+	let number_match = NUMBER_PATTERN
+		.captures(&core)
+		.map(|captures| {
+			let whole = captures.get(0).unwrap();
+			(whole.range(), captures[1].parse::<f64>().ok())
+		})
+		.or_else(|| bare_number(&core, volume_range.as_ref()));
+
+	let first_token = [
+		volume_range.as_ref().map(|range| range.start),
+		number_match.as_ref().map(|(range, _)| range.start),
+	]
+	.into_iter()
+	.flatten()
+	.min();
+	// The series is only inferred when something follows it, otherwise the whole file name
+	// is more likely to be a title than a series
+	let series = first_token
+		.map(|start| clean_series(&core[..start]))
+		.filter(|series| !series.is_empty());
+
+	ParsedFilename {
+		series,
+		number: number_match.and_then(|(_, number)| number),
+		volume: volume_match.and_then(|(_, volume)| volume),
+		year,
+	}
+}
+
+/// Find the last standalone number in a file name, e.g. `193` in `The Walking Dead 193`. A
+/// number at the very start is skipped, since it is more likely part of the title (e.g.
+/// `1984`), as is a number within the volume marker.
+fn bare_number(
+	core: &str,
+	volume: Option<&Range<usize>>,
+) -> Option<(Range<usize>, Option<f64>)> {
+	BARE_NUMBER_PATTERN
+		.find_iter(core)
+		.filter(|found| {
+			let preceded_by_separator = core[..found.start()]
+				.chars()
+				.next_back()
+				.is_some_and(|c| c.is_whitespace() || c == '-');
+			let followed_by_separator = core[found.end()..]
+				.chars()
+				.next()
+				.is_none_or(|c| c.is_whitespace() || c == '-');
+			let in_volume = volume.is_some_and(|range| {
+				range.start <= found.start() && found.end() <= range.end
+			});
+			found.start() > 0
+				&& preceded_by_separator
+				&& followed_by_separator
+				&& !in_volume
+		})
+		.last()
+		.map(|found| (found.range(), found.as_str().parse().ok()))
+}
+
+/// Trim separators from the ends of a series name and collapse any repeated whitespace
+fn clean_series(series: &str) -> String {
+	series
+		.split_whitespace()
+		.collect::<Vec<_>>()
+		.join(" ")
+		.trim_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '.' | ',' | ':'))
+		.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	fn parsed(
+		series: Option<&str>,
+		number: Option<f64>,
+		volume: Option<i32>,
+		year: Option<i32>,
+	) -> ParsedFilename {
+		ParsedFilename {
+			series: series.map(String::from),
+			number,
+			volume,
+			year,
+		}
+	}
+
+	/// A corpus of real-world file names, with what is expected to be parsed from each
+	fn corpus() -> Vec<(&'static str, ParsedFilename)> {
+		vec![
+			(
+				"Series v02 #013 (2019).cbz",
+				parsed(Some("Series"), Some(13.0), Some(2), Some(2019)),
+			),
+			("Vol.3 Ch.25.cbz", parsed(None, Some(25.0), Some(3), None)),
+			(
+				"Saga #054 (2018) (Digital) (Zone-Empire).cbr",
+				parsed(Some("Saga"), Some(54.0), None, Some(2018)),
+			),
+			(
+				"The Walking Dead 193 (2019) (digital) (Son of Ultron-Empire).cbz",
+				parsed(Some("The Walking Dead"), Some(193.0), None, Some(2019)),
+			),
+			(
+				"Monstress_031_(2020)_(Digital)_(Zone-Empire).cbz",
+				parsed(Some("Monstress"), Some(31.0), None, Some(2020)),
+			),
+			(
+				"Batman - The Long Halloween 01 (of 13) (1996).cbz",
+				parsed(
+					Some("Batman - The Long Halloween"),
+					Some(1.0),
+					None,
+					Some(1996),
+				),
+			),
+			(
+				"Spider-Man 2099 v1 001.cbz",
+				parsed(Some("Spider-Man 2099"), Some(1.0), Some(1), None),
+			),
+			(
+				"Invincible 100.5 (2013).cbz",
+				parsed(Some("Invincible"), Some(100.5), None, Some(2013)),
+			),
+			(
+				"X-Men '92 001 (2016).cbz",
+				parsed(Some("X-Men '92"), Some(1.0), None, Some(2016)),
+			),
+			(
+				"Akira v03 (1988) [Dark Horse].cbz",
+				parsed(Some("Akira"), None, Some(3), Some(1988)),
+			),
+			(
+				"Berserk Volume 01.cbz",
+				parsed(Some("Berserk"), None, Some(1), None),
+			),
+			(
+				"One Piece v01 c001.cbz",
+				parsed(Some("One Piece"), Some(1.0), Some(1), None),
+			),
+			(
+				"Attack on Titan c139 [Scanlator].cbz",
+				parsed(Some("Attack on Titan"), Some(139.0), None, None),
+			),
+			(
+				"Chainsaw Man - Chapter 097.cbz",
+				parsed(Some("Chainsaw Man"), Some(97.0), None, None),
+			),
+			(
+				"Vinland Saga Vol. 12 Ch. 87.5.cbz",
+				parsed(Some("Vinland Saga"), Some(87.5), Some(12), None),
+			),
+			(
+				"[Group] Yotsuba&! v14 (2022).cbz",
+				parsed(Some("Yotsuba&!"), None, Some(14), Some(2022)),
+			),
+			(
+				"Sandman 1989 #1.cbz",
+				parsed(Some("Sandman 1989"), Some(1.0), None, None),
+			),
+			("1984.epub", parsed(None, None, None, None)),
+			("The Hobbit.epub", parsed(None, None, None, None)),
+			("Dune (1965).epub", parsed(None, None, None, Some(1965))),
+			(
+				"Hellboy - Seed of Destruction 002.cbz",
+				parsed(Some("Hellboy - Seed of Destruction"), Some(2.0), None, None),
+			),
+		]
+	}
+
+	#[test]
+	fn test_corpus() {
+		for (file_name, expected) in corpus() {
+			let path = PathBuf::from("/library/series").join(file_name);
+			assert_eq!(parse_filename(&path, &[]), expected, "{file_name}");
+		}
+	}
+
+	#[test]
+	fn test_custom_rules() {
+		let rules = vec![Regex::new(
+			r"(?P<series>[^/]+)/Volume (?P<volume>\d+)/[^/]*?(?P<number>\d+)$",
+		)
+		.unwrap()];
+		let path = PathBuf::from("/library/Berserk/Volume 02/Page Set 015.cbz");
+		assert_eq!(
+			parse_filename(&path, &rules),
+			parsed(Some("Berserk"), Some(15.0), Some(2), None)
+		);
+	}
+
+	#[test]
+	fn test_custom_rules_are_filled_by_builtins() {
+		let rules =
+			vec![Regex::new(r"/(?P<series>[^/]+) Issue-(?P<number>\d+)").unwrap()];
+		let path = PathBuf::from("/library/Saga/Saga Issue-7 (2012).cbz");
+		assert_eq!(
+			parse_filename(&path, &rules),
+			parsed(Some("Saga"), Some(7.0), None, Some(2012))
+		);
+	}
+
+	#[test]
+	fn test_into_metadata() {
+		assert!(ParsedFilename::default().into_metadata().is_none());
+
+		let metadata = parsed(Some("Saga"), Some(54.0), None, Some(2018))
+			.into_metadata()
+			.unwrap();
+		assert_eq!(metadata.series, Some("Saga".to_string()));
+		assert_eq!(metadata.number, Some(54.0));
+		assert_eq!(metadata.year, Some(2018));
+	}
+}
@@ -1,18 +1,54 @@
 use merge::Merge;
-use quick_xml::{events::Event, Reader};
-use std::{collections::HashMap, fs::File, io::BufReader, path::PathBuf};
+use quick_xml::{
+	events::{BytesStart, BytesText, Event},
+	name::QName,
+	Reader, Writer,
+};
+use regex::Regex;
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::BufReader,
+	path::{Path, PathBuf},
+	sync::{Arc, LazyLock},
+};
 
-const ACCEPTED_EPUB_COVER_MIMES: [&str; 2] = ["image/jpeg", "image/png"];
+const ACCEPTED_EPUB_COVER_MIMES: [&str; 5] = [
+	"image/jpeg",
+	"image/png",
+	"image/webp",
+	"image/gif",
+	"image/svg+xml",
+];
 const DEFAULT_EPUB_COVER_ID: &str = "cover";
+/// How many documents are followed to find a cover image, e.g. a cover page which embeds an
+/// SVG which embeds the image
+const MAX_COVER_REFERENCE_DEPTH: usize = 2;
+/// The height SVG covers are rasterized at, which is more than enough for thumbnails
+const SVG_COVER_HEIGHT: f32 = 1600.0;
+/// The largest width SVG covers are rasterized at, so an unusually wide SVG can't allocate
+/// an enormous image
+const SVG_COVER_MAX_WIDTH: f32 = 1600.0;
+
+/// The fonts installed on the system, which text in SVG covers is rendered with. Loading them
+/// scans the font directories, so it is only done once
+static SVG_FONT_DB: LazyLock<Arc<resvg::usvg::fontdb::Database>> = LazyLock::new(|| {
+	let mut fontdb = resvg::usvg::fontdb::Database::new();
+	fontdb.load_system_fonts();
+	Arc::new(fontdb)
+});
 
 use crate::{
 	config::StumpConfig,
-	db::entity::MediaMetadata,
+	db::entity::{EpubPositionList, EpubSpinePositions, MediaMetadata},
 	filesystem::{
 		content_type::ContentType,
 		error::FileError,
 		hash::{self, generate_koreader_hash},
-		media::process::{FileProcessor, FileProcessorOptions, ProcessedFile},
+		media::{
+			process::{FileProcessor, FileProcessorOptions, ProcessedFile},
+			zip::ZipProcessor,
+		},
 		ProcessedFileHashes,
 	},
 };
@@ -165,9 +201,9 @@ impl FileProcessor for EpubProcessor {
 		let metadata = Self::process_metadata(path);
 
 		let path_buf = PathBuf::from(path);
-		let epub_file = Self::open(path)?;
+		let mut epub_file = Self::open(path)?;
 
-		let pages = epub_file.get_num_pages() as i32;
+		let pages = Self::count_positions(&mut epub_file);
 		// Get metadata from epub file if process_metadata failed
 		let metadata = match metadata {
 			Ok(Some(m)) => m,
@@ -187,67 +223,84 @@ impl FileProcessor for EpubProcessor {
 		})
 	}
 
+	/// Get the spine resource containing a position of the EPUB. Pages of an EPUB are
+	/// positions in its [`EpubPositionList`], so the cover is not necessarily page 1. Use
+	/// [`EpubProcessor::get_cover`] for the cover. This reads the positions from the file, see
+	/// [`EpubProcessor::get_page_from_positions`] to use the positions stored for the EPUB.
 	fn get_page(
 		path: &str,
 		page: i32,
 		_: &StumpConfig,
 	) -> Result<(ContentType, Vec<u8>), FileError> {
-		if page == 1 {
-			// Assume this is the cover page
-			EpubProcessor::get_cover(path)
-		} else {
-			EpubProcessor::get_chapter(path, page as usize)
-		}
+		let positions = Self::get_positions(path)?;
+		Self::get_page_from_positions(path, page, &positions)
 	}
 
+	/// Get the number of positions in the EPUB's [`EpubPositionList`]
 	fn get_page_count(path: &str, _: &StumpConfig) -> Result<i32, FileError> {
-		// TODO At present, this likely does not return the correct count of
-		// pages. It should be updated when a better method is determined.
-		let epub_file = Self::open(path)?;
-		let pages = epub_file.get_num_pages() as i32;
-
-		Ok(pages)
+		let mut epub_file = Self::open(path)?;
+		Ok(Self::count_positions(&mut epub_file))
 	}
 
 	fn get_page_content_types(
 		path: &str,
 		pages: Vec<i32>,
 	) -> Result<HashMap<i32, ContentType>, FileError> {
-		let mut epub_file = Self::open(path)?;
+		let positions = Self::get_positions(path)?;
 
 		let mut content_types = HashMap::new();
 
-		for chapter in pages {
-			if chapter == 1 {
-				// Assume this is the cover page
-				let (content_type, _) = Self::get_cover_internal(&mut epub_file)?;
-				content_types.insert(chapter, content_type);
-				continue;
-			}
-
-			if !epub_file.set_current_page(chapter as usize) {
-				tracing::error!(path, chapter, "Failed to get chapter from epub file!");
+		for page in pages {
+			let Some(position) = positions.get(page) else {
+				tracing::error!(path, page, "Position is out of bounds for epub file!");
 				return Err(FileError::EpubReadError(
 					"Failed to get chapter from epub file".to_string(),
 				));
-			}
-
-			let content_type = if let Some(mime) = epub_file.get_current_mime() {
-				ContentType::from(mime.as_str())
-			} else {
-				tracing::error!(
-					chapter_path = ?path,
-					"Failed to get explicit resource mime for chapter. Returning XHTML",
-				);
-
-				ContentType::XHTML
 			};
 
-			content_types.insert(chapter, content_type);
+			content_types.insert(page, ContentType::from(position._type.as_str()));
 		}
 
 		Ok(content_types)
 	}
+
+	/// Verifies the EPUB as a ZIP archive (see [`ZipProcessor::verify`]), and then that it has
+	/// a spine and that every resource in its manifest exists
+	fn verify(path: &str, config: &StumpConfig) -> Result<(), FileError> {
+		ZipProcessor::verify(path, config)?;
+
+		let mut epub_file = Self::open(path)?;
+		if epub_file.spine.is_empty() {
+			return Err(FileError::IntegrityError(
+				"The EPUB has no spine".to_string(),
+			));
+		}
+		if let Some(idref) = epub_file
+			.spine
+			.iter()
+			.find(|idref| !epub_file.resources.contains_key(*idref))
+		{
+			return Err(FileError::IntegrityError(format!(
+				"The spine references {idref}, which is not in the manifest"
+			)));
+		}
+
+		let resource_paths = epub_file
+			.resources
+			.values()
+			.map(|(path, _)| path.clone())
+			.collect::<Vec<_>>();
+		for resource_path in resource_paths {
+			if epub_file.get_resource_by_path(&resource_path).is_none() {
+				return Err(FileError::IntegrityError(format!(
+					"{} is in the manifest but missing from the EPUB",
+					resource_path.display()
+				)));
+			}
+		}
+
+		Ok(())
+	}
 }
 
 impl EpubProcessor {
@@ -256,6 +309,14 @@ impl EpubProcessor {
 	}
 
 	fn get_cover_path(resources: &HashMap<String, (PathBuf, String)>) -> Option<String> {
+		Self::get_weighted_cover_path(resources).map(|(_, id)| id)
+	}
+
+	/// Find the image most likely to be the cover based on its name, along with its weight. A
+	/// weight of 0 means no image was named like a cover, and the first image by ID is returned
+	fn get_weighted_cover_path(
+		resources: &HashMap<String, (PathBuf, String)>,
+	) -> Option<(i32, String)> {
 		let search_result = resources
 			.iter()
 			.filter(|(_, (_, mime))| {
@@ -272,9 +333,7 @@ impl EpubProcessor {
 				// highest ranked cover is a top level "cover.png"
 				// next highest ranked cover is any file starting with "cover"
 				// next highest ranked cover is any file ending with "cover"
-				// TODO: add more other fallbacks
-				//  - parse the first html file and look for the first image
-				//  - check for images that have a ratio between [1.4, 1.6]
+				// TODO: check for images that have a ratio between [1.4, 1.6]
 				let path_str = path.to_string_lossy().to_lowercase();
 				let extension = path
 					.extension()
@@ -310,35 +369,49 @@ impl EpubProcessor {
 				})
 				.collect::<Vec<_>>();
 			sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
-			return sorted.first().map(|(id, _)| id.to_string());
-		}
-
-		if let Some((_, id)) = search_result {
-			return Some(id.to_string());
+			return sorted.first().map(|(id, _)| (0, id.to_string()));
 		}
 
-		None
+		search_result.map(|(weight, id)| (weight, id.to_string()))
 	}
 
 	fn get_cover_internal(
 		epub_file: &mut EpubDoc<BufReader<File>>,
 	) -> Result<(ContentType, Vec<u8>), FileError> {
-		let cover_id = epub_file.get_cover_id().unwrap_or_else(|| {
-			tracing::debug!("Epub file does not contain cover metadata");
-			DEFAULT_EPUB_COVER_ID.to_string()
-		});
-
-		if let Some((buf, mime)) = epub_file.get_resource(&cover_id) {
-			return Ok((ContentType::from(mime.as_str()), buf));
+		for path in Self::get_cover_references(epub_file) {
+			if let Some(cover) = Self::read_cover_resource(epub_file, &path, 0) {
+				return Ok(cover);
+			}
+			tracing::debug!(?path, "Referenced cover could not be read as an image");
 		}
 
 		tracing::debug!(
 			"Explicit cover image could not be found, falling back to searching for best match..."
 		);
-		let id = Self::get_cover_path(&epub_file.resources);
-		if let Some(id) = id {
-			if let Some((buf, mime)) = epub_file.get_resource(id.as_str()) {
-				return Ok((ContentType::from(mime.as_str()), buf));
+		let best_match = Self::get_weighted_cover_path(&epub_file.resources);
+		if let Some((weight, id)) = best_match.as_ref().filter(|(weight, _)| *weight > 0)
+		{
+			tracing::trace!(weight, id, "Found image named like a cover");
+			if let Some(cover) = Self::read_cover_resource_by_id(epub_file, id) {
+				return Ok(cover);
+			}
+		}
+
+		// The first page of a book is often its cover, even when it isn't marked as one
+		let first_page = epub_file
+			.spine
+			.first()
+			.and_then(|idref| epub_file.resources.get(idref))
+			.map(|(path, _)| path.clone());
+		if let Some(cover) =
+			first_page.and_then(|path| Self::read_cover_resource(epub_file, &path, 0))
+		{
+			return Ok(cover);
+		}
+
+		if let Some((_, id)) = best_match {
+			if let Some(cover) = Self::read_cover_resource_by_id(epub_file, &id) {
+				return Ok(cover);
 			}
 		}
 		tracing::error!("Failed to find cover for epub file");
@@ -347,14 +420,130 @@ impl EpubProcessor {
 		))
 	}
 
-	/// Returns the cover image for the epub file. If a cover image cannot be extracted via the
-	/// metadata, it will go through two rounds of fallback methods:
+	/// Get the paths of the resources the EPUB marks as its cover, in order of precedence:
+	///
+	/// 1. The manifest item with the EPUB3 `cover-image` property
+	/// 2. The item referenced by the EPUB2 `<meta name="cover">`
+	/// 3. The cover `<reference>` in the EPUB2 guide
+	/// 4. The cover link in the landmarks of the EPUB3 navigation document
+	/// 5. The item with the default ID of "cover"
 	///
-	/// 1. Attempt to find a resource with the default ID of "cover"
-	/// 2. Attempt to find a resource with a mime type of "image/jpeg" or "image/png", and weight the
-	///    results based on how likely they are to be the cover. For example, if the cover is named
-	///    "cover.jpg", it's probably the cover. The entry with the highest weight, if any, will be
-	///    returned.
+	/// A reference may point to a cover page rather than an image, which is resolved by
+	/// [`EpubProcessor::read_cover_resource`]
+	fn get_cover_references(epub_file: &mut EpubDoc<BufReader<File>>) -> Vec<PathBuf> {
+		let root_file = epub_file.root_file.clone();
+		let Some(package) = epub_file.get_resource_by_path(&root_file) else {
+			tracing::warn!(?root_file, "Failed to read epub package document");
+			return vec![];
+		};
+		let package = EpubCoverReferences::from_package(&package);
+
+		let resource_path = |epub_file: &EpubDoc<BufReader<File>>, id: &str| {
+			epub_file.resources.get(id).map(|(path, _)| path.clone())
+		};
+
+		let mut references = vec![];
+		if let Some(id) = package.cover_image_id {
+			references.extend(resource_path(epub_file, &id));
+		}
+		// The content of the meta should be an ID, but some EPUBs use a path instead
+		if let Some(content) = package.meta_cover {
+			references.extend(
+				resource_path(epub_file, &content)
+					.or_else(|| resolve_epub_href(&root_file, &content)),
+			);
+		}
+		if let Some(href) = package.guide_cover_href {
+			references.extend(resolve_epub_href(&root_file, &href));
+		}
+		if let Some(nav_path) =
+			package.nav_id.and_then(|id| resource_path(epub_file, &id))
+		{
+			let landmark = epub_file
+				.get_resource_by_path(&nav_path)
+				.and_then(|nav| find_landmark_cover_href(&nav))
+				.and_then(|href| resolve_epub_href(&nav_path, &href));
+			references.extend(landmark);
+		}
+		references.extend(resource_path(epub_file, DEFAULT_EPUB_COVER_ID));
+
+		references.into_iter().fold(vec![], |mut unique, path| {
+			if !unique.contains(&path) {
+				unique.push(path);
+			}
+			unique
+		})
+	}
+
+	fn read_cover_resource_by_id(
+		epub_file: &mut EpubDoc<BufReader<File>>,
+		id: &str,
+	) -> Option<(ContentType, Vec<u8>)> {
+		let (path, _) = epub_file.resources.get(id).cloned()?;
+		Self::read_cover_resource(epub_file, &path, 0)
+	}
+
+	/// Read a cover image from the resource at the given path. JPEG, PNG, WebP and GIF images
+	/// are returned as they are. SVG images are followed to the image they embed, if any, or
+	/// are otherwise rasterized to a PNG. Cover pages (XHTML) are followed to the first image
+	/// they reference.
+	fn read_cover_resource(
+		epub_file: &mut EpubDoc<BufReader<File>>,
+		path: &Path,
+		depth: usize,
+	) -> Option<(ContentType, Vec<u8>)> {
+		let content = epub_file.get_resource_by_path(path)?;
+		let mime = epub_file
+			.resources
+			.values()
+			.find(|(resource_path, _)| resource_path == path)
+			.map(|(_, mime)| mime.clone())
+			.unwrap_or_else(|| {
+				let extension = path
+					.extension()
+					.map(|ext| ext.to_string_lossy().to_lowercase())
+					.unwrap_or_default();
+				// SVG isn't a supported content type on its own, since it is rasterized
+				if extension == "svg" {
+					"image/svg+xml".to_string()
+				} else {
+					ContentType::from_extension(&extension).mime_type()
+				}
+			});
+
+		let follow_first_image = |epub_file: &mut EpubDoc<BufReader<File>>| {
+			if depth >= MAX_COVER_REFERENCE_DEPTH {
+				return None;
+			}
+			let referenced = find_first_image_href(&content)
+				.and_then(|href| resolve_epub_href(path, &href))?;
+			tracing::trace!(?path, ?referenced, "Following image referenced by cover");
+			Self::read_cover_resource(epub_file, &referenced, depth + 1)
+		};
+
+		match mime.as_str() {
+			"image/svg+xml" => follow_first_image(epub_file).or_else(|| {
+				rasterize_svg(&content).map(|buffer| (ContentType::PNG, buffer))
+			}),
+			mime if ACCEPTED_EPUB_COVER_MIMES.contains(&mime) => {
+				Some((ContentType::from(mime), content))
+			},
+			"application/xhtml+xml" | "text/html" => follow_first_image(epub_file),
+			_ => None,
+		}
+	}
+
+	/// Returns the cover image for the epub file. The cover is first resolved from the references
+	/// in the package and navigation documents (see [`EpubProcessor::get_cover_references`]). If
+	/// none of them lead to an image, it will go through a few rounds of fallback methods:
+	///
+	/// 1. Attempt to find an image named like a cover, and weight the results based on how likely
+	///    they are to be the cover. For example, if the cover is named "cover.jpg", it's probably
+	///    the cover. The entry with the highest weight, if any, will be returned.
+	/// 2. Attempt to find the first image referenced by the first page of the book
+	/// 3. Return the first image by ID
+	///
+	/// SVG covers are rasterized to PNG, so the returned cover is always a raster image.
 	pub fn get_cover(path: &str) -> Result<(ContentType, Vec<u8>), FileError> {
 		let mut epub_file = EpubDoc::new(path).map_err(|e| {
 			tracing::error!("Failed to open epub file: {e}");
@@ -369,9 +558,15 @@ impl EpubProcessor {
 		chapter: usize,
 	) -> Result<(ContentType, Vec<u8>), FileError> {
 		let mut epub_file = Self::open(path)?;
+		Self::get_chapter_internal(&mut epub_file, chapter)
+	}
 
+	fn get_chapter_internal(
+		epub_file: &mut EpubDoc<BufReader<File>>,
+		chapter: usize,
+	) -> Result<(ContentType, Vec<u8>), FileError> {
 		if !epub_file.set_current_page(chapter) {
-			tracing::error!(path, chapter, "Failed to get chapter from epub file!");
+			tracing::error!(chapter, "Failed to get chapter from epub file!");
 			return Err(FileError::EpubReadError(
 				"Failed to get chapter from epub file".to_string(),
 			));
@@ -386,7 +581,7 @@ impl EpubProcessor {
 			ContentType::from(mime.as_str())
 		} else {
 			tracing::error!(
-				chapter_path = ?path,
+				chapter,
 				"Failed to get explicit resource mime for chapter. Returning XHTML",
 			);
 
@@ -396,6 +591,86 @@ impl EpubProcessor {
 		Ok((content_type, content))
 	}
 
+	/// Get the positions of a resource in the spine, or `None` if the spine item does not
+	/// reference a resource in the EPUB
+	fn get_resource_positions(
+		epub_file: &mut EpubDoc<BufReader<File>>,
+		idref: &str,
+	) -> Option<EpubSpinePositions> {
+		let (href, _) = epub_file.resources.get(idref).cloned()?;
+		let Some((buf, mime)) = epub_file.get_resource(idref) else {
+			tracing::warn!(idref, "Spine item does not reference a resource");
+			return None;
+		};
+		Some(EpubSpinePositions::new(
+			href.to_string_lossy().to_string(),
+			mime,
+			buf.len(),
+		))
+	}
+
+	fn get_spine_positions_internal(
+		epub_file: &mut EpubDoc<BufReader<File>>,
+	) -> Vec<EpubSpinePositions> {
+		let spine = epub_file.spine.clone();
+		spine
+			.iter()
+			.filter_map(|idref| Self::get_resource_positions(epub_file, idref))
+			.collect()
+	}
+
+	fn count_positions(epub_file: &mut EpubDoc<BufReader<File>>) -> i32 {
+		Self::get_spine_positions_internal(epub_file)
+			.iter()
+			.map(|resource| resource.count)
+			.sum()
+	}
+
+	/// Get the index of the spine item containing a (1-based) position of the positions list
+	fn get_chapter_for_position(
+		epub_file: &EpubDoc<BufReader<File>>,
+		positions: &EpubPositionList,
+		position: i32,
+	) -> Result<usize, FileError> {
+		let chapter = positions.get(position).and_then(|position| {
+			epub_file.spine.iter().position(|idref| {
+				epub_file
+					.resources
+					.get(idref)
+					.is_some_and(|(href, _)| href.to_string_lossy() == position.href)
+			})
+		});
+
+		chapter.ok_or_else(|| {
+			tracing::error!(position, "Position is out of bounds for epub file!");
+			FileError::EpubReadError("Failed to get chapter from epub file".to_string())
+		})
+	}
+
+	/// Get the spine resource containing a position of the given positions list, e.g. the
+	/// positions stored for the EPUB when it was analyzed
+	pub fn get_page_from_positions(
+		path: &str,
+		page: i32,
+		positions: &EpubPositionList,
+	) -> Result<(ContentType, Vec<u8>), FileError> {
+		let mut epub_file = Self::open(path)?;
+		let chapter = Self::get_chapter_for_position(&epub_file, positions, page)?;
+		Self::get_chapter_internal(&mut epub_file, chapter)
+	}
+
+	/// Returns the positions of each resource in the spine of the EPUB, which is what gets
+	/// stored for the EPUB after analysis
+	pub fn get_spine_positions(path: &str) -> Result<Vec<EpubSpinePositions>, FileError> {
+		let mut epub_file = Self::open(path)?;
+		Ok(Self::get_spine_positions_internal(&mut epub_file))
+	}
+
+	/// Returns the positions list of the EPUB, see [`EpubPositionList`]
+	pub fn get_positions(path: &str) -> Result<EpubPositionList, FileError> {
+		Self::get_spine_positions(path).map(EpubPositionList::from_spine)
+	}
+
 	pub fn get_resource_by_id(
 		path: &str,
 		resource_id: &str,
@@ -445,43 +720,267 @@ impl EpubProcessor {
 		Ok((content_type, contents))
 	}
 
-	// TODO: write me, maybe using https://docs.rs/regex/latest/regex/
-	pub fn sanitize_html(
+	/// Get a chapter of the EPUB by its index in the spine, sanitized to be served to a browser.
+	/// See [`EpubProcessor::sanitize_resource`]
+	pub fn get_sanitized_chapter(
+		path: &str,
+		chapter: usize,
 		base_url: &str,
-		root: PathBuf,
+	) -> Result<(ContentType, Vec<u8>), FileError> {
+		let mut epub_file = Self::open(path)?;
+
+		if !epub_file.set_current_page(chapter) {
+			tracing::error!(path, chapter, "Failed to get chapter from epub file!");
+			return Err(FileError::EpubReadError(
+				"Failed to get chapter from epub file".to_string(),
+			));
+		}
+
+		let resource_path = epub_file
+			.spine
+			.get(chapter)
+			.and_then(|idref| epub_file.resources.get(idref))
+			.map(|(resource_path, _)| resource_path.clone())
+			.unwrap_or_default();
+		// The raw chapter is used, rather than one with epub URIs, so its URLs are resolved
+		// against the chapter by the sanitizer
+		let (content, mime) = epub_file.get_current().ok_or_else(|| {
+			FileError::EpubReadError("Failed to get chapter from epub file".to_string())
+		})?;
+
+		Ok((
+			ContentType::from(mime.as_str()),
+			Self::sanitize_resource(base_url, &resource_path, content)?,
+		))
+	}
+
+	/// Get a resource of the EPUB by its ID, sanitized to be served to a browser. See
+	/// [`EpubProcessor::sanitize_resource`]
+	pub fn get_sanitized_resource_by_id(
+		path: &str,
+		resource_id: &str,
+		base_url: &str,
+	) -> Result<(ContentType, Vec<u8>), FileError> {
+		let mut epub_file = Self::open(path)?;
+
+		let resource_path = epub_file
+			.resources
+			.get(resource_id)
+			.map(|(resource_path, _)| resource_path.clone());
+		let (buf, mime) = epub_file.get_resource(resource_id).ok_or_else(|| {
+			tracing::error!("Failed to get resource: {resource_id}");
+			FileError::EpubReadError("Failed to get resource".to_string())
+		})?;
+
+		let content = match resource_path {
+			Some(resource_path) => {
+				Self::sanitize_resource(base_url, &resource_path, buf)?
+			},
+			None => buf,
+		};
+
+		Ok((ContentType::from(mime.as_str()), content))
+	}
+
+	/// Get a resource of the EPUB by its path, sanitized to be served to a browser. See
+	/// [`EpubProcessor::sanitize_resource`]
+	pub fn get_sanitized_resource_by_path(
+		path: &str,
+		root: &str,
+		resource_path: PathBuf,
+		base_url: &str,
+	) -> Result<(ContentType, Vec<u8>), FileError> {
+		let adjusted_path = normalize_resource_path(resource_path.clone(), root);
+		let (content_type, content) =
+			Self::get_resource_by_path(path, root, resource_path)?;
+
+		Ok((
+			content_type,
+			Self::sanitize_resource(base_url, &adjusted_path, content)?,
+		))
+	}
+
+	/// Sanitizes a resource of an EPUB, located at `resource_path` within the EPUB, before it
+	/// is served to a browser. HTML, XHTML and SVG documents are sanitized with
+	/// [`EpubProcessor::sanitize_html`], and stylesheets have their URLs rewritten the same
+	/// way. Any other resource is returned as is.
+	pub fn sanitize_resource(
+		base_url: &str,
+		resource_path: &Path,
 		content: Vec<u8>,
 	) -> Result<Vec<u8>, FileError> {
-		// replace all src attributes with `{epubBaseURl}/{root}/{src}`
-		// replace all href attributes with `{epubBaseURl}/{root}/{href}`
-		// base_url/root/
-		let _resolved_base = PathBuf::from(base_url).join(root);
-
-		// 1. convert to string
-		// 2. match all elements with src or href attributes
-		// 3. iterate over all elements
-		// 4. if element has src or href attribute, replace it
-		// 5. convert back to string
-		// 6. convert back to bytes
-
-		let content_str = String::from_utf8(content).map_err(|e| {
-			tracing::error!(error = ?e, "Failed to HTML buffer content to string");
-			FileError::EpubReadError(e.to_string())
-		})?;
+		let extension = resource_path
+			.extension()
+			.map(|extension| extension.to_string_lossy().to_lowercase())
+			.unwrap_or_default();
+
+		match extension.as_str() {
+			"xhtml" | "html" | "htm" | "svg" => {
+				Self::sanitize_html(base_url, resource_path, &content)
+			},
+			"css" => Ok(rewrite_css_urls(
+				&String::from_utf8_lossy(&content),
+				base_url,
+				resource_path,
+			)
+			.into_bytes()),
+			_ => Ok(content),
+		}
+	}
 
-		// use regex to replace all src and href attributes, e.g. invalid_elements = content_str.match(/src="[^"]+"/g)
-		// for each invalid_element, replace it with the correct value
-		// e.g. content_str.replace(invalid_element, `src="${epub_base_url}/${root}/${invalid_element}"`)
+	/// Sanitizes an HTML or XHTML document of an EPUB, located at `resource_path` within the
+	/// EPUB, so it can be safely rendered by a browser:
+	///
+	/// 1. Scripts, frames, embedded objects, `<base>` elements, meta refreshes and SVG
+	///    animations of URLs are removed
+	/// 2. Event handler attributes (e.g. `onload`) and `javascript:` URLs are removed
+	/// 3. Remote resources (e.g. `<img src="https://...">`) are removed, so reading a book does
+	///    not make requests to other servers. Links to remote pages are kept
+	/// 4. Relative URLs, including those in inline styles, are resolved against the document and
+	///    rewritten to the resource route under `base_url`, e.g. `/api/v1/epub/{id}`
+	///
+	/// A document which fails to parse part way through is cut short at the error, since
+	/// everything before it has already been sanitized.
+	pub fn sanitize_html(
+		base_url: &str,
+		resource_path: &Path,
+		content: &[u8],
+	) -> Result<Vec<u8>, FileError> {
+		let mut reader = lenient_reader(content);
+		let mut writer = Writer::new(Vec::with_capacity(content.len()));
+		// The (local) name of an element which is being removed along with its content, and the
+		// depth within it. Only elements with the same name are counted, so stray markup in the
+		// content can't end the removal early or carry it past the end of the element
+		let mut removed: Option<(String, usize)> = None;
+		let mut in_style = false;
+
+		loop {
+			let event = match reader.read_event() {
+				Ok(event) => event,
+				Err(error) => {
+					tracing::warn!(
+						?error,
+						?resource_path,
+						"Failed to parse epub document, truncating it"
+					);
+					break;
+				},
+			};
+
+			if let Some((name, depth)) = removed.as_mut() {
+				match event {
+					Event::Eof => break,
+					Event::Start(element)
+						if local_name(element.name().local_name().as_ref()) == *name =>
+					{
+						*depth += 1
+					},
+					Event::End(element)
+						if local_name(element.name().local_name().as_ref()) == *name =>
+					{
+						*depth -= 1;
+						if *depth == 0 {
+							removed = None;
+						}
+					},
+					_ => {},
+				}
+				continue;
+			}
 
-		let content_bytes = content_str.as_bytes().to_vec();
+			match event {
+				Event::Eof => break,
+				Event::Start(element) if is_raw_text_element(&element) => {
+					// The content of a script isn't markup (e.g. `a<b`), so it is skipped as raw
+					// text up to the matching end tag rather than parsed
+					let name = element.name().as_ref().to_vec();
+					if let Err(error) = reader.read_text(QName(&name)) {
+						tracing::warn!(
+							?error,
+							?resource_path,
+							"Failed to find the end of a script, truncating the document"
+						);
+						break;
+					}
+				},
+				Event::Start(element) if is_removed_element(&element) => {
+					removed = Some((local_name(element.name().local_name().as_ref()), 1));
+				},
+				Event::Empty(element) if is_removed_element(&element) => {},
+				// Processing instructions can load remote stylesheets
+				Event::PI(_) => {},
+				Event::Start(element) => {
+					in_style =
+						local_name(element.name().local_name().as_ref()) == "style";
+					writer.write_event(Event::Start(sanitize_element(
+						&element,
+						base_url,
+						resource_path,
+					)))?;
+				},
+				Event::Empty(element) => {
+					writer.write_event(Event::Empty(sanitize_element(
+						&element,
+						base_url,
+						resource_path,
+					)))?;
+				},
+				Event::End(element) => {
+					if local_name(element.name().local_name().as_ref()) == "style" {
+						in_style = false;
+					}
+					writer.write_event(Event::End(element))?;
+				},
+				Event::Text(text) if in_style => {
+					let css = rewrite_css_urls(
+						&String::from_utf8_lossy(&text),
+						base_url,
+						resource_path,
+					);
+					writer.write_event(Event::Text(BytesText::from_escaped(css)))?;
+				},
+				// A chapter may be served as HTML, which has no CDATA sections outside of
+				// foreign content. An HTML parser ends the bogus comment it sees at the first
+				// `>`, so the content is written as text (or CSS) instead
+				Event::CData(text) if in_style => {
+					let css = rewrite_css_urls(
+						&String::from_utf8_lossy(&text),
+						base_url,
+						resource_path,
+					);
+					// `</` is escaped so the CSS can't close the style element early
+					writer.write_event(Event::Text(BytesText::from_escaped(
+						css.replace("</", "<\\/"),
+					)))?;
+				},
+				Event::CData(text) => {
+					writer.write_event(Event::Text(BytesText::new(
+						&String::from_utf8_lossy(&text),
+					)))?;
+				},
+				// Comments and doctype internals are parsed differently by HTML and XML
+				// parsers, so neither is kept
+				Event::Comment(_) => {},
+				Event::DocType(_) => {
+					writer
+						.write_event(Event::DocType(BytesText::from_escaped("html")))?;
+				},
+				event => writer.write_event(event)?,
+			}
+		}
 
-		Ok(content_bytes)
+		Ok(writer.into_inner())
 	}
 }
 
-pub(crate) fn normalize_resource_path(path: PathBuf, root: &str) -> PathBuf {
+/// The `root` of the resource route for resources at the top level of the EPUB, which are not
+/// in a directory which could be used as the root
+pub const EPUB_TOP_LEVEL_ROOT: &str = "~";
+
+pub fn normalize_resource_path(path: PathBuf, root: &str) -> PathBuf {
 	let mut adjusted_path = path.clone();
 
-	if !adjusted_path.starts_with(root) {
+	if root != EPUB_TOP_LEVEL_ROOT && !adjusted_path.starts_with(root) {
 		adjusted_path = PathBuf::from(root).join(adjusted_path);
 	}
 
@@ -503,11 +1002,745 @@ pub(crate) fn normalize_resource_path(path: PathBuf, root: &str) -> PathBuf {
 	normalized
 }
 
+/// Elements which are removed from EPUB documents along with their content, since they either
+/// run scripts, embed other documents or change how URLs are resolved
+const REMOVED_EPUB_ELEMENTS: [&str; 8] = [
+	"script", "iframe", "frame", "frameset", "object", "embed", "applet", "base",
+];
+
+/// SVG elements which animate the attributes of another element, e.g. its `href`
+const SVG_ANIMATION_ELEMENTS: [&str; 4] =
+	["animate", "set", "animatetransform", "animatemotion"];
+
+static CSS_URL_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+	Regex::new(r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)"'\s]*))\s*\)"#).unwrap()
+});
+static CSS_IMPORT_PATTERN: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r#"(?i)@import\s+(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+/// How a URL in an EPUB document is used, which determines how it is rewritten
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EpubUrlKind {
+	/// The URL is loaded by the reader, e.g. an image or a stylesheet
+	Resource,
+	/// The URL is navigated to, e.g. a link to another chapter
+	Navigation,
+}
+
+fn local_name(name: &[u8]) -> String {
+	String::from_utf8_lossy(name).to_lowercase()
+}
+
+/// Whether the content of an element is raw text rather than markup, so it must not be parsed
+fn is_raw_text_element(element: &BytesStart) -> bool {
+	local_name(element.name().local_name().as_ref()) == "script"
+}
+
+fn is_removed_element(element: &BytesStart) -> bool {
+	let name = local_name(element.name().local_name().as_ref());
+	if REMOVED_EPUB_ELEMENTS.contains(&name.as_str()) {
+		return true;
+	}
+
+	// An animation can set a URL which the sanitizer never sees, e.g. a `javascript:` href
+	if SVG_ANIMATION_ELEMENTS.contains(&name.as_str()) {
+		let animated = get_attribute(element, "attributename").unwrap_or_default();
+		let animated = animated.rsplit(':').next().unwrap_or_default().trim();
+		if animated.eq_ignore_ascii_case("href") || animated.eq_ignore_ascii_case("src") {
+			return true;
+		}
+	}
+
+	// A meta refresh can navigate away from the book
+	name == "meta"
+		&& element
+			.html_attributes()
+			.filter_map(Result::ok)
+			.any(|attr| {
+				local_name(attr.key.as_ref()) == "http-equiv"
+					&& String::from_utf8_lossy(&attr.value)
+						.eq_ignore_ascii_case("refresh")
+			})
+}
+
+/// Rebuilds an element without event handlers, scripted URLs or remote resources, and with
+/// its relative URLs rewritten. See [`EpubProcessor::sanitize_html`]
+fn sanitize_element(
+	element: &BytesStart,
+	base_url: &str,
+	resource_path: &Path,
+) -> BytesStart<'static> {
+	let name = String::from_utf8_lossy(element.name().as_ref()).to_string();
+	let element_name = local_name(element.name().local_name().as_ref());
+	let mut sanitized = BytesStart::new(name);
+
+	for attr in element.html_attributes().filter_map(Result::ok) {
+		let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+		let attr_name = local_name(attr.key.local_name().as_ref());
+		if attr_name.starts_with("on") {
+			continue;
+		}
+
+		let value = attr
+			.unescape_value()
+			.map(|value| value.to_string())
+			.unwrap_or_else(|_| String::from_utf8_lossy(&attr.value).to_string());
+		let value = match attr_name.as_str() {
+			"href" if matches!(element_name.as_str(), "a" | "area") => {
+				rewrite_epub_url(&value, base_url, resource_path, EpubUrlKind::Navigation)
+			},
+			"action" | "formaction" => {
+				rewrite_epub_url(&value, base_url, resource_path, EpubUrlKind::Navigation)
+			},
+			"href" | "src" | "poster" | "background" => {
+				rewrite_epub_url(&value, base_url, resource_path, EpubUrlKind::Resource)
+			},
+			"srcset" => Some(rewrite_srcset(&value, base_url, resource_path)),
+			"style" => Some(rewrite_css_urls(&value, base_url, resource_path)),
+			// The values of SVG animations, which may be URLs
+			"from" | "to" | "by" | "values" if has_unsafe_url(&value) => None,
+			_ => Some(value),
+		};
+
+		if let Some(value) = value {
+			sanitized.push_attribute((key.as_str(), value.as_str()));
+		}
+	}
+
+	sanitized
+}
+
+/// Returns the lowercase scheme of a URL, if it has one
+fn url_scheme(url: &str) -> Option<String> {
+	let (scheme, _) = url.split_once(':')?;
+	let is_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+		&& scheme
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+	is_scheme.then(|| scheme.to_lowercase())
+}
+
+/// Whether any of the `;` separated values of an SVG animation is a URL with a scheme which
+/// isn't safe to navigate to, e.g. `javascript:`
+fn has_unsafe_url(values: &str) -> bool {
+	values.split(';').any(|value| {
+		url_scheme(value.trim())
+			.is_some_and(|scheme| !matches!(scheme.as_str(), "http" | "https" | "mailto"))
+	})
+}
+
+/// Resolves a (percent-decoded) URL path against the resource it was found in, returning
+/// `None` if it points outside of the EPUB
+fn resolve_epub_path(resource_path: &Path, href: &str) -> Option<PathBuf> {
+	let joined = match href.strip_prefix('/') {
+		Some(absolute) => PathBuf::from(absolute),
+		None => resource_path
+			.parent()
+			.map(|parent| parent.join(href))
+			.unwrap_or_else(|| PathBuf::from(href)),
+	};
+
+	let mut resolved = PathBuf::new();
+	for component in joined.components() {
+		match component {
+			std::path::Component::Normal(c) => resolved.push(c),
+			std::path::Component::ParentDir => {
+				if !resolved.pop() {
+					return None;
+				}
+			},
+			_ => {},
+		}
+	}
+
+	(resolved.components().next().is_some()).then_some(resolved)
+}
+
+/// Formats the URL of the resource route for a resource at `resource_path` within the EPUB.
+/// The first directory of the path is the `root` ([EPUB_TOP_LEVEL_ROOT] for resources which
+/// aren't in a directory), and the whole path is encoded as a single `resource` segment so
+/// nested directories survive the route.
+fn epub_resource_url(base_url: &str, resource_path: &Path) -> String {
+	let components = resource_path
+		.components()
+		.map(|component| component.as_os_str().to_string_lossy())
+		.collect::<Vec<_>>();
+	let root = match components.as_slice() {
+		[root, _, ..] => root.clone(),
+		_ => EPUB_TOP_LEVEL_ROOT.into(),
+	};
+
+	format!(
+		"{base_url}/{}/{}",
+		urlencoding::encode(&root),
+		urlencoding::encode(&components.join("/"))
+	)
+}
+
+/// Rewrites a URL found in the EPUB resource at `resource_path`. Relative URLs are resolved
+/// against the resource and rewritten to the resource route under `base_url`. Returns `None`
+/// when the URL should be removed, i.e. it runs a script, loads a remote resource or points
+/// outside of the EPUB.
+fn rewrite_epub_url(
+	url: &str,
+	base_url: &str,
+	resource_path: &Path,
+	kind: EpubUrlKind,
+) -> Option<String> {
+	let url = url.trim();
+	if url.is_empty() || url.starts_with('#') {
+		return Some(url.to_string());
+	}
+
+	if let Some(scheme) = url_scheme(url) {
+		let allowed = match kind {
+			EpubUrlKind::Resource => scheme == "data",
+			EpubUrlKind::Navigation => {
+				matches!(scheme.as_str(), "http" | "https" | "mailto")
+			},
+		};
+		return allowed.then(|| url.to_string());
+	} else if url.starts_with("//") {
+		return (kind == EpubUrlKind::Navigation).then(|| url.to_string());
+	}
+
+	let (path, fragment) = match url.split_once('#') {
+		Some((path, fragment)) => (path, Some(fragment)),
+		None => (url, None),
+	};
+	let path = path.split_once('?').map_or(path, |(path, _)| path);
+	if path.is_empty() {
+		return Some(url.to_string());
+	}
+
+	let resolved = resolve_epub_href(resource_path, path)?;
+	let rewritten = epub_resource_url(base_url, &resolved);
+
+	Some(match fragment {
+		Some(fragment) => format!("{rewritten}#{fragment}"),
+		None => rewritten,
+	})
+}
+
+/// Rewrites each candidate of a `srcset` attribute, dropping those which are removed
+fn rewrite_srcset(srcset: &str, base_url: &str, resource_path: &Path) -> String {
+	srcset
+		.split(',')
+		.filter_map(|candidate| {
+			let candidate = candidate.trim();
+			let (url, descriptor) = candidate
+				.split_once(char::is_whitespace)
+				.unwrap_or((candidate, ""));
+			let url =
+				rewrite_epub_url(url, base_url, resource_path, EpubUrlKind::Resource)?;
+			Some(
+				format!("{url} {}", descriptor.trim())
+					.trim_end()
+					.to_string(),
+			)
+		})
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Rewrites the URLs of `url()` functions and `@import` rules in a stylesheet, replacing
+/// those which are removed with `none`
+fn rewrite_css_urls(css: &str, base_url: &str, resource_path: &Path) -> String {
+	// Returns the rewritten URL, or `None` if it should be removed. URLs which are kept as
+	// is (e.g. data URLs) are returned as they were written, quotes and all.
+	let rewrite = |captures: &regex::Captures, format: fn(&str) -> String| {
+		let url = (1..=3)
+			.find_map(|group| captures.get(group))
+			.map_or("", |url| url.as_str());
+		match rewrite_epub_url(url, base_url, resource_path, EpubUrlKind::Resource) {
+			Some(rewritten) if rewritten == url.trim() => Some(captures[0].to_string()),
+			rewritten => rewritten.map(|rewritten| format(&rewritten)),
+		}
+	};
+
+	let css = CSS_URL_PATTERN.replace_all(css, |captures: &regex::Captures| {
+		rewrite(captures, |url| format!("url(\"{url}\")"))
+			.unwrap_or_else(|| "none".to_string())
+	});
+	CSS_IMPORT_PATTERN
+		.replace_all(&css, |captures: &regex::Captures| {
+			rewrite(captures, |url| format!("@import url(\"{url}\")"))
+				.unwrap_or_else(|| "@import none".to_string())
+		})
+		.to_string()
+}
+
+/// Creates a reader for EPUB documents. Chapters are not always well-formed XHTML, so
+/// mismatched tags are passed through
+fn lenient_reader(content: &[u8]) -> Reader<&[u8]> {
+	let mut reader = Reader::from_reader(content);
+	let config = reader.config_mut();
+	config.check_end_names = false;
+	config.allow_unmatched_ends = true;
+	reader
+}
+
+/// Get the value of an attribute by its (case-insensitive) qualified name, e.g. `epub:type`
+fn get_attribute(element: &BytesStart, name: &str) -> Option<String> {
+	element
+		.html_attributes()
+		.filter_map(Result::ok)
+		.find(|attr| local_name(attr.key.as_ref()) == name)
+		.map(|attr| {
+			attr.unescape_value()
+				.map(|value| value.to_string())
+				.unwrap_or_else(|_| String::from_utf8_lossy(&attr.value).to_string())
+		})
+}
+
+/// Resolves an href found in the EPUB resource at `resource_path` to the path of the resource
+/// it references. Returns `None` for remote URLs and paths outside of the EPUB
+fn resolve_epub_href(resource_path: &Path, href: &str) -> Option<PathBuf> {
+	let href = href.trim();
+	if url_scheme(href).is_some() || href.starts_with("//") {
+		return None;
+	}
+
+	let path = href.split(['#', '?']).next().unwrap_or_default();
+	if path.is_empty() {
+		return None;
+	}
+	let decoded = urlencoding::decode(path).ok()?;
+	resolve_epub_path(resource_path, &decoded)
+}
+
+/// The references to the cover of an EPUB in its package document. See
+/// [`EpubProcessor::get_cover_references`]
+#[derive(Debug, Default)]
+struct EpubCoverReferences {
+	cover_image_id: Option<String>,
+	meta_cover: Option<String>,
+	guide_cover_href: Option<String>,
+	nav_id: Option<String>,
+}
+
+impl EpubCoverReferences {
+	fn from_package(content: &[u8]) -> Self {
+		let mut references = Self::default();
+		let mut reader = lenient_reader(content);
+
+		while let Ok(event) = reader.read_event() {
+			let element = match event {
+				Event::Eof => break,
+				Event::Start(element) | Event::Empty(element) => element,
+				_ => continue,
+			};
+
+			match local_name(element.name().local_name().as_ref()).as_str() {
+				"item" => {
+					let properties =
+						get_attribute(&element, "properties").unwrap_or_default();
+					let properties = properties.split_whitespace().collect::<Vec<_>>();
+					if references.cover_image_id.is_none()
+						&& properties.contains(&"cover-image")
+					{
+						references.cover_image_id = get_attribute(&element, "id");
+					}
+					if references.nav_id.is_none() && properties.contains(&"nav") {
+						references.nav_id = get_attribute(&element, "id");
+					}
+				},
+				"meta"
+					if references.meta_cover.is_none()
+						&& get_attribute(&element, "name").as_deref()
+							== Some("cover") =>
+				{
+					references.meta_cover = get_attribute(&element, "content");
+				},
+				"reference"
+					if references.guide_cover_href.is_none()
+						&& get_attribute(&element, "type")
+							.is_some_and(|kind| kind.eq_ignore_ascii_case("cover")) =>
+				{
+					references.guide_cover_href = get_attribute(&element, "href");
+				},
+				_ => {},
+			}
+		}
+
+		references
+	}
+}
+
+/// Find the href of the cover link in the landmarks of an EPUB3 navigation document
+fn find_landmark_cover_href(content: &[u8]) -> Option<String> {
+	let has_type = |element: &BytesStart, kind: &str| {
+		get_attribute(element, "epub:type")
+			.is_some_and(|types| types.split_whitespace().any(|value| value == kind))
+	};
+
+	let mut reader = lenient_reader(content);
+	let mut in_landmarks = false;
+	while let Ok(event) = reader.read_event() {
+		match event {
+			Event::Eof => break,
+			Event::Start(element) | Event::Empty(element) => {
+				let name = local_name(element.name().local_name().as_ref());
+				if name == "nav" {
+					in_landmarks = has_type(&element, "landmarks");
+				} else if in_landmarks && name == "a" && has_type(&element, "cover") {
+					return get_attribute(&element, "href");
+				}
+			},
+			Event::End(element)
+				if local_name(element.name().local_name().as_ref()) == "nav" =>
+			{
+				in_landmarks = false;
+			},
+			_ => {},
+		}
+	}
+
+	None
+}
+
+/// Find the first image referenced by an XHTML or SVG document, either by an `<img>` or an SVG
+/// `<image>`
+fn find_first_image_href(content: &[u8]) -> Option<String> {
+	let mut reader = lenient_reader(content);
+	while let Ok(event) = reader.read_event() {
+		let element = match event {
+			Event::Eof => break,
+			Event::Start(element) | Event::Empty(element) => element,
+			_ => continue,
+		};
+
+		let href = match local_name(element.name().local_name().as_ref()).as_str() {
+			"img" => get_attribute(&element, "src"),
+			"image" => get_attribute(&element, "xlink:href")
+				.or_else(|| get_attribute(&element, "href")),
+			_ => None,
+		};
+		if href.is_some() {
+			return href;
+		}
+	}
+
+	None
+}
+
+/// Rasterizes an SVG to a PNG which is [`SVG_COVER_HEIGHT`] pixels tall, unless that would
+/// make it wider than [`SVG_COVER_MAX_WIDTH`]. Text is rendered with the fonts installed on the
+/// system, so an SVG which relies on a font the system doesn't have falls back to another one.
+fn rasterize_svg(content: &[u8]) -> Option<Vec<u8>> {
+	let options = resvg::usvg::Options {
+		fontdb: SVG_FONT_DB.clone(),
+		// Only embedded (data URL) images are rendered. The default resolver reads any path
+		// on disk, which would let a crafted cover bake files from the server into its
+		// thumbnail
+		image_href_resolver: resvg::usvg::ImageHrefResolver {
+			resolve_data: resvg::usvg::ImageHrefResolver::default_data_resolver(),
+			resolve_string: Box::new(|_, _| None),
+		},
+		..Default::default()
+	};
+	let tree = resvg::usvg::Tree::from_data(content, &options)
+		.inspect_err(|e| tracing::warn!(error = ?e, "Failed to parse SVG cover"))
+		.ok()?;
+
+	let size = tree.size();
+	let scale =
+		(SVG_COVER_HEIGHT / size.height()).min(SVG_COVER_MAX_WIDTH / size.width());
+	let width = (size.width() * scale).round().max(1.0) as u32;
+	let height = (size.height() * scale).round().max(1.0) as u32;
+	let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height)?;
+	resvg::render(
+		&tree,
+		resvg::tiny_skia::Transform::from_scale(scale, scale),
+		&mut pixmap.as_mut(),
+	);
+
+	pixmap
+		.encode_png()
+		.inspect_err(|e| tracing::warn!(error = ?e, "Failed to encode SVG cover"))
+		.ok()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 	use crate::filesystem::media::tests::get_test_epub_path;
 
+	const BASE_URL: &str = "/api/v1/epub/1";
+
+	fn sanitize(resource_path: &str, content: &str) -> String {
+		let sanitized = EpubProcessor::sanitize_html(
+			BASE_URL,
+			Path::new(resource_path),
+			content.as_bytes(),
+		)
+		.unwrap();
+		String::from_utf8(sanitized).unwrap()
+	}
+
+	/// Writes an EPUB with the given extra metadata, package contents (manifest, spine and
+	/// guide) and files, which are relative to the `OEBPS` directory of the package
+	fn write_test_epub(
+		dir: &Path,
+		metadata: &str,
+		package: &str,
+		files: &[(&str, &[u8])],
+	) -> String {
+		use std::io::Write;
+		use zip::{write::FileOptions, ZipWriter};
+
+		let container = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+	<rootfiles>
+		<rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+	</rootfiles>
+</container>"#;
+		let opf = format!(
+			r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+	<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+		<dc:identifier id="id">fixture</dc:identifier>
+		<dc:title>Fixture</dc:title>
+		{metadata}
+	</metadata>
+	{package}
+</package>"#
+		);
+
+		let path = dir.join("fixture.epub");
+		let mut writer = ZipWriter::new(File::create(&path).unwrap());
+		let options: FileOptions<()> = FileOptions::default();
+		let entries = [
+			("mimetype".to_string(), b"application/epub+zip".as_slice()),
+			("META-INF/container.xml".to_string(), container.as_bytes()),
+			("OEBPS/content.opf".to_string(), opf.as_bytes()),
+		]
+		.into_iter()
+		.chain(
+			files
+				.iter()
+				.map(|(name, content)| (format!("OEBPS/{name}"), *content)),
+		);
+		for (name, content) in entries {
+			writer.start_file(name, options).unwrap();
+			writer.write_all(content).unwrap();
+		}
+		writer.finish().unwrap();
+
+		path.to_string_lossy().to_string()
+	}
+
+	/// Writes an EPUB with nested directories and file names which need to be encoded
+	fn write_tricky_epub(dir: &Path) -> String {
+		write_test_epub(
+			dir,
+			"",
+			r#"<manifest>
+		<item id="chapter" href="Text/Part%201/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+		<item id="figure" href="Images/fig%201.png" media-type="image/png"/>
+	</manifest>
+	<spine>
+		<itemref idref="chapter"/>
+	</spine>"#,
+			&[
+				(
+					"Text/Part 1/chapter 1.xhtml",
+					br#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><script>alert(1)</script></head>
+<body onload="alert(2)"><img src="../../Images/fig%201.png" alt="Figure 1"/></body>
+</html>"#,
+				),
+				("Images/fig 1.png", b"not really a png"),
+			],
+		)
+	}
+
+	const COVER_CHAPTER: &[u8] = br#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml"><body><p>Once upon a time</p></body></html>"#;
+
+	/// Writes an EPUB whose only chapter is [`COVER_CHAPTER`], with an image named like a
+	/// cover which should lose to the cover the EPUB references
+	fn write_cover_epub(
+		dir: &Path,
+		metadata: &str,
+		manifest: &str,
+		guide: &str,
+		files: &[(&str, &[u8])],
+	) -> String {
+		let package = format!(
+			r#"<manifest>
+		<item id="chapter" href="Text/chapter.xhtml" media-type="application/xhtml+xml"/>
+		<item id="decoy" href="Images/cover.png" media-type="image/png"/>
+		{manifest}
+	</manifest>
+	<spine>
+		<itemref idref="chapter"/>
+	</spine>
+	{guide}"#
+		);
+		let files = [
+			("Text/chapter.xhtml", COVER_CHAPTER),
+			("Images/cover.png", b"decoy".as_slice()),
+		]
+		.into_iter()
+		.chain(files.iter().copied())
+		.collect::<Vec<_>>();
+
+		write_test_epub(dir, metadata, &package, &files)
+	}
+
+	#[test]
+	fn test_sanitize_html_removes_scripts() {
+		let sanitized = sanitize(
+			"OEBPS/chapter.xhtml",
+			r#"<html><head><meta http-equiv="Refresh" content="0; url=https://example.com"/><script type="text/javascript">document.write("<p>hi</p>")</script></head><body onload="steal()"><p onClick="steal()" class="text">Hello</p><a href=" JavaScript:steal()">link</a><iframe src="chapter2.xhtml"><p>nested</p></iframe><svg:script>steal()</svg:script></body></html>"#,
+		);
+
+		assert_eq!(
+			sanitized,
+			r#"<html><head></head><body><p class="text">Hello</p><a>link</a></body></html>"#
+		);
+	}
+
+	#[test]
+	fn test_sanitize_html_blocks_remote_resources() {
+		let sanitized = sanitize(
+			"OEBPS/chapter.xhtml",
+			r#"<div><img src="https://example.com/pixel.png" alt="pixel"/><img src="//example.com/pixel.png"/><img src="data:image/png;base64,AAAA"/><link rel="stylesheet" href="http://example.com/style.css"/><a href="https://example.com">Example</a><p style="background: url('https://example.com/bg.png')">Text</p></div>"#,
+		);
+
+		assert_eq!(
+			sanitized,
+			r#"<div><img alt="pixel"/><img/><img src="data:image/png;base64,AAAA"/><link rel="stylesheet"/><a href="https://example.com">Example</a><p style="background: none">Text</p></div>"#
+		);
+	}
+
+	#[test]
+	fn test_sanitize_html_rewrites_nested_paths() {
+		let sanitized = sanitize(
+			"OEBPS/Text/Part 1/chapter 1.xhtml",
+			r##"<div><img src="../../Images/fig%201.png"/><a href="chapter%202.xhtml#note-1">Note</a><a href="#top">Top</a><a href="../../../../etc/passwd">Escape</a><img srcset="../../Images/small.png 1x, https://example.com/large.png 2x"/></div>"##,
+		);
+
+		assert_eq!(
+			sanitized,
+			r##"<div><img src="/api/v1/epub/1/OEBPS/OEBPS%2FImages%2Ffig%201.png"/><a href="/api/v1/epub/1/OEBPS/OEBPS%2FText%2FPart%201%2Fchapter%202.xhtml#note-1">Note</a><a href="#top">Top</a><a>Escape</a><img srcset="/api/v1/epub/1/OEBPS/OEBPS%2FImages%2Fsmall.png 1x"/></div>"##
+		);
+	}
+
+	#[test]
+	fn test_sanitize_html_rewrites_styles() {
+		let sanitized = sanitize(
+			"OEBPS/Text/chapter.xhtml",
+			r#"<html><head><style>@import "https://example.com/fonts.css"; @font-face { src: url(../Fonts/serif.otf); } p > em { color: red; }</style></head></html>"#,
+		);
+
+		assert_eq!(
+			sanitized,
+			r#"<html><head><style>@import none; @font-face { src: url("/api/v1/epub/1/OEBPS/OEBPS%2FFonts%2Fserif.otf"); } p > em { color: red; }</style></head></html>"#
+		);
+	}
+
+	#[test]
+	fn test_sanitize_html_passes_through_malformed_html() {
+		let sanitized = sanitize(
+			"chapter.html",
+			r#"<!DOCTYPE html><p>Line<br>Next &amp; last</p><img src="cover.jpg" hidden>"#,
+		);
+
+		assert_eq!(
+			sanitized,
+			r#"<!DOCTYPE html><p>Line<br>Next &amp; last</p><img src="/api/v1/epub/1/~/cover.jpg" hidden="">"#
+		);
+	}
+
+	#[test]
+	fn test_sanitize_html_escapes_cdata() {
+		let sanitized = sanitize(
+			"OEBPS/chapter.html",
+			r#"<p><![CDATA[><img src=x onerror=alert(1)>]]></p><style><![CDATA[p { color: red; }</style><img src=x onerror=alert(1)>]]></style>"#,
+		);
+
+		assert_eq!(
+			sanitized,
+			r#"<p>&gt;&lt;img src=x onerror=alert(1)&gt;</p><style>p { color: red; }<\/style><img src=x onerror=alert(1)></style>"#
+		);
+	}
+
+	#[test]
+	fn test_sanitize_html_removes_comments_and_doctype_internals() {
+		let sanitized = sanitize(
+			"OEBPS/chapter.html",
+			r#"<!DOCTYPE html [<!ENTITY x "y">]><p><!-- --!><img src=x onerror=alert(1)> -->Text</p>"#,
+		);
+
+		assert_eq!(sanitized, r#"<!DOCTYPE html><p>Text</p>"#);
+	}
+
+	#[test]
+	fn test_sanitize_html_removes_svg_animations() {
+		let sanitized = sanitize(
+			"OEBPS/chapter.xhtml",
+			r##"<svg><a href="#"><animate attributeName="href" to="javascript:steal()"/><set attributeName="xlink:href" to="javascript:steal()"></set><animateTransform attributeName="transform" type="rotate" from="0" to="360"/><animate attributeName="fill" values="red;javascript:steal()"/><text>Click</text></a></svg>"##,
+		);
+
+		assert_eq!(
+			sanitized,
+			r##"<svg><a href="#"><animateTransform attributeName="transform" type="rotate" from="0" to="360"/><animate attributeName="fill"/><text>Click</text></a></svg>"##
+		);
+	}
+
+	#[test]
+	fn test_sanitize_html_skips_script_content() {
+		let sanitized = sanitize(
+			"OEBPS/chapter.xhtml",
+			r#"<body><p>Before</p><script>if (a<b && c>d) { steal() }</script><iframe><iframe>nested</iframe><p>inside</p></iframe><p>After</p></body>"#,
+		);
+
+		assert_eq!(sanitized, r#"<body><p>Before</p><p>After</p></body>"#);
+	}
+
+	#[test]
+	fn test_sanitize_html_truncates_unparseable_documents() {
+		let sanitized = sanitize(
+			"OEBPS/chapter.xhtml",
+			r#"<body><p>Before</p><!-- never closed <p>After</p></body>"#,
+		);
+
+		assert_eq!(sanitized, r#"<body><p>Before</p>"#);
+	}
+
+	#[test]
+	fn test_sanitize_tricky_epub() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_tricky_epub(dir.path());
+
+		let (_, chapter) =
+			EpubProcessor::get_sanitized_chapter(&path, 0, BASE_URL).unwrap();
+		let chapter = String::from_utf8(chapter).unwrap();
+		assert!(!chapter.contains("script"));
+		assert!(!chapter.contains("onload"));
+
+		let url = "/api/v1/epub/1/OEBPS/OEBPS%2FImages%2Ffig%201.png";
+		assert!(chapter.contains(url));
+
+		// The rewritten URL should resolve to the image through the resource route
+		let (root, resource) = url
+			.strip_prefix(&format!("{BASE_URL}/"))
+			.and_then(|route| route.split_once('/'))
+			.unwrap();
+		let resource = urlencoding::decode(resource).unwrap();
+		let (_, image) = EpubProcessor::get_sanitized_resource_by_path(
+			&path,
+			root,
+			PathBuf::from(resource.as_ref()),
+			BASE_URL,
+		)
+		.unwrap();
+		assert_eq!(image, b"not really a png");
+	}
+
 	#[test]
 	fn test_get_cover_first_sorted_image() {
 		let resources = HashMap::from([
@@ -530,6 +1763,214 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn test_get_cover_from_cover_image_property() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_cover_epub(
+			dir.path(),
+			r#"<meta name="cover" content="decoy"/>"#,
+			r#"<item id="front" href="Images/front.webp" media-type="image/webp" properties="cover-image"/>"#,
+			"",
+			&[("Images/front.webp", b"webp cover")],
+		);
+
+		let (content_type, cover) = EpubProcessor::get_cover(&path).unwrap();
+		assert_eq!(content_type, ContentType::WEBP);
+		assert_eq!(cover, b"webp cover");
+	}
+
+	#[test]
+	fn test_get_cover_from_meta() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_cover_epub(
+			dir.path(),
+			r#"<meta name="cover" content="art"/>"#,
+			r#"<item id="art" href="Images/art.gif" media-type="image/gif"/>"#,
+			"",
+			&[("Images/art.gif", b"gif cover")],
+		);
+
+		let (content_type, cover) = EpubProcessor::get_cover(&path).unwrap();
+		assert_eq!(content_type, ContentType::GIF);
+		assert_eq!(cover, b"gif cover");
+	}
+
+	#[test]
+	fn test_get_cover_from_guide_cover_page() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_cover_epub(
+			dir.path(),
+			"",
+			r#"<item id="title" href="Text/title%20page.xhtml" media-type="application/xhtml+xml"/>
+		<item id="front" href="Images/front.jpg" media-type="image/jpeg"/>"#,
+			r#"<guide><reference type="cover" title="Cover" href="Text/title%20page.xhtml"/></guide>"#,
+			&[
+				(
+					"Text/title page.xhtml",
+					br#"<html xmlns="http://www.w3.org/1999/xhtml"><body><div><img src="../Images/front.jpg" alt="Cover"/></div></body></html>"#,
+				),
+				("Images/front.jpg", b"jpeg cover"),
+			],
+		);
+
+		let (content_type, cover) = EpubProcessor::get_cover(&path).unwrap();
+		assert_eq!(content_type, ContentType::JPEG);
+		assert_eq!(cover, b"jpeg cover");
+	}
+
+	#[test]
+	fn test_get_cover_from_landmarks_svg_cover_page() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_cover_epub(
+			dir.path(),
+			"",
+			r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+		<item id="titlepage" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+		<item id="front" href="Images/front.jpg" media-type="image/jpeg"/>"#,
+			"",
+			&[
+				(
+					"nav.xhtml",
+					br#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><body>
+<nav epub:type="toc"><ol><li><a href="Text/chapter.xhtml">Chapter</a></li></ol></nav>
+<nav epub:type="landmarks"><ol><li><a epub:type="cover" href="Text/cover.xhtml#start">Cover</a></li></ol></nav>
+</body></html>"#,
+				),
+				(
+					"Text/cover.xhtml",
+					br#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 600 800"><image width="600" height="800" xlink:href="../Images/front.jpg"/></svg>
+</body></html>"#,
+				),
+				("Images/front.jpg", b"jpeg cover"),
+			],
+		);
+
+		let (content_type, cover) = EpubProcessor::get_cover(&path).unwrap();
+		assert_eq!(content_type, ContentType::JPEG);
+		assert_eq!(cover, b"jpeg cover");
+	}
+
+	#[test]
+	fn test_get_cover_rasterizes_svg() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_cover_epub(
+			dir.path(),
+			"",
+			r#"<item id="front" href="Images/front.svg" media-type="image/svg+xml" properties="cover-image"/>"#,
+			"",
+			&[(
+				"Images/front.svg",
+				br##"<svg xmlns="http://www.w3.org/2000/svg" width="600" height="800" viewBox="0 0 600 800"><rect width="600" height="800" fill="#336699"/></svg>"##,
+			)],
+		);
+
+		let (content_type, cover) = EpubProcessor::get_cover(&path).unwrap();
+		assert_eq!(content_type, ContentType::PNG);
+		let image = image::load_from_memory(&cover).unwrap();
+		assert_eq!((image.width(), image.height()), (1200, 1600));
+	}
+
+	#[test]
+	fn test_rasterize_wide_svg() {
+		let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="8000" height="100" viewBox="0 0 8000 100"><rect width="8000" height="100" fill="#336699"/><text x="10" y="80" font-size="60">Title</text></svg>"##;
+
+		let cover = rasterize_svg(svg).unwrap();
+		let image = image::load_from_memory(&cover).unwrap();
+		assert_eq!((image.width(), image.height()), (1600, 20));
+	}
+
+	#[test]
+	fn test_rasterize_svg_ignores_file_images() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let image_path = dir.path().join("secret.png");
+		image::RgbaImage::from_pixel(10, 10, image::Rgba([255, 0, 0, 255]))
+			.save(&image_path)
+			.unwrap();
+		let svg = format!(
+			r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="10" height="10"><image width="10" height="10" xlink:href="{}"/></svg>"#,
+			image_path.display()
+		);
+
+		let cover = rasterize_svg(svg.as_bytes()).unwrap();
+		let image = image::load_from_memory(&cover).unwrap().to_rgba8();
+		assert!(image.pixels().all(|pixel| pixel[3] == 0));
+	}
+
+	#[test]
+	fn test_get_cover_from_first_page() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_test_epub(
+			dir.path(),
+			"",
+			r#"<manifest>
+		<item id="chapter" href="Text/chapter.xhtml" media-type="application/xhtml+xml"/>
+		<item id="a_map" href="Images/map.png" media-type="image/png"/>
+		<item id="z_front" href="Images/front.png" media-type="image/png"/>
+	</manifest>
+	<spine>
+		<itemref idref="chapter"/>
+	</spine>"#,
+			&[
+				(
+					"Text/chapter.xhtml",
+					br#"<html xmlns="http://www.w3.org/1999/xhtml"><body><img src="../Images/front.png"/><p>Once upon a time</p></body></html>"#,
+				),
+				("Images/map.png", b"map"),
+				("Images/front.png", b"png cover"),
+			],
+		);
+
+		let (content_type, cover) = EpubProcessor::get_cover(&path).unwrap();
+		assert_eq!(content_type, ContentType::PNG);
+		assert_eq!(cover, b"png cover");
+	}
+
+	#[test]
+	fn test_get_cover_falls_back_to_named_image() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_cover_epub(
+			dir.path(),
+			r#"<meta name="cover" content="missing"/>"#,
+			"",
+			"",
+			&[],
+		);
+
+		let (content_type, cover) = EpubProcessor::get_cover(&path).unwrap();
+		assert_eq!(content_type, ContentType::PNG);
+		assert_eq!(cover, b"decoy");
+	}
+
+	#[test]
+	fn test_verify() {
+		let path = get_test_epub_path();
+
+		assert!(EpubProcessor::verify(&path, &StumpConfig::debug()).is_ok());
+	}
+
+	#[test]
+	fn test_verify_missing_manifest_resource() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = write_test_epub(
+			dir.path(),
+			"",
+			r#"<manifest>
+		<item id="chapter" href="Text/chapter.xhtml" media-type="application/xhtml+xml"/>
+		<item id="map" href="Images/map.png" media-type="image/png"/>
+	</manifest>
+	<spine>
+		<itemref idref="chapter"/>
+	</spine>"#,
+			&[("Text/chapter.xhtml", COVER_CHAPTER)],
+		);
+
+		let result = EpubProcessor::verify(&path, &StumpConfig::debug());
+		assert!(
+			matches!(result, Err(FileError::IntegrityError(reason)) if reason.contains("map.png"))
+		);
+	}
+
 	#[test]
 	fn test_get_resource_by_id() {
 		let path = get_test_epub_path();
@@ -682,6 +2123,10 @@ mod tests {
 		let path = PathBuf::from("chapters/chapter1/../../Styles/style.css");
 		let result = normalize_resource_path(path, "OEBPS");
 		assert_eq!(result, PathBuf::from("OEBPS/Styles/style.css"));
+
+		let path = PathBuf::from("cover.jpg");
+		let result = normalize_resource_path(path, EPUB_TOP_LEVEL_ROOT);
+		assert_eq!(result, PathBuf::from("cover.jpg"));
 	}
 
 	#[test]
@@ -723,8 +2168,72 @@ mod tests {
 	fn test_get_page_content_types() {
 		let path = get_test_epub_path();
 
-		let cover = EpubProcessor::get_page_content_types(&path, vec![1]);
-		assert!(cover.is_ok());
+		let content_types = EpubProcessor::get_page_content_types(&path, vec![1]);
+		assert!(content_types.is_ok());
+	}
+
+	#[test]
+	fn test_get_positions() {
+		let path = get_test_epub_path();
+		let config = StumpConfig::debug();
+
+		let positions = EpubProcessor::get_positions(&path).unwrap();
+		assert!(positions.total > 0);
+		assert_eq!(positions.positions.len(), positions.total as usize);
+		assert_eq!(
+			EpubProcessor::get_page_count(&path, &config).unwrap(),
+			positions.total
+		);
+
+		let last = positions.get(positions.total).unwrap();
+		assert!(last.locations.total_progression < 1.0);
+	}
+
+	#[test]
+	fn test_get_page_by_position() {
+		let path = get_test_epub_path();
+		let config = StumpConfig::debug();
+
+		let positions = EpubProcessor::get_positions(&path).unwrap();
+		let page = EpubProcessor::get_page(&path, positions.total, &config);
+		assert!(page.is_ok());
+
+		let out_of_bounds = EpubProcessor::get_page(&path, positions.total + 1, &config);
+		assert!(out_of_bounds.is_err());
+	}
+
+	#[test]
+	fn test_positions_count_bytes() {
+		let dir = tempfile::TempDir::new().unwrap();
+		// Fewer than 1024 characters, but more than 1024 bytes
+		let chapter = format!("<html><body><p>{}</p></body></html>", "é".repeat(600));
+		let path = write_test_epub(
+			dir.path(),
+			"",
+			r#"<manifest>
+		<item id="one" href="Text/one.xhtml" media-type="application/xhtml+xml"/>
+		<item id="two" href="Text/two.xhtml" media-type="application/xhtml+xml"/>
+	</manifest>
+	<spine>
+		<itemref idref="one"/>
+		<itemref idref="two"/>
+	</spine>"#,
+			&[
+				("Text/one.xhtml", chapter.as_bytes()),
+				("Text/two.xhtml", b"<html><body><p>Two</p></body></html>"),
+			],
+		);
+
+		let positions = EpubProcessor::get_positions(&path).unwrap();
+		assert_eq!(positions.total, 3);
+		assert_eq!(positions.get(3).unwrap().href, "OEBPS/Text/two.xhtml");
+
+		let (_, content) =
+			EpubProcessor::get_page_from_positions(&path, 3, &positions).unwrap();
+		assert!(String::from_utf8_lossy(&content).contains("Two"));
+		let (_, content) =
+			EpubProcessor::get_page_from_positions(&path, 2, &positions).unwrap();
+		assert!(String::from_utf8_lossy(&content).contains("é"));
 	}
 
 	#[test]
@@ -1,12 +1,19 @@
 use std::{
-	collections::HashMap,
+	collections::{HashMap, VecDeque},
 	io::Cursor,
 	num::TryFromIntError,
 	path::{Path, PathBuf},
+	sync::{LazyLock, Mutex},
+	time::SystemTime,
 };
 
+use image::DynamicImage;
 use pdf::{file::FileOptions, object::ParseOptions};
-use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
+use pdfium_render::prelude::{PdfAction, PdfBookmark, PdfRenderConfig, Pdfium};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+use webp::Encoder;
 
 use crate::{
 	config::StumpConfig,
@@ -21,8 +28,208 @@ use crate::{
 		},
 		ContentType, FileParts, PathUtils, ProcessedFileHashes,
 	},
+	prisma::{library, library_config, media, series, PrismaClient},
+	CoreError, CoreResult,
 };
 
+/// The resolution PDF pages are rendered at when a library doesn't configure one
+pub const DEFAULT_PDF_RENDER_DPI: u32 = 150;
+const MIN_PDF_RENDER_DPI: u32 = 36;
+const MAX_PDF_RENDER_DPI: u32 = 600;
+/// The smallest maximum dimension a library may configure, so pages stay legible
+const MIN_PDF_RENDER_DIMENSION: u32 = 256;
+/// The largest width or height of a rendered page when a library doesn't configure one
+pub const DEFAULT_PDF_RENDER_DIMENSION: u32 = 4096;
+/// The largest maximum dimension a library may configure, which bounds the memory a single
+/// rendered page can take even at the highest resolution
+const MAX_PDF_RENDER_DIMENSION: u32 = 8192;
+/// The resolution of PDF user space, which page sizes are measured in
+const PDF_POINTS_PER_INCH: f32 = 72.0;
+/// How deep the outline of a PDF is read, to guard against malformed outlines which nest
+/// back into themselves
+const MAX_PDF_OUTLINE_DEPTH: usize = 16;
+/// The most items read from the outline of a PDF. Together with [MAX_PDF_OUTLINE_DEPTH], this
+/// guards against malformed outlines whose siblings loop back around
+const MAX_PDF_OUTLINE_ITEMS: usize = 4096;
+/// The most bytes of rendered pages kept in memory, across all PDFs
+const PDF_PAGE_CACHE_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+static PDF_PAGE_CACHE: LazyLock<Mutex<PdfPageCache>> =
+	LazyLock::new(|| Mutex::new(PdfPageCache::new(PDF_PAGE_CACHE_MAX_BYTES)));
+
+/// Options for rendering the pages of PDFs to images, which are configured per library
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize, Type, ToSchema)]
+pub struct PdfRenderOptions {
+	/// The format pages are rendered to. Defaults to PNG
+	#[serde(default = "default_pdf_render_format")]
+	pub format: ImageFormat,
+	/// The resolution pages are rendered at, in dots per inch. Defaults to 150
+	#[serde(default = "default_pdf_render_dpi")]
+	pub dpi: u32,
+	/// The largest width or height of a rendered page, in pixels. Pages which would be larger
+	/// at the configured resolution are scaled down to fit. Defaults to 4096
+	#[serde(default = "default_pdf_render_dimension")]
+	pub max_dimension: Option<u32>,
+}
+
+fn default_pdf_render_format() -> ImageFormat {
+	ImageFormat::Png
+}
+
+fn default_pdf_render_dpi() -> u32 {
+	DEFAULT_PDF_RENDER_DPI
+}
+
+fn default_pdf_render_dimension() -> Option<u32> {
+	Some(DEFAULT_PDF_RENDER_DIMENSION)
+}
+
+impl Default for PdfRenderOptions {
+	fn default() -> Self {
+		Self {
+			format: default_pdf_render_format(),
+			dpi: default_pdf_render_dpi(),
+			max_dimension: default_pdf_render_dimension(),
+		}
+	}
+}
+
+impl PdfRenderOptions {
+	/// Get the render options of the library the book with the given ID belongs to. Returns
+	/// `None` when the book isn't a PDF, and the default options when the library doesn't
+	/// configure any.
+	pub async fn for_book(
+		client: &PrismaClient,
+		id: &str,
+		path: &str,
+	) -> CoreResult<Option<Self>> {
+		if ContentType::from_file(path) != ContentType::PDF {
+			return Ok(None);
+		}
+
+		let config = client
+			.library_config()
+			.find_first(vec![library_config::library::is(vec![
+				library::series::some(vec![series::media::some(vec![
+					media::id::equals(id.to_string()),
+				])]),
+			])])
+			.exec()
+			.await?;
+
+		Ok(Some(
+			config
+				.and_then(|config| config.pdf_render_config)
+				.and_then(|options| Self::try_from(options).ok())
+				.unwrap_or_default(),
+		))
+	}
+
+	/// Validate that the resolution and maximum dimension are within reasonable bounds
+	pub fn validate(&self) -> CoreResult<()> {
+		if !(MIN_PDF_RENDER_DPI..=MAX_PDF_RENDER_DPI).contains(&self.dpi) {
+			return Err(CoreError::BadRequest(format!(
+				"The PDF render DPI must be between {MIN_PDF_RENDER_DPI} and {MAX_PDF_RENDER_DPI}"
+			)));
+		}
+
+		match self.max_dimension {
+			Some(max_dimension)
+				if !(MIN_PDF_RENDER_DIMENSION..=MAX_PDF_RENDER_DIMENSION)
+					.contains(&max_dimension) =>
+			{
+				Err(CoreError::BadRequest(format!(
+					"The maximum PDF render dimension must be between {MIN_PDF_RENDER_DIMENSION} and {MAX_PDF_RENDER_DIMENSION} pixels"
+				)))
+			},
+			_ => Ok(()),
+		}
+	}
+
+	/// The largest width or height of a rendered page. This is always bounded, even for
+	/// options which were stored without a maximum dimension or before it was validated.
+	pub fn effective_max_dimension(&self) -> u32 {
+		self.max_dimension
+			.unwrap_or(DEFAULT_PDF_RENDER_DIMENSION)
+			.clamp(MIN_PDF_RENDER_DIMENSION, MAX_PDF_RENDER_DIMENSION)
+	}
+
+	/// Serialize the options to a byte vector, which gets dumped into the database
+	pub fn as_bytes(&self) -> CoreResult<Vec<u8>> {
+		serde_json::to_vec(self).map_err(|error| {
+			tracing::error!(?error, "Failed to serialize PDF render options");
+			error.into()
+		})
+	}
+
+	/// The content type of pages rendered with these options
+	pub fn content_type(&self) -> ContentType {
+		ContentType::from(self.format.clone())
+	}
+
+	fn render_config(&self) -> PdfRenderConfig {
+		let dpi = self.dpi.clamp(MIN_PDF_RENDER_DPI, MAX_PDF_RENDER_DPI);
+		let max_dimension = self.effective_max_dimension() as i32;
+
+		PdfRenderConfig::new()
+			.scale_page_by_factor(dpi as f32 / PDF_POINTS_PER_INCH)
+			.set_maximum_width(max_dimension)
+			.set_maximum_height(max_dimension)
+	}
+
+	fn encode(&self, image: DynamicImage) -> Result<Vec<u8>, FileError> {
+		match self.format {
+			// The image crate can only encode lossless WebP, which is larger than the PNG
+			ImageFormat::Webp => {
+				let encoder = Encoder::from_image(&image)
+					.map_err(|err| FileError::WebpEncodeError(err.to_string()))?;
+				Ok(encoder.encode(90f32).to_vec())
+			},
+			ImageFormat::Jpeg => {
+				// JPEG doesn't support an alpha channel
+				let image = DynamicImage::from(image.into_rgb8());
+				let mut buffer = Cursor::new(vec![]);
+				image.write_to(&mut buffer, image::ImageFormat::Jpeg)?;
+				Ok(buffer.into_inner())
+			},
+			ImageFormat::Png => {
+				let mut buffer = Cursor::new(vec![]);
+				image.write_to(&mut buffer, image::ImageFormat::Png)?;
+				Ok(buffer.into_inner())
+			},
+		}
+	}
+}
+
+impl TryFrom<Vec<u8>> for PdfRenderOptions {
+	type Error = CoreError;
+
+	fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+		serde_json::from_slice(&value).map_err(|error| {
+			tracing::error!(?error, "Failed to deserialize PDF render options");
+			error.into()
+		})
+	}
+}
+
+/// An entry in the outline (bookmarks) of a PDF
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+pub struct PdfOutlineItem {
+	pub title: String,
+	/// The page the entry links to, starting from 1. Entries which only group other entries,
+	/// or which link outside of the document, don't have a page
+	pub page: Option<i32>,
+	#[schema(no_recursion)]
+	pub children: Vec<PdfOutlineItem>,
+}
+
+/// The text extracted from a page of a PDF
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+pub struct PdfPageText {
+	pub page: i32,
+	pub text: String,
+}
+
 /// A file processor for PDF files.
 pub struct PdfProcessor;
 
@@ -118,45 +325,14 @@ impl FileProcessor for PdfProcessor {
 		})
 	}
 
-	// TODO: The decision to use PNG should be a configuration option
+	/// Render a page with the default [`PdfRenderOptions`]. Use [`PdfProcessor::render_page`]
+	/// to render it with the options of its library
 	fn get_page(
 		path: &str,
 		page: i32,
 		config: &StumpConfig,
 	) -> Result<(ContentType, Vec<u8>), FileError> {
-		let pdfium = PdfProcessor::renderer(&config.pdfium_path)?;
-
-		let document = pdfium.load_pdf_from_file(path, None)?;
-		let document_page = document.pages().get((page - 1).try_into().map_err(
-			|e: TryFromIntError| FileError::PdfProcessingError(e.to_string()),
-		)?)?;
-
-		let render_config = PdfRenderConfig::new();
-
-		let bitmap = document_page.render_with_config(&render_config)?;
-		let dyn_image = bitmap.as_image();
-
-		if let Some(image) = dyn_image.as_rgba8() {
-			let mut buffer = Cursor::new(vec![]);
-			image
-				.write_to(&mut buffer, image::ImageFormat::Png)
-				.map_err(|e| {
-					tracing::error!(error = ?e, "Failed to write image to buffer");
-					FileError::PdfProcessingError(String::from(
-						"An image could not be rendered from the PDF page",
-					))
-				})?;
-			Ok((ContentType::PNG, buffer.into_inner()))
-		} else {
-			tracing::warn!(
-				path,
-				page,
-				"An image could not be rendered from the PDF page"
-			);
-			Err(FileError::PdfProcessingError(String::from(
-				"An image could not be rendered from the PDF page",
-			)))
-		}
+		PdfProcessor::render_page(path, page, &PdfRenderOptions::default(), config)
 	}
 
 	fn get_page_count(path: &str, config: &StumpConfig) -> Result<i32, FileError> {
@@ -166,16 +342,39 @@ impl FileProcessor for PdfProcessor {
 		Ok(document.pages().len() as i32)
 	}
 
+	/// Get the content types of pages rendered with the default [`PdfRenderOptions`]. Use
+	/// [`PdfRenderOptions::content_type`] for the pages of a library with its own options
 	fn get_page_content_types(
 		_: &str,
 		pages: Vec<i32>,
 	) -> Result<HashMap<i32, ContentType>, FileError> {
-		// Lol this is sorta funny, but right now all PDFProcessor::get_page calls convert the buffer
-		// to PNG format. So, we'll just return that here.
-		Ok(pages
-			.into_iter()
-			.map(|page| (page, ContentType::PNG))
-			.collect())
+		// All pages are rendered to the same format, so there is no need to open the file
+		let content_type = PdfRenderOptions::default().content_type();
+		Ok(pages.into_iter().map(|page| (page, content_type)).collect())
+	}
+
+	/// Parses the document and resolves every page in its page tree. This doesn't need
+	/// PDFium, so it works even when PDF rendering isn't configured
+	fn verify(path: &str, _: &StumpConfig) -> Result<(), FileError> {
+		let file = FileOptions::cached()
+			.parse_options(ParseOptions::tolerant())
+			.open(path)?;
+
+		let mut pages = 0;
+		for (index, page) in file.pages().enumerate() {
+			page.map_err(|e| {
+				FileError::IntegrityError(format!("Page {} is invalid: {e}", index + 1))
+			})?;
+			pages += 1;
+		}
+
+		if pages == 0 {
+			return Err(FileError::IntegrityError(
+				"The PDF has no pages".to_string(),
+			));
+		}
+
+		Ok(())
 	}
 }
 
@@ -198,6 +397,222 @@ impl PdfProcessor {
 				.map_err(|_| FileError::PdfConfigurationError)
 		}
 	}
+
+	/// Render a page (starting from 1) to an image with the given options. Rendered pages are
+	/// cached in memory, so paging back and forth through a PDF doesn't render the same pages
+	/// again.
+	pub fn render_page(
+		path: &str,
+		page: i32,
+		options: &PdfRenderOptions,
+		config: &StumpConfig,
+	) -> Result<(ContentType, Vec<u8>), FileError> {
+		let content_type = options.content_type();
+		let cache_key = PdfPageCacheKey::new(path, page, options);
+		if let Some(buffer) = PdfPageCache::get_shared(&cache_key) {
+			tracing::trace!(path, page, "Rendered PDF page found in cache");
+			return Ok((content_type, buffer));
+		}
+
+		let pdfium = PdfProcessor::renderer(&config.pdfium_path)?;
+		let document = pdfium.load_pdf_from_file(path, None)?;
+		let document_page = document.pages().get((page - 1).try_into().map_err(
+			|e: TryFromIntError| FileError::PdfProcessingError(e.to_string()),
+		)?)?;
+
+		let bitmap = document_page.render_with_config(&options.render_config())?;
+		let buffer = options.encode(bitmap.as_image()).map_err(|e| {
+			tracing::error!(error = ?e, path, page, "Failed to encode rendered PDF page");
+			FileError::PdfProcessingError(String::from(
+				"An image could not be rendered from the PDF page",
+			))
+		})?;
+
+		PdfPageCache::insert_shared(cache_key, buffer.clone());
+
+		Ok((content_type, buffer))
+	}
+
+	/// Extract the text of a page (starting from 1), e.g. for search or screen readers. Pages
+	/// which are scanned images have no text.
+	pub fn get_page_text(
+		path: &str,
+		page: i32,
+		config: &StumpConfig,
+	) -> Result<PdfPageText, FileError> {
+		let pdfium = PdfProcessor::renderer(&config.pdfium_path)?;
+		let document = pdfium.load_pdf_from_file(path, None)?;
+		let document_page = document.pages().get((page - 1).try_into().map_err(
+			|e: TryFromIntError| FileError::PdfProcessingError(e.to_string()),
+		)?)?;
+
+		Ok(PdfPageText {
+			page,
+			text: document_page.text()?.all(),
+		})
+	}
+
+	/// Get the outline (bookmarks) of a PDF, which serves as its table of contents. PDFs
+	/// without an outline have an empty one.
+	pub fn get_outline(
+		path: &str,
+		config: &StumpConfig,
+	) -> Result<Vec<PdfOutlineItem>, FileError> {
+		let pdfium = PdfProcessor::renderer(&config.pdfium_path)?;
+		let document = pdfium.load_pdf_from_file(path, None)?;
+
+		Ok(document
+			.bookmarks()
+			.root()
+			.map(|root| {
+				let mut remaining = MAX_PDF_OUTLINE_ITEMS;
+				Self::read_outline_level(root, 0, &mut remaining)
+			})
+			.unwrap_or_default())
+	}
+
+	/// Read a bookmark and its siblings, along with their children, until `remaining` items
+	/// have been read
+	fn read_outline_level(
+		first: PdfBookmark,
+		depth: usize,
+		remaining: &mut usize,
+	) -> Vec<PdfOutlineItem> {
+		let mut items = vec![];
+		let mut next = Some(first);
+		while let Some(bookmark) = next {
+			if *remaining == 0 {
+				tracing::warn!(
+					max_items = MAX_PDF_OUTLINE_ITEMS,
+					"PDF outline has too many items, ignoring the rest"
+				);
+				break;
+			}
+			*remaining -= 1;
+
+			let destination =
+				bookmark.destination().or_else(|| match bookmark.action() {
+					Some(PdfAction::LocalDestination(action)) => {
+						action.destination().ok()
+					},
+					_ => None,
+				});
+			let page = destination
+				.and_then(|destination| destination.page_index().ok())
+				.map(|index| index as i32 + 1);
+			let children = match bookmark.first_child() {
+				Some(child) if depth < MAX_PDF_OUTLINE_DEPTH => {
+					Self::read_outline_level(child, depth + 1, remaining)
+				},
+				_ => vec![],
+			};
+
+			items.push(PdfOutlineItem {
+				title: bookmark.title().unwrap_or_default(),
+				page,
+				children,
+			});
+			next = bookmark.next_sibling();
+		}
+
+		items
+	}
+}
+
+/// Identifies a rendered page in the [`PdfPageCache`]. The modification time and size of the
+/// file are part of the key, so pages of a file which changed aren't served from the cache.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PdfPageCacheKey {
+	path: String,
+	modified: Option<SystemTime>,
+	size: u64,
+	page: i32,
+	options: PdfRenderOptions,
+}
+
+impl PdfPageCacheKey {
+	fn new(path: &str, page: i32, options: &PdfRenderOptions) -> Self {
+		let metadata = std::fs::metadata(path).ok();
+		Self {
+			path: path.to_string(),
+			modified: metadata
+				.as_ref()
+				.and_then(|metadata| metadata.modified().ok()),
+			size: metadata.map_or(0, |metadata| metadata.len()),
+			page,
+			options: options.clone(),
+		}
+	}
+}
+
+/// A cache of rendered pages which evicts the least recently used pages once it holds more than
+/// its maximum number of bytes
+#[derive(Debug)]
+struct PdfPageCache {
+	max_bytes: usize,
+	bytes: usize,
+	pages: HashMap<PdfPageCacheKey, Vec<u8>>,
+	/// The keys of the cached pages, from least to most recently used
+	order: VecDeque<PdfPageCacheKey>,
+}
+
+impl PdfPageCache {
+	fn new(max_bytes: usize) -> Self {
+		Self {
+			max_bytes,
+			bytes: 0,
+			pages: HashMap::new(),
+			order: VecDeque::new(),
+		}
+	}
+
+	fn get_shared(key: &PdfPageCacheKey) -> Option<Vec<u8>> {
+		PDF_PAGE_CACHE.lock().ok()?.get(key)
+	}
+
+	fn insert_shared(key: PdfPageCacheKey, buffer: Vec<u8>) {
+		if let Ok(mut cache) = PDF_PAGE_CACHE.lock() {
+			cache.insert(key, buffer);
+		}
+	}
+
+	fn get(&mut self, key: &PdfPageCacheKey) -> Option<Vec<u8>> {
+		let buffer = self.pages.get(key)?.clone();
+		self.touch(key);
+		Some(buffer)
+	}
+
+	fn insert(&mut self, key: PdfPageCacheKey, buffer: Vec<u8>) {
+		if buffer.len() > self.max_bytes {
+			return;
+		}
+
+		if let Some(previous) = self.pages.remove(&key) {
+			self.bytes -= previous.len();
+			self.order.retain(|existing| existing != &key);
+		}
+		self.bytes += buffer.len();
+		self.order.push_back(key.clone());
+		self.pages.insert(key, buffer);
+
+		while self.bytes > self.max_bytes {
+			let Some(evicted) = self.order.pop_front() else {
+				break;
+			};
+			if let Some(buffer) = self.pages.remove(&evicted) {
+				self.bytes -= buffer.len();
+			}
+		}
+	}
+
+	/// Mark a page as the most recently used
+	fn touch(&mut self, key: &PdfPageCacheKey) {
+		if let Some(position) = self.order.iter().position(|existing| existing == key) {
+			if let Some(key) = self.order.remove(position) {
+				self.order.push_back(key);
+			}
+		}
+	}
 }
 
 impl FileConverter for PdfProcessor {
@@ -212,7 +627,7 @@ impl FileConverter for PdfProcessor {
 		let document = pdfium.load_pdf_from_file(path, None)?;
 		let iter = document.pages().iter();
 
-		let render_config = PdfRenderConfig::new();
+		let render_config = PdfRenderOptions::default().render_config();
 
 		let output_format = format
 			.clone()
@@ -325,4 +740,150 @@ mod tests {
 		let content_types = PdfProcessor::get_page_content_types(&path, vec![1]);
 		assert!(content_types.is_ok());
 	}
+
+	#[test]
+	fn test_verify() {
+		let path = get_test_pdf_path();
+
+		assert!(PdfProcessor::verify(&path, &StumpConfig::debug()).is_ok());
+	}
+
+	#[test]
+	fn test_verify_truncated_pdf() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = dir.path().join("truncated.pdf");
+		let bytes = std::fs::read(get_test_pdf_path()).unwrap();
+		std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+
+		let result = PdfProcessor::verify(&path.to_string_lossy(), &StumpConfig::debug());
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn test_render_options_defaults() {
+		let options: PdfRenderOptions = serde_json::from_str("{}").unwrap();
+		assert_eq!(options, PdfRenderOptions::default());
+		assert_eq!(options.content_type(), ContentType::PNG);
+		assert_eq!(options.dpi, DEFAULT_PDF_RENDER_DPI);
+		assert_eq!(options.max_dimension, Some(DEFAULT_PDF_RENDER_DIMENSION));
+
+		let options: PdfRenderOptions =
+			serde_json::from_str(r#"{ "format": "Webp", "max_dimension": 2048 }"#)
+				.unwrap();
+		assert_eq!(options.content_type(), ContentType::WEBP);
+		assert_eq!(options.dpi, DEFAULT_PDF_RENDER_DPI);
+		assert_eq!(options.max_dimension, Some(2048));
+	}
+
+	#[test]
+	fn test_render_options_round_trip() {
+		let options = PdfRenderOptions {
+			format: ImageFormat::Jpeg,
+			dpi: 300,
+			max_dimension: Some(4096),
+		};
+		let bytes = options.as_bytes().unwrap();
+		assert_eq!(PdfRenderOptions::try_from(bytes).unwrap(), options);
+	}
+
+	#[test]
+	fn test_validate_render_options() {
+		assert!(PdfRenderOptions::default().validate().is_ok());
+
+		let too_low = PdfRenderOptions {
+			dpi: 10,
+			..Default::default()
+		};
+		assert!(too_low.validate().is_err());
+
+		let too_high = PdfRenderOptions {
+			dpi: 1200,
+			..Default::default()
+		};
+		assert!(too_high.validate().is_err());
+
+		let too_small = PdfRenderOptions {
+			max_dimension: Some(100),
+			..Default::default()
+		};
+		assert!(too_small.validate().is_err());
+
+		let too_large = PdfRenderOptions {
+			max_dimension: Some(20_000),
+			..Default::default()
+		};
+		assert!(too_large.validate().is_err());
+	}
+
+	#[test]
+	fn test_effective_max_dimension() {
+		let unbounded = PdfRenderOptions {
+			max_dimension: None,
+			..Default::default()
+		};
+		assert_eq!(
+			unbounded.effective_max_dimension(),
+			DEFAULT_PDF_RENDER_DIMENSION
+		);
+
+		let too_large = PdfRenderOptions {
+			max_dimension: Some(20_000),
+			..Default::default()
+		};
+		assert_eq!(
+			too_large.effective_max_dimension(),
+			MAX_PDF_RENDER_DIMENSION
+		);
+	}
+
+	fn cache_key(page: i32) -> PdfPageCacheKey {
+		PdfPageCacheKey {
+			path: "book.pdf".to_string(),
+			modified: None,
+			size: 0,
+			page,
+			options: PdfRenderOptions::default(),
+		}
+	}
+
+	#[test]
+	fn test_page_cache_evicts_least_recently_used() {
+		let mut cache = PdfPageCache::new(10);
+		cache.insert(cache_key(1), vec![1; 4]);
+		cache.insert(cache_key(2), vec![2; 4]);
+
+		// Page 1 is used again, so page 2 is evicted to make room for page 3
+		assert_eq!(cache.get(&cache_key(1)), Some(vec![1; 4]));
+		cache.insert(cache_key(3), vec![3; 4]);
+
+		assert_eq!(cache.get(&cache_key(2)), None);
+		assert_eq!(cache.get(&cache_key(1)), Some(vec![1; 4]));
+		assert_eq!(cache.get(&cache_key(3)), Some(vec![3; 4]));
+		assert_eq!(cache.bytes, 8);
+	}
+
+	#[test]
+	fn test_page_cache_keys_by_options() {
+		let mut cache = PdfPageCache::new(10);
+		cache.insert(cache_key(1), vec![1; 4]);
+
+		let other_options = PdfPageCacheKey {
+			options: PdfRenderOptions {
+				dpi: 300,
+				..Default::default()
+			},
+			..cache_key(1)
+		};
+		assert_eq!(cache.get(&other_options), None);
+	}
+
+	#[test]
+	fn test_page_cache_skips_oversized_pages() {
+		let mut cache = PdfPageCache::new(10);
+		cache.insert(cache_key(1), vec![1; 4]);
+		cache.insert(cache_key(2), vec![2; 11]);
+
+		assert_eq!(cache.get(&cache_key(2)), None);
+		assert_eq!(cache.get(&cache_key(1)), Some(vec![1; 4]));
+	}
 }
@@ -20,7 +20,7 @@ use crate::{
 			process::{
 				FileConverter, FileProcessor, FileProcessorOptions, ProcessedFile,
 			},
-			utils::metadata_from_buf,
+			utils::{metadata_from_buf, verify_image_header},
 			zip::ZipProcessor,
 		},
 		FileParts, PathUtils, ProcessedFileHashes,
@@ -334,6 +334,41 @@ impl FileProcessor for RarProcessor {
 
 		Ok(content_types)
 	}
+
+	/// Extracts every entry of the archive to memory, which checks it against its CRC, and
+	/// decodes the header of every image
+	fn verify(path: &str, _: &StumpConfig) -> Result<(), FileError> {
+		let mut archive = RarProcessor::open_for_processing(path)?;
+		let mut entries = 0;
+
+		while let Some(header) = archive.read_header()? {
+			let entry = header.entry();
+			if entry.is_directory() {
+				archive = header.skip()?;
+				continue;
+			}
+
+			let name = entry.filename.clone();
+			let (bytes, next) = header.read().map_err(|e| {
+				FileError::IntegrityError(format!(
+					"{} could not be read: {e}",
+					name.display()
+				))
+			})?;
+			if name.is_img() && !name.is_hidden_file() {
+				verify_image_header(&name, &bytes)?;
+			}
+
+			entries += 1;
+			archive = next;
+		}
+
+		if entries == 0 {
+			return Err(FileError::ArchiveEmptyError);
+		}
+
+		Ok(())
+	}
 }
 
 impl FileConverter for RarProcessor {
@@ -458,6 +493,13 @@ mod tests {
 		assert!(content_types.is_ok());
 	}
 
+	#[test]
+	fn test_verify() {
+		let path = get_test_rar_path();
+
+		assert!(RarProcessor::verify(&path, &StumpConfig::debug()).is_ok());
+	}
+
 	#[test]
 	fn test_rar_with_complex_file_tree() {
 		let path = get_test_complex_rar_path();
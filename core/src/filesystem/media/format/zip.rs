@@ -1,4 +1,9 @@
-use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{self, Read},
+	path::PathBuf,
+};
 use tracing::{debug, error, trace};
 
 use crate::{
@@ -10,7 +15,7 @@ use crate::{
 		hash,
 		media::{
 			process::{FileProcessor, FileProcessorOptions, ProcessedFile},
-			utils::{metadata_from_buf, sort_file_names},
+			utils::{metadata_from_buf, sort_file_names, verify_image_header},
 		},
 		FileParts, PathUtils, ProcessedFileHashes,
 	},
@@ -317,6 +322,43 @@ impl FileProcessor for ZipProcessor {
 
 		Ok(content_types)
 	}
+
+	/// Reads every entry of the archive, which checks it against its CRC, and decodes the
+	/// header of every image
+	fn verify(path: &str, _: &StumpConfig) -> Result<(), FileError> {
+		let zip_file = File::open(path)?;
+		let mut archive = zip::ZipArchive::new(zip_file)?;
+
+		if archive.is_empty() {
+			return Err(FileError::ArchiveEmptyError);
+		}
+
+		for i in 0..archive.len() {
+			let mut file = archive.by_index(i)?;
+			if file.is_dir() {
+				continue;
+			}
+
+			let name = PathBuf::from(file.name());
+			let read_error = |e: io::Error| {
+				FileError::IntegrityError(format!(
+					"{} could not be read: {e}",
+					name.display()
+				))
+			};
+
+			if name.is_hidden_file() || !name.naive_content_type().is_image() {
+				io::copy(&mut file, &mut io::sink()).map_err(read_error)?;
+				continue;
+			}
+
+			let mut contents = Vec::new();
+			file.read_to_end(&mut contents).map_err(read_error)?;
+			verify_image_header(&name, &contents)?;
+		}
+
+		Ok(())
+	}
 }
 
 #[cfg(test)]
@@ -449,4 +491,88 @@ mod tests {
 		// See https://github.com/stumpapp/stump/issues/641
 		assert!(processed_file.metadata.is_some());
 	}
+
+	fn png_bytes() -> Vec<u8> {
+		let mut buffer = std::io::Cursor::new(vec![]);
+		image::DynamicImage::new_rgb8(4, 4)
+			.write_to(&mut buffer, image::ImageFormat::Png)
+			.unwrap();
+		buffer.into_inner()
+	}
+
+	/// Writes a CBZ whose entries are stored uncompressed, so tests can corrupt them in place
+	fn write_stored_cbz(dir: &std::path::Path, entries: &[(&str, &[u8])]) -> String {
+		use std::io::Write;
+		use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+		let path = dir.join("book.cbz");
+		let mut writer = ZipWriter::new(File::create(&path).unwrap());
+		let options: FileOptions<()> =
+			FileOptions::default().compression_method(CompressionMethod::Stored);
+		for (name, content) in entries {
+			writer.start_file(*name, options).unwrap();
+			writer.write_all(content).unwrap();
+		}
+		writer.finish().unwrap();
+
+		path.to_string_lossy().to_string()
+	}
+
+	#[test]
+	fn test_verify_cbz() {
+		let config = StumpConfig::debug();
+
+		assert!(ZipProcessor::verify(&get_test_cbz_path(), &config).is_ok());
+		assert!(
+			ZipProcessor::verify(&get_nested_macos_compressed_cbz_path(), &config)
+				.is_ok()
+		);
+	}
+
+	#[test]
+	fn test_verify_undecodable_image() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let png = png_bytes();
+		let path = write_stored_cbz(
+			dir.path(),
+			&[
+				("001.png", &png),
+				("002.png", b"\x89PNG\r\n\x1a\nnot a png"),
+			],
+		);
+
+		let result = ZipProcessor::verify(&path, &StumpConfig::debug());
+		assert!(
+			matches!(result, Err(FileError::IntegrityError(reason)) if reason.contains("002.png"))
+		);
+	}
+
+	#[test]
+	fn test_verify_checksum_mismatch() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let png = png_bytes();
+		let path = write_stored_cbz(dir.path(), &[("001.png", &png)]);
+
+		// Corrupt the last byte of the entry, which leaves the image header intact
+		let mut bytes = std::fs::read(&path).unwrap();
+		let offset = bytes
+			.windows(png.len())
+			.position(|window| window == png.as_slice())
+			.unwrap();
+		bytes[offset + png.len() - 1] ^= 0xFF;
+		std::fs::write(&path, bytes).unwrap();
+
+		let result = ZipProcessor::verify(&path, &StumpConfig::debug());
+		assert!(matches!(result, Err(FileError::IntegrityError(_))));
+	}
+
+	#[test]
+	fn test_verify_not_a_zip() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = dir.path().join("book.cbz");
+		std::fs::write(&path, b"not a zip").unwrap();
+
+		let result = ZipProcessor::verify(&path.to_string_lossy(), &StumpConfig::debug());
+		assert!(result.is_err());
+	}
 }
@@ -1,11 +1,23 @@
 pub mod analyze_media_job;
 mod builder;
+mod convert;
+pub mod duplicate_analysis_job;
+mod filename;
 mod format;
 mod process;
 mod utils;
+pub mod verify_media_job;
 
 pub use crate::filesystem::media::epub::EpubProcessor;
+pub use crate::filesystem::media::pdf::{
+	PdfOutlineItem, PdfPageText, PdfProcessor, PdfRenderOptions,
+};
 pub(crate) use builder::{MediaBuilder, SeriesBuilder};
+pub use convert::{
+	convert_for_device, convert_for_device_async, ConvertedFile, DeviceConversionOptions,
+	DeviceTargetFormat,
+};
+pub use filename::{parse_filename, ParsedFilename};
 pub use format::*;
 pub use process::*;
 pub use utils::is_accepted_cover_name;
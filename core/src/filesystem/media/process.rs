@@ -11,11 +11,16 @@ use tracing::debug;
 
 use crate::{
 	config::StumpConfig,
-	db::entity::{LibraryConfig, MediaMetadata, SeriesMetadata},
+	db::entity::{EpubPositionList, LibraryConfig, MediaMetadata, SeriesMetadata},
 	filesystem::{
-		content_type::ContentType, epub::EpubProcessor, error::FileError,
-		image::ImageFormat, pdf::PdfProcessor,
+		content_type::ContentType,
+		epub::EpubProcessor,
+		error::FileError,
+		image::ImageFormat,
+		pdf::{PdfProcessor, PdfRenderOptions},
 	},
+	prisma::PrismaClient,
+	CoreResult,
 };
 
 use super::{rar::RarProcessor, zip::ZipProcessor};
@@ -109,6 +114,10 @@ pub trait FileProcessor {
 		path: &str,
 		pages: Vec<i32>,
 	) -> Result<HashMap<i32, ContentType>, FileError>;
+
+	/// Verify the integrity of the file, e.g. that every entry of an archive can be read and
+	/// that its images can be decoded. Returns an error describing the first problem found.
+	fn verify(path: &str, config: &StumpConfig) -> Result<(), FileError>;
 }
 
 /// Trait defining a standard API for converting files throughout Stump.
@@ -323,6 +332,30 @@ pub async fn generate_hashes_async(
 	Ok(processed_hashes)
 }
 
+/// Options for reading the pages of a book which come from its record rather than its file
+#[derive(Debug, Clone, Default)]
+pub struct PageOptions {
+	/// The options PDF pages are rendered with, rather than the defaults
+	pub pdf: Option<PdfRenderOptions>,
+	/// The positions list stored for an EPUB, which is otherwise read from the file
+	pub epub_positions: Option<EpubPositionList>,
+}
+
+impl PageOptions {
+	/// Get the page options of the book with the given ID, i.e. the PDF render options of its
+	/// library or its stored EPUB positions
+	pub async fn for_book(
+		client: &PrismaClient,
+		id: &str,
+		path: &str,
+	) -> CoreResult<Self> {
+		Ok(Self {
+			pdf: PdfRenderOptions::for_book(client, id, path).await?,
+			epub_positions: EpubPositionList::stored_for_book(client, id, path).await?,
+		})
+	}
+}
+
 /// A function to extract the bytes of a page from a file in a blocking manner. This will call the
 /// appropriate [`FileProcessor::get_page`] implementation based on the file's mime type, or return an
 /// error if the file type is not supported.
@@ -330,6 +363,18 @@ pub fn get_page(
 	path: &str,
 	page: i32,
 	config: &StumpConfig,
+) -> Result<(ContentType, Vec<u8>), FileError> {
+	get_page_with_options(path, page, &PageOptions::default(), config)
+}
+
+/// A function to extract the bytes of a page from a file in a blocking manner, using the given
+/// [`PageOptions`] of the book (e.g. the render options of its library) rather than the
+/// defaults. See [get_page]
+pub fn get_page_with_options(
+	path: &str,
+	page: i32,
+	options: &PageOptions,
+	config: &StumpConfig,
 ) -> Result<(ContentType, Vec<u8>), FileError> {
 	let mime = ContentType::from_file(path).mime_type();
 
@@ -340,8 +385,16 @@ pub fn get_page(
 		"application/vnd.rar" | "application/vnd.comicbook-rar" => {
 			RarProcessor::get_page(path, page, config)
 		},
-		"application/epub+zip" => EpubProcessor::get_page(path, page, config),
-		"application/pdf" => PdfProcessor::get_page(path, page, config),
+		"application/epub+zip" => match options.epub_positions.as_ref() {
+			Some(positions) => {
+				EpubProcessor::get_page_from_positions(path, page, positions)
+			},
+			None => EpubProcessor::get_page(path, page, config),
+		},
+		"application/pdf" => match options.pdf.as_ref() {
+			Some(options) => PdfProcessor::render_page(path, page, options, config),
+			None => PdfProcessor::get_page(path, page, config),
+		},
 		_ => Err(FileError::UnsupportedFileType(path.to_string())),
 	}
 }
@@ -353,6 +406,18 @@ pub async fn get_page_async(
 	path: impl AsRef<Path>,
 	page: i32,
 	config: &StumpConfig,
+) -> Result<(ContentType, Vec<u8>), FileError> {
+	get_page_with_options_async(path, page, PageOptions::default(), config).await
+}
+
+/// A function to extract the bytes of a page from a file in the context of a spawned, blocking task,
+/// using the given [`PageOptions`] of the book. See [get_page_with_options]
+#[tracing::instrument(err, skip(options), fields(path = %path.as_ref().display()))]
+pub async fn get_page_with_options_async(
+	path: impl AsRef<Path>,
+	page: i32,
+	options: PageOptions,
+	config: &StumpConfig,
 ) -> Result<(ContentType, Vec<u8>), FileError> {
 	let (tx, rx) = oneshot::channel();
 
@@ -361,8 +426,12 @@ pub async fn get_page_async(
 		let config = config.clone();
 
 		move || {
-			let send_result =
-				tx.send(get_page(path.to_str().unwrap_or_default(), page, &config));
+			let send_result = tx.send(get_page_with_options(
+				path.to_str().unwrap_or_default(),
+				page,
+				&options,
+				&config,
+			));
 			tracing::trace!(
 				is_err = send_result.is_err(),
 				"Sending result of sync get_page"
@@ -384,6 +453,35 @@ pub async fn get_page_async(
 	Ok(page_result)
 }
 
+/// A function to extract the bytes of the cover of a file in a blocking manner. For most formats
+/// this is the first page, but the pages of an EPUB are positions in its text, so its cover is
+/// resolved from the package instead.
+pub fn get_cover(
+	path: &str,
+	config: &StumpConfig,
+) -> Result<(ContentType, Vec<u8>), FileError> {
+	if ContentType::from_file(path).is_epub() {
+		EpubProcessor::get_cover(path)
+	} else {
+		get_page(path, 1, config)
+	}
+}
+
+/// A function to extract the bytes of the cover of a file in the context of a spawned, blocking
+/// task. See [get_cover] for how the cover is resolved.
+#[tracing::instrument(err, fields(path = %path.as_ref().display()))]
+pub async fn get_cover_async(
+	path: impl AsRef<Path>,
+	config: &StumpConfig,
+) -> Result<(ContentType, Vec<u8>), FileError> {
+	let path = path.as_ref().to_path_buf();
+	let config = config.clone();
+
+	spawn_blocking(move || get_cover(path.to_str().unwrap_or_default(), &config))
+		.await
+		.map_err(|e| FileError::UnknownError(e.to_string()))?
+}
+
 /// Get the number of pages in a file. This will call the appropriate [`FileProcessor::get_page_count`]
 /// implementation based on the file's mime type, or return an error if the file type is not supported.
 pub fn get_page_count(path: &str, config: &StumpConfig) -> Result<i32, FileError> {
@@ -439,6 +537,38 @@ pub async fn get_page_count_async(
 	Ok(page_count)
 }
 
+/// Verify the integrity of a file. This will call the appropriate [`FileProcessor::verify`]
+/// implementation based on the file's mime type, or return an error if the file type is not supported.
+pub fn verify(path: &str, config: &StumpConfig) -> Result<(), FileError> {
+	let mime = ContentType::from_file(path).mime_type();
+
+	match mime.as_str() {
+		"application/zip" | "application/vnd.comicbook+zip" => {
+			ZipProcessor::verify(path, config)
+		},
+		"application/vnd.rar" | "application/vnd.comicbook-rar" => {
+			RarProcessor::verify(path, config)
+		},
+		"application/epub+zip" => EpubProcessor::verify(path, config),
+		"application/pdf" => PdfProcessor::verify(path, config),
+		_ => Err(FileError::UnsupportedFileType(path.to_string())),
+	}
+}
+
+/// Verify the integrity of a file in the context of a spawned, blocking task. See [verify]
+#[tracing::instrument(err, fields(path = %path.as_ref().display()))]
+pub async fn verify_async(
+	path: impl AsRef<Path>,
+	config: &StumpConfig,
+) -> Result<(), FileError> {
+	let path = path.as_ref().to_path_buf();
+	let config = config.clone();
+
+	spawn_blocking(move || verify(path.to_str().unwrap_or_default(), &config))
+		.await
+		.map_err(|e| FileError::UnknownError(e.to_string()))?
+}
+
 /// Get the content types of a list of pages of a file. This will call the appropriate
 /// [`FileProcessor::get_page_content_types`] implementation based on the file's mime type, or return an
 /// error if the file type is not supported.
@@ -489,6 +619,28 @@ fn get_content_type_for_page_sync(
 	Ok(result.get(&page).cloned().unwrap_or(ContentType::UNKNOWN))
 }
 
+/// Get the content type of the cover of a file. See [get_cover] for how the cover is resolved.
+pub fn get_cover_content_type(path: &str) -> Result<ContentType, FileError> {
+	if ContentType::from_file(path).is_epub() {
+		EpubProcessor::get_cover(path).map(|(content_type, _)| content_type)
+	} else {
+		get_content_type_for_page_sync(path, 1)
+	}
+}
+
+/// Get the content type of the cover of a file in the context of a spawned, blocking task. See
+/// [get_cover] for how the cover is resolved.
+#[tracing::instrument(err, fields(path = %path.as_ref().display()))]
+pub async fn get_cover_content_type_async(
+	path: impl AsRef<Path>,
+) -> Result<ContentType, FileError> {
+	let path = path.as_ref().to_path_buf();
+
+	spawn_blocking(move || get_cover_content_type(path.to_str().unwrap_or_default()))
+		.await
+		.map_err(|e| FileError::UnknownError(e.to_string()))?
+}
+
 /// Get the content type for a specific page of a file in the context of a spawned, blocking task.
 /// This will call the [get_content_type_for_page_sync] function and send the result back out through
 /// a oneshot channel.
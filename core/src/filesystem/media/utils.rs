@@ -1,6 +1,9 @@
+use std::{io::Cursor, path::Path};
+
+use image::{ImageError, ImageReader};
 use tracing::error;
 
-use crate::db::entity::MediaMetadata;
+use crate::{db::entity::MediaMetadata, filesystem::error::FileError};
 
 pub fn is_accepted_cover_name(name: &str) -> bool {
 	let cover_file_names = ["cover", "thumbnail", "folder"];
@@ -34,6 +37,32 @@ where
 	alphanumeric_sort::sort_str_slice(file_names);
 }
 
+/// Verify that the bytes of an image entry in a book can be decoded, by reading the
+/// dimensions from its header. Formats the image crate can't decode (e.g. JXL) are not
+/// verified.
+pub(crate) fn verify_image_header(name: &Path, bytes: &[u8]) -> Result<(), FileError> {
+	if bytes.is_empty() {
+		return Err(FileError::IntegrityError(format!(
+			"{} is empty",
+			name.display()
+		)));
+	}
+
+	let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+	if reader.format().is_none() {
+		tracing::trace!(?name, "Skipping verification of unrecognized image format");
+		return Ok(());
+	}
+
+	match reader.into_dimensions() {
+		Ok(_) | Err(ImageError::Unsupported(_)) => Ok(()),
+		Err(e) => Err(FileError::IntegrityError(format!(
+			"{} could not be decoded: {e}",
+			name.display()
+		))),
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -70,6 +99,28 @@ mod tests {
 		assert_eq!(names, expected);
 	}
 
+	#[test]
+	fn test_verify_image_header() {
+		let mut buffer = Cursor::new(vec![]);
+		image::DynamicImage::new_rgb8(4, 4)
+			.write_to(&mut buffer, image::ImageFormat::Png)
+			.unwrap();
+		let png = buffer.into_inner();
+		let name = Path::new("001.png");
+
+		assert!(verify_image_header(name, &png).is_ok());
+		assert!(matches!(
+			verify_image_header(name, &png[..16]),
+			Err(FileError::IntegrityError(_))
+		));
+		assert!(matches!(
+			verify_image_header(name, &[]),
+			Err(FileError::IntegrityError(_))
+		));
+		// Bytes which aren't a known image format can't be verified
+		assert!(verify_image_header(name, b"not an image").is_ok());
+	}
+
 	#[test]
 	fn test_should_parse_incomplete_metadata() {
 		let contents = "<?xml version=\"1.0\"?>\n<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n  <Series>Delete</Series>\n  <Number>1</Number>\n  <Volume>2016</Volume>\n  <Summary>In the near future, where science can implant or remove human memories and the government uses brain scan technology in criminal investigations, a mute girl witnesses a multiple murder and must turn to a handyman for protection from the police and an army of killers. From the Harley Quinn team of writers Jimmy Palmiotti and Justin Grey and artist John Timms, with covers by Amanda Conner.\n\n\nNote: The digital edition (3/2/2016) for this issue was released before the print edition.</Summary>\n  <Notes>Tagged with ComicTagger 1.3.0-alpha.0 using info from Comic Vine on 2021-12-01 20:34:52.  [Issue ID 517895]</Notes>\n  <Year>2016</Year>\n  <Month>03</Month>\n  <Day>31</Day>\n  <Writer>Jimmy Palmiotti, Justin Gray</Writer>\n  <Penciller>John Timms, John Timms</Penciller>\n  <Inker>John Timms, John Timms</Inker>\n  <Colorist>David Curiel, Paul Mounts</Colorist>\n  <Letterer>Bill Tortolini</Letterer>\n  <CoverArtist>Amanda Conner, Paul Mounts</CoverArtist>\n  <Editor>Alex Wald, Joanne Starer</Editor>\n  <Publisher>1First Comics</Publisher>\n  <Web>https://comicvine.gamespot.com/delete-1/4000-517895/</Web>\n  <PageCount>27</PageCount>\n  <ScanInformation>(digital) (Son of Ultron-Empire)</ScanInformation>\n  <Pages>\n    <Page Image=\"0\" ImageSize=\"907332\" Type=\"FrontCover\" />\n    <Page Image=\"1\" ImageSize=\"431378\" />\n    <Page Image=\"2\" ImageSize=\"776720\" />\n    <Page Image=\"3\" ImageSize=\"524902\" />\n    <Page Image=\"4\" ImageSize=\"753942\" />\n    <Page Image=\"5\" ImageSize=\"607990\" />\n    <Page Image=\"6\" ImageSize=\"438880\" />\n    <Page Image=\"7\" ImageSize=\"504806\" />\n    <Page Image=\"8\" ImageSize=\"532746\" />\n    <Page Image=\"9\" ImageSize=\"542816\" />\n    <Page Image=\"10\" ImageSize=\"571650\" />\n    <Page Image=\"11\" ImageSize=\"626656\" />\n    <Page Image=\"12\" ImageSize=\"605810\" />\n    <Page Image=\"13\" ImageSize=\"585234\" />\n    <Page Image=\"14\" ImageSize=\"553270\" />\n    <Page Image=\"15\" ImageSize=\"440568\" />\n    <Page Image=\"16\" ImageSize=\"483816\" />\n    <Page Image=\"17\" ImageSize=\"492922\" />\n    <Page Image=\"18\" ImageSize=\"470748\" />\n    <Page Image=\"19\" ImageSize=\"644256\" />\n    <Page Image=\"20\" ImageSize=\"584142\" />\n    <Page Image=\"21\" ImageSize=\"425322\" />\n    <Page Image=\"22\" ImageSize=\"565166\" />\n    <Page Image=\"23\" ImageSize=\"582706\" />\n    <Page Image=\"24\" ImageSize=\"507370\" />\n    <Page Image=\"25\" ImageSize=\"489280\" />\n    <Page Image=\"26\" ImageSize=\"519906\" />\n  </Pages>\n</ComicInfo>";
@@ -0,0 +1,264 @@
+use std::{io, time::Duration};
+
+use prisma_client_rust::chrono::Utc;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use zip::result::ZipError;
+
+use crate::{
+	config::StumpConfig,
+	db::entity::FileStatus,
+	filesystem::{media::process::verify_async, FileError},
+	job::{
+		error::JobError, JobExecuteLog, JobExt, JobOutputExt, JobTaskOutput, WorkerCtx,
+		WorkingState, WrappedJob,
+	},
+	prisma::{media, series},
+};
+
+pub const VERIFY_MEDIA_JOB_NAME: &str = "verify_media";
+
+/// The number of media which are verified in a single task
+const VERIFY_CHUNK_SIZE: usize = 25;
+
+/// The number of times a file is read before a failure to read it is given up on
+const VERIFY_ATTEMPTS: u32 = 3;
+
+/// How long to wait before reading a file again, multiplied by the number of attempts so far
+const VERIFY_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum VerifyMediaTask {
+	/// Verify the integrity of the media specified by ID
+	Verify(Vec<String>),
+}
+
+#[derive(Clone, Serialize, Deserialize, Default, Debug, Type)]
+pub struct VerifyMediaOutput {
+	/// The number of media which were verified
+	media_verified: u64,
+	/// The number of media which failed verification
+	media_broken: u64,
+	/// The number of media which previously failed verification and now passed
+	media_repaired: u64,
+	/// The number of media which could not be read, e.g. because the share they are on was
+	/// unavailable. These are left as they were, since nothing is known to be wrong with them.
+	#[serde(default)]
+	media_unreadable: u64,
+}
+
+impl JobOutputExt for VerifyMediaOutput {
+	fn update(&mut self, updated: Self) {
+		self.media_verified += updated.media_verified;
+		self.media_broken += updated.media_broken;
+		self.media_repaired += updated.media_repaired;
+		self.media_unreadable += updated.media_unreadable;
+	}
+}
+
+/// Whether a failure to verify a file says nothing about the file itself, e.g. the file
+/// couldn't be opened or the read was interrupted. A read which ends early is not transient,
+/// since that is how a truncated file fails.
+fn is_transient_failure(error: &FileError) -> bool {
+	let is_transient_io =
+		|error: &io::Error| error.kind() != io::ErrorKind::UnexpectedEof;
+	match error {
+		FileError::FileIoError(error) => is_transient_io(error),
+		FileError::ZipFileError(ZipError::Io(error)) => is_transient_io(error),
+		FileError::PdfConfigurationError | FileError::UnknownError(_) => true,
+		_ => false,
+	}
+}
+
+/// Verify a file, reading it again when a failure is transient
+async fn verify_with_retries(path: &str, config: &StumpConfig) -> Result<(), FileError> {
+	let mut attempt = 1;
+	loop {
+		match verify_async(path, config).await {
+			Err(error) if is_transient_failure(&error) && attempt < VERIFY_ATTEMPTS => {
+				tracing::debug!(?error, path, attempt, "Retrying media verification");
+				tokio::time::sleep(VERIFY_RETRY_DELAY * attempt).await;
+				attempt += 1;
+			},
+			result => return result,
+		}
+	}
+}
+
+/// A job which verifies the integrity of media files, so corrupt or truncated files are found
+/// before someone tries to read them. Every entry of an archive is read and every image is
+/// decoded, and EPUBs and PDFs have their structure checked. Media which fail are put in the
+/// `ERROR` status with the reason they failed, and media which pass are put back in the
+/// `READY` status.
+#[derive(Clone)]
+pub struct VerifyMediaJob {
+	/// The library to verify the media of, or `None` to verify all media
+	pub library_id: Option<String>,
+}
+
+impl VerifyMediaJob {
+	pub fn new(library_id: Option<String>) -> Box<WrappedJob<VerifyMediaJob>> {
+		WrappedJob::new(Self { library_id })
+	}
+}
+
+#[async_trait::async_trait]
+impl JobExt for VerifyMediaJob {
+	const NAME: &'static str = VERIFY_MEDIA_JOB_NAME;
+
+	type Output = VerifyMediaOutput;
+	type Task = VerifyMediaTask;
+
+	fn description(&self) -> Option<String> {
+		match &self.library_id {
+			Some(id) => Some(format!("Verify media in library with id: {id}")),
+			None => Some("Verify all media".to_string()),
+		}
+	}
+
+	async fn init(
+		&mut self,
+		ctx: &WorkerCtx,
+	) -> Result<WorkingState<Self::Output, Self::Task>, JobError> {
+		// Missing files can't be read, and unsupported files have no processor to verify them
+		let mut where_params = vec![
+			media::deleted_at::equals(None),
+			media::status::in_vec(vec![
+				FileStatus::Ready.to_string(),
+				FileStatus::Error.to_string(),
+			]),
+		];
+		if let Some(library_id) = &self.library_id {
+			where_params.push(media::series::is(vec![series::library_id::equals(Some(
+				library_id.clone(),
+			))]));
+		}
+
+		let ids = ctx
+			.db
+			.media()
+			.find_many(where_params)
+			.select(media::select!({ id }))
+			.exec()
+			.await
+			.map_err(|e| JobError::InitFailed(e.to_string()))?
+			.into_iter()
+			.map(|media| media.id)
+			.collect::<Vec<_>>();
+		let tasks = ids
+			.chunks(VERIFY_CHUNK_SIZE)
+			.map(|chunk| VerifyMediaTask::Verify(chunk.to_vec()))
+			.collect();
+
+		Ok(WorkingState {
+			output: Some(Self::Output::default()),
+			tasks,
+			completed_tasks: 0,
+			logs: vec![],
+		})
+	}
+
+	async fn execute_task(
+		&self,
+		ctx: &WorkerCtx,
+		task: Self::Task,
+	) -> Result<JobTaskOutput<Self>, JobError> {
+		let mut output = Self::Output::default();
+		let mut logs = vec![];
+
+		let VerifyMediaTask::Verify(ids) = task;
+		let books = ctx
+			.db
+			.media()
+			.find_many(vec![media::id::in_vec(ids)])
+			.select(media::select!({ id path status }))
+			.exec()
+			.await?;
+
+		for book in books {
+			let result = verify_with_retries(&book.path, &ctx.config).await;
+
+			if let Some(e) = result.as_ref().err().filter(|e| is_transient_failure(e)) {
+				tracing::warn!(?e, path = ?book.path, "Media could not be read");
+				logs.push(
+					JobExecuteLog::warn(&format!(
+						"{} could not be read, so it was not verified: {}",
+						book.path, e
+					))
+					.with_ctx(book.id.clone()),
+				);
+				output.media_unreadable += 1;
+				continue;
+			}
+			output.media_verified += 1;
+
+			let (status, reason) = match result {
+				Ok(()) => {
+					if book.status == FileStatus::Error.to_string() {
+						output.media_repaired += 1;
+					}
+					(FileStatus::Ready, None)
+				},
+				Err(e) => {
+					tracing::warn!(?e, path = ?book.path, "Media failed verification");
+					logs.push(
+						JobExecuteLog::warn(&format!(
+							"{} failed verification: {}",
+							book.path, e
+						))
+						.with_ctx(book.id.clone()),
+					);
+					output.media_broken += 1;
+					(FileStatus::Error, Some(e.to_string()))
+				},
+			};
+
+			ctx.db
+				.media()
+				.update(
+					media::id::equals(book.id),
+					vec![
+						media::status::set(status.to_string()),
+						media::status_reason::set(reason),
+						media::verified_at::set(Some(Utc::now().into())),
+					],
+				)
+				.exec()
+				.await?;
+		}
+
+		Ok(JobTaskOutput {
+			output,
+			subtasks: vec![],
+			logs,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_is_transient_failure() {
+		let io_error = |kind| FileError::FileIoError(io::Error::from(kind));
+		assert!(is_transient_failure(&io_error(io::ErrorKind::TimedOut)));
+		assert!(is_transient_failure(&io_error(
+			io::ErrorKind::PermissionDenied
+		)));
+		assert!(is_transient_failure(&FileError::ZipFileError(
+			ZipError::Io(io::Error::from(io::ErrorKind::Interrupted))
+		)));
+
+		// A truncated or corrupt file fails the same way every time
+		assert!(!is_transient_failure(&io_error(
+			io::ErrorKind::UnexpectedEof
+		)));
+		assert!(!is_transient_failure(&FileError::ZipFileError(
+			ZipError::InvalidArchive("Invalid zip header".into())
+		)));
+		assert!(!is_transient_failure(&FileError::IntegrityError(
+			"Failed to decode page 3".to_string()
+		)));
+	}
+}
@@ -0,0 +1,278 @@
+use std::{
+	collections::HashSet,
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+
+use crate::{
+	db::entity::MediaMetadata,
+	prisma::{media, series, PrismaClient},
+	CoreResult,
+};
+
+use super::{
+	moved_media::MovedMedia, options::BookVisitOperation, WalkedLibrary, WalkedSeries,
+};
+
+/// The changes a scan would make, as recorded by a dry run. All entries are paths on disk,
+/// so that nothing needs to exist in the database for the diff to be read.
+#[derive(Clone, Serialize, Deserialize, Default, Debug, PartialEq, Type, ToSchema)]
+pub struct ScanDiff {
+	/// The series which would be created
+	pub series_to_create: Vec<String>,
+	/// The series which would be marked as missing
+	pub series_to_mark_missing: Vec<String>,
+	/// The series which were missing but have been found on disk again
+	pub series_to_restore: Vec<String>,
+	/// The books which would be created
+	pub media_to_create: Vec<String>,
+	/// The books which would be marked as missing and moved to the trash
+	pub media_to_mark_missing: Vec<String>,
+	/// The books which were missing but have been found on disk again
+	pub media_to_restore: Vec<String>,
+	/// The books which exist under another series, but which would be moved to the series
+	/// they now belong to
+	pub media_to_reassign: Vec<String>,
+	/// The books which would be rebuilt, either because they changed on disk or because the
+	/// scan forces a rebuild
+	pub media_to_rebuild: Vec<String>,
+	/// The books whose hashes would be regenerated by a custom visit
+	pub media_to_rehash: Vec<String>,
+	/// The books whose metadata would be different after they are rebuilt or visited
+	pub media_with_changed_metadata: Vec<String>,
+	/// The books which were moved or renamed on disk, and which would be re-pointed at their
+	/// new path instead of being created and marked as missing
+	#[serde(default)]
+	pub media_to_move: Vec<MovedMedia>,
+}
+
+impl ScanDiff {
+	pub fn is_empty(&self) -> bool {
+		self == &Self::default()
+	}
+
+	/// Merge another diff into this one, e.g. the diff of a series into that of its library
+	pub fn extend(&mut self, other: ScanDiff) {
+		self.series_to_create.extend(other.series_to_create);
+		self.series_to_mark_missing
+			.extend(other.series_to_mark_missing);
+		self.series_to_restore.extend(other.series_to_restore);
+		self.media_to_create.extend(other.media_to_create);
+		self.media_to_mark_missing
+			.extend(other.media_to_mark_missing);
+		self.media_to_restore.extend(other.media_to_restore);
+		self.media_to_reassign.extend(other.media_to_reassign);
+		self.media_to_rebuild.extend(other.media_to_rebuild);
+		self.media_to_rehash.extend(other.media_to_rehash);
+		self.media_with_changed_metadata
+			.extend(other.media_with_changed_metadata);
+		self.media_to_move.extend(other.media_to_move);
+
+		// A move may be found by a different series than the one the book was moved out of,
+		// so both sides are only reconciled once the diffs are merged
+		let moved_from = self
+			.media_to_move
+			.iter()
+			.map(|moved| moved.from.as_str())
+			.collect::<HashSet<_>>();
+		let moved_to = self
+			.media_to_move
+			.iter()
+			.map(|moved| moved.to.as_str())
+			.collect::<HashSet<_>>();
+		self.media_to_create
+			.retain(|path| !moved_to.contains(path.as_str()));
+		self.media_to_mark_missing
+			.retain(|path| !moved_from.contains(path.as_str()));
+	}
+
+	/// Build the diff for the series-level changes found by walking a library
+	pub async fn for_library(
+		db: &PrismaClient,
+		walked: &WalkedLibrary,
+	) -> CoreResult<ScanDiff> {
+		let series_to_restore = if walked.recovered_series.is_empty() {
+			vec![]
+		} else {
+			db.series()
+				.find_many(vec![series::id::in_vec(walked.recovered_series.clone())])
+				.select(series::select!({ path }))
+				.exec()
+				.await?
+				.into_iter()
+				.map(|series| series.path)
+				.collect()
+		};
+
+		Ok(ScanDiff {
+			series_to_create: paths_to_strings(&walked.series_to_create),
+			series_to_mark_missing: paths_to_strings(&walked.missing_series),
+			series_to_restore,
+			..Default::default()
+		})
+	}
+
+	/// Build the diff for the book-level changes found by walking a series. The books whose
+	/// metadata would be regenerated are returned alongside it, since it has to be generated
+	/// to know whether it would change.
+	pub async fn for_series(
+		db: &PrismaClient,
+		path: &Path,
+		walked: WalkedSeries,
+	) -> CoreResult<(ScanDiff, Vec<(PathBuf, BookVisitOperation)>)> {
+		if walked.series_is_missing {
+			return Ok((
+				ScanDiff {
+					series_to_mark_missing: vec![path.to_string_lossy().to_string()],
+					..Default::default()
+				},
+				vec![],
+			));
+		}
+
+		let media_paths = |ids: Vec<String>| async move {
+			if ids.is_empty() {
+				return Ok(vec![]);
+			}
+			db.media()
+				.find_many(vec![media::id::in_vec(ids)])
+				.select(media::select!({ path }))
+				.exec()
+				.await
+				.map(|media| media.into_iter().map(|m| m.path).collect::<Vec<_>>())
+		};
+
+		let (media_to_rebuild, media_to_rehash) = walked.media_to_visit.iter().fold(
+			(vec![], vec![]),
+			|(mut rebuild, mut rehash), (path, operation)| {
+				let path = path.to_string_lossy().to_string();
+				match operation {
+					BookVisitOperation::Rebuild => rebuild.push(path),
+					BookVisitOperation::Custom(custom) if custom.regen_hashes => {
+						rehash.push(path)
+					},
+					BookVisitOperation::Custom(_) => {},
+				}
+				(rebuild, rehash)
+			},
+		);
+
+		let diff = ScanDiff {
+			media_to_create: paths_to_strings(&walked.media_to_create),
+			media_to_mark_missing: paths_to_strings(&walked.missing_media),
+			media_to_restore: media_paths(walked.recovered_media).await?,
+			media_to_reassign: media_paths(walked.media_to_reassign).await?,
+			media_to_rebuild,
+			media_to_rehash,
+			..Default::default()
+		};
+
+		// Only the visits which regenerate metadata need to be compared
+		let media_to_compare = walked
+			.media_to_visit
+			.into_iter()
+			.filter(|(_, operation)| match operation {
+				BookVisitOperation::Rebuild => true,
+				BookVisitOperation::Custom(custom) => custom.regen_meta,
+			})
+			.collect();
+
+		Ok((diff, media_to_compare))
+	}
+}
+
+fn paths_to_strings(paths: &[PathBuf]) -> Vec<String> {
+	paths
+		.iter()
+		.map(|path| path.to_string_lossy().to_string())
+		.collect()
+}
+
+/// Whether the metadata generated for a book differs from what is stored for it. The page
+/// dimensions are ignored, since they are only ever generated by a separate job.
+pub(crate) fn metadata_changed(
+	existing: Option<&MediaMetadata>,
+	generated: &MediaMetadata,
+) -> bool {
+	let comparable = |metadata: &MediaMetadata| {
+		serde_json::to_value(MediaMetadata {
+			page_dimensions: None,
+			..metadata.clone()
+		})
+		.ok()
+	};
+	existing.is_none_or(|existing| comparable(existing) != comparable(generated))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_extend_diff() {
+		let mut diff = ScanDiff {
+			series_to_create: vec!["/library/a".to_string()],
+			..Default::default()
+		};
+		assert!(!diff.is_empty());
+
+		diff.extend(ScanDiff {
+			series_to_create: vec!["/library/b".to_string()],
+			media_to_create: vec!["/library/b/1.cbz".to_string()],
+			..Default::default()
+		});
+		assert_eq!(diff.series_to_create, vec!["/library/a", "/library/b"]);
+		assert_eq!(diff.media_to_create, vec!["/library/b/1.cbz"]);
+		assert!(ScanDiff::default().is_empty());
+	}
+
+	#[test]
+	fn test_extend_diff_with_moves() {
+		let mut diff = ScanDiff {
+			media_to_mark_missing: vec![
+				"/library/a/1.cbz".to_string(),
+				"/library/a/2.cbz".to_string(),
+			],
+			..Default::default()
+		};
+
+		diff.extend(ScanDiff {
+			media_to_create: vec!["/library/b/1.cbz".to_string()],
+			media_to_move: vec![MovedMedia {
+				id: "1".to_string(),
+				from: "/library/a/1.cbz".to_string(),
+				to: "/library/b/1.cbz".to_string(),
+			}],
+			..Default::default()
+		});
+		assert!(diff.media_to_create.is_empty());
+		assert_eq!(diff.media_to_mark_missing, vec!["/library/a/2.cbz"]);
+		assert_eq!(diff.media_to_move.len(), 1);
+	}
+
+	#[test]
+	fn test_metadata_changed() {
+		let existing = MediaMetadata {
+			id: "existing".to_string(),
+			title: Some("Saga".to_string()),
+			number: Some(1.0),
+			..Default::default()
+		};
+
+		let same = MediaMetadata {
+			id: String::new(),
+			..existing.clone()
+		};
+		assert!(!metadata_changed(Some(&existing), &same));
+
+		let renumbered = MediaMetadata {
+			number: Some(2.0),
+			..existing.clone()
+		};
+		assert!(metadata_changed(Some(&existing), &renumbered));
+		assert!(metadata_changed(None, &same));
+	}
+}
@@ -1,6 +1,7 @@
 use std::{collections::VecDeque, path::PathBuf};
 
 use prisma_client_rust::chrono;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use utoipa::ToSchema;
@@ -28,13 +29,16 @@ use crate::{
 };
 
 use super::{
+	moved_media::MovedMedia,
 	series_scan_job::SeriesScanTask,
 	utils::{
-		handle_missing_media, handle_missing_series, handle_restored_media,
+		build_filename_rules, diff_moved_media, diff_visited_media, handle_missing_media,
+		handle_missing_series, handle_reassigned_media, handle_restored_media,
 		safely_build_and_insert_media, safely_build_series, visit_and_update_media,
-		MediaBuildOperation, MediaOperationOutput, MissingSeriesOutput,
+		MediaBuildOperation, MediaDiffOutput, MediaOperationOutput, MissingSeriesOutput,
 	},
-	walk_library, walk_series, ScanOptions, WalkedLibrary, WalkedSeries, WalkerCtx,
+	walk_library, walk_series, ScanDiff, ScanOptions, WalkedLibrary, WalkedSeries,
+	WalkerCtx,
 };
 
 /// The task variants that are used to scan a library
@@ -66,6 +70,10 @@ pub struct LibraryScanJob {
 	pub path: String,
 	/// The library configuration to use
 	pub config: Option<LibraryConfig>,
+	/// The filename rules of the library, compiled from its configuration when the job starts
+	pub filename_rules: Vec<Regex>,
+	/// The changed paths which caused the library watcher to queue the scan, if it did
+	pub triggered_by: Vec<String>,
 	/// The scan options to use, if any
 	pub options: ScanOptions,
 }
@@ -80,9 +88,28 @@ impl LibraryScanJob {
 			id,
 			path,
 			config: None,
+			filename_rules: vec![],
+			triggered_by: vec![],
 			options: options.unwrap_or_default(),
 		})
 	}
+
+	/// Create a scan for the changes the library watcher picked up, which records the paths
+	/// which triggered it in its output
+	pub fn for_changed_paths(
+		id: String,
+		path: String,
+		triggered_by: Vec<String>,
+	) -> Box<WrappedJob<LibraryScanJob>> {
+		WrappedJob::new(Self {
+			id,
+			path,
+			config: None,
+			filename_rules: vec![],
+			triggered_by,
+			options: ScanOptions::default(),
+		})
+	}
 }
 
 /// The data that is collected and updated during the execution of a library scan job
@@ -103,10 +130,25 @@ pub struct LibraryScanOutput {
 	created_media: u64,
 	/// The number of media entities updated
 	updated_media: u64,
+	/// The media entities which were found at a new path
+	moved_media: Vec<MovedMedia>,
 	/// The number of series entities created
 	created_series: u64,
 	/// The number of series entities updated
 	updated_series: u64,
+	/// The changes the scan would make, if it was a dry run
+	#[serde(default)]
+	diff: Option<ScanDiff>,
+	/// The changed paths which caused the library watcher to queue the scan, if it did
+	#[serde(default)]
+	triggered_by: Vec<String>,
+}
+
+impl LibraryScanOutput {
+	/// The changes the scan would make, if it was a dry run
+	pub fn diff(&self) -> Option<&ScanDiff> {
+		self.diff.as_ref()
+	}
 }
 
 impl JobOutputExt for LibraryScanOutput {
@@ -118,8 +160,13 @@ impl JobOutputExt for LibraryScanOutput {
 		self.ignored_directories += updated.ignored_directories;
 		self.created_media += updated.created_media;
 		self.updated_media += updated.updated_media;
+		self.moved_media.extend(updated.moved_media);
 		self.created_series += updated.created_series;
 		self.updated_series += updated.updated_series;
+		self.triggered_by.extend(updated.triggered_by);
+		if let Some(diff) = updated.diff {
+			self.diff.get_or_insert_with(Default::default).extend(diff);
+		}
 	}
 }
 
@@ -138,7 +185,10 @@ impl JobExt for LibraryScanJob {
 		&mut self,
 		ctx: &WorkerCtx,
 	) -> Result<WorkingState<Self::Output, Self::Task>, JobError> {
-		let mut output = Self::Output::default();
+		let mut output = Self::Output {
+			triggered_by: self.triggered_by.clone(),
+			..Default::default()
+		};
 		// Note: We ignore the potential self.config here in the event that it was
 		// updated since being queued. This is perhaps a bit overly cautious, but it's
 		// just one additional query.
@@ -156,28 +206,40 @@ impl JobExt for LibraryScanJob {
 		library_config.apply(self.options);
 		let is_collection_based = library_config.is_collection_based();
 		let ignore_rules = library_config.ignore_rules.build()?;
+		let series_resolver = library_config
+			.has_series_detection()
+			.then(|| library_config.series_resolver(&self.path))
+			.transpose()?;
 
+		self.filename_rules = build_filename_rules(&library_config);
 		self.config = Some(library_config);
 
 		ctx.report_progress(JobProgress::msg("Performing task discovery"));
-		let WalkedLibrary {
-			series_to_create,
-			recovered_series,
-			series_to_visit,
-			missing_series,
-			library_is_missing,
-			ignored_directories,
-			seen_directories,
-		} = walk_library(
+		let walked_library = walk_library(
 			&self.path,
 			WalkerCtx {
 				db: ctx.db.clone(),
 				ignore_rules,
 				max_depth: is_collection_based.then_some(1),
+				series_resolver,
 				options: self.options,
 			},
 		)
 		.await?;
+		// A dry run records the changes instead of making them, so the init task (which
+		// creates, restores and marks series as missing) is skipped entirely
+		if self.options.dry_run && !walked_library.library_is_missing {
+			output.diff = Some(ScanDiff::for_library(&ctx.db, &walked_library).await?);
+		}
+		let WalkedLibrary {
+			series_to_create,
+			recovered_series,
+			series_to_visit,
+			missing_series,
+			library_is_missing,
+			ignored_directories,
+			seen_directories,
+		} = walked_library;
 		tracing::debug!(
 			series_to_create = series_to_create.len(),
 			series_to_visit = series_to_visit.len(),
@@ -189,7 +251,11 @@ impl JobExt for LibraryScanJob {
 		output.total_directories = seen_directories + ignored_directories;
 		output.ignored_directories = ignored_directories;
 
-		if library_is_missing {
+		if library_is_missing && self.options.dry_run {
+			return Err(JobError::InitFailed(
+				"Library could not be found on disk".to_string(),
+			));
+		} else if library_is_missing {
 			handle_missing_library(&ctx.db, self.id.as_str()).await?;
 			ctx.send_batch(vec![
 				JobProgress::msg("Failed to find library on disk").into_worker_send(),
@@ -219,7 +285,8 @@ impl JobExt for LibraryScanJob {
 			.collect::<Vec<LibraryScanTask>>();
 
 		let tasks = VecDeque::from(
-			[LibraryScanTask::Init(init_task_input)]
+			(!self.options.dry_run)
+				.then_some(LibraryScanTask::Init(init_task_input))
 				.into_iter()
 				.chain(series_to_visit)
 				.collect::<Vec<LibraryScanTask>>(),
@@ -245,6 +312,11 @@ impl JobExt for LibraryScanJob {
 			output: CoreJobOutput::LibraryScan(output.clone()),
 		});
 
+		if self.options.dry_run {
+			tracing::debug!("Dry run complete, skipping scan completion");
+			return Ok(None);
+		}
+
 		let did_create = output.created_series > 0 || output.created_media > 0;
 		let did_update = output.updated_series > 0 || output.updated_media > 0;
 		let image_options = self
@@ -468,6 +540,22 @@ impl JobExt for LibraryScanJob {
 					.config
 					.as_ref()
 					.and_then(|o| (!o.is_collection_based()).then_some(1));
+				// When the library uses a series detection strategy, the resolver decides which of
+				// the nested books belong to this series instead
+				let series_resolver = match self
+					.config
+					.as_ref()
+					.filter(|config| config.has_series_detection())
+					.map(|config| config.series_resolver(&self.path))
+				{
+					Some(Ok(resolver)) => Some(resolver),
+					Some(Err(error)) => {
+						return Err(JobError::TaskFailed(format!(
+							"Failed to build the series detection strategy: {error}"
+						)));
+					},
+					None => None,
+				};
 				if path_buf == PathBuf::from(&self.path) {
 					// The exception is when the series "is" the libray (i.e. the root of the library contains
 					// books). This is kind of an anti-pattern wrt collection-priority, but it needs to be handled
@@ -491,21 +579,13 @@ impl JobExt for LibraryScanJob {
 						db: ctx.db.clone(),
 						ignore_rules,
 						max_depth,
+						series_resolver,
 						options: self.options,
 					},
 				)
 				.await;
 
-				let WalkedSeries {
-					series_is_missing,
-					media_to_create,
-					media_to_visit,
-					recovered_media,
-					missing_media,
-					seen_files,
-					ignored_files,
-					skipped_files,
-				} = match walk_result {
+				let walked_series = match walk_result {
 					Ok(walked_series) => walked_series,
 					Err(core_error) => {
 						tracing::error!(error = ?core_error, "Critical error during attempt to walk series!");
@@ -522,9 +602,73 @@ impl JobExt for LibraryScanJob {
 						});
 					},
 				};
-				output.total_files += seen_files + ignored_files;
-				output.ignored_files += ignored_files;
-				output.skipped_files += skipped_files;
+				output.total_files +=
+					walked_series.seen_files + walked_series.ignored_files;
+				output.ignored_files += walked_series.ignored_files;
+				output.skipped_files += walked_series.skipped_files;
+
+				if self.options.dry_run {
+					let media_to_create = walked_series.media_to_create.clone();
+					let (mut diff, media_to_compare) =
+						ScanDiff::for_series(&ctx.db, &path_buf, walked_series).await?;
+					// The series may not exist yet, and the books are never inserted, so they
+					// are built without one
+					let MediaDiffOutput {
+						moved_media,
+						logs: diff_logs,
+						..
+					} = diff_moved_media(
+						MediaBuildOperation {
+							series_id: String::new(),
+							library_config: self.config.clone().unwrap_or_default(),
+							filename_rules: self.filename_rules.clone(),
+							max_concurrency,
+						},
+						ctx,
+						media_to_create,
+					)
+					.await?;
+					logs.extend(diff_logs);
+					diff.extend(ScanDiff {
+						media_to_move: moved_media,
+						..Default::default()
+					});
+					output.diff = Some(diff);
+					// Only media which already exist are visited, so the series must exist too
+					if !media_to_compare.is_empty() {
+						let series_path_str = path_buf.to_string_lossy().to_string();
+						subtasks = ctx
+							.db
+							.series()
+							.find_first(vec![series::path::equals(
+								series_path_str.clone(),
+							)])
+							.exec()
+							.await?
+							.map(|series| LibraryScanTask::SeriesTask {
+								id: series.id,
+								path: series_path_str,
+								task: SeriesScanTask::DiffMedia(media_to_compare),
+							})
+							.into_iter()
+							.collect();
+					}
+					return Ok(JobTaskOutput {
+						output,
+						subtasks,
+						logs,
+					});
+				}
+
+				let WalkedSeries {
+					series_is_missing,
+					media_to_create,
+					media_to_visit,
+					recovered_media,
+					missing_media,
+					media_to_reassign,
+					..
+				} = walked_series;
 
 				if series_is_missing {
 					ctx.report_progress(JobProgress::msg("Series not found on disk!"));
@@ -567,6 +711,8 @@ impl JobExt for LibraryScanJob {
 							.then_some(SeriesScanTask::MarkMissingMedia(missing_media)),
 						(!recovered_media.is_empty())
 							.then_some(SeriesScanTask::RestoreMedia(recovered_media)),
+						(!media_to_reassign.is_empty())
+							.then_some(SeriesScanTask::ReassignMedia(media_to_reassign)),
 						(!media_to_create.is_empty())
 							.then_some(SeriesScanTask::CreateMedia(media_to_create)),
 						(!media_to_visit.is_empty())
@@ -604,6 +750,24 @@ impl JobExt for LibraryScanJob {
 					output.updated_media += updated_media;
 					logs.extend(new_logs);
 				},
+				SeriesScanTask::ReassignMedia(ids) => {
+					ctx.report_progress(JobProgress::msg("Reassigning media entities"));
+					let MediaOperationOutput {
+						updated_media,
+						logs: new_logs,
+						..
+					} = handle_reassigned_media(ctx, &series_id, ids).await;
+					ctx.send_batch(vec![
+						JobProgress::msg("Reassigned media entities").into_worker_send(),
+						CoreEvent::CreatedOrUpdatedManyMedia {
+							count: updated_media,
+							series_id,
+						}
+						.into_worker_send(),
+					]);
+					output.updated_media += updated_media;
+					logs.extend(new_logs);
+				},
 				SeriesScanTask::MarkMissingMedia(paths) => {
 					ctx.report_progress(JobProgress::msg("Handling missing media"));
 					let MediaOperationOutput {
@@ -628,12 +792,14 @@ impl JobExt for LibraryScanJob {
 					));
 					let MediaOperationOutput {
 						created_media,
+						moved_media,
 						logs: new_logs,
 						..
 					} = safely_build_and_insert_media(
 						MediaBuildOperation {
 							series_id: series_id.clone(),
 							library_config: self.config.clone().unwrap_or_default(),
+							filename_rules: self.filename_rules.clone(),
 							max_concurrency,
 						},
 						ctx,
@@ -643,12 +809,13 @@ impl JobExt for LibraryScanJob {
 					ctx.send_batch(vec![
 						JobProgress::msg("Created new media").into_worker_send(),
 						CoreEvent::CreatedOrUpdatedManyMedia {
-							count: created_media,
+							count: created_media + moved_media.len() as u64,
 							series_id,
 						}
 						.into_worker_send(),
 					]);
 					output.created_media += created_media;
+					output.moved_media.extend(moved_media);
 					logs.extend(new_logs);
 				},
 				SeriesScanTask::VisitMedia(params) => {
@@ -664,6 +831,7 @@ impl JobExt for LibraryScanJob {
 						MediaBuildOperation {
 							series_id: series_id.clone(),
 							library_config: self.config.clone().unwrap_or_default(),
+							filename_rules: self.filename_rules.clone(),
 							max_concurrency,
 						},
 						ctx,
@@ -681,6 +849,31 @@ impl JobExt for LibraryScanJob {
 					output.updated_media += updated_media;
 					logs.extend(new_logs);
 				},
+				SeriesScanTask::DiffMedia(params) => {
+					ctx.report_progress(JobProgress::msg(
+						format!("Comparing {} media entities on disk", params.len())
+							.as_str(),
+					));
+					let MediaDiffOutput {
+						changed_metadata,
+						logs: new_logs,
+					} = diff_visited_media(
+						MediaBuildOperation {
+							series_id,
+							library_config: self.config.clone().unwrap_or_default(),
+							filename_rules: self.filename_rules.clone(),
+							max_concurrency,
+						},
+						ctx,
+						params,
+					)
+					.await?;
+					output.diff = Some(ScanDiff {
+						media_with_changed_metadata: changed_metadata,
+						..Default::default()
+					});
+					logs.extend(new_logs);
+				},
 			},
 		}
 
@@ -1,23 +1,37 @@
-use crate::db::entity::macros::library_idents_select;
-use crate::prisma::{library, library_config, PrismaClient};
+use crate::db::entity::{macros::library_idents_select, LibraryConfig};
+use crate::prisma::{library, library_config, series, PrismaClient};
 use crate::{
-	filesystem::scanner::LibraryScanJob,
+	filesystem::{
+		scanner::{LibraryScanJob, SeriesResolver, SeriesScanJob},
+		PathUtils,
+	},
 	job::{JobController, JobControllerCommand},
 	CoreError, CoreResult,
 };
 use async_trait::async_trait;
 use notify::{Event, RecommendedWatcher, Watcher};
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::Mutex;
 
+use super::poll_watcher::PollWatcher;
+
+/// How the files of a library are watched for changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatcherBackend {
+	/// Native filesystem events, via the recommended watcher for the platform
+	Native,
+	/// Diffing snapshots of the library, taken at the given interval
+	Poll(Duration),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LibraryWatcherCommand {
-	AddWatcher(PathBuf),
+	AddWatcher(PathBuf, WatcherBackend),
 	RemoveWatcher(PathBuf),
 	ChangedFiles(Vec<PathBuf>),
 	Flush,
@@ -27,7 +41,9 @@ pub enum LibraryWatcherCommand {
 fn create_watcher(sender: UnboundedSender<LibraryWatcherCommand>) -> RecommendedWatcher {
 	notify::recommended_watcher(move |result: Result<Event, _>| match result {
 		Ok(event) => match event.kind {
-			notify::EventKind::Create(_) | notify::EventKind::Modify(_) => {
+			notify::EventKind::Create(_)
+			| notify::EventKind::Modify(_)
+			| notify::EventKind::Remove(_) => {
 				let _ = sender
 					.send(LibraryWatcherCommand::ChangedFiles(event.paths))
 					.map_err(|e| {
@@ -47,6 +63,7 @@ struct LibraryWatcherInternal {
 	wait_interval: Duration,
 	sender: UnboundedSender<LibraryWatcherCommand>,
 	watcher: RecommendedWatcher,
+	poller: PollWatcher,
 	last_update_time: Arc<Mutex<std::time::SystemTime>>,
 	accumulated_paths: HashSet<PathBuf>,
 	wait_thread: Option<tokio::task::JoinHandle<()>>,
@@ -62,6 +79,7 @@ impl LibraryWatcherInternal {
 			wait_interval: wait_duration,
 			sender: sender.clone(),
 			watcher,
+			poller: PollWatcher::new(sender),
 			last_update_time: Arc::new(Mutex::new(std::time::SystemTime::now())),
 			accumulated_paths: HashSet::new(),
 			wait_thread: None,
@@ -108,9 +126,110 @@ impl LibraryWatcherInternal {
 	}
 }
 
+/// A watched library, along with what is needed to map changed paths to its series
+#[derive(Debug, Clone)]
+struct WatchedLibrary {
+	id: String,
+	path: String,
+	resolver: SeriesResolver,
+	/// The IDs of the existing series in the library, keyed by their path
+	series: HashMap<PathBuf, String>,
+}
+
+/// The work the watcher submits in response to changed paths
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum WatcherScan {
+	Library { id: String, path: String },
+	Series { id: String, path: String },
+}
+
+impl WatchedLibrary {
+	/// Find the existing series a changed path affects. Returns `None` when the change
+	/// affects the structure of the library instead, e.g. a directory was added or removed,
+	/// or the book belongs to a series which does not exist yet.
+	fn affected_series(&self, path: &Path) -> Option<(&PathBuf, &String)> {
+		// A series.json file can change which directories are series
+		let is_series_file = path.file_name().is_some_and(|name| name == "series.json");
+		// A removed directory can't be told apart from a removed file, so anything which
+		// contained a series is assumed to be a directory
+		let removed_series = !path.exists()
+			&& self
+				.series
+				.keys()
+				.any(|series_path| series_path.starts_with(path));
+		if path.is_dir() || is_series_file || removed_series {
+			return None;
+		}
+
+		let series_path = self.resolver.series_for_book(path)?;
+		self.series.get_key_value(&series_path)
+	}
+
+	/// Map the changed paths within the library to the scans which should be submitted, along
+	/// with the paths which triggered each of them. The whole library is only scanned when
+	/// its structure changed, otherwise each affected series is scanned on its own.
+	fn plan_scans(&self, paths: Vec<PathBuf>) -> Vec<(WatcherScan, Vec<PathBuf>)> {
+		let mut series_scans = BTreeMap::<&String, (&PathBuf, Vec<PathBuf>)>::new();
+		let mut structural_changes = vec![];
+
+		for path in paths {
+			// Hidden and unsupported files (e.g. partial downloads) are never picked up by a
+			// scan, so there is nothing to do for them
+			if path.is_file() && path.is_default_ignored() {
+				continue;
+			}
+			match self.affected_series(&path) {
+				Some((series_path, series_id)) => {
+					series_scans
+						.entry(series_id)
+						.or_insert_with(|| (series_path, vec![]))
+						.1
+						.push(path);
+				},
+				None => structural_changes.push(path),
+			}
+		}
+
+		if !structural_changes.is_empty() {
+			let paths = structural_changes
+				.into_iter()
+				.chain(series_scans.into_values().flat_map(|(_, paths)| paths))
+				.collect();
+			return vec![(
+				WatcherScan::Library {
+					id: self.id.clone(),
+					path: self.path.clone(),
+				},
+				paths,
+			)];
+		}
+
+		series_scans
+			.into_iter()
+			.map(|(id, (path, paths))| {
+				(
+					WatcherScan::Series {
+						id: id.clone(),
+						path: path.to_string_lossy().to_string(),
+					},
+					paths,
+				)
+			})
+			.collect()
+	}
+}
+
 #[async_trait]
 trait LibrariesProvider {
 	async fn get_libraries(&self) -> CoreResult<Vec<library_idents_select::Data>>;
+	async fn get_config(
+		&self,
+		library: &library_idents_select::Data,
+	) -> CoreResult<LibraryConfig>;
+	async fn get_watched_library(
+		&self,
+		library: &library_idents_select::Data,
+	) -> CoreResult<WatchedLibrary>;
 }
 
 #[derive(Debug, Clone)]
@@ -136,11 +255,55 @@ impl LibrariesProvider for LibraryProvider {
 			.into_iter()
 			.collect())
 	}
+
+	async fn get_config(
+		&self,
+		library: &library_idents_select::Data,
+	) -> CoreResult<LibraryConfig> {
+		Ok(self
+			.db_client
+			.library_config()
+			.find_first(vec![library_config::library::is(vec![
+				library::id::equals(library.id.clone()),
+			])])
+			.exec()
+			.await?
+			.map(LibraryConfig::from)
+			.unwrap_or_default())
+	}
+
+	async fn get_watched_library(
+		&self,
+		library: &library_idents_select::Data,
+	) -> CoreResult<WatchedLibrary> {
+		let config = self.get_config(library).await?;
+		let series = self
+			.db_client
+			.series()
+			.find_many(vec![series::library_id::equals(Some(library.id.clone()))])
+			.select(series::select!({ id path }))
+			.exec()
+			.await?
+			.into_iter()
+			.map(|series| (PathBuf::from(series.path), series.id))
+			.collect();
+
+		Ok(WatchedLibrary {
+			id: library.id.clone(),
+			path: library.path.clone(),
+			resolver: config.series_resolver(&library.path)?,
+			series,
+		})
+	}
 }
 
 #[async_trait]
 trait SubmitScanJob {
-	async fn submit(&self, id: String, path: String) -> Result<(), ()>;
+	async fn submit(
+		&self,
+		scan: WatcherScan,
+		triggered_by: Vec<PathBuf>,
+	) -> Result<(), ()>;
 }
 
 #[derive(Clone)]
@@ -150,14 +313,26 @@ struct JobControllerSubmitter {
 
 #[async_trait]
 impl SubmitScanJob for JobControllerSubmitter {
-	async fn submit(&self, id: String, path: String) -> Result<(), ()> {
-		self.job_controller
-			.push_command(JobControllerCommand::EnqueueJob(LibraryScanJob::new(
-				id, path, None,
-			)))
-			.map_err(|e| {
-				tracing::error!(error = ?e, "Error sending library scan job");
-			})
+	async fn submit(
+		&self,
+		scan: WatcherScan,
+		triggered_by: Vec<PathBuf>,
+	) -> Result<(), ()> {
+		let triggered_by = triggered_by
+			.into_iter()
+			.map(|path| path.to_string_lossy().to_string())
+			.collect();
+		let job = match scan {
+			WatcherScan::Library { id, path } => JobControllerCommand::EnqueueJob(
+				LibraryScanJob::for_changed_paths(id, path, triggered_by),
+			),
+			WatcherScan::Series { id, path } => JobControllerCommand::EnqueueJob(
+				SeriesScanJob::for_changed_paths(id, path, triggered_by),
+			),
+		};
+		self.job_controller.push_command(job).map_err(|e| {
+			tracing::error!(error = ?e, "Error sending scan job");
+		})
 	}
 }
 
@@ -224,21 +399,47 @@ impl LibraryWatcher {
 				LibraryWatcherInternal::new(watcher, sender, wait_duration);
 			while let Some(command) = receiver.recv().await {
 				match command {
-					LibraryWatcherCommand::AddWatcher(path) => {
-						tracing::debug!("Adding watcher for path: {:?}", path);
-						if let Err(e) = lib_watcher
-							.watcher
-							.watch(path.as_path(), notify::RecursiveMode::Recursive)
-						{
-							tracing::error!(error = ?e, "Error adding file watcher");
-							break;
+					LibraryWatcherCommand::AddWatcher(path, backend) => {
+						tracing::debug!(?backend, "Adding watcher for path: {:?}", path);
+						// The library may already be watched, possibly with the other backend. A
+						// library which stays polled keeps its poller (and so its snapshot)
+						match backend {
+							WatcherBackend::Native => {
+								if !lib_watcher.poller.unwatch(&path) {
+									let _ = lib_watcher.watcher.unwatch(path.as_path());
+								}
+								if let Err(e) = lib_watcher.watcher.watch(
+									path.as_path(),
+									notify::RecursiveMode::Recursive,
+								) {
+									tracing::error!(error = ?e, "Error adding file watcher");
+									break;
+								}
+							},
+							WatcherBackend::Poll(interval) => {
+								if !lib_watcher.poller.is_watching(&path) {
+									let _ = lib_watcher.watcher.unwatch(path.as_path());
+								}
+								lib_watcher.poller.watch(path, interval);
+							},
 						}
 					},
 					LibraryWatcherCommand::RemoveWatcher(path) => {
 						tracing::debug!("Removing watcher for path: {:?}", path);
-						if let Err(e) = lib_watcher.watcher.unwatch(path.as_path()) {
-							tracing::error!(error = ?e, "Error removing file watcher");
-							break;
+						if lib_watcher.poller.unwatch(&path) {
+							continue;
+						}
+						match lib_watcher.watcher.unwatch(path.as_path()) {
+							Err(e)
+								if matches!(e.kind, notify::ErrorKind::WatchNotFound) =>
+							{
+								tracing::debug!("Path was not watched: {:?}", path);
+							},
+							Err(e) => {
+								tracing::error!(error = ?e, "Error removing file watcher");
+								break;
+							},
+							Ok(_) => {},
 						}
 					},
 					LibraryWatcherCommand::ChangedFiles(paths) => {
@@ -253,6 +454,7 @@ impl LibraryWatcher {
 						.await;
 					},
 					LibraryWatcherCommand::StopWatchers => {
+						lib_watcher.poller.stop();
 						break;
 					},
 				};
@@ -267,23 +469,51 @@ impl LibraryWatcher {
 	) -> Result<(), CoreError> {
 		let libraries = library_provider.as_ref().get_libraries().await?;
 
-		let mut libraries_to_scan = HashMap::new();
-		for library in libraries {
-			for path in &paths {
-				if path.starts_with(&library.path) {
-					libraries_to_scan.insert(library.id.clone(), library.path.clone());
-				}
+		let mut changed_paths = HashMap::<String, Vec<PathBuf>>::new();
+		for path in paths {
+			if let Some(library) = libraries
+				.iter()
+				.find(|library| path.starts_with(&library.path))
+			{
+				changed_paths
+					.entry(library.id.clone())
+					.or_default()
+					.push(path);
 			}
 		}
 
-		let results = libraries_to_scan
-			.into_iter()
-			.map(|(id, path_str)| job_submitter.submit(id, path_str));
+		for library in libraries {
+			let Some(paths) = changed_paths.remove(&library.id) else {
+				continue;
+			};
+			// A library which can't be loaded (e.g. its series detection strategy is invalid)
+			// shouldn't prevent the changes to other libraries from being handled
+			let watched_library =
+				match library_provider.get_watched_library(&library).await {
+					Ok(watched_library) => watched_library,
+					Err(error) => {
+						tracing::error!(
+							?error,
+							library_id = ?library.id,
+							?paths,
+							"Failed to load watched library, skipping its changed paths"
+						);
+						continue;
+					},
+				};
 
-		for result in results {
-			result.await.map_err(|e| {
-				CoreError::InitializationError(format!("Failed to submit job: {:?}", e))
-			})?;
+			for (scan, triggered_by) in watched_library.plan_scans(paths) {
+				tracing::info!(?scan, ?triggered_by, "Submitting scan for changed paths");
+				job_submitter
+					.submit(scan, triggered_by)
+					.await
+					.map_err(|e| {
+						CoreError::InitializationError(format!(
+							"Failed to submit job: {:?}",
+							e
+						))
+					})?;
+			}
 		}
 
 		Ok(())
@@ -308,28 +538,42 @@ impl LibraryWatcher {
 	pub async fn add_watcher(
 		&self,
 		path: PathBuf,
+		backend: WatcherBackend,
 	) -> Result<(), SendError<LibraryWatcherCommand>> {
 		self.sender
-			.send(LibraryWatcherCommand::AddWatcher(path.clone()))
+			.send(LibraryWatcherCommand::AddWatcher(path.clone(), backend))
 	}
 
 	pub async fn init(&self) -> CoreResult<()> {
 		let libraries = self.library_provider.get_libraries().await?;
 		for library in libraries {
-			self.add_watcher(library.path.into()).await.map_err(|e| {
-				CoreError::InitializationError(format!("Failed to add watcher: {:?}", e))
-			})?;
+			let backend = self
+				.library_provider
+				.get_config(&library)
+				.await?
+				.watcher_backend();
+			self.add_watcher(library.path.into(), backend)
+				.await
+				.map_err(|e| {
+					CoreError::InitializationError(format!(
+						"Failed to add watcher: {:?}",
+						e
+					))
+				})?;
 		}
 		Ok(())
 	}
 }
 
+#[cfg(test)]
 mod tests {
 	use super::*;
 
 	#[allow(dead_code)]
 	struct MockLibraryProvider {
 		libraries: Vec<library_idents_select::Data>,
+		/// The IDs of the libraries which fail to load when they are watched
+		broken_library_ids: Vec<String>,
 	}
 
 	#[async_trait]
@@ -337,17 +581,40 @@ mod tests {
 		async fn get_libraries(&self) -> CoreResult<Vec<library_idents_select::Data>> {
 			Ok(self.libraries.clone())
 		}
+
+		async fn get_config(
+			&self,
+			_library: &library_idents_select::Data,
+		) -> CoreResult<LibraryConfig> {
+			Ok(LibraryConfig::default())
+		}
+
+		async fn get_watched_library(
+			&self,
+			library: &library_idents_select::Data,
+		) -> CoreResult<WatchedLibrary> {
+			if self.broken_library_ids.contains(&library.id) {
+				return Err(CoreError::BadRequest("Broken library".to_string()));
+			}
+			let mut watched = watched_library(&library.path, &[]);
+			watched.id = library.id.clone();
+			Ok(watched)
+		}
 	}
 
 	#[allow(dead_code)]
 	struct MockJobControllerSubmitter {
-		tx: UnboundedSender<(String, String)>,
+		tx: UnboundedSender<(WatcherScan, Vec<PathBuf>)>,
 	}
 
 	#[async_trait]
 	impl SubmitScanJob for MockJobControllerSubmitter {
-		async fn submit(&self, id: String, path: String) -> Result<(), ()> {
-			let _ = self.tx.send((id, path)).map_err(|e| {
+		async fn submit(
+			&self,
+			scan: WatcherScan,
+			triggered_by: Vec<PathBuf>,
+		) -> Result<(), ()> {
+			let _ = self.tx.send((scan, triggered_by)).map_err(|e| {
 				eprintln!("Error sending job: {:?}", e);
 			});
 			Ok(())
@@ -358,17 +625,28 @@ mod tests {
 	struct MockObjs {
 		library_watcher: LibraryWatcher,
 		sender: UnboundedSender<LibraryWatcherCommand>,
-		jobs_receiver: UnboundedReceiver<(String, String)>,
+		jobs_receiver: UnboundedReceiver<(WatcherScan, Vec<PathBuf>)>,
 	}
 
 	#[allow(dead_code)]
 	async fn create_mock_library(
 		libraries: Vec<library_idents_select::Data>,
+	) -> Result<MockObjs, CoreError> {
+		create_mock_library_with_broken(libraries, vec![]).await
+	}
+
+	#[allow(dead_code)]
+	async fn create_mock_library_with_broken(
+		libraries: Vec<library_idents_select::Data>,
+		broken_library_ids: Vec<String>,
 	) -> Result<MockObjs, CoreError> {
 		let (tx_jobs, rx_jobs) = tokio::sync::mpsc::unbounded_channel();
 		let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
 
-		let library_provider = MockLibraryProvider { libraries };
+		let library_provider = MockLibraryProvider {
+			libraries,
+			broken_library_ids,
+		};
 		let job_submitter = MockJobControllerSubmitter {
 			tx: tx_jobs.clone(),
 		};
@@ -391,6 +669,24 @@ mod tests {
 		})
 	}
 
+	#[allow(dead_code)]
+	fn watched_library(path: &str, series: &[(&str, &str)]) -> WatchedLibrary {
+		WatchedLibrary {
+			id: "42".to_string(),
+			path: path.to_string(),
+			resolver: SeriesResolver::new(
+				path,
+				&crate::db::entity::LibraryPattern::SeriesBased,
+				None,
+			)
+			.unwrap(),
+			series: series
+				.iter()
+				.map(|(id, name)| (Path::new(path).join(name), id.to_string()))
+				.collect(),
+		}
+	}
+
 	#[allow(dead_code)]
 	fn create_test_libraries(base_dir: String) -> Vec<library_idents_select::Data> {
 		vec![library_idents_select::Data {
@@ -409,7 +705,7 @@ mod tests {
 
 		assert!(mock_objs
 			.library_watcher
-			.add_watcher(tmp_dir.clone())
+			.add_watcher(tmp_dir.clone(), WatcherBackend::Native)
 			.await
 			.is_ok());
 		let new_file = tmp_dir.join("new_file");
@@ -420,9 +716,14 @@ mod tests {
 
 		// Wait for the background thread to trigger the flush
 		tokio::time::sleep(Duration::from_millis(20)).await;
-		let (id, path) = mock_objs.jobs_receiver.try_recv().expect("Expected a job");
-		assert_eq!(id, "42");
-		assert_eq!(path, tmp_dir.to_string_lossy().to_string());
+		let (scan, _) = mock_objs.jobs_receiver.try_recv().expect("Expected a job");
+		assert_eq!(
+			scan,
+			WatcherScan::Library {
+				id: "42".to_string(),
+				path: tmp_dir.to_string_lossy().to_string(),
+			}
+		);
 	}
 
 	#[tokio::test]
@@ -435,7 +736,7 @@ mod tests {
 
 		assert!(mock_objs
 			.library_watcher
-			.add_watcher(tmp_dir.clone())
+			.add_watcher(tmp_dir.clone(), WatcherBackend::Native)
 			.await
 			.is_ok());
 		assert!(mock_objs
@@ -461,12 +762,12 @@ mod tests {
 		let mock_objs = create_mock_library(libraries).await.unwrap();
 		assert!(mock_objs
 			.library_watcher
-			.add_watcher(tmp_dir.clone())
+			.add_watcher(tmp_dir.clone(), WatcherBackend::Native)
 			.await
 			.is_ok());
 		assert!(mock_objs
 			.library_watcher
-			.add_watcher(tmp_dir.clone())
+			.add_watcher(tmp_dir.clone(), WatcherBackend::Native)
 			.await
 			.is_ok());
 	}
@@ -481,7 +782,7 @@ mod tests {
 
 		assert!(mock_objs
 			.library_watcher
-			.add_watcher(tmp_dir.clone())
+			.add_watcher(tmp_dir.clone(), WatcherBackend::Native)
 			.await
 			.is_ok());
 		let new_file = tmp_dir.join("new_file");
@@ -492,6 +793,39 @@ mod tests {
 		assert!(mock_objs.library_watcher.stop().await.is_ok());
 	}
 
+	#[tokio::test]
+	async fn test_poll_watcher() {
+		let tmp_dir = tempfile::TempDir::new().unwrap();
+		let library_path = tmp_dir.path().to_path_buf();
+		let libraries = create_test_libraries(library_path.to_string_lossy().to_string());
+
+		let mut mock_objs = create_mock_library(libraries).await.unwrap();
+		assert!(mock_objs
+			.library_watcher
+			.add_watcher(
+				library_path.clone(),
+				WatcherBackend::Poll(Duration::from_millis(10))
+			)
+			.await
+			.is_ok());
+
+		// Wait for the first snapshot to be taken before changing anything
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		std::fs::create_dir_all(library_path.join("Saga")).unwrap();
+
+		// Wait for the next poll, and then the flush
+		tokio::time::sleep(Duration::from_millis(200)).await;
+		let (scan, _) = mock_objs.jobs_receiver.try_recv().expect("Expected a job");
+		assert_eq!(
+			scan,
+			WatcherScan::Library {
+				id: "42".to_string(),
+				path: library_path.to_string_lossy().to_string(),
+			}
+		);
+		assert!(mock_objs.library_watcher.stop().await.is_ok());
+	}
+
 	#[tokio::test]
 	async fn test_start_jobs() {
 		let tmp_dir = std::env::temp_dir().join("stump_test");
@@ -510,9 +844,59 @@ mod tests {
 		.await
 		.is_ok());
 
-		let (id, path) = mock_objs.jobs_receiver.try_recv().expect("Expected a job");
-		assert_eq!(id, "42");
-		assert_eq!(path, tmp_dir.to_string_lossy().to_string());
+		let (scan, triggered_by) =
+			mock_objs.jobs_receiver.try_recv().expect("Expected a job");
+		assert_eq!(
+			scan,
+			WatcherScan::Library {
+				id: "42".to_string(),
+				path: tmp_dir.to_string_lossy().to_string(),
+			}
+		);
+		assert_eq!(triggered_by, vec![tmp_dir.join("new_file")]);
+	}
+
+	#[tokio::test]
+	async fn test_start_jobs_skips_broken_library() {
+		let broken_dir = tempfile::TempDir::new().unwrap();
+		let working_dir = tempfile::TempDir::new().unwrap();
+		let libraries = vec![
+			library_idents_select::Data {
+				id: "broken".to_string(),
+				path: broken_dir.path().to_string_lossy().to_string(),
+			},
+			library_idents_select::Data {
+				id: "working".to_string(),
+				path: working_dir.path().to_string_lossy().to_string(),
+			},
+		];
+		let paths = HashSet::from_iter(vec![
+			broken_dir.path().join("new_file"),
+			working_dir.path().join("new_file"),
+		]);
+
+		let mut mock_objs =
+			create_mock_library_with_broken(libraries, vec!["broken".to_string()])
+				.await
+				.unwrap();
+
+		assert!(LibraryWatcher::start_jobs(
+			&mock_objs.library_watcher.library_provider,
+			&mock_objs.library_watcher.job_submitter,
+			paths,
+		)
+		.await
+		.is_ok());
+
+		let (scan, _) = mock_objs.jobs_receiver.try_recv().expect("Expected a job");
+		assert_eq!(
+			scan,
+			WatcherScan::Library {
+				id: "working".to_string(),
+				path: working_dir.path().to_string_lossy().to_string(),
+			}
+		);
+		assert!(mock_objs.jobs_receiver.try_recv().is_err());
 	}
 
 	#[tokio::test]
@@ -539,4 +923,81 @@ mod tests {
 			tokio::sync::mpsc::error::TryRecvError::Empty
 		);
 	}
+
+	#[test]
+	fn test_plan_series_scan_for_new_book() {
+		let library = tempfile::TempDir::new().unwrap();
+		let library_path = library.path().to_string_lossy().to_string();
+		std::fs::create_dir_all(library.path().join("Saga")).unwrap();
+		let book = library.path().join("Saga/Saga 001.cbz");
+		std::fs::write(&book, b"").unwrap();
+
+		let watched = watched_library(&library_path, &[("saga", "Saga")]);
+		let planned = watched.plan_scans(vec![book.clone()]);
+		assert_eq!(
+			planned,
+			vec![(
+				WatcherScan::Series {
+					id: "saga".to_string(),
+					path: library.path().join("Saga").to_string_lossy().to_string(),
+				},
+				vec![book],
+			)]
+		);
+	}
+
+	#[test]
+	fn test_plan_series_scan_for_removed_book() {
+		let library = tempfile::TempDir::new().unwrap();
+		let library_path = library.path().to_string_lossy().to_string();
+		std::fs::create_dir_all(library.path().join("Saga")).unwrap();
+
+		let watched = watched_library(&library_path, &[("saga", "Saga")]);
+		let removed = library.path().join("Saga/Saga 002.cbz");
+		let planned = watched.plan_scans(vec![removed]);
+		assert_eq!(planned.len(), 1);
+		assert!(matches!(&planned[0].0, WatcherScan::Series { id, .. } if id == "saga"));
+	}
+
+	#[test]
+	fn test_plan_library_scan_for_structural_changes() {
+		let library = tempfile::TempDir::new().unwrap();
+		let library_path = library.path().to_string_lossy().to_string();
+		std::fs::create_dir_all(library.path().join("Saga")).unwrap();
+		std::fs::create_dir_all(library.path().join("Monstress")).unwrap();
+		let book = library.path().join("Saga/Saga 001.cbz");
+		std::fs::write(&book, b"").unwrap();
+
+		let watched = watched_library(&library_path, &[("saga", "Saga")]);
+		let library_scan = WatcherScan::Library {
+			id: "42".to_string(),
+			path: library_path.clone(),
+		};
+
+		// A new series directory
+		let new_series = library.path().join("Monstress");
+		let planned = watched.plan_scans(vec![book.clone(), new_series.clone()]);
+		assert_eq!(
+			planned,
+			vec![(library_scan.clone(), vec![new_series, book])]
+		);
+
+		// A removed series directory
+		let removed_series = library.path().join("Old");
+		let watched = watched_library(&library_path, &[("old", "Old")]);
+		let planned = watched.plan_scans(vec![removed_series.clone()]);
+		assert_eq!(planned, vec![(library_scan, vec![removed_series])]);
+	}
+
+	#[test]
+	fn test_plan_skips_ignored_files() {
+		let library = tempfile::TempDir::new().unwrap();
+		let library_path = library.path().to_string_lossy().to_string();
+		std::fs::create_dir_all(library.path().join("Saga")).unwrap();
+		let ignored = library.path().join("Saga/.DS_Store");
+		std::fs::write(&ignored, b"").unwrap();
+
+		let watched = watched_library(&library_path, &[("saga", "Saga")]);
+		assert!(watched.plan_scans(vec![ignored]).is_empty());
+	}
 }
@@ -1,15 +1,24 @@
+mod diff;
 mod library_scan_job;
 mod library_watcher;
+mod moved_media;
 mod options;
+mod poll_watcher;
+mod series_detection;
 mod series_scan_job;
 mod utils;
 mod walk;
 
+pub use diff::ScanDiff;
 pub use library_scan_job::{LibraryScanJob, LibraryScanOutput};
-pub use library_watcher::LibraryWatcher;
+pub use library_watcher::{LibraryWatcher, WatcherBackend};
+pub use moved_media::MovedMedia;
 pub use options::{
 	CustomVisit, CustomVisitResult, LastLibraryScan, LibraryScanRecord, ScanConfig,
 	ScanOptions,
 };
+pub use series_detection::{
+	detect_series, DetectedSeries, SeriesDetection, SeriesResolver,
+};
 pub use series_scan_job::{SeriesScanJob, SeriesScanOutput};
 pub use walk::{walk_library, walk_series, WalkedLibrary, WalkedSeries, WalkerCtx};
@@ -0,0 +1,363 @@
+use std::collections::HashSet;
+
+use prisma_client_rust::{
+	chrono::DateTime, or, prisma_errors::query_engine::UniqueKeyViolation,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::fs;
+use utoipa::ToSchema;
+
+use crate::{
+	db::{entity::Media, FileStatus},
+	error::CoreResult,
+	prisma::{media, series, PrismaClient},
+};
+
+/// The minimum similarity between the names of two files of the same size for them to be
+/// considered the same book, when neither has a hash to compare
+const NAME_SIMILARITY_THRESHOLD: f64 = 0.6;
+
+/// The number of books to find candidates for per query. Each book adds up to three
+/// parameters, which keeps a query well below SQLite's limit on bound parameters.
+const FIND_CHUNK_SIZE: usize = 250;
+
+/// A media entity which was found at a new path during a scan, rather than being marked as
+/// missing and created again
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Type, ToSchema)]
+pub struct MovedMedia {
+	/// The ID of the media which was moved
+	pub id: String,
+	/// The path the media was previously at
+	pub from: String,
+	/// The path the media was found at
+	pub to: String,
+}
+
+/// An existing media entity which a new file may be a moved or renamed copy of
+#[derive(Debug, Clone)]
+struct MoveCandidate {
+	id: String,
+	name: String,
+	extension: String,
+	size: i64,
+	hash: Option<String>,
+	koreader_hash: Option<String>,
+	path: String,
+}
+
+impl MoveCandidate {
+	/// Whether the candidate has the same hash, or KOReader hash, as a book
+	fn hash_matches(&self, book: &Media) -> bool {
+		let same_hash = book.hash.is_some() && self.hash == book.hash;
+		let same_koreader_hash =
+			book.koreader_hash.is_some() && self.koreader_hash == book.koreader_hash;
+		same_hash || same_koreader_hash
+	}
+
+	/// Whether the candidate is a file of the same size and type as a book. Files with hashes
+	/// which differ are not the same file.
+	fn file_matches(&self, book: &Media) -> bool {
+		let hashes_differ = matches!(
+			(&self.hash, &book.hash),
+			(Some(a), Some(b)) if a != b
+		);
+		!hashes_differ
+			&& self.size == book.size
+			&& self.extension.eq_ignore_ascii_case(&book.extension)
+	}
+}
+
+impl From<media::Data> for MoveCandidate {
+	fn from(data: media::Data) -> Self {
+		Self {
+			id: data.id,
+			name: data.name,
+			extension: data.extension,
+			size: data.size,
+			hash: data.hash,
+			koreader_hash: data.koreader_hash,
+			path: data.path,
+		}
+	}
+}
+
+/// The media in a library which are no longer at their recorded path, and so may be matched
+/// against newly discovered files
+#[derive(Debug, Default)]
+pub(crate) struct MoveCandidates {
+	candidates: Vec<MoveCandidate>,
+}
+
+impl MoveCandidates {
+	/// Find the media in a library which could have been moved to one of the given books.
+	/// Media is only considered if its file no longer exists, regardless of whether it has
+	/// been marked as missing yet, since the series it was moved out of may not have been
+	/// scanned yet. The disk is only checked for media which could match one of the books.
+	pub async fn find(
+		db: &PrismaClient,
+		library_id: &str,
+		books: &[Media],
+	) -> CoreResult<Self> {
+		if books.is_empty() {
+			return Ok(Self::default());
+		}
+
+		let mut candidates = Vec::new();
+		let mut seen_ids = HashSet::new();
+		for chunk in books.chunks(FIND_CHUNK_SIZE) {
+			let hashes = chunk
+				.iter()
+				.filter_map(|book| book.hash.clone())
+				.collect::<Vec<_>>();
+			let koreader_hashes = chunk
+				.iter()
+				.filter_map(|book| book.koreader_hash.clone())
+				.collect::<Vec<_>>();
+			let sizes = chunk
+				.iter()
+				.map(|book| book.size)
+				.collect::<HashSet<_>>()
+				.into_iter()
+				.collect::<Vec<_>>();
+
+			let chunk_candidates = db
+				.media()
+				.find_many(vec![
+					media::series::is(vec![series::library_id::equals(Some(
+						library_id.to_string(),
+					))]),
+					or![
+						media::hash::in_vec(hashes),
+						media::koreader_hash::in_vec(koreader_hashes),
+						media::size::in_vec(sizes),
+					],
+				])
+				.exec()
+				.await?;
+			// The same media may match books of more than one chunk
+			let chunk_candidates = chunk_candidates
+				.into_iter()
+				.filter(|data| seen_ids.insert(data.id.clone()))
+				.map(MoveCandidate::from)
+				.filter(|candidate| {
+					chunk.iter().any(|book| {
+						candidate.hash_matches(book) || candidate.file_matches(book)
+					})
+				})
+				.collect::<Vec<_>>();
+			for candidate in chunk_candidates {
+				// A file which can't be checked is not assumed to be gone
+				if let Ok(false) = fs::try_exists(&candidate.path).await {
+					candidates.push(candidate);
+				}
+			}
+		}
+		tracing::trace!(count = candidates.len(), "Found candidates for moved media");
+
+		Ok(Self { candidates })
+	}
+
+	/// Find the existing media a new book was moved from, if any. A matched candidate is
+	/// removed, so it can't be claimed by more than one book.
+	pub fn take_match(&mut self, book: &Media) -> Option<MovedMedia> {
+		let index = self
+			.candidates
+			.iter()
+			.position(|candidate| candidate.hash_matches(book))
+			.or_else(|| {
+				self.candidates
+					.iter()
+					.enumerate()
+					.filter(|(_, candidate)| candidate.file_matches(book))
+					.map(|(index, candidate)| {
+						(index, name_similarity(&candidate.name, &book.name))
+					})
+					.filter(|(_, similarity)| *similarity >= NAME_SIMILARITY_THRESHOLD)
+					.max_by(|(_, a), (_, b)| a.total_cmp(b))
+					.map(|(index, _)| index)
+			})?;
+
+		let candidate = self.candidates.swap_remove(index);
+		Some(MovedMedia {
+			id: candidate.id,
+			from: candidate.path,
+			to: book.path.clone(),
+		})
+	}
+}
+
+/// The similarity of two names, from 0 (entirely different) to 1 (identical), ignoring case
+fn name_similarity(a: &str, b: &str) -> f64 {
+	let a = a.to_lowercase().chars().collect::<Vec<_>>();
+	let b = b.to_lowercase().chars().collect::<Vec<_>>();
+	let longest = a.len().max(b.len());
+	if longest == 0 {
+		return 1.0;
+	}
+
+	// Levenshtein distance, keeping a single row of the matrix
+	let mut row = (0..=b.len()).collect::<Vec<_>>();
+	for (i, a_char) in a.iter().enumerate() {
+		let mut previous_diagonal = row[0];
+		row[0] = i + 1;
+		for (j, b_char) in b.iter().enumerate() {
+			let substitution = previous_diagonal + usize::from(a_char != b_char);
+			previous_diagonal = row[j + 1];
+			row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+		}
+	}
+
+	1.0 - row[b.len()] as f64 / longest as f64
+}
+
+/// Re-point an existing media entity at the file it was moved to. Everything which hangs off
+/// of the media (e.g. reading progress, bookmarks, tags) is kept, and only the file details
+/// are updated from the newly built book.
+pub(crate) async fn relocate_media(
+	db: &PrismaClient,
+	moved: &MovedMedia,
+	book: Media,
+) -> CoreResult<Media> {
+	let modified_at = book
+		.modified_at
+		.as_deref()
+		.and_then(|date| DateTime::parse_from_rfc3339(date).ok());
+
+	let result = db
+		.media()
+		.update(
+			media::id::equals(moved.id.clone()),
+			vec![
+				media::name::set(book.name),
+				media::size::set(book.size),
+				media::extension::set(book.extension),
+				media::pages::set(book.pages),
+				media::hash::set(book.hash),
+				media::koreader_hash::set(book.koreader_hash),
+				media::path::set(book.path),
+				media::modified_at::set(modified_at),
+				media::status::set(FileStatus::Ready.to_string()),
+				media::deleted_at::set(None),
+				media::series::connect(series::id::equals(book.series_id)),
+			],
+		)
+		.exec()
+		.await;
+
+	match result {
+		Ok(updated_media) => Ok(Media::from(updated_media)),
+		Err(error) if error.is_prisma_error::<UniqueKeyViolation>() => {
+			tracing::warn!(?moved, "A media entity already exists at the new path");
+			Err(error.into())
+		},
+		Err(error) => Err(error.into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn candidate(id: &str, name: &str, size: i64, hash: Option<&str>) -> MoveCandidate {
+		MoveCandidate {
+			id: id.to_string(),
+			name: name.to_string(),
+			extension: "cbz".to_string(),
+			size,
+			hash: hash.map(String::from),
+			koreader_hash: None,
+			path: format!("/old/{name}.cbz"),
+		}
+	}
+
+	fn book(name: &str, size: i64, hash: Option<&str>) -> Media {
+		Media {
+			name: name.to_string(),
+			extension: "cbz".to_string(),
+			size,
+			hash: hash.map(String::from),
+			path: format!("/new/{name}.cbz"),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_name_similarity() {
+		assert_eq!(name_similarity("Batman 001", "batman 001"), 1.0);
+		assert!(name_similarity("Batman 001", "Batman #001") > NAME_SIMILARITY_THRESHOLD);
+		assert!(
+			name_similarity("Batman 001", "Superman 017") < NAME_SIMILARITY_THRESHOLD
+		);
+		assert_eq!(name_similarity("", ""), 1.0);
+	}
+
+	#[test]
+	fn test_match_by_hash() {
+		let mut candidates = MoveCandidates {
+			candidates: vec![
+				candidate("a", "Batman 001", 100, Some("hash-a")),
+				candidate("b", "Something else", 200, Some("hash-b")),
+			],
+		};
+
+		let moved = candidates
+			.take_match(&book("Renamed entirely", 200, Some("hash-b")))
+			.expect("Should match by hash");
+		assert_eq!(moved.id, "b");
+		assert_eq!(moved.from, "/old/Something else.cbz");
+		assert_eq!(moved.to, "/new/Renamed entirely.cbz");
+
+		// A candidate can only be claimed once
+		assert!(candidates
+			.take_match(&book("Renamed entirely", 200, Some("hash-b")))
+			.is_none());
+	}
+
+	#[test]
+	fn test_candidate_could_match() {
+		let with_hash = candidate("a", "Batman 001", 100, Some("hash-a"));
+		assert!(with_hash.hash_matches(&book("Anything", 999, Some("hash-a"))));
+		assert!(!with_hash.hash_matches(&book("Batman 001", 100, None)));
+		assert!(with_hash.file_matches(&book("Anything", 100, None)));
+		assert!(!with_hash.file_matches(&book("Batman 001", 100, Some("hash-b"))));
+		assert!(!with_hash.file_matches(&book("Batman 001", 101, None)));
+		assert!(!with_hash.file_matches(&Media {
+			extension: "pdf".to_string(),
+			..book("Batman 001", 100, None)
+		}));
+	}
+
+	#[test]
+	fn test_match_by_size_and_name() {
+		let mut candidates = MoveCandidates {
+			candidates: vec![
+				candidate("a", "Batman 001", 100, None),
+				candidate("b", "Batman 002", 100, None),
+			],
+		};
+
+		let moved = candidates
+			.take_match(&book("Batman 002 (v2)", 100, None))
+			.expect("Should match by size and name");
+		assert_eq!(moved.id, "b");
+
+		assert!(candidates
+			.take_match(&book("Batman 001", 101, None))
+			.is_none());
+		assert!(candidates
+			.take_match(&book("Detective Comics 1000", 100, None))
+			.is_none());
+	}
+
+	#[test]
+	fn test_no_match_when_hashes_differ() {
+		let mut candidates = MoveCandidates {
+			candidates: vec![candidate("a", "Batman 001", 100, Some("hash-a"))],
+		};
+
+		assert!(candidates
+			.take_match(&book("Batman 001", 100, Some("hash-z")))
+			.is_none());
+	}
+}
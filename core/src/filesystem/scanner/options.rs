@@ -82,6 +82,10 @@ impl BookVisitResult {
 pub struct ScanOptions {
 	#[serde(default)]
 	pub config: ScanConfig,
+	/// Whether the scan should only walk the filesystem and record what it would change, as
+	/// a [`ScanDiff`](super::ScanDiff) in the job output, without touching the database
+	#[serde(default)]
+	pub dry_run: bool,
 }
 
 #[derive(Default, Debug, Clone, Copy, Deserialize, Serialize, Type, ToSchema)]
@@ -194,6 +198,7 @@ mod tests {
 
 		let options = ScanOptions {
 			config: ScanConfig::BuildChanged,
+			dry_run: false,
 		};
 		assert_eq!(options.book_operation(), None);
 
@@ -201,6 +206,7 @@ mod tests {
 			config: ScanConfig::ForceRebuild {
 				force_rebuild: true,
 			},
+			dry_run: false,
 		};
 		assert_eq!(options.book_operation(), Some(BookVisitOperation::Rebuild));
 
@@ -209,6 +215,7 @@ mod tests {
 				regen_meta: true,
 				regen_hashes: false,
 			}),
+			dry_run: false,
 		};
 		assert_eq!(
 			options.book_operation(),
@@ -228,6 +235,7 @@ mod tests {
 					config: ScanConfig::ForceRebuild {
 						force_rebuild: true,
 					},
+					dry_run: false,
 				})
 				.unwrap(),
 			),
@@ -277,20 +285,22 @@ mod tests {
 			serde_json::to_string(&ScanOptions {
 				config: ScanConfig::ForceRebuild {
 					force_rebuild: true
-				}
+				},
+				dry_run: false,
 			})
 			.unwrap(),
-			r#"{"config":{"force_rebuild":true}}"#
+			r#"{"config":{"force_rebuild":true},"dry_run":false}"#
 		);
 
 		assert_eq!(
 			serde_json::to_string(&ScanOptions {
 				config: ScanConfig::ForceRebuild {
 					force_rebuild: false
-				}
+				},
+				dry_run: false,
 			})
 			.unwrap(),
-			r#"{"config":{"force_rebuild":false}}"#
+			r#"{"config":{"force_rebuild":false},"dry_run":false}"#
 		);
 
 		assert_eq!(
@@ -298,10 +308,11 @@ mod tests {
 				config: ScanConfig::Custom(CustomVisit {
 					regen_meta: true,
 					regen_hashes: false
-				})
+				}),
+				dry_run: false,
 			})
 			.unwrap(),
-			r#"{"config":{"regen_meta":true,"regen_hashes":false}}"#
+			r#"{"config":{"regen_meta":true,"regen_hashes":false},"dry_run":false}"#
 		);
 
 		assert_eq!(
@@ -309,10 +320,11 @@ mod tests {
 				config: ScanConfig::Custom(CustomVisit {
 					regen_meta: false,
 					regen_hashes: true
-				})
+				}),
+				dry_run: false,
 			})
 			.unwrap(),
-			r#"{"config":{"regen_meta":false,"regen_hashes":true}}"#
+			r#"{"config":{"regen_meta":false,"regen_hashes":true},"dry_run":false}"#
 		);
 
 		assert_eq!(
@@ -320,10 +332,11 @@ mod tests {
 				config: ScanConfig::Custom(CustomVisit {
 					regen_meta: true,
 					regen_hashes: true
-				})
+				}),
+				dry_run: false,
 			})
 			.unwrap(),
-			r#"{"config":{"regen_meta":true,"regen_hashes":true}}"#
+			r#"{"config":{"regen_meta":true,"regen_hashes":true},"dry_run":false}"#
 		);
 	}
 
@@ -390,6 +403,7 @@ mod tests {
 			config: ScanConfig::ForceRebuild {
 				force_rebuild: false,
 			},
+			dry_run: false,
 		};
 		assert!(options.is_default());
 		assert!(options.book_operation().is_none());
@@ -399,6 +413,7 @@ mod tests {
 				regen_meta: false,
 				regen_hashes: false,
 			}),
+			dry_run: false,
 		};
 		assert!(options.config.is_useless());
 		assert!(options.book_operation().is_none());
@@ -408,6 +423,7 @@ mod tests {
 				regen_meta: true,
 				regen_hashes: false,
 			}),
+			dry_run: false,
 		};
 		assert!(!options.config.is_useless());
 
@@ -416,11 +432,13 @@ mod tests {
 				regen_meta: false,
 				regen_hashes: true,
 			}),
+			dry_run: false,
 		};
 		assert!(!options.config.is_useless());
 
 		let options = ScanOptions {
 			config: ScanConfig::BuildChanged,
+			dry_run: false,
 		};
 		assert!(options.book_operation().is_none());
 		assert!(options.is_default());
@@ -435,5 +453,15 @@ mod tests {
 		let options = r#"{"config": null}"#;
 		let options: ScanOptions = serde_json::from_str(options).unwrap();
 		assert!(options.is_default());
+		assert!(!options.dry_run);
+	}
+
+	#[test]
+	fn test_deserialize_dry_run() {
+		let options: ScanOptions =
+			serde_json::from_str(r#"{"config":{"force_rebuild":true},"dry_run":true}"#)
+				.unwrap();
+		assert!(options.dry_run);
+		assert_eq!(options.book_operation(), Some(BookVisitOperation::Rebuild));
 	}
 }
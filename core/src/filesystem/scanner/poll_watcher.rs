@@ -0,0 +1,325 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, PoisonError},
+	time::{Duration, SystemTime},
+};
+
+use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
+use walkdir::WalkDir;
+
+use super::library_watcher::LibraryWatcherCommand;
+
+/// The state of a single entry in a [`DirectorySnapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EntryState {
+	is_dir: bool,
+	modified: Option<SystemTime>,
+	size: u64,
+}
+
+/// A snapshot of a directory tree, holding only the modification time and size of each entry
+/// so that it is cheap to take, even over a network share
+#[derive(Debug, Default)]
+pub(crate) struct DirectorySnapshot(HashMap<PathBuf, EntryState>);
+
+impl DirectorySnapshot {
+	/// Walk the directory tree and record the state of each entry in it. Returns `None` when
+	/// the root can't be read, e.g. the share it is on is unreachable. This is blocking, and
+	/// should be called from a blocking context.
+	pub(crate) fn take(root: &Path) -> Option<Self> {
+		if let Err(error) = std::fs::read_dir(root) {
+			tracing::warn!(?error, ?root, "Failed to read polled directory");
+			return None;
+		}
+
+		let entries = WalkDir::new(root)
+			.min_depth(1)
+			.into_iter()
+			.filter_map(Result::ok)
+			.filter_map(|entry| {
+				let metadata = entry.metadata().ok()?;
+				let state = EntryState {
+					is_dir: metadata.is_dir(),
+					modified: metadata.modified().ok(),
+					size: metadata.len(),
+				};
+				Some((entry.into_path(), state))
+			})
+			.collect();
+
+		Some(Self(entries))
+	}
+
+	/// Whether the snapshot has any files, as opposed to only (empty) directories
+	fn has_files(&self) -> bool {
+		self.0.values().any(|state| !state.is_dir)
+	}
+
+	/// Whether every file vanished between this snapshot and a newer one. This is far more
+	/// likely to be an unmounted share (which leaves an empty mount point behind) than a real
+	/// deletion, and diffing it would have the scan trash the whole library.
+	pub(crate) fn lost_all_files(&self, newer: &DirectorySnapshot) -> bool {
+		self.has_files() && !newer.has_files()
+	}
+
+	/// Get the paths which changed between this snapshot and a newer one. Files are changed
+	/// when they were added, removed, or their modification time or size differ. Directories
+	/// are only changed when they were added or removed, since their modification time
+	/// changes whenever a file is added to them.
+	pub(crate) fn changed_paths(&self, newer: &DirectorySnapshot) -> Vec<PathBuf> {
+		let added_or_modified = newer.0.iter().filter_map(|(path, state)| {
+			let changed = match self.0.get(path) {
+				Some(previous) if previous.is_dir && state.is_dir => false,
+				Some(previous) => previous != state,
+				None => true,
+			};
+			changed.then(|| path.clone())
+		});
+		let removed = self
+			.0
+			.keys()
+			.filter(|path| !newer.0.contains_key(*path))
+			.cloned();
+
+		let mut changed = added_or_modified.chain(removed).collect::<Vec<_>>();
+		changed.sort();
+		changed
+	}
+}
+
+/// Watches directories by periodically diffing snapshots of them, for filesystems which do not
+/// emit native events. The changed paths are sent as [`LibraryWatcherCommand::ChangedFiles`],
+/// the same as native events, so they go through the same debouncing and job submission.
+pub(crate) struct PollWatcher {
+	sender: UnboundedSender<LibraryWatcherCommand>,
+	pollers: HashMap<PathBuf, Poller>,
+}
+
+/// The task polling a single directory
+struct Poller {
+	handle: JoinHandle<()>,
+	interval: Duration,
+	/// The last snapshot of the directory, which outlives the task so a new interval doesn't
+	/// lose the changes made since the last poll
+	snapshot: Arc<Mutex<Option<DirectorySnapshot>>>,
+}
+
+impl PollWatcher {
+	pub(crate) fn new(sender: UnboundedSender<LibraryWatcherCommand>) -> Self {
+		Self {
+			sender,
+			pollers: HashMap::new(),
+		}
+	}
+
+	/// Start polling the directory at the given interval. A directory which is already polled
+	/// keeps its last snapshot, so only a new path starts from a fresh one.
+	pub(crate) fn watch(&mut self, path: PathBuf, interval: Duration) {
+		let snapshot = match self.pollers.remove(&path) {
+			Some(poller) if poller.interval == interval => {
+				self.pollers.insert(path, poller);
+				return;
+			},
+			Some(poller) => {
+				poller.handle.abort();
+				poller.snapshot
+			},
+			None => Arc::new(Mutex::new(None)),
+		};
+
+		let sender = self.sender.clone();
+		let root = path.clone();
+		let task_snapshot = snapshot.clone();
+		let handle = tokio::spawn(async move {
+			let take_snapshot = |root: PathBuf| async move {
+				tokio::task::spawn_blocking(move || DirectorySnapshot::take(&root))
+					.await
+					.ok()
+					.flatten()
+			};
+			let lock_snapshot =
+				|| task_snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+
+			let is_new = lock_snapshot().is_none();
+			if is_new {
+				if let Some(initial) = take_snapshot(root.clone()).await {
+					*lock_snapshot() = Some(initial);
+				}
+			}
+
+			loop {
+				tokio::time::sleep(interval).await;
+
+				// An unreachable share is skipped rather than treated as every file having been
+				// removed, and the last good snapshot is kept to diff against once it is back
+				let Some(next_snapshot) = take_snapshot(root.clone()).await else {
+					tracing::warn!(?root, "Polled library is unreachable");
+					continue;
+				};
+				let changed = {
+					let mut snapshot = lock_snapshot();
+					let previous = snapshot.as_ref();
+					if previous
+						.is_some_and(|previous| previous.lost_all_files(&next_snapshot))
+					{
+						tracing::warn!(
+							?root,
+							"Every file of the polled library vanished, assuming it is unmounted"
+						);
+						continue;
+					}
+
+					let changed = previous
+						.map(|previous| previous.changed_paths(&next_snapshot))
+						.unwrap_or_default();
+					*snapshot = Some(next_snapshot);
+					changed
+				};
+				if changed.is_empty() {
+					continue;
+				}
+
+				tracing::debug!(
+					?root,
+					count = changed.len(),
+					"Polling found changed paths"
+				);
+				if sender
+					.send(LibraryWatcherCommand::ChangedFiles(changed))
+					.is_err()
+				{
+					break;
+				}
+			}
+		});
+
+		self.pollers.insert(
+			path,
+			Poller {
+				handle,
+				interval,
+				snapshot,
+			},
+		);
+	}
+
+	/// Whether the directory is being polled
+	pub(crate) fn is_watching(&self, path: &Path) -> bool {
+		self.pollers.contains_key(path)
+	}
+
+	/// Stop polling the directory. Returns whether it was being polled.
+	pub(crate) fn unwatch(&mut self, path: &Path) -> bool {
+		self.pollers
+			.remove(path)
+			.map(|poller| poller.handle.abort())
+			.is_some()
+	}
+
+	pub(crate) fn stop(&mut self) {
+		for (_, poller) in self.pollers.drain() {
+			poller.handle.abort();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use tempfile::TempDir;
+
+	use super::*;
+
+	fn relative(root: &TempDir, paths: Vec<PathBuf>) -> Vec<String> {
+		paths
+			.into_iter()
+			.map(|path| {
+				path.strip_prefix(root.path())
+					.unwrap()
+					.to_string_lossy()
+					.to_string()
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_unchanged_snapshot() {
+		let root = TempDir::new().unwrap();
+		fs::create_dir_all(root.path().join("Saga")).unwrap();
+		fs::write(root.path().join("Saga/Saga 001.cbz"), b"book").unwrap();
+
+		let snapshot = DirectorySnapshot::take(root.path()).unwrap();
+		let next_snapshot = DirectorySnapshot::take(root.path()).unwrap();
+		assert!(snapshot.changed_paths(&next_snapshot).is_empty());
+	}
+
+	#[test]
+	fn test_changed_snapshot() {
+		let root = TempDir::new().unwrap();
+		fs::create_dir_all(root.path().join("Saga")).unwrap();
+		fs::write(root.path().join("Saga/Saga 001.cbz"), b"book").unwrap();
+		fs::write(root.path().join("Saga/Saga 002.cbz"), b"book").unwrap();
+
+		let snapshot = DirectorySnapshot::take(root.path()).unwrap();
+
+		fs::write(root.path().join("Saga/Saga 001.cbz"), b"a larger book").unwrap();
+		fs::remove_file(root.path().join("Saga/Saga 002.cbz")).unwrap();
+		fs::write(root.path().join("Saga/Saga 003.cbz"), b"book").unwrap();
+		fs::create_dir_all(root.path().join("Monstress")).unwrap();
+
+		let next_snapshot = DirectorySnapshot::take(root.path()).unwrap();
+		assert_eq!(
+			relative(&root, snapshot.changed_paths(&next_snapshot)),
+			vec![
+				"Monstress",
+				"Saga/Saga 001.cbz",
+				"Saga/Saga 002.cbz",
+				"Saga/Saga 003.cbz",
+			]
+		);
+	}
+
+	#[test]
+	fn test_removed_directory() {
+		let root = TempDir::new().unwrap();
+		fs::create_dir_all(root.path().join("Saga")).unwrap();
+		fs::write(root.path().join("Saga/Saga 001.cbz"), b"book").unwrap();
+
+		let snapshot = DirectorySnapshot::take(root.path()).unwrap();
+		fs::remove_dir_all(root.path().join("Saga")).unwrap();
+		let next_snapshot = DirectorySnapshot::take(root.path()).unwrap();
+
+		assert_eq!(
+			relative(&root, snapshot.changed_paths(&next_snapshot)),
+			vec!["Saga", "Saga/Saga 001.cbz"]
+		);
+	}
+
+	#[test]
+	fn test_unreachable_root() {
+		let root = TempDir::new().unwrap();
+		assert!(DirectorySnapshot::take(&root.path().join("unmounted")).is_none());
+	}
+
+	#[test]
+	fn test_lost_all_files() {
+		let root = TempDir::new().unwrap();
+		fs::create_dir_all(root.path().join("Saga")).unwrap();
+		fs::write(root.path().join("Saga/Saga 001.cbz"), b"book").unwrap();
+		fs::write(root.path().join("Saga/Saga 002.cbz"), b"book").unwrap();
+
+		let snapshot = DirectorySnapshot::take(root.path()).unwrap();
+
+		fs::remove_file(root.path().join("Saga/Saga 002.cbz")).unwrap();
+		let next_snapshot = DirectorySnapshot::take(root.path()).unwrap();
+		assert!(!snapshot.lost_all_files(&next_snapshot));
+
+		// An unmounted share typically leaves its (empty) mount point directory behind
+		fs::remove_file(root.path().join("Saga/Saga 001.cbz")).unwrap();
+		let empty_snapshot = DirectorySnapshot::take(root.path()).unwrap();
+		assert!(next_snapshot.lost_all_files(&empty_snapshot));
+		assert!(!empty_snapshot.lost_all_files(&empty_snapshot));
+	}
+}
@@ -0,0 +1,371 @@
+use std::{
+	collections::BTreeMap,
+	path::{Component, Path, PathBuf},
+};
+
+use globset::GlobSet;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use utoipa::ToSchema;
+use walkdir::WalkDir;
+
+use crate::{db::entity::LibraryPattern, filesystem::PathUtils, CoreError, CoreResult};
+
+/// How the series of a collection-based library are detected. When a library is
+/// collection-based without a strategy, each top-level directory is a series.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type, ToSchema)]
+#[serde(tag = "strategy")]
+pub enum SeriesDetection {
+	/// Each directory at the given depth below the library root is a series, and all books
+	/// nested beneath it belong to it. E.g. a depth of 2 for a `publisher/series/volume` tree
+	#[serde(rename = "FOLDER_DEPTH")]
+	FolderDepth { depth: usize },
+	/// The nearest directory above a book which contains a `series.json` file is its series
+	#[serde(rename = "SERIES_JSON")]
+	SeriesJson,
+	/// The shallowest directory above a book whose path relative to the library root
+	/// (separated by `/`) matches the pattern is its series
+	#[serde(rename = "PATH_PATTERN")]
+	PathPattern { pattern: String },
+}
+
+impl SeriesDetection {
+	/// Validate the strategy, e.g. that the pattern is a valid regular expression
+	pub fn validate(&self) -> CoreResult<()> {
+		match self {
+			SeriesDetection::FolderDepth { depth } if *depth == 0 => Err(
+				CoreError::BadRequest("The folder depth must be at least 1".to_string()),
+			),
+			SeriesDetection::PathPattern { pattern } => Regex::new(pattern)
+				.map(|_| ())
+				.map_err(|e| CoreError::BadRequest(format!("Invalid path pattern: {e}"))),
+			_ => Ok(()),
+		}
+	}
+
+	/// Serialize the strategy to a byte vector, which gets dumped into the database
+	pub fn as_bytes(&self) -> CoreResult<Vec<u8>> {
+		serde_json::to_vec(self).map_err(|error| {
+			tracing::error!(?error, "Failed to serialize series detection");
+			error.into()
+		})
+	}
+}
+
+impl TryFrom<Vec<u8>> for SeriesDetection {
+	type Error = CoreError;
+
+	fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+		serde_json::from_slice(&value).map_err(|error| {
+			tracing::error!(?error, "Failed to deserialize series detection");
+			error.into()
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+enum ResolveStrategy {
+	/// The directory directly containing a book is its series
+	Parent,
+	FolderDepth(usize),
+	SeriesJson,
+	PathPattern(Regex),
+}
+
+/// Resolves which series directory a book belongs to, according to the pattern and series
+/// detection strategy of its library
+#[derive(Debug, Clone)]
+pub struct SeriesResolver {
+	library_path: PathBuf,
+	strategy: ResolveStrategy,
+}
+
+impl SeriesResolver {
+	pub fn new(
+		library_path: impl Into<PathBuf>,
+		pattern: &LibraryPattern,
+		detection: Option<&SeriesDetection>,
+	) -> CoreResult<Self> {
+		let strategy = match (pattern, detection) {
+			(LibraryPattern::SeriesBased, _) => ResolveStrategy::Parent,
+			(LibraryPattern::CollectionBased, None) => ResolveStrategy::FolderDepth(1),
+			(LibraryPattern::CollectionBased, Some(detection)) => {
+				detection.validate()?;
+				match detection {
+					SeriesDetection::FolderDepth { depth } => {
+						ResolveStrategy::FolderDepth(*depth)
+					},
+					SeriesDetection::SeriesJson => ResolveStrategy::SeriesJson,
+					SeriesDetection::PathPattern { pattern } => {
+						ResolveStrategy::PathPattern(Regex::new(pattern).map_err(
+							|e| {
+								CoreError::BadRequest(format!(
+									"Invalid path pattern: {e}"
+								))
+							},
+						)?)
+					},
+				}
+			},
+		};
+
+		Ok(Self {
+			library_path: library_path.into(),
+			strategy,
+		})
+	}
+
+	/// Get the path of the series directory the book at the given path belongs to. Returns
+	/// `None` if the book is not within the library.
+	pub fn series_for_book(&self, book: &Path) -> Option<PathBuf> {
+		let parent = book.parent()?;
+		let segments = parent
+			.strip_prefix(&self.library_path)
+			.ok()?
+			.components()
+			.filter_map(|component| match component {
+				Component::Normal(segment) => Some(segment),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		let at_depth = |depth: usize| {
+			segments[..depth]
+				.iter()
+				.fold(self.library_path.clone(), |path, segment| {
+					path.join(segment)
+				})
+		};
+		// Books shallower than any series directory are grouped by the directory they are in,
+		// the same way books in the root of a collection-based library are
+		let top_level = || at_depth(segments.len().min(1));
+
+		let series_path = match &self.strategy {
+			ResolveStrategy::Parent => parent.to_path_buf(),
+			ResolveStrategy::FolderDepth(depth) if segments.len() >= *depth => {
+				at_depth(*depth)
+			},
+			ResolveStrategy::FolderDepth(_) => parent.to_path_buf(),
+			ResolveStrategy::SeriesJson => (1..=segments.len())
+				.rev()
+				.map(at_depth)
+				.find(|path| path.join("series.json").exists())
+				.unwrap_or_else(top_level),
+			ResolveStrategy::PathPattern(regex) => (1..=segments.len())
+				.find(|depth| {
+					let relative_path = segments[..*depth]
+						.iter()
+						.map(|segment| segment.to_string_lossy())
+						.collect::<Vec<_>>()
+						.join("/");
+					regex.is_match(&relative_path)
+				})
+				.map(at_depth)
+				.unwrap_or_else(top_level),
+		};
+
+		Some(series_path)
+	}
+}
+
+/// The series found by walking a library with a [`SeriesResolver`]
+#[derive(Debug, Default)]
+pub struct DetectedSeries {
+	/// The paths of the series, with the number of books which belong to each
+	pub series: BTreeMap<PathBuf, u64>,
+	/// The total number of directories seen during the walk
+	pub seen_directories: u64,
+	/// The number of directories that were ignored via ignore rules
+	pub ignored_directories: u64,
+}
+
+/// Walk the entire library, and group every book in it into the series it belongs to. This
+/// is blocking, and should be called from a blocking context.
+pub fn detect_series(
+	library_path: &Path,
+	ignore_rules: &GlobSet,
+	resolver: &SeriesResolver,
+) -> DetectedSeries {
+	let mut detected = DetectedSeries::default();
+
+	let mut walker = WalkDir::new(library_path).into_iter();
+	while let Some(entry) = walker.next() {
+		let Ok(entry) = entry else {
+			continue;
+		};
+		let path = entry.path();
+		if entry.file_type().is_dir() {
+			detected.seen_directories += 1;
+			if entry.depth() > 0 && ignore_rules.is_match(path) {
+				detected.ignored_directories += 1;
+				walker.skip_current_dir();
+			}
+			continue;
+		}
+		if path.is_default_ignored() || ignore_rules.is_match(path) {
+			continue;
+		}
+		if let Some(series_path) = resolver.series_for_book(path) {
+			*detected.series.entry(series_path).or_default() += 1;
+		}
+	}
+
+	detected
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use globset::GlobSetBuilder;
+	use tempfile::TempDir;
+
+	use super::*;
+
+	/// Create a library with a `publisher/series/volume` tree, along with a book in the root
+	fn nested_library() -> TempDir {
+		let dir = TempDir::new().unwrap();
+		let books = [
+			"root.cbz",
+			"Marvel/loose.cbz",
+			"Marvel/Spider-Man/Volume 1/001.cbz",
+			"Marvel/Spider-Man/Volume 1/002.cbz",
+			"Marvel/Spider-Man/Volume 2/003.cbz",
+			"Marvel/X-Men/001.cbz",
+			"DC/Batman/Volume 1/001.cbz",
+		];
+		for book in books {
+			let path = dir.path().join(book);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, b"").unwrap();
+		}
+		dir
+	}
+
+	fn detect(
+		library: &TempDir,
+		detection: Option<SeriesDetection>,
+	) -> Vec<(String, u64)> {
+		let resolver = SeriesResolver::new(
+			library.path(),
+			&LibraryPattern::CollectionBased,
+			detection.as_ref(),
+		)
+		.unwrap();
+		detect_series(library.path(), &GlobSet::empty(), &resolver)
+			.series
+			.into_iter()
+			.map(|(path, count)| {
+				let relative = path.strip_prefix(library.path()).unwrap();
+				(relative.to_string_lossy().to_string(), count)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_default_collection_detection() {
+		let library = nested_library();
+		assert_eq!(
+			detect(&library, None),
+			vec![
+				("".to_string(), 1),
+				("DC".to_string(), 1),
+				("Marvel".to_string(), 5),
+			]
+		);
+	}
+
+	#[test]
+	fn test_folder_depth_detection() {
+		let library = nested_library();
+		assert_eq!(
+			detect(&library, Some(SeriesDetection::FolderDepth { depth: 2 })),
+			vec![
+				("".to_string(), 1),
+				("DC/Batman".to_string(), 1),
+				("Marvel".to_string(), 1),
+				("Marvel/Spider-Man".to_string(), 3),
+				("Marvel/X-Men".to_string(), 1),
+			]
+		);
+	}
+
+	#[test]
+	fn test_series_json_detection() {
+		let library = nested_library();
+		fs::write(library.path().join("Marvel/Spider-Man/series.json"), b"{}").unwrap();
+		fs::write(library.path().join("DC/Batman/Volume 1/series.json"), b"{}").unwrap();
+
+		assert_eq!(
+			detect(&library, Some(SeriesDetection::SeriesJson)),
+			vec![
+				("".to_string(), 1),
+				("DC/Batman/Volume 1".to_string(), 1),
+				("Marvel".to_string(), 2),
+				("Marvel/Spider-Man".to_string(), 3),
+			]
+		);
+	}
+
+	#[test]
+	fn test_path_pattern_detection() {
+		let library = nested_library();
+		let detection = SeriesDetection::PathPattern {
+			pattern: "^Marvel/[^/]+$".to_string(),
+		};
+		assert_eq!(
+			detect(&library, Some(detection)),
+			vec![
+				("".to_string(), 1),
+				("DC".to_string(), 1),
+				("Marvel".to_string(), 1),
+				("Marvel/Spider-Man".to_string(), 3),
+				("Marvel/X-Men".to_string(), 1),
+			]
+		);
+	}
+
+	#[test]
+	fn test_series_based_detection() {
+		let library = nested_library();
+		let resolver =
+			SeriesResolver::new(library.path(), &LibraryPattern::SeriesBased, None)
+				.unwrap();
+		let detected = detect_series(library.path(), &GlobSet::empty(), &resolver);
+		assert_eq!(detected.series.len(), 6);
+		assert_eq!(
+			detected
+				.series
+				.get(&library.path().join("Marvel/Spider-Man/Volume 1")),
+			Some(&2)
+		);
+	}
+
+	#[test]
+	fn test_ignored_directories() {
+		let library = nested_library();
+		let mut builder = GlobSetBuilder::new();
+		builder.add(globset::Glob::new("**/DC").unwrap());
+		let ignore_rules = builder.build().unwrap();
+		let resolver =
+			SeriesResolver::new(library.path(), &LibraryPattern::CollectionBased, None)
+				.unwrap();
+
+		let detected = detect_series(library.path(), &ignore_rules, &resolver);
+		assert!(!detected.series.contains_key(&library.path().join("DC")));
+		assert_eq!(detected.ignored_directories, 1);
+	}
+
+	#[test]
+	fn test_invalid_detection() {
+		assert!(SeriesDetection::FolderDepth { depth: 0 }
+			.validate()
+			.is_err());
+		assert!(SeriesDetection::PathPattern {
+			pattern: "(".to_string()
+		}
+		.validate()
+		.is_err());
+		assert!(SeriesDetection::SeriesJson.validate().is_ok());
+	}
+}
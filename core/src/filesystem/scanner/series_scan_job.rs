@@ -1,5 +1,6 @@
 use std::{collections::VecDeque, path::PathBuf};
 
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use utoipa::ToSchema;
@@ -22,12 +23,15 @@ use crate::{
 };
 
 use super::{
+	moved_media::MovedMedia,
 	options::BookVisitOperation,
 	utils::{
-		handle_missing_media, handle_restored_media, safely_build_and_insert_media,
-		visit_and_update_media, MediaBuildOperation, MediaOperationOutput,
+		build_filename_rules, diff_moved_media, diff_visited_media, handle_missing_media,
+		handle_reassigned_media, handle_restored_media, safely_build_and_insert_media,
+		visit_and_update_media, MediaBuildOperation, MediaDiffOutput,
+		MediaOperationOutput,
 	},
-	walk_series, ScanOptions, WalkedSeries, WalkerCtx,
+	walk_series, ScanDiff, ScanOptions, WalkedSeries, WalkerCtx,
 };
 
 #[allow(clippy::enum_variant_names)]
@@ -35,8 +39,12 @@ use super::{
 pub enum SeriesScanTask {
 	MarkMissingMedia(Vec<PathBuf>),
 	RestoreMedia(Vec<String>),
+	ReassignMedia(Vec<String>),
 	CreateMedia(Vec<PathBuf>),
 	VisitMedia(Vec<(PathBuf, BookVisitOperation)>),
+	/// Generate the metadata of the media which would be visited, and record which of them
+	/// would change without updating anything. Only used by dry runs
+	DiffMedia(Vec<(PathBuf, BookVisitOperation)>),
 }
 
 #[derive(Clone)]
@@ -44,6 +52,10 @@ pub struct SeriesScanJob {
 	pub id: String,
 	pub path: String,
 	pub config: Option<LibraryConfig>,
+	/// The filename rules of the library, compiled from its configuration when the job starts
+	pub filename_rules: Vec<Regex>,
+	/// The changed paths which caused the library watcher to queue the scan, if it did
+	pub triggered_by: Vec<String>,
 	pub options: ScanOptions,
 }
 
@@ -57,9 +69,28 @@ impl SeriesScanJob {
 			id,
 			path,
 			config: None,
+			filename_rules: vec![],
+			triggered_by: vec![],
 			options: options.unwrap_or_default(),
 		})
 	}
+
+	/// Create a scan for the changes the library watcher picked up, which records the paths
+	/// which triggered it in its output
+	pub fn for_changed_paths(
+		id: String,
+		path: String,
+		triggered_by: Vec<String>,
+	) -> Box<WrappedJob<SeriesScanJob>> {
+		WrappedJob::new(Self {
+			id,
+			path,
+			config: None,
+			filename_rules: vec![],
+			triggered_by,
+			options: ScanOptions::default(),
+		})
+	}
 }
 
 // TODO: emit progress events. This job isn't exposed in the UI yet, so it's not a big deal for now
@@ -77,6 +108,21 @@ pub struct SeriesScanOutput {
 	created_media: u64,
 	/// The number of media entities that were updated
 	updated_media: u64,
+	/// The media entities which were found at a new path
+	moved_media: Vec<MovedMedia>,
+	/// The changes the scan would make, if it was a dry run
+	#[serde(default)]
+	diff: Option<ScanDiff>,
+	/// The changed paths which caused the library watcher to queue the scan, if it did
+	#[serde(default)]
+	triggered_by: Vec<String>,
+}
+
+impl SeriesScanOutput {
+	/// The changes the scan would make, if it was a dry run
+	pub fn diff(&self) -> Option<&ScanDiff> {
+		self.diff.as_ref()
+	}
 }
 
 impl JobOutputExt for SeriesScanOutput {
@@ -86,6 +132,11 @@ impl JobOutputExt for SeriesScanOutput {
 		self.skipped_files += updated.skipped_files;
 		self.created_media += updated.created_media;
 		self.updated_media += updated.updated_media;
+		self.moved_media.extend(updated.moved_media);
+		self.triggered_by.extend(updated.triggered_by);
+		if let Some(diff) = updated.diff {
+			self.diff.get_or_insert_with(Default::default).extend(diff);
+		}
 	}
 }
 
@@ -104,7 +155,10 @@ impl JobExt for SeriesScanJob {
 		&mut self,
 		ctx: &WorkerCtx,
 	) -> Result<WorkingState<Self::Output, Self::Task>, JobError> {
-		let mut output = Self::Output::default();
+		let mut output = Self::Output {
+			triggered_by: self.triggered_by.clone(),
+			..Default::default()
+		};
 		let path_buf = PathBuf::from(self.path.clone());
 		let library = ctx
 			.db
@@ -128,6 +182,12 @@ impl JobExt for SeriesScanJob {
 		// Therefore, we only scan one level deep when walking a series whose library is not
 		// collection-priority to avoid scanning duplicates which are part of other series
 		let mut max_depth = (!library_config.is_collection_based()).then_some(1);
+		// When the library uses a series detection strategy, the resolver decides which of the
+		// nested books belong to this series instead
+		let series_resolver = library_config
+			.has_series_detection()
+			.then(|| library_config.series_resolver(&library.path))
+			.transpose()?;
 		if path_buf == PathBuf::from(&library.path) {
 			// The exception is when the series "is" the libray (i.e. the root of the library contains
 			// books). This is kind of an anti-pattern wrt collection-priority, but it needs to be handled
@@ -135,27 +195,68 @@ impl JobExt for SeriesScanJob {
 			max_depth = Some(1);
 		}
 
+		self.filename_rules = build_filename_rules(&library_config);
 		self.config = Some(library_config);
 
-		let WalkedSeries {
-			series_is_missing,
-			media_to_create,
-			media_to_visit,
-			recovered_media,
-			missing_media,
-			seen_files,
-			ignored_files,
-			skipped_files,
-		} = walk_series(
+		let walked_series = walk_series(
 			PathBuf::from(self.path.clone()).as_path(),
 			WalkerCtx {
 				db: ctx.db.clone(),
 				ignore_rules,
 				max_depth,
+				series_resolver,
 				options: self.options,
 			},
 		)
 		.await?;
+		output.total_files = walked_series.seen_files + walked_series.ignored_files;
+		output.ignored_files = walked_series.ignored_files;
+		output.skipped_files = walked_series.skipped_files;
+
+		if self.options.dry_run {
+			let media_to_create = walked_series.media_to_create.clone();
+			let (mut diff, media_to_compare) =
+				ScanDiff::for_series(&ctx.db, &path_buf, walked_series).await?;
+			let MediaDiffOutput {
+				moved_media, logs, ..
+			} = diff_moved_media(
+				MediaBuildOperation {
+					series_id: self.id.clone(),
+					library_config: self.config.clone().unwrap_or_default(),
+					filename_rules: self.filename_rules.clone(),
+					max_concurrency: ctx.config.max_scanner_concurrency,
+				},
+				ctx,
+				media_to_create,
+			)
+			.await?;
+			diff.extend(ScanDiff {
+				media_to_move: moved_media,
+				..Default::default()
+			});
+			output.diff = Some(diff);
+			let tasks = (!media_to_compare.is_empty())
+				.then_some(SeriesScanTask::DiffMedia(media_to_compare))
+				.into_iter()
+				.collect();
+
+			return Ok(WorkingState {
+				output: Some(output),
+				tasks,
+				completed_tasks: 0,
+				logs,
+			});
+		}
+
+		let WalkedSeries {
+			series_is_missing,
+			media_to_create,
+			media_to_visit,
+			recovered_media,
+			missing_media,
+			media_to_reassign,
+			..
+		} = walked_series;
 
 		if series_is_missing {
 			let _ = handle_missing_series(&ctx.db, self.path.as_str()).await;
@@ -169,9 +270,6 @@ impl JobExt for SeriesScanJob {
 			media_to_visit = media_to_visit.len(),
 			"Walked series"
 		);
-		output.total_files = seen_files + ignored_files;
-		output.ignored_files = ignored_files;
-		output.skipped_files = skipped_files;
 
 		let tasks = VecDeque::from(chain_optional_iter(
 			[],
@@ -180,6 +278,8 @@ impl JobExt for SeriesScanJob {
 					.then_some(SeriesScanTask::MarkMissingMedia(missing_media)),
 				(!recovered_media.is_empty())
 					.then_some(SeriesScanTask::RestoreMedia(recovered_media)),
+				(!media_to_reassign.is_empty())
+					.then_some(SeriesScanTask::ReassignMedia(media_to_reassign)),
 				(!media_to_create.is_empty())
 					.then_some(SeriesScanTask::CreateMedia(media_to_create)),
 				(!media_to_visit.is_empty())
@@ -258,6 +358,24 @@ impl JobExt for SeriesScanJob {
 				output.updated_media += updated_media;
 				logs.extend(new_logs);
 			},
+			SeriesScanTask::ReassignMedia(ids) => {
+				ctx.report_progress(JobProgress::msg("Reassigning media entities"));
+				let MediaOperationOutput {
+					updated_media,
+					logs: new_logs,
+					..
+				} = handle_reassigned_media(ctx, &self.id, ids).await;
+				ctx.send_batch(vec![
+					JobProgress::msg("Reassigned media entities").into_worker_send(),
+					CoreEvent::CreatedOrUpdatedManyMedia {
+						count: updated_media,
+						series_id: self.id.clone(),
+					}
+					.into_worker_send(),
+				]);
+				output.updated_media += updated_media;
+				logs.extend(new_logs);
+			},
 			SeriesScanTask::MarkMissingMedia(paths) => {
 				ctx.report_progress(JobProgress::msg("Handling missing media"));
 				let MediaOperationOutput {
@@ -282,12 +400,14 @@ impl JobExt for SeriesScanJob {
 				));
 				let MediaOperationOutput {
 					created_media,
+					moved_media,
 					logs: new_logs,
 					..
 				} = safely_build_and_insert_media(
 					MediaBuildOperation {
 						series_id: self.id.clone(),
 						library_config: self.config.clone().unwrap_or_default(),
+						filename_rules: self.filename_rules.clone(),
 						max_concurrency,
 					},
 					ctx,
@@ -297,12 +417,13 @@ impl JobExt for SeriesScanJob {
 				ctx.send_batch(vec![
 					JobProgress::msg("Created new media").into_worker_send(),
 					CoreEvent::CreatedOrUpdatedManyMedia {
-						count: created_media,
+						count: created_media + moved_media.len() as u64,
 						series_id: self.id.clone(),
 					}
 					.into_worker_send(),
 				]);
 				output.created_media += created_media;
+				output.moved_media.extend(moved_media);
 				logs.extend(new_logs);
 			},
 			SeriesScanTask::VisitMedia(params) => {
@@ -317,6 +438,7 @@ impl JobExt for SeriesScanJob {
 					MediaBuildOperation {
 						series_id: self.id.clone(),
 						library_config: self.config.clone().unwrap_or_default(),
+						filename_rules: self.filename_rules.clone(),
 						max_concurrency,
 					},
 					ctx,
@@ -334,6 +456,30 @@ impl JobExt for SeriesScanJob {
 				output.updated_media += updated_media;
 				logs.extend(new_logs);
 			},
+			SeriesScanTask::DiffMedia(params) => {
+				ctx.report_progress(JobProgress::msg(
+					format!("Comparing {} media entities on disk", params.len()).as_str(),
+				));
+				let MediaDiffOutput {
+					changed_metadata,
+					logs: new_logs,
+				} = diff_visited_media(
+					MediaBuildOperation {
+						series_id: self.id.clone(),
+						library_config: self.config.clone().unwrap_or_default(),
+						filename_rules: self.filename_rules.clone(),
+						max_concurrency,
+					},
+					ctx,
+					params,
+				)
+				.await?;
+				output.diff = Some(ScanDiff {
+					media_with_changed_metadata: changed_metadata,
+					..Default::default()
+				});
+				logs.extend(new_logs);
+			},
 		}
 
 		Ok(JobTaskOutput {
@@ -1,5 +1,5 @@
 use std::{
-	collections::{HashMap, VecDeque},
+	collections::{HashMap, HashSet, VecDeque},
 	path::{Path, PathBuf},
 	pin::pin,
 	sync::{
@@ -14,6 +14,7 @@ use prisma_client_rust::{
 	chrono::{DateTime, Utc},
 	QueryError,
 };
+use regex::Regex;
 use tokio::{
 	sync::{oneshot, Semaphore},
 	task::spawn_blocking,
@@ -37,7 +38,11 @@ use crate::{
 	CoreEvent,
 };
 
-use super::options::BookVisitResult;
+use super::{
+	diff::metadata_changed,
+	moved_media::{relocate_media, MoveCandidates, MovedMedia},
+	options::BookVisitResult,
+};
 
 pub(crate) fn file_updated_since_scan(
 	entry: &DirEntry,
@@ -182,8 +187,13 @@ pub(crate) async fn update_media(db: &PrismaClient, media: Media) -> CoreResult<
 							media::pages::set(media.pages),
 							media::hash::set(media.hash.clone()),
 							media::koreader_hash::set(media.koreader_hash.clone()),
+							// The file changed, so the cover must be hashed again and the file
+							// verified again
+							media::cover_hash::set(None),
+							media::verified_at::set(None),
 							media::path::set(media.path.clone()),
 							media::status::set(media.status.to_string()),
+							media::status_reason::set(media.status_reason.clone()),
 						],
 						[metadata_id.map(|id| {
 							media::metadata::connect(media_metadata::id::equals(id))
@@ -317,10 +327,14 @@ pub(crate) async fn handle_missing_series(
 	let _affected_media = client
 		.media()
 		.update_many(
-			vec![media::series::is(vec![series::path::equals(
-				path.to_string(),
-			)])],
-			vec![media::status::set(FileStatus::Missing.to_string())],
+			vec![
+				media::series::is(vec![series::path::equals(path.to_string())]),
+				media::deleted_at::equals(None),
+			],
+			vec![
+				media::status::set(FileStatus::Missing.to_string()),
+				media::deleted_at::set(Some(Utc::now().into())),
+			],
 		)
 		.exec()
 		.await
@@ -342,15 +356,29 @@ pub(crate) async fn handle_missing_series(
 	Ok(output)
 }
 
+/// The most media reassigned in a single query, to stay within the variable limit of SQLite
+const REASSIGN_CHUNK_SIZE: usize = 500;
+
 #[derive(Default)]
 pub(crate) struct MediaOperationOutput {
 	pub created_media: u64,
 	pub updated_media: u64,
+	pub moved_media: Vec<MovedMedia>,
+	pub logs: Vec<JobExecuteLog>,
+}
+
+#[derive(Default)]
+pub(crate) struct MediaDiffOutput {
+	/// The paths of the media whose metadata would change
+	pub changed_metadata: Vec<String>,
+	/// The new books which are existing media moved from elsewhere in the library
+	pub moved_media: Vec<MovedMedia>,
 	pub logs: Vec<JobExecuteLog>,
 }
 
 /// Handles missing media by updating the database with the latest information. A media is
 /// considered missing if it was previously marked as ready and is no longer found on disk.
+/// Missing media is moved to the trash, unless it is already there.
 pub(crate) async fn handle_missing_media(
 	ctx: &WorkerCtx,
 	series_id: &str,
@@ -375,8 +403,12 @@ pub(crate) async fn handle_missing_media(
 						.map(|e| e.to_string_lossy().to_string())
 						.collect::<Vec<String>>(),
 				),
+				media::deleted_at::equals(None),
+			],
+			vec![
+				media::status::set(FileStatus::Missing.to_string()),
+				media::deleted_at::set(Some(Utc::now().into())),
 			],
-			vec![media::status::set(FileStatus::Missing.to_string())],
 		)
 		.exec()
 		.await
@@ -421,7 +453,10 @@ pub(crate) async fn handle_restored_media(
 				media::series::is(vec![series::id::equals(series_id.to_string())]),
 				media::id::in_vec(ids),
 			],
-			vec![media::status::set(FileStatus::Ready.to_string())],
+			vec![
+				media::status::set(FileStatus::Ready.to_string()),
+				media::deleted_at::set(None),
+			],
 		)
 		.exec()
 		.await
@@ -443,6 +478,98 @@ pub(crate) async fn handle_restored_media(
 	output
 }
 
+/// Handles media which now belong to another series than the one they were created under,
+/// e.g. because the series detection strategy of the library changed. Media which were marked
+/// missing from their previous series are restored, and any previous series which are left
+/// without media are deleted.
+pub(crate) async fn handle_reassigned_media(
+	ctx: &WorkerCtx,
+	series_id: &str,
+	ids: Vec<String>,
+) -> MediaOperationOutput {
+	let mut output = MediaOperationOutput::default();
+
+	if ids.is_empty() {
+		tracing::debug!("No reassigned media to handle");
+		return output;
+	}
+
+	let mut previous_series_ids = HashSet::new();
+	for chunk in ids.chunks(REASSIGN_CHUNK_SIZE) {
+		let chunk = chunk.to_vec();
+		match ctx
+			.db
+			.media()
+			.find_many(vec![media::id::in_vec(chunk.clone())])
+			.select(media::select!({ series_id }))
+			.exec()
+			.await
+		{
+			Ok(media) => {
+				previous_series_ids.extend(media.into_iter().filter_map(|m| m.series_id))
+			},
+			Err(error) => {
+				tracing::error!(?error, "Failed to fetch the series of reassigned media");
+			},
+		}
+
+		let _affected_rows = ctx
+			.db
+			.media()
+			.update_many(
+				vec![media::id::in_vec(chunk)],
+				vec![
+					media::series_id::set(Some(series_id.to_string())),
+					media::status::set(FileStatus::Ready.to_string()),
+					media::deleted_at::set(None),
+				],
+			)
+			.exec()
+			.await
+			.map_or_else(
+				|error| {
+					tracing::error!(error = ?error, "Failed to reassign media");
+					output.logs.push(JobExecuteLog::error(format!(
+						"Failed to reassign media to series: {:?}",
+						error.to_string()
+					)));
+					0
+				},
+				|count| {
+					output.updated_media += count as u64;
+					count
+				},
+			);
+	}
+
+	previous_series_ids.remove(series_id);
+	if !previous_series_ids.is_empty() {
+		let deleted_series = ctx
+			.db
+			.series()
+			.delete_many(vec![
+				series::id::in_vec(previous_series_ids.into_iter().collect()),
+				series::media::none(vec![]),
+			])
+			.exec()
+			.await;
+		match deleted_series {
+			Ok(count) => {
+				tracing::debug!(count, "Deleted series left empty by reassigned media")
+			},
+			Err(error) => {
+				tracing::error!(?error, "Failed to delete series left empty");
+				output.logs.push(JobExecuteLog::error(format!(
+					"Failed to delete series left empty by reassigned media: {:?}",
+					error.to_string()
+				)));
+			},
+		}
+	}
+
+	output
+}
+
 /// Builds a series from the given path
 ///
 /// # Arguments
@@ -557,10 +684,27 @@ pub(crate) async fn safely_build_series(
 	(created_series, logs)
 }
 
+/// Compile the filename rules of a library. Rules are validated when they are saved, but if
+/// they somehow fail to compile, books are parsed with the built-in patterns only.
+pub(crate) fn build_filename_rules(library_config: &LibraryConfig) -> Vec<Regex> {
+	library_config
+		.filename_rules
+		.build()
+		.unwrap_or_else(|error| {
+			tracing::warn!(
+				?error,
+				"Failed to build filename rules, using built-ins only"
+			);
+			vec![]
+		})
+}
+
 // TODO(granular-scans): intake ScanOptions
 pub(crate) struct MediaBuildOperation {
 	pub series_id: String,
 	pub library_config: LibraryConfig,
+	/// The filename rules of the library, compiled once for the whole scan
+	pub filename_rules: Vec<Regex>,
 	pub max_concurrency: usize,
 }
 
@@ -571,12 +715,14 @@ pub(crate) struct MediaBuildOperation {
 /// * `series_id` - The series ID to associate the media with
 /// * `existing_book` - An optional existing media to rebuild
 /// * `library_config` - The library configuration
+/// * `filename_rules` - The compiled filename rules of the library
 /// * `config` - The core configuration
 async fn build_book(
 	path: &Path,
 	series_id: &str,
 	existing_book: Option<Media>,
 	library_config: LibraryConfig,
+	filename_rules: Vec<Regex>,
 	config: &StumpConfig,
 ) -> CoreResult<Media> {
 	let (tx, rx) = oneshot::channel();
@@ -589,7 +735,8 @@ async fn build_book(
 		let config = config.clone();
 
 		move || {
-			let builder = MediaBuilder::new(&path, &series_id, library_config, &config);
+			let builder = MediaBuilder::new(&path, &series_id, library_config, &config)
+				.with_filename_rules(filename_rules);
 			let send_result = tx.send(if let Some(existing_book) = existing_book {
 				builder.rebuild(&existing_book)
 			} else {
@@ -631,6 +778,7 @@ async fn handle_book(
 		existing_book,
 	}: BookVisitCtx,
 	library_config: LibraryConfig,
+	filename_rules: Vec<Regex>,
 	config: &StumpConfig,
 ) -> CoreResult<BookVisitResult> {
 	let (tx, rx) = oneshot::channel();
@@ -643,7 +791,8 @@ async fn handle_book(
 		let config = config.clone();
 
 		move || {
-			let builder = MediaBuilder::new(&path, &series_id, library_config, &config);
+			let builder = MediaBuilder::new(&path, &series_id, library_config, &config)
+				.with_filename_rules(filename_rules);
 			let send_result = tx.send(match (operation, existing_book) {
 				(BookVisitOperation::Rebuild, Some(book)) => builder
 					.rebuild(&book)
@@ -682,29 +831,16 @@ async fn handle_book(
 	Ok(build_result)
 }
 
-/// Safely builds media from a list of paths concurrently, with a maximum concurrency limit
-/// as defined by the core configuration. The media is then inserted into the database.
-///
-/// # Arguments
-/// * `MediaBuildOperation` - The operation configuration for building media
-/// * `worker_ctx` - The worker context
-/// * `paths` - A list of paths to build media from
-pub(crate) async fn safely_build_and_insert_media(
-	MediaBuildOperation {
-		series_id,
-		library_config,
-		max_concurrency,
-	}: MediaBuildOperation,
+/// Builds the books at the given paths from disk, without inserting them. Books which fail to
+/// build are logged and skipped.
+async fn build_books(
+	series_id: &str,
+	library_config: &LibraryConfig,
+	filename_rules: &[Regex],
+	max_concurrency: usize,
 	worker_ctx: &WorkerCtx,
-	paths: Vec<PathBuf>,
-) -> Result<MediaOperationOutput, JobError> {
-	if paths.is_empty() {
-		tracing::trace!("No media to create?");
-		return Ok(MediaOperationOutput::default());
-	}
-
-	let mut output = MediaOperationOutput::default();
-
+	paths: &[PathBuf],
+) -> (VecDeque<Media>, Vec<JobExecuteLog>) {
 	let semaphore = Arc::new(Semaphore::new(max_concurrency));
 	tracing::debug!(max_concurrency, "Semaphore created for media creation");
 
@@ -716,8 +852,9 @@ pub(crate) async fn safely_build_and_insert_media(
 		.iter()
 		.map(|path| {
 			let semaphore = semaphore.clone();
-			let series_id = series_id.clone();
+			let series_id = series_id.to_string();
 			let library_config = library_config.clone();
+			let filename_rules = filename_rules.to_vec();
 			let path = path.clone();
 
 			async move {
@@ -729,9 +866,16 @@ pub(crate) async fn safely_build_and_insert_media(
 					.await
 					.map_err(|e| (CoreError::Unknown(e.to_string()), path.clone()))?;
 				tracing::trace!(?path, "Acquired permit for media creation");
-				build_book(&path, &series_id, None, library_config, &worker_ctx.config)
-					.await
-					.map_err(|e| (e, path.clone()))
+				build_book(
+					&path,
+					&series_id,
+					None,
+					library_config,
+					filename_rules,
+					&worker_ctx.config,
+				)
+				.await
+				.map_err(|e| (e, path.clone()))
 			}
 		})
 		.collect::<FuturesUnordered<_>>();
@@ -742,6 +886,7 @@ pub(crate) async fn safely_build_and_insert_media(
 
 	let mut futures = pin!(futures);
 	let mut books = VecDeque::with_capacity(paths.len());
+	let mut logs = vec![];
 
 	while let Some(result) = futures.next().await {
 		match result {
@@ -750,7 +895,7 @@ pub(crate) async fn safely_build_and_insert_media(
 			},
 			Err((error, path)) => {
 				tracing::error!(error = ?error, ?path, "Failed to build book");
-				output.logs.push(
+				logs.push(
 					JobExecuteLog::error(format!(
 						"Failed to build book: {:?}",
 						error.to_string()
@@ -765,23 +910,106 @@ pub(crate) async fn safely_build_and_insert_media(
 		));
 	}
 
-	let success_count = books.len();
-	let error_count = output.logs.len();
 	tracing::debug!(
 		elapsed = ?start.elapsed(),
-		success_count, error_count,
+		success_count = books.len(),
+		error_count = logs.len(),
 		"Built books from disk"
 	);
 
+	(books, logs)
+}
+
+/// Safely builds media from a list of paths concurrently, with a maximum concurrency limit
+/// as defined by the core configuration. The media is then inserted into the database.
+///
+/// # Arguments
+/// * `MediaBuildOperation` - The operation configuration for building media
+/// * `worker_ctx` - The worker context
+/// * `paths` - A list of paths to build media from
+pub(crate) async fn safely_build_and_insert_media(
+	MediaBuildOperation {
+		series_id,
+		library_config,
+		filename_rules,
+		max_concurrency,
+	}: MediaBuildOperation,
+	worker_ctx: &WorkerCtx,
+	paths: Vec<PathBuf>,
+) -> Result<MediaOperationOutput, JobError> {
+	if paths.is_empty() {
+		tracing::trace!("No media to create?");
+		return Ok(MediaOperationOutput::default());
+	}
+
+	let mut output = MediaOperationOutput::default();
+
+	let (mut books, logs) = build_books(
+		&series_id,
+		&library_config,
+		&filename_rules,
+		max_concurrency,
+		worker_ctx,
+		&paths,
+	)
+	.await;
+	output.logs.extend(logs);
+	let error_count = output.logs.len();
+
 	worker_ctx.report_progress(JobProgress::msg("Inserting books into database"));
 	let task_count = books.len() as i32;
 	let start = Instant::now();
 
 	let atomic_cursor = Arc::new(AtomicUsize::new(1));
 
+	// Books which were moved or renamed are matched against existing media which is no
+	// longer on disk, so they keep their history rather than being created again
+	let mut move_candidates = match library_config.library_id.as_deref() {
+		Some(library_id) => {
+			MoveCandidates::find(&worker_ctx.db, library_id, books.make_contiguous())
+				.await
+				.unwrap_or_else(|error| {
+					tracing::error!(?error, "Failed to find candidates for moved media");
+					output.logs.push(JobExecuteLog::warn(&format!(
+						"Failed to check for moved media: {:?}",
+						error.to_string()
+					)));
+					MoveCandidates::default()
+				})
+		},
+		None => MoveCandidates::default(),
+	};
+
 	// TODO: consider small batches of _batch instead?
 	while let Some(book) = books.pop_front() {
 		let path = book.path.clone();
+
+		if let Some(moved) = move_candidates.take_match(&book) {
+			match relocate_media(&worker_ctx.db, &moved, book.clone()).await {
+				Ok(_) => {
+					tracing::debug!(?moved, "Detected moved media");
+					output.moved_media.push(moved);
+					worker_ctx.report_progress(JobProgress::subtask_position(
+						atomic_cursor.fetch_add(1, Ordering::SeqCst) as i32,
+						task_count,
+					));
+					continue;
+				},
+				// The book is still created, it just won't keep the history of the media
+				// it was moved from
+				Err(e) => {
+					tracing::error!(error = ?e, ?path, "Failed to relocate media");
+					output.logs.push(
+						JobExecuteLog::warn(&format!(
+							"Failed to relocate moved media, creating it instead: {:?}",
+							e.to_string()
+						))
+						.with_ctx(path.clone()),
+					);
+				},
+			}
+		}
+
 		match create_media(&worker_ctx.db, book).await {
 			Ok(created_media) => {
 				output.created_media += 1;
@@ -833,6 +1061,7 @@ pub(crate) async fn visit_and_update_media(
 	MediaBuildOperation {
 		series_id,
 		library_config,
+		filename_rules,
 		max_concurrency,
 	}: MediaBuildOperation,
 	worker_ctx: &WorkerCtx,
@@ -895,6 +1124,7 @@ pub(crate) async fn visit_and_update_media(
 			let semaphore = semaphore.clone();
 			let path = ctx.path.clone();
 			let config = library_config.clone();
+			let filename_rules = filename_rules.clone();
 
 			async move {
 				if semaphore.available_permits() == 0 {
@@ -907,7 +1137,7 @@ pub(crate) async fn visit_and_update_media(
 					.map_err(|e| (CoreError::Unknown(e.to_string()), path.clone()))?;
 				tracing::trace!(?permit, ?path, "Acquired permit for media visit");
 
-				handle_book(ctx, config, &worker_ctx.config)
+				handle_book(ctx, config, filename_rules, &worker_ctx.config)
 					.await
 					.map_err(|e| (e, path))
 			}
@@ -982,3 +1212,163 @@ pub(crate) async fn visit_and_update_media(
 
 	Ok(output)
 }
+
+/// Visits the media on disk the same way [`visit_and_update_media`] would, but only compares
+/// the metadata which would be generated against what is stored, without updating anything.
+/// This is used by dry runs to report which media would have their metadata changed.
+///
+/// # Arguments
+/// * `MediaBuildOperation` - The operation configuration for visiting media
+/// * `worker_ctx` - The worker context
+/// * `params` - A list of paths and operations to visit
+pub(crate) async fn diff_visited_media(
+	MediaBuildOperation {
+		series_id,
+		library_config,
+		filename_rules,
+		max_concurrency,
+	}: MediaBuildOperation,
+	worker_ctx: &WorkerCtx,
+	params: Vec<(PathBuf, BookVisitOperation)>,
+) -> Result<MediaDiffOutput, JobError> {
+	let mut output = MediaDiffOutput::default();
+
+	let paths_to_operation = params
+		.iter()
+		.map(|(p, o)| (p.to_string_lossy().to_string(), *o))
+		.collect::<HashMap<_, _>>();
+	let paths = paths_to_operation.keys().cloned().collect::<Vec<String>>();
+
+	let media = worker_ctx
+		.db
+		.media()
+		.find_many(vec![
+			media::path::in_vec(paths),
+			media::series_id::equals(Some(series_id.clone())),
+		])
+		.with(media::metadata::fetch())
+		.exec()
+		.await?
+		.into_iter()
+		.map(Media::from)
+		.collect::<Vec<Media>>();
+
+	let semaphore = Arc::new(Semaphore::new(max_concurrency));
+	let task_count = media.len() as i32;
+
+	let futures = media
+		.into_iter()
+		.filter_map(|book| {
+			paths_to_operation.get(&book.path).map(|operation| {
+				let existing_metadata = book.metadata.clone();
+				let ctx = BookVisitCtx {
+					operation: *operation,
+					path: PathBuf::from(book.path.as_str()),
+					series_id: series_id.clone(),
+					existing_book: Some(book),
+				};
+				(ctx, existing_metadata)
+			})
+		})
+		.map(|(ctx, existing_metadata)| {
+			let semaphore = semaphore.clone();
+			let path = ctx.path.clone();
+			let config = library_config.clone();
+			let filename_rules = filename_rules.clone();
+
+			async move {
+				let _permit = semaphore
+					.acquire()
+					.await
+					.map_err(|e| (CoreError::Unknown(e.to_string()), path.clone()))?;
+
+				let generated_metadata =
+					match handle_book(ctx, config, filename_rules, &worker_ctx.config)
+						.await
+						.map_err(|e| (e, path.clone()))?
+					{
+						BookVisitResult::Built(book) => book.metadata,
+						BookVisitResult::Custom(result) => result.meta.map(|meta| *meta),
+					};
+				let changed = generated_metadata.is_some_and(|generated| {
+					metadata_changed(existing_metadata.as_ref(), &generated)
+				});
+
+				Ok::<_, (CoreError, PathBuf)>((path, changed))
+			}
+		})
+		.collect::<FuturesUnordered<_>>();
+
+	let atomic_cursor = Arc::new(AtomicUsize::new(1));
+	let mut futures = pin!(futures);
+
+	while let Some(future_result) = futures.next().await {
+		match future_result {
+			Ok((path, true)) => {
+				output
+					.changed_metadata
+					.push(path.to_string_lossy().to_string());
+			},
+			Ok(_) => {},
+			Err((error, path)) => {
+				output.logs.push(
+					JobExecuteLog::error(format!(
+						"Failed to handle book: {:?}",
+						error.to_string()
+					))
+					.with_ctx(format!("Path: {path:?}")),
+				);
+			},
+		}
+		worker_ctx.report_progress(JobProgress::subtask_position(
+			atomic_cursor.fetch_add(1, Ordering::SeqCst) as i32,
+			task_count,
+		));
+	}
+
+	Ok(output)
+}
+
+/// Builds the books which would be created, and records which of them would instead be
+/// matched to existing media which was moved, without updating anything. Only used by dry
+/// runs, so that moves are reported as such rather than as a new and a missing book.
+pub(crate) async fn diff_moved_media(
+	MediaBuildOperation {
+		series_id,
+		library_config,
+		filename_rules,
+		max_concurrency,
+	}: MediaBuildOperation,
+	worker_ctx: &WorkerCtx,
+	paths: Vec<PathBuf>,
+) -> Result<MediaDiffOutput, JobError> {
+	let mut output = MediaDiffOutput::default();
+
+	let Some(library_id) = library_config.library_id.clone() else {
+		return Ok(output);
+	};
+	if paths.is_empty() {
+		return Ok(output);
+	}
+
+	let (mut books, logs) = build_books(
+		&series_id,
+		&library_config,
+		&filename_rules,
+		max_concurrency,
+		worker_ctx,
+		&paths,
+	)
+	.await;
+	output.logs.extend(logs);
+
+	let mut move_candidates =
+		MoveCandidates::find(&worker_ctx.db, &library_id, books.make_contiguous())
+			.await?;
+	output.moved_media = books
+		.iter()
+		.filter_map(|book| move_candidates.take_match(book))
+		.collect();
+
+	Ok(output)
+}
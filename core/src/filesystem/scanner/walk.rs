@@ -1,11 +1,12 @@
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	path::{Path, PathBuf},
 	sync::Arc,
 };
 
 use globset::GlobSet;
 use itertools::Either;
+use prisma_client_rust::or;
 use rayon::iter::{
 	IntoParallelIterator, IntoParallelRefIterator, ParallelBridge, ParallelIterator,
 };
@@ -17,11 +18,15 @@ use crate::{
 		scanner::{options::BookVisitOperation, utils::file_updated_since_scan},
 		PathUtils,
 	},
-	prisma::{media, series, PrismaClient},
-	CoreResult,
+	prisma::{excluded_media_file, media, series, PrismaClient},
+	CoreError, CoreResult,
 };
 
-use super::ScanOptions;
+use super::{detect_series, DetectedSeries, ScanOptions, SeriesResolver};
+
+/// The most paths looked up in a single query when finding media to reassign, to stay within
+/// the variable limit of SQLite
+const REASSIGN_FIND_CHUNK_SIZE: usize = 500;
 
 pub struct WalkerCtx {
 	/// A reference to the Prisma client
@@ -30,6 +35,10 @@ pub struct WalkerCtx {
 	pub ignore_rules: GlobSet,
 	// Will be 1 if the library is collection based, None
 	pub max_depth: Option<usize>,
+	/// The resolver for which series each book belongs to, when the library uses a series
+	/// detection strategy. When set, it takes precedence over `max_depth` for deciding which
+	/// directories are series and which books belong to them.
+	pub series_resolver: Option<SeriesResolver>,
 	/// The scan options to apply during the walk
 	pub options: ScanOptions,
 }
@@ -69,6 +78,7 @@ pub async fn walk_library(
 		db,
 		ignore_rules,
 		max_depth,
+		series_resolver,
 		..
 	}: WalkerCtx,
 ) -> CoreResult<WalkedLibrary> {
@@ -78,60 +88,32 @@ pub async fn walk_library(
 		return Ok(WalkedLibrary::missing());
 	}
 
-	let mut walkdir = WalkDir::new(path);
-	if let Some(num) = max_depth {
-		walkdir = walkdir.max_depth(num);
-	}
-
 	let walk_start = std::time::Instant::now();
-	let is_collection_based = max_depth.is_some_and(|d| d == 1);
-	tracing::debug!(
-		?path,
-		max_depth,
-		is_collection_based,
-		?ignore_rules,
-		"Walking library",
-	);
-
-	let (valid_entries, ignored_entries) = walkdir
-		// Set min_depth to 0 so we include the library path itself,
-		// which allows us to add it as a series when there are media items in it
-		.min_depth(0)
-		.into_iter()
-		.filter_entry(|e| e.path().is_dir())
-		.filter_map(Result::ok)
-		.par_bridge()
-		.partition_map::<Vec<DirEntry>, Vec<DirEntry>, _, _, _>(|entry| {
-			let entry_path = entry.path();
-			let entry_path_str = entry_path.as_os_str().to_string_lossy().to_string();
-			let check_deep = is_collection_based && entry_path_str != path;
-
-			let should_ignore = ignore_rules.is_match(entry.path());
-			// If we're doing a top level scan, we need to check that the path
-			// has media deeply nested. Exception for when the path is the library path,
-			// then we only need to check if it has media in it directly
-			//
-			// If we're doing a bottom up scan, we need to check that the path has
-			// media directly in it.
-			let is_valid = !should_ignore
-				&& (check_deep && entry_path.dir_has_media_deep(&ignore_rules)
-					|| (!check_deep && entry_path.dir_has_media(&ignore_rules)));
-
-			tracing::trace!(?is_valid, ?entry_path_str);
-
-			if is_valid {
-				Either::Left(entry)
-			} else {
-				Either::Right(entry)
-			}
-		});
-
-	let ignored_directories = ignored_entries.len() as u64;
-	let seen_directories = valid_entries.len() as u64 + ignored_directories;
+	let (valid_paths, seen_directories, ignored_directories) = match series_resolver {
+		Some(resolver) => {
+			tracing::debug!(?path, ?resolver, ?ignore_rules, "Walking library");
+			let library_path = PathBuf::from(path);
+			let DetectedSeries {
+				series,
+				seen_directories,
+				ignored_directories,
+			} = tokio::task::spawn_blocking(move || {
+				detect_series(&library_path, &ignore_rules, &resolver)
+			})
+			.await
+			.map_err(|e| CoreError::InternalError(e.to_string()))?;
+			(
+				series.into_keys().collect::<Vec<_>>(),
+				seen_directories,
+				ignored_directories,
+			)
+		},
+		None => walk_library_directories(path, ignore_rules, max_depth),
+	};
 
 	tracing::debug!(
 		seen_directories,
-		ignored_entries = ignored_entries.len(),
+		ignored_directories,
 		"Walk finished in {}ms",
 		walk_start.elapsed().as_millis()
 	);
@@ -148,11 +130,7 @@ pub async fn walk_library(
 			tracing::debug!(
 				"No existing series found in the database, all series are new"
 			);
-			let series_to_create = valid_entries
-				.into_iter()
-				.map(|e| e.path().to_owned())
-				.collect::<Vec<PathBuf>>();
-			(series_to_create, vec![], vec![], vec![])
+			(valid_paths, vec![], vec![], vec![])
 		} else {
 			let existing_series_map = existing_records
 				.iter()
@@ -174,10 +152,9 @@ pub async fn walk_library(
 				.map(|s| s.id)
 				.collect::<Vec<String>>();
 
-			let (series_to_create, series_to_visit) = valid_entries
-				.par_iter()
-				.filter(|e| !missing_series.contains(&e.path().to_path_buf()))
-				.map(|e| e.path().to_owned())
+			let (series_to_create, series_to_visit) = valid_paths
+				.into_par_iter()
+				.filter(|path| !missing_series.contains(path))
 				.partition_map::<Vec<PathBuf>, Vec<PathBuf>, _, _, _>(|path| {
 					let already_exists =
 						existing_series_map.contains_key(path.to_string_lossy().as_ref());
@@ -223,6 +200,72 @@ pub async fn walk_library(
 	})
 }
 
+/// Walk the directories of a library, returning the paths of those which are series along
+/// with the number of directories seen and ignored. A directory is a series if it has media
+/// directly in it, or anywhere beneath it for top-level directories of a collection-based
+/// library (i.e. when `max_depth` is 1).
+fn walk_library_directories(
+	path: &str,
+	ignore_rules: GlobSet,
+	max_depth: Option<usize>,
+) -> (Vec<PathBuf>, u64, u64) {
+	let mut walkdir = WalkDir::new(path);
+	if let Some(num) = max_depth {
+		walkdir = walkdir.max_depth(num);
+	}
+
+	let is_collection_based = max_depth.is_some_and(|d| d == 1);
+	tracing::debug!(
+		?path,
+		max_depth,
+		is_collection_based,
+		?ignore_rules,
+		"Walking library",
+	);
+
+	let (valid_entries, ignored_entries) = walkdir
+		// Set min_depth to 0 so we include the library path itself,
+		// which allows us to add it as a series when there are media items in it
+		.min_depth(0)
+		.into_iter()
+		.filter_entry(|e| e.path().is_dir())
+		.filter_map(Result::ok)
+		.par_bridge()
+		.partition_map::<Vec<DirEntry>, Vec<DirEntry>, _, _, _>(|entry| {
+			let entry_path = entry.path();
+			let entry_path_str = entry_path.as_os_str().to_string_lossy().to_string();
+			let check_deep = is_collection_based && entry_path_str != path;
+
+			let should_ignore = ignore_rules.is_match(entry.path());
+			// If we're doing a top level scan, we need to check that the path
+			// has media deeply nested. Exception for when the path is the library path,
+			// then we only need to check if it has media in it directly
+			//
+			// If we're doing a bottom up scan, we need to check that the path has
+			// media directly in it.
+			let is_valid = !should_ignore
+				&& (check_deep && entry_path.dir_has_media_deep(&ignore_rules)
+					|| (!check_deep && entry_path.dir_has_media(&ignore_rules)));
+
+			tracing::trace!(?is_valid, ?entry_path_str);
+
+			if is_valid {
+				Either::Left(entry)
+			} else {
+				Either::Right(entry)
+			}
+		});
+
+	let ignored_directories = ignored_entries.len() as u64;
+	let seen_directories = valid_entries.len() as u64 + ignored_directories;
+	let valid_paths = valid_entries
+		.into_iter()
+		.map(DirEntry::into_path)
+		.collect::<Vec<PathBuf>>();
+
+	(valid_paths, seen_directories, ignored_directories)
+}
+
 /// The output of walking a series
 #[derive(Default)]
 pub struct WalkedSeries {
@@ -242,6 +285,9 @@ pub struct WalkedSeries {
 	pub media_to_visit: Vec<(PathBuf, BookVisitOperation)>,
 	/// The paths for media that are missing from the filesystem
 	pub missing_media: Vec<PathBuf>,
+	/// A list of media IDs that exist in the database under a different series, but which
+	/// now belong to this one (e.g. after the library's series detection was changed)
+	pub media_to_reassign: Vec<String>,
 	/// Whether the series is missing from the filesystem
 	pub series_is_missing: bool,
 }
@@ -261,6 +307,7 @@ pub async fn walk_series(
 		db,
 		ignore_rules,
 		max_depth,
+		series_resolver,
 		options,
 	}: WalkerCtx,
 ) -> CoreResult<WalkedSeries> {
@@ -284,6 +331,12 @@ pub async fn walk_series(
 		.into_iter()
 		.filter_map(Result::ok)
 		.filter_map(|e| e.path().is_file().then_some(e))
+		// Books nested in this series' directory may belong to a different series
+		.filter(|e| {
+			series_resolver.as_ref().is_none_or(|resolver| {
+				resolver.series_for_book(e.path()).as_deref() == Some(path)
+			})
+		})
 		.par_bridge()
 		.partition_map::<Vec<DirEntry>, Vec<DirEntry>, _, _, _>(|entry| {
 			let entry_path = entry.path();
@@ -329,19 +382,106 @@ pub async fn walk_series(
 		.map(|m| (m.path.clone(), m.clone()))
 		.collect::<HashMap<String, _>>();
 
+	// When the library uses a series detection strategy, the books of this series may have
+	// been created under another series before the strategy was set or changed
+	let (media_to_reassign, trashed_paths) = match series_resolver {
+		Some(_) => {
+			let unknown_paths = valid_entries
+				.iter()
+				.map(|entry| entry.path().to_string_lossy().to_string())
+				.filter(|entry_path| !existing_media_map.contains_key(entry_path))
+				.collect::<Vec<_>>();
+			let other_series = || {
+				media::series::is_not(vec![series::path::equals(
+					path.to_string_lossy().to_string(),
+				)])
+			};
+
+			let mut media_to_reassign = vec![];
+			let mut trashed_paths = HashSet::new();
+			for chunk in unknown_paths.chunks(REASSIGN_FIND_CHUNK_SIZE) {
+				// Media which were marked missing from their previous series are reassigned
+				media_to_reassign.extend(
+					db.media()
+						.find_many(vec![
+							media::path::in_vec(chunk.to_vec()),
+							other_series(),
+							or![
+								media::deleted_at::equals(None),
+								media::status::equals(FileStatus::Missing.to_string()),
+							],
+						])
+						.select(media_path_modified_at_select::select())
+						.exec()
+						.await?,
+				);
+				// ...but media which were trashed stay in the trash, and aren't created again
+				trashed_paths.extend(
+					db.media()
+						.find_many(vec![
+							media::path::in_vec(chunk.to_vec()),
+							other_series(),
+							media::deleted_at::not(None),
+							media::status::not(FileStatus::Missing.to_string()),
+						])
+						.select(media::select!({ path }))
+						.exec()
+						.await?
+						.into_iter()
+						.map(|m| m.path),
+				);
+			}
+			(media_to_reassign, trashed_paths)
+		},
+		None => (vec![], HashSet::new()),
+	};
+	let reassigned_paths = media_to_reassign
+		.iter()
+		.map(|m| m.path.clone())
+		.collect::<HashSet<_>>();
+	let media_to_reassign = media_to_reassign
+		.into_iter()
+		.map(|m| m.id)
+		.collect::<Vec<_>>();
+
 	let (media_to_create, remaining_entries) = valid_entries
 		.into_par_iter()
 		.partition_map::<Vec<PathBuf>, Vec<DirEntry>, _, _, _>(|entry| {
 			let entry_path = entry.path();
 			let entry_path_str = entry_path.to_string_lossy().to_string();
 
-			if existing_media_map.contains_key(entry_path_str.as_str()) {
+			if existing_media_map.contains_key(entry_path_str.as_str())
+				|| reassigned_paths.contains(&entry_path_str)
+				|| trashed_paths.contains(&entry_path_str)
+			{
 				Either::Right(entry)
 			} else {
 				Either::Left(entry_path.to_path_buf())
 			}
 		});
 
+	// Duplicates which were trashed without deleting their files stay out of the library
+	let media_to_create = if media_to_create.is_empty() {
+		media_to_create
+	} else {
+		let excluded_paths = db
+			.excluded_media_file()
+			.find_many(vec![excluded_media_file::path::starts_with(format!(
+				"{}{}",
+				path.to_string_lossy(),
+				std::path::MAIN_SEPARATOR
+			))])
+			.exec()
+			.await?
+			.into_iter()
+			.map(|file| file.path)
+			.collect::<HashSet<_>>();
+		media_to_create
+			.into_iter()
+			.filter(|path| !excluded_paths.contains(path.to_string_lossy().as_ref()))
+			.collect::<Vec<_>>()
+	};
+
 	let book_visit_operations = remaining_entries
 		.into_par_iter()
 		.filter_map(|entry| {
@@ -403,6 +543,9 @@ pub async fn walk_series(
 	let to_visit = book_visit_operations.len();
 	tracing::trace!("Found {to_visit} media to visit");
 
+	let to_reassign = media_to_reassign.len();
+	tracing::trace!("Found {to_reassign} media to reassign");
+
 	let skipped_files = seen_files - (to_create + to_visit) as u64;
 	tracing::trace!(
 		skipped_files,
@@ -428,6 +571,7 @@ pub async fn walk_series(
 		recovered_media,
 		media_to_visit: book_visit_operations,
 		missing_media,
+		media_to_reassign,
 		series_is_missing: false,
 	})
 }
@@ -28,7 +28,7 @@ mod worker;
 
 use error::JobError;
 pub use progress::*;
-pub use scheduler::JobScheduler;
+pub use scheduler::{JobScheduler, PeriodicJob};
 use specta::Type;
 pub use task::JobTaskOutput;
 use task::{job_task_handler, JobTaskHandlerOutput};
@@ -1,9 +1,16 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+	task::JoinHandle,
+	time::{Instant, MissedTickBehavior},
+};
 
 use crate::{
-	db::entity::LibraryConfig,
+	config::StumpConfig,
+	db::{entity::LibraryConfig, trash::TrashPurgeJob},
+	digest::NewBooksDigestJob,
 	filesystem::scanner::LibraryScanJob,
-	job::WrappedJob,
+	job::{Executor, WrappedJob},
 	prisma::{job_schedule_config, library},
 	CoreResult, Ctx,
 };
@@ -12,14 +19,81 @@ use crate::{
 // 1. Schedule multiple job types (complex config)
 // 2. Last run timestamp, so on boot we don't immediately trigger the scheduled tasks
 
+/// A job which is dispatched on a fixed interval for as long as Stump is running, separately
+/// from the configurable library scan schedule
+#[async_trait::async_trait]
+pub trait PeriodicJob: Send + Sync {
+	/// The name of the job, used for logging
+	fn name(&self) -> &'static str;
+
+	/// How often the job is dispatched, or `None` if it is disabled by the configuration
+	fn period(&self, config: &StumpConfig) -> Option<Duration>;
+
+	/// Whether the job is first dispatched on boot, rather than one period after it
+	fn dispatch_on_boot(&self) -> bool {
+		false
+	}
+
+	/// Create the job to dispatch, or `None` if there is nothing for it to do yet
+	async fn create_job(&self, ctx: &Ctx) -> Option<Box<dyn Executor>>;
+}
+
+/// The jobs which are dispatched periodically, see [PeriodicJob]
+fn periodic_jobs() -> Vec<Box<dyn PeriodicJob>> {
+	vec![Box::new(TrashPurgeJob), Box::new(NewBooksDigestJob)]
+}
+
+/// Spawn a task which dispatches a [PeriodicJob] on its interval, unless it is disabled
+fn spawn_periodic_job(
+	ctx: Arc<Ctx>,
+	job: Box<dyn PeriodicJob>,
+) -> Option<JoinHandle<()>> {
+	let Some(period) = job.period(&ctx.config) else {
+		tracing::debug!(job = job.name(), "Periodic job is disabled");
+		return None;
+	};
+
+	Some(tokio::spawn(async move {
+		let start = if job.dispatch_on_boot() {
+			Instant::now()
+		} else {
+			Instant::now() + period
+		};
+		let mut interval = tokio::time::interval_at(start, period);
+		interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			interval.tick().await;
+			let Some(executor) = job.create_job(&ctx).await else {
+				tracing::trace!(job = job.name(), "Periodic job has nothing to do");
+				continue;
+			};
+			if let Err(error) = ctx.enqueue_job(executor) {
+				tracing::error!(
+					job = job.name(),
+					?error,
+					"Failed to dispatch periodic job"
+				);
+			} else {
+				tracing::trace!(job = job.name(), "Dispatched periodic job");
+			}
+		}
+	}))
+}
+
 pub struct JobScheduler {
-	pub scheduler_handle: Option<tokio::task::JoinHandle<()>>,
+	pub scheduler_handle: Option<JoinHandle<()>>,
+	pub periodic_handles: Vec<JoinHandle<()>>,
 }
 
 impl JobScheduler {
 	pub async fn init(core_ctx: Arc<Ctx>) -> CoreResult<Arc<Self>> {
 		let client = core_ctx.db.clone();
 
+		let periodic_handles = periodic_jobs()
+			.into_iter()
+			.filter_map(|job| spawn_periodic_job(core_ctx.clone(), job))
+			.collect::<Vec<_>>();
+
 		let result = client
 			.job_schedule_config()
 			.find_first(vec![])
@@ -88,6 +162,8 @@ impl JobScheduler {
 								id: library.id.clone(),
 								path: library_path,
 								config: config.map(LibraryConfig::from),
+								filename_rules: vec![],
+								triggered_by: vec![],
 								options: Default::default(),
 							}));
 						if result.is_err() {
@@ -102,11 +178,13 @@ impl JobScheduler {
 
 			Ok(Arc::new(Self {
 				scheduler_handle: Some(handle),
+				periodic_handles,
 			}))
 		} else {
 			tracing::info!("No schedule config found. Scheduling is disabled.");
 			Ok(Arc::new(Self {
 				scheduler_handle: None,
+				periodic_handles,
 			}))
 		}
 	}
@@ -10,6 +10,7 @@ use std::sync::Arc;
 
 pub mod config;
 pub mod db;
+pub mod digest;
 mod event;
 pub mod filesystem;
 pub mod job;
@@ -26,15 +27,18 @@ pub mod prisma;
 use config::logging::STUMP_SHADOW_TEXT;
 use config::StumpConfig;
 use db::{DBPragma, JournalMode};
+use filesystem::{analyze_media_job::AnalyzeMediaJob, ContentType};
 use job::{JobController, JobScheduler};
-use prisma::server_config;
+use prisma::{media, server_config};
 
 pub use context::Ctx;
 pub use error::{CoreError, CoreResult};
 pub use event::CoreEvent;
 
 pub use email::{
-	AttachmentPayload, EmailContentType, EmailerClient, EmailerClientConfig,
+	is_valid_email, render_template_with_overrides, validate_template, AttachmentPayload,
+	EmailContentType, EmailTemplate, EmailerClient, EmailerClientConfig,
+	TemplateOverrides,
 };
 
 /// A type alias strictly for explicitness in the return type of `init_journal_mode`.
@@ -228,6 +232,54 @@ impl StumpCore {
 		}
 	}
 
+	/// Queues an analysis of every EPUB which existed before positions were stored for EPUBs,
+	/// or before they were counted by bytes. Until an EPUB is analyzed, its page count is from
+	/// whichever pagination it was scanned with. This only happens once.
+	pub async fn init_epub_positions(&self) -> CoreResult<()> {
+		let client = &self.ctx.db;
+
+		let backfill_completed = client
+			.server_config()
+			.find_first(vec![
+				server_config::epub_positions_backfill_complete::equals(true),
+			])
+			.exec()
+			.await?
+			.is_some();
+		if backfill_completed {
+			tracing::trace!("EPUB positions backfill has already been queued, skipping");
+			return Ok(());
+		}
+
+		let epub_ids = client
+			.media()
+			.find_many(vec![])
+			.select(media::select!({ id path }))
+			.exec()
+			.await?
+			.into_iter()
+			.filter(|media| ContentType::from_file(&media.path).is_epub())
+			.map(|media| media.id)
+			.collect::<Vec<_>>();
+		if !epub_ids.is_empty() {
+			tracing::debug!(count = epub_ids.len(), "Queueing EPUB positions backfill");
+			self.ctx
+				.enqueue_job(AnalyzeMediaJob::analyze_media_group(epub_ids))
+				.map_err(|e| CoreError::InternalError(e.to_string()))?;
+		}
+
+		client
+			.server_config()
+			.update_many(
+				vec![],
+				vec![server_config::epub_positions_backfill_complete::set(true)],
+			)
+			.exec()
+			.await?;
+
+		Ok(())
+	}
+
 	pub async fn init_scheduler(&self) -> Result<Arc<JobScheduler>, CoreError> {
 		JobScheduler::init(self.ctx.arced()).await
 	}
@@ -242,7 +294,7 @@ mod tests {
 	use std::{fs::File, io::Write, path::PathBuf};
 
 	use common::*;
-	use email::EmailerClientConfig;
+	use email::{EmailTemplate, EmailerClientConfig};
 	use specta::{
 		ts::{export, BigIntExportBehavior, ExportConfiguration, TsExportError},
 		NamedType,
@@ -250,6 +302,7 @@ mod tests {
 
 	use crate::{
 		db::{
+			backup::{Backup, BackupManifest},
 			entity::*,
 			filter::*,
 			query::{ordering::*, pagination::*},
@@ -302,6 +355,8 @@ mod tests {
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<JobUpdate>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<JobProgress>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<MovedMedia>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<ScanDiff>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<LibraryScanOutput>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<SeriesScanOutput>()?).as_bytes())?;
 		file.write_all(
@@ -324,6 +379,9 @@ mod tests {
 			format!("{}\n\n", ts_export::<InheritPermissionValue>()?).as_bytes(),
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<APIKeyPermissions>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<APIKeyRouteScope>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<APIKeyScopes>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<APIKeyUsage>()?).as_bytes())?;
 
 		file.write_all(format!("{}\n\n", ts_export::<SupportedFont>()?).as_bytes())?;
 
@@ -340,6 +398,11 @@ mod tests {
 		file.write_all(format!("{}\n\n", ts_export::<UserPreferences>()?).as_bytes())?;
 
 		file.write_all(format!("{}\n\n", ts_export::<LoginActivity>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<UserNotificationKind>()?).as_bytes(),
+		)?;
+		file.write_all(format!("{}\n\n", ts_export::<UserNotification>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<OidcIdentity>()?).as_bytes())?;
 
 		file.write_all(format!("{}\n\n", ts_export::<EmailerSendTo>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<EmailerConfig>()?).as_bytes())?;
@@ -348,14 +411,31 @@ mod tests {
 		)?;
 
 		file.write_all(format!("{}\n\n", ts_export::<SMTPEmailer>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<DeviceTargetFormat>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<DeviceFormatProfile>()?).as_bytes(),
+		)?;
 		file.write_all(
 			format!("{}\n\n", ts_export::<RegisteredEmailDevice>()?).as_bytes(),
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<EmailerSendRecord>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<AttachmentMeta>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<EmailTemplate>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<EmailTemplateOrigin>()?).as_bytes(),
+		)?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<EmailTemplateDetails>()?).as_bytes(),
+		)?;
+		file.write_all(format!("{}\n\n", ts_export::<DigestFrequency>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<NewBooksDigestSubscription>()?).as_bytes(),
+		)?;
 
 		file.write_all(format!("{}\n\n", ts_export::<ReadingDirection>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<ReadingMode>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<ReadingOverrides>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<ReadingPreferences>()?).as_bytes())?;
 		file.write_all(
 			format!("{}\n\n", ts_export::<ReadingImageScaleFit>()?).as_bytes(),
 		)?;
@@ -363,21 +443,27 @@ mod tests {
 		file.write_all(format!("{}\n\n", ts_export::<FileStatus>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<Library>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<LibraryPattern>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<WatcherMode>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<LibraryScanMode>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<CustomVisit>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<ScanConfig>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<ScanOptions>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<LastLibraryScan>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<IgnoreRules>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<FilenameRules>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<SeriesDetection>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<LibraryConfig>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<LibraryStats>()?).as_bytes())?;
 
 		file.write_all(format!("{}\n\n", ts_export::<SeriesMetadata>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<Series>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<MangaFormat>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<MediaMetadata>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<Media>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<Bookmark>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<MediaAnnotation>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<DuplicateReason>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<DuplicateGroup>()?).as_bytes())?;
 		file.write_all(
 			format!("{}\n\n", ts_export::<ActiveReadingSession>()?).as_bytes(),
 		)?;
@@ -388,6 +474,8 @@ mod tests {
 			format!("{}\n\n", ts_export::<ProgressUpdateReturn>()?).as_bytes(),
 		)?;
 		file.write_all(format!("{}\n\n", ts_export::<PageDimension>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<PageLayout>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<PagePosition>()?).as_bytes())?;
 		file.write_all(
 			format!("{}\n\n", ts_export::<PageDimensionsEntity>()?).as_bytes(),
 		)?;
@@ -462,6 +550,11 @@ mod tests {
 		file.write_all(format!("{}\n\n", ts_export::<Epub>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<UpdateEpubProgress>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<EpubContent>()?).as_bytes())?;
+		file.write_all(
+			format!("{}\n\n", ts_export::<EpubPositionLocations>()?).as_bytes(),
+		)?;
+		file.write_all(format!("{}\n\n", ts_export::<EpubPosition>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<EpubPositionList>()?).as_bytes())?;
 
 		file.write_all(format!("{}\n\n", ts_export::<JobStatus>()?).as_bytes())?;
 		file.write_all(format!("{}\n\n", ts_export::<JobSchedulerConfig>()?).as_bytes())?;
@@ -482,6 +575,12 @@ mod tests {
 		file.write_all(
 			format!("{}\n\n", ts_export::<ImageProcessorOptions>()?).as_bytes(),
 		)?;
+		file.write_all(format!("{}\n\n", ts_export::<PdfRenderOptions>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<PdfOutlineItem>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<PdfPageText>()?).as_bytes())?;
+
+		file.write_all(format!("{}\n\n", ts_export::<BackupManifest>()?).as_bytes())?;
+		file.write_all(format!("{}\n\n", ts_export::<Backup>()?).as_bytes())?;
 
 		file.write_all(format!("{}\n\n", ts_export::<DirectoryListing>()?).as_bytes())?;
 		file.write_all(
@@ -12,7 +12,9 @@ use xml::{writer::XmlEvent, EventWriter};
 
 use crate::db::entity::MediaMetadata;
 use crate::error::CoreResult;
-use crate::filesystem::media::get_content_types_for_pages;
+use crate::filesystem::media::{
+	get_content_types_for_pages, get_cover_content_type, PdfRenderOptions,
+};
 use crate::filesystem::{ContentType, FileParts, PathUtils};
 use crate::{
 	opds::v1_2::link::OpdsStreamLink,
@@ -175,6 +177,25 @@ impl IntoOPDSEntry for OPDSEntryBuilder<series::Data> {
 	}
 }
 
+/// The content type the pages of a PDF are rendered to, using the render options of its
+/// library when they were fetched along with the book. Returns `None` for other books.
+fn pdf_page_content_type(data: &media::Data) -> Option<ContentType> {
+	if ContentType::from_file(&data.path) != ContentType::PDF {
+		return None;
+	}
+
+	let options = data
+		.series()
+		.ok()
+		.flatten()
+		.and_then(|series| series.library().ok().flatten())
+		.and_then(|library| library.config().ok())
+		.and_then(|config| config.pdf_render_config.clone())
+		.and_then(|options| PdfRenderOptions::try_from(options).ok())
+		.unwrap_or_default();
+	Some(options.content_type())
+}
+
 impl IntoOPDSEntry for OPDSEntryBuilder<media::Data> {
 	fn into_opds_entry(self) -> OpdsEntry {
 		let base_url = self.format_url(&format!("books/{}", self.data.id));
@@ -193,28 +214,30 @@ impl IntoOPDSEntry for OPDSEntryBuilder<media::Data> {
 				(session.page, Some(session.updated_at))
 			});
 
-		let target_pages = if let Some(page) = current_page {
-			vec![1, page]
-		} else {
-			vec![1]
+		// The cover is resolved separately, since it is not necessarily the first page (e.g.
+		// for EPUBs, whose pages are positions in the text)
+		let pdf_page_type = pdf_page_content_type(&self.data);
+		let page_content_types = match (current_page, pdf_page_type) {
+			// Every page of a PDF is rendered to the same format, so there is no need to
+			// open the file
+			(Some(page), Some(content_type)) => HashMap::from([(page, content_type)]),
+			(Some(page), None) => {
+				get_content_types_for_pages(&self.data.path, vec![page]).unwrap_or_else(
+					|error| {
+						tracing::error!(error = ?error, "Failed to get content types for pages");
+						HashMap::default()
+					},
+				)
+			},
+			(None, _) => HashMap::default(),
 		};
-
-		let page_content_types =
-			get_content_types_for_pages(&self.data.path, target_pages).unwrap_or_else(
-				|error| {
-					tracing::error!(error = ?error, "Failed to get content types for pages");
-					HashMap::default()
-				},
-			);
 		tracing::trace!(?page_content_types, "Got page content types");
 
-		let thumbnail_link_type = page_content_types
-			.get(&1)
-			.unwrap_or_else(|| {
-				tracing::error!("Failed to get content type for thumbnail");
-				&ContentType::JPEG
-			})
-			.to_owned();
+		let thumbnail_link_type =
+			get_cover_content_type(&self.data.path).unwrap_or_else(|error| {
+				tracing::error!(error = ?error, "Failed to get content type for thumbnail");
+				ContentType::JPEG
+			});
 
 		let current_page_link_type = match current_page {
 			Some(page) if page < self.data.pages => page_content_types
@@ -226,9 +249,9 @@ impl IntoOPDSEntry for OPDSEntryBuilder<media::Data> {
 				.to_owned(),
 			Some(page) => {
 				tracing::warn!(current_page=?page, book_pages=?self.data.pages, "Current page is out of bounds!");
-				thumbnail_link_type
+				pdf_page_type.unwrap_or(thumbnail_link_type)
 			},
-			_ => thumbnail_link_type,
+			_ => pdf_page_type.unwrap_or(thumbnail_link_type),
 		};
 
 		let thumbnail_opds_link_type = OpdsLinkType::try_from(thumbnail_link_type)
@@ -237,6 +260,10 @@ impl IntoOPDSEntry for OPDSEntryBuilder<media::Data> {
 			OpdsLinkType::ImageJpeg
 		});
 
+		let first_page_opds_link_type = pdf_page_type
+			.and_then(|content_type| OpdsLinkType::try_from(content_type).ok())
+			.unwrap_or(thumbnail_opds_link_type);
+
 		let entry_file_acquisition_link_type =
 			OpdsLinkType::from_extension(&self.data.extension).unwrap_or_else(|| {
 				tracing::error!(?self.data.extension, "Failed to convert file extension to OPDS link type");
@@ -250,7 +277,7 @@ impl IntoOPDSEntry for OPDSEntryBuilder<media::Data> {
 				format!("{base_url}/thumbnail"),
 			),
 			OpdsLink::new(
-				thumbnail_opds_link_type,
+				first_page_opds_link_type,
 				OpdsLinkRel::Image,
 				format!("{base_url}/pages/1"),
 			),
@@ -3,6 +3,8 @@ use prisma_client_rust::chrono::Utc;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
+use crate::db::entity::common::ReadingDirection;
+
 use super::{books_as_publications, link::OPDSLink};
 
 /// Pagination-specific metadata fields for an OPDS collection
@@ -87,6 +89,8 @@ pub struct OPDSMetadata {
 	description: Option<String>,
 	/// The entity that the feed or collection belongs to, if applicable
 	belongs_to: Option<OPDSEntryBelongsTo>,
+	/// The direction a publication is read in, if applicable
+	reading_progression: Option<ReadingDirection>,
 	#[serde(flatten)]
 	pagination: Option<OPDSPaginationMetadata>,
 	#[serde(flatten)]
@@ -108,6 +112,7 @@ impl Default for OPDSMetadata {
 			modified: Some(Utc::now().to_rfc3339()),
 			description: None,
 			belongs_to: None,
+			reading_progression: None,
 			pagination: None,
 			dynamic_metadata: None,
 		}
@@ -130,6 +135,7 @@ mod tests {
 				position: Some(1),
 				links: vec![],
 			})),
+			reading_progression: Some(ReadingDirection::RightToLeft),
 			pagination: Some(OPDSPaginationMetadata {
 				number_of_items: Some(10),
 				items_per_page: Some(5),
@@ -143,7 +149,7 @@ mod tests {
 		let json = serde_json::to_string(&metadata).unwrap();
 		assert_eq!(
 			json,
-			r#"{"title":"Book","modified":"2021-08-01T00:00:00Z","description":"A cool book","belongsTo":{"series":{"name":"Test Series","position":1}},"numberOfItems":10,"itemsPerPage":5,"currentPage":1,"test":"value"}"#
+			r#"{"title":"Book","modified":"2021-08-01T00:00:00Z","description":"A cool book","belongsTo":{"series":{"name":"Test Series","position":1}},"readingProgression":"rtl","numberOfItems":10,"itemsPerPage":5,"currentPage":1,"test":"value"}"#
 		);
 	}
 }
@@ -8,9 +8,14 @@ media::include!(books_as_publications {
 	series: select {
 		id
 		name
+		reading_direction
+		reading_mode
 		metadata: select {
 			title
 		}
+		library: select {
+			config: select { default_reading_dir default_reading_mode }
+		}
 	}
 });
 
@@ -25,5 +30,6 @@ active_reading_session::select!(reading_session_opds_progression {
 	  extension
 	 pages
 	  metadata: select { page_dimensions }
+	  epub_positions: select { positions }
    }
 });
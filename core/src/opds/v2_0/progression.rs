@@ -1,4 +1,4 @@
-use crate::CoreResult;
+use crate::{db::entity::EpubPositionList, CoreResult};
 
 use super::{
 	link::{OPDSLinkFinalizer, OPDSLinkType},
@@ -16,10 +16,13 @@ pub const CANTOOK_PROGRESSION_REL: &str = "http://www.cantook.com/api/progressio
 #[serde(rename_all = "camelCase")]
 pub struct OPDSProgression {
 	#[builder(default = "default_now()")]
+	#[serde(default = "default_now")]
 	modified: String,
 	#[builder(default)]
+	#[serde(default)]
 	device: OPDSProgressionDevice,
 	#[builder(default)]
+	#[serde(default)]
 	locator: OPDSProgressionLocator,
 }
 
@@ -41,18 +44,39 @@ impl OPDSProgression {
 		let extension = data.media.extension.to_lowercase();
 		let (title, href, _type, locations) =
 			match (extension.as_str(), data.epubcfi, data.page) {
-				("epub", Some(cfi), _) => {
-					// TODO: Lookup chapter without opening file, e.g. epubcfi?
+				("epub", cfi, page) if cfi.is_some() || page.is_some() => {
 					let title = "Ebook Progress".to_string();
-					// TODO: Use resource URL for href, e.g. OEBPS/chapter008.xhtml ?
-					let locations = data.percentage_completed.map(|progression| {
-						vec![OPDSProgressionLocation {
-							fragments: Some(vec![cfi]),
-							total_progression: Some(progression),
-							..Default::default()
-						}]
+					// The positions list (when the book has been analyzed) locates the
+					// progress within a resource of the book
+					let positions = data.media.epub_positions.and_then(|stored| {
+						EpubPositionList::from_stored(&stored.positions).ok()
 					});
-					(Some(title), None, Some(OPDSLinkType::Xhtml), locations)
+					let (position, total_progression) = positions
+						.as_ref()
+						.map(|positions| {
+							positions.resolve_progress(page, data.percentage_completed)
+						})
+						.unwrap_or((page, data.percentage_completed));
+					let resolved = position.and_then(|position| {
+						positions
+							.as_ref()
+							.and_then(|positions| positions.get(position))
+					});
+
+					let locations = vec![OPDSProgressionLocation {
+						fragments: cfi.map(|cfi| vec![cfi]),
+						position: position.map(|position| position.to_string()),
+						progression: resolved
+							.map(|position| position.locations.progression),
+						total_progression,
+					}];
+					let href = resolved.map(|position| position.href.clone());
+					(
+						Some(title),
+						href,
+						Some(OPDSLinkType::Xhtml),
+						Some(locations),
+					)
 				},
 				(_, None, Some(current_page)) => {
 					let title = format!("Page {}", current_page);
@@ -85,6 +109,42 @@ impl OPDSProgression {
 			)
 			.build()
 	}
+
+	/// The first location reported by the locator, if any
+	fn location(&self) -> Option<&OPDSProgressionLocation> {
+		self.locator.locations.as_ref()?.first()
+	}
+
+	/// The progression through the entire publication (0.0 to 1.0), if reported
+	pub fn total_progression(&self) -> Option<f64> {
+		self.location()
+			.and_then(|location| location.total_progression)
+	}
+
+	/// The 1-based position (page) in the publication, if reported
+	pub fn position(&self) -> Option<i32> {
+		self.location()
+			.and_then(|location| location.position.as_deref())
+			.and_then(|position| position.parse().ok())
+	}
+
+	/// The first fragment of the location which is an epubcfi, if any
+	pub fn epubcfi(&self) -> Option<String> {
+		self.location()
+			.and_then(|location| location.fragments.as_ref())
+			.and_then(|fragments| {
+				fragments
+					.iter()
+					.find(|fragment| fragment.starts_with("epubcfi("))
+					.cloned()
+			})
+	}
+
+	/// The ID and name of the device which reported the progression, if provided
+	pub fn device(&self) -> Option<(String, String)> {
+		(!self.device.id.is_empty())
+			.then(|| (self.device.id.clone(), self.device.name.clone()))
+	}
 }
 
 // https://readium.org/architecture/schema/locator.schema.json
@@ -120,3 +180,40 @@ struct OPDSProgressionDevice {
 	id: String,
 	name: String,
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_deserialize_client_progression() {
+		let progression: OPDSProgression = serde_json::from_str(
+			r#"{
+				"device": { "id": "abc", "name": "My Reader" },
+				"locator": {
+					"locations": [{ "position": "12", "totalProgression": 0.5, "fragments": ["epubcfi(/6/4!/4/2)"] }]
+				}
+			}"#,
+		)
+		.unwrap();
+
+		assert_eq!(progression.position(), Some(12));
+		assert_eq!(progression.total_progression(), Some(0.5));
+		assert_eq!(
+			progression.epubcfi(),
+			Some("epubcfi(/6/4!/4/2)".to_string())
+		);
+		assert_eq!(
+			progression.device(),
+			Some(("abc".to_string(), "My Reader".to_string()))
+		);
+	}
+
+	#[test]
+	fn test_deserialize_minimal_progression() {
+		let progression: OPDSProgression = serde_json::from_str("{}").unwrap();
+		assert_eq!(progression.position(), None);
+		assert_eq!(progression.total_progression(), None);
+		assert_eq!(progression.device(), None);
+	}
+}
@@ -6,6 +6,8 @@ use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
+use crate::db::entity::PagePosition;
+
 use super::link::OPDSLinkType;
 
 /// A struct for representing dynamic properties of an OPDS feed or collection. This is just
@@ -23,6 +25,9 @@ pub const AUTH_ROUTE: &str = "/opds/v2.0/auth";
 pub struct OPDSProperties {
 	/// The URI of the authentication document
 	pub authenticate: Option<OPDSAuthenticateProperties>,
+	/// The side of a two-page view the linked page is displayed on, as described by the
+	/// Readium `page` property
+	pub page: Option<PagePosition>,
 	#[serde(flatten)]
 	pub dynamic_properties: Option<OPDSDynamicProperties>,
 }
@@ -1,15 +1,18 @@
 //! A module for representing OPDS 2.0 publications, as defined by the OPDS 2.0 spec at
 //! https://drafts.opds.io/opds-2.0#51-opds-publication
 
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
 
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
 use crate::{
-	db::entity::{MediaMetadata, PageDimensionsEntity},
-	filesystem::{get_content_type_for_page, ContentType},
+	db::entity::{
+		common::ReadingDirection, page_positions, MangaFormat, MediaMetadata,
+		PageDimensionsEntity, PageLayout, ReadingOverrides, ReadingPreferences,
+	},
+	filesystem::{get_cover_content_type_async, ContentType},
 	prisma::{page_dimensions, PrismaClient},
 	CoreError, CoreResult,
 };
@@ -108,6 +111,8 @@ impl OPDSPublication {
 				let images = OPDSPublication::images_for_book(&book, &finalizer).await?;
 
 				let position = positions.get(&book.id).copied();
+				let reading_direction =
+					OPDSPublication::reading_direction_for_book(&book);
 
 				let metadata = book
 					.metadata
@@ -129,6 +134,7 @@ impl OPDSPublication {
 					.modified(OPDSMetadata::generate_modified())
 					.description(description)
 					.belongs_to(OPDSEntryBelongsTo::from((series.clone(), position)))
+					.reading_progression(reading_direction)
 					.dynamic_metadata(OPDSDynamicMetadata(serde_json::to_value(
 						media_metadata,
 					)?))
@@ -147,6 +153,54 @@ impl OPDSPublication {
 		Ok(publications)
 	}
 
+	/// The direction a book is read in, resolved from the overrides of the book and its series,
+	/// the book's metadata and the defaults of its library
+	fn reading_direction_for_book(
+		book: &books_as_publications::Data,
+	) -> ReadingDirection {
+		let overrides = ReadingOverrides::from_stored(
+			book.reading_direction.as_deref(),
+			book.reading_mode.as_deref(),
+		);
+		let metadata_direction = book
+			.metadata
+			.as_ref()
+			.and_then(|metadata| metadata.manga.as_deref())
+			.and_then(|manga| MangaFormat::from_str(manga).ok())
+			.and_then(|manga| manga.reading_direction());
+		let (series_overrides, library_defaults) = book
+			.series
+			.as_ref()
+			.map(|series| {
+				let library_defaults = series
+					.library
+					.as_ref()
+					.map(|library| {
+						ReadingPreferences::from_library_defaults(
+							&library.config.default_reading_dir,
+							&library.config.default_reading_mode,
+						)
+					})
+					.unwrap_or_default();
+				(
+					ReadingOverrides::from_stored(
+						series.reading_direction.as_deref(),
+						series.reading_mode.as_deref(),
+					),
+					library_defaults,
+				)
+			})
+			.unwrap_or_default();
+
+		ReadingPreferences::resolve(
+			&overrides,
+			&series_overrides,
+			metadata_direction,
+			library_defaults,
+		)
+		.reading_direction
+	}
+
 	pub async fn from_book(
 		client: &PrismaClient,
 		finalizer: OPDSLinkFinalizer,
@@ -154,6 +208,7 @@ impl OPDSPublication {
 	) -> CoreResult<Self> {
 		let links = OPDSPublication::links_for_book(&book, &finalizer)?;
 		let images = OPDSPublication::images_for_book(&book, &finalizer).await?;
+		let reading_direction = OPDSPublication::reading_direction_for_book(&book);
 
 		let series = book
 			.series
@@ -179,11 +234,30 @@ impl OPDSPublication {
 			.exec()
 			.await?
 			.map(PageDimensionsEntity::from)
-			.map(|pd| pd.dimensions);
+			.map(|pd| pd.dimensions)
+			.unwrap_or_default();
+
+		// Page positions are only hinted when every page has a known layout, which is
+		// not the case for books analyzed before layouts were classified
+		let layouts = page_dimensions
+			.iter()
+			.map(|dim| dim.layout)
+			.collect::<Option<Vec<PageLayout>>>()
+			.filter(|layouts| !layouts.is_empty());
+		let sides = match layouts {
+			Some(layouts) => page_positions(
+				&layouts,
+				reading_direction == ReadingDirection::RightToLeft,
+			)
+			.into_iter()
+			.map(Some)
+			.collect(),
+			None => vec![None; page_dimensions.len()],
+		};
 
 		let mut reading_order = vec![];
 
-		for (idx, dim) in page_dimensions.unwrap_or_default().into_iter().enumerate() {
+		for (idx, (dim, side)) in page_dimensions.into_iter().zip(sides).enumerate() {
 			let base_link = OPDSBaseLinkBuilder::default()
 				.href(finalizer.format_link(format!(
 					"/opds/v2.0/books/{}/pages/{}",
@@ -192,6 +266,10 @@ impl OPDSPublication {
 				)))
 				// FIXME(311): Don't make this assumption
 				._type(OPDSLinkType::ImageJpeg)
+				.properties(side.map(|page| OPDSProperties {
+					page: Some(page),
+					..Default::default()
+				}))
 				.build()?;
 			let image_link = OPDSImageLinkBuilder::default()
 				.height(dim.height)
@@ -214,6 +292,7 @@ impl OPDSPublication {
 			.modified(OPDSMetadata::generate_modified())
 			.description(description)
 			.belongs_to(OPDSEntryBelongsTo::from((series.clone(), position)))
+			.reading_progression(reading_direction)
 			.dynamic_metadata(OPDSDynamicMetadata(serde_json::to_value(media_metadata)?))
 			.build()?;
 
@@ -247,7 +326,7 @@ impl OPDSPublication {
 						)),
 					)
 					._type(OPDSLinkType::from(
-						get_content_type_for_page(&book.path, 1).await?,
+						get_cover_content_type_async(&book.path).await?,
 					))
 					.build()?
 					.with_auth(finalizer.format_link(AUTH_ROUTE)),
@@ -312,7 +391,10 @@ mod tests {
 			series: Some(books_as_publications::series::Data {
 				id: "1".to_string(),
 				name: "Series 1".to_string(),
+				reading_direction: None,
+				reading_mode: None,
 				metadata: None,
+				library: None,
 			}),
 			created_at: Utc::now().into(),
 			updated_at: Utc::now().into(),
@@ -320,8 +402,13 @@ mod tests {
 			extension: String::from("epub"),
 			path: get_test_epub_path(),
 			status: FileStatus::Ready.to_string(),
+			status_reason: None,
+			verified_at: None,
+			reading_direction: None,
+			reading_mode: None,
 			hash: Some(String::from("hash")),
 			koreader_hash: None,
+			cover_hash: None,
 			series_id: Some("1".to_string()),
 			pages: 0,
 			modified_at: None,
@@ -430,6 +517,7 @@ mod tests {
 				id: "1".to_string(),
 				metadata_id: "1".to_string(),
 				dimensions: "1920,1080;800,600;1920,1080".to_string(),
+				layouts: None,
 				metadata: None,
 			}),
 		)
@@ -445,4 +533,129 @@ mod tests {
 
 		assert!(publication.reading_order.is_some());
 	}
+
+	#[tokio::test]
+	async fn test_from_book_with_layouts() {
+		let book = mock_book();
+
+		let (client, mock) = PrismaClient::_mock();
+
+		mock.expect(
+			client._query_raw(book_positions_in_series_raw_query(
+				&["1".to_string()],
+				"1".to_string(),
+			)),
+			vec![EntityPosition {
+				id: "1".to_string(),
+				position: 1,
+			}],
+		)
+		.await;
+
+		mock.expect(
+			client
+				.page_dimensions()
+				.find_first(vec![page_dimensions::metadata_id::equals(String::new())]),
+			Some(page_dimensions::Data {
+				id: "1".to_string(),
+				metadata_id: "1".to_string(),
+				dimensions: "1920,1080;800,600;1920,1080".to_string(),
+				layouts: Some("SDD".to_string()),
+				metadata: None,
+			}),
+		)
+		.await;
+
+		let publication = OPDSPublication::from_book(
+			&client,
+			OPDSLinkFinalizer::new("https://my-stump-instance.cloud".to_string()),
+			book,
+		)
+		.await
+		.expect("Failed to generate publication");
+
+		let sides = publication
+			.reading_order
+			.expect("Reading order should be set")
+			.into_iter()
+			.map(|link| {
+				let value = serde_json::to_value(link).expect("Failed to serialize link");
+				value["properties"]["page"].as_str().map(String::from)
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			sides,
+			vec![
+				Some("center".to_string()),
+				Some("left".to_string()),
+				Some("right".to_string())
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_from_book_right_to_left() {
+		let book = books_as_publications::Data {
+			reading_direction: Some("rtl".to_string()),
+			..mock_book()
+		};
+
+		let (client, mock) = PrismaClient::_mock();
+
+		mock.expect(
+			client._query_raw(book_positions_in_series_raw_query(
+				&["1".to_string()],
+				"1".to_string(),
+			)),
+			vec![EntityPosition {
+				id: "1".to_string(),
+				position: 1,
+			}],
+		)
+		.await;
+
+		mock.expect(
+			client
+				.page_dimensions()
+				.find_first(vec![page_dimensions::metadata_id::equals(String::new())]),
+			Some(page_dimensions::Data {
+				id: "1".to_string(),
+				metadata_id: "1".to_string(),
+				dimensions: "1920,1080;800,600;1920,1080".to_string(),
+				layouts: Some("SDD".to_string()),
+				metadata: None,
+			}),
+		)
+		.await;
+
+		let publication = OPDSPublication::from_book(
+			&client,
+			OPDSLinkFinalizer::new("https://my-stump-instance.cloud".to_string()),
+			book,
+		)
+		.await
+		.expect("Failed to generate publication");
+
+		let value = serde_json::to_value(&publication.metadata)
+			.expect("Failed to serialize metadata");
+		assert_eq!(value["readingProgression"], "rtl");
+
+		let sides = publication
+			.reading_order
+			.expect("Reading order should be set")
+			.into_iter()
+			.map(|link| {
+				let value = serde_json::to_value(link).expect("Failed to serialize link");
+				value["properties"]["page"].as_str().map(String::from)
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			sides,
+			vec![
+				Some("center".to_string()),
+				Some("right".to_string()),
+				Some("left".to_string())
+			]
+		);
+	}
 }
@@ -40,6 +40,13 @@ pub enum Account {
 		#[clap(long)]
 		username: String,
 	},
+	/// Disable two-factor authentication for an account, e.g. if the authenticator and
+	/// recovery codes were lost
+	ResetTwoFactor {
+		/// The username of the account to reset two-factor authentication for
+		#[clap(long)]
+		username: String,
+	},
 	/// Enter a flow to change the server owner to another account
 	ResetOwner,
 }
@@ -59,6 +66,9 @@ pub async fn handle_account_command(
 		Account::ResetPassword { username } => {
 			reset_account_password(username, config.password_hash_cost, config).await
 		},
+		Account::ResetTwoFactor { username } => {
+			reset_account_two_factor(username, config).await
+		},
 		Account::ResetOwner => change_server_owner(config).await,
 	}
 }
@@ -156,6 +166,53 @@ async fn reset_account_password(
 	}
 }
 
+async fn reset_account_two_factor(
+	username: String,
+	config: &StumpConfig,
+) -> CliResult<()> {
+	let confirmation = Confirm::new()
+		.with_prompt(format!(
+			"This will disable two-factor authentication for {username}. Are you sure you want to continue?"
+		))
+		.interact()?;
+
+	if !confirmation {
+		println!("Exiting...");
+		return Ok(());
+	}
+
+	let progress = default_progress_spinner();
+	progress.set_message("Resetting two-factor authentication...");
+
+	let client = create_client(config).await;
+
+	let affected_rows = client
+		.user()
+		.update_many(
+			vec![user::username::equals(username.clone())],
+			vec![
+				user::totp_secret::set(None),
+				user::totp_enabled_at::set(None),
+				user::totp_last_used_step::set(None),
+				user::totp_recovery_codes::set(None),
+			],
+		)
+		.exec()
+		.await?;
+
+	thread::sleep(Duration::from_millis(500));
+
+	if affected_rows == 0 {
+		progress.abandon_with_message("No account with that username was found");
+		Err(CliError::OperationFailed(String::from(
+			"No account with that username was found",
+		)))
+	} else {
+		progress.finish_with_message("Two-factor authentication reset successfully!");
+		Ok(())
+	}
+}
+
 async fn print_accounts(locked: Option<bool>, config: &StumpConfig) -> CliResult<()> {
 	let progress = default_progress_spinner();
 	progress.set_message("Fetching accounts...");
@@ -179,12 +236,17 @@ async fn print_accounts(locked: Option<bool>, config: &StumpConfig) -> CliResult
 
 		// Create table using prettytable-rs
 		let mut table = prettytable::Table::new();
-		table.add_row(prettytable::row!["Account", "Status"]);
+		table.add_row(prettytable::row!["Account", "Status", "2FA"]);
 
 		for user in users {
 			table.add_row(prettytable::row![
 				user.username,
-				if user.is_locked { "locked" } else { "unlocked" }
+				if user.is_locked { "locked" } else { "unlocked" },
+				if user.totp_enabled_at.is_some() {
+					"enabled"
+				} else {
+					"disabled"
+				}
 			]);
 		}
 
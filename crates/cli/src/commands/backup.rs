@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use dialoguer::Confirm;
+use stump_core::{
+	config::StumpConfig,
+	db::{
+		backup::{
+			create_backup, get_backup_path, list_backups, prune_backups, restore_backup,
+			RestoreOptions,
+		},
+		create_client,
+	},
+};
+
+use super::default_progress_spinner;
+use crate::error::CliResult;
+
+/// Subcommands for creating and restoring backups of the database, thumbnails and config
+#[derive(Subcommand, Debug)]
+pub enum Backup {
+	/// Create a backup in the backups directory. This is safe to run while the server is
+	/// running
+	Create {
+		/// Do not include the thumbnails in the backup
+		#[clap(long)]
+		no_thumbnails: bool,
+	},
+	/// Restore a backup. The server must be stopped before restoring!
+	Restore {
+		/// The name of a backup in the backups directory, or the path to a backup archive
+		backup: String,
+		/// Also restore the thumbnails in the backup, replacing the existing thumbnails
+		#[clap(long)]
+		thumbnails: bool,
+		/// Also restore the Stump.toml in the backup, replacing the existing one
+		#[clap(long)]
+		config: bool,
+	},
+	/// List the backups in the backups directory
+	List,
+}
+
+pub async fn handle_backup_command(
+	command: Backup,
+	config: &StumpConfig,
+) -> CliResult<()> {
+	match command {
+		Backup::Create { no_thumbnails } => create(!no_thumbnails, config).await,
+		Backup::Restore {
+			backup,
+			thumbnails,
+			config: restore_config,
+		} => restore(
+			backup,
+			RestoreOptions {
+				thumbnails,
+				config: restore_config,
+			},
+			config,
+		),
+		Backup::List => print_backups(config),
+	}
+}
+
+async fn create(include_thumbnails: bool, config: &StumpConfig) -> CliResult<()> {
+	let progress = default_progress_spinner();
+	progress.set_message("Connecting to database...");
+
+	let client = create_client(config).await;
+
+	progress.set_message("Creating backup...");
+	let backup = create_backup(&client, config, include_thumbnails).await?;
+
+	progress.set_message("Deleting old backups...");
+	let pruned = prune_backups(config)?;
+
+	progress.finish_with_message(format!(
+		"Created backup {} ({} bytes), deleted {} old backup(s)",
+		backup.name,
+		backup.size,
+		pruned.len()
+	));
+
+	Ok(())
+}
+
+fn restore(
+	backup: String,
+	options: RestoreOptions,
+	config: &StumpConfig,
+) -> CliResult<()> {
+	let archive_path = if PathBuf::from(&backup).is_file() {
+		PathBuf::from(&backup)
+	} else {
+		get_backup_path(config, &backup)?
+	};
+
+	let confirmation = Confirm::new()
+		.with_prompt(format!(
+			"This will replace the database at {:?}. Make sure the server is stopped. Are you sure you want to continue?",
+			config.get_db_path()
+		))
+		.interact()?;
+	if !confirmation {
+		println!("Exiting...");
+		return Ok(());
+	}
+
+	let progress = default_progress_spinner();
+	progress.set_message("Restoring backup...");
+
+	let manifest = restore_backup(config, &archive_path, options)?;
+
+	progress.finish_with_message(format!(
+		"Restored backup created at {} by Stump {}",
+		manifest.created_at, manifest.stump_version
+	));
+
+	Ok(())
+}
+
+fn print_backups(config: &StumpConfig) -> CliResult<()> {
+	let backups = list_backups(config)?;
+	if backups.is_empty() {
+		println!("No backups found in {:?}", config.get_backups_dir());
+		return Ok(());
+	}
+
+	let mut table = prettytable::Table::new();
+	table.add_row(prettytable::row![
+		"Name",
+		"Size (bytes)",
+		"Stump Version",
+		"Thumbnails"
+	]);
+	for backup in backups {
+		table.add_row(prettytable::row![
+			backup.name,
+			backup.size,
+			backup.manifest.stump_version,
+			backup.manifest.includes_thumbnails
+		]);
+	}
+	table.printstd();
+
+	Ok(())
+}
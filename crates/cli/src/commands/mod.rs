@@ -1,4 +1,5 @@
 mod account;
+mod backup;
 mod system;
 
 use std::time::Duration;
@@ -9,13 +10,15 @@ use stump_core::config::StumpConfig;
 
 use crate::error::CliResult;
 
-use self::{account::Account, system::System};
+use self::{account::Account, backup::Backup, system::System};
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
 	#[command(subcommand)]
 	Account(Account),
 	#[command(subcommand)]
+	Backup(Backup),
+	#[command(subcommand)]
 	System(System),
 }
 
@@ -24,6 +27,7 @@ pub async fn handle_command(command: Commands, config: &StumpConfig) -> CliResul
 		Commands::Account(account) => {
 			account::handle_account_command(account, config).await
 		},
+		Commands::Backup(backup) => backup::handle_backup_command(backup, config).await,
 		Commands::System(system) => system::handle_system_command(system, config).await,
 	}
 }
@@ -4,17 +4,20 @@ use lettre::{
 	address::AddressError,
 	message::{
 		header::{self, ContentType},
-		Attachment, MultiPart, SinglePart,
+		Attachment, Mailbox, MultiPart, SinglePart,
 	},
 	transport::smtp::authentication::Credentials,
-	Message, SmtpTransport, Transport,
+	Address, Message, SmtpTransport, Transport,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use specta::Type;
 use utoipa::ToSchema;
 
-use crate::{render_template, EmailError, EmailResult, EmailTemplate};
+use crate::{
+	render_template_with_overrides, EmailError, EmailResult, EmailTemplate,
+	TemplateOverrides,
+};
 
 /// The configuration for an [EmailerClient]
 #[derive(Serialize, Deserialize, ToSchema, Type)]
@@ -52,12 +55,32 @@ pub struct AttachmentPayload {
 	pub content_type: ContentType,
 }
 
+/// An image which is embedded in the body of an email, rather than attached to it. The
+/// template references it with `cid:{content_id}`
+#[derive(Debug)]
+pub struct InlineImage {
+	/// The ID the template uses to reference the image
+	pub content_id: String,
+	/// The bytes of the image
+	pub content: Vec<u8>,
+	/// The content type of the image, e.g. "image/jpeg"
+	pub content_type: ContentType,
+}
+
+/// Check whether the given address is a valid email address
+pub fn is_valid_email(address: &str) -> bool {
+	address.parse::<Address>().is_ok()
+}
+
 /// A client for sending emails
 pub struct EmailerClient {
 	/// The configuration for the email client
 	config: EmailerClientConfig,
 	/// The directory where email templates are stored
 	template_dir: PathBuf,
+	/// The templates which were overridden through the API, which take priority over the
+	/// templates in the template directory
+	template_overrides: TemplateOverrides,
 }
 
 impl EmailerClient {
@@ -86,6 +109,15 @@ impl EmailerClient {
 		Self {
 			config,
 			template_dir,
+			template_overrides: TemplateOverrides::new(),
+		}
+	}
+
+	/// Set the templates which take priority over those in the template directory
+	pub fn with_template_overrides(self, template_overrides: TemplateOverrides) -> Self {
+		Self {
+			template_overrides,
+			..self
 		}
 	}
 
@@ -184,22 +216,15 @@ impl EmailerClient {
 		recipient: &str,
 		payloads: Vec<AttachmentPayload>,
 	) -> EmailResult<()> {
-		let from = self
-			.config
-			.sender_email
-			.parse()
-			.map_err(|e: AddressError| EmailError::InvalidEmail(e.to_string()))?;
+		let (from, to) = self.mailboxes(recipient)?;
 
-		let to = recipient
-			.parse()
-			.map_err(|e: AddressError| EmailError::InvalidEmail(e.to_string()))?;
-
-		let html = render_template(
+		let html = render_template_with_overrides(
 			EmailTemplate::Attachment,
 			&json!({
 				"title": "Stump Attachment",
 			}),
 			self.template_dir.clone(),
+			&self.template_overrides,
 		)?;
 
 		let mut multipart_builder = MultiPart::mixed().singlepart(
@@ -220,6 +245,98 @@ impl EmailerClient {
 			.subject(subject)
 			.multipart(multipart_builder)?;
 
+		self.send(&email)
+	}
+
+	/// Send an email with the given subject to the given recipient, where the body is rendered
+	/// from the given template and data. The images are embedded in the body, so the template
+	/// can display them without the recipient having to load anything from the server.
+	///
+	/// # Example
+	/// ```no_run
+	/// use email::{EmailTemplate, EmailerClient, EmailerClientConfig};
+	/// use serde_json::json;
+	/// use std::path::PathBuf;
+	///
+	/// async fn test() {
+	///     let config = EmailerClientConfig {
+	///         sender_email: "aaron@stumpapp.dev".to_string(),
+	///         sender_display_name: "Aaron's Stump Instance".to_string(),
+	///         username: "aaron@stumpapp.dev".to_string(),
+	///         password: Some("decrypted_password".to_string()),
+	///         host: "smtp.stumpapp.dev".to_string(),
+	///         port: 587,
+	///         tls_enabled: true,
+	///         max_attachment_size_bytes: Some(10_000_000),
+	///         max_num_attachments: Some(5),
+	///     };
+	///     let template_dir = PathBuf::from("/templates");
+	///     let emailer = EmailerClient::new(config, template_dir);
+	///
+	///     let result = emailer.send_template(
+	///         "New books on Stump",
+	///         "aaron@stumpapp.dev",
+	///         EmailTemplate::NewBooksDigest,
+	///         &EmailTemplate::NewBooksDigest.sample_data(),
+	///         vec![],
+	///     ).await;
+	///     assert!(result.is_err()); // This will fail because the SMTP server is not real
+	/// }
+	/// ```
+	pub async fn send_template(
+		&self,
+		subject: &str,
+		recipient: &str,
+		template: EmailTemplate,
+		data: &serde_json::Value,
+		images: Vec<InlineImage>,
+	) -> EmailResult<()> {
+		let (from, to) = self.mailboxes(recipient)?;
+
+		let html = render_template_with_overrides(
+			template,
+			data,
+			self.template_dir.clone(),
+			&self.template_overrides,
+		)?;
+		let html_part = SinglePart::builder()
+			.header(header::ContentType::TEXT_HTML)
+			.body(html);
+
+		let builder = Message::builder().from(from).to(to).subject(subject);
+		let email = if images.is_empty() {
+			builder.singlepart(html_part)?
+		} else {
+			let mut multipart_builder = MultiPart::related().singlepart(html_part);
+			for image in images {
+				multipart_builder = multipart_builder.singlepart(
+					Attachment::new_inline(image.content_id)
+						.body(image.content, image.content_type),
+				);
+			}
+			builder.multipart(multipart_builder)?
+		};
+
+		self.send(&email)
+	}
+
+	/// Parse the sender and recipient of an email
+	fn mailboxes(&self, recipient: &str) -> EmailResult<(Mailbox, Mailbox)> {
+		let from = self
+			.config
+			.sender_email
+			.parse()
+			.map_err(|e: AddressError| EmailError::InvalidEmail(e.to_string()))?;
+
+		let to = recipient
+			.parse()
+			.map_err(|e: AddressError| EmailError::InvalidEmail(e.to_string()))?;
+
+		Ok((from, to))
+	}
+
+	/// Send an email through the configured SMTP server
+	fn send(&self, email: &Message) -> EmailResult<()> {
 		let password = self
 			.config
 			.password
@@ -241,13 +358,13 @@ impl EmailerClient {
 				.build()
 		};
 
-		match transport.send(&email) {
+		match transport.send(email) {
 			Ok(res) => {
-				tracing::trace!(?res, "Email with attachments was sent");
+				tracing::trace!(?res, "Email was sent");
 				Ok(())
 			},
 			Err(e) => {
-				tracing::error!(error = ?e, "Failed to send email with attachments");
+				tracing::error!(error = ?e, "Failed to send email");
 				Err(e.into())
 			},
 		}
@@ -10,10 +10,16 @@ mod error;
 /// A module containing the template rendering functionality, via the `handlebars` crate
 mod template;
 
-pub use emailer::{AttachmentPayload, EmailerClient, EmailerClientConfig};
+pub use emailer::{
+	is_valid_email, AttachmentPayload, EmailerClient, EmailerClientConfig, InlineImage,
+};
 pub use error::{EmailError, EmailResult};
 pub use template::{
-	render_template, EmailTemplate, ATTACHMENT_TEMPLATE, BASE_TEMPLATE, TEMPLATES,
+	read_template_from_disk, render_template, render_template_with_overrides,
+	validate_template, EmailTemplate, TemplateOverrides, ATTACHMENT_TEMPLATE,
+	BASE_TEMPLATE, INVITATION_TEMPLATE, JOB_FAILURE_TEMPLATE,
+	NEW_BOOKS_DIGEST_CONFIRMATION_TEMPLATE, NEW_BOOKS_DIGEST_TEMPLATE,
+	PASSWORD_RESET_TEMPLATE, TEMPLATES,
 };
 
 pub use lettre::message::header::ContentType as EmailContentType;
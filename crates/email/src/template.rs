@@ -1,29 +1,197 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, fmt, path::PathBuf, str::FromStr};
 
 use crate::EmailResult;
 use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+use utoipa::ToSchema;
 
 pub static BASE_TEMPLATE: &str = include_str!("../templates/base.hbs");
 pub static ATTACHMENT_TEMPLATE: &str = include_str!("../templates/attachment.hbs");
+pub static INVITATION_TEMPLATE: &str = include_str!("../templates/invitation.hbs");
+pub static PASSWORD_RESET_TEMPLATE: &str =
+	include_str!("../templates/password_reset.hbs");
+pub static NEW_BOOKS_DIGEST_TEMPLATE: &str =
+	include_str!("../templates/new_books_digest.hbs");
+pub static NEW_BOOKS_DIGEST_CONFIRMATION_TEMPLATE: &str =
+	include_str!("../templates/new_books_digest_confirmation.hbs");
+pub static JOB_FAILURE_TEMPLATE: &str = include_str!("../templates/job_failure.hbs");
 
-pub static TEMPLATES: &[(&str, &str)] =
-	&[("base", BASE_TEMPLATE), ("attachment", ATTACHMENT_TEMPLATE)];
+pub static TEMPLATES: &[(&str, &str)] = &[
+	("base", BASE_TEMPLATE),
+	("attachment", ATTACHMENT_TEMPLATE),
+	("invitation", INVITATION_TEMPLATE),
+	("password_reset", PASSWORD_RESET_TEMPLATE),
+	("new_books_digest", NEW_BOOKS_DIGEST_TEMPLATE),
+	(
+		"new_books_digest_confirmation",
+		NEW_BOOKS_DIGEST_CONFIRMATION_TEMPLATE,
+	),
+	("job_failure", JOB_FAILURE_TEMPLATE),
+];
 
-// TODO: expose this enumeration to the public API somehow, so that users can define their own template overrides
+/// The sources of templates which take priority over the templates on disk and the defaults,
+/// keyed by the template they override
+pub type TemplateOverrides = HashMap<EmailTemplate, String>;
 
+/// The kinds of emails which Stump can send, each of which is rendered from its own template
+#[derive(
+	Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type, ToSchema,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum EmailTemplate {
 	/// A template for an email which includes attachment(s), e.g. a book on the server
 	Attachment,
+	/// A template for an email inviting someone to join the server. Not sent by Stump yet
+	Invitation,
+	/// A template for an email with a link to reset the password of an account. Not sent by
+	/// Stump yet
+	PasswordReset,
+	/// A template for an email listing the books which were recently added to the server
+	NewBooksDigest,
+	/// A template for an email with the code which confirms the address of a new books digest
+	NewBooksDigestConfirmation,
+	/// A template for an email notifying an admin that a job failed. Not sent by Stump yet
+	JobFailure,
+}
+
+impl EmailTemplate {
+	/// All of the templates, in the order they are listed
+	pub const ALL: [EmailTemplate; 6] = [
+		EmailTemplate::Attachment,
+		EmailTemplate::Invitation,
+		EmailTemplate::PasswordReset,
+		EmailTemplate::NewBooksDigest,
+		EmailTemplate::NewBooksDigestConfirmation,
+		EmailTemplate::JobFailure,
+	];
+
+	/// The source of the template which is bundled with Stump
+	pub fn default_source(&self) -> &'static str {
+		match self {
+			Self::Attachment => ATTACHMENT_TEMPLATE,
+			Self::Invitation => INVITATION_TEMPLATE,
+			Self::PasswordReset => PASSWORD_RESET_TEMPLATE,
+			Self::NewBooksDigest => NEW_BOOKS_DIGEST_TEMPLATE,
+			Self::NewBooksDigestConfirmation => NEW_BOOKS_DIGEST_CONFIRMATION_TEMPLATE,
+			Self::JobFailure => JOB_FAILURE_TEMPLATE,
+		}
+	}
+
+	/// Data which resembles what the template is rendered with, used to preview and validate
+	/// the template
+	pub fn sample_data(&self) -> serde_json::Value {
+		match self {
+			Self::Attachment => json!({
+				"title": "Stump Attachment",
+			}),
+			Self::Invitation => json!({
+				"title": "You're invited to Stump",
+				"username": "reader",
+				"server_name": "Stump",
+				"invite_url": "https://stump.example.com/invite/abc123",
+			}),
+			Self::PasswordReset => json!({
+				"title": "Reset your Stump password",
+				"username": "reader",
+				"server_name": "Stump",
+				"reset_url": "https://stump.example.com/reset-password/abc123",
+				"expires_in_minutes": 30,
+			}),
+			Self::NewBooksDigest => json!({
+				"title": "New books on Stump",
+				"username": "reader",
+				"server_name": "Stump",
+				"server_url": "https://stump.example.com",
+				"book_count": 3,
+				"more_count": 1,
+				"books": [
+					{
+						"title": "The Way of Kings",
+						"series": "The Stormlight Archive",
+						"url": "https://stump.example.com/books/1",
+						"thumbnail_cid": "book-1",
+					},
+					{
+						"title": "Delete #1",
+						"series": null,
+						"url": "https://stump.example.com/books/2",
+						"thumbnail_cid": null,
+					},
+				],
+			}),
+			Self::NewBooksDigestConfirmation => json!({
+				"title": "Confirm your Stump new books digest",
+				"username": "reader",
+				"server_name": "Stump",
+				"code": "a1b2c3d4e5",
+				"expires_in_hours": 24,
+			}),
+			Self::JobFailure => json!({
+				"title": "A Stump job failed",
+				"server_name": "Stump",
+				"job_name": "library_scan",
+				"error": "Failed to read directory: permission denied",
+				"failed_at": "2025-03-14T12:00:00Z",
+			}),
+		}
+	}
 }
 
 impl AsRef<str> for EmailTemplate {
 	fn as_ref(&self) -> &str {
 		match self {
 			Self::Attachment => "attachment",
+			Self::Invitation => "invitation",
+			Self::PasswordReset => "password_reset",
+			Self::NewBooksDigest => "new_books_digest",
+			Self::NewBooksDigestConfirmation => "new_books_digest_confirmation",
+			Self::JobFailure => "job_failure",
+		}
+	}
+}
+
+impl fmt::Display for EmailTemplate {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Attachment => write!(f, "ATTACHMENT"),
+			Self::Invitation => write!(f, "INVITATION"),
+			Self::PasswordReset => write!(f, "PASSWORD_RESET"),
+			Self::NewBooksDigest => write!(f, "NEW_BOOKS_DIGEST"),
+			Self::NewBooksDigestConfirmation => {
+				write!(f, "NEW_BOOKS_DIGEST_CONFIRMATION")
+			},
+			Self::JobFailure => write!(f, "JOB_FAILURE"),
+		}
+	}
+}
+
+impl FromStr for EmailTemplate {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"ATTACHMENT" => Ok(Self::Attachment),
+			"INVITATION" => Ok(Self::Invitation),
+			"PASSWORD_RESET" => Ok(Self::PasswordReset),
+			"NEW_BOOKS_DIGEST" => Ok(Self::NewBooksDigest),
+			"NEW_BOOKS_DIGEST_CONFIRMATION" => Ok(Self::NewBooksDigestConfirmation),
+			"JOB_FAILURE" => Ok(Self::JobFailure),
+			_ => Err(format!("\"{s}\" is not a valid email template")),
 		}
 	}
 }
 
+/// Read the source of a template from the templates directory, if it has been replaced on disk
+pub fn read_template_from_disk(
+	template: EmailTemplate,
+	templates_dir: PathBuf,
+) -> Option<String> {
+	let path = templates_dir.join(format!("{}.hbs", template.as_ref()));
+	std::fs::read_to_string(path).ok()
+}
+
 /// Render a template to a string using the given data and templates directory.
 /// If the template does not exist on disk, the default template will be used.
 ///
@@ -44,6 +212,35 @@ pub fn render_template(
 	template: EmailTemplate,
 	data: &serde_json::Value,
 	templates_dir: PathBuf,
+) -> EmailResult<String> {
+	render_template_with_overrides(template, data, templates_dir, &HashMap::new())
+}
+
+/// Render a template to a string using the given data, where the given overrides take priority
+/// over the templates on disk, which take priority over the defaults.
+///
+/// # Example
+/// ```no_run
+/// use email::{render_template_with_overrides, EmailTemplate, TemplateOverrides};
+/// use serde_json::json;
+/// use std::path::PathBuf;
+///
+/// let data = json!({
+///     "title": "Stump Attachment",
+/// });
+/// let overrides = TemplateOverrides::from([(
+///     EmailTemplate::Attachment,
+///     "<p>{{title}}</p>".to_string(),
+/// )]);
+///
+/// let rendered = render_template_with_overrides(EmailTemplate::Attachment, &data, PathBuf::from("templates"), &overrides).unwrap();
+/// assert_eq!(rendered, "<p>Stump Attachment</p>");
+/// ```
+pub fn render_template_with_overrides(
+	template: EmailTemplate,
+	data: &serde_json::Value,
+	templates_dir: PathBuf,
+	overrides: &TemplateOverrides,
 ) -> EmailResult<String> {
 	let mut handlebars = Handlebars::new();
 	handlebars.register_partial("base_partial", "{{> base}}")?;
@@ -57,9 +254,30 @@ pub fn render_template(
 		}
 	}
 
+	for (template, source) in overrides {
+		handlebars.register_template_string(template.as_ref(), source)?;
+	}
+
 	Ok(handlebars.render(template.as_ref(), data)?)
 }
 
+/// Validate the source of a template before it is saved. The source must compile, and render
+/// with the sample data of the template (e.g. any partials it uses must exist).
+pub fn validate_template(
+	template: EmailTemplate,
+	source: &str,
+	templates_dir: PathBuf,
+) -> EmailResult<()> {
+	let overrides = TemplateOverrides::from([(template, source.to_string())]);
+	render_template_with_overrides(
+		template,
+		&template.sample_data(),
+		templates_dir,
+		&overrides,
+	)
+	.map(|_| ())
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -92,4 +310,79 @@ mod tests {
 
 		assert!(rendered.contains("Stump Attachment"));
 	}
+
+	#[test]
+	fn render_default_templates_with_sample_data() {
+		for template in EmailTemplate::ALL {
+			let rendered =
+				render_template(template, &template.sample_data(), PathBuf::new())
+					.unwrap();
+			assert!(rendered.contains("<html>"), "{template} should extend base");
+		}
+
+		let rendered = render_template(
+			EmailTemplate::NewBooksDigest,
+			&EmailTemplate::NewBooksDigest.sample_data(),
+			PathBuf::new(),
+		)
+		.unwrap();
+		assert!(rendered.contains("The Way of Kings"));
+		assert!(rendered.contains("cid:book-1"));
+		assert!(rendered.contains("1 more"));
+	}
+
+	#[test]
+	fn render_template_with_override() {
+		let overrides = TemplateOverrides::from([(
+			EmailTemplate::Invitation,
+			"{{#*inline \"page\"}}<p>Welcome, {{username}}</p>{{/inline}}{{> base}}"
+				.to_string(),
+		)]);
+
+		let rendered = render_template_with_overrides(
+			EmailTemplate::Invitation,
+			&EmailTemplate::Invitation.sample_data(),
+			default_templates_dir(),
+			&overrides,
+		)
+		.unwrap();
+
+		assert!(rendered.contains("<p>Welcome, reader</p>"));
+	}
+
+	#[test]
+	fn validate_template_rejects_invalid_syntax() {
+		assert!(validate_template(
+			EmailTemplate::JobFailure,
+			"<p>{{job_name}}</p>",
+			PathBuf::new()
+		)
+		.is_ok());
+		assert!(validate_template(
+			EmailTemplate::JobFailure,
+			"<p>{{#if job_name}}</p>",
+			PathBuf::new()
+		)
+		.is_err());
+		assert!(validate_template(
+			EmailTemplate::JobFailure,
+			"{{> missing_partial}}",
+			PathBuf::new()
+		)
+		.is_err());
+	}
+
+	#[test]
+	fn email_template_round_trip() {
+		for template in EmailTemplate::ALL {
+			assert_eq!(
+				EmailTemplate::from_str(&template.to_string()).unwrap(),
+				template
+			);
+			assert_eq!(
+				serde_json::to_value(template).unwrap(),
+				serde_json::Value::String(template.to_string())
+			);
+		}
+	}
 }
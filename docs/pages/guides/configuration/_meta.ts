@@ -2,6 +2,7 @@ import { Meta } from 'nextra'
 
 export default {
 	'server-options': 'Server',
+	backups: 'Backups',
 	theming: 'Theming',
 	layout: 'Layout',
 } satisfies Meta
@@ -4,6 +4,7 @@ import { AuthenticationMethod, Configuration } from './configuration'
 import {
 	APIKeyAPI,
 	AuthAPI,
+	BackupAPI,
 	BookClubAPI,
 	EmailerAPI,
 	EpubAPI,
@@ -18,6 +19,7 @@ import {
 	ServerAPI,
 	SmartListAPI,
 	TagAPI,
+	TrashAPI,
 	UploadAPI,
 	UserAPI,
 } from './controllers'
@@ -229,6 +231,13 @@ export class Api {
 		return new AuthAPI(this)
 	}
 
+	/**
+	 * Get an instance for the BackupAPI
+	 */
+	get backup(): BackupAPI {
+		return new BackupAPI(this)
+	}
+
 	/**
 	 * Get an instance for the APIKeyAPI
 	 */
@@ -331,6 +340,13 @@ export class Api {
 		return new TagAPI(this)
 	}
 
+	/**
+	 * Get an instance for the TrashAPI
+	 */
+	get trash(): TrashAPI {
+		return new TrashAPI(this)
+	}
+
 	get upload(): UploadAPI {
 		return new UploadAPI(this)
 	}
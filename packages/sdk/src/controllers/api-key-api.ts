@@ -1,5 +1,5 @@
 import { APIBase } from '../base'
-import { APIKey, CreatedAPIKey, CreateOrUpdateAPIKey } from '../types'
+import { APIKey, APIKeyUsage, CreatedAPIKey, CreateOrUpdateAPIKey } from '../types'
 import { ClassQueryKeys } from './types'
 import { createRouteURLHandler } from './utils'
 
@@ -41,6 +41,14 @@ export class APIKeyAPI extends APIBase {
 		return key
 	}
 
+	/**
+	 * Fetch the daily usage of an API key, most recent day first
+	 */
+	async getUsage(id: number): Promise<APIKeyUsage[]> {
+		const { data: usage } = await this.api.axios.get<APIKeyUsage[]>(apiKeyURL(`/${id}/usage`))
+		return usage
+	}
+
 	/**
 	 * Create a new API key
 	 */
@@ -72,6 +80,7 @@ export class APIKeyAPI extends APIBase {
 			get: 'api-key.get',
 			validateKey: 'api-key.validateKey',
 			getByID: 'api-key.getByID',
+			getUsage: 'api-key.getUsage',
 			create: 'api-key.create',
 			update: 'api-key.update',
 			delete: 'api-key.delete',
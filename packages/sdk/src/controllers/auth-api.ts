@@ -1,5 +1,19 @@
 import { APIBase } from '../base'
-import { LoginOrRegisterArgs, LoginResponse, User } from '../types'
+import {
+	DisableTwoFactor,
+	LoginOrRegisterArgs,
+	LoginResponse,
+	LoginTwoFactorArgs,
+	OidcAuthorizeParams,
+	OidcConfig,
+	OidcIdentity,
+	SetupTwoFactor,
+	TwoFactorRecoveryCodes,
+	TwoFactorSetup,
+	TwoFactorStatus,
+	User,
+	VerifyTwoFactorCode,
+} from '../types'
 import { ClassQueryKeys } from './types'
 import { createRouteURLHandler } from './utils'
 
@@ -50,6 +64,23 @@ export class AuthAPI extends APIBase {
 		return response.data
 	}
 
+	/**
+	 * Complete a login which requires a second factor, using the token returned by `login`
+	 * and a TOTP or recovery code
+	 */
+	async loginTwoFactor(args: LoginTwoFactorArgs): Promise<LoginResponse> {
+		const response = await this.api.axios.post<LoginResponse>(authURL('/login/2fa'), args)
+
+		if ('token' in response.data) {
+			const {
+				token: { access_token },
+			} = response.data
+			this.api.token = access_token
+		}
+
+		return response.data
+	}
+
 	/**
 	 * Register a new user with the given username and password
 	 */
@@ -75,10 +106,102 @@ export class AuthAPI extends APIBase {
 		}
 	}
 
+	/**
+	 * Fetch the two-factor authentication status of the currently authenticated user
+	 */
+	async twoFactorStatus(): Promise<TwoFactorStatus> {
+		const { data } = await this.api.axios.get<TwoFactorStatus>(authURL('/2fa'))
+		return data
+	}
+
+	/**
+	 * Start two-factor enrollment, returning the secret and provisioning URI to show as a QR code
+	 */
+	async setupTwoFactor(args: SetupTwoFactor): Promise<TwoFactorSetup> {
+		const { data } = await this.api.axios.post<TwoFactorSetup>(authURL('/2fa/setup'), args)
+		return data
+	}
+
+	/**
+	 * Complete two-factor enrollment, returning the one-time recovery codes
+	 */
+	async confirmTwoFactor(args: VerifyTwoFactorCode): Promise<TwoFactorRecoveryCodes> {
+		const { data } = await this.api.axios.post<TwoFactorRecoveryCodes>(
+			authURL('/2fa/confirm'),
+			args,
+		)
+		return data
+	}
+
+	/**
+	 * Replace the recovery codes of the currently authenticated user
+	 */
+	async regenerateRecoveryCodes(args: VerifyTwoFactorCode): Promise<TwoFactorRecoveryCodes> {
+		const { data } = await this.api.axios.post<TwoFactorRecoveryCodes>(
+			authURL('/2fa/recovery-codes'),
+			args,
+		)
+		return data
+	}
+
+	/**
+	 * Disable two-factor authentication for the currently authenticated user
+	 */
+	async disableTwoFactor(args: DisableTwoFactor): Promise<User> {
+		const { data } = await this.api.axios.post<User>(authURL('/2fa/disable'), args)
+		return data
+	}
+
+	/**
+	 * Fetch whether OpenID Connect login is available on the server
+	 */
+	async oidcConfig(): Promise<OidcConfig> {
+		const { data } = await this.api.axios.get<OidcConfig>(authURL('/oidc/config'))
+		return data
+	}
+
+	/**
+	 * The URL to navigate to in order to log in with (or link) an OpenID Connect identity. This
+	 * must be a full page navigation, since the server redirects to the identity provider.
+	 */
+	oidcAuthorizeURL(params?: Partial<OidcAuthorizeParams>): string {
+		return this.withServiceURL(authURL('/oidc/authorize', params))
+	}
+
+	/**
+	 * Fetch the OpenID Connect identities linked to the currently authenticated user
+	 */
+	async oidcIdentities(): Promise<OidcIdentity[]> {
+		const { data } = await this.api.axios.get<OidcIdentity[]>(authURL('/oidc/identities'))
+		return data
+	}
+
+	/**
+	 * Unlink an OpenID Connect identity from the currently authenticated user
+	 */
+	async unlinkOidcIdentity(id: string): Promise<OidcIdentity> {
+		const { data } = await this.api.axios.delete<OidcIdentity>(authURL(`/oidc/identities/${id}`))
+		return data
+	}
+
 	/**
 	 * The query keys for the auth API, used for query caching on a client (e.g. react-query)
 	 */
 	get keys(): ClassQueryKeys<InstanceType<typeof AuthAPI>> {
-		return { login: 'auth.login', logout: 'auth.logout', me: 'auth.me', register: 'auth.register' }
+		return {
+			confirmTwoFactor: 'auth.confirmTwoFactor',
+			disableTwoFactor: 'auth.disableTwoFactor',
+			login: 'auth.login',
+			loginTwoFactor: 'auth.loginTwoFactor',
+			logout: 'auth.logout',
+			me: 'auth.me',
+			oidcConfig: 'auth.oidcConfig',
+			oidcIdentities: 'auth.oidcIdentities',
+			regenerateRecoveryCodes: 'auth.regenerateRecoveryCodes',
+			register: 'auth.register',
+			setupTwoFactor: 'auth.setupTwoFactor',
+			twoFactorStatus: 'auth.twoFactorStatus',
+			unlinkOidcIdentity: 'auth.unlinkOidcIdentity',
+		}
 	}
 }
@@ -0,0 +1,51 @@
+import { APIBase } from '../base'
+import { Backup, CreateBackup, QueuedJob } from '../types'
+import { ClassQueryKeys } from './types'
+import { createRouteURLHandler } from './utils'
+
+/**
+ * The root route for the backup API
+ */
+const BACKUP_ROUTE = '/backups'
+/**
+ * A helper function to format the URL for backup API routes with optional query parameters
+ */
+const backupURL = createRouteURLHandler(BACKUP_ROUTE)
+
+/**
+ * The backup API controller, used for interacting with the backup endpoints of the Stump API
+ */
+export class BackupAPI extends APIBase {
+	/**
+	 * Fetch all backups, newest first
+	 */
+	async get(): Promise<Backup[]> {
+		const { data: backups } = await this.axios.get<Backup[]>(backupURL(''))
+		return backups
+	}
+
+	/**
+	 * Queue a job which creates a new backup
+	 */
+	async create(payload: CreateBackup = {}): Promise<QueuedJob> {
+		const { data: job } = await this.axios.post<QueuedJob>(backupURL(''), payload)
+		return job
+	}
+
+	/**
+	 * The URL for downloading a backup
+	 */
+	downloadURL(name: string): string {
+		return this.withServiceURL(backupURL(`/${name}`))
+	}
+
+	/**
+	 * The query keys for the backup API, used for caching
+	 */
+	get keys(): ClassQueryKeys<InstanceType<typeof BackupAPI>> {
+		return {
+			create: 'backup.create',
+			get: 'backup.get',
+		}
+	}
+}
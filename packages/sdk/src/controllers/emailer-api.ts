@@ -5,11 +5,16 @@ import {
 	EmailerIncludeParams,
 	EmailerSendRecord,
 	EmailerSendRecordIncludeParams,
+	EmailTemplate,
+	EmailTemplateDetails,
+	EmailTemplatePreview,
 	PatchEmailDevice,
+	PreviewEmailTemplate,
 	RegisteredEmailDevice,
 	SendAttachmentEmailResponse,
 	SendAttachmentEmailsPayload,
 	SMTPEmailer,
+	UpdateEmailTemplate,
 } from '../types'
 import { ClassQueryKeys } from './types'
 import { createRouteURLHandler } from './utils'
@@ -32,6 +37,15 @@ const EMAIL_DEVICE_ROUTE = '/email-devices'
  */
 const emailDeviceURL = createRouteURLHandler(EMAIL_DEVICE_ROUTE)
 
+/**
+ * The root route for the email template API
+ */
+const EMAIL_TEMPLATE_ROUTE = '/email-templates'
+/**
+ * A helper function to format the URL for email template API routes with optional query parameters
+ */
+const emailTemplateURL = createRouteURLHandler(EMAIL_TEMPLATE_ROUTE)
+
 /**
  * The emailer API controller, used for interacting with the emailer endpoints of the Stump API
  */
@@ -172,6 +186,65 @@ export class EmailerAPI extends APIBase {
 		return deletedDevice
 	}
 
+	/**
+	 * Fetch all email templates, along with the source each is currently rendered from
+	 */
+	async getTemplates(): Promise<EmailTemplateDetails[]> {
+		const { data: templates } = await this.api.axios.get<EmailTemplateDetails[]>(
+			emailTemplateURL(''),
+		)
+		return templates
+	}
+
+	/**
+	 * Fetch an email template, along with the source it is currently rendered from
+	 */
+	async getTemplate(template: EmailTemplate): Promise<EmailTemplateDetails> {
+		const { data: details } = await this.api.axios.get<EmailTemplateDetails>(
+			emailTemplateURL(`/${template}`),
+		)
+		return details
+	}
+
+	/**
+	 * Override an email template. The source is validated before it is saved
+	 */
+	async updateTemplate(
+		template: EmailTemplate,
+		payload: UpdateEmailTemplate,
+	): Promise<EmailTemplateDetails> {
+		const { data: updatedTemplate } = await this.api.axios.put<EmailTemplateDetails>(
+			emailTemplateURL(`/${template}`),
+			payload,
+		)
+		return updatedTemplate
+	}
+
+	/**
+	 * Remove the override of an email template
+	 */
+	async resetTemplate(template: EmailTemplate): Promise<EmailTemplateDetails> {
+		const { data: resetTemplate } = await this.api.axios.delete<EmailTemplateDetails>(
+			emailTemplateURL(`/${template}`),
+		)
+		return resetTemplate
+	}
+
+	/**
+	 * Render an email template with sample data. If a source is provided, it is rendered
+	 * in place of the current source of the template
+	 */
+	async previewTemplate(
+		template: EmailTemplate,
+		payload: PreviewEmailTemplate = {},
+	): Promise<EmailTemplatePreview> {
+		const { data: preview } = await this.api.axios.post<EmailTemplatePreview>(
+			emailTemplateURL(`/${template}/preview`),
+			payload,
+		)
+		return preview
+	}
+
 	get keys(): ClassQueryKeys<InstanceType<typeof EmailerAPI>> {
 		return {
 			create: 'emailer.create',
@@ -183,10 +256,15 @@ export class EmailerAPI extends APIBase {
 			getDeviceByID: 'emailer.getDeviceByID',
 			getDevices: 'emailer.getDevices',
 			getSendHistory: 'emailer.getSendHistory',
+			getTemplate: 'emailer.getTemplate',
+			getTemplates: 'emailer.getTemplates',
 			patchDevice: 'emailer.patchDevice',
+			previewTemplate: 'emailer.previewTemplate',
+			resetTemplate: 'emailer.resetTemplate',
 			send: 'emailer.send',
 			update: 'emailer.update',
 			updateDevice: 'emailer.updateDevice',
+			updateTemplate: 'emailer.updateTemplate',
 		}
 	}
 }
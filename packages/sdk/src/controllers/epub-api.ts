@@ -4,6 +4,7 @@ import {
 	CreateOrUpdateBookmark,
 	DeleteBookmark,
 	Epub,
+	EpubPositionList,
 	UpdateEpubProgress,
 } from '../types'
 import { ClassQueryKeys } from './types'
@@ -55,6 +56,16 @@ export class EpubAPI extends APIBase {
 		return resource
 	}
 
+	/**
+	 * Fetch the positions list of an epub by its ID
+	 */
+	async getPositions(id: string): Promise<EpubPositionList> {
+		const { data: positions } = await this.api.axios.get<EpubPositionList>(
+			epubURL(`${id}/positions`),
+		)
+		return positions
+	}
+
 	async updateProgress({ id, ...payload }: UpdateEpubProgress & { id: string }) {
 		const { data: updatedProgress } = await this.api.axios.put<UpdateEpubProgress>(
 			epubURL(`${id}/progress`),
@@ -115,6 +126,7 @@ export class EpubAPI extends APIBase {
 			fetchResource: 'epub.fetchResource',
 			getBookmarks: 'epub.getBookmarks',
 			getByID: 'epub.getByID',
+			getPositions: 'epub.getPositions',
 			updateBookmark: 'epub.updateBookmark',
 			updateProgress: 'epub.updateProgress',
 		}
@@ -1,5 +1,6 @@
 export * from './api-key-api'
 export * from './auth-api'
+export * from './backup-api'
 export * from './bookclub-api'
 export * from './emailer-api'
 export * from './epub-api'
@@ -14,6 +15,7 @@ export * from './series-api'
 export * from './server-api'
 export * from './smartlist-api'
 export * from './tag-api'
+export * from './trash-api'
 export * from './upload-api'
 export * from './user-api'
 export * from './utils'
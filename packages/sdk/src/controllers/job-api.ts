@@ -1,5 +1,11 @@
 import { APIBase } from '../base'
-import { JobSchedulerConfig, Pageable, PersistedJob, UpdateSchedulerConfig } from '../types'
+import {
+	JobSchedulerConfig,
+	Pageable,
+	PersistedJob,
+	ScanDiff,
+	UpdateSchedulerConfig,
+} from '../types'
 import { ClassQueryKeys, PagedQueryParams } from './types'
 import { createRouteURLHandler } from './utils'
 
@@ -32,6 +38,14 @@ export class JobAPI extends APIBase {
 		return job
 	}
 
+	/**
+	 * Fetch the changes recorded by a completed dry run library or series scan
+	 */
+	async getScanDiff(id: string): Promise<ScanDiff> {
+		const { data: diff } = await this.axios.get<ScanDiff>(jobURL(`${id}/scan-diff`))
+		return diff
+	}
+
 	/**
 	 * Cancel a job by its ID
 	 */
@@ -82,6 +96,7 @@ export class JobAPI extends APIBase {
 			deleteAll: 'job.deleteAll',
 			get: 'job.get',
 			getByID: 'job.getByID',
+			getScanDiff: 'job.getScanDiff',
 			getSchedulerConfig: 'job.getSchedulerConfig',
 			updateSchedulerConfig: 'job.updateSchedulerConfig',
 		}
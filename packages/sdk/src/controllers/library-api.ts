@@ -7,11 +7,14 @@ import {
 	Library,
 	LibraryFilter,
 	LibraryScanRecord,
+	LibrarySeriesPreview,
 	LibraryStats,
 	LibraryStatsParams,
 	Pageable,
 	PaginationQuery,
 	PatchLibraryThumbnail,
+	PreviewLibrarySeries,
+	QueuedJob,
 	ScanOptions,
 	Series,
 	UpdateLibrary,
@@ -72,6 +75,18 @@ export class LibraryAPI extends APIBase {
 		return createdLibrary
 	}
 
+	/**
+	 * Preview how a directory would be split into series, without creating or changing
+	 * anything
+	 */
+	async previewSeries(payload: PreviewLibrarySeries): Promise<LibrarySeriesPreview> {
+		const { data: preview } = await this.api.axios.post<LibrarySeriesPreview>(
+			libraryURL('/series-preview'),
+			payload,
+		)
+		return preview
+	}
+
 	/**
 	 * Get the URL for fetching a library thumbnail
 	 *
@@ -194,10 +209,15 @@ export class LibraryAPI extends APIBase {
 	}
 
 	/**
-	 * Initiate a scan of a library
+	 * Initiate a scan of a library, returning the queued job. The changes of a dry run scan
+	 * can be fetched with the job ID once it completes
 	 */
-	async scan(id: string, options: ScanOptions = {}): Promise<void> {
-		await this.api.axios.post(libraryURL(`/${id}/scan`), options)
+	async scan(id: string, options: ScanOptions = {}): Promise<QueuedJob> {
+		const { data: job } = await this.api.axios.post<QueuedJob>(
+			libraryURL(`/${id}/scan`),
+			options,
+		)
+		return job
 	}
 
 	async lastScanDetails(id: string): Promise<LastScanDetails> {
@@ -272,6 +292,7 @@ export class LibraryAPI extends APIBase {
 			getSeriesCursor: 'library.getSeriesCursor',
 			getLastVisited: 'library.getLastVisited',
 			getStats: 'library.getStats',
+			previewSeries: 'library.previewSeries',
 			scan: 'library.scan',
 			lastScanDetails: 'library.lastScanDetails',
 			scanHistory: 'library.scanHistory',
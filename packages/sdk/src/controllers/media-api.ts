@@ -1,14 +1,25 @@
 import { APIBase } from '../base'
 import {
+	ActiveReadingSession,
+	DuplicateGroup,
+	DuplicateGroupsParams,
 	Media,
 	MediaFilter,
 	MediaMetadata,
 	Pageable,
+	PaginationQuery,
 	PatchMediaThumbnail,
+	PdfOutlineItem,
+	PdfPageText,
 	ProgressUpdateReturn,
 	PutMediaCompletionStatus,
 	PutMediaProgress,
+	PutMediaProgressHeartbeat,
+	ReadingOverrides,
+	ReadingPreferences,
+	ResolveDuplicateGroup,
 	ScaledDimensionResize,
+	VerifyMediaParams,
 } from '../types'
 import { ClassQueryKeys, CursorQueryParams, FullQueryParams } from './types'
 import { createRouteURLHandler } from './utils'
@@ -101,6 +112,27 @@ export class MediaAPI extends APIBase {
 		return this.withServiceURL(mediaURL(`${mediaID}/page/${page}`, params))
 	}
 
+	/**
+	 * Fetch the outline (bookmarks) of a PDF media entity
+	 *
+	 * @param id The ID of the media entity
+	 */
+	async getOutline(id: string): Promise<PdfOutlineItem[]> {
+		const { data: outline } = await this.axios.get<PdfOutlineItem[]>(mediaURL(`${id}/outline`))
+		return outline
+	}
+
+	/**
+	 * Fetch the extracted text of a page of a PDF media entity
+	 *
+	 * @param id The ID of the media entity
+	 * @param page The page to extract the text of, starting at 1
+	 */
+	async getPageText(id: string, page: number): Promise<PdfPageText> {
+		const { data: text } = await this.axios.get<PdfPageText>(mediaURL(`${id}/page/${page}/text`))
+		return text
+	}
+
 	/**
 	 * Update the progress of a media entity
 	 */
@@ -109,6 +141,17 @@ export class MediaAPI extends APIBase {
 		return data
 	}
 
+	/**
+	 * Record reading activity for a media entity, accumulating reading time on the active session
+	 */
+	async heartbeat(
+		mediaID: string,
+		params: PutMediaProgressHeartbeat = {},
+	): Promise<ActiveReadingSession> {
+		const { data } = await this.axios.put(mediaURL(`${mediaID}/progress/heartbeat`), params)
+		return data
+	}
+
 	/**
 	 * Update the thumbnail of a media entity
 	 */
@@ -171,24 +214,132 @@ export class MediaAPI extends APIBase {
 		return updatedMeta
 	}
 
+	/**
+	 * Fetch the reading direction and mode a media entity should be opened with, after the
+	 * overrides of the media and its series, its metadata and the library defaults are applied
+	 *
+	 * @param id The ID of the media entity
+	 */
+	async getReadingPreferences(id: string): Promise<ReadingPreferences> {
+		const { data: preferences } = await this.axios.get<ReadingPreferences>(
+			mediaURL(`${id}/reading-preferences`),
+		)
+		return preferences
+	}
+
+	/**
+	 * Update the reading direction and mode overrides of a media entity. Overrides which are
+	 * not set are removed
+	 *
+	 * @param id The ID of the media entity
+	 * @param payload The overrides to set
+	 */
+	async updateReadingOverrides(id: string, payload: ReadingOverrides): Promise<Media> {
+		const { data: media } = await this.axios.put<Media>(
+			mediaURL(`${id}/reading-overrides`),
+			payload,
+		)
+		return media
+	}
+
+	/**
+	 * Start a job which finds likely duplicate media, replacing the current duplicate report
+	 */
+	async analyzeDuplicates(): Promise<void> {
+		await this.axios.post(mediaURL('duplicates/analyze'))
+	}
+
+	/**
+	 * Fetch the duplicate groups found by the last duplicate analysis
+	 */
+	async getDuplicateGroups(params?: DuplicateGroupsParams): Promise<DuplicateGroup[]> {
+		const { data: groups } = await this.axios.get<DuplicateGroup[]>(
+			mediaURL('duplicates/groups', params),
+		)
+		return groups
+	}
+
+	/**
+	 * Dismiss a duplicate group, so it is not reported again by future analyses
+	 *
+	 * @param id The ID of the duplicate group
+	 */
+	async dismissDuplicateGroup(id: string): Promise<DuplicateGroup> {
+		const { data: group } = await this.axios.post<DuplicateGroup>(
+			mediaURL(`duplicates/groups/${id}/dismiss`),
+		)
+		return group
+	}
+
+	/**
+	 * Resolve a duplicate group by keeping one media, merging reading progress into it and
+	 * trashing the rest
+	 *
+	 * @param id The ID of the duplicate group
+	 * @param payload The media to keep, and whether to delete the other files from disk
+	 */
+	async resolveDuplicateGroup(id: string, payload: ResolveDuplicateGroup): Promise<Media> {
+		const { data: media } = await this.axios.post<Media>(
+			mediaURL(`duplicates/groups/${id}/resolve`),
+			payload,
+		)
+		return media
+	}
+
+	/**
+	 * Start a job which verifies the integrity of media files, marking broken files with the
+	 * ERROR status and the reason they failed
+	 *
+	 * @param params The library to verify, otherwise all media are verified
+	 */
+	async verify(params?: VerifyMediaParams): Promise<void> {
+		await this.axios.post(mediaURL('verify', params))
+	}
+
+	/**
+	 * Fetch a page of the media which failed verification
+	 *
+	 * @param params The library to report on (otherwise all libraries are included) and the
+	 * pagination options
+	 */
+	async getVerificationReport(
+		params?: VerifyMediaParams & PaginationQuery,
+	): Promise<Pageable<Media[]>> {
+		const { data: media } = await this.axios.get<Pageable<Media[]>>(
+			mediaURL('verify/report', params),
+		)
+		return media
+	}
+
 	/**
 	 * The keys for the media API, used for query caching on a client (e.g. react-query)
 	 */
 	get keys(): ClassQueryKeys<InstanceType<typeof MediaAPI>> {
 		return {
 			analyze: 'media.analyze',
+			analyzeDuplicates: 'media.analyzeDuplicates',
 			complete: 'media.complete',
 			deleteActiveReadingSession: 'media.deleteActiveReadingSession',
+			dismissDuplicateGroup: 'media.dismissDuplicateGroup',
 			get: 'media.get',
 			getByID: 'media.getByID',
 			getByPath: 'media.getByPath',
 			getCursor: 'media.getCursor',
+			getDuplicateGroups: 'media.getDuplicateGroups',
+			getOutline: 'media.getOutline',
+			getPageText: 'media.getPageText',
+			getReadingPreferences: 'media.getReadingPreferences',
+			getVerificationReport: 'media.getVerificationReport',
 			inProgress: 'media.inProgress',
 			patchThumbnail: 'media.patchThumbnail',
 			recentlyAdded: 'media.recentlyAdded',
+			resolveDuplicateGroup: 'media.resolveDuplicateGroup',
 			updateProgress: 'media.updateProgress',
+			updateReadingOverrides: 'media.updateReadingOverrides',
 			uploadThumbnail: 'media.uploadThumbnail',
+			verify: 'media.verify',
 			getMeta: 'media.getMeta',
+			heartbeat: 'media.heartbeat',
 			updateMeta: 'media.updateMeta',
 		}
 	}
@@ -1,5 +1,14 @@
 import { APIBase } from '../base'
-import { Media, Pageable, PatchSeriesThumbnail, Series, SeriesFilter } from '../types'
+import {
+	Media,
+	Pageable,
+	PatchSeriesThumbnail,
+	QueuedJob,
+	ReadingOverrides,
+	ScanOptions,
+	Series,
+	SeriesFilter,
+} from '../types'
 import { MediaAPI } from './media-api'
 import { ClassQueryKeys, CursorQueryParams, FullQueryParams, PagedQueryParams } from './types'
 import { createRouteURLHandler } from './utils'
@@ -134,6 +143,30 @@ export class SeriesAPI extends APIBase {
 		await this.axios.post(seriesURL(`${id}/analyze`))
 	}
 
+	/**
+	 * Initiate a scan of a series, returning the queued job. The changes of a dry run scan
+	 * can be fetched with the job ID once it completes
+	 */
+	async scan(id: string, options: ScanOptions = {}): Promise<QueuedJob> {
+		const { data: job } = await this.axios.post<QueuedJob>(seriesURL(`${id}/scan`), options)
+		return job
+	}
+
+	/**
+	 * Update the reading direction and mode overrides of a series, which apply to the books in
+	 * the series without overrides of their own. Overrides which are not set are removed
+	 *
+	 * @param id The ID of the series
+	 * @param payload The overrides to set
+	 */
+	async updateReadingOverrides(id: string, payload: ReadingOverrides): Promise<Series> {
+		const { data: series } = await this.axios.put<Series>(
+			seriesURL(`${id}/reading-overrides`),
+			payload,
+		)
+		return series
+	}
+
 	/**
 	 * The keys for the series API
 	 */
@@ -148,6 +181,8 @@ export class SeriesAPI extends APIBase {
 			nextBooks: 'series.nextBooks',
 			patchThumbnail: 'series.patchThumbnail',
 			recentlyAdded: 'series.recentlyAdded',
+			scan: 'series.scan',
+			updateReadingOverrides: 'series.updateReadingOverrides',
 			uploadThumbnail: 'series.uploadThumbnail',
 		}
 	}
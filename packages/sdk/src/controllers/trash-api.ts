@@ -0,0 +1,62 @@
+import { APIBase } from '../base'
+import { Media, Pageable, PaginationQuery, PurgedTrash } from '../types'
+import { ClassQueryKeys } from './types'
+import { createRouteURLHandler } from './utils'
+
+/**
+ * The root route for the trash API
+ */
+const TRASH_ROUTE = '/trash'
+/**
+ * A helper function to format the URL for trash API routes with optional query parameters
+ */
+const trashURL = createRouteURLHandler(TRASH_ROUTE)
+
+/**
+ * The trash API controller, used for managing media which has been moved to the trash
+ */
+export class TrashAPI extends APIBase {
+	/**
+	 * Fetch the media in the trash, most recently trashed first
+	 */
+	async get(params?: PaginationQuery): Promise<Pageable<Media[]>> {
+		const { data: media } = await this.axios.get<Pageable<Media[]>>(trashURL('', params))
+		return media
+	}
+
+	/**
+	 * Restore a media from the trash
+	 */
+	async restore(id: string): Promise<Media> {
+		const { data: media } = await this.axios.post<Media>(trashURL(`/${id}/restore`))
+		return media
+	}
+
+	/**
+	 * Permanently delete a media from the trash
+	 */
+	async purge(id: string): Promise<PurgedTrash> {
+		const { data: purged } = await this.axios.delete<PurgedTrash>(trashURL(`/${id}`))
+		return purged
+	}
+
+	/**
+	 * Permanently delete all media in the trash
+	 */
+	async empty(): Promise<PurgedTrash> {
+		const { data: purged } = await this.axios.delete<PurgedTrash>(trashURL(''))
+		return purged
+	}
+
+	/**
+	 * The query keys for the trash API, used for caching
+	 */
+	get keys(): ClassQueryKeys<InstanceType<typeof TrashAPI>> {
+		return {
+			empty: 'trash.empty',
+			get: 'trash.get',
+			purge: 'trash.purge',
+			restore: 'trash.restore',
+		}
+	}
+}
@@ -46,4 +46,5 @@ export type ClassQueryKeys<T> = Omit<
 	| 'bookThumbnailURL'
 	| 'bookFileURL'
 	| 'imageURL'
+	| 'oidcAuthorizeURL'
 >
@@ -1,5 +1,11 @@
 import { APIBase } from '../base'
-import { UploadConfig } from '../types'
+import {
+	CompletedUpload,
+	CreateUploadSession,
+	UploadConfig,
+	UploadPreview,
+	UploadSession,
+} from '../types'
 import { ClassQueryKeys } from './types'
 import { createRouteURLHandler } from './utils'
 
@@ -92,11 +98,105 @@ export class UploadAPI extends APIBase {
 		})
 	}
 
+	/**
+	 * Start a resumable upload of a single book to a library
+	 */
+	async createSession(libraryId: string, params: CreateUploadSession) {
+		const { data } = await this.axios.post<UploadSession>(
+			uploadURL(`/libraries/${libraryId}/sessions`),
+			params,
+		)
+		return data
+	}
+
+	/**
+	 * Fetch the state of a resumable upload, including the offset it should be resumed from
+	 */
+	async getSession(id: string) {
+		const { data } = await this.axios.get<UploadSession>(uploadURL(`/sessions/${id}`))
+		return data
+	}
+
+	/**
+	 * Send a chunk of a resumable upload, starting at the given offset
+	 *
+	 * @param checksum An optional checksum of the chunk, formatted as `sha256 <base64 digest>`
+	 */
+	async uploadChunk(id: string, offset: number, chunk: Blob, checksum?: string) {
+		const { data } = await this.axios.patch<UploadSession>(
+			uploadURL(`/sessions/${id}`),
+			chunk,
+			{
+				headers: {
+					'Content-Type': 'application/offset+octet-stream',
+					'Upload-Offset': offset.toString(),
+					...(checksum ? { 'Upload-Checksum': checksum } : {}),
+				},
+			},
+		)
+		return data
+	}
+
+	/**
+	 * Upload the remainder of a file for a resumable upload, in chunks of the given size. If the
+	 * upload was interrupted, it is resumed from the offset the server last received.
+	 */
+	async uploadSessionFile(
+		id: string,
+		file: File,
+		{
+			chunkSize = 8 * 1024 * 1024,
+			onProgress,
+		}: Pick<UploaderParams<object>, 'onProgress'> & { chunkSize?: number } = {},
+	) {
+		let session = await this.getSession(id)
+		while (session.offset < session.size) {
+			const chunk = file.slice(session.offset, session.offset + chunkSize)
+			session = await this.uploadChunk(id, session.offset, chunk)
+			onProgress?.(Math.round((session.offset * 100) / session.size))
+		}
+		return session
+	}
+
+	/**
+	 * Preview the detected metadata and target series of a fully received upload
+	 */
+	async previewSession(id: string) {
+		const { data } = await this.axios.get<UploadPreview>(
+			uploadURL(`/sessions/${id}/preview`),
+		)
+		return data
+	}
+
+	/**
+	 * Complete a resumable upload, moving the book into the library and scanning it
+	 */
+	async completeSession(id: string) {
+		const { data } = await this.axios.post<CompletedUpload>(
+			uploadURL(`/sessions/${id}/complete`),
+		)
+		return data
+	}
+
+	/**
+	 * Abort a resumable upload, discarding any data received so far
+	 */
+	async abortSession(id: string) {
+		await this.axios.delete(uploadURL(`/sessions/${id}`))
+	}
+
 	get keys(): ClassQueryKeys<InstanceType<typeof UploadAPI>> {
 		return {
+			abortSession: 'upload.abortSession',
+			completeSession: 'upload.completeSession',
 			config: 'upload.config',
+			createSession: 'upload.createSession',
+			getSession: 'upload.getSession',
+			previewSession: 'upload.previewSession',
+			uploadChunk: 'upload.uploadChunk',
 			uploadLibraryBooks: 'upload.LibraryBooks',
 			uploadLibrarySeries: 'upload.LibrarySeries',
+			uploadSessionFile: 'upload.uploadSessionFile',
 		}
 	}
 }
@@ -1,14 +1,18 @@
 import { APIBase } from '../base'
 import {
 	Arrangement,
+	ConfirmNewBooksDigest,
 	CreateUser,
 	LoginActivity,
 	NavigationItem,
+	NewBooksDigestSubscription,
 	Pageable,
 	PaginationQuery,
+	UpdateNewBooksDigestSubscription,
 	UpdateUser,
 	UpdateUserPreferences,
 	User,
+	UserNotification,
 	UserPreferences,
 	UserQueryRelation,
 } from '../types'
@@ -180,21 +184,91 @@ export class UserAPI extends APIBase {
 		await this.axios.delete(userURL(`${forUser}/sessions`))
 	}
 
+	/**
+	 * Fetch the current authenticated user's new books digest subscription, if they opted in
+	 */
+	async newBooksDigest(): Promise<NewBooksDigestSubscription | null> {
+		const { data: subscription } = await this.axios.get<NewBooksDigestSubscription | null>(
+			userURL('/me/new-books-digest'),
+		)
+		return subscription
+	}
+
+	/**
+	 * Opt the current authenticated user in to the new books digest, or update their subscription
+	 */
+	async updateNewBooksDigest(
+		payload: UpdateNewBooksDigestSubscription,
+	): Promise<NewBooksDigestSubscription> {
+		const { data: subscription } = await this.axios.put<NewBooksDigestSubscription>(
+			userURL('/me/new-books-digest'),
+			payload,
+		)
+		return subscription
+	}
+
+	/**
+	 * Confirm the address of the current authenticated user's new books digest with the code
+	 * which was emailed to it
+	 */
+	async confirmNewBooksDigest(
+		payload: ConfirmNewBooksDigest,
+	): Promise<NewBooksDigestSubscription> {
+		const { data: subscription } = await this.axios.post<NewBooksDigestSubscription>(
+			userURL('/me/new-books-digest/confirm'),
+			payload,
+		)
+		return subscription
+	}
+
+	/**
+	 * Opt the current authenticated user out of the new books digest
+	 */
+	async deleteNewBooksDigest(): Promise<void> {
+		await this.axios.delete(userURL('/me/new-books-digest'))
+	}
+
+	/**
+	 * Fetch the most recent notifications of the current authenticated user
+	 */
+	async notifications(): Promise<UserNotification[]> {
+		const { data: notifications } = await this.axios.get<UserNotification[]>(
+			userURL('/me/notifications'),
+		)
+		return notifications
+	}
+
+	/**
+	 * Mark a notification of the current authenticated user as read
+	 */
+	async markNotificationRead(id: string): Promise<UserNotification> {
+		const { data: notification } = await this.axios.put<UserNotification>(
+			userURL(`/me/notifications/${id}/read`),
+		)
+		return notification
+	}
+
 	get keys(): ClassQueryKeys<InstanceType<typeof UserAPI>> {
 		return {
+			confirmNewBooksDigest: 'user.confirmNewBooksDigest',
 			create: 'user.create',
 			delete: 'user.delete',
 			deleteLoginActivity: 'user.deleteLoginActivity',
+			deleteNewBooksDigest: 'user.deleteNewBooksDigest',
 			deleteUserSessions: 'user.deleteUserSessions',
 			get: 'user.get',
 			getByID: 'user.getByID',
 			getUserPreferences: 'user.getUserPreferences',
 			lockUser: 'user.lockUser',
 			loginActivity: 'user.loginActivity',
+			markNotificationRead: 'user.markNotificationRead',
 			navigationArrangement: 'user.navigationArrangement',
+			newBooksDigest: 'user.newBooksDigest',
+			notifications: 'user.notifications',
 			preferences: 'user.preferences',
 			update: 'user.update',
 			updateNavigationArrangement: 'user.updateNavigationArrangement',
+			updateNewBooksDigest: 'user.updateNewBooksDigest',
 			updatePreferences: 'user.updatePreferences',
 			updateUserPreferences: 'user.updateUserPreferences',
 			updateViewer: 'user.updateViewer',
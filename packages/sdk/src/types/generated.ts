@@ -42,12 +42,24 @@ export type JobUpdate = ({ status?: JobStatus | null; message?: string | null; c
  */
 export type JobProgress = { status?: JobStatus | null; message?: string | null; completed_tasks?: number | null; remaining_tasks?: number | null; completed_subtasks?: number | null; total_subtasks?: number | null }
 
+/**
+ * A media entity which was found at a new path during a scan, rather than being marked as
+ * missing and created again
+ */
+export type MovedMedia = { id: string; from: string; to: string }
+
+/**
+ * The changes a scan would make, as recorded by a dry run. All entries are paths on disk,
+ * so that nothing needs to exist in the database for the diff to be read.
+ */
+export type ScanDiff = { series_to_create: string[]; series_to_mark_missing: string[]; series_to_restore: string[]; media_to_create: string[]; media_to_mark_missing: string[]; media_to_restore: string[]; media_to_reassign: string[]; media_to_rebuild: string[]; media_to_rehash: string[]; media_with_changed_metadata: string[]; media_to_move?: MovedMedia[] }
+
 /**
  * The data that is collected and updated during the execution of a library scan job
  */
-export type LibraryScanOutput = { total_files: number; total_directories: number; ignored_files: number; skipped_files: number; ignored_directories: number; created_media: number; updated_media: number; created_series: number; updated_series: number }
+export type LibraryScanOutput = { total_files: number; total_directories: number; ignored_files: number; skipped_files: number; ignored_directories: number; created_media: number; updated_media: number; moved_media: MovedMedia[]; created_series: number; updated_series: number; diff?: ScanDiff | null; triggered_by?: string[] }
 
-export type SeriesScanOutput = { total_files: number; ignored_files: number; skipped_files: number; created_media: number; updated_media: number }
+export type SeriesScanOutput = { total_files: number; ignored_files: number; skipped_files: number; created_media: number; updated_media: number; moved_media: MovedMedia[]; diff?: ScanDiff | null; triggered_by?: string[] }
 
 export type ThumbnailGenerationJobVariant = ({ type: "SingleLibrary" } & string) | ({ type: "SingleSeries" } & string) | ({ type: "MediaGroup" } & string[])
 
@@ -55,7 +67,7 @@ export type ThumbnailGenerationJobParams = { variant: ThumbnailGenerationJobVari
 
 export type ThumbnailGenerationOutput = { visited_files: number; skipped_files: number; generated_thumbnails: number; removed_thumbnails: number }
 
-export type User = { id: string; username: string; is_server_owner: boolean; avatar_url: string | null; created_at: string; last_login: string | null; is_locked: boolean; permissions: UserPermission[]; max_sessions_allowed?: number | null; login_sessions_count?: number | null; user_preferences?: UserPreferences | null; login_activity?: LoginActivity[] | null; age_restriction?: AgeRestriction | null; active_reading_sessions?: ActiveReadingSession[] | null; finished_reading_sessions?: FinishedReadingSession[] | null }
+export type User = { id: string; username: string; is_server_owner: boolean; avatar_url: string | null; created_at: string; last_login: string | null; is_locked: boolean; two_factor_enabled: boolean; permissions: UserPermission[]; max_sessions_allowed?: number | null; login_sessions_count?: number | null; user_preferences?: UserPreferences | null; login_activity?: LoginActivity[] | null; age_restriction?: AgeRestriction | null; active_reading_sessions?: ActiveReadingSession[] | null; finished_reading_sessions?: FinishedReadingSession[] | null }
 
 /**
  * A partial representation of a user, which does not include all fields. This should be
@@ -76,12 +88,28 @@ export type AgeRestriction = { age: number; restrict_on_unset: boolean }
  * An API key which can be used to interact with the API. API keys are scoped to a user,
  * so all actions taken with an API key are done as if the user was taking them.
  */
-export type APIKey = { id: number; name: string; permissions: APIKeyPermissions; created_at: string; last_used_at: string | null; expires_at: string | null }
+export type APIKey = { id: number; name: string; permissions: APIKeyPermissions; scopes: APIKeyScopes; created_at: string; last_used_at: string | null; expires_at: string | null }
 
 export type InheritPermissionValue = "inherit"
 
 export type APIKeyPermissions = InheritPermissionValue | UserPermission[]
 
+/**
+ * A family of routes an API key may be restricted to
+ */
+export type APIKeyRouteScope = "api" | "opds" | "koreader"
+
+/**
+ * Restrictions placed on an API key, on top of its permissions. An empty set of scopes
+ * does not restrict the key at all.
+ */
+export type APIKeyScopes = { library_ids?: string[] | null; read_only?: boolean; routes?: APIKeyRouteScope[] | null }
+
+/**
+ * The requests made with an API key on a single day (UTC)
+ */
+export type APIKeyUsage = { id: number; day: string; request_count: number; last_used_at: string; last_method: string; last_path: string; last_ip_address: string | null }
+
 export type SupportedFont = "atkinsonhyperlegible" | "bitter" | "charis" | "inter" | "librebaskerville" | "literata" | "nunito" | "opendyslexic"
 
 export type NavigationMode = "SIDEBAR" | "TOPBAR"
@@ -100,6 +128,22 @@ export type UserPreferences = { id: string; locale: string; app_theme: string; e
 
 export type LoginActivity = { id: string; ip_address: string; user_agent: string; authentication_successful: boolean; timestamp: string; user?: User | null }
 
+/**
+ * What a [UserNotification] is about
+ */
+export type UserNotificationKind = "API_KEY_EXPIRING"
+
+/**
+ * A notice for a single user, which is kept until they read it
+ */
+export type UserNotification = { id: string; kind: UserNotificationKind; message: string; created_at: string; read_at: string | null }
+
+/**
+ * An identity at an OpenID Connect provider which is linked to a Stump user. A user may
+ * have multiple identities, but an identity may only be linked to a single user.
+ */
+export type OidcIdentity = { id: string; issuer: string; subject: string; email: string | null; created_at: string; last_used: string | null; user_id: string }
+
 export type EmailerSendTo = { device_id: number } | { email: string }
 
 /**
@@ -119,7 +163,18 @@ export type EmailerClientConfig = { sender_email: string; sender_display_name: s
  */
 export type SMTPEmailer = { id: number; name: string; is_primary: boolean; config: EmailerConfig; last_used_at: string | null }
 
-export type RegisteredEmailDevice = { id: number; name: string; email: string; forbidden: boolean }
+/**
+ * The format a book is converted to before it is emailed to a device
+ */
+export type DeviceTargetFormat = "ORIGINAL" | "CBZ" | "EPUB" | "PDF"
+
+/**
+ * How books are converted before they are emailed to a device, e.g. a Kindle which rejects
+ * CBR files
+ */
+export type DeviceFormatProfile = { target_format?: DeviceTargetFormat; max_image_dimension?: number | null }
+
+export type RegisteredEmailDevice = { id: number; name: string; email: string; forbidden: boolean; format_profile: DeviceFormatProfile }
 
 /**
  * A record of an email that was sent, used to keep track of emails that
@@ -132,10 +187,47 @@ export type EmailerSendRecord = { id: number; emailer_id: number; recipient_emai
  */
 export type AttachmentMeta = { filename: string; media_id: string | null; size: number }
 
+/**
+ * The kinds of emails which Stump can send, each of which is rendered from its own template
+ */
+export type EmailTemplate = "ATTACHMENT" | "INVITATION" | "PASSWORD_RESET" | "NEW_BOOKS_DIGEST" | "NEW_BOOKS_DIGEST_CONFIRMATION" | "JOB_FAILURE"
+
+/**
+ * Where the source of an email template comes from
+ */
+export type EmailTemplateOrigin = "DEFAULT" | "DISK" | "OVERRIDE"
+
+/**
+ * An email template and the source it is currently rendered from
+ */
+export type EmailTemplateDetails = { template: EmailTemplate; source: string; default_source: string; origin: EmailTemplateOrigin; updated_at: string | null }
+
+/**
+ * How often a user receives the new books digest
+ */
+export type DigestFrequency = "DAILY" | "WEEKLY"
+
+/**
+ * A user's opt-in to a periodic email listing the books which were added to the libraries
+ * they can see
+ */
+export type NewBooksDigestSubscription = { email: string; frequency: DigestFrequency; last_digest_at: string | null; confirmed_at: string | null; created_at: string }
+
 export type ReadingDirection = "ltr" | "rtl"
 
 export type ReadingMode = "paged" | "continuous:vertical" | "continuous:horizontal"
 
+/**
+ * The reading direction and mode set on a book or series, which take priority over the
+ * defaults of its library. Fields which are `None` are not overridden.
+ */
+export type ReadingOverrides = { reading_direction: ReadingDirection | null; reading_mode: ReadingMode | null }
+
+/**
+ * The reading direction and mode a book should be opened with
+ */
+export type ReadingPreferences = { reading_direction: ReadingDirection; reading_mode: ReadingMode }
+
 export type ReadingImageScaleFit = "height" | "width" | "none"
 
 export type FileStatus = "UNKNOWN" | "READY" | "UNSUPPORTED" | "ERROR" | "MISSING"
@@ -144,6 +236,11 @@ export type Library = { id: string; name: string; description: string | null; em
 
 export type LibraryPattern = "SERIES_BASED" | "COLLECTION_BASED"
 
+/**
+ * How a library is watched for changes when watching is enabled
+ */
+export type WatcherMode = "NATIVE" | "POLL"
+
 export type LibraryScanMode = "DEFAULT" | "NONE"
 
 export type CustomVisit = { regen_meta: boolean; regen_hashes: boolean }
@@ -155,26 +252,40 @@ export type ScanConfig = null | { force_rebuild: boolean } | CustomVisit
  * means that the scanner will visit books it otherwise would not. How much extra work is done depends on the
  * specific options.
  */
-export type ScanOptions = { config?: ScanConfig }
+export type ScanOptions = { config?: ScanConfig; dry_run?: boolean }
 
 export type LastLibraryScan = { options: ScanOptions | null; timestamp: string }
 
 export type IgnoreRules = string[]
 
-export type LibraryConfig = { id?: string | null; convert_rar_to_zip: boolean; hard_delete_conversions: boolean; generate_file_hashes: boolean; generate_koreader_hashes: boolean; process_metadata: boolean; watch: boolean; library_pattern: LibraryPattern; thumbnail_config: ImageProcessorOptions | null; default_reading_dir?: ReadingDirection; default_reading_mode?: ReadingMode; default_reading_image_scale_fit?: ReadingImageScaleFit; ignore_rules?: IgnoreRules; library_id?: string | null }
+export type FilenameRules = string[]
+
+/**
+ * How the series of a collection-based library are detected. When a library is
+ * collection-based without a strategy, each top-level directory is a series.
+ */
+export type SeriesDetection = { strategy: "FOLDER_DEPTH"; depth: number } | { strategy: "SERIES_JSON" } | { strategy: "PATH_PATTERN"; pattern: string }
+
+export type LibraryConfig = { id?: string | null; convert_rar_to_zip: boolean; hard_delete_conversions: boolean; generate_file_hashes: boolean; generate_koreader_hashes: boolean; process_metadata: boolean; watch: boolean; watcher_mode?: WatcherMode; poll_interval?: number | null; library_pattern: LibraryPattern; thumbnail_config: ImageProcessorOptions | null; default_reading_dir?: ReadingDirection; default_reading_mode?: ReadingMode; default_reading_image_scale_fit?: ReadingImageScaleFit; ignore_rules?: IgnoreRules; filename_rules?: FilenameRules; series_detection?: SeriesDetection | null; pdf_render_config?: PdfRenderOptions | null; library_id?: string | null }
 
 export type LibraryStats = { series_count: number; book_count: number; total_bytes: number; completed_books: number; in_progress_books: number }
 
 export type SeriesMetadata = { _type: string; title: string | null; summary: string | null; publisher: string | null; imprint: string | null; comicid: number | null; volume: number | null; booktype: string | null; age_rating: number | null; status: string | null }
 
-export type Series = { id: string; name: string; path: string; description: string | null; status: FileStatus; updated_at: string; created_at: string; library_id: string; library: Library | null; media: Media[] | null; metadata: SeriesMetadata | null; media_count?: number | null; unread_media_count?: number | null; tags?: Tag[] | null }
+export type Series = { id: string; name: string; path: string; description: string | null; status: FileStatus; updated_at: string; created_at: string; library_id: string; reading_direction: ReadingDirection | null; reading_mode: ReadingMode | null; library: Library | null; media: Media[] | null; metadata: SeriesMetadata | null; media_count?: number | null; unread_media_count?: number | null; tags?: Tag[] | null }
+
+/**
+ * Whether a book is a manga, as described by the ComicInfo `Manga` field. The `Unknown` value
+ * of the field is represented by the absence of a value.
+ */
+export type MangaFormat = "No" | "Yes" | "YesAndRightToLeft"
 
 /**
  * Struct representing the metadata for a processed file.
  */
-export type MediaMetadata = { title?: string | null; series?: string | null; number?: number | null; volume?: number | null; summary?: string | null; notes?: string | null; age_rating?: number | null; genre?: string[] | null; year?: number | null; month?: number | null; day?: number | null; writers?: string[] | null; pencillers?: string[] | null; inkers?: string[] | null; colorists?: string[] | null; letterers?: string[] | null; cover_artists?: string[] | null; editors?: string[] | null; publisher?: string | null; links?: string[] | null; characters?: string[] | null; teams?: string[] | null; page_count?: number | null; page_dimensions?: PageDimensionsEntity | null }
+export type MediaMetadata = { title?: string | null; series?: string | null; number?: number | null; volume?: number | null; summary?: string | null; notes?: string | null; age_rating?: number | null; genre?: string[] | null; year?: number | null; month?: number | null; day?: number | null; writers?: string[] | null; pencillers?: string[] | null; inkers?: string[] | null; colorists?: string[] | null; letterers?: string[] | null; cover_artists?: string[] | null; editors?: string[] | null; publisher?: string | null; links?: string[] | null; characters?: string[] | null; teams?: string[] | null; manga?: MangaFormat | null; page_count?: number | null; page_dimensions?: PageDimensionsEntity | null }
 
-export type Media = { id: string; name: string; size: number; extension: string; pages: number; updated_at: string; created_at: string; modified_at: string | null; hash: string | null; koreader_hash: string | null; path: string; status: FileStatus; series_id: string; metadata: MediaMetadata | null; series?: Series | null; active_reading_session?: ActiveReadingSession | null; finished_reading_sessions: FinishedReadingSession[] | null; current_page?: number | null; current_epubcfi?: string | null; is_completed?: boolean | null; tags?: Tag[] | null; bookmarks?: Bookmark[] | null }
+export type Media = { id: string; name: string; size: number; extension: string; pages: number; updated_at: string; created_at: string; modified_at: string | null; deleted_at: string | null; hash: string | null; koreader_hash: string | null; path: string; status: FileStatus; status_reason: string | null; verified_at: string | null; reading_direction: ReadingDirection | null; reading_mode: ReadingMode | null; series_id: string; metadata: MediaMetadata | null; series?: Series | null; active_reading_session?: ActiveReadingSession | null; finished_reading_sessions: FinishedReadingSession[] | null; current_page?: number | null; current_epubcfi?: string | null; is_completed?: boolean | null; tags?: Tag[] | null; bookmarks?: Bookmark[] | null }
 
 /**
  * A model representing a bookmark in the database. Bookmarks are used to save specific locations
@@ -184,7 +295,18 @@ export type Bookmark = { id: string; preview_content: string | null; epubcfi: st
 
 export type MediaAnnotation = { id: string; highlighted_text: string | null; page: number | null; page_coordinates_x: number | null; page_coordinates_y: number | null; epubcfi: string | null; notes: string | null; media_id: string; media?: Media | null }
 
-export type ActiveReadingSession = { id: string; page: number | null; epubcfi: string | null; percentage_completed: number | null; elapsed_seconds: number | null; started_at: string; media_id: string; media: Media | null; user_id: string; user: User | null }
+/**
+ * The reason a set of media were considered to be duplicates of one another
+ */
+export type DuplicateReason = "HASH" | "COVER" | "METADATA"
+
+/**
+ * A group of media which were found to likely be duplicates of one another by the
+ * duplicate analysis job
+ */
+export type DuplicateGroup = { id: string; reasons: DuplicateReason[]; created_at: string; dismissed_at: string | null; media: Media[] }
+
+export type ActiveReadingSession = { id: string; page: number | null; epubcfi: string | null; percentage_completed: number | null; elapsed_seconds: number | null; started_at: string; last_heartbeat_at: string | null; media_id: string; media: Media | null; user_id: string; user: User | null }
 
 export type FinishedReadingSession = { id: string; started_at: string; completed_at: string; elapsed_seconds: number | null; media_id: string; media: Media | null; user_id: string; user: User | null }
 
@@ -192,9 +314,20 @@ export type ProgressUpdateReturn = ActiveReadingSession | FinishedReadingSession
 
 /**
  * Represents a page dimension for a page of a Stump media item. It consists of a
- * height and a width.
+ * height and a width, along with the layout derived from them once the media is analyzed.
  */
-export type PageDimension = { height: number; width: number }
+export type PageDimension = { height: number; width: number; layout?: PageLayout | null }
+
+/**
+ * How a page should be laid out by a reader which displays two pages at a time
+ */
+export type PageLayout = "SINGLE" | "DOUBLE" | "SPREAD"
+
+/**
+ * The side of a two-page view a page is displayed on, or the center when it is displayed
+ * on its own. Pages read right-to-left start on the right.
+ */
+export type PagePosition = "left" | "right" | "center"
 
 /**
  * Represents a database [`page_dimensions::Data`] object.
@@ -288,6 +421,23 @@ export type UpdateEpubProgress = { epubcfi: string; percentage: number; is_compl
 
 export type EpubContent = { label: string; content: string; children: EpubContent[]; play_order: number }
 
+/**
+ * The locations of a single position in an EPUB
+ */
+export type EpubPositionLocations = { position: number; progression: number; totalProgression: number }
+
+/**
+ * A single position in an EPUB, in the form of a Readium locator
+ */
+export type EpubPosition = { href: string; type: string; locations: EpubPositionLocations }
+
+/**
+ * The positions list of an EPUB, which splits each resource in the spine into chunks of
+ * [`EPUB_POSITION_LENGTH`] characters. Pages of an EPUB are positions in this list. See
+ * https://readium.org/architecture/models/locators/positions/
+ */
+export type EpubPositionList = { total: number; positions: EpubPosition[] }
+
 export type JobStatus = "RUNNING" | "PAUSED" | "COMPLETED" | "CANCELLED" | "FAILED" | "QUEUED"
 
 export type JobSchedulerConfig = { id: string; interval_secs: number; excluded_libraries: Library[] }
@@ -323,6 +473,31 @@ export type ImageFormat = "Webp" | "Jpeg" | "Png"
  */
 export type ImageProcessorOptions = { resize_options?: ImageResizeOptions | null; format: ImageFormat; quality?: number | null; page?: number | null }
 
+/**
+ * Options for rendering the pages of PDFs to images, which are configured per library
+ */
+export type PdfRenderOptions = { format?: ImageFormat; dpi?: number; max_dimension?: number | null }
+
+/**
+ * An entry in the outline (bookmarks) of a PDF
+ */
+export type PdfOutlineItem = { title: string; page: number | null; children: PdfOutlineItem[] }
+
+/**
+ * The text extracted from a page of a PDF
+ */
+export type PdfPageText = { page: number; text: string }
+
+/**
+ * The manifest stored at the root of each backup archive, describing what it contains
+ */
+export type BackupManifest = { format_version: number; stump_version: string; created_at: string; includes_thumbnails: boolean }
+
+/**
+ * A backup archive in the backups directory
+ */
+export type Backup = { name: string; size: number; manifest: BackupManifest }
+
 export type DirectoryListing = { parent: string | null; files: DirectoryListingFile[] }
 
 export type DirectoryListingFile = { is_directory: boolean; name: string; path: string }
@@ -360,10 +535,28 @@ export type AuthenticationOptions = { generate_token?: boolean; create_session?:
 
 export type CreatedToken = { access_token: string; expires_at: string }
 
-export type LoginResponse = User | { for_user: User; token: CreatedToken }
+export type LoginResponse = User | { for_user: User; token: CreatedToken } | { two_factor_token: string }
 
 export type LoginOrRegisterArgs = { username: string; password: string }
 
+export type LoginTwoFactorArgs = { two_factor_token: string; code: string }
+
+export type TwoFactorStatus = { enabled: boolean; required: boolean; recovery_codes_remaining: number }
+
+export type SetupTwoFactor = { password?: string | null }
+
+export type TwoFactorSetup = { secret: string; provisioning_uri: string }
+
+export type VerifyTwoFactorCode = { code: string }
+
+export type TwoFactorRecoveryCodes = { recovery_codes: string[] }
+
+export type DisableTwoFactor = { password: string; code: string }
+
+export type OidcConfig = { enabled: boolean }
+
+export type OidcAuthorizeParams = { redirect?: string | null; link?: boolean }
+
 export type CreateUser = { username: string; password: string; permissions?: UserPermission[]; age_restriction: AgeRestriction | null; max_sessions_allowed?: number | null }
 
 export type UpdateUser = { username: string; password: string | null; avatar_url: string | null; permissions?: UserPermission[]; age_restriction: AgeRestriction | null; max_sessions_allowed?: number | null }
@@ -372,10 +565,14 @@ export type UpdateUserPreferences = { id: string; locale: string; preferred_layo
 
 export type DeleteUser = { hard_delete: boolean | null }
 
+export type UpdateNewBooksDigestSubscription = { email: string; frequency?: DigestFrequency }
+
+export type ConfirmNewBooksDigest = { code: string }
+
 /**
  * The request body for creating or updating an API key
  */
-export type CreateOrUpdateAPIKey = { name: string; permissions: APIKeyPermissions; expires_at?: string | null }
+export type CreateOrUpdateAPIKey = { name: string; permissions: APIKeyPermissions; expires_at?: string | null; scopes?: APIKeyScopes | null }
 
 /**
  * The response after creating a new API key
@@ -398,12 +595,18 @@ export type CreateOrUpdateEmailer = { name: string; is_primary: boolean; config:
 /**
  * Input object for creating or updating an email device
  */
-export type CreateOrUpdateEmailDevice = { name: string; email: string; forbidden: boolean }
+export type CreateOrUpdateEmailDevice = { name: string; email: string; forbidden: boolean; format_profile?: DeviceFormatProfile }
 
 /**
  * Patch an existing email device by its ID
  */
-export type PatchEmailDevice = { name: string | null; email: string | null; forbidden: boolean | null }
+export type PatchEmailDevice = { name: string | null; email: string | null; forbidden: boolean | null; format_profile: DeviceFormatProfile | null }
+
+export type UpdateEmailTemplate = { source: string }
+
+export type PreviewEmailTemplate = { source?: string | null }
+
+export type EmailTemplatePreview = { html: string }
 
 export type LogFilter = { level?: LogLevel | null; job_id?: string | null; timestamp?: ValueOrRange<string> | null }
 
@@ -425,12 +628,14 @@ export type MediaMetadataRelationFilter = { media?: MediaFilter | null }
 
 export type MediaMetadataFilter = ({ publisher?: string[]; genre?: string[]; character?: string[]; colorist?: string[]; writer?: string[]; penciller?: string[]; inker?: string[]; letterer?: string[]; editor?: string[]; age_rating?: number | null; year?: ValueOrRange<number> | null }) & ({ media?: MediaFilter | null })
 
-export type MediaBaseFilter = { id?: string[]; name?: string[]; extension?: string[]; path?: string[]; read_status?: ReadStatus[]; tags?: string[]; search?: string | null; metadata?: MediaMetadataBaseFilter | null }
+export type MediaBaseFilter = { id?: string[]; name?: string[]; extension?: string[]; path?: string[]; status?: string[]; read_status?: ReadStatus[]; tags?: string[]; search?: string | null; metadata?: MediaMetadataBaseFilter | null }
 
 export type MediaFilter = ({ id?: string[]; name?: string[]; extension?: string[]; path?: string[]; read_status?: ReadStatus[]; tags?: string[]; search?: string | null; metadata?: MediaMetadataBaseFilter | null }) & ({ series?: SeriesFilter | null })
 
 export type PutMediaProgress = { page: number; epubcfi?: string | null; elapsed_seconds?: number | null }
 
+export type PutMediaProgressHeartbeat = { delta_seconds?: number | null }
+
 /**
  * Represents the relations to load for a book entity, including optional loading
  * of the series and library relationships.
@@ -453,16 +658,41 @@ export type SeriesQueryRelation = { load_media?: boolean | null; load_library?:
 
 export type CreateLibrary = { name: string; path: string; description?: string | null; tags?: string[] | null; scan_mode?: LibraryScanMode | null; config?: LibraryConfig | null }
 
+export type PreviewLibrarySeries = { path: string; library_pattern: LibraryPattern; series_detection?: SeriesDetection | null; ignore_rules?: IgnoreRules }
+
+export type SeriesPreview = { path: string; name: string; book_count: number; exists: boolean }
+
+export type LibrarySeriesPreview = { series: SeriesPreview[]; orphaned_series: string[] }
+
 export type UpdateLibrary = { name: string; path: string; description?: string | null; emoji?: string | null; tags?: string[] | null; config: LibraryConfig; scan_mode?: LibraryScanMode | null }
 
 export type UpdateLibraryExcludedUsers = { user_ids: string[] }
 
 export type CleanLibraryResponse = { deleted_media_count: number; deleted_series_count: number; is_empty: boolean }
 
+export type PurgedTrash = { deleted_media_count: number; deleted_series_count: number }
+
 export type GenerateLibraryThumbnails = { image_options?: ImageProcessorOptions | null; force_regenerate?: boolean }
 
 export type LibraryStatsParams = { all_users?: boolean }
 
+export type CreateUploadSession = { place_at: string; file_name: string; size: number; checksum?: string | null }
+
+/**
+ * A resumable upload of a single book
+ */
+export type UploadSession = { id: string; library_id: string; user_id: string; file_name: string; placement_path: string; size: number; offset: number; checksum: string | null; created_at: string }
+
+/**
+ * A preview of what completing an upload would result in
+ */
+export type UploadPreview = { file_name: string; path: string; pages: number; metadata: MediaMetadata | null; series: Series | null }
+
+/**
+ * The result of a completed upload
+ */
+export type CompletedUpload = { path: string; series: Series | null }
+
 /**
  * Represents an update to the completion status of a media item.
  */
@@ -473,6 +703,12 @@ export type PutMediaCompletionStatus = { is_complete: boolean; page?: number | n
  */
 export type MediaIsComplete = { is_completed: boolean; last_completed_at: string | null }
 
+export type DuplicateGroupsParams = { include_dismissed?: boolean }
+
+export type ResolveDuplicateGroup = { keep_media_id: string; delete_files?: boolean }
+
+export type VerifyMediaParams = { library_id?: string | null }
+
 export type MediaMetadataOverview = { genres: string[]; writers: string[]; pencillers: string[]; inkers: string[]; colorists: string[]; letterers: string[]; editors: string[]; publishers: string[]; characters: string[]; teams: string[] }
 
 export type CreateOrUpdateBookmark = { epubcfi: string; preview_content: string | null }
@@ -483,6 +719,13 @@ export type SeriesIsComplete = { is_complete: boolean; completed_at: string | nu
 
 export type UpdateSchedulerConfig = { interval_secs: number | null; excluded_library_ids: string[] | null }
 
+/**
+ * A job which was queued, which can be followed through the jobs API using its ID
+ */
+export type QueuedJob = { id: string }
+
+export type CreateBackup = { include_thumbnails?: boolean }
+
 export type GetBookClubsParams = { all?: boolean }
 
 export type CreateBookClub = { name: string; is_private?: boolean; member_role_spec?: BookClubMemberRoleSpec | null; creator_hide_progress?: boolean; creator_display_name?: string | null }
@@ -569,7 +812,7 @@ export type UploadConfig = { enabled: boolean; max_file_upload_size: number }
  * }
  * ```
  */
-export type StumpConfig = { profile: string; port: number; verbosity: number; pretty_logs: boolean; db_path: string | null; client_dir: string; custom_templates_dir: string | null; config_dir: string; allowed_origins: string[]; pdfium_path: string | null; enable_swagger: boolean; enable_koreader_sync: boolean; password_hash_cost: number; session_ttl: number; access_token_ttl: number; expired_session_cleanup_interval: number; max_scanner_concurrency: number; max_thumbnail_concurrency: number; max_image_upload_size: number; enable_upload: boolean; max_file_upload_size: number }
+export type StumpConfig = { profile: string; port: number; verbosity: number; pretty_logs: boolean; db_path: string | null; client_dir: string; custom_templates_dir: string | null; config_dir: string; allowed_origins: string[]; pdfium_path: string | null; enable_swagger: boolean; enable_koreader_sync: boolean; password_hash_cost: number; session_ttl: number; access_token_ttl: number; expired_session_cleanup_interval: number; max_scanner_concurrency: number; max_thumbnail_concurrency: number; max_image_upload_size: number; enable_upload: boolean; max_file_upload_size: number; oidc_enabled: boolean; oidc_issuer_url: string | null; oidc_client_id: string | null; oidc_client_secret: string | null; oidc_redirect_uri: string | null; oidc_scopes: string[]; oidc_username_claim: string; oidc_groups_claim: string; oidc_auto_provision: boolean; oidc_allow_symmetric_signing: boolean; oidc_group_permissions: string[]; enforce_manager_two_factor: boolean; proxy_auth_header: string | null; proxy_auth_trusted_proxies: string[]; proxy_auth_auto_create: boolean; proxy_auth_default_permissions: string[]; api_key_expiry_notice_days: number; trash_retention_days: number; backup_dir: string | null; backup_interval_hours: number; backup_retention: number }
 
 // DESKTOP TYPE GENERATION
 